@@ -12,6 +12,6 @@ set -x
 
 # Build/test all host crates
 cargo +"$rust_stable" build
-cargo +"$rust_stable" test -- --nocapture
+cargo +"$rust_stable" test --features local-cluster-tests -- --nocapture
 
 exit 0
@@ -8,26 +8,138 @@ CREATE TABLE account (
     pubkey BYTEA PRIMARY KEY,
     owner BYTEA,
     lamports BIGINT NOT NULL,
+    -- The change of the lamports versus the previous stored update, null for the first
+    lamports_delta BIGINT,
     slot BIGINT NOT NULL,
     executable BOOL NOT NULL,
     rent_epoch BIGINT NOT NULL,
     data BYTEA,
     write_version BIGINT NOT NULL,
-    updated_on TIMESTAMP NOT NULL
+    updated_on TIMESTAMP NOT NULL,
+    -- How the data is encoded, 0 as it is and 1 compressed with zstd by the
+    -- account_data_compression
+    data_codec SMALLINT NOT NULL DEFAULT 0,
+    -- The first 8 bytes of the data of the accounts of the discriminator_owners
+    discriminator BYTEA,
+    -- The data decoded with the layout descriptors of the account_data_decoders
+    data_json JSONB,
+    -- The signature of the transaction causing the update, null for the updates of the
+    -- startup and of the validators not notifying the transactions
+    txn_signature BYTEA
 );
 
 CREATE INDEX account_owner ON account (owner);
 
+CREATE INDEX account_owner_discriminator ON account (owner, discriminator) WHERE discriminator IS NOT NULL;
+
 CREATE INDEX account_slot ON account (slot);
 
+Create TYPE "TokenAccountState" AS ENUM (
+    'Initialized',
+    'Frozen'
+);
+
+-- The token accounts of the SPL Token and Token-2022 programs decoded from their data
+-- with the token_account_storage, the amounts in the base units of the mints
+CREATE TABLE spl_token_account (
+    pubkey BYTEA PRIMARY KEY,
+    program_id BYTEA NOT NULL,
+    mint BYTEA NOT NULL,
+    owner BYTEA NOT NULL,
+    amount NUMERIC(20) NOT NULL,
+    delegate BYTEA,
+    state "TokenAccountState" NOT NULL,
+    -- The rent-exempt reserve of the wrapped SOL accounts, null for the other mints
+    is_native BIGINT,
+    delegated_amount NUMERIC(20) NOT NULL,
+    close_authority BYTEA,
+    slot BIGINT NOT NULL,
+    write_version BIGINT NOT NULL,
+    decoder_version SMALLINT NOT NULL, -- The version of the decoding of the row
+    updated_on TIMESTAMP NOT NULL
+);
+
+CREATE INDEX spl_token_account_owner ON spl_token_account (owner, mint);
+
+CREATE INDEX spl_token_account_mint ON spl_token_account (mint);
+
+-- The mints of the SPL Token and Token-2022 programs decoded from their data
+CREATE TABLE spl_token_mint (
+    pubkey BYTEA PRIMARY KEY,
+    program_id BYTEA NOT NULL,
+    mint_authority BYTEA,
+    supply NUMERIC(20) NOT NULL,
+    decimals SMALLINT NOT NULL,
+    freeze_authority BYTEA,
+    slot BIGINT NOT NULL,
+    write_version BIGINT NOT NULL,
+    decoder_version SMALLINT NOT NULL, -- The version of the decoding of the row
+    updated_on TIMESTAMP NOT NULL
+);
+
+-- The amount in the base units of a mint as a decimal amount, like the uiAmountString
+-- of the RPC nodes
+CREATE OR REPLACE FUNCTION spl_token_ui_amount(amount NUMERIC, decimals SMALLINT) RETURNS NUMERIC
+    LANGUAGE SQL IMMUTABLE STRICT AS
+$$ SELECT round(amount / power(10::NUMERIC, decimals), decimals) $$;
+
+-- The amount of the raw data of a token account, the little-endian u64 following its
+-- mint and owner. The data must not be compressed.
+CREATE OR REPLACE FUNCTION spl_token_amount(data BYTEA) RETURNS NUMERIC
+    LANGUAGE SQL IMMUTABLE STRICT AS
+$$ SELECT sum(get_byte(data, 64 + i)::NUMERIC * power(256::NUMERIC, i)) FROM generate_series(0, 7) AS i $$;
+
+-- The balances of the decoded token accounts in the units of their mints
+CREATE OR REPLACE VIEW spl_token_balance AS
+    SELECT t.pubkey, t.mint, t.owner, t.amount, m.decimals,
+        spl_token_ui_amount(t.amount, m.decimals) AS ui_amount, t.slot
+    FROM spl_token_account t JOIN spl_token_mint m ON m.pubkey = t.mint;
+
 -- The table storing slot information
 CREATE TABLE slot (
     slot BIGINT PRIMARY KEY,
     parent BIGINT,
     status VARCHAR(32) NOT NULL,
-    updated_on TIMESTAMP NOT NULL
+    processed_on TIMESTAMP,
+    confirmed_on TIMESTAMP,
+    rooted_on TIMESTAMP,
+    dead_reason TEXT, -- The reason reported by the validator when the slot is marked dead
+    updated_on TIMESTAMP NOT NULL,
+    -- The transactions selected received by the plugin and committed by the workers,
+    -- with the store_slot_transaction_counts
+    transactions_received BIGINT,
+    transactions_committed BIGINT,
+    -- The wrapping sum of the digests of the account and transaction rows ingested and
+    -- their count, with the store_slot_checksums
+    ingest_checksum BIGINT,
+    ingest_checksum_rows BIGINT
 );
 
+-- The confirmation latencies of the slots in milliseconds, measured from the time
+-- the slot is processed
+CREATE VIEW slot_confirmation_latency AS
+    SELECT slot,
+        processed_on,
+        EXTRACT(EPOCH FROM (confirmed_on - processed_on)) * 1000 AS confirmed_latency_ms,
+        EXTRACT(EPOCH FROM (rooted_on - processed_on)) * 1000 AS rooted_latency_ms
+    FROM slot
+    WHERE processed_on IS NOT NULL;
+
+-- The distribution of the confirmation latencies per minute
+CREATE VIEW slot_confirmation_latency_distribution AS
+    SELECT date_trunc('minute', processed_on) AS minute,
+        COUNT(*) AS slot_count,
+        percentile_cont(0.5) WITHIN GROUP (ORDER BY confirmed_latency_ms) AS confirmed_p50_ms,
+        percentile_cont(0.9) WITHIN GROUP (ORDER BY confirmed_latency_ms) AS confirmed_p90_ms,
+        percentile_cont(0.99) WITHIN GROUP (ORDER BY confirmed_latency_ms) AS confirmed_p99_ms,
+        MAX(confirmed_latency_ms) AS confirmed_max_ms,
+        percentile_cont(0.5) WITHIN GROUP (ORDER BY rooted_latency_ms) AS rooted_p50_ms,
+        percentile_cont(0.9) WITHIN GROUP (ORDER BY rooted_latency_ms) AS rooted_p90_ms,
+        percentile_cont(0.99) WITHIN GROUP (ORDER BY rooted_latency_ms) AS rooted_p99_ms,
+        MAX(rooted_latency_ms) AS rooted_max_ms
+    FROM slot_confirmation_latency
+    GROUP BY 1;
+
 -- Types for Transactions
 
 Create TYPE "TransactionErrorCode" AS ENUM (
@@ -160,9 +272,14 @@ CREATE TYPE "LoadedMessageV0" AS (
 );
 
 -- The table storing transactions
+-- The signature column is to be created as VARCHAR(88) for the 'base58' signature_storage.
 CREATE TABLE transaction (
     slot BIGINT NOT NULL,
+    index BIGINT,
     signature BYTEA NOT NULL,
+    signature_text VARCHAR(88),
+    failed BOOL,
+    signer VARCHAR(44),
     is_vote BOOL NOT NULL,
     message_type SMALLINT, -- 0: legacy, 1: v0 message
     legacy_message "TransactionMessage",
@@ -170,32 +287,495 @@ CREATE TABLE transaction (
     signatures BYTEA[],
     message_hash BYTEA,
     meta "TransactionStatusMeta",
+    transaction_json JSONB,
     updated_on TIMESTAMP NOT NULL,
+    -- The error of the failed transactions decoded, such as 'InstructionError::Custom'
+    error_code VARCHAR(64),
+    error_instruction_index SMALLINT,
+    error_custom_code BIGINT,
+    error_program VARCHAR(44),
     CONSTRAINT transaction_pk PRIMARY KEY (slot, signature)
 );
 
+CREATE INDEX transaction_signature_text ON transaction (signature_text);
+
+CREATE INDEX transaction_error_program ON transaction (error_program, error_custom_code)
+    WHERE error_program IS NOT NULL;
+
+-- The table indexing the transactions by the addresses they mention, including the
+-- addresses loaded from the address lookup tables, to serve getSignaturesForAddress.
+-- The signature column is to be created as VARCHAR(88) for the 'base58' signature_storage.
+CREATE TABLE transaction_address (
+    address BYTEA NOT NULL,
+    slot BIGINT NOT NULL,
+    index BIGINT,
+    signature BYTEA NOT NULL
+);
+
+CREATE UNIQUE INDEX transaction_address_slot ON transaction_address (address, slot DESC, signature);
+
+-- The table storing the lamports moved by the System Program instructions, including
+-- the inner instructions, of the successful transactions.
+-- The signature column is to be created as VARCHAR(88) for the 'base58' signature_storage.
+CREATE TABLE sol_transfer (
+    slot BIGINT NOT NULL,
+    signature BYTEA NOT NULL,
+    transfer_index SMALLINT NOT NULL, -- The position among the transfers of the transaction
+    instruction_index SMALLINT NOT NULL,
+    inner_instruction_index SMALLINT, -- null for the top level instructions
+    kind VARCHAR(32) NOT NULL, -- The instruction type as in the 'jsonParsed' encoding
+    source BYTEA NOT NULL,
+    destination BYTEA NOT NULL,
+    lamports BIGINT NOT NULL,
+    updated_on TIMESTAMP NOT NULL,
+    CONSTRAINT sol_transfer_pk PRIMARY KEY (slot, signature, transfer_index)
+);
+
+CREATE INDEX sol_transfer_source ON sol_transfer (source, slot);
+
+CREATE INDEX sol_transfer_destination ON sol_transfer (destination, slot);
+
+-- The table of the addresses and the signatures of the transactions to be stored
+-- regardless of the transaction_selector, maintained outside of the plugin and polled
+-- when the transaction_allowlist_poll_interval_secs is set. The entry is either a
+-- 32-byte address mentioned by the transactions or a 64-byte transaction signature.
+CREATE TABLE transaction_allowlist (
+    entry BYTEA PRIMARY KEY CHECK (length(entry) IN (32, 64)),
+    note VARCHAR(256), -- The reason of the monitoring, e.g. the case it belongs to
+    added_on TIMESTAMP NOT NULL DEFAULT now()
+);
+
+-- The table storing the writes failed repeatedly, with the payload as written by the
+-- plugin, e.g. the serialized account or transaction, along with the error.
+CREATE TABLE dead_letter (
+    dead_letter_id BIGSERIAL PRIMARY KEY,
+    stream VARCHAR(16) NOT NULL, -- 'account' or 'transaction'
+    slot BIGINT NOT NULL,
+    payload JSONB NOT NULL,
+    error TEXT NOT NULL,
+    attempts INT NOT NULL,
+    failed_on TIMESTAMP NOT NULL,
+    replayed_on TIMESTAMP -- null until written by the replay
+);
+
+CREATE INDEX dead_letter_stream ON dead_letter (stream, slot);
+
+-- The table storing the human-readable names and categories of the programs, maintained
+-- from the program_labels_file of the plugin.
+CREATE TABLE program_label (
+    program_id BYTEA PRIMARY KEY,
+    name VARCHAR(128) NOT NULL,
+    category VARCHAR(64),
+    updated_on TIMESTAMP NOT NULL
+);
+
+-- The table storing the tags of the addresses, maintained from the address_tags of the
+-- plugin.
+CREATE TABLE address_tag (
+    address BYTEA NOT NULL,
+    tag VARCHAR(64) NOT NULL,
+    updated_on TIMESTAMP NOT NULL,
+    CONSTRAINT address_tag_pk PRIMARY KEY (address, tag)
+);
+
+-- The SOL transfers along with the tags of their sources and destinations, null when
+-- not tagged.
+CREATE VIEW tagged_sol_transfer AS
+    SELECT t.*,
+        (SELECT array_agg(tag ORDER BY tag) FROM address_tag WHERE address = t.source) AS source_tags,
+        (SELECT array_agg(tag ORDER BY tag) FROM address_tag WHERE address = t.destination) AS destination_tags
+    FROM sol_transfer t;
+
+-- The table storing the memos of the SPL Memo instructions, including the inner
+-- instructions, of the successful transactions.
+-- The signature column is to be created as VARCHAR(88) for the 'base58' signature_storage.
+CREATE TABLE transaction_memo (
+    slot BIGINT NOT NULL,
+    signature BYTEA NOT NULL,
+    memo_index SMALLINT NOT NULL, -- The position among the memos of the transaction
+    instruction_index SMALLINT NOT NULL,
+    inner_instruction_index SMALLINT, -- null for the top level instructions
+    memo TEXT NOT NULL,
+    updated_on TIMESTAMP NOT NULL,
+    CONSTRAINT transaction_memo_pk PRIMARY KEY (slot, signature, memo_index)
+);
+
+CREATE INDEX transaction_memo_memo ON transaction_memo (memo, slot);
+
+-- The table storing the top level instructions of the parsed_instruction_programs, of
+-- the successful transactions, parsed as in the 'jsonParsed' encoding.
+-- The signature column is to be created as VARCHAR(88) for the 'base58' signature_storage.
+CREATE TABLE transaction_instruction (
+    slot BIGINT NOT NULL,
+    signature BYTEA NOT NULL,
+    instruction_index SMALLINT NOT NULL,
+    program VARCHAR(32) NOT NULL, -- The program name as in the 'jsonParsed' encoding
+    program_id BYTEA NOT NULL,
+    parsed JSONB NOT NULL,
+    updated_on TIMESTAMP NOT NULL,
+    CONSTRAINT transaction_instruction_pk PRIMARY KEY (slot, signature, instruction_index)
+);
+
+CREATE INDEX transaction_instruction_program ON transaction_instruction (program, slot);
+
+-- The tables storing the balances, the token balances, the rewards and the log messages
+-- of the transactions, normalized from their meta for the analytics queries.
+-- The signature columns are to be created as VARCHAR(88) for the 'base58' signature_storage.
+CREATE TABLE transaction_balance (
+    slot BIGINT NOT NULL,
+    signature BYTEA NOT NULL,
+    account_index SMALLINT NOT NULL,
+    account VARCHAR(44) NOT NULL,
+    pre_balance BIGINT NOT NULL,
+    post_balance BIGINT NOT NULL,
+    updated_on TIMESTAMP NOT NULL,
+    CONSTRAINT transaction_balance_pk PRIMARY KEY (slot, signature, account_index)
+);
+
+CREATE INDEX transaction_balance_account ON transaction_balance (account, slot);
+
+CREATE TABLE transaction_token_balance (
+    slot BIGINT NOT NULL,
+    signature BYTEA NOT NULL,
+    account_index SMALLINT NOT NULL,
+    account VARCHAR(44) NOT NULL,
+    mint VARCHAR(44) NOT NULL,
+    owner VARCHAR(44) NOT NULL,
+    program_id VARCHAR(44) NOT NULL,
+    decimals SMALLINT NOT NULL,
+    pre_amount NUMERIC(20), -- null for the token accounts created by the transaction
+    post_amount NUMERIC(20), -- null for the token accounts closed by the transaction
+    updated_on TIMESTAMP NOT NULL,
+    CONSTRAINT transaction_token_balance_pk PRIMARY KEY (slot, signature, account_index)
+);
+
+CREATE INDEX transaction_token_balance_owner_mint ON transaction_token_balance (owner, mint, slot);
+
+CREATE TABLE transaction_reward (
+    slot BIGINT NOT NULL,
+    signature BYTEA NOT NULL,
+    reward_index SMALLINT NOT NULL,
+    pubkey VARCHAR(44) NOT NULL,
+    lamports BIGINT NOT NULL,
+    post_balance BIGINT NOT NULL,
+    reward_type "RewardType",
+    commission SMALLINT,
+    updated_on TIMESTAMP NOT NULL,
+    CONSTRAINT transaction_reward_pk PRIMARY KEY (slot, signature, reward_index)
+);
+
+CREATE TABLE transaction_log (
+    slot BIGINT NOT NULL,
+    signature BYTEA NOT NULL,
+    log_index SMALLINT NOT NULL,
+    message TEXT NOT NULL,
+    updated_on TIMESTAMP NOT NULL,
+    CONSTRAINT transaction_log_pk PRIMARY KEY (slot, signature, log_index)
+);
+
+-- The table storing the fee burn and the rent collected per slot, derived from the
+-- fees of all the transactions and the rewards of the block.
+CREATE TABLE slot_economics (
+    slot BIGINT PRIMARY KEY,
+    transaction_count BIGINT NOT NULL,
+    transaction_fees BIGINT NOT NULL, -- The fees paid, including the priority fees
+    fee_rewards BIGINT NOT NULL, -- The fees credited to the leader
+    fee_burn BIGINT NOT NULL, -- The fees not credited to the leader
+    rent_rewards BIGINT NOT NULL, -- The rent collected credited to the validators
+    rent_reward_count BIGINT NOT NULL,
+    updated_on TIMESTAMP NOT NULL
+);
+
+-- The table storing the fee, rent and voting rewards of the validators in the blocks,
+-- the epoch being the epoch of the block paying the rewards. The voting rewards are
+-- paid in the first block of an epoch for the previous epoch.
+CREATE TABLE validator_reward (
+    slot BIGINT NOT NULL,
+    epoch BIGINT NOT NULL,
+    pubkey BYTEA NOT NULL, -- The identity for the fee and rent rewards, the vote account for the voting rewards
+    reward_type "RewardType" NOT NULL,
+    lamports BIGINT NOT NULL,
+    post_balance BIGINT NOT NULL,
+    commission SMALLINT, -- The commission of the vote account, for the voting rewards
+    updated_on TIMESTAMP NOT NULL,
+    CONSTRAINT validator_reward_pk PRIMARY KEY (slot, pubkey, reward_type)
+);
+
+CREATE INDEX validator_reward_pubkey ON validator_reward (pubkey, epoch);
+
+-- The rewards of the validators per epoch, of the rooted blocks only.
+CREATE VIEW validator_epoch_reward AS
+    SELECT r.epoch, r.pubkey, r.reward_type,
+        COUNT(*) AS reward_count,
+        SUM(r.lamports) AS lamports,
+        MIN(r.commission) AS min_commission,
+        MAX(r.commission) AS max_commission
+    FROM validator_reward r JOIN slot s ON s.slot = r.slot
+    WHERE s.status = 'rooted'
+    GROUP BY r.epoch, r.pubkey, r.reward_type;
+
+-- The table storing the staking rewards of the stake accounts, the epoch being the
+-- epoch the rewards are earned in, the one before the epoch of the block distributing
+-- them.
+CREATE TABLE stake_reward (
+    epoch BIGINT NOT NULL,
+    pubkey BYTEA NOT NULL,
+    slot BIGINT NOT NULL,
+    num_partitions BIGINT, -- The partitions the rewards of the epoch are distributed across
+    lamports BIGINT NOT NULL,
+    post_balance BIGINT NOT NULL,
+    commission SMALLINT,
+    updated_on TIMESTAMP NOT NULL,
+    CONSTRAINT stake_reward_pk PRIMARY KEY (epoch, pubkey)
+);
+
+CREATE INDEX stake_reward_pubkey ON stake_reward (pubkey, epoch);
+
+-- The table storing the slots processed whose slot status, block metadata or
+-- transactions did not all arrive and get committed within the ingest SLA
+CREATE TABLE ingest_sla_violation (
+    slot BIGINT PRIMARY KEY,
+    sla_secs BIGINT NOT NULL,
+    slot_status_committed BOOL NOT NULL,
+    block_metadata_arrived BOOL NOT NULL,
+    block_metadata_committed BOOL NOT NULL,
+    expected_transactions BIGINT, -- The transactions executed in the block
+    notified_transactions BIGINT NOT NULL,
+    stored_transactions BIGINT NOT NULL, -- The transactions selected to be stored
+    committed_transactions BIGINT NOT NULL,
+    detected_on TIMESTAMP NOT NULL
+);
+
+-- The table reporting the indexes and the partitioning advised from the queries against
+-- the tables of the plugin observed by pg_stat_statements, replaced by each report.
+CREATE TABLE index_advice (
+    table_name VARCHAR(64) NOT NULL,
+    kind VARCHAR(16) NOT NULL, -- 'index' or 'partition'
+    columns VARCHAR(64)[] NOT NULL,
+    statement TEXT, -- The DDL of the index advised
+    calls BIGINT NOT NULL,
+    total_exec_time_ms DOUBLE PRECISION NOT NULL,
+    mean_exec_time_ms DOUBLE PRECISION NOT NULL,
+    query TEXT NOT NULL, -- The slowest query in total of the ones advised for
+    advised_on TIMESTAMP NOT NULL,
+    CONSTRAINT index_advice_pk PRIMARY KEY (table_name, kind, columns)
+);
+
+-- The table storing the rows and the estimated bytes written per table per interval
+-- by each worker, for the capacity planning.
+CREATE TABLE table_write_stats (
+    table_name VARCHAR(64) NOT NULL,
+    interval_start TIMESTAMP NOT NULL,
+    interval_end TIMESTAMP NOT NULL,
+    row_count BIGINT NOT NULL,
+    byte_count BIGINT NOT NULL, -- The encoded parameters along with the estimated row overhead
+    updated_on TIMESTAMP NOT NULL
+);
+
+CREATE INDEX table_write_stats_interval ON table_write_stats (interval_start, table_name);
+
+-- The table storing the account rows inserted, updated and skipped as stale by the
+-- upserts per interval by each worker, along with the write stats.
+CREATE TABLE account_upsert_stats (
+    interval_start TIMESTAMP NOT NULL,
+    interval_end TIMESTAMP NOT NULL,
+    inserted_count BIGINT NOT NULL,
+    updated_count BIGINT NOT NULL,
+    skipped_count BIGINT NOT NULL, -- The updates older than the stored versions or within the dedup window
+    updated_on TIMESTAMP NOT NULL
+);
+
+CREATE INDEX account_upsert_stats_interval ON account_upsert_stats (interval_start);
+
 -- The table storing block metadata
 CREATE TABLE block (
     slot BIGINT PRIMARY KEY,
     blockhash VARCHAR(44),
+    parent_slot BIGINT,
+    parent_blockhash VARCHAR(44),
     rewards "Reward"[],
     block_time BIGINT,
     block_height BIGINT,
-    updated_on TIMESTAMP NOT NULL
+    block_json JSONB,
+    updated_on TIMESTAMP NOT NULL,
+    leader VARCHAR(44), -- The identity paid the fee reward of the block
+    executed_transaction_count BIGINT,
+    entry_count BIGINT
+);
+
+CREATE INDEX block_leader ON block (leader, slot);
+
+-- The table storing the rewards of the blocks, one row per reward
+CREATE TABLE block_reward (
+    slot BIGINT NOT NULL,
+    reward_index INT NOT NULL,
+    pubkey VARCHAR(44) NOT NULL,
+    lamports BIGINT NOT NULL,
+    post_balance BIGINT NOT NULL,
+    reward_type "RewardType",
+    commission SMALLINT,
+    updated_on TIMESTAMP NOT NULL,
+    CONSTRAINT block_reward_pk PRIMARY KEY (slot, reward_index)
 );
 
--- The table storing spl token owner to account indexes
+CREATE INDEX block_reward_pubkey ON block_reward (pubkey, slot);
+
+-- The table storing the entries of the blocks, the hash of the last entry of a block
+-- being its blockhash
+CREATE TABLE entry (
+    slot BIGINT NOT NULL,
+    entry_index BIGINT NOT NULL,
+    num_hashes BIGINT NOT NULL,
+    hash VARCHAR(44) NOT NULL,
+    executed_transaction_count BIGINT NOT NULL,
+    -- The index in the block of the first executed transaction of the entry
+    starting_transaction_index BIGINT NOT NULL,
+    updated_on TIMESTAMP NOT NULL,
+    CONSTRAINT entry_pk PRIMARY KEY (slot, entry_index)
+);
+
+-- The slots whose transactions are all committed with the commit_per_slot, written
+-- within the database transaction of the final chunk of the transactions of the slot
+CREATE TABLE slot_commit (
+    slot BIGINT PRIMARY KEY,
+    transaction_count BIGINT NOT NULL,
+    chunk_count BIGINT NOT NULL,
+    committed_on TIMESTAMP NOT NULL
+);
+
+-- The signatures of the transactions mentioning the address in the shape of the
+-- getSignaturesForAddress results, to be ordered by slot DESC for the newest first
+CREATE VIEW signatures_for_address AS
+    SELECT a.address,
+        a.slot,
+        a.index,
+        a.signature,
+        t.signature_text,
+        (t.meta).error AS err,
+        b.block_time,
+        s.status AS confirmation_status
+    FROM transaction_address a
+    JOIN transaction t ON t.slot = a.slot AND t.signature = a.signature
+    LEFT JOIN block b ON b.slot = a.slot
+    LEFT JOIN slot s ON s.slot = a.slot;
+
+-- The table storing the runs of the plugin instances
+CREATE TABLE plugin_run (
+    run_id BIGSERIAL PRIMARY KEY,
+    validator_identity VARCHAR(44) NOT NULL,
+    process_id BIGINT NOT NULL,
+    started_on TIMESTAMP NOT NULL,
+    heartbeat_on TIMESTAMP NOT NULL,
+    stopped_on TIMESTAMP
+);
+
+CREATE INDEX plugin_run_validator_identity ON plugin_run (validator_identity, heartbeat_on);
+
+-- The settings altering which tables and columns are written, recorded by the
+-- record_config_history whenever they change, along with their changes versus the
+-- previous record of the validator identity
+CREATE TABLE config_history (
+    id BIGSERIAL PRIMARY KEY,
+    validator_identity VARCHAR(44),
+    -- What changed the settings, 'load' or 'selector_reload'
+    change VARCHAR(16) NOT NULL,
+    settings JSONB NOT NULL,
+    -- The settings changed with their previous and new values
+    diff JSONB NOT NULL,
+    changed_on TIMESTAMP NOT NULL
+);
+
+CREATE INDEX config_history_changed_on ON config_history (changed_on);
+
+-- The table storing the transitions of the degradation ladder, the steps entered as
+-- the backlog of the queue of the workers grows and left as it drains
+CREATE TABLE degradation_transition (
+    id BIGSERIAL PRIMARY KEY,
+    validator_identity VARCHAR(44),
+    previous_level SMALLINT NOT NULL,
+    level SMALLINT NOT NULL, -- The count of the steps entered
+    -- The action of the step entered or left
+    action VARCHAR(32) NOT NULL,
+    queue_depth BIGINT NOT NULL,
+    transitioned_on TIMESTAMP NOT NULL
+);
+
+CREATE INDEX degradation_transition_transitioned_on ON degradation_transition (transitioned_on);
+
+-- The table keeping the definitions of the secondary indexes of the account table
+-- dropped for the startup by the manage_indexes_on_startup, until they are recreated
+CREATE TABLE startup_dropped_index (
+    index_name VARCHAR(64) PRIMARY KEY,
+    table_name VARCHAR(64) NOT NULL,
+    definition TEXT NOT NULL,
+    dropped_on TIMESTAMP NOT NULL
+);
+
+-- The barriers written by the write_epoch_barriers before the first slot of each epoch,
+-- all the updates queued before the slot being committed before the barrier
+CREATE TABLE epoch_barrier (
+    epoch BIGINT PRIMARY KEY,
+    slot BIGINT NOT NULL,
+    written_on TIMESTAMP NOT NULL
+);
+
+-- The table storing the summaries of the accounts loaded during the startup
+CREATE TABLE startup_report (
+    report_id BIGSERIAL PRIMARY KEY,
+    started_on TIMESTAMP NOT NULL,
+    finished_on TIMESTAMP NOT NULL,
+    duration_ms BIGINT NOT NULL,
+    account_count BIGINT NOT NULL,
+    failed_account_count BIGINT NOT NULL
+);
+
+-- The table storing the per-owner account counts of the startup reports
+CREATE TABLE startup_report_owner (
+    report_id BIGINT NOT NULL REFERENCES startup_report (report_id) ON DELETE CASCADE,
+    owner BYTEA NOT NULL,
+    account_count BIGINT NOT NULL,
+    CONSTRAINT startup_report_owner_pk PRIMARY KEY (report_id, owner)
+);
+
+-- The table storing the slot ranges needing backfill found by the block chain verification
+CREATE TABLE block_chain_gap (
+    start_slot BIGINT NOT NULL,
+    end_slot BIGINT NOT NULL,
+    reason VARCHAR(32) NOT NULL,
+    detected_on TIMESTAMP NOT NULL,
+    CONSTRAINT block_chain_gap_pk PRIMARY KEY (start_slot, end_slot)
+);
+
+-- The table storing vote activity aggregated per slot and voter
+CREATE TABLE vote_activity (
+    slot BIGINT NOT NULL,
+    voter BYTEA NOT NULL,
+    vote_count BIGINT NOT NULL,
+    latest_vote_slot BIGINT,
+    updated_on TIMESTAMP NOT NULL,
+    CONSTRAINT vote_activity_pk PRIMARY KEY (slot, voter)
+);
+
+-- The table storing spl token owner to account indexes, maintained when
+-- token_secondary_indexes includes spl_token_owner
 CREATE TABLE spl_token_owner_index (
     owner_key BYTEA NOT NULL,
-    inner_key BYTEA NOT NULL
+    account_key BYTEA NOT NULL,
+    slot BIGINT NOT NULL,
+    write_version BIGINT NOT NULL,
+    CONSTRAINT spl_token_owner_index_pk PRIMARY KEY (account_key)
 );
 
 CREATE INDEX spl_token_owner_index_owner_key ON spl_token_owner_index (owner_key);
 
--- The table storing spl mint to account indexes
+-- The table storing spl mint to account indexes, maintained when
+-- token_secondary_indexes includes spl_token_mint
 CREATE TABLE spl_token_mint_index (
     mint_key BYTEA NOT NULL,
-    inner_key BYTEA NOT NULL
+    account_key BYTEA NOT NULL,
+    slot BIGINT NOT NULL,
+    write_version BIGINT NOT NULL,
+    CONSTRAINT spl_token_mint_index_pk PRIMARY KEY (account_key)
 );
 
 CREATE INDEX spl_token_mint_index_mint_key ON spl_token_mint_index (mint_key);
@@ -208,21 +788,33 @@ CREATE TABLE account_audit (
     pubkey BYTEA,
     owner BYTEA,
     lamports BIGINT NOT NULL,
+    -- The change of the lamports versus the previous stored update, null for the first
+    lamports_delta BIGINT,
     slot BIGINT NOT NULL,
     executable BOOL NOT NULL,
     rent_epoch BIGINT NOT NULL,
     data BYTEA,
     write_version BIGINT NOT NULL,
-    updated_on TIMESTAMP NOT NULL
-);
+    updated_on TIMESTAMP NOT NULL,
+    data_codec SMALLINT NOT NULL DEFAULT 0,
+    txn_signature BYTEA
+) PARTITION BY RANGE (slot);
+
+-- The partition of the rows of the slots without a partition, the partitions of the slot
+-- ranges are created and dropped by the plugin when account_audit_retention_slots is set
+CREATE TABLE account_audit_default PARTITION OF account_audit DEFAULT;
 
 CREATE INDEX account_audit_account_key ON  account_audit (pubkey, write_version);
 
 CREATE FUNCTION audit_account_update() RETURNS trigger AS $audit_account_update$
     BEGIN
-		INSERT INTO account_audit (pubkey, owner, lamports, slot, executable, rent_epoch, data, write_version, updated_on)
-            VALUES (OLD.pubkey, OLD.owner, OLD.lamports, OLD.slot,
-                    OLD.executable, OLD.rent_epoch, OLD.data, OLD.write_version, OLD.updated_on);
+        -- The sessions of the plugin skip the audit while degraded
+        IF current_setting('accountsdb_plugin.skip_audit', true) = 'on' THEN
+            RETURN NEW;
+        END IF;
+		INSERT INTO account_audit (pubkey, owner, lamports, lamports_delta, slot, executable, rent_epoch, data, write_version, updated_on, data_codec, txn_signature)
+            VALUES (OLD.pubkey, OLD.owner, OLD.lamports, OLD.lamports_delta, OLD.slot,
+                    OLD.executable, OLD.rent_epoch, OLD.data, OLD.write_version, OLD.updated_on, OLD.data_codec, OLD.txn_signature);
         RETURN NEW;
     END;
 
@@ -230,3 +822,97 @@ $audit_account_update$ LANGUAGE plpgsql;
 
 CREATE TRIGGER account_update_trigger AFTER UPDATE OR DELETE ON account
     FOR EACH ROW EXECUTE PROCEDURE audit_account_update();
+
+-- The digest of a row ingested, the first 8 bytes of the SHA-256 of its content as a
+-- big-endian BIGINT, as computed by the plugin
+CREATE OR REPLACE FUNCTION ingest_row_digest(content BYTEA) RETURNS BIGINT
+    LANGUAGE SQL IMMUTABLE STRICT AS
+$$ SELECT ('x' || encode(substring(sha256(content) FROM 1 FOR 8), 'hex'))::BIT(64)::BIGINT $$;
+
+-- The checksum of the account and transaction rows of the slot recomputed from the
+-- tables, the wrapping sum of their digests along with their count, to compare with
+-- the ingest_checksum and the ingest_checksum_rows of the slot row. The versions of
+-- the accounts updated since the slot are read from the account_audit table.
+CREATE OR REPLACE FUNCTION slot_ingest_checksum(slot_number BIGINT, OUT checksum BIGINT, OUT row_count BIGINT)
+    LANGUAGE SQL STABLE AS
+$$
+    WITH digest AS (
+        SELECT ingest_row_digest('\x00'::BYTEA || pubkey || owner || int8send(lamports) || boolsend(executable)
+            || int8send(rent_epoch) || int8send(write_version) || sha256(coalesce(data, ''::BYTEA))) AS digest
+        FROM account WHERE slot = slot_number
+        UNION ALL
+        SELECT ingest_row_digest('\x00'::BYTEA || pubkey || owner || int8send(lamports) || boolsend(executable)
+            || int8send(rent_epoch) || int8send(write_version) || sha256(coalesce(data, ''::BYTEA)))
+        FROM account_audit WHERE slot = slot_number
+        UNION ALL
+        SELECT ingest_row_digest('\x01'::BYTEA || signature || coalesce(message_hash, ''::BYTEA) || boolsend(is_vote)
+            || int8send(coalesce(index, 0)) || int8send(coalesce((meta).fee, 0)) || boolsend(coalesce(failed, false)))
+        FROM transaction WHERE slot = slot_number
+    ), total AS (
+        SELECT mod(coalesce(sum(digest::NUMERIC), 0), 18446744073709551616) AS total, count(*) AS row_count
+        FROM digest
+    )
+    SELECT (CASE
+            WHEN total >= 9223372036854775808 THEN total - 18446744073709551616
+            WHEN total < -9223372036854775808 THEN total + 18446744073709551616
+            ELSE total
+        END)::BIGINT, row_count
+    FROM total
+$$;
+
+-- The table storing the changes of the owners of the accounts, for the security monitoring
+CREATE TABLE owner_change (
+    pubkey BYTEA NOT NULL,
+    old_owner BYTEA,
+    new_owner BYTEA,
+    slot BIGINT NOT NULL,
+    write_version BIGINT NOT NULL,
+    updated_on TIMESTAMP NOT NULL
+);
+
+CREATE INDEX owner_change_pubkey ON owner_change (pubkey, slot);
+
+CREATE INDEX owner_change_slot ON owner_change (slot);
+
+CREATE FUNCTION record_owner_change() RETURNS trigger AS $record_owner_change$
+    BEGIN
+        INSERT INTO owner_change (pubkey, old_owner, new_owner, slot, write_version, updated_on)
+            VALUES (NEW.pubkey, OLD.owner, NEW.owner, NEW.slot, NEW.write_version, NEW.updated_on);
+        RETURN NEW;
+    END;
+
+$record_owner_change$ LANGUAGE plpgsql;
+
+CREATE TRIGGER account_owner_change_trigger AFTER UPDATE OF owner ON account
+    FOR EACH ROW WHEN (OLD.owner IS DISTINCT FROM NEW.owner)
+    EXECUTE PROCEDURE record_owner_change();
+
+-- The table storing every version of the accounts selected by account_versions_selector,
+-- including the multiple writes to an account within a slot, for the state of the
+-- accounts to be reconstructed at any slot
+CREATE TABLE account_version (
+    pubkey BYTEA NOT NULL,
+    slot BIGINT NOT NULL,
+    write_version BIGINT NOT NULL,
+    owner BYTEA,
+    lamports BIGINT NOT NULL,
+    executable BOOL NOT NULL,
+    rent_epoch BIGINT NOT NULL,
+    data BYTEA,
+    data_codec SMALLINT NOT NULL DEFAULT 0,
+    updated_on TIMESTAMP NOT NULL,
+    CONSTRAINT account_version_pk PRIMARY KEY (pubkey, slot, write_version)
+);
+
+CREATE INDEX account_version_slot ON account_version (slot);
+
+-- The migrations in scripts/migrations applied to the schema, a schema created by this
+-- script is at the version of the latest migration
+CREATE TABLE schema_version (
+    version INT PRIMARY KEY,
+    applied_on TIMESTAMP NOT NULL,
+    -- The SHA-256 checksum of the migration applied by the plugin
+    checksum VARCHAR(64)
+);
+
+INSERT INTO schema_version (version, applied_on) VALUES (17, now());
@@ -0,0 +1,30 @@
+/**
+ * Script for comparing the account tables written by two startup loads of the same
+ * snapshot, e.g. by the validators before and after an upgrade loading into the
+ * schemas :before and :after. The accounts updated after the snapshot :slot in either
+ * load are left out. Reports the divergent pubkeys along with the divergent columns.
+ *
+ * psql -v before=before_upgrade -v after=after_upgrade -v slot=1000000 -f scripts/diff_startup_accounts.sql
+ */
+
+SELECT COALESCE(b.pubkey, a.pubkey) AS pubkey,
+    CASE
+        WHEN b.pubkey IS NULL THEN 'missing_before'
+        WHEN a.pubkey IS NULL THEN 'missing_after'
+        ELSE concat_ws(',',
+            CASE WHEN b.owner IS DISTINCT FROM a.owner THEN 'owner' END,
+            CASE WHEN b.lamports <> a.lamports THEN 'lamports' END,
+            CASE WHEN b.executable <> a.executable THEN 'executable' END,
+            CASE WHEN b.rent_epoch <> a.rent_epoch THEN 'rent_epoch' END,
+            CASE WHEN b.data IS DISTINCT FROM a.data THEN 'data' END)
+    END AS divergence
+FROM :"before".account b
+FULL OUTER JOIN :"after".account a ON b.pubkey = a.pubkey
+WHERE COALESCE(b.slot, 0) <= :slot AND COALESCE(a.slot, 0) <= :slot
+    AND (b.pubkey IS NULL OR a.pubkey IS NULL
+        OR b.owner IS DISTINCT FROM a.owner
+        OR b.lamports <> a.lamports
+        OR b.executable <> a.executable
+        OR b.rent_epoch <> a.rent_epoch
+        OR b.data IS DISTINCT FROM a.data)
+ORDER BY 1;
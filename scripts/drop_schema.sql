@@ -2,13 +2,66 @@
  * Script for cleaning up the schema for PostgreSQL used for the AccountsDb plugin.
  */
 
-DROP TRIGGER account_update_trigger ON account;
-DROP FUNCTION audit_account_update;
+DROP TRIGGER IF EXISTS account_update_trigger ON account;
+DROP FUNCTION IF EXISTS audit_account_update;
+DROP TRIGGER IF EXISTS account_owner_change_trigger ON account;
+DROP FUNCTION IF EXISTS record_owner_change;
+DROP FUNCTION IF EXISTS slot_ingest_checksum;
+DROP FUNCTION IF EXISTS ingest_row_digest;
+DROP TABLE owner_change;
 DROP TABLE account_audit;
+DROP TABLE account_version;
+DROP VIEW IF EXISTS account_with_data;
+DROP VIEW IF EXISTS account_tiered;
+DROP TABLE IF EXISTS account_cold;
+DROP TABLE IF EXISTS selector_dry_run_stats;
+DROP TABLE IF EXISTS account_data;
 DROP TABLE account;
+DROP VIEW IF EXISTS spl_token_balance;
+DROP FUNCTION IF EXISTS spl_token_ui_amount;
+DROP FUNCTION IF EXISTS spl_token_amount;
+DROP TABLE spl_token_account;
+DROP TABLE spl_token_mint;
+DROP VIEW slot_confirmation_latency_distribution;
+DROP VIEW slot_confirmation_latency;
+DROP VIEW signatures_for_address;
+DROP VIEW tagged_sol_transfer;
+DROP VIEW validator_epoch_reward;
 DROP TABLE slot;
+DROP TABLE transaction_address;
+DROP TABLE sol_transfer;
+DROP TABLE transaction_memo;
+DROP TABLE transaction_instruction;
+DROP TABLE schema_version;
+DROP TABLE transaction_balance;
+DROP TABLE transaction_token_balance;
+DROP TABLE transaction_reward;
+DROP TABLE transaction_log;
+DROP TABLE program_label;
+DROP TABLE address_tag;
+DROP TABLE dead_letter;
+DROP TABLE transaction_allowlist;
+DROP TABLE slot_economics;
+DROP TABLE validator_reward;
+DROP TABLE stake_reward;
+DROP TABLE ingest_sla_violation;
+DROP TABLE index_advice;
+DROP TABLE startup_dropped_index;
+DROP TABLE epoch_barrier;
+DROP TABLE table_write_stats;
+DROP TABLE account_upsert_stats;
 DROP TABLE transaction;
 DROP TABLE block;
+DROP TABLE block_reward;
+DROP TABLE entry;
+DROP TABLE slot_commit;
+DROP TABLE block_chain_gap;
+DROP TABLE plugin_run;
+DROP TABLE config_history;
+DROP TABLE degradation_transition;
+DROP TABLE startup_report_owner;
+DROP TABLE startup_report;
+DROP TABLE vote_activity;
 DROP TABLE spl_token_owner_index;
 DROP TABLE spl_token_mint_index;
 
@@ -22,6 +75,7 @@ DROP TYPE "TransactionMessageHeader" CASCADE;
 DROP TYPE "TransactionMessageAddressTableLookup" CASCADE;
 DROP TYPE "TransactionStatusMeta" CASCADE;
 DROP TYPE "RewardType" CASCADE;
+DROP TYPE "TokenAccountState" CASCADE;
 DROP TYPE "Reward" CASCADE;
 DROP TYPE "TransactionTokenBalance" CASCADE;
 DROP TYPE "InnerInstructions" CASCADE;
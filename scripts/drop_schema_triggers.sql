@@ -0,0 +1,10 @@
+/**
+ * Script for converting the schema created by create_schema.sql into the trigger-free
+ * schema, for the plugin configured with "use_schema_triggers": false to write the
+ * account_audit and owner_change rows itself.
+ */
+
+DROP TRIGGER account_update_trigger ON account;
+DROP FUNCTION audit_account_update;
+DROP TRIGGER account_owner_change_trigger ON account;
+DROP FUNCTION record_owner_change;
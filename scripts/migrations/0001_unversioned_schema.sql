@@ -0,0 +1,39 @@
+/**
+ * Migration of a schema created by a create_schema.sql predating the schema versions,
+ * adding the columns and the tables added since then.
+ */
+
+CREATE TABLE IF NOT EXISTS schema_version (
+    version INT PRIMARY KEY,
+    applied_on TIMESTAMP NOT NULL
+);
+
+ALTER TABLE account ADD COLUMN IF NOT EXISTS discriminator BYTEA;
+ALTER TABLE account ADD COLUMN IF NOT EXISTS data_json JSONB;
+CREATE INDEX IF NOT EXISTS account_owner_discriminator ON account (owner, discriminator) WHERE discriminator IS NOT NULL;
+
+-- The rows decoded before the decoder versions are decoded again by redecode_token_accounts
+ALTER TABLE spl_token_account ADD COLUMN IF NOT EXISTS decoder_version SMALLINT NOT NULL DEFAULT 0;
+ALTER TABLE spl_token_account ALTER COLUMN decoder_version DROP DEFAULT;
+ALTER TABLE spl_token_mint ADD COLUMN IF NOT EXISTS decoder_version SMALLINT NOT NULL DEFAULT 0;
+ALTER TABLE spl_token_mint ALTER COLUMN decoder_version DROP DEFAULT;
+
+CREATE TABLE IF NOT EXISTS transaction_instruction (
+    slot BIGINT NOT NULL,
+    signature BYTEA NOT NULL,
+    instruction_index SMALLINT NOT NULL,
+    program VARCHAR(32) NOT NULL, -- The program name as in the 'jsonParsed' encoding
+    program_id BYTEA NOT NULL,
+    parsed JSONB NOT NULL,
+    updated_on TIMESTAMP NOT NULL,
+    CONSTRAINT transaction_instruction_pk PRIMARY KEY (slot, signature, instruction_index)
+);
+
+CREATE INDEX IF NOT EXISTS transaction_instruction_program ON transaction_instruction (program, slot);
+
+CREATE TABLE IF NOT EXISTS slot_commit (
+    slot BIGINT PRIMARY KEY,
+    transaction_count BIGINT NOT NULL,
+    chunk_count BIGINT NOT NULL,
+    committed_on TIMESTAMP NOT NULL
+);
@@ -0,0 +1,23 @@
+/**
+ * Migration adding the leader, the executed transaction count and the entry count of
+ * the blocks, and the block_reward table.
+ */
+
+ALTER TABLE block ADD COLUMN IF NOT EXISTS leader VARCHAR(44);
+ALTER TABLE block ADD COLUMN IF NOT EXISTS executed_transaction_count BIGINT;
+ALTER TABLE block ADD COLUMN IF NOT EXISTS entry_count BIGINT;
+CREATE INDEX IF NOT EXISTS block_leader ON block (leader, slot);
+
+CREATE TABLE IF NOT EXISTS block_reward (
+    slot BIGINT NOT NULL,
+    reward_index INT NOT NULL,
+    pubkey VARCHAR(44) NOT NULL,
+    lamports BIGINT NOT NULL,
+    post_balance BIGINT NOT NULL,
+    reward_type "RewardType",
+    commission SMALLINT,
+    updated_on TIMESTAMP NOT NULL,
+    CONSTRAINT block_reward_pk PRIMARY KEY (slot, reward_index)
+);
+
+CREATE INDEX IF NOT EXISTS block_reward_pubkey ON block_reward (pubkey, slot);
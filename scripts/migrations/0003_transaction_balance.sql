@@ -0,0 +1,17 @@
+/**
+ * Migration adding the transaction_balance table, normalizing the balances of the
+ * accounts of the transactions from their meta.
+ */
+
+CREATE TABLE IF NOT EXISTS transaction_balance (
+    slot BIGINT NOT NULL,
+    signature BYTEA NOT NULL,
+    account_index SMALLINT NOT NULL,
+    account VARCHAR(44) NOT NULL,
+    pre_balance BIGINT NOT NULL,
+    post_balance BIGINT NOT NULL,
+    updated_on TIMESTAMP NOT NULL,
+    CONSTRAINT transaction_balance_pk PRIMARY KEY (slot, signature, account_index)
+);
+
+CREATE INDEX IF NOT EXISTS transaction_balance_account ON transaction_balance (account, slot);
@@ -0,0 +1,16 @@
+/**
+ * Migration adding the index_advice table of the index advisor.
+ */
+
+CREATE TABLE IF NOT EXISTS index_advice (
+    table_name VARCHAR(64) NOT NULL,
+    kind VARCHAR(16) NOT NULL, -- 'index' or 'partition'
+    columns VARCHAR(64)[] NOT NULL,
+    statement TEXT, -- The DDL of the index advised
+    calls BIGINT NOT NULL,
+    total_exec_time_ms DOUBLE PRECISION NOT NULL,
+    mean_exec_time_ms DOUBLE PRECISION NOT NULL,
+    query TEXT NOT NULL, -- The slowest query in total of the ones advised for
+    advised_on TIMESTAMP NOT NULL,
+    CONSTRAINT index_advice_pk PRIMARY KEY (table_name, kind, columns)
+);
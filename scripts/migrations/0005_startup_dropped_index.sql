@@ -0,0 +1,10 @@
+/**
+ * Migration adding the startup_dropped_index table of the manage_indexes_on_startup.
+ */
+
+CREATE TABLE IF NOT EXISTS startup_dropped_index (
+    index_name VARCHAR(64) PRIMARY KEY,
+    table_name VARCHAR(64) NOT NULL,
+    definition TEXT NOT NULL,
+    dropped_on TIMESTAMP NOT NULL
+);
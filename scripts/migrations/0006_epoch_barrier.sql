@@ -0,0 +1,9 @@
+/**
+ * Migration adding the epoch_barrier table of the write_epoch_barriers.
+ */
+
+CREATE TABLE IF NOT EXISTS epoch_barrier (
+    epoch BIGINT PRIMARY KEY,
+    slot BIGINT NOT NULL,
+    written_on TIMESTAMP NOT NULL
+);
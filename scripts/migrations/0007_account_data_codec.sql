@@ -0,0 +1,31 @@
+/**
+ * Migration adding the data_codec column of the account_data_compression, telling how the
+ * data of each account row is encoded. The column goes to the account_data table under the
+ * split account layout.
+ */
+
+DO $$
+BEGIN
+    IF to_regclass('account_data') IS NULL THEN
+        ALTER TABLE account ADD COLUMN IF NOT EXISTS data_codec SMALLINT NOT NULL DEFAULT 0;
+    ELSE
+        ALTER TABLE account_data ADD COLUMN IF NOT EXISTS data_codec SMALLINT NOT NULL DEFAULT 0;
+        CREATE OR REPLACE VIEW account_with_data AS
+            SELECT a.pubkey, a.owner, a.lamports, a.lamports_delta, a.slot, a.executable, a.rent_epoch,
+                d.data, a.write_version, a.updated_on, a.discriminator, a.data_json, d.data_codec
+            FROM account a LEFT JOIN account_data d USING (pubkey);
+    END IF;
+END
+$$;
+
+ALTER TABLE account_audit ADD COLUMN IF NOT EXISTS data_codec SMALLINT NOT NULL DEFAULT 0;
+
+CREATE OR REPLACE FUNCTION audit_account_update() RETURNS trigger AS $audit_account_update$
+    BEGIN
+		INSERT INTO account_audit (pubkey, owner, lamports, lamports_delta, slot, executable, rent_epoch, data, write_version, updated_on, data_codec)
+            VALUES (OLD.pubkey, OLD.owner, OLD.lamports, OLD.lamports_delta, OLD.slot,
+                    OLD.executable, OLD.rent_epoch, OLD.data, OLD.write_version, OLD.updated_on, OLD.data_codec);
+        RETURN NEW;
+    END;
+
+$audit_account_update$ LANGUAGE plpgsql;
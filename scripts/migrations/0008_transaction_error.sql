@@ -0,0 +1,13 @@
+/**
+ * Migration adding the columns of the transaction error decoded, for the failed
+ * transactions to be filtered on their error code, failing instruction and custom
+ * program error.
+ */
+
+ALTER TABLE transaction ADD COLUMN IF NOT EXISTS error_code VARCHAR(64);
+ALTER TABLE transaction ADD COLUMN IF NOT EXISTS error_instruction_index SMALLINT;
+ALTER TABLE transaction ADD COLUMN IF NOT EXISTS error_custom_code BIGINT;
+ALTER TABLE transaction ADD COLUMN IF NOT EXISTS error_program VARCHAR(44);
+
+CREATE INDEX IF NOT EXISTS transaction_error_program ON transaction (error_program, error_custom_code)
+    WHERE error_program IS NOT NULL;
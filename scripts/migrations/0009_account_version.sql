@@ -0,0 +1,21 @@
+/**
+ * Migration adding the account_version table storing every version of the accounts
+ * selected by account_versions_selector, for the state of the accounts to be
+ * reconstructed at any slot.
+ */
+
+CREATE TABLE IF NOT EXISTS account_version (
+    pubkey BYTEA NOT NULL,
+    slot BIGINT NOT NULL,
+    write_version BIGINT NOT NULL,
+    owner BYTEA,
+    lamports BIGINT NOT NULL,
+    executable BOOL NOT NULL,
+    rent_epoch BIGINT NOT NULL,
+    data BYTEA,
+    data_codec SMALLINT NOT NULL DEFAULT 0,
+    updated_on TIMESTAMP NOT NULL,
+    CONSTRAINT account_version_pk PRIMARY KEY (pubkey, slot, write_version)
+);
+
+CREATE INDEX IF NOT EXISTS account_version_slot ON account_version (slot);
@@ -0,0 +1,30 @@
+/**
+ * Migration reshaping the spl_token_owner_index and spl_token_mint_index tables into
+ * the secondary indexes of the token accounts maintained by token_secondary_indexes,
+ * keyed by the token accounts. The tables of the earlier schemas were never written by
+ * the plugin, they are created again.
+ */
+
+DROP TABLE IF EXISTS spl_token_owner_index;
+
+CREATE TABLE spl_token_owner_index (
+    owner_key BYTEA NOT NULL,
+    account_key BYTEA NOT NULL,
+    slot BIGINT NOT NULL,
+    write_version BIGINT NOT NULL,
+    CONSTRAINT spl_token_owner_index_pk PRIMARY KEY (account_key)
+);
+
+CREATE INDEX spl_token_owner_index_owner_key ON spl_token_owner_index (owner_key);
+
+DROP TABLE IF EXISTS spl_token_mint_index;
+
+CREATE TABLE spl_token_mint_index (
+    mint_key BYTEA NOT NULL,
+    account_key BYTEA NOT NULL,
+    slot BIGINT NOT NULL,
+    write_version BIGINT NOT NULL,
+    CONSTRAINT spl_token_mint_index_pk PRIMARY KEY (account_key)
+);
+
+CREATE INDEX spl_token_mint_index_mint_key ON spl_token_mint_index (mint_key);
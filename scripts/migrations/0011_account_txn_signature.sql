@@ -0,0 +1,33 @@
+/**
+ * Migration adding the txn_signature column of the account and account_audit tables,
+ * the signature of the transaction causing each account update, null for the updates
+ * of the startup. The column goes to the account_cold table as well when the cold tier
+ * is configured, its rows being moved back into the account table.
+ */
+
+ALTER TABLE account ADD COLUMN IF NOT EXISTS txn_signature BYTEA;
+
+ALTER TABLE IF EXISTS account_cold ADD COLUMN IF NOT EXISTS txn_signature BYTEA;
+
+ALTER TABLE account_audit ADD COLUMN IF NOT EXISTS txn_signature BYTEA;
+
+DO $$
+BEGIN
+    IF to_regclass('account_data') IS NOT NULL THEN
+        CREATE OR REPLACE VIEW account_with_data AS
+            SELECT a.pubkey, a.owner, a.lamports, a.lamports_delta, a.slot, a.executable, a.rent_epoch,
+                d.data, a.write_version, a.updated_on, a.discriminator, a.data_json, d.data_codec, a.txn_signature
+            FROM account a LEFT JOIN account_data d USING (pubkey);
+    END IF;
+END
+$$;
+
+CREATE OR REPLACE FUNCTION audit_account_update() RETURNS trigger AS $audit_account_update$
+    BEGIN
+		INSERT INTO account_audit (pubkey, owner, lamports, lamports_delta, slot, executable, rent_epoch, data, write_version, updated_on, data_codec, txn_signature)
+            VALUES (OLD.pubkey, OLD.owner, OLD.lamports, OLD.lamports_delta, OLD.slot,
+                    OLD.executable, OLD.rent_epoch, OLD.data, OLD.write_version, OLD.updated_on, OLD.data_codec, OLD.txn_signature);
+        RETURN NEW;
+    END;
+
+$audit_account_update$ LANGUAGE plpgsql;
@@ -0,0 +1,17 @@
+/**
+ * Migration adding the config_history table recording the settings altering which
+ * tables and columns are written, for the record_config_history.
+ */
+
+CREATE TABLE IF NOT EXISTS config_history (
+    id BIGSERIAL PRIMARY KEY,
+    validator_identity VARCHAR(44),
+    -- What changed the settings, 'load' or 'selector_reload'
+    change VARCHAR(16) NOT NULL,
+    settings JSONB NOT NULL,
+    -- The settings changed with their previous and new values
+    diff JSONB NOT NULL,
+    changed_on TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS config_history_changed_on ON config_history (changed_on);
@@ -0,0 +1,7 @@
+/**
+ * Migration adding the counts of the transactions selected received and committed to
+ * the slot table, for the store_slot_transaction_counts.
+ */
+
+ALTER TABLE slot ADD COLUMN IF NOT EXISTS transactions_received BIGINT;
+ALTER TABLE slot ADD COLUMN IF NOT EXISTS transactions_committed BIGINT;
@@ -0,0 +1,6 @@
+/**
+ * Migration adding the checksum of the migrations applied by the plugin to the
+ * schema_version table, checked against the migrations of the schema_source.
+ */
+
+ALTER TABLE schema_version ADD COLUMN IF NOT EXISTS checksum VARCHAR(64);
@@ -0,0 +1,22 @@
+/**
+ * The functions computing the decimal amounts of the token accounts from the decimals
+ * of their mints, and the view of the balances of the decoded token accounts.
+ */
+
+-- The amount in the base units of a mint as a decimal amount, like the uiAmountString
+-- of the RPC nodes
+CREATE OR REPLACE FUNCTION spl_token_ui_amount(amount NUMERIC, decimals SMALLINT) RETURNS NUMERIC
+    LANGUAGE SQL IMMUTABLE STRICT AS
+$$ SELECT round(amount / power(10::NUMERIC, decimals), decimals) $$;
+
+-- The amount of the raw data of a token account, the little-endian u64 following its
+-- mint and owner. The data must not be compressed.
+CREATE OR REPLACE FUNCTION spl_token_amount(data BYTEA) RETURNS NUMERIC
+    LANGUAGE SQL IMMUTABLE STRICT AS
+$$ SELECT sum(get_byte(data, 64 + i)::NUMERIC * power(256::NUMERIC, i)) FROM generate_series(0, 7) AS i $$;
+
+-- The balances of the decoded token accounts in the units of their mints
+CREATE OR REPLACE VIEW spl_token_balance AS
+    SELECT t.pubkey, t.mint, t.owner, t.amount, m.decimals,
+        spl_token_ui_amount(t.amount, m.decimals) AS ui_amount, t.slot
+    FROM spl_token_account t JOIN spl_token_mint m ON m.pubkey = t.mint;
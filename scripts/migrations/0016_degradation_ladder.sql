@@ -0,0 +1,34 @@
+/**
+ * Migration adding the degradation_transition table recording the transitions of the
+ * degradation ladder, and the skipping of the audit by the audit_account_update
+ * trigger while the ladder disables it.
+ */
+
+-- The table storing the transitions of the degradation ladder, the steps entered as
+-- the backlog of the queue of the workers grows and left as it drains
+CREATE TABLE IF NOT EXISTS degradation_transition (
+    id BIGSERIAL PRIMARY KEY,
+    validator_identity VARCHAR(44),
+    previous_level SMALLINT NOT NULL,
+    level SMALLINT NOT NULL, -- The count of the steps entered
+    -- The action of the step entered or left
+    action VARCHAR(32) NOT NULL,
+    queue_depth BIGINT NOT NULL,
+    transitioned_on TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS degradation_transition_transitioned_on ON degradation_transition (transitioned_on);
+
+CREATE OR REPLACE FUNCTION audit_account_update() RETURNS trigger AS $audit_account_update$
+    BEGIN
+        -- The sessions of the plugin skip the audit while degraded
+        IF current_setting('accountsdb_plugin.skip_audit', true) = 'on' THEN
+            RETURN NEW;
+        END IF;
+		INSERT INTO account_audit (pubkey, owner, lamports, lamports_delta, slot, executable, rent_epoch, data, write_version, updated_on, data_codec, txn_signature)
+            VALUES (OLD.pubkey, OLD.owner, OLD.lamports, OLD.lamports_delta, OLD.slot,
+                    OLD.executable, OLD.rent_epoch, OLD.data, OLD.write_version, OLD.updated_on, OLD.data_codec, OLD.txn_signature);
+        RETURN NEW;
+    END;
+
+$audit_account_update$ LANGUAGE plpgsql;
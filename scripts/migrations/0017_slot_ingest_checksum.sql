@@ -0,0 +1,44 @@
+/**
+ * Migration adding the checksums of the rows ingested to the slot table, for the
+ * store_slot_checksums, and the functions recomputing them from the tables.
+ */
+
+ALTER TABLE slot ADD COLUMN IF NOT EXISTS ingest_checksum BIGINT;
+ALTER TABLE slot ADD COLUMN IF NOT EXISTS ingest_checksum_rows BIGINT;
+
+-- The digest of a row ingested, the first 8 bytes of the SHA-256 of its content as a
+-- big-endian BIGINT, as computed by the plugin
+CREATE OR REPLACE FUNCTION ingest_row_digest(content BYTEA) RETURNS BIGINT
+    LANGUAGE SQL IMMUTABLE STRICT AS
+$$ SELECT ('x' || encode(substring(sha256(content) FROM 1 FOR 8), 'hex'))::BIT(64)::BIGINT $$;
+
+-- The checksum of the account and transaction rows of the slot recomputed from the
+-- tables, the wrapping sum of their digests along with their count, to compare with
+-- the ingest_checksum and the ingest_checksum_rows of the slot row. The versions of
+-- the accounts updated since the slot are read from the account_audit table.
+CREATE OR REPLACE FUNCTION slot_ingest_checksum(slot_number BIGINT, OUT checksum BIGINT, OUT row_count BIGINT)
+    LANGUAGE SQL STABLE AS
+$$
+    WITH digest AS (
+        SELECT ingest_row_digest('\x00'::BYTEA || pubkey || owner || int8send(lamports) || boolsend(executable)
+            || int8send(rent_epoch) || int8send(write_version) || sha256(coalesce(data, ''::BYTEA))) AS digest
+        FROM account WHERE slot = slot_number
+        UNION ALL
+        SELECT ingest_row_digest('\x00'::BYTEA || pubkey || owner || int8send(lamports) || boolsend(executable)
+            || int8send(rent_epoch) || int8send(write_version) || sha256(coalesce(data, ''::BYTEA)))
+        FROM account_audit WHERE slot = slot_number
+        UNION ALL
+        SELECT ingest_row_digest('\x01'::BYTEA || signature || coalesce(message_hash, ''::BYTEA) || boolsend(is_vote)
+            || int8send(coalesce(index, 0)) || int8send(coalesce((meta).fee, 0)) || boolsend(coalesce(failed, false)))
+        FROM transaction WHERE slot = slot_number
+    ), total AS (
+        SELECT mod(coalesce(sum(digest::NUMERIC), 0), 18446744073709551616) AS total, count(*) AS row_count
+        FROM digest
+    )
+    SELECT (CASE
+            WHEN total >= 9223372036854775808 THEN total - 18446744073709551616
+            WHEN total < -9223372036854775808 THEN total + 18446744073709551616
+            ELSE total
+        END)::BIGINT, row_count
+    FROM total
+$$;
@@ -0,0 +1,27 @@
+/**
+ * Script for converting the account table of the trigger-free schema into the split
+ * layout, for the plugin configured with "account_layout": "split" to write the data of
+ * the accounts into the account_data table, keeping the account table narrow for the
+ * balance and ownership queries. The schema triggers are to be dropped first with
+ * scripts/drop_schema_triggers.sql.
+ */
+
+CREATE TABLE account_data (
+    pubkey BYTEA PRIMARY KEY,
+    data BYTEA,
+    slot BIGINT NOT NULL,
+    write_version BIGINT NOT NULL,
+    updated_on TIMESTAMP NOT NULL,
+    data_codec SMALLINT NOT NULL DEFAULT 0
+);
+
+INSERT INTO account_data (pubkey, data, slot, write_version, updated_on, data_codec)
+SELECT pubkey, data, slot, write_version, updated_on, data_codec FROM account;
+
+ALTER TABLE account DROP COLUMN data, DROP COLUMN data_codec;
+
+-- The accounts along with their data, in the columns of the wide layout
+CREATE VIEW account_with_data AS
+    SELECT a.pubkey, a.owner, a.lamports, a.lamports_delta, a.slot, a.executable, a.rent_epoch,
+        d.data, a.write_version, a.updated_on, a.discriminator, a.data_json, d.data_codec, a.txn_signature
+    FROM account a LEFT JOIN account_data d USING (pubkey);
@@ -0,0 +1,69 @@
+/// The account sampler is responsible for ingesting only a percentage of the accounts,
+/// to trial the plugin on the production traffic and extrapolate the storage and the
+/// throughput of the full ingestion.
+use {log::*, solana_sdk::hash::hash};
+
+#[derive(Debug, Default)]
+pub(crate) struct AccountSampler {
+    /// The accounts whose pubkey hash is below the threshold are stored, all the
+    /// accounts are stored when not set
+    threshold: Option<u64>,
+}
+
+impl AccountSampler {
+    pub fn new(sampling_percentage: Option<f64>) -> Self {
+        info!(
+            "Creating AccountSampler from account_sampling_percentage: {:?}",
+            sampling_percentage
+        );
+        let threshold = sampling_percentage
+            .filter(|percentage| *percentage < 100.0)
+            .map(|percentage| (percentage / 100.0 * u64::MAX as f64) as u64);
+        Self { threshold }
+    }
+
+    /// Check if the updates of the account are to be stored. The accounts are sampled
+    /// by the hash of the pubkey, so that all the updates of a sampled account are
+    /// stored, during the startup and across the restarts alike.
+    pub fn is_account_sampled(&self, pubkey: &[u8]) -> bool {
+        match self.threshold {
+            Some(threshold) => {
+                let hash = hash(pubkey);
+                let value = u64::from_le_bytes(hash.as_ref()[..8].try_into().unwrap());
+                value < threshold
+            }
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, solana_sdk::pubkey::Pubkey};
+
+    #[test]
+    fn test_account_sampler() {
+        let pubkeys: Vec<Pubkey> = (0..10_000).map(|_| Pubkey::new_unique()).collect();
+
+        let sampler = AccountSampler::default();
+        assert!(pubkeys
+            .iter()
+            .all(|pubkey| sampler.is_account_sampled(pubkey.as_ref())));
+        let sampler = AccountSampler::new(Some(100.0));
+        assert!(pubkeys
+            .iter()
+            .all(|pubkey| sampler.is_account_sampled(pubkey.as_ref())));
+
+        let sampler = AccountSampler::new(Some(10.0));
+        let sampled = pubkeys
+            .iter()
+            .filter(|pubkey| sampler.is_account_sampled(pubkey.as_ref()))
+            .count();
+        assert!((800..1200).contains(&sampled), "sampled {}", sampled);
+        // The sampling is deterministic by the pubkey
+        assert!(pubkeys
+            .iter()
+            .all(|pubkey| sampler.is_account_sampled(pubkey.as_ref())
+                == AccountSampler::new(Some(10.0)).is_account_sampled(pubkey.as_ref())));
+    }
+}
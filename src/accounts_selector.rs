@@ -1,10 +1,136 @@
-use {log::*, std::collections::HashSet};
+use {
+    crate::accountsdb_plugin_postgres::{AccountsSelectorConfig, AccountsSelectorRuleConfig},
+    log::*,
+    std::collections::HashSet,
+};
+
+/// The presets of the accounts selector, each expanding to the curated owners of an
+/// ecosystem, in addition to the owners of the selector
+pub(crate) const ACCOUNTS_SELECTOR_PRESETS: [(&str, &[&str]); 3] = [
+    (
+        "spl-token",
+        &[
+            // SPL Token
+            "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+            // SPL Token-2022
+            "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb",
+        ],
+    ),
+    (
+        "all-defi-core",
+        &[
+            // Orca Whirlpools
+            "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc",
+            // Raydium AMM v4
+            "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8",
+            // Raydium CLMM
+            "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK",
+            // Meteora DLMM
+            "LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo",
+            // OpenBook v2
+            "opnb2LAfJYbRMAHHvqjCwQxanZn7ReEHp1k81EohpZb",
+            // Phoenix
+            "PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY",
+            // Kamino Lending
+            "KLend2g3cP87fffoy8q1mQqGKjrxjC8boSyAYavgmjD",
+            // Solend
+            "So1endDq2YkqhipRh3WViPa8hdiSpxWy6z3Z6tMCpAo",
+            // Drift v2
+            "dRiftyHA39MWEi3m9aunc5MzRF1JYuBsbn6VPcn33UH",
+            // Marinade
+            "MarBmsSgKXdrN1egZf5sqe1TMai9K1rChYNDJgjq7aD",
+        ],
+    ),
+    (
+        "validator-ops",
+        &[
+            // Vote
+            "Vote111111111111111111111111111111111111111",
+            // Stake
+            "Stake11111111111111111111111111111111111111",
+            // Config, owning the validator info accounts
+            "Config1111111111111111111111111111111111111",
+            // The sysvars
+            "Sysvar1111111111111111111111111111111111111",
+        ],
+    ),
+];
+
+/// Get the owners of the preset, None when the preset is unknown
+pub(crate) fn preset_owners(preset: &str) -> Option<&'static [&'static str]> {
+    ACCOUNTS_SELECTOR_PRESETS
+        .iter()
+        .find(|(name, _)| *name == preset)
+        .map(|(_, owners)| *owners)
+}
+
+/// Get the owners of the selector along with the owners of its preset
+pub(crate) fn owners_with_preset(owners: &[String], preset: Option<&str>) -> Vec<String> {
+    owners
+        .iter()
+        .cloned()
+        .chain(
+            preset
+                .and_then(preset_owners)
+                .into_iter()
+                .flatten()
+                .map(|owner| owner.to_string()),
+        )
+        .collect()
+}
+
+/// A compound rule selecting the accounts satisfying all of its conditions
+#[derive(Debug)]
+pub(crate) struct AccountsSelectorRule {
+    pub owner: Option<Vec<u8>>,
+    pub min_data_size: Option<usize>,
+    pub max_data_size: Option<usize>,
+    pub discriminator: Option<Vec<u8>>,
+}
+
+impl AccountsSelectorRule {
+    pub fn new(rule: &AccountsSelectorRuleConfig) -> Self {
+        AccountsSelectorRule {
+            owner: rule
+                .owner
+                .as_ref()
+                .map(|key| bs58::decode(key).into_vec().unwrap()),
+            min_data_size: rule.min_data_size,
+            max_data_size: rule.max_data_size,
+            discriminator: rule
+                .discriminator
+                .as_ref()
+                .map(|bytes| bs58::decode(bytes).into_vec().unwrap()),
+        }
+    }
+
+    pub fn is_account_selected(&self, owner: &[u8], data: &[u8]) -> bool {
+        self.owner
+            .as_deref()
+            .is_none_or(|rule_owner| rule_owner == owner)
+            && self.min_data_size.is_none_or(|size| data.len() >= size)
+            && self.max_data_size.is_none_or(|size| data.len() <= size)
+            && self
+                .discriminator
+                .as_deref()
+                .is_none_or(|discriminator| data.starts_with(discriminator))
+    }
+}
 
 #[derive(Debug)]
 pub(crate) struct AccountsSelector {
     pub accounts: HashSet<Vec<u8>>,
     pub owners: HashSet<Vec<u8>>,
+    pub rules: Vec<AccountsSelectorRule>,
     pub select_all_accounts: bool,
+    /// The accounts never selected, taking precedence over the other conditions
+    pub exclude_accounts: HashSet<Vec<u8>>,
+    /// The owners whose accounts are never selected
+    pub exclude_owners: HashSet<Vec<u8>>,
+    /// The inclusive lower bound of the lamports of the accounts selected
+    pub min_lamports: Option<u64>,
+    /// The inclusive upper bound of the lamports of the accounts selected
+    pub max_lamports: Option<u64>,
 }
 
 impl AccountsSelector {
@@ -12,14 +138,23 @@ impl AccountsSelector {
         AccountsSelector {
             accounts: HashSet::default(),
             owners: HashSet::default(),
+            rules: Vec::default(),
             select_all_accounts: true,
+            exclude_accounts: HashSet::default(),
+            exclude_owners: HashSet::default(),
+            min_lamports: None,
+            max_lamports: None,
         }
     }
 
-    pub fn new(accounts: &[String], owners: &[String]) -> Self {
+    pub fn new(
+        accounts: &[String],
+        owners: &[String],
+        rules: &[AccountsSelectorRuleConfig],
+    ) -> Self {
         info!(
-            "Creating AccountsSelector from accounts: {:?}, owners: {:?}",
-            accounts, owners
+            "Creating AccountsSelector from accounts: {:?}, owners: {:?}, rules: {:?}",
+            accounts, owners, rules
         );
 
         let select_all_accounts = accounts.iter().any(|key| key == "*");
@@ -27,7 +162,12 @@ impl AccountsSelector {
             return AccountsSelector {
                 accounts: HashSet::default(),
                 owners: HashSet::default(),
+                rules: Vec::default(),
                 select_all_accounts,
+                exclude_accounts: HashSet::default(),
+                exclude_owners: HashSet::default(),
+                min_lamports: None,
+                max_lamports: None,
             };
         }
         let accounts = accounts
@@ -38,20 +178,157 @@ impl AccountsSelector {
             .iter()
             .map(|key| bs58::decode(key).into_vec().unwrap())
             .collect();
+        let rules = rules.iter().map(AccountsSelectorRule::new).collect();
         AccountsSelector {
             accounts,
             owners,
+            rules,
             select_all_accounts,
+            exclude_accounts: HashSet::default(),
+            exclude_owners: HashSet::default(),
+            min_lamports: None,
+            max_lamports: None,
         }
     }
 
-    pub fn is_account_selected(&self, account: &[u8], owner: &[u8]) -> bool {
-        self.select_all_accounts || self.accounts.contains(account) || self.owners.contains(owner)
+    /// Create the selector of a section in the format of the `accounts_selector`, along
+    /// with its preset and its exclusions.
+    pub fn from_config(config: &AccountsSelectorConfig) -> Self {
+        Self::new(
+            config.accounts.as_deref().unwrap_or_default(),
+            &owners_with_preset(
+                config.owners.as_deref().unwrap_or_default(),
+                config.preset.as_deref(),
+            ),
+            config.rules.as_deref().unwrap_or_default(),
+        )
+        .with_exclusions(
+            config.exclude_accounts.as_deref().unwrap_or_default(),
+            config.exclude_owners.as_deref().unwrap_or_default(),
+        )
+        .with_lamports_range(config.min_lamports, config.max_lamports)
+    }
+
+    /// Exclude the accounts and the accounts of the owners from the selection. When
+    /// nothing else is selected, all of the other accounts are.
+    pub fn with_exclusions(
+        mut self,
+        exclude_accounts: &[String],
+        exclude_owners: &[String],
+    ) -> Self {
+        if exclude_accounts.is_empty() && exclude_owners.is_empty() {
+            return self;
+        }
+        info!(
+            "Excluding from AccountsSelector the accounts: {:?}, owners: {:?}",
+            exclude_accounts, exclude_owners
+        );
+        if !self.is_enabled() {
+            self.select_all_accounts = true;
+        }
+        self.exclude_accounts = exclude_accounts
+            .iter()
+            .map(|key| bs58::decode(key).into_vec().unwrap())
+            .collect();
+        self.exclude_owners = exclude_owners
+            .iter()
+            .map(|key| bs58::decode(key).into_vec().unwrap())
+            .collect();
+        self
+    }
+
+    /// Select only the accounts whose lamports are within the inclusive bounds, such as
+    /// to leave out the dust accounts. The closed accounts, of 0 lamports, are still
+    /// selected for the closing of the accounts stored to be written. When nothing else
+    /// is selected, all of the other accounts within the bounds are.
+    pub fn with_lamports_range(
+        mut self,
+        min_lamports: Option<u64>,
+        max_lamports: Option<u64>,
+    ) -> Self {
+        if min_lamports.is_none() && max_lamports.is_none() {
+            return self;
+        }
+        info!(
+            "Selecting with AccountsSelector the accounts of lamports from {:?} to {:?}",
+            min_lamports, max_lamports
+        );
+        if !self.is_enabled() {
+            self.select_all_accounts = true;
+        }
+        self.min_lamports = min_lamports;
+        self.max_lamports = max_lamports;
+        self
+    }
+
+    /// Check if the lamports of the account are within the bounds. The closed accounts,
+    /// of 0 lamports, always are.
+    pub fn is_lamports_selected(&self, lamports: u64) -> bool {
+        lamports == 0
+            || (self.min_lamports.is_none_or(|min| lamports >= min)
+                && self.max_lamports.is_none_or(|max| lamports <= max))
+    }
+
+    /// Check if the account is selected, regardless of the bounds of the lamports
+    pub fn is_account_selected(&self, account: &[u8], owner: &[u8], data: &[u8]) -> bool {
+        if self.exclude_accounts.contains(account) || self.exclude_owners.contains(owner) {
+            return false;
+        }
+        self.select_all_accounts
+            || self.accounts.contains(account)
+            || self.owners.contains(owner)
+            || self
+                .rules
+                .iter()
+                .any(|rule| rule.is_account_selected(owner, data))
+    }
+
+    /// Check if the account is selected, its lamports within the bounds. An account
+    /// whose lamports fall out of the bounds is no longer selected, so its last state
+    /// stored within them is kept.
+    pub fn is_account_selected_with_lamports(
+        &self,
+        account: &[u8],
+        owner: &[u8],
+        lamports: u64,
+        data: &[u8],
+    ) -> bool {
+        self.is_lamports_selected(lamports) && self.is_account_selected(account, owner, data)
+    }
+
+    /// The conditions of the selector selecting the account, '*' for all the accounts,
+    /// 'accounts' for the accounts listed, 'owner:<owner>' for the owner listed and
+    /// 'rules[<index>]' for each rule satisfied. Empty when the account is not selected.
+    pub fn matched_rules(&self, account: &[u8], owner: &[u8], data: &[u8]) -> Vec<String> {
+        if self.exclude_accounts.contains(account) || self.exclude_owners.contains(owner) {
+            return Vec::default();
+        }
+        if self.select_all_accounts {
+            return vec!["*".to_string()];
+        }
+        let mut matched_rules = Vec::default();
+        if self.accounts.contains(account) {
+            matched_rules.push("accounts".to_string());
+        }
+        if self.owners.contains(owner) {
+            matched_rules.push(format!("owner:{}", bs58::encode(owner).into_string()));
+        }
+        matched_rules.extend(
+            self.rules
+                .iter()
+                .enumerate()
+                .filter(|(_, rule)| rule.is_account_selected(owner, data))
+                .map(|(index, _)| format!("rules[{}]", index)),
+        );
+        matched_rules
     }
 
     /// Check if any account is of interested at all
     pub fn is_enabled(&self) -> bool {
-        self.select_all_accounts || !self.accounts.is_empty() || !self.owners.is_empty()
+        self.select_all_accounts
+            || !self.accounts.is_empty()
+            || !self.owners.is_empty()
+            || !self.rules.is_empty()
     }
 }
 
@@ -64,11 +341,135 @@ pub(crate) mod tests {
         AccountsSelector::new(
             &["9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin".to_string()],
             &[],
+            &[],
         );
 
         AccountsSelector::new(
             &[],
             &["9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin".to_string()],
+            &[],
+        );
+    }
+
+    #[test]
+    fn test_accounts_selector_presets() {
+        for (_, owners) in ACCOUNTS_SELECTOR_PRESETS {
+            for owner in owners {
+                assert_eq!(bs58::decode(owner).into_vec().unwrap().len(), 32);
+            }
+        }
+        let owners = owners_with_preset(
+            &["9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin".to_string()],
+            Some("spl-token"),
+        );
+        assert_eq!(owners.len(), 3);
+        let selector = AccountsSelector::new(&[], &owners, &[]);
+        let token_program = bs58::decode("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")
+            .into_vec()
+            .unwrap();
+        assert!(selector.is_account_selected(&[1; 32], &token_program, &[]));
+        assert!(!selector.is_account_selected(&[1; 32], &[2; 32], &[]));
+        assert!(preset_owners("all").is_none());
+    }
+
+    #[test]
+    fn test_accounts_selector_rules() {
+        let owner = "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin";
+        let other_owner = "9oT9R5ZyRovSVnt37QvVoBttGpNqR3J7unkb567NP8k3";
+        let selector = AccountsSelector::new(
+            &[],
+            &[other_owner.to_string()],
+            &[AccountsSelectorRuleConfig {
+                owner: Some(owner.to_string()),
+                max_data_size: Some(1023),
+                discriminator: Some(bs58::encode([1, 2, 3]).into_string()),
+                ..AccountsSelectorRuleConfig::default()
+            }],
+        );
+        assert!(selector.is_enabled());
+        let owner = bs58::decode(owner).into_vec().unwrap();
+        let other_owner = bs58::decode(other_owner).into_vec().unwrap();
+
+        assert!(selector.is_account_selected(&[], &owner, &[1, 2, 3, 4]));
+        // All of the conditions of the rule must be satisfied
+        assert!(!selector.is_account_selected(&[], &owner, &[1, 2, 4]));
+        assert!(!selector.is_account_selected(&[], &owner, &[1, 2, 3, 0].repeat(256)));
+        assert!(!selector.is_account_selected(&[], &[0; 32], &[1, 2, 3]));
+        // The owners are still selected independently of the rules
+        assert!(selector.is_account_selected(&[], &other_owner, &[]));
+
+        assert_eq!(
+            selector.matched_rules(&[], &owner, &[1, 2, 3, 4]),
+            vec!["rules[0]".to_string()]
+        );
+        assert_eq!(
+            selector.matched_rules(&[], &other_owner, &[]),
+            vec![format!(
+                "owner:{}",
+                bs58::encode(&other_owner).into_string()
+            )]
+        );
+        assert!(selector.matched_rules(&[], &[0; 32], &[1, 2, 3]).is_empty());
+    }
+
+    #[test]
+    fn test_accounts_selector_exclusions() {
+        let hot_owner = "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin";
+        let hot_account = "9oT9R5ZyRovSVnt37QvVoBttGpNqR3J7unkb567NP8k3";
+        let selector = AccountsSelector::new(&["*".to_string()], &[], &[])
+            .with_exclusions(&[hot_account.to_string()], &[hot_owner.to_string()]);
+        let hot_owner = bs58::decode(hot_owner).into_vec().unwrap();
+        let hot_account = bs58::decode(hot_account).into_vec().unwrap();
+        assert!(selector.is_account_selected(&[1; 32], &[2; 32], &[]));
+        assert!(!selector.is_account_selected(&[1; 32], &hot_owner, &[]));
+        assert!(!selector.is_account_selected(&hot_account, &[2; 32], &[]));
+        assert_eq!(
+            selector.matched_rules(&[1; 32], &[2; 32], &[]),
+            vec!["*".to_string()]
         );
+        assert!(selector
+            .matched_rules(&hot_account, &[2; 32], &[])
+            .is_empty());
+
+        // The exclusions take precedence over the accounts selected explicitly
+        let selector = AccountsSelector::new(&[], &[bs58::encode(&hot_owner).into_string()], &[])
+            .with_exclusions(&[bs58::encode(&hot_account).into_string()], &[]);
+        assert!(selector.is_account_selected(&[1; 32], &hot_owner, &[]));
+        assert!(!selector.is_account_selected(&hot_account, &hot_owner, &[]));
+        assert!(!selector.is_account_selected(&[1; 32], &[2; 32], &[]));
+
+        // The exclusions alone select all of the other accounts
+        let selector = AccountsSelector::new(&[], &[], &[])
+            .with_exclusions(&[], &[bs58::encode(&hot_owner).into_string()]);
+        assert!(selector.is_enabled());
+        assert!(selector.is_account_selected(&[1; 32], &[2; 32], &[]));
+        assert!(!selector.is_account_selected(&[1; 32], &hot_owner, &[]));
+        assert!(!AccountsSelector::new(&[], &[], &[])
+            .with_exclusions(&[], &[])
+            .is_enabled());
+    }
+
+    #[test]
+    fn test_accounts_selector_lamports_range() {
+        let owner = bs58::decode("9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin")
+            .into_vec()
+            .unwrap();
+        let selector = AccountsSelector::new(&[], &[bs58::encode(&owner).into_string()], &[])
+            .with_lamports_range(Some(1000), Some(2000));
+        assert!(selector.is_account_selected_with_lamports(&[], &owner, 1000, &[]));
+        assert!(selector.is_account_selected_with_lamports(&[], &owner, 2000, &[]));
+        assert!(!selector.is_account_selected_with_lamports(&[], &owner, 999, &[]));
+        assert!(!selector.is_account_selected_with_lamports(&[], &owner, 2001, &[]));
+        assert!(!selector.is_account_selected_with_lamports(&[], &[2; 32], 1500, &[]));
+        // The bounds are not checked without the lamports
+        assert!(selector.is_account_selected(&[], &owner, &[]));
+        // The closed accounts are still selected
+        assert!(selector.is_account_selected_with_lamports(&[], &owner, 0, &[]));
+
+        // The bounds alone select all of the accounts within them
+        let selector = AccountsSelector::new(&[], &[], &[]).with_lamports_range(Some(1000), None);
+        assert!(selector.is_enabled());
+        assert!(selector.is_account_selected_with_lamports(&[1; 32], &[2; 32], u64::MAX, &[]));
+        assert!(!selector.is_account_selected_with_lamports(&[1; 32], &[2; 32], 999, &[]));
     }
 }
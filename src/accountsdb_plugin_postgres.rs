@@ -1,13 +1,26 @@
 /// Main entry for the PostgreSQL plugin
 use {
     crate::{
-        accounts_selector::AccountsSelector,
-        postgres_client::{ParallelPostgresClient, PostgresClientBuilder},
+        account_sampler::AccountSampler,
+        accounts_selector::{owners_with_preset, AccountsSelector},
+        config_include::load_config_file,
+        config_validation::parse_config,
+        geyser_interface,
+        notification_capture::NotificationCapture,
+        postgres_client::{
+            PostgresClientBuilder, ShardedPostgresClient, Tunnel, DEFAULT_SKIP_STARTUP_ACCOUNTS,
+            DEFAULT_STORE_BLOCK_JSON, DEFAULT_STORE_ENTRIES, DEFAULT_STORE_SLOT_ECONOMICS,
+            DEFAULT_STORE_VOTE_ACTIVITY, DEFAULT_UNLOAD_AT_STOP_SLOT,
+        },
+        selector_dry_run::SelectorDryRun,
+        selector_reload::SelectorReloader,
+        slot_range::SlotRange,
+        sysvar_sampler::SysvarSampler,
         transaction_selector::TransactionSelector,
     },
     agave_geyser_plugin_interface::geyser_plugin_interface::{
         GeyserPlugin, GeyserPluginError, ReplicaAccountInfoVersions, ReplicaBlockInfoVersions,
-        ReplicaTransactionInfoVersions, Result, SlotStatus,
+        ReplicaEntryInfoVersions, ReplicaTransactionInfoVersions, Result, SlotStatus,
     },
     bs58,
     log::*,
@@ -15,17 +28,84 @@ use {
     serde_json,
     solana_measure::measure::Measure,
     solana_metrics::*,
-    std::{fs::File, io::Read},
+    std::{
+        collections::BTreeMap,
+        path::Path,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, RwLock,
+        },
+        thread::JoinHandle,
+    },
     thiserror::Error,
 };
 
+/// The selectors of the accounts and the transactions, swapped as a whole when reloaded
 #[derive(Default)]
-pub struct AccountsDbPluginPostgres {
-    client: Option<ParallelPostgresClient>,
+pub(crate) struct Selectors {
     accounts_selector: Option<AccountsSelector>,
+    /// The accounts selector during the startup, the accounts_selector is used when not set
+    startup_accounts_selector: Option<AccountsSelector>,
     transaction_selector: Option<TransactionSelector>,
 }
 
+impl Selectors {
+    pub(crate) fn from_config(config: &serde_json::Value) -> Self {
+        Self {
+            accounts_selector: Some(
+                AccountsDbPluginPostgres::create_accounts_selector_from_config(config),
+            ),
+            startup_accounts_selector:
+                AccountsDbPluginPostgres::create_startup_accounts_selector_from_config(config),
+            transaction_selector: Some(
+                AccountsDbPluginPostgres::create_transaction_selector_from_config(config),
+            ),
+        }
+    }
+
+    /// Get the accounts selector for the startup or the live account updates
+    pub(crate) fn accounts_selector(&self, is_startup: bool) -> Option<&AccountsSelector> {
+        if is_startup {
+            self.startup_accounts_selector
+                .as_ref()
+                .or(self.accounts_selector.as_ref())
+        } else {
+            self.accounts_selector.as_ref()
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct AccountsDbPluginPostgres {
+    client: Option<ShardedPostgresClient>,
+    /// The selectors, swapped by the selector reloader when the config file changes
+    selectors: Arc<RwLock<Arc<Selectors>>>,
+    /// Set to stop the selector reloader when the plugin is unloaded
+    exit_selector_reloader: Arc<AtomicBool>,
+    selector_reloader: Option<JoinHandle<()>>,
+    store_vote_activity: bool,
+    slot_range: SlotRange,
+    unload_at_stop_slot: bool,
+    skip_startup_accounts: bool,
+    sysvar_sampler: SysvarSampler,
+    account_sampler: AccountSampler,
+    /// Set when the notifications selected are captured into the capture file
+    notification_capture: Option<NotificationCapture>,
+    /// Set during the dry run of the selectors, nothing is written then
+    selector_dry_run: Option<SelectorDryRun>,
+    /// Indicates if to buffer all the transactions for the block JSON
+    store_block_json: bool,
+    /// Indicates if to buffer the fees of all the transactions for the slot economics
+    store_slot_economics: bool,
+    store_entries: bool,
+    /// Indicates if to store the block metadata, not stored by the minimal footprint
+    store_block_metadata: bool,
+    /// Set when the plugin has stopped writing at the stop slot
+    is_stopped: AtomicBool,
+    /// The tunnel the connections to the database are made through, when configured
+    tunnel: Option<Tunnel>,
+}
+
 impl std::fmt::Debug for AccountsDbPluginPostgres {
     fn fmt(&self, _: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         Ok(())
@@ -33,20 +113,700 @@ impl std::fmt::Debug for AccountsDbPluginPostgres {
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct AccountsDbPluginPostgresConfig {
+    /// The path of the plugin library, used by the validator to load the plugin
+    pub libpath: Option<String>,
+    /// The name of the plugin, used by the validator
+    pub name: Option<String>,
     pub host: Option<String>,
     pub user: Option<String>,
     pub port: Option<u16>,
     pub connection_str: Option<String>,
+    /// The databases the updates are spread over, each written by its own workers, the
+    /// updates are written to the one database of the connection settings when not set
+    pub sharding: Option<ShardingConfig>,
     pub threads: Option<usize>,
     pub batch_size: Option<usize>,
+    /// Indicates if the writes of all the accounts and the transactions are accepted
+    /// with few threads or small batches
+    pub acknowledge_high_volume: Option<bool>,
     pub panic_on_db_errors: Option<bool>,
     /// Indicates if to store historical data for accounts
     pub store_account_historical_data: Option<bool>,
     pub use_ssl: Option<bool>,
+    /// The verification of the TLS connections, taking precedence over use_ssl
+    pub ssl_mode: Option<SslMode>,
     pub server_ca: Option<String>,
     pub client_cert: Option<String>,
     pub client_key: Option<String>,
+    /// Indicates if to summarize vote transactions into the vote_activity table
+    pub store_vote_activity: Option<bool>,
+    /// The number of rooted slots between the verifications of the stored block chain,
+    /// the verification is disabled when not set
+    pub block_chain_verification_interval: Option<u64>,
+    /// How the slots of the abandoned forks are cleaned up, they are left as they are
+    /// when not set
+    pub fork_cleanup: Option<ForkCleanup>,
+    /// The rooted slots between the cleanups of the abandoned forks
+    pub fork_cleanup_interval_slots: Option<u64>,
+    /// The watchlist of accounts written through a dedicated low-latency connection
+    pub priority_accounts: Option<AccountsSelectorConfig>,
+    /// The accounts whose every version is kept in the account_version table
+    pub account_versions_selector: Option<AccountsSelectorConfig>,
+    /// The identity of the validator, used to detect another plugin instance writing
+    /// for the same validator
+    pub validator_identity: Option<String>,
+    /// Indicates if to fail the loading when another plugin instance is detected
+    pub fail_on_duplicate_plugin_instance: Option<bool>,
+    /// Indicates if to record the changes of the settings altering the tables and
+    /// columns written into the config_history table
+    pub record_config_history: Option<bool>,
+    pub accounts_selector: Option<AccountsSelectorConfig>,
+    pub startup_accounts_selector: Option<AccountsSelectorConfig>,
+    pub transaction_selector: Option<TransactionSelectorConfig>,
+    /// The static columns and their values appended to the inserts into the account,
+    /// account_audit, slot, transaction, block, vote_activity and entry tables
+    pub static_columns: Option<BTreeMap<String, String>>,
+    /// The columns and the SQL expressions of their values, computed from the account
+    /// columns, appended to every upsert into the account table
+    pub computed_columns: Option<BTreeMap<String, String>>,
+    /// The owners of the accounts whose discriminators, the first 8 bytes of the data,
+    /// are stored in the discriminator column of the account table
+    pub discriminator_owners: Option<Vec<String>>,
+    /// The layout descriptors the data of the accounts of the owners are decoded with
+    /// into the data_json column of the account table, keyed by the owner
+    pub account_data_decoders: Option<BTreeMap<String, AccountDataDecoderConfig>>,
+    /// How the data of the accounts is compressed with zstd before it is written, the
+    /// data is written as it is when not set
+    pub account_data_compression: Option<AccountDataCompressionConfig>,
+    /// How the accounts of the SPL Token programs are stored
+    pub token_account_storage: Option<TokenAccountStorage>,
+    /// The secondary indexes of the token accounts maintained in the index tables
+    pub token_secondary_indexes: Option<Vec<TokenSecondaryIndex>>,
+    /// The first slot of the notifications to store, inclusive
+    pub start_slot: Option<u64>,
+    /// The last slot of the notifications to store, inclusive
+    pub stop_slot: Option<u64>,
+    /// Indicates if to stop writing to the database once the stop slot is rooted
+    pub unload_at_stop_slot: Option<bool>,
+    /// Indicates if to drop the account notifications during the startup
+    pub skip_startup_accounts: Option<bool>,
+    /// Indicates if to drop the secondary indexes of the account table during the
+    /// startup, recreating them at the end of the startup
+    pub manage_indexes_on_startup: Option<bool>,
+    /// How the transaction signatures are stored
+    pub signature_storage: Option<SignatureStorage>,
+    /// Indicates if to store the getBlock-shaped JSON of the blocks in the block table
+    pub store_block_json: Option<bool>,
+    /// Indicates if to store the getTransaction-shaped JSON of the transactions in the
+    /// transaction table
+    pub store_transaction_json: Option<bool>,
+    /// Indicates if to index the transactions by the addresses they mention in the
+    /// transaction_address table
+    pub store_transaction_addresses: Option<bool>,
+    /// Store one in this many slots of the updates of the sysvars updated every slot
+    pub sysvar_sampling_rate: Option<u64>,
+    /// The percentage of the accounts to store, sampled by the hash of the pubkey
+    pub account_sampling_percentage: Option<f64>,
+    /// The file the notifications selected are captured into, as the length-delimited
+    /// SubscribeUpdate messages of the Yellowstone gRPC protobuf definitions
+    pub capture_file: Option<String>,
+    /// The seconds the selectors are run against the account updates without writing
+    /// them, counting the updates selected into the selector_dry_run_stats table, the
+    /// updates are written when not set
+    pub selector_dry_run_secs: Option<u64>,
+    /// Indicates if to extract the SOL transfers of the System Program instructions
+    /// into the sol_transfer table
+    pub store_sol_transfers: Option<bool>,
+    /// Indicates if to extract the memos of the SPL Memo instructions into the
+    /// transaction_memo table
+    pub store_transaction_memos: Option<bool>,
+    /// The programs, named as in the 'jsonParsed' encoding, whose top level
+    /// instructions are parsed into the transaction_instruction table
+    pub parsed_instruction_programs: Option<Vec<String>>,
+    /// Indicates if to store the balances, the token balances, the rewards and the log
+    /// messages of the transactions into the transaction_balance,
+    /// transaction_token_balance, transaction_reward and transaction_log tables
+    pub store_normalized_transaction_meta: Option<bool>,
+    /// Indicates if to store the fee burn and the rent collected per slot in the
+    /// slot_economics table
+    pub store_slot_economics: Option<bool>,
+    /// Indicates if to store all the rewards of the blocks in the block_reward table
+    pub store_block_rewards: Option<bool>,
+    /// Indicates if to store the fee, rent and voting rewards of the validators in the
+    /// validator_reward table
+    pub store_validator_rewards: Option<bool>,
+    /// Indicates if to store the staking rewards of the stake accounts in the
+    /// stake_reward table
+    pub store_stake_rewards: Option<bool>,
+    /// Indicates if to store the entries of the blocks in the entry table
+    pub store_entries: Option<bool>,
+    /// The max number of the entries of a slot written by a statement
+    pub entry_batch_size: Option<usize>,
+    /// The percentage of a core each worker may use, the CPU usage is not capped when
+    /// not set
+    pub worker_cpu_budget_percentage: Option<u64>,
+    /// What a worker over its CPU budget does with the low-priority work
+    pub cpu_budget_exceeded_action: Option<CpuBudgetAction>,
+    /// The max number of the account, transaction and slot updates queued together to
+    /// a worker written in one database transaction, each update is committed on its
+    /// own when not set
+    pub statement_batch_size: Option<usize>,
+    /// Indicates if to commit the transactions of each slot together once its block
+    /// metadata is notified, followed by the slot_commit row of the slot
+    pub commit_per_slot: Option<bool>,
+    /// The max number of the transactions of a slot committed per database transaction
+    /// with the commit_per_slot
+    pub slot_commit_chunk_size: Option<usize>,
+    /// The sizes and the max delays of the statement batches per kind of update, the
+    /// statement_batch_size applies to the kinds without their own
+    pub batch: Option<BatchConfig>,
+    /// The statement timeout in milliseconds of the writes, the one of the session is
+    /// kept when not set
+    pub statement_timeout_ms: Option<u64>,
+    /// The statement timeouts in milliseconds of the writes to the tables, overriding
+    /// the statement_timeout_ms
+    pub table_statement_timeouts_ms: Option<BTreeMap<String, u64>>,
+    /// The endpoint serving the Prometheus metrics of the plugin, the metrics are not
+    /// served when not set
+    pub metrics: Option<MetricsConfig>,
+    /// The depth of the queue of the workers from which the account updates block the
+    /// validator, the updates are buffered up to the capacity of the queue when not set
+    pub backpressure_queue_threshold: Option<usize>,
+    /// The longest an account update blocks for past the queue threshold
+    pub backpressure_max_block_ms: Option<u64>,
+    /// The notification channel the downstream consumers signal to slow down or to
+    /// pause on, tightening the backpressure
+    pub flow_control_channel: Option<String>,
+    /// The capacity of the queue of the workers
+    pub queue_capacity: Option<usize>,
+    /// What is done with the work items notified while the queue is full
+    pub queue_overflow_policy: Option<QueueOverflowPolicy>,
+    /// The steps of the degradation walked as the backlog of the queue grows and
+    /// reversed as it drains
+    pub degradation_ladder: Option<DegradationLadderConfig>,
+    /// The interval in seconds of checking the config file for the selectors changed,
+    /// the selectors are only loaded along with the plugin when not set
+    pub selector_reload_interval_secs: Option<u64>,
+    /// The slots per epoch of the cluster, for the epochs of the validator and the
+    /// stake rewards
+    pub slots_per_epoch: Option<u64>,
+    /// Indicates if to write a barrier into the epoch_barrier table before the first
+    /// slot of each epoch, once the updates queued before are committed
+    pub write_epoch_barriers: Option<bool>,
+    /// The SQL executed after the writes of a table are committed, keyed by the table
+    pub post_commit_hooks: Option<BTreeMap<String, String>>,
+    /// The channels notified of the rows written with pg_notify, keyed by the update
+    /// type
+    pub notify_channels: Option<BTreeMap<String, String>>,
+    /// Indicates if the schema triggers copy the account updates into the account_audit
+    /// and owner_change tables, the plugin writes the copies otherwise
+    pub use_schema_triggers: Option<bool>,
+    /// The layout of the account rows, the data being stored along with the other
+    /// columns when not set
+    pub account_layout: Option<AccountLayout>,
+    /// The preset of what is stored, all the notifications selected when not set
+    pub footprint: Option<Footprint>,
+    /// The slots after which the accounts not updated are moved into the account_cold
+    /// table, the accounts are not tiered when not set
+    pub account_cold_tier_slots: Option<u64>,
+    /// The tablespace of the account_cold table, the default tablespace when not set
+    pub account_cold_tier_tablespace: Option<String>,
+    /// The storage engine of the database, plain PostgreSQL tables when not set
+    pub storage_engine: Option<StorageEngine>,
+    /// The slots of the chunks of the hypertables with the TimescaleDB storage engine
+    pub hypertable_chunk_slots: Option<u64>,
+    /// The slots after which the chunks of the hypertables are compressed, they are not
+    /// compressed when not set
+    pub hypertable_compress_after_slots: Option<u64>,
+    /// The extension the tables are distributed across the nodes with, the tables are
+    /// not distributed when not set
+    pub distributed: Option<Distributed>,
+    /// The shards of the tables distributed, the default of Citus when not set
+    pub distributed_shard_count: Option<u64>,
+    /// The idle time in seconds after which the connections of the workers are pinged,
+    /// the keepalive is disabled when not set
+    pub connection_keepalive_interval_secs: Option<u64>,
+    /// The max backoff in seconds between the attempts of the workers reconnecting the
+    /// dropped connections, the reconnection is disabled when not set
+    pub reconnect_max_backoff_secs: Option<u64>,
+    /// The max number of the updates buffered per worker while reconnecting
+    pub reconnect_buffer_size: Option<usize>,
+    /// The seconds the updates committed by a worker are kept to be written again once
+    /// reconnected, none are replayed when not set
+    pub replay_window_secs: Option<u64>,
+    /// The max number of the updates kept per worker within the replay window
+    pub replay_window_size: Option<usize>,
+    /// The connection string of the read-write standby the workers fail over to when
+    /// the primary is unreachable, there is no failover when not set
+    pub fallback_connection_str: Option<String>,
+    /// The interval in seconds of the health checks of the connections of the workers
+    pub health_check_interval_secs: Option<u64>,
+    /// The seconds the primary is unreachable for before the workers fail over
+    pub failover_after_secs: Option<u64>,
+    /// The tunnel the connections to the database are made through, when the database
+    /// is not directly reachable
+    pub tunnel: Option<TunnelConfig>,
+    /// The provider of the password of the database, kept out of the config file
+    pub credentials: Option<CredentialsConfig>,
+    /// The seconds after the processing of a slot within which all its streams are
+    /// expected to be committed, the SLA is not monitored when not set
+    pub ingest_sla_secs: Option<u64>,
+    /// Controls if to store on the slot rows the transactions selected received and
+    /// committed, the default is false
+    pub store_slot_transaction_counts: Option<bool>,
+    /// Controls if to store on the slot rows the checksums of the account and
+    /// transaction rows ingested, the default is false
+    pub store_slot_checksums: Option<bool>,
+    /// The interval in seconds of reporting the rows and the bytes written per table,
+    /// the reporting is disabled when not set
+    pub write_stats_interval_secs: Option<u64>,
+    /// The percentage of the account and transaction rows read back after they are
+    /// written to verify them, the writes are not verified when not set
+    pub write_verification_percentage: Option<f64>,
+    /// The connection string of the read replica the writes are read back from and
+    /// the block chain is verified against, the primary when not set
+    pub verification_connection_str: Option<String>,
+    /// The seconds the verifications wait for the replica to replay the writes
+    pub verification_max_lag_secs: Option<u64>,
+    /// The seconds the workers are given to write the updates queued when the plugin is
+    /// unloaded, 30 by default, they are not drained when zero
+    pub shutdown_drain_timeout_secs: Option<u64>,
+    /// The file the updates left unwritten on the unload are spilled to and replayed
+    /// from on the next load, they are dropped when not set
+    pub spill_path: Option<String>,
+    /// The interval in seconds of running ANALYZE on the account and transaction
+    /// tables written to, not scheduled on a cadence when not set
+    pub analyze_interval_secs: Option<u64>,
+    /// The count of the rows written to the account or transaction table after which
+    /// ANALYZE is run on it, not scheduled on a row count when not set
+    pub analyze_row_count_delta: Option<u64>,
+    /// The directory the account history is copied to before it is deleted, by the
+    /// archive_account_audit tool and by the retention of the account_audit partitions
+    pub account_audit_archive_path: Option<String>,
+    /// The interval in seconds of reporting the indexes and the partitioning advised
+    /// from pg_stat_statements to the index_advice table, not advised when not set
+    pub index_advisor_interval_secs: Option<u64>,
+    /// The calls of a query for it to be advised for, 100 by default
+    pub index_advisor_min_calls: Option<u64>,
+    /// The interval in seconds of the checks of the schema against the schema expected
+    /// by the plugin, the schema is not checked when not set
+    pub schema_drift_check_interval_secs: Option<u64>,
+    /// The cluster of the validator selecting its section of the clusters in the config
+    /// file, the section is merged into the config when it is loaded
+    pub cluster: Option<String>,
+    /// The schema of the tables written to, the default search path of the user when
+    /// not set
+    pub schema: Option<String>,
+    /// Indicates if to create the schema along with its tables when the account table
+    /// is missing from it
+    pub create_schema: Option<bool>,
+    /// Indicates if to apply the migrations of the schema older than the version the
+    /// plugin writes to when it is loaded, the loading fails on such a schema when not set
+    pub auto_migrate: Option<bool>,
+    /// The directory or the URL of the bundle of the migrations applied in place of the
+    /// ones compiled into the plugin
+    pub schema_source: Option<String>,
+    /// The time in seconds the loading of the plugin waits for the database and the
+    /// workers to connect, the loading fails on the first failed connection when not set
+    pub startup_wait_secs: Option<u64>,
+    /// The JSON or CSV file mapping the program ids to the names and the categories
+    /// stored in the program_label table
+    pub program_labels_file: Option<String>,
+    /// The tags of the addresses keyed by the address, stored in the address_tag table
+    pub address_tags: Option<BTreeMap<String, Vec<String>>>,
+    /// The attempts of the account updates outside of the startup and of the
+    /// transactions before they are written to the dead_letter table, the dead letter
+    /// is disabled when not set. The other writes are not retried nor dead lettered.
+    pub dead_letter_attempts: Option<u64>,
+    /// The interval in seconds of the polling of the transaction_allowlist table, the
+    /// allowlist is disabled when not set
+    pub transaction_allowlist_poll_interval_secs: Option<u64>,
+    /// The slots up to the highest slot stored at the load in which the writes already
+    /// stored before a restart are ignored, the dedup window is disabled when not set
+    pub dedup_window_slots: Option<u64>,
+    /// The client writing the updates, the blocking workers when not set
+    pub client: Option<ClientKind>,
+    /// The connections of the pool of the async client
+    pub async_pool_size: Option<usize>,
+    /// The time in milliseconds a write of the async client is given before it is
+    /// cancelled
+    pub async_request_timeout_ms: Option<u64>,
+    /// What is done when the validator restarts from a snapshot older than the highest
+    /// rooted slot stored
+    pub snapshot_rewind_action: Option<SnapshotRewindAction>,
+    /// The slots of the account history kept in the partitioned account_audit table,
+    /// all the history is kept when not set
+    pub account_audit_retention_slots: Option<u64>,
+    /// The slots of each partition of the account_audit table created by the plugin
+    pub account_audit_partition_slots: Option<u64>,
+    /// The slots of the account history kept per owner, overriding the
+    /// account_audit_retention_slots for the accounts of the owner
+    pub account_audit_retention_overrides: Option<BTreeMap<String, u64>>,
+    /// The TimescaleDB continuous aggregates refreshed as the rooted slot progresses
+    pub continuous_aggregates: Option<Vec<String>>,
+    /// The rooted slots between the refreshes of the continuous aggregates
+    pub continuous_aggregate_refresh_slots: Option<u64>,
+    /// The highest slot stored at the load, resolved by the plugin for the dedup window
+    #[serde(skip)]
+    pub dedup_until_slot: Option<u64>,
+    /// The local port of the tunnel the connections are made to, set by the plugin once
+    /// the tunnel is started
+    #[serde(skip)]
+    pub tunnel_local_port: Option<u16>,
+}
+
+impl AccountsDbPluginPostgresConfig {
+    /// Load the config file the way the plugin does, with the included files merged, the
+    /// section of the cluster selected and the fields validated, for the tools writing
+    /// to the database of the plugin outside of a validator.
+    pub fn load(config_file: &Path) -> Result<Self> {
+        parse_config(load_config_file(config_file)?)
+    }
+}
+
+/// The TLS of the connections, after the sslmode of libpq
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SslMode {
+    /// The connections are not encrypted
+    Disable,
+    /// The connections are encrypted without verifying the server certificate
+    Require,
+    /// The server certificate is verified, but not its hostname
+    VerifyCa,
+    /// The server certificate and its hostname are verified
+    VerifyFull,
+}
+
+/// The storage of the token accounts and mints of the SPL Token and Token-2022 programs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenAccountStorage {
+    /// The accounts are stored as raw rows of the account table
+    #[default]
+    Raw,
+    /// The accounts are decoded into the spl_token_account and spl_token_mint tables
+    /// instead of the account table
+    Decoded,
+    /// The accounts are stored as raw rows and decoded
+    Both,
+    /// The accounts are stored as raw rows, and the mints are decoded into the
+    /// spl_token_mint table too
+    Mints,
+}
+
+/// The secondary indexes of the token accounts of the SPL Token and Token-2022 programs
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenSecondaryIndex {
+    /// The token accounts by their owner, in the spl_token_owner_index table
+    SplTokenOwner,
+    /// The token accounts by their mint, in the spl_token_mint_index table
+    SplTokenMint,
+}
+
+/// What a worker over its CPU budget does with the low-priority work
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CpuBudgetAction {
+    /// The work is delayed until the CPU usage is back within the budget
+    #[default]
+    Delay,
+    /// The work is dropped
+    Shed,
+}
+
+/// What is done with the work items notified while the queue of the workers is full
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueOverflowPolicy {
+    /// The notifications block until the workers make room
+    #[default]
+    Block,
+    /// The oldest account updates and transactions queued are dropped to make room
+    DropOldest,
+    /// The account updates are dropped, the other notifications block
+    DropAccountUpdatesOnly,
+}
+
+/// What is given up by a step of the degradation ladder
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DegradationAction {
+    /// The previous versions of the accounts are no longer copied into the
+    /// account_audit table
+    DisableAudit,
+    /// The transactions are no longer queued
+    DisableTransactions,
+    /// One in every "sampling_rate" account updates is queued
+    SampleAccounts,
+    /// Neither the account updates nor the transactions are queued
+    Drop,
+}
+
+/// A step of the degradation ladder
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DegradationStepConfig {
+    /// The depth of the queue of the workers from which the step is entered once
+    /// sustained
+    pub queue_depth: usize,
+    pub action: DegradationAction,
+    /// The account updates of which one is queued by the 'sample_accounts' action
+    pub sampling_rate: Option<u64>,
+}
+
+/// The degradation ladder, its steps ordered by their queue depths
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DegradationLadderConfig {
+    pub steps: Vec<DegradationStepConfig>,
+    /// The seconds the depth of the queue must be sustained for before a step is
+    /// entered or left
+    pub sustain_secs: Option<u64>,
+}
+
+/// How the slots of the abandoned forks are cleaned up
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForkCleanup {
+    /// The slots are marked as abandoned in the slot table
+    Mark,
+    /// The slots are marked as abandoned, and their rows are deleted
+    Purge,
+}
+
+/// What is done on the restart of the validator from a snapshot older than the slots stored
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotRewindAction {
+    /// The rewind is logged as an error and reported as an error datapoint
+    #[default]
+    Alert,
+    /// The rewind is alerted, and the rows of the slots after the snapshot are purged
+    /// for the replay to write them again
+    Reconcile,
+}
+
+/// How the updates are spread over the databases
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShardingStrategy {
+    /// The accounts are spread by the hash of their pubkeys, the other updates are
+    /// routed per kind
+    #[default]
+    PubkeyHash,
+    /// Each kind of update is routed to its database
+    ByKind,
+}
+
+/// The storage engine of the database
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageEngine {
+    /// The tables are plain PostgreSQL tables
+    #[default]
+    Postgres,
+    /// The account_audit, transaction and block tables are TimescaleDB hypertables
+    #[serde(rename = "timescaledb")]
+    TimescaleDb,
+}
+
+/// The extension the tables are distributed with
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Distributed {
+    /// The tables are Citus distributed tables
+    Citus,
+}
+
+/// The layout of the account rows
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountLayout {
+    /// The account table stores the data along with the other columns
+    #[default]
+    Wide,
+    /// The account table stores the columns other than the data, which is stored in
+    /// the account_data table
+    Split,
+}
+
+/// The preset of what the plugin stores
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Footprint {
+    /// The notifications selected are stored as configured
+    #[default]
+    Full,
+    /// Only the latest state of the accounts selected and the slot statuses are
+    /// stored, with the account updates of a batch coalesced, for an accounts cache
+    Minimal,
+}
+
+/// The storage of the transaction signatures in the transaction table
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureStorage {
+    /// The signature column stores the signature bytes
+    #[default]
+    Bytea,
+    /// The signature column stores the base58 encoded signature
+    Base58,
+    /// The signature column stores the signature bytes, and the signature_text
+    /// column the base58 encoded signature
+    Both,
+}
+
+/// The client writing the updates to the database
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientKind {
+    /// The worker threads write the updates on their blocking connections
+    #[default]
+    Blocking,
+    /// The updates are written concurrently by the tasks of a tokio runtime through a
+    /// pool of tokio-postgres connections
+    Async,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccountsSelectorConfig {
+    pub accounts: Option<Vec<String>>,
+    pub owners: Option<Vec<String>>,
+    /// The preset expanding to the curated owners of an ecosystem, in addition to the
+    /// owners
+    pub preset: Option<String>,
+    pub rules: Option<Vec<AccountsSelectorRuleConfig>>,
+    /// The base58 encoded accounts never selected
+    pub exclude_accounts: Option<Vec<String>>,
+    /// The base58 encoded owners whose accounts are never selected
+    pub exclude_owners: Option<Vec<String>>,
+    /// The inclusive lower bound of the lamports of the accounts selected
+    pub min_lamports: Option<u64>,
+    /// The inclusive upper bound of the lamports of the accounts selected
+    pub max_lamports: Option<u64>,
+}
+
+/// A compound rule of the accounts selector, selecting the accounts satisfying all of
+/// the conditions set
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccountsSelectorRuleConfig {
+    /// The base58 encoded owner
+    pub owner: Option<String>,
+    /// The inclusive lower bound of the data size in bytes
+    pub min_data_size: Option<usize>,
+    /// The inclusive upper bound of the data size in bytes
+    pub max_data_size: Option<usize>,
+    /// The base58 encoded bytes the data starts with, such as an Anchor discriminator
+    pub discriminator: Option<String>,
+}
+
+/// The layout descriptor of the accounts of an owner, either of the paths being set
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccountDataDecoderConfig {
+    /// The path of the Anchor IDL JSON of the program
+    pub idl: Option<String>,
+    /// The path of the borsh schema file, the accounts and the types in the format of
+    /// the IDL, the accounts without a discriminator being decoded from the first byte
+    pub borsh_schema: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransactionSelectorConfig {
+    pub mentions: Option<Vec<String>>,
+    pub all_mentions: Option<Vec<String>>,
+    /// Indicates if to leave out the vote transactions
+    pub exclude_votes: Option<bool>,
+    /// Indicates if to select only the vote transactions
+    pub include_only_votes: Option<bool>,
+    /// Indicates if to leave out the failed transactions
+    pub exclude_failed: Option<bool>,
+}
+
+/// The limits of the statement batches per kind of update
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatchConfig {
+    pub account: Option<BatchLimitConfig>,
+    pub transaction: Option<BatchLimitConfig>,
+    pub slot: Option<BatchLimitConfig>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatchLimitConfig {
+    /// The max number of the updates of a batch starting with an update of the kind
+    pub size: Option<usize>,
+    /// The longest a batch with an update of the kind waits for the updates to fill it
+    pub max_delay_ms: Option<u64>,
+}
+
+/// The databases the updates are spread over, the kinds of update are routed by the
+/// indexes of the connection strings, the first database when not set
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShardingConfig {
+    pub connection_strs: Vec<String>,
+    pub strategy: Option<ShardingStrategy>,
+    /// The database of the accounts with the by_kind strategy
+    pub account: Option<usize>,
+    /// The database of the transactions, along with the blocks and the vote activity
+    pub transaction: Option<usize>,
+    pub entry: Option<usize>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccountDataCompressionConfig {
+    /// The zstd compression level
+    pub level: Option<i32>,
+    /// The least length of the data compressed
+    pub min_bytes: Option<usize>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TunnelConfig {
+    /// The SOCKS5 proxy the connections are made through, such as "10.0.0.5:1080"
+    pub socks5_proxy: Option<String>,
+    /// The username of the SOCKS5 proxy, when it requires one
+    pub socks5_user: Option<String>,
+    /// The password of the SOCKS5 proxy, along with the username
+    pub socks5_password: Option<String>,
+    /// The SSH jump host the connections are forwarded through, such as "solana@bastion"
+    pub ssh_jump_host: Option<String>,
+    /// The SSH port of the jump host
+    pub ssh_port: Option<u16>,
+    /// The private key the ssh command authenticates to the jump host with
+    pub ssh_identity_file: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CredentialsConfig {
+    /// The environment variable holding the password
+    pub password_env: Option<String>,
+    /// The file holding the password, such as a mounted secret
+    pub password_file: Option<String>,
+    /// The command printing the password, along with its arguments
+    pub password_command: Option<Vec<String>>,
+    /// The seconds the password resolved is reused for, it is resolved on each
+    /// connection when not set
+    pub refresh_interval_secs: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// The address the metrics endpoint listens on, such as "0.0.0.0:9091"
+    pub bind: String,
 }
 
 #[derive(Error, Debug)]
@@ -59,6 +819,9 @@ pub enum AccountsDbPluginPostgresError {
 
     #[error("Error preparing data store schema. Error message: ({msg})")]
     ConfigurationError { msg: String },
+
+    #[error("Another plugin instance is running. Error message: ({msg})")]
+    DuplicateInstanceError { msg: String },
 }
 
 impl GeyserPlugin for AccountsDbPluginPostgres {
@@ -68,53 +831,693 @@ impl GeyserPlugin for AccountsDbPluginPostgres {
 
     /// Do initialization for the PostgreSQL plugin.
     ///
+    /// The config file is parsed strictly, unknown fields and values out of the supported
+    /// ranges fail the loading.
+    ///
+    /// The config file can list base config files to share across validators in the
+    /// `include` field, a path or an array of paths relative to the config file. The
+    /// included files are merged in order, and the fields of the including file take
+    /// precedence, the objects being merged field by field except the selectors like
+    /// `accounts_selector`, which are replaced as a whole.
+    ///
     /// # Format of the config file:
     /// * The `accounts_selector` section allows the user to controls accounts selections.
-    /// "accounts_selector" : {
-    ///     "accounts" : \["pubkey-1", "pubkey-2", ..., "pubkey-n"\],
-    /// }
-    /// or:
-    /// "accounts_selector" = {
-    ///     "owners" : \["pubkey-1", "pubkey-2", ..., "pubkey-m"\]
-    /// }
-    /// Accounts either satisyfing the accounts condition or owners condition will be selected.
-    /// When only owners is specified,
-    /// all accounts belonging to the owners will be streamed.
-    /// The accounts field supports wildcard to select all accounts:
-    /// "accounts_selector" : {
-    ///     "accounts" : \["*"\],
-    /// }
+    ///   ```text
+    ///   "accounts_selector" : {
+    ///       "accounts" : ["pubkey-1", "pubkey-2", ..., "pubkey-n"],
+    ///   }
+    ///   ```
+    ///   or:
+    ///   ```text
+    ///   "accounts_selector" = {
+    ///       "owners" : ["pubkey-1", "pubkey-2", ..., "pubkey-m"]
+    ///   }
+    ///   ```
+    ///   Accounts either satisyfing the accounts condition or owners condition will be selected.
+    ///   When only owners is specified,
+    ///   all accounts belonging to the owners will be streamed.
+    ///   The accounts field supports wildcard to select all accounts:
+    ///   ```text
+    ///   "accounts_selector" : {
+    ///       "accounts" : ["*"],
+    ///   }
+    ///   ```
+    ///   The `rules` field selects the accounts satisfying all of the conditions of any of the rules, the
+    ///   owner, the inclusive bounds of the data size and the base58 encoded bytes the data starts with:
+    ///   ```text
+    ///   "accounts_selector" : {
+    ///       "rules" : [{ "owner" : "pubkey-1", "max_data_size" : 1023, "discriminator" : "base58-bytes" }]
+    ///   }
+    ///   ```
+    ///   The rules are combined with the accounts and owners conditions, an account satisfying any of them
+    ///   is selected.
+    ///   The `preset` field adds the curated owners of an ecosystem to the owners, one of 'spl-token' for the
+    ///   token accounts and mints of SPL Token and Token-2022, 'all-defi-core' for the accounts of the major
+    ///   AMMs, order books and lending protocols, and 'validator-ops' for the vote, stake, validator info and
+    ///   sysvar accounts:
+    ///   ```text
+    ///   "accounts_selector" : {
+    ///       "preset" : "spl-token"
+    ///   }
+    ///   ```
+    ///   The `exclude_accounts` and `exclude_owners` fields exclude the accounts and the accounts of the
+    ///   owners, taking precedence over the other conditions. When specified alone, all of the other
+    ///   accounts are selected:
+    ///   ```text
+    ///   "accounts_selector" : {
+    ///       "exclude_owners" : ["pubkey-1", "pubkey-2"]
+    ///   }
+    ///   ```
+    ///   The `min_lamports` and `max_lamports` fields, the inclusive bounds of the lamports, exclude the
+    ///   accounts outside of the bounds, such as the dust accounts created by the airdrop spam, taking
+    ///   precedence over the other conditions. The closed accounts, of 0 lamports, are not excluded for their
+    ///   closing to be written. An account whose balance drops below `min_lamports`, or rises above
+    ///   `max_lamports`, is no longer updated and keeps its last state stored until its balance is back
+    ///   within the bounds. When specified alone, all of the accounts within the bounds are selected:
+    ///   ```text
+    ///   "accounts_selector" : {
+    ///       "owners" : ["pubkey-1"],
+    ///       "min_lamports" : 2039280
+    ///   }
+    ///   ```
+    /// * The `startup_accounts_selector` section, optional, in the same format as the `accounts_selector`,
+    ///   controls the accounts selections during the startup when the accounts are restored from the snapshot.
+    ///   When missing, the `accounts_selector` also applies to the startup.
     /// * "host", optional, specifies the PostgreSQL server.
     /// * "user", optional, specifies the PostgreSQL user.
     /// * "port", optional, specifies the PostgreSQL server's port.
     /// * "connection_str", optional, the custom PostgreSQL connection string.
-    /// Please refer to https://docs.rs/postgres/0.19.2/postgres/config/struct.Config.html for the connection configuration.
-    /// When `connection_str` is set, the values in "host", "user" and "port" are ignored. If `connection_str` is not given,
-    /// `host` and `user` must be given.
-    /// "store_account_historical_data", optional, set it to 'true', to store historical account data to account_audit
-    /// table.
+    ///   Please refer to https://docs.rs/postgres/0.19.2/postgres/config/struct.Config.html for the connection configuration.
+    ///   When `connection_str` is set, the values in "host", "user" and "port" are ignored. If `connection_str` is not given,
+    ///   `host` and `user` must be given.
+    /// * "sharding", optional, spreads the updates over several PostgreSQL databases, each written by its own
+    ///   workers, in place of the "connection_str".
+    ///   ```text
+    ///   "sharding" : {
+    ///       "connection_strs" : ["host=db-a user=solana", "host=db-b user=solana"],
+    ///       "strategy" : "pubkey_hash",
+    ///       "transaction" : 1
+    ///   }
+    ///   ```
+    ///   The "strategy" 'pubkey_hash' spreads the accounts over the databases by the hash of their pubkeys, and
+    ///   'by_kind' writes them to the database of the "account" index. The transactions, along with the blocks
+    ///   and the vote activity, are written to the database of the "transaction" index, and the entries to the
+    ///   one of the "entry" index. The slot statuses are written to all the databases. The indexes default to
+    ///   '0' and the "strategy" to 'pubkey_hash'.
+    /// * "store_account_historical_data", optional, set it to 'true', to store historical account data to account_audit
+    ///   table.
     /// * "threads" optional, specifies the number of worker threads for the plugin. A thread
-    /// maintains a PostgreSQL connection to the server. The default is '10'.
+    ///   maintains a PostgreSQL connection to the server. The default is '10'.
     /// * "batch_size" optional, specifies the batch size of bulk insert when the AccountsDb is created
-    /// from restoring a snapshot. The default is '10'.
+    ///   from restoring a snapshot. The default is '10'.
+    /// * "acknowledge_high_volume", optional, when all the accounts and all the transactions are selected, the
+    ///   whole firehose of the cluster, the loading of the plugin fails with fewer than 32 "threads" or a
+    ///   "batch_size" under 10, and warns of the write rate otherwise. Set it to 'true' when the database is
+    ///   sized for it. The default is 'false'.
     /// * "panic_on_db_errors", optional, contols if to panic when there are errors replicating data to the
-    /// PostgreSQL database. The default is 'false'.
+    ///   PostgreSQL database. The default is 'false'.
+    /// * "ssl_mode", optional, the TLS of the connections, one of 'disable', 'require' encrypting the connections
+    ///   without verifying the server certificate, 'verify-ca' verifying the server certificate against
+    ///   "server_ca", or the trusted certificates of the system when not set, and 'verify-full' verifying the
+    ///   hostname of the server certificate too. The "client_cert" and "client_key" are optional, for the servers
+    ///   authenticating the clients by certificates. The default is 'verify-ca' when "use_ssl" is 'true', and
+    ///   'disable' otherwise.
     /// * "transaction_selector", optional, controls if and what transaction to store. If this field is missing
-    /// None of the transction is stored.
-    /// "transaction_selector" : {
-    ///     "mentions" : \["pubkey-1", "pubkey-2", ..., "pubkey-n"\],
-    /// }
-    /// The `mentions` field support wildcard to select all transaction or all 'vote' transactions:
-    /// For example, to select all transactions:
-    /// "transaction_selector" : {
-    ///     "mentions" : \["*"\],
-    /// }
-    /// To select all vote transactions:
-    /// "transaction_selector" : {
-    ///     "mentions" : \["all_votes"\],
-    /// }
+    ///   None of the transction is stored.
+    ///   ```text
+    ///   "transaction_selector" : {
+    ///       "mentions" : ["pubkey-1", "pubkey-2", ..., "pubkey-n"],
+    ///   }
+    ///   ```
+    ///   The `mentions` field support wildcard to select all transaction or all 'vote' transactions:
+    ///   For example, to select all transactions:
+    ///   ```text
+    ///   "transaction_selector" : {
+    ///       "mentions" : ["*"],
+    ///   }
+    ///   ```
+    ///   To select all vote transactions:
+    ///   ```text
+    ///   "transaction_selector" : {
+    ///       "mentions" : ["all_votes"],
+    ///   }
+    ///   ```
+    ///   The `all_mentions` field selects the transactions mentioning every one of the addresses, in
+    ///   addition to the transactions selected by the `mentions`:
+    ///   ```text
+    ///   "transaction_selector" : {
+    ///       "all_mentions" : ["market-pubkey", "wallet-pubkey"],
+    ///   }
+    ///   ```
+    ///   The `exclude_votes`, `include_only_votes` and `exclude_failed` fields, set to 'true', leave out the vote
+    ///   transactions, the non-vote transactions and the failed transactions of the ones selected by the
+    ///   mentions, taking precedence over the mentions. For example, to select all the successful non-vote
+    ///   transactions:
+    ///   ```text
+    ///   "transaction_selector" : {
+    ///       "mentions" : ["*"],
+    ///       "exclude_votes" : true,
+    ///       "exclude_failed" : true
+    ///   }
+    ///   ```
+    /// * "store_vote_activity", optional, set it to 'true' to aggregate vote transactions into per-slot
+    ///   per-voter counts in the vote_activity table, independent of the transaction_selector. The default is 'false'.
+    /// * "block_chain_verification_interval", optional, when set, every time this many slots are rooted, the plugin
+    ///   verifies the parent links of the stored blocks and records the slot ranges needing backfill in the
+    ///   block_chain_gap table.
+    /// * "fork_cleanup", optional, how the slots of the abandoned forks, the ones never rooted whose slot is below
+    ///   the rooted slot and not one of its ancestors, are cleaned up: 'mark' sets their status to 'abandoned' in
+    ///   the slot table, and 'purge' also deletes their rows from the account_audit, account_version, owner_change, block, entry,
+    ///   and transaction tables along with the tables of the transaction details. Only the history is purged,
+    ///   the account table keeps the latest account updates. They are left as they are when not set.
+    /// * "fork_cleanup_interval_slots", optional, the abandoned forks are cleaned up every time this many slots
+    ///   are rooted, up to the slots this many slots behind the rooted slot. The default is 32.
+    /// * "priority_accounts", optional, a watchlist of accounts and owners whose updates bypass the shared queue
+    ///   and are written by a dedicated worker with its own connection. The accounts must also be selected by
+    ///   the `accounts_selector`. The updates during the startup still follow the normal batching.
+    ///   ```text
+    ///   "priority_accounts" : {
+    ///       "accounts" : ["pubkey-1", "pubkey-2"],
+    ///       "owners" : ["pubkey-3"]
+    ///   }
+    ///   ```
+    /// * "account_versions_selector", optional, in the same format as the `accounts_selector`, the accounts
+    ///   whose every version is kept in the account_version table keyed by the pubkey, the slot and the write
+    ///   version, including the multiple writes within a slot, for their state to be reconstructed at any slot.
+    ///   The accounts must also be selected by the `accounts_selector`.
+    ///   ```text
+    ///   "account_versions_selector" : {
+    ///       "owners" : ["pubkey-1"]
+    ///   }
+    ///   ```
+    /// * "validator_identity", optional, the identity pubkey of the validator. When set, the plugin records its
+    ///   run in the plugin_run table and holds an advisory lock for the identity to detect another live plugin
+    ///   instance writing for the same validator.
+    /// * "fail_on_duplicate_plugin_instance", optional, controls if to fail the loading when another live plugin
+    ///   instance is detected, when 'false', a warning is logged instead. The default is 'true'.
+    /// * "record_config_history", optional, controls if to record the settings altering which tables and columns
+    ///   are written, such as the selectors and the storage modes, into the config_history table along with
+    ///   their changes, when the plugin is loaded with settings different from the ones last recorded for the
+    ///   validator_identity and when the selectors are reloaded. The default is 'false'.
+    /// * "static_columns", optional, the columns and their values appended to every insert into the account,
+    ///   account_audit, slot, transaction, block, vote_activity and entry tables, the other tables being left
+    ///   without them. The account_audit rows written by the audit_account_update trigger do not carry them. The
+    ///   columns must be added to the tables beforehand.
+    ///   ```text
+    ///   "static_columns" : {
+    ///       "region" : "us-east",
+    ///       "shard" : "3"
+    ///   }
+    ///   ```
+    /// * "computed_columns", optional, the columns and the SQL expressions of their values appended to every
+    ///   upsert into the account table, the expressions referring to the columns of the account by the
+    ///   `{pubkey}`, `{slot}`, `{owner}`, `{lamports}`, `{executable}`, `{rent_epoch}`, `{data}`,
+    ///   `{write_version}`, `{updated_on}` and `{txn_signature}` placeholders. The columns must be added to the
+    ///   table beforehand.
+    ///   ```text
+    ///   "computed_columns" : {
+    ///       "data_len" : "length({data})"
+    ///   }
+    ///   ```
+    /// * "discriminator_owners", optional, the owners, such as the Anchor programs, of the accounts whose
+    ///   discriminators, the first 8 bytes of the data, are stored in the indexed discriminator column of the
+    ///   account table. The discriminator is null for the accounts of the other owners, and for all the
+    ///   accounts when not set.
+    /// * "account_data_decoders", optional, the layout descriptors of the owners whose account data is decoded
+    ///   into the data_json column of the account table, keyed by the owner: either the "idl" path of the
+    ///   Anchor IDL JSON of the program, or the "borsh_schema" path of a borsh schema file in the same type
+    ///   format. The raw data is kept in the data column, and data_json is null for the accounts of the other
+    ///   owners and those whose data does not decode.
+    ///   ```text
+    ///   "account_data_decoders" : {
+    ///       "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc" : {"idl" : "/etc/solana/whirlpool.json"}
+    ///   }
+    ///   ```
+    /// * "account_data_compression", optional, compresses the data of the accounts with zstd at the "level",
+    ///   3 by default, before it is written into the data column of the account and account_audit tables. The
+    ///   data shorter than "min_bytes", 128 by default, or which does not shrink, is written as it is. The
+    ///   data_codec column of the rows is 1 for the data compressed and 0 otherwise. It cannot be set along with
+    ///   the "discriminator_owners" or the computed columns of the data, whose expressions would see the data
+    ///   compressed.
+    ///   ```text
+    ///   "account_data_compression" : {"level" : 3, "min_bytes" : 128}
+    ///   ```
+    /// * "token_account_storage", optional, how the token accounts and mints of the SPL Token and Token-2022
+    ///   programs are stored: 'raw' stores them in the account table only, 'decoded' decodes them into the
+    ///   spl_token_account and spl_token_mint tables instead, and 'both' does both. 'mints' stores them in
+    ///   the account table and decodes the mints into the spl_token_mint table too, for the decimals and
+    ///   supplies to be joined with the raw token accounts. The other accounts of the token programs, such as
+    ///   the multisig accounts, are stored in the account table. The default is 'raw'.
+    /// * "token_secondary_indexes", optional, the secondary indexes of the token accounts to maintain, like the
+    ///   account indexes of the RPC nodes: 'spl_token_owner' maps the owners to their token accounts in the
+    ///   spl_token_owner_index table, and 'spl_token_mint' maps the mints to their token accounts in the
+    ///   spl_token_mint_index table, whatever the "token_account_storage". No index is maintained by default.
+    ///   ```text
+    ///   "token_secondary_indexes" : ["spl_token_owner", "spl_token_mint"]
+    ///   ```
+    /// * "start_slot" and "stop_slot", optional, the inclusive bounds of the slots of the notifications to store,
+    ///   the notifications outside of the range are ignored.
+    /// * "unload_at_stop_slot", optional, set it to 'true' to flush the pending writes and close the connections
+    ///   once the stop_slot, or a later slot when it is skipped, is rooted, within the shutdown_drain_timeout_secs.
+    ///   The default is 'false'.
+    /// * "skip_startup_accounts", optional, set it to 'true' to drop the account notifications from restoring the
+    ///   snapshot during the startup, only the live account updates are stored. The default is 'false'.
+    /// * "manage_indexes_on_startup", optional, set it to 'true' to drop the secondary indexes of the account
+    ///   table when the plugin is loaded, speeding up the ingestion of the snapshot, and to recreate them at the
+    ///   end of the startup, which waits for the indexes to be built. The primary key and the unique indexes are
+    ///   kept. The indexes dropped are kept in the startup_dropped_index table, and recreated at the next load
+    ///   when the plugin is stopped during the startup. The default is 'false'.
+    /// * "signature_storage", optional, how the transaction signatures are stored: 'bytea' stores the signature
+    ///   bytes, 'base58' stores the base58 encoded signature in the signature column, which must be created as
+    ///   text, and 'both' additionally stores the base58 encoded signature in the signature_text column.
+    ///   The default is 'bytea'.
+    /// * "store_block_json", optional, set it to 'true' to store a JSON document per block in the block_json
+    ///   column of the block table, in the shape of the RPC getBlock response with the full transaction details
+    ///   in the 'json' encoding. All the transactions of the blocks are kept in memory until the block metadata
+    ///   is notified, regardless of the transaction_selector. The default is 'false'.
+    /// * "store_transaction_json", optional, set it to 'true' to store a JSON document per transaction selected in
+    ///   the transaction_json column of the transaction table, in the shape of the RPC getTransaction response in
+    ///   the 'jsonParsed' encoding. The blockTime is left null as it is not known yet. The default is 'false'.
+    /// * "store_transaction_addresses", optional, set it to 'true' to index the transactions selected by the
+    ///   addresses they mention, including the addresses loaded from the address lookup tables, in the
+    ///   transaction_address table, to serve getSignaturesForAddress. The default is 'false'.
+    /// * "sysvar_sampling_rate", optional, when set to N, only the updates of the sysvars updated every slot, the
+    ///   clock, slot hashes and slot history, at the slots divisible by N are stored. The updates during the
+    ///   startup are always stored. All the updates are stored when not set.
+    /// * "account_sampling_percentage", optional, when set, only this percentage of the accounts, sampled by the
+    ///   hash of the pubkey, is stored, with all the updates of the accounts sampled including during the startup,
+    ///   to trial the plugin on the production traffic and extrapolate the storage and the throughput. All the
+    ///   accounts are stored when not set.
+    /// * "capture_file", optional, when set, the account updates, the slot statuses, the transactions and the
+    ///   block metadata selected are also appended to this file, as the length-delimited SubscribeUpdate
+    ///   messages of the geyser.proto definitions of Yellowstone gRPC, for the captures to be read and replayed
+    ///   by the tooling of the Geyser ecosystem. The file is flushed as the slots are rooted and when the plugin
+    ///   is unloaded. Nothing is captured when not set.
+    /// * "selector_dry_run_secs", optional, when set, the plugin runs the accounts selectors against the account
+    ///   updates for these seconds after it is loaded without writing anything, counting the updates and the
+    ///   estimated bytes of the rows selected by each condition of the selectors, and writes the counts into the
+    ///   selector_dry_run_stats table at the end, or when unloaded before. The plugin is then reloaded without it
+    ///   to write the updates.
+    /// * "store_sol_transfers", optional, set it to 'true' to extract the lamports moved by the System Program
+    ///   transfer, create account and nonce withdraw instructions, including the inner instructions, of the
+    ///   successful transactions selected into the sol_transfer table. The default is 'false'.
+    /// * "store_transaction_memos", optional, set it to 'true' to extract the memos of the SPL Memo instructions,
+    ///   including the inner instructions, of the successful transactions selected into the transaction_memo
+    ///   table, indexed by the memo for the attribution of the deposits. The default is 'false'.
+    /// * "parsed_instruction_programs", optional, the programs whose top level instructions of the successful
+    ///   transactions selected are parsed as in the 'jsonParsed' encoding into the parsed column of the
+    ///   transaction_instruction table, named as in the encoding: 'system', 'spl-token', 'stake', 'vote',
+    ///   'spl-associated-token-account', 'spl-memo', 'address-lookup-table', 'bpf-loader' or
+    ///   'bpf-upgradeable-loader'. The instructions are not parsed when not set.
+    /// * "store_normalized_transaction_meta", optional, set it to 'true' to store the pre and post balances, the
+    ///   pre and post token balances, the rewards and the log messages of the transactions selected into the
+    ///   transaction_balance, transaction_token_balance, transaction_reward and transaction_log tables, keyed by
+    ///   the slot and the signature, in addition to the meta of the transaction table. The default is 'false'.
+    /// * "store_slot_economics", optional, set it to 'true' to store the fee burn and the rent collected per slot
+    ///   in the slot_economics table, derived from the fees of all the transactions of the block, regardless of
+    ///   the transaction_selector, and the fee and rent rewards of the block. The default is 'false'.
+    /// * "store_block_rewards", optional, set it to 'true' to store the rewards of the blocks in the block_reward
+    ///   table, one row per reward, in addition to the rewards array of the block table. The default is 'false'.
+    /// * "store_validator_rewards", optional, set it to 'true' to store the fee, rent and voting rewards of the
+    ///   blocks, along with the commissions of the vote accounts, in the validator_reward table, aggregated per
+    ///   validator per epoch of the rooted blocks by the validator_epoch_reward view. The staking rewards of the
+    ///   stake accounts are stored by "store_stake_rewards" instead. The default is 'false'.
+    /// * "store_stake_rewards", optional, set it to 'true' to store the staking rewards of the stake accounts
+    ///   distributed at the epoch boundaries, possibly partitioned across the first blocks of the epoch, in the
+    ///   stake_reward table keyed by the epoch the rewards are earned in and the stake account. The default is
+    ///   'false'.
+    /// * "store_entries", optional, set it to 'true' to store the entries of the blocks in the entry table. The
+    ///   entries are high volume, they are written in batches of the entries of a slot. The default is 'false'.
+    /// * "entry_batch_size", optional, the max number of the entries of a slot written by a statement when
+    ///   store_entries is set. The default is '64'.
+    /// * "worker_cpu_budget_percentage", optional, when set, the percentage of a core each worker may use,
+    ///   measured over windows of a second. A worker over its budget delays or sheds its low-priority work:
+    ///   the vote activity, the entries and the block chain verification. The CPU usage is not capped when
+    ///   not set.
+    /// * "cpu_budget_exceeded_action", optional, what a worker over its CPU budget does with the low-priority
+    ///   work: 'delay' waits until the usage is back within the budget, and 'shed' drops it. The default is
+    ///   'delay'.
+    /// * "statement_batch_size", optional, when set, the max number of the account, transaction and slot
+    ///   updates queued together to a worker written in one database transaction with a single COMMIT,
+    ///   reducing the fsyncs of the server under load. A batch failing is rolled back and its updates are
+    ///   written again one by one. Each update is committed on its own when not set.
+    /// * "commit_per_slot", optional, set it to 'true' to buffer the transactions of each slot until its block
+    ///   metadata is notified, and then commit them together by a worker in chunks of up to the
+    ///   "slot_commit_chunk_size" transactions, one database transaction per chunk. The slot_commit row of the
+    ///   slot is written within the transaction of the final chunk, marking the transactions of the slot all
+    ///   committed. The transactions are committed as they are notified when not set. The default is 'false'.
+    /// * "slot_commit_chunk_size", optional, the max number of the transactions of a slot committed per database
+    ///   transaction with the "commit_per_slot". The default is 1000.
+    /// * "batch", optional, the "size" and the "max_delay_ms" of the statement batches per kind of update, of
+    ///   the "account", "transaction" and "slot" updates, for example:
+    ///   ```text
+    ///   "batch" : {
+    ///       "account" : { "size" : 500, "max_delay_ms" : 20 },
+    ///       "transaction" : { "size" : 100, "max_delay_ms" : 50 },
+    ///       "slot" : { "size" : 10, "max_delay_ms" : 0 }
+    ///   }
+    ///   ```
+    /// * "statement_timeout_ms", optional, the statement timeout in milliseconds of the writes of the workers.
+    ///   The statement timeout of the session, such as the one of the role, is kept when not set.
+    /// * "table_statement_timeouts_ms", optional, the statement timeouts in milliseconds of the writes to the
+    ///   "account", "account_audit", "slot", "transaction", "block", "entry" and "vote_activity" tables,
+    ///   overriding the statement_timeout_ms. The account updates, which copy the versions replaced into the
+    ///   account_audit table, take the longer of the timeouts of the account and account_audit tables.
+    ///   ```text
+    ///   "table_statement_timeouts_ms" : {
+    ///       "account_audit" : 60000,
+    ///       "slot" : 500
+    ///   }
+    ///   ```
+    ///   A batch holds up to the size of the kind of its first update, and waits for the updates queued to
+    ///   fill it up to the shortest max delay of the kinds of its updates, so that a slot update joining a
+    ///   batch of account updates is committed without waiting longer than its own max delay. The kinds without
+    ///   their own size are batched up to the "statement_batch_size", or not batched when it is not set, and
+    ///   the default max delay is '0', a batch only taking the updates already queued.
+    /// * "metrics", optional, when set, serves the Prometheus metrics of the plugin at the /metrics path of the
+    ///   "bind" address, for example:
+    ///   ```text
+    ///   "metrics" : {
+    ///       "bind" : "0.0.0.0:9091"
+    ///   }
+    ///   ```
+    ///   The metrics are the depths of the queues, the durations of the writes, the updates notified, the work
+    ///   items written and failed per stream, and the slots notified and committed along with the lag between
+    ///   them. The metrics are not served when not set.
+    /// * "backpressure_queue_threshold", optional, when set, the account updates block the validator while the
+    ///   queue they are sent to holds at least this many work items, until the workers drain it below the
+    ///   threshold or for up to "backpressure_max_block_ms", trading the throughput of the validator for the
+    ///   plugin keeping up with it. The updates are buffered up to the capacity of the queue, 40960, when not
+    ///   set.
+    /// * "backpressure_max_block_ms", optional, the longest an account update blocks for past the queue
+    ///   threshold, after which it is queued regardless. The default is '1000'.
+    /// * "flow_control_channel", optional, when set, the plugin listens on this PostgreSQL notification channel
+    ///   for the downstream consumers to signal 'slow_down', lowering the "backpressure_queue_threshold" to a
+    ///   quarter, or 'paused', blocking every account update for "backpressure_max_block_ms", until they signal
+    ///   'resume'. A signal lapses a minute after it is notified unless notified again, so that a consumer
+    ///   exiting without resuming does not hold back the plugin. It requires the "backpressure_queue_threshold".
+    /// * "queue_capacity", optional, the work items the queue of the workers holds at most, buffered in memory.
+    ///   The default is '40960'.
+    /// * "queue_overflow_policy", optional, what is done with the work items notified while the queue of the
+    ///   workers is full, one of 'block' blocking the notifications of the validator until the workers make
+    ///   room, 'drop_oldest' dropping the oldest account updates and transactions queued to make room, and
+    ///   'drop_account_updates_only' dropping the account updates notified while blocking the other
+    ///   notifications. The work items dropped are lost, and counted by the
+    ///   `accountsdb-plugin-postgres-queue-dropped` metric and the geyser_postgres_work_items_dropped_total
+    ///   Prometheus metric. The default is 'block'.
+    /// * "degradation_ladder", optional, the steps the plugin degrades by, in order, as the backlog of the queue
+    ///   of the workers grows. Each step is entered once the depth of the queue stays at or above its
+    ///   "queue_depth" for "sustain_secs", 10 by default, and left once the depth stays below half of it for as
+    ///   long, one step at a time, the steps entered adding up. The action of a step is one of 'disable_audit'
+    ///   no longer copying the previous versions of the accounts into the account_audit table,
+    ///   'disable_transactions' no longer queuing the transactions, 'sample_accounts' queuing one in every
+    ///   "sampling_rate" account updates, and 'drop' queuing neither the account updates nor the transactions.
+    ///   The ladder is walked once the startup is done. Every transition is logged and recorded into the
+    ///   degradation_transition table.
+    ///   ```text
+    ///   "degradation_ladder" : {
+    ///       "steps" : [
+    ///           {"queue_depth" : 10000, "action" : "disable_audit"},
+    ///           {"queue_depth" : 20000, "action" : "disable_transactions"},
+    ///           {"queue_depth" : 30000, "action" : "sample_accounts", "sampling_rate" : 10},
+    ///           {"queue_depth" : 40000, "action" : "drop"}
+    ///       ]
+    ///   }
+    ///   ```
+    /// * "selector_reload_interval_secs", optional, when set, the config file is checked every this many seconds
+    ///   and, once modified, the accounts_selector, startup_accounts_selector and transaction_selector are
+    ///   swapped for the ones in it without reloading the plugin. The config is validated as when loaded, and
+    ///   the current selectors are kept when it is not valid. The other settings are not reloaded, and the
+    ///   kinds of the notifications enabled when loaded are not changed. The selectors are only loaded along
+    ///   with the plugin when not set.
+    /// * "slots_per_epoch", optional, the slots per epoch of the cluster for the epochs of the validator and the
+    ///   stake rewards, assuming no warmup of the epochs. The default is '432000' of mainnet-beta.
+    /// * "write_epoch_barriers", optional, set it to 'true' to write a barrier into the epoch_barrier table before
+    ///   the first slot of each epoch of the "slots_per_epoch" is written, once all the updates notified before
+    ///   are committed and the write-ahead log is flushed. The default is 'false'.
+    /// * "post_commit_hooks", optional, the SQL executed after each batch of the writes of a table is committed,
+    ///   keyed by the table: 'account', 'slot', 'transaction' or 'block'. The batch metadata is bound as the
+    ///   parameters: $1 the table, $2 the count of the rows written, $3 and $4 the lowest and the highest slot of
+    ///   the rows, and $5 the commit time. A failed hook is handled as a failed write of the table.
+    ///   ```text
+    ///   "post_commit_hooks" : {
+    ///       "block" : "SELECT pg_notify('block_written', $3::text)"
+    ///   }
+    ///   ```
+    /// * "notify_channels", optional, the channels notified with pg_notify of each row written once committed,
+    ///   keyed by the update type: 'account', 'slot', 'transaction' or 'block'. Each notification carries the
+    ///   JSON payload of the row: the pubkey, owner, slot and write_version of an account, the slot, parent
+    ///   and status of a slot, the signature and slot of a transaction, or the slot and blockhash of a block.
+    ///   ```text
+    ///   "notify_channels" : {
+    ///       "slot" : "slot_updates"
+    ///   }
+    ///   ```
+    /// * "use_schema_triggers", optional, set it to 'false' for the trigger-free schema, with the triggers of the
+    ///   account table dropped by scripts/drop_schema_triggers.sql. The plugin then copies the previous versions
+    ///   of the accounts updated into the account_audit table when store_account_historical_data is set, and
+    ///   records the owner changes into the owner_change table, in the statements updating the accounts. The
+    ///   default is 'true'.
+    /// * "account_layout", optional, 'wide' stores the data of the accounts in the account table along with the
+    ///   other columns, 'split' stores it in the account_data table joined by the pubkey, keeping the account
+    ///   table narrow for the balance and ownership queries. The split layout is converted to by
+    ///   scripts/split_account_data.sql, and requires the "use_schema_triggers" set to 'false'. The default is
+    ///   'wide'.
+    /// * "footprint", optional, 'minimal' stores only the latest state of the accounts selected in the account
+    ///   table and the slot statuses in the slot table, for the RPC nodes using the database as an accounts
+    ///   cache: no account history, owner changes, transactions, blocks nor entries are written, the
+    ///   "use_schema_triggers" defaults to 'false', and the account updates are batched for up to 400ms with
+    ///   the updates of an account within a batch coalesced into its latest one. The settings storing anything
+    ///   else are rejected. The default is 'full', storing the notifications as configured.
+    /// * "account_cold_tier_slots", optional, when set, the accounts not updated in these slots are moved from
+    ///   the account table into the account_cold table every minute, keeping the frequently updated accounts in a
+    ///   small table. A cold account updated again is moved back into the account table, and the account_tiered
+    ///   view unions both tables. Requires the "use_schema_triggers" set to 'false' and the 'wide'
+    ///   "account_layout".
+    /// * "account_cold_tier_tablespace", optional, the tablespace the account_cold table is moved to, such as one
+    ///   on cheaper storage. Requires the "account_cold_tier_slots".
+    /// * "storage_engine", optional, 'postgres' or 'timescaledb'. With 'timescaledb', the account_audit,
+    ///   transaction and block tables are converted into hypertables partitioned by the slot when the plugin is
+    ///   loaded, and the transactions written again are left as they are rather than updated. The default is
+    ///   'postgres'.
+    /// * "hypertable_chunk_slots", optional, the slots of each chunk of the hypertables. The default is
+    ///   '432000'.
+    /// * "hypertable_compress_after_slots", optional, when set, the chunks of the hypertables whose slots are
+    ///   all older than these slots are compressed by the compression policies of TimescaleDB.
+    /// * "distributed", optional, when set to 'citus', the tables of the accounts are converted into Citus
+    ///   distributed tables by the pubkey, and the tables of the slots, the blocks and the transactions by the
+    ///   slot, each colocated with the account and slot tables respectively, when the plugin is loaded. The
+    ///   statements writing an account or a transaction are then routed to a single worker node. Requires the
+    ///   "use_schema_triggers" set to 'false'. The tables are not distributed when not set.
+    /// * "distributed_shard_count", optional, the shards of the account and slot tables when they are
+    ///   distributed, the default of Citus when not set. Requires the "distributed".
+    /// * "connection_keepalive_interval_secs", optional, when set, the connection of a worker idle for this many
+    ///   seconds is pinged, and rebuilt along with its prepared statements when it has been dropped, such as by a
+    ///   firewall or the idle timeout of a connection pooler. The keepalive is disabled when not set.
+    /// * "reconnect_max_backoff_secs", optional, when set, a worker whose write fails on a dropped connection,
+    ///   such as by a restart of the database or a network blip, reconnects with an exponential backoff from
+    ///   100 milliseconds up to this many seconds, and then writes the update again, instead of subjecting it to
+    ///   panic_on_db_errors. The reconnection is disabled when not set.
+    /// * "reconnect_buffer_size", optional, the max number of the updates a worker buffers while reconnecting,
+    ///   after which the updates are left queued to the worker, blocking the validator once the queue is full.
+    ///   The default is 10000.
+    /// * "replay_window_secs", optional, when set along with reconnect_max_backoff_secs, a worker keeps the
+    ///   updates it committed within this many seconds and writes them again once reconnected, so that a standby
+    ///   lagging behind the failed primary is left without a gap after a failover. Only the account updates, the
+    ///   slot statuses and the transactions are replayed, as their writes are idempotent; the account updates
+    ///   are left out when store_account_historical_data is set. None are replayed when not set.
+    /// * "replay_window_size", optional, the max number of the updates a worker keeps within the replay window,
+    ///   the oldest ones being evicted first past it even though they are within replay_window_secs. The default
+    ///   is 100000.
+    /// * "fallback_connection_str", optional, when set along with reconnect_max_backoff_secs, the connection
+    ///   string of a read-write standby. Once a worker finds the primary unreachable for failover_after_secs,
+    ///   the workers all reconnect to the standby, writing their buffered updates and the ones of the replay
+    ///   window there, and fail back to the primary once the primary accepts the writes again, writing the
+    ///   updates of the replay window to the primary. The updates written to the standby before the replay
+    ///   window are not copied to the primary. There is no failover when not set.
+    /// * "health_check_interval_secs", optional, the interval in seconds at which a worker with a
+    ///   fallback_connection_str pings its connection, and checks if the primary accepts the writes again while
+    ///   failed over. The default is 5.
+    /// * "failover_after_secs", optional, the seconds the primary is to be unreachable for before a worker fails
+    ///   over to the fallback_connection_str. The default is 30.
+    /// * "tunnel", optional, the tunnel the connections to a database in a private network are made through,
+    ///   either the "socks5_proxy", "host:port", along with the "socks5_user" and "socks5_password" when the
+    ///   proxy requires them, or the "ssh_jump_host", "[user@]host", along with the "ssh_port" and the
+    ///   "ssh_identity_file", forwarded by the ssh command. The tunnel listens on a local port the connections
+    ///   are made to, the host of the connection_str being kept for the verification of the certificate.
+    ///   ```text
+    ///   "tunnel" : {"ssh_jump_host" : "solana@bastion.internal", "ssh_identity_file" : "/home/solana/.ssh/id_ed25519"}
+    ///   ```
+    /// * "credentials", optional, the provider of the password of the database, so that it is kept out of the
+    ///   config file, either the "password_env", the environment variable holding it, the "password_file",
+    ///   the file holding it, or the "password_command", the command along with its arguments printing it.
+    ///   The password is resolved on each connection, so that a rotated password is picked up on the
+    ///   reconnections, or reused for the "refresh_interval_secs" when set. The connection_str must not
+    ///   contain a password then.
+    ///   ```text
+    ///   "credentials" : {"password_command" : ["vault", "kv", "get", "-field=password", "secret/geyser-pg"], "refresh_interval_secs" : 300}
+    ///   ```
+    /// * "ingest_sla_secs", optional, when set, the slot status, the block metadata and the transactions of each
+    ///   slot processed are expected to have arrived and been committed within this many seconds of the
+    ///   processing of the slot, and the slots missing any of them are written to the ingest_sla_violation
+    ///   table. The SLA is not monitored when not set.
+    /// * "store_slot_transaction_counts", optional, controls if to count per slot the transactions selected
+    ///   received by the plugin and the ones committed by the workers, written every second to the
+    ///   transactions_received and transactions_committed columns of the slot rows, so that the transactions
+    ///   dropped or lost with failed batches show as a mismatch of the two. The default is false.
+    /// * "store_slot_checksums", optional, controls if to sum per slot the digests of the account and transaction
+    ///   rows committed by the workers, written every second to the ingest_checksum and ingest_checksum_rows
+    ///   columns of the slot rows, so that the slot_ingest_checksum function recomputing them from the tables
+    ///   proves that the rows were not lost or mutated after their ingestion. It requires the
+    ///   "store_account_historical_data" and the rows stored as they are notified, once each, so it is not
+    ///   allowed along with the "replay_window_secs" or the 'disable_audit' step of the "degradation_ladder".
+    ///   The default is false.
+    /// * "write_stats_interval_secs", optional, when set, each worker accounts the rows and the bytes written per
+    ///   table, the encoded parameters along with an estimated row overhead, and reports them every this many
+    ///   seconds to the metrics and the table_write_stats table, along with the account rows inserted, updated
+    ///   and skipped as stale by the upserts to the account_upsert_stats table. The reporting is disabled when
+    ///   not set.
+    /// * "write_verification_percentage", optional, when set, this percentage of the account and transaction
+    ///   rows, sampled by the hash of the pubkey or the signature along with the slot, is read back after it is
+    ///   written and compared byte for byte with the update it was written from, logging and counting the
+    ///   columns differing. The writes are not verified when not set.
+    /// * "verification_connection_str", optional, when set, the connection string of a read replica the rows
+    ///   of the write verification are read back from and the block chain verification is run against, so
+    ///   that they do not compete with the writes on the primary. On a hot standby, the rows are read back
+    ///   once the standby has replayed the WAL past their writes, and the block chain is verified once the
+    ///   standby has caught up with the primary. The verifications run on the primary when not set.
+    /// * "verification_max_lag_secs", optional, the seconds the verifications wait for the standby to replay
+    ///   the writes, after which the write verifications are skipped and the block chain is verified on the
+    ///   primary. The default is 60.
+    /// * "shutdown_drain_timeout_secs", optional, the seconds the workers are given on the unload of the plugin
+    ///   to write the updates queued to them and to flush their pending batches before they exit. The updates
+    ///   are not drained when zero. The default is 30.
+    /// * "spill_path", optional, when set, the updates left unwritten when the workers exit, such as when the
+    ///   database is unreachable during the drain, are appended to this file, one JSON line per update, and
+    ///   queued to the workers again on the next load, removing the file. The updates left are dropped when
+    ///   not set.
+    /// * "analyze_interval_secs", optional, when set, ANALYZE is run on a dedicated connection on the account
+    ///   and transaction tables written to in the last this many seconds, as the autovacuum frequently lags
+    ///   behind the write rate of the plugin. The ANALYZE is not scheduled on a cadence when not set.
+    /// * "analyze_row_count_delta", optional, when set, ANALYZE is run on the account or transaction table once
+    ///   this many rows have been written to it since its last ANALYZE. Either schedule triggers the ANALYZE,
+    ///   which is not run during the startup. The ANALYZE is not scheduled on a row count when not set.
+    /// * "account_audit_archive_path", optional, the directory, created if missing, the archive_account_audit
+    ///   tool copies the account_audit rows older than a slot to in the binary format of COPY before deleting
+    ///   them, the partitions wholly older than the slot being archived and dropped as a whole. With the
+    ///   account_audit_retention_slots, the partitions or the chunks older than the retention are archived
+    ///   there too, a file named after each, before they are dropped, a partition or a chunk failing to be
+    ///   archived not being dropped until archived. The rows deleted from the account_audit_default partition
+    ///   are not archived. Not supported when pg_partman manages the partitions.
+    /// * "index_advisor_interval_secs", optional, when set, the queries against the tables of the plugin observed
+    ///   by pg_stat_statements are inspected every this many seconds, and the indexes of their predicates not
+    ///   served by an index, along with the partitioning by the slot of the large tables queried by slot ranges,
+    ///   are reported to the index_advice table. The pg_stat_statements extension must be created in the
+    ///   database. The indexes are not advised when not set.
+    /// * "index_advisor_min_calls", optional, the calls of a query for the index advisor to advise for it. The
+    ///   default is 100.
+    /// * "schema_drift_check_interval_secs", optional, when set, the tables, columns and indexes of the schema are
+    ///   compared every interval with the ones of scripts/create_schema.sql once the startup is done, the missing
+    ///   tables, columns and indexes and the altered column types being logged and reported by the metrics.
+    /// * "cluster", optional, the cluster of the validator, such as 'mainnet-beta', 'testnet' or 'devnet',
+    ///   selecting its section in "clusters" when one database serves the validators of several clusters.
+    /// * "clusters", optional, the config sections keyed by the cluster. The fields of the section of the
+    ///   cluster replace the ones of the config as a whole, so that each cluster can have its own "schema" and
+    ///   independent selectors, for example:
+    ///   "clusters" : { "mainnet-beta" : { "schema" : "mainnet", "accounts_selector" : { "owners" : [...] } },
+    ///   "testnet" : { "schema" : "testnet", "accounts_selector" : { "accounts" : ["*"] } } }
+    /// * "schema", optional, the schema of the tables written to. The search path of the connections is set to
+    ///   the schema, the default search path of the user is used when not set. The name must consist of
+    ///   lowercase letters, digits and underscores.
+    /// * "create_schema", optional, set it to 'true' to create the schema along with the tables of
+    ///   scripts/create_schema.sql when the account table is missing from it, such as for a new cluster. The
+    ///   default is 'false'.
+    /// * "auto_migrate", optional, the version of the schema is recorded in the schema_version table and
+    ///   checked when the plugin is loaded, the loading failing when the schema is older than the version the
+    ///   plugin writes to. Set it to 'true' to apply the pending migrations of scripts/migrations instead. The
+    ///   default is 'false'.
+    /// * "schema_source", optional, the directory or the http(s) URL of a bundle of versioned migrations used in
+    ///   place of the ones of scripts/migrations, listed along with their SHA-256 checksums in its SHA256SUMS
+    ///   manifest. The migrations are checked against the manifest, and the checksums recorded in the
+    ///   schema_version table for the migrations applied are checked against them, the loading failing on a
+    ///   mismatch. The bundle must go up to the version the plugin writes to at least, and its pending
+    ///   migrations are applied with the "auto_migrate" set to 'true'.
+    /// * "startup_wait_secs", optional, when set, the loading of the plugin, and so the startup of the
+    ///   validator, waits up to this many seconds for the database to accept the connections with the account
+    ///   table in place, and then for the workers to connect, such as when the database starts slightly after
+    ///   the validator. The loading fails on the first failed connection when not set.
+    /// * "program_labels_file", optional, the file mapping the program ids to the human-readable names and
+    ///   categories, stored in the program_label table at every load of the plugin, including the reloads. The
+    ///   labels no longer in the file are deleted. A .csv file has lines of the base58 program id, the name and
+    ///   the optional category, other files are JSON objects keyed by the program id, for example:
+    ///   { "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4" : { "name" : "Jupiter v6", "category" : "dex" } }
+    /// * "address_tags", optional, the tags of the addresses keyed by the base58 address, such as to flag the
+    ///   addresses of the exchanges and the bridges, stored in the address_tag table at every load of the
+    ///   plugin, including the reloads. The tags no longer in the config are deleted. The tagged_sol_transfer
+    ///   view joins the tags into the SOL transfers, for example:
+    ///   "address_tags" : { "5tzFkiKscXHK5ZXCGbXZxdw7gTjjD1mBwuoFbhUvuAi9" : ["exchange", "binance"] }
+    /// * "dead_letter_attempts", optional, when set, a failed account update outside of the startup or a failed
+    ///   transaction is attempted up to this many times in total, with a backoff from 100 milliseconds to 2
+    ///   seconds between the attempts, and then written as JSON along with the error to the dead_letter table,
+    ///   instead of failing the write, so that it can be repaired and replayed. A write failing with the
+    ///   connection dropped is neither retried nor dead lettered, and is subject to "panic_on_db_errors" and
+    ///   the reconnection as without the dead letter, as is a write whose dead letter fails too. The startup
+    ///   account batches, the slot statuses, the block metadata and the entries are not covered. The dead letter
+    ///   is disabled when not set.
+    /// * "transaction_allowlist_poll_interval_secs", optional, when set, the transaction_allowlist table, maintained
+    ///   outside of the plugin, is polled at this interval, and the transactions of its signatures or mentioning
+    ///   its addresses are stored regardless of the transaction_selector. The allowlist is disabled when not set.
+    /// * "dedup_window_slots", optional, when set, the writes at the last this many slots up to the highest slot
+    ///   stored at the load, notified again by the validator replaying them after a restart, are ignored when
+    ///   already stored: the account updates at a slot already stored for the account, and thus their
+    ///   account_audit and owner_change rows, and the votes of the vote_activity counted already. The dedup window
+    ///   is disabled when not set.
+    /// * "client", optional, the client writing the updates, one of 'blocking', the worker threads writing on
+    ///   their blocking connections, and 'async', a tokio runtime writing the updates concurrently through a
+    ///   pool of tokio-postgres connections. The async client writes the account, slot, transaction and block
+    ///   rows and the startup report, and only supports the connection, selection, storage and queue settings
+    ///   listed in the README. The loading fails when another setting is set along with it. The default is
+    ///   'blocking'.
+    /// * "async_pool_size", optional, the connections of the pool of the async client, the writes in flight at
+    ///   most. The default is 10.
+    /// * "async_request_timeout_ms", optional, the time in milliseconds a write of the async client is given,
+    ///   past which the query is cancelled, its connection closed and the write lost. The
+    ///   "statement_timeout_ms" also applies to the connections of the pool. The default is 30000.
+    /// * "snapshot_rewind_action", optional, what is done at the end of the startup when the validator restarted
+    ///   from a snapshot older than the highest rooted slot stored at the load, the accounts of the snapshot being
+    ///   notified at a lower slot: 'alert', the default, logs an error and reports the
+    ///   accountsdb-plugin-postgres-snapshot-rewind datapoint, and 'reconcile' also purges the rows of the slots
+    ///   after the snapshot from the slot, block, transaction, account_audit and owner_change tables and the other
+    ///   tables purged by the fork cleanup, for the replay to write them again.
+    /// * "account_audit_retention_slots", optional, when set, the plugin maintains the partitions of the
+    ///   account_audit table, which must be partitioned by the slot as in scripts/create_schema.sql: it creates the
+    ///   partitions of the current and the next slot ranges and drops the partitions older than this many slots
+    ///   behind the highest slot. When the account_audit table is registered with the pg_partman installed, the
+    ///   retention is set in its part_config and the plugin runs the maintenance of pg_partman instead. All the
+    ///   history is kept when not set.
+    /// * "account_audit_partition_slots", optional, the slots of each partition of the account_audit table
+    ///   created for the account_audit_retention_slots. The default is 216000, about a day. It is ignored when
+    ///   pg_partman manages the partitions.
+    /// * "account_audit_retention_overrides", optional, the slots of the history kept for the accounts of the
+    ///   owners, base58 encoded, instead of the account_audit_retention_slots, such as a longer history of the
+    ///   oracle price accounts. The rows of an owner kept longer are moved to the account_audit_default
+    ///   partition before the partitions holding them are dropped, and the rows of an owner kept shorter are
+    ///   deleted; the history of each owner is deleted once it is older than its retention. The overrides
+    ///   longer than the account_audit_retention_slots are not supported with the TimescaleDB storage engine
+    ///   nor with pg_partman.
+    ///   ```text
+    ///   "account_audit_retention_overrides" : {"FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH" : 1512000}
+    ///   ```
+    /// * "continuous_aggregates", optional, the names of the TimescaleDB continuous aggregates over the
+    ///   hypertables partitioned by the slot, refreshed by the plugin up to the rooted slot rather than by
+    ///   the refresh policies on the wall time, so that they only materialize the buckets of the rooted
+    ///   slots. The loading fails when any of them does not exist.
+    /// * "continuous_aggregate_refresh_slots", optional, the continuous aggregates are refreshed each time the
+    ///   rooted slot progresses this many slots. The default is 32.
+    ///
     /// # Examples
     ///
+    /// ```text
     /// {
     ///    "libpath": "/home/solana/target/release/libsolana_accountsdb_plugin_postgres.so",
     ///    "host": "host_foo",
@@ -124,7 +1527,7 @@ impl GeyserPlugin for AccountsDbPluginPostgres {
     ///       "owners" : ["9oT9R5ZyRovSVnt37QvVoBttGpNqR3J7unkb567NP8k3"]
     ///    }
     /// }
-
+    /// ```
     fn on_load(&mut self, config_file: &str, _is_reload: bool) -> Result<()> {
         solana_logger::setup_with_default("info");
         info!(
@@ -132,30 +1535,43 @@ impl GeyserPlugin for AccountsDbPluginPostgres {
             self.name(),
             config_file
         );
-        let mut file = File::open(config_file)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
-
-        let result: serde_json::Value = serde_json::from_str(&contents).unwrap();
-        self.accounts_selector = Some(Self::create_accounts_selector_from_config(&result));
-        self.transaction_selector = Some(Self::create_transaction_selector_from_config(&result));
-
-        let result: serde_json::Result<AccountsDbPluginPostgresConfig> =
-            serde_json::from_str(&contents);
-        match result {
-            Err(err) => {
-                return Err(GeyserPluginError::ConfigFileReadError {
-                    msg: format!(
-                        "The config file is not in the JSON format expected: {:?}",
-                        err
-                    ),
-                })
-            }
-            Ok(config) => {
-                let client = PostgresClientBuilder::build_pararallel_postgres_client(&config)?;
-                self.client = Some(client);
-            }
+        let result = load_config_file(Path::new(config_file))?;
+        let mut config = parse_config(result.clone())?;
+        self.tunnel = Tunnel::start(&mut config)?;
+
+        self.selectors = Arc::new(RwLock::new(Arc::new(Selectors::from_config(&result))));
+
+        self.store_vote_activity = config
+            .store_vote_activity
+            .unwrap_or(DEFAULT_STORE_VOTE_ACTIVITY);
+        self.slot_range = SlotRange::new(config.start_slot, config.stop_slot);
+        self.unload_at_stop_slot = config
+            .unload_at_stop_slot
+            .unwrap_or(DEFAULT_UNLOAD_AT_STOP_SLOT);
+        self.skip_startup_accounts = config
+            .skip_startup_accounts
+            .unwrap_or(DEFAULT_SKIP_STARTUP_ACCOUNTS);
+        self.sysvar_sampler = SysvarSampler::new(config.sysvar_sampling_rate);
+        self.account_sampler = AccountSampler::new(config.account_sampling_percentage);
+        self.store_block_json = config.store_block_json.unwrap_or(DEFAULT_STORE_BLOCK_JSON);
+        self.store_slot_economics = config
+            .store_slot_economics
+            .unwrap_or(DEFAULT_STORE_SLOT_ECONOMICS);
+        self.store_entries = config.store_entries.unwrap_or(DEFAULT_STORE_ENTRIES);
+        self.notification_capture = NotificationCapture::new(config.capture_file.as_deref())?;
+        self.store_block_metadata = config.footprint.unwrap_or_default() != Footprint::Minimal;
+        self.selector_dry_run = SelectorDryRun::new(&config)?;
+        if self.selector_dry_run.is_none() {
+            let client = PostgresClientBuilder::build_sharded_postgres_client(&config)?;
+            self.client = Some(client);
+        }
+        let transactions_notified = self.transaction_notifications_enabled();
+        if let Some(client) = &self.client {
+            client.set_transactions_notified(transactions_notified);
         }
+        self.selector_reloader =
+            SelectorReloader::new(config_file.into(), &config, self.selectors.clone())
+                .map(|reloader| reloader.spawn(self.exit_selector_reloader.clone()));
 
         Ok(())
     }
@@ -163,12 +1579,25 @@ impl GeyserPlugin for AccountsDbPluginPostgres {
     fn on_unload(&mut self) {
         info!("Unloading plugin: {:?}", self.name());
 
+        self.exit_selector_reloader.store(true, Ordering::Relaxed);
+        if let Some(selector_reloader) = self.selector_reloader.take() {
+            selector_reloader.join().unwrap();
+        }
+        if let Some(notification_capture) = &self.notification_capture {
+            // The error is logged, the notifications still buffered are lost
+            let _ = notification_capture.flush();
+        }
+        if let Some(selector_dry_run) = &mut self.selector_dry_run {
+            selector_dry_run.join();
+        }
         match &mut self.client {
             None => {}
             Some(client) => {
                 client.join().unwrap();
             }
         }
+        // Stopped once the connections made through it are closed
+        self.tunnel = None;
     }
 
     fn update_account(
@@ -178,68 +1607,90 @@ impl GeyserPlugin for AccountsDbPluginPostgres {
         is_startup: bool,
     ) -> Result<()> {
         let mut measure_all = Measure::start("accountsdb-plugin-postgres-update-account-main");
-        match account {
-            ReplicaAccountInfoVersions::V0_0_3(account) => {
-                let mut measure_select =
-                    Measure::start("accountsdb-plugin-postgres-update-account-select");
-                if let Some(accounts_selector) = &self.accounts_selector {
-                    if !accounts_selector.is_account_selected(account.pubkey, account.owner) {
-                        return Ok(());
-                    }
-                } else {
-                    return Ok(());
+        let account = geyser_interface::account_info(account);
+        if (is_startup && self.skip_startup_accounts)
+            || !self.is_slot_selected(slot)
+            || !self
+                .sysvar_sampler
+                .is_update_sampled(account.pubkey, slot, is_startup)
+            || !self.account_sampler.is_account_sampled(account.pubkey)
+        {
+            return Ok(());
+        }
+        let mut measure_select = Measure::start("accountsdb-plugin-postgres-update-account-select");
+        let selectors = self.selectors();
+        let accounts_selector = selectors.accounts_selector(is_startup);
+        if let Some(selector_dry_run) = &self.selector_dry_run {
+            if let Some(accounts_selector) = accounts_selector {
+                selector_dry_run.record(
+                    accounts_selector,
+                    account.pubkey,
+                    account.owner,
+                    account.lamports,
+                    account.data,
+                    is_startup,
+                );
+            }
+            return Ok(());
+        }
+        if let Some(accounts_selector) = accounts_selector {
+            if !accounts_selector.is_account_selected_with_lamports(
+                account.pubkey,
+                account.owner,
+                account.lamports,
+                account.data,
+            ) {
+                return Ok(());
+            }
+        } else {
+            return Ok(());
+        }
+        measure_select.stop();
+        inc_new_counter_debug!(
+            "accountsdb-plugin-postgres-update-account-select-us",
+            measure_select.as_us() as usize,
+            100000,
+            100000
+        );
+
+        debug!(
+            "Updating account {:?} with owner {:?} at slot {:?} using account selector {:?}",
+            bs58::encode(account.pubkey).into_string(),
+            bs58::encode(account.owner).into_string(),
+            slot,
+            accounts_selector.unwrap()
+        );
+
+        match &self.client {
+            None => {
+                return Err(GeyserPluginError::Custom(Box::new(
+                    AccountsDbPluginPostgresError::DataStoreConnectionError {
+                        msg: "There is no connection to the PostgreSQL database.".to_string(),
+                    },
+                )));
+            }
+            Some(client) => {
+                if let Some(notification_capture) = &self.notification_capture {
+                    notification_capture.capture_account(&account, slot, is_startup)?;
                 }
-                measure_select.stop();
+                let mut measure_update =
+                    Measure::start("accountsdb-plugin-postgres-update-account-client");
+                let result = { client.update_account(&account, slot, is_startup) };
+                measure_update.stop();
+
                 inc_new_counter_debug!(
-                    "accountsdb-plugin-postgres-update-account-select-us",
-                    measure_select.as_us() as usize,
+                    "accountsdb-plugin-postgres-update-account-client-us",
+                    measure_update.as_us() as usize,
                     100000,
                     100000
                 );
 
-                debug!(
-                    "Updating account {:?} with owner {:?} at slot {:?} using account selector {:?}",
-                    bs58::encode(account.pubkey).into_string(),
-                    bs58::encode(account.owner).into_string(),
-                    slot,
-                    self.accounts_selector.as_ref().unwrap()
-                );
-
-                match &self.client {
-                    None => {
-                        return Err(GeyserPluginError::Custom(Box::new(
-                            AccountsDbPluginPostgresError::DataStoreConnectionError {
-                                msg: "There is no connection to the PostgreSQL database."
-                                    .to_string(),
-                            },
-                        )));
-                    }
-                    Some(client) => {
-                        let mut measure_update =
-                            Measure::start("accountsdb-plugin-postgres-update-account-client");
-                        let result = { client.update_account(account, slot, is_startup) };
-                        measure_update.stop();
-
-                        inc_new_counter_debug!(
-                            "accountsdb-plugin-postgres-update-account-client-us",
-                            measure_update.as_us() as usize,
-                            100000,
-                            100000
-                        );
-
-                        if let Err(err) = result {
-                            return Err(GeyserPluginError::AccountsUpdateError {
-                                msg: format!("Failed to persist the update of account to the PostgreSQL database. Error: {:?}", err)
-                            });
-                        }
-                    }
+                if let Err(err) = result {
+                    return Err(GeyserPluginError::AccountsUpdateError {
+                        msg: format!("Failed to persist the update of account to the PostgreSQL database. Error: {:?}", err)
+                    });
                 }
             }
-            _ => {
-                return Err(GeyserPluginError::AccountsUpdateError {
-                    msg: "Failed to persist the update of account to the PostgreSQL database.Unsupport Account Version".to_string()
-                });
-            }
         }
 
         measure_all.stop();
@@ -256,6 +1707,19 @@ impl GeyserPlugin for AccountsDbPluginPostgres {
 
     fn update_slot_status(&self, slot: u64, parent: Option<u64>, status: &SlotStatus) -> Result<()> {
         info!("Updating slot {:?} at with status {:?}", slot, status);
+        if self.selector_dry_run.is_some() {
+            return Ok(());
+        }
+        // The slots past the stop slot are not selected, but their rooting still stops
+        // the writes, as the stop slot itself may be skipped
+        let is_stop_slot_reached = self.unload_at_stop_slot
+            && *status == SlotStatus::Rooted
+            && self.slot_range.is_stop_slot_reached(slot)
+            && !self.is_stopped.load(Ordering::Relaxed);
+        let is_slot_selected = self.is_slot_selected(slot);
+        if !is_slot_selected && !is_stop_slot_reached {
+            return Ok(());
+        }
 
         match &self.client {
             None => {
@@ -266,12 +1730,25 @@ impl GeyserPlugin for AccountsDbPluginPostgres {
                 )));
             }
             Some(client) => {
-                let result = client.update_slot_status(slot, parent, status.clone());
+                if is_slot_selected {
+                    if let Some(notification_capture) = &self.notification_capture {
+                        notification_capture.capture_slot_status(slot, parent, status)?;
+                    }
+                    let result = client.update_slot_status(slot, parent, status.clone());
 
-                if let Err(err) = result {
-                    return Err(GeyserPluginError::SlotStatusUpdateError{
-                        msg: format!("Failed to persist the update of slot to the PostgreSQL database. Error: {:?}", err)
-                    });
+                    if let Err(err) = result {
+                        return Err(GeyserPluginError::SlotStatusUpdateError{
+                            msg: format!("Failed to persist the update of slot to the PostgreSQL database. Error: {:?}", err)
+                        });
+                    }
+                }
+
+                if is_stop_slot_reached && !self.is_stopped.swap(true, Ordering::Relaxed) {
+                    info!(
+                        "Reached the stop slot at slot {:?}, stopping the writes",
+                        slot
+                    );
+                    client.stop();
                 }
             }
         }
@@ -281,6 +1758,9 @@ impl GeyserPlugin for AccountsDbPluginPostgres {
 
     fn notify_end_of_startup(&self) -> Result<()> {
         info!("Notifying the end of startup for accounts notifications");
+        if self.selector_dry_run.is_some() {
+            return Ok(());
+        }
         match &self.client {
             None => {
                 return Err(GeyserPluginError::Custom(Box::new(
@@ -307,6 +1787,9 @@ impl GeyserPlugin for AccountsDbPluginPostgres {
         transaction_info: ReplicaTransactionInfoVersions,
         slot: u64,
     ) -> Result<()> {
+        if !self.is_slot_selected(slot) {
+            return Ok(());
+        }
         match &self.client {
             None => {
                 return Err(GeyserPluginError::Custom(Box::new(
@@ -315,39 +1798,60 @@ impl GeyserPlugin for AccountsDbPluginPostgres {
                     },
                 )));
             }
-            Some(client) => match transaction_info {
-                ReplicaTransactionInfoVersions::V0_0_1(_transaction_info) => {
-                    return Err(GeyserPluginError::SlotStatusUpdateError {
-                        msg: format!("Wrong ReplicaTransactionInfoVersions version: V0_0_1."),
+            Some(client) => {
+                let transaction_info = geyser_interface::transaction_info(transaction_info)?;
+                // The block JSON and the slot economics include all the transactions
+                // of the block, regardless of the transaction_selector.
+                if let Err(err) = client.buffer_block_transaction(transaction_info, slot) {
+                    return Err(GeyserPluginError::TransactionUpdateError {
+                        msg: format!(
+                            "Failed to buffer the transaction of the block. Error: {:?}",
+                            err
+                        ),
                     });
                 }
-                ReplicaTransactionInfoVersions::V0_0_2(transaction_info) => {
-                    if let Some(transaction_selector) = &self.transaction_selector {
-                        if !transaction_selector.is_transaction_selected(
-                            transaction_info.is_vote,
-                            Box::new(transaction_info.transaction.message().account_keys().iter()),
-                        ) {
-                            return Ok(());
-                        }
-                    } else {
+
+                if self.store_vote_activity && transaction_info.is_vote {
+                    if let Err(err) = client.log_vote_activity(transaction_info, slot) {
+                        return Err(GeyserPluginError::TransactionUpdateError {
+                            msg: format!("Failed to persist the vote activity to the PostgreSQL database. Error: {:?}", err)
+                        });
+                    }
+                }
+
+                if let Some(transaction_selector) = &self.selectors().transaction_selector {
+                    if !transaction_selector.is_transaction_selected(
+                        transaction_info.is_vote,
+                        transaction_info.transaction_status_meta.status.is_err(),
+                        Box::new(transaction_info.transaction.message().account_keys().iter()),
+                    ) && !client.is_transaction_allowlisted(transaction_info)
+                    {
                         return Ok(());
                     }
+                } else {
+                    return Ok(());
+                }
 
-                    let result = client.log_transaction_info(transaction_info, slot);
+                if let Some(notification_capture) = &self.notification_capture {
+                    notification_capture.capture_transaction(transaction_info, slot)?;
+                }
+                let result = client.log_transaction_info(transaction_info, slot);
 
-                    if let Err(err) = result {
-                        return Err(GeyserPluginError::SlotStatusUpdateError{
-                                msg: format!("Failed to persist the transaction info to the PostgreSQL database. Error: {:?}", err)
-                            });
-                    }
+                if let Err(err) = result {
+                    return Err(GeyserPluginError::SlotStatusUpdateError{
+                            msg: format!("Failed to persist the transaction info to the PostgreSQL database. Error: {:?}", err)
+                        });
                 }
-            },
+            }
         }
 
         Ok(())
     }
 
     fn notify_block_metadata(&self, block_info: ReplicaBlockInfoVersions) -> Result<()> {
+        if self.selector_dry_run.is_some() || !self.store_block_metadata {
+            return Ok(());
+        }
         match &self.client {
             None => {
                 return Err(GeyserPluginError::Custom(Box::new(
@@ -356,22 +1860,52 @@ impl GeyserPlugin for AccountsDbPluginPostgres {
                     },
                 )));
             }
-            Some(client) => match block_info {
-                ReplicaBlockInfoVersions::V0_0_4(block_info) => {
-                    let result = client.update_block_metadata(block_info);
-
-                    if let Err(err) = result {
-                        return Err(GeyserPluginError::SlotStatusUpdateError{
-                                msg: format!("Failed to persist the update of block metadata to the PostgreSQL database. Error: {:?}", err)
-                            });
-                    }
+            Some(client) => {
+                let block_info = geyser_interface::block_info(block_info)?;
+                if !self.is_slot_selected(block_info.slot) {
+                    return Ok(());
+                }
+                if let Some(notification_capture) = &self.notification_capture {
+                    notification_capture.capture_block_metadata(block_info)?;
                 }
-                _ => {
+                let result = client.update_block_metadata(block_info);
+
+                if let Err(err) = result {
                     return Err(GeyserPluginError::SlotStatusUpdateError{
-                        msg: format!("Failed to persist the update of block metadata to the PostgreSQL database. Error: {:?}", "Unsupported version")
+                            msg: format!("Failed to persist the update of block metadata to the PostgreSQL database. Error: {:?}", err)
+                        });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn notify_entry(&self, entry_info: ReplicaEntryInfoVersions) -> Result<()> {
+        match &self.client {
+            None => {
+                return Err(GeyserPluginError::Custom(Box::new(
+                    AccountsDbPluginPostgresError::DataStoreConnectionError {
+                        msg: "There is no connection to the PostgreSQL database.".to_string(),
+                    },
+                )));
+            }
+            Some(client) => {
+                let entry_info = geyser_interface::entry_info(entry_info)?;
+                if !self.is_slot_selected(entry_info.slot) {
+                    return Ok(());
+                }
+                let result = client.update_entry(entry_info);
+
+                if let Err(err) = result {
+                    return Err(GeyserPluginError::SlotStatusUpdateError {
+                        msg: format!(
+                            "Failed to persist the entry to the PostgreSQL database. Error: {:?}",
+                            err
+                        ),
                     });
                 }
-            },
+            }
         }
 
         Ok(())
@@ -381,50 +1915,111 @@ impl GeyserPlugin for AccountsDbPluginPostgres {
     /// Default is true -- if the plugin is not interested in
     /// account data, please return false.
     fn account_data_notifications_enabled(&self) -> bool {
-        self.accounts_selector
-            .as_ref()
-            .map_or_else(|| false, |selector| selector.is_enabled())
+        let selectors = self.selectors();
+        [false, true].into_iter().any(|is_startup| {
+            selectors
+                .accounts_selector(is_startup)
+                .is_some_and(|selector| selector.is_enabled())
+        })
+    }
+
+    /// Check if the plugin is interested in account data from snapshot
+    fn account_data_snapshot_notifications_enabled(&self) -> bool {
+        !self.skip_startup_accounts
+            && self
+                .selectors()
+                .accounts_selector(true)
+                .is_some_and(|selector| selector.is_enabled())
+    }
+
+    /// Check if the plugin is interested in entry data
+    fn entry_notifications_enabled(&self) -> bool {
+        self.store_entries && self.selector_dry_run.is_none()
     }
 
     /// Check if the plugin is interested in transaction data
     fn transaction_notifications_enabled(&self) -> bool {
-        self.transaction_selector
-            .as_ref()
-            .map_or_else(|| false, |selector| selector.is_enabled())
+        // The transactions are not counted by the dry run of the selectors
+        if self.selector_dry_run.is_some() {
+            return false;
+        }
+        self.store_vote_activity
+            || self.store_block_json
+            || self.store_slot_economics
+            || self
+                .client
+                .as_ref()
+                .is_some_and(|client| client.has_transaction_allowlist())
+            || self
+                .selectors()
+                .transaction_selector
+                .as_ref()
+                .map_or_else(|| false, |selector| selector.is_enabled())
     }
 }
 
 impl AccountsDbPluginPostgres {
+    /// Check if the notification at the slot is to be stored
+    fn is_slot_selected(&self, slot: u64) -> bool {
+        !self.is_stopped.load(Ordering::Relaxed) && self.slot_range.is_slot_selected(slot)
+    }
+
+    /// Get the current selectors, which the selector reloader may swap afterwards
+    fn selectors(&self) -> Arc<Selectors> {
+        self.selectors.read().unwrap().clone()
+    }
+
     fn create_accounts_selector_from_config(config: &serde_json::Value) -> AccountsSelector {
         let accounts_selector = &config["accounts_selector"];
 
         if accounts_selector.is_null() {
             AccountsSelector::default()
         } else {
-            let accounts = &accounts_selector["accounts"];
-            let accounts: Vec<String> = if accounts.is_array() {
-                accounts
-                    .as_array()
-                    .unwrap()
-                    .iter()
-                    .map(|val| val.as_str().unwrap().to_string())
-                    .collect()
-            } else {
-                Vec::default()
-            };
-            let owners = &accounts_selector["owners"];
-            let owners: Vec<String> = if owners.is_array() {
-                owners
-                    .as_array()
+            Self::create_accounts_selector_from_section(accounts_selector)
+        }
+    }
+
+    fn create_startup_accounts_selector_from_config(
+        config: &serde_json::Value,
+    ) -> Option<AccountsSelector> {
+        let startup_accounts_selector = &config["startup_accounts_selector"];
+
+        if startup_accounts_selector.is_null() {
+            None
+        } else {
+            Some(Self::create_accounts_selector_from_section(
+                startup_accounts_selector,
+            ))
+        }
+    }
+
+    fn create_accounts_selector_from_section(
+        accounts_selector: &serde_json::Value,
+    ) -> AccountsSelector {
+        let keys = |name: &str| -> Vec<String> {
+            let keys = &accounts_selector[name];
+            if keys.is_array() {
+                keys.as_array()
                     .unwrap()
                     .iter()
                     .map(|val| val.as_str().unwrap().to_string())
                     .collect()
             } else {
                 Vec::default()
-            };
-            AccountsSelector::new(&accounts, &owners)
-        }
+            }
+        };
+        let rules: Vec<AccountsSelectorRuleConfig> =
+            serde_json::from_value(accounts_selector["rules"].clone())
+                .ok()
+                .flatten()
+                .unwrap_or_default();
+        let owners = owners_with_preset(&keys("owners"), accounts_selector["preset"].as_str());
+        AccountsSelector::new(&keys("accounts"), &owners, &rules)
+            .with_exclusions(&keys("exclude_accounts"), &keys("exclude_owners"))
+            .with_lamports_range(
+                accounts_selector["min_lamports"].as_u64(),
+                accounts_selector["max_lamports"].as_u64(),
+            )
     }
 
     fn create_transaction_selector_from_config(config: &serde_json::Value) -> TransactionSelector {
@@ -433,18 +2028,26 @@ impl AccountsDbPluginPostgres {
         if transaction_selector.is_null() {
             TransactionSelector::default()
         } else {
-            let accounts = &transaction_selector["mentions"];
-            let accounts: Vec<String> = if accounts.is_array() {
-                accounts
-                    .as_array()
-                    .unwrap()
-                    .iter()
-                    .map(|val| val.as_str().unwrap().to_string())
-                    .collect()
-            } else {
-                Vec::default()
+            let to_addresses = |addresses: &serde_json::Value| -> Vec<String> {
+                if addresses.is_array() {
+                    addresses
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|val| val.as_str().unwrap().to_string())
+                        .collect()
+                } else {
+                    Vec::default()
+                }
             };
-            TransactionSelector::new(&accounts)
+            let accounts = to_addresses(&transaction_selector["mentions"]);
+            let all_accounts = to_addresses(&transaction_selector["all_mentions"]);
+            let flag = |name: &str| transaction_selector[name].as_bool().unwrap_or_default();
+            TransactionSelector::new(&accounts, &all_accounts).with_filters(
+                flag("exclude_votes"),
+                flag("include_only_votes"),
+                flag("exclude_failed"),
+            )
         }
     }
 
@@ -477,4 +2080,28 @@ pub(crate) mod tests {
         let config: serde_json::Value = serde_json::from_str(config).unwrap();
         AccountsDbPluginPostgres::create_accounts_selector_from_config(&config);
     }
+
+    #[test]
+    fn test_startup_accounts_selector_from_config() {
+        let config = "{\"accounts_selector\" : { \
+           \"accounts\" : [\"*\"] \
+        }, \
+        \"startup_accounts_selector\" : { \
+           \"owners\" : [\"9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin\"] \
+        }}";
+
+        let config: serde_json::Value = serde_json::from_str(config).unwrap();
+        let selectors = Selectors::from_config(&config);
+        let owner = bs58::decode("9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin")
+            .into_vec()
+            .unwrap();
+        let other_owner = bs58::decode("9oT9R5ZyRovSVnt37QvVoBttGpNqR3J7unkb567NP8k3")
+            .into_vec()
+            .unwrap();
+        let startup_selector = selectors.accounts_selector(true).unwrap();
+        assert!(startup_selector.is_account_selected(&[], &owner, &[]));
+        assert!(!startup_selector.is_account_selected(&[], &other_owner, &[]));
+        let live_selector = selectors.accounts_selector(false).unwrap();
+        assert!(live_selector.is_account_selected(&[], &other_owner, &[]));
+    }
 }
@@ -0,0 +1,41 @@
+/// Archive the history of the accounts older than a slot, the rows of the account_audit
+/// table, to the account_audit_archive_path before deleting it, with the config file of
+/// the plugin:
+///
+/// archive_account_audit <config_file> <slot>
+use {
+    log::*,
+    solana_accountsdb_plugin_postgres::{
+        accountsdb_plugin_postgres::AccountsDbPluginPostgresConfig,
+        postgres_client::SimplePostgresClient,
+    },
+    std::{path::Path, process::exit},
+};
+
+fn main() {
+    solana_logger::setup_with_default("info");
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 3 {
+        eprintln!("Usage: {} <config_file> <slot>", args[0]);
+        exit(2);
+    }
+    let Ok(before_slot) = args[2].parse::<u64>() else {
+        eprintln!("Invalid slot {:?}", args[2]);
+        exit(2);
+    };
+
+    let result = AccountsDbPluginPostgresConfig::load(Path::new(&args[1]))
+        .and_then(|config| SimplePostgresClient::archive_account_audit(&config, before_slot));
+    match result {
+        Ok(report) => {
+            info!(
+                "Archived the account history before the slot {}: {} partitions dropped, {} rows deleted",
+                before_slot, report.partitions, report.rows
+            );
+        }
+        Err(err) => {
+            error!("Failed to archive the account history: ({})", err);
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+/// Check the config file of the plugin before a validator loads it: the fields and the
+/// selectors are validated as the plugin does, and each database is connected to, its
+/// schema version checked and its tables and indexes compared with the expected ones.
+/// With --suggest-config, the databases and the machine are probed instead, and the
+/// write settings suggested for them are printed as the fields of the config file:
+///
+/// check_config <config_file> [--suggest-config]
+use {
+    solana_accountsdb_plugin_postgres::{
+        accountsdb_plugin_postgres::AccountsDbPluginPostgresConfig,
+        postgres_client::{suggest_config, MachineProbe, SimplePostgresClient},
+    },
+    std::{path::Path, process::exit},
+};
+
+/// Probe the databases and the machine, and print the write settings suggested.
+fn print_suggested_config(config: &AccountsDbPluginPostgresConfig) {
+    let machine = MachineProbe::probe();
+    match machine.memory_bytes {
+        Some(memory_bytes) => println!(
+            "Machine: {} cores, {} MiB of memory",
+            machine.cores,
+            memory_bytes >> 20
+        ),
+        None => println!("Machine: {} cores, unknown memory", machine.cores),
+    }
+    let mut databases = Vec::new();
+    for (index, probe) in SimplePostgresClient::probe_databases(config)
+        .into_iter()
+        .enumerate()
+    {
+        match probe {
+            Ok(probe) => {
+                println!(
+                    "Database {}: version {}, round trip {:?}, {} of {} connections used",
+                    index + 1,
+                    probe.version,
+                    probe.round_trip,
+                    probe.connections_used,
+                    probe.max_connections
+                );
+                databases.push(probe);
+            }
+            Err(err) => {
+                println!("Database {}: {}", index + 1, err);
+                exit(1);
+            }
+        }
+    }
+    let suggestion = suggest_config(config, &databases, &machine);
+    println!("Suggested settings:");
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&suggestion.to_json()).unwrap()
+    );
+}
+
+fn main() {
+    solana_logger::setup_with_default("warn");
+    let args: Vec<String> = std::env::args().collect();
+    let suggest = args.len() == 3 && args[2] == "--suggest-config";
+    if args.len() != 2 && !suggest {
+        eprintln!("Usage: {} <config_file> [--suggest-config]", args[0]);
+        exit(2);
+    }
+
+    let config = match AccountsDbPluginPostgresConfig::load(Path::new(&args[1])) {
+        Ok(config) => config,
+        Err(err) => {
+            println!("The config file is invalid: {}", err);
+            exit(1);
+        }
+    };
+    println!("The config file is valid");
+    if suggest {
+        print_suggested_config(&config);
+        return;
+    }
+
+    let reports = SimplePostgresClient::check_databases(&config);
+    let mut problem_count = 0;
+    for (index, report) in reports.iter().enumerate() {
+        println!("Database {} of {}:", index + 1, reports.len());
+        match report {
+            Ok(report) => {
+                match report.schema_version {
+                    Some(version) => println!("  schema version {}", version),
+                    None => println!("  no schema"),
+                }
+                for problem in &report.problems {
+                    println!("  {}", problem);
+                }
+                problem_count += report.problems.len();
+            }
+            Err(err) => {
+                println!("  {}", err);
+                problem_count += 1;
+            }
+        }
+    }
+    if problem_count > 0 {
+        println!("Found {} problems", problem_count);
+        exit(1);
+    }
+    println!("No problems found");
+}
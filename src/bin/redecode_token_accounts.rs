@@ -0,0 +1,39 @@
+/// Decode again the token accounts and mints decoded by an earlier version of the
+/// decoding of the plugin, from their raw accounts, with the config file of the plugin:
+///
+/// redecode_token_accounts <config_file>
+use {
+    log::*,
+    solana_accountsdb_plugin_postgres::{
+        accountsdb_plugin_postgres::AccountsDbPluginPostgresConfig,
+        postgres_client::{SimplePostgresClient, Tunnel, TOKEN_DECODER_VERSION},
+    },
+    std::{path::Path, process::exit},
+};
+
+fn main() {
+    solana_logger::setup_with_default("info");
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 2 {
+        eprintln!("Usage: {} <config_file>", args[0]);
+        exit(2);
+    }
+
+    let result =
+        AccountsDbPluginPostgresConfig::load(Path::new(&args[1])).and_then(|mut config| {
+            let _tunnel = Tunnel::start(&mut config)?;
+            SimplePostgresClient::new(&config)?.redecode_token_accounts()
+        });
+    match result {
+        Ok(report) => {
+            info!(
+                "Decoded again {} token accounts and mints to the version {}, {} remaining without a raw account to decode",
+                report.redecoded, TOKEN_DECODER_VERSION, report.remaining
+            );
+        }
+        Err(err) => {
+            error!("Failed to decode again the token accounts: ({})", err);
+            exit(1);
+        }
+    }
+}
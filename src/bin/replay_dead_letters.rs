@@ -0,0 +1,45 @@
+/// Replay the dead letters of the plugin, the writes failed repeatedly and written to
+/// the dead_letter table, with the config file of the plugin:
+///
+/// replay_dead_letters <config_file> [account|transaction]
+use {
+    log::*,
+    solana_accountsdb_plugin_postgres::{
+        accountsdb_plugin_postgres::AccountsDbPluginPostgresConfig,
+        postgres_client::{SimplePostgresClient, Tunnel},
+    },
+    std::{path::Path, process::exit},
+};
+
+fn main() {
+    solana_logger::setup_with_default("info");
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 || args.len() > 3 {
+        eprintln!("Usage: {} <config_file> [account|transaction]", args[0]);
+        exit(2);
+    }
+    let stream = args.get(2).map(String::as_str);
+
+    let result =
+        AccountsDbPluginPostgresConfig::load(Path::new(&args[1])).and_then(|mut config| {
+            // The dead letters failing again are updated in place instead
+            config.dead_letter_attempts = None;
+            let _tunnel = Tunnel::start(&mut config)?;
+            SimplePostgresClient::new(&config)?.replay_dead_letters(stream)
+        });
+    match result {
+        Ok(report) => {
+            info!(
+                "Replayed {} dead letters, {} failed again",
+                report.replayed, report.failed
+            );
+            if report.failed > 0 {
+                exit(1);
+            }
+        }
+        Err(err) => {
+            error!("Failed to replay the dead letters: ({})", err);
+            exit(1);
+        }
+    }
+}
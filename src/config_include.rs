@@ -0,0 +1,284 @@
+/// The config include is responsible for loading the plugin config file along with the
+/// base config files it includes, so a shared base can be overlaid per host, and for
+/// selecting the section of the cluster of the validator.
+use {
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    std::{
+        fs,
+        path::{Path, PathBuf},
+    },
+};
+
+const INCLUDE_KEY: &str = "include";
+/// The suffix of the keys of the selectors, like accounts_selector
+const SELECTOR_KEY_SUFFIX: &str = "_selector";
+const CLUSTER_KEY: &str = "cluster";
+const CLUSTERS_KEY: &str = "clusters";
+
+/// Merge the overlay into the base config. Objects are merged recursively, except the
+/// selectors which the overlay replaces as a whole, since merging the fields of two
+/// selectors widens the selection. Any other value in the overlay replaces the one in
+/// the base.
+fn merge_config(base: &mut serde_json::Value, overlay: serde_json::Value) {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(base_value) if !key.ends_with(SELECTOR_KEY_SUFFIX) => {
+                        merge_config(base_value, value)
+                    }
+                    _ => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn read_config_error(path: &Path, msg: impl std::fmt::Display) -> GeyserPluginError {
+    GeyserPluginError::ConfigFileReadError {
+        msg: format!("Failed to read the config file {:?}: {}", path, msg),
+    }
+}
+
+/// Get the paths of the config files included, relative paths are resolved against
+/// the directory of the including file.
+fn included_paths(
+    path: &Path,
+    include: &serde_json::Value,
+) -> Result<Vec<PathBuf>, GeyserPluginError> {
+    let includes = match include {
+        serde_json::Value::String(include) => vec![include.as_str()],
+        serde_json::Value::Array(includes) => includes
+            .iter()
+            .map(|include| {
+                include.as_str().ok_or_else(|| {
+                    read_config_error(path, "the \"include\" entries must be strings")
+                })
+            })
+            .collect::<Result<_, _>>()?,
+        _ => {
+            return Err(read_config_error(
+                path,
+                "\"include\" must be a string or an array of strings",
+            ))
+        }
+    };
+    let config_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    Ok(includes
+        .into_iter()
+        .map(|include| config_dir.join(include))
+        .collect())
+}
+
+fn load_config_file_with_includes(
+    path: &Path,
+    including: &mut Vec<PathBuf>,
+) -> Result<serde_json::Value, GeyserPluginError> {
+    let canonical_path = fs::canonicalize(path).map_err(|err| read_config_error(path, err))?;
+    if including.contains(&canonical_path) {
+        return Err(read_config_error(path, "the config file includes itself"));
+    }
+
+    let contents = fs::read_to_string(path).map_err(|err| read_config_error(path, err))?;
+    let mut overlay: serde_json::Value = serde_json::from_str(&contents)
+        .map_err(|err| read_config_error(path, format!("not in the JSON format: {:?}", err)))?;
+
+    let include = overlay
+        .as_object_mut()
+        .and_then(|overlay| overlay.remove(INCLUDE_KEY));
+    let Some(include) = include else {
+        return Ok(overlay);
+    };
+
+    including.push(canonical_path);
+    let mut config = serde_json::Value::Object(serde_json::Map::default());
+    for included_path in included_paths(path, &include)? {
+        info!(
+            "Including the config file {:?} in {:?}",
+            included_path, path
+        );
+        let base = load_config_file_with_includes(&included_path, including)?;
+        merge_config(&mut config, base);
+    }
+    including.pop();
+
+    merge_config(&mut config, overlay);
+    Ok(config)
+}
+
+/// Select the section of the cluster in the `clusters` key, its fields replace the
+/// ones of the config as a whole so that the selectors of the clusters are independent.
+fn select_cluster_section(
+    path: &Path,
+    config: &mut serde_json::Value,
+) -> Result<(), GeyserPluginError> {
+    let Some(config) = config.as_object_mut() else {
+        return Ok(());
+    };
+    let Some(clusters) = config.remove(CLUSTERS_KEY) else {
+        return Ok(());
+    };
+    let serde_json::Value::Object(mut clusters) = clusters else {
+        return Err(read_config_error(
+            path,
+            "\"clusters\" must be an object keyed by the cluster",
+        ));
+    };
+    let cluster_names: Vec<String> = clusters.keys().cloned().collect();
+    let section = match config.get(CLUSTER_KEY) {
+        Some(serde_json::Value::String(cluster)) => {
+            clusters.remove(cluster.as_str()).ok_or_else(|| {
+                read_config_error(
+                    path,
+                    format!(
+                        "the \"cluster\": {:?} is not one of the \"clusters\": {:?}",
+                        cluster, cluster_names
+                    ),
+                )
+            })?
+        }
+        _ => {
+            return Err(read_config_error(
+                path,
+                format!(
+                    "\"cluster\" must be specified to select one of the \"clusters\": {:?}",
+                    cluster_names
+                ),
+            ))
+        }
+    };
+    let serde_json::Value::Object(section) = section else {
+        return Err(read_config_error(
+            path,
+            "the sections of the \"clusters\" must be objects",
+        ));
+    };
+
+    info!(
+        "Selected the section of the cluster {:?} in the config file {:?}",
+        config[CLUSTER_KEY], path
+    );
+    for (key, value) in section {
+        config.insert(key, value);
+    }
+    Ok(())
+}
+
+/// Load the config file, the files listed in its `include` key are loaded first in
+/// order, with the fields of the including file taking precedence. The section of the
+/// cluster is then selected from the `clusters` key.
+pub(crate) fn load_config_file(path: &Path) -> Result<serde_json::Value, GeyserPluginError> {
+    let mut config = load_config_file_with_includes(path, &mut Vec::default())?;
+    select_cluster_section(path, &mut config)?;
+    Ok(config)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, serde_json::json};
+
+    #[test]
+    fn test_merge_config() {
+        let mut config = json!({
+            "host": "postgres-server",
+            "threads": 20,
+            "accounts_selector": {"accounts": ["*"]},
+            "degradation_ladder": {"steps": [], "sustain_secs": 10},
+        });
+        merge_config(
+            &mut config,
+            json!({
+                "threads": 4,
+                "accounts_selector": {"owners": ["9oT9R5ZyRovSVnt37QvVoBttGpNqR3J7unkb567NP8k3"]},
+                "degradation_ladder": {"sustain_secs": 20},
+            }),
+        );
+        // The selector of the overlay replaces the one of the base rather than selecting
+        // all the accounts along with the owners
+        assert_eq!(
+            config,
+            json!({
+                "host": "postgres-server",
+                "threads": 4,
+                "accounts_selector": {
+                    "owners": ["9oT9R5ZyRovSVnt37QvVoBttGpNqR3J7unkb567NP8k3"],
+                },
+                "degradation_ladder": {"steps": [], "sustain_secs": 20},
+            })
+        );
+    }
+
+    #[test]
+    fn test_load_config_file() {
+        let dir = std::env::temp_dir().join(format!("config-include-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("base.json"),
+            "{\"host\": \"postgres-server\", \"user\": \"solana\", \"threads\": 20}",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("host.json"),
+            "{\"include\": \"base.json\", \"threads\": 4}",
+        )
+        .unwrap();
+        fs::write(dir.join("cycle.json"), "{\"include\": [\"cycle.json\"]}").unwrap();
+
+        let config = load_config_file(&dir.join("host.json")).unwrap();
+        assert_eq!(
+            config,
+            json!({"host": "postgres-server", "user": "solana", "threads": 4})
+        );
+        assert!(load_config_file(&dir.join("cycle.json")).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_select_cluster_section() {
+        let path = Path::new("validator.json");
+        let clusters = json!({
+            "mainnet-beta": {
+                "schema": "mainnet",
+                "accounts_selector": {"owners": ["TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"]},
+            },
+            "testnet": {"schema": "testnet", "threads": 2},
+        });
+        let mut config = json!({
+            "host": "postgres-server",
+            "threads": 20,
+            "accounts_selector": {"accounts": ["*"]},
+            "cluster": "mainnet-beta",
+            "clusters": clusters.clone(),
+        });
+        select_cluster_section(path, &mut config).unwrap();
+        // The selector of the cluster replaces the one of the config
+        assert_eq!(
+            config,
+            json!({
+                "host": "postgres-server",
+                "threads": 20,
+                "accounts_selector": {"owners": ["TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"]},
+                "cluster": "mainnet-beta",
+                "schema": "mainnet",
+            })
+        );
+
+        let mut config = json!({"cluster": "devnet", "clusters": clusters.clone()});
+        assert!(select_cluster_section(path, &mut config).is_err());
+        let mut config = json!({"clusters": clusters});
+        assert!(select_cluster_section(path, &mut config).is_err());
+
+        // The config without the clusters is left as is
+        let mut config = json!({"host": "postgres-server", "cluster": "testnet"});
+        select_cluster_section(path, &mut config).unwrap();
+        assert_eq!(
+            config,
+            json!({"host": "postgres-server", "cluster": "testnet"})
+        );
+    }
+}
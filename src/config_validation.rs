@@ -0,0 +1,2671 @@
+/// The config validation is responsible for parsing the plugin config file strictly,
+/// rejecting unknown fields with suggestions and values out of the supported ranges.
+use {
+    crate::{
+        accounts_selector::{preset_owners, ACCOUNTS_SELECTOR_PRESETS},
+        accountsdb_plugin_postgres::{
+            AccountDataCompressionConfig, AccountDataDecoderConfig, AccountLayout,
+            AccountsDbPluginPostgresConfig, AccountsSelectorConfig, AccountsSelectorRuleConfig,
+            BatchConfig, BatchLimitConfig, ClientKind, CredentialsConfig, DegradationAction,
+            DegradationLadderConfig, Distributed, Footprint, MetricsConfig, ShardingConfig,
+            ShardingStrategy, SignatureStorage, StorageEngine, TokenAccountStorage,
+            TransactionSelectorConfig, TunnelConfig,
+        },
+        postgres_client::{
+            check_expression, ACCOUNT_COLUMNS, ASYNC_CLIENT_FIELDS, DATA_JSON_COLUMN,
+            DEFAULT_ACCOUNTS_INSERT_BATCH_SIZE, DEFAULT_ASYNC_POOL_SIZE, DEFAULT_THREADS_COUNT,
+            DEFAULT_USE_SCHEMA_TRIGGERS, DISCRIMINATOR_COLUMN, MAX_ACCOUNTS_INSERT_BATCH_SIZE,
+            MAX_ASYNC_REQUESTS, PARSED_INSTRUCTION_PROGRAMS, POST_COMMIT_HOOK_TABLES,
+            STATEMENT_TIMEOUT_TABLES,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    serde::{
+        de::{self, value, Deserialize, Deserializer, Visitor},
+        forward_to_deserialize_any,
+    },
+    std::collections::BTreeMap,
+};
+
+/// The highest compression level of zstd
+const MAX_ZSTD_LEVEL: i32 = 22;
+/// The largest edit distance for an unknown field to be suggested a known one
+const MAX_SUGGESTION_DISTANCE: usize = 3;
+/// The least threads and batch size of the writes of all the accounts and transactions
+/// to be accepted without the "acknowledge_high_volume"
+const HIGH_VOLUME_MIN_THREADS: usize = 32;
+const HIGH_VOLUME_MIN_BATCH_SIZE: usize = 10;
+/// The statement batches of the account updates of the minimal footprint, about a slot
+/// long for the updates of the accounts updated in every slot to be coalesced
+const MINIMAL_FOOTPRINT_ACCOUNT_BATCH_SIZE: usize = 1000;
+const MINIMAL_FOOTPRINT_ACCOUNT_BATCH_MAX_DELAY_MS: u64 = 400;
+
+/// A deserializer capturing the field names of the struct being deserialized
+struct FieldNamesDeserializer<'a> {
+    fields: &'a mut &'static [&'static str],
+}
+
+impl<'de> Deserializer<'de> for FieldNamesDeserializer<'_> {
+    type Error = value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("only structs have field names"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.fields = fields;
+        Err(de::Error::custom("the field names are captured"))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+fn field_names<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNamesDeserializer {
+        fields: &mut fields,
+    });
+    fields
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut distances: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut previous_diagonal = distances[0];
+        distances[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous_diagonal + usize::from(a_char != *b_char);
+            previous_diagonal = distances[j + 1];
+            distances[j + 1] = substitution.min(distances[j] + 1).min(distances[j + 1] + 1);
+        }
+    }
+    distances[b.len()]
+}
+
+fn suggest_field(unknown: &str, fields: &[&'static str]) -> Option<&'static str> {
+    fields
+        .iter()
+        .map(|field| (edit_distance(unknown, field), *field))
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE && *distance < unknown.len())
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, field)| field)
+}
+
+/// Check the keys of the config object against the known fields, `path` locates the
+/// object in the config file for the error message.
+fn check_unknown_fields(
+    config: &serde_json::Value,
+    fields: &[&'static str],
+    path: &str,
+) -> Result<(), GeyserPluginError> {
+    let Some(config) = config.as_object() else {
+        return Ok(());
+    };
+    for key in config.keys() {
+        if fields.contains(&key.as_str()) {
+            continue;
+        }
+        let msg = match suggest_field(key, fields) {
+            Some(suggestion) => format!(
+                "Unknown field \"{}{}\" in the config file, did you mean \"{}{}\"?",
+                path, key, path, suggestion
+            ),
+            None => format!(
+                "Unknown field \"{}{}\" in the config file, the known fields are: {:?}",
+                path, key, fields
+            ),
+        };
+        return Err(GeyserPluginError::ConfigFileReadError { msg });
+    }
+    Ok(())
+}
+
+fn check_range<T: PartialOrd + std::fmt::Display>(
+    name: &str,
+    value: Option<T>,
+    min: T,
+    max: T,
+) -> Result<(), GeyserPluginError> {
+    match value {
+        Some(value) if value < min || value > max => Err(GeyserPluginError::ConfigFileReadError {
+            msg: format!(
+                "The value of \"{}\": {} is out of the range [{}, {}]",
+                name, value, min, max
+            ),
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn check_positive<T: Default + PartialEq + std::fmt::Display>(
+    name: &str,
+    value: Option<T>,
+) -> Result<(), GeyserPluginError> {
+    match value {
+        Some(value) if value == T::default() => Err(GeyserPluginError::ConfigFileReadError {
+            msg: format!(
+                "The value of \"{}\": {} must be greater than 0",
+                name, value
+            ),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Check the name can be used as an unquoted column or schema name
+fn is_column_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_lowercase() || first == '_')
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Check the exclusions and the rules of the accounts selector of the section are of
+/// valid base58 values, that the bounds of the lamports are ordered and that the rules
+/// can be satisfied, a rule without any condition would select all the accounts.
+fn check_accounts_selector(
+    section: &str,
+    accounts_selector: Option<&AccountsSelectorConfig>,
+) -> Result<(), GeyserPluginError> {
+    for (name, keys) in accounts_selector.into_iter().flat_map(|selector| {
+        [
+            ("exclude_accounts", &selector.exclude_accounts),
+            ("exclude_owners", &selector.exclude_owners),
+        ]
+    }) {
+        for key in keys.iter().flatten() {
+            if bs58::decode(key).into_vec().is_err() {
+                return Err(GeyserPluginError::ConfigFileReadError {
+                    msg: format!(
+                        "The value of \"{}.{}\": {:?} is not base58 encoded",
+                        section, name, key
+                    ),
+                });
+            }
+        }
+    }
+    if let Some(selector) = accounts_selector {
+        if let (Some(min_lamports), Some(max_lamports)) =
+            (selector.min_lamports, selector.max_lamports)
+        {
+            if min_lamports > max_lamports {
+                return Err(GeyserPluginError::ConfigFileReadError {
+                    msg: format!(
+                        "The \"{}.min_lamports\": {} must not be greater than the \"{}.max_lamports\": {}",
+                        section, min_lamports, section, max_lamports
+                    ),
+                });
+            }
+        }
+    }
+    if let Some(preset) = accounts_selector.and_then(|selector| selector.preset.as_deref()) {
+        if preset_owners(preset).is_none() {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The preset \"{}.preset\": {:?} is not one of the presets: {:?}",
+                    section,
+                    preset,
+                    ACCOUNTS_SELECTOR_PRESETS.map(|(name, _)| name)
+                ),
+            });
+        }
+    }
+    let rules = accounts_selector.and_then(|selector| selector.rules.as_deref());
+    for (i, rule) in rules.into_iter().flatten().enumerate() {
+        let path = format!("{}.rules[{}]", section, i);
+        if *rule == AccountsSelectorRuleConfig::default() {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!("The accounts selector rule \"{}\" has no condition", path),
+            });
+        }
+        for (name, value) in [
+            ("owner", &rule.owner),
+            ("discriminator", &rule.discriminator),
+        ] {
+            if let Some(value) = value {
+                if bs58::decode(value).into_vec().is_err() {
+                    return Err(GeyserPluginError::ConfigFileReadError {
+                        msg: format!(
+                            "The value of \"{}.{}\": {:?} is not base58 encoded",
+                            path, name, value
+                        ),
+                    });
+                }
+            }
+        }
+        if let (Some(min_data_size), Some(max_data_size)) = (rule.min_data_size, rule.max_data_size)
+        {
+            if min_data_size > max_data_size {
+                return Err(GeyserPluginError::ConfigFileReadError {
+                    msg: format!(
+                        "The \"{}.min_data_size\": {} must not be greater than the \"{}.max_data_size\": {}",
+                        path, min_data_size, path, max_data_size
+                    ),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check the config of the async client only sets the fields it supports, and that the
+/// fields of the async client are only set along with it.
+fn check_client_fields(value: &serde_json::Value) -> Result<(), GeyserPluginError> {
+    let is_async = value["client"] == "async";
+    for (name, field) in value.as_object().into_iter().flatten() {
+        if field.is_null() {
+            continue;
+        }
+        if is_async && !ASYNC_CLIENT_FIELDS.contains(&name.as_str()) {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The \"{}\" is not supported with the \"client\" 'async'",
+                    name
+                ),
+            });
+        }
+        if !is_async && name.starts_with("async_") {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!("The \"{}\" requires the \"client\" 'async'", name),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check the config selecting all the accounts and all the transactions, the whole
+/// firehose of the cluster, is not written by too few threads or in too small batches
+/// unless acknowledged, and warn of the load otherwise.
+fn check_high_volume(config: &AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    let is_wildcard = |values: &Option<Vec<String>>| values.iter().flatten().any(|v| v == "*");
+    // All the accounts are selected when the accounts selector is missing
+    let selects_all_accounts = config
+        .accounts_selector
+        .as_ref()
+        .is_none_or(|selector| is_wildcard(&selector.accounts) || is_wildcard(&selector.owners));
+    let selects_all_transactions = config
+        .transaction_selector
+        .as_ref()
+        .is_some_and(|selector| is_wildcard(&selector.mentions));
+    if !selects_all_accounts
+        || !selects_all_transactions
+        || config.acknowledge_high_volume.unwrap_or_default()
+    {
+        return Ok(());
+    }
+    // The pool of the async client writes one update per connection
+    if config.client == Some(ClientKind::Async) {
+        let pool_size = config.async_pool_size.unwrap_or(DEFAULT_ASYNC_POOL_SIZE);
+        if pool_size < HIGH_VOLUME_MIN_THREADS {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The \"accounts_selector\" and the \"transaction_selector\" select all the accounts and the transactions, written by the {} connections of the \"async_pool_size\". Set the \"async_pool_size\" to at least {}, or set \"acknowledge_high_volume\" to 'true' if the database is sized for it",
+                    pool_size, HIGH_VOLUME_MIN_THREADS
+                ),
+            });
+        }
+    } else {
+        let threads = config.threads.unwrap_or(DEFAULT_THREADS_COUNT);
+        let batch_size = config
+            .batch_size
+            .unwrap_or(DEFAULT_ACCOUNTS_INSERT_BATCH_SIZE);
+        if threads < HIGH_VOLUME_MIN_THREADS || batch_size < HIGH_VOLUME_MIN_BATCH_SIZE {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The \"accounts_selector\" and the \"transaction_selector\" select all the accounts and the transactions, written by {} \"threads\" in batches of {}. Set the \"threads\" to at least {} and the \"batch_size\" to at least {}, or set \"acknowledge_high_volume\" to 'true' if the database is sized for it",
+                    threads, batch_size, HIGH_VOLUME_MIN_THREADS, HIGH_VOLUME_MIN_BATCH_SIZE
+                ),
+            });
+        }
+    }
+    warn!(
+        "All the accounts and the transactions are selected, the database must sustain the write rate of the whole cluster, typically tens of thousands of account updates and thousands of transactions a second on mainnet. Set \"acknowledge_high_volume\" to 'true' to silence this warning."
+    );
+    Ok(())
+}
+
+fn validate_config(config: &AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    check_workers(config)?;
+    check_connection(config)?;
+    check_verification(config)?;
+    check_sampling(config)?;
+    check_selectors(config)?;
+    check_reconnection(config)?;
+    check_monitoring(config)?;
+    check_failover(config)?;
+    check_maintenance(config)?;
+    check_sharding(config)?;
+    check_queue(config)?;
+    check_storage(config)?;
+    check_audit(config)?;
+    check_slots(config)?;
+    check_columns(config)?;
+    check_instructions(config)?;
+    check_notifications(config)?;
+    Ok(())
+}
+
+/// Check the settings of the workers writing to the database.
+fn check_workers(config: &AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    check_positive("threads", config.threads)?;
+    check_positive("async_pool_size", config.async_pool_size)?;
+    check_positive("async_request_timeout_ms", config.async_request_timeout_ms)?;
+    check_positive("statement_timeout_ms", config.statement_timeout_ms)?;
+    check_range(
+        "batch_size",
+        config.batch_size,
+        1,
+        MAX_ACCOUNTS_INSERT_BATCH_SIZE,
+    )?;
+    check_positive("dead_letter_attempts", config.dead_letter_attempts)?;
+    check_positive("dedup_window_slots", config.dedup_window_slots)?;
+    check_positive("entry_batch_size", config.entry_batch_size)?;
+    check_range(
+        "worker_cpu_budget_percentage",
+        config.worker_cpu_budget_percentage,
+        1,
+        100,
+    )?;
+    check_positive("statement_batch_size", config.statement_batch_size)?;
+    check_positive("slot_commit_chunk_size", config.slot_commit_chunk_size)?;
+    if config.slot_commit_chunk_size.is_some() && config.commit_per_slot != Some(true) {
+        return Err(GeyserPluginError::ConfigFileReadError {
+            msg: "The \"slot_commit_chunk_size\" requires the \"commit_per_slot\" set to 'true'"
+                .to_string(),
+        });
+    }
+    if let Some(batch) = &config.batch {
+        for (kind, limit) in [
+            ("account", &batch.account),
+            ("transaction", &batch.transaction),
+            ("slot", &batch.slot),
+        ] {
+            check_positive(
+                &format!("batch.{}.size", kind),
+                limit.as_ref().and_then(|limit| limit.size),
+            )?;
+        }
+    }
+    for (table, timeout_ms) in config.table_statement_timeouts_ms.iter().flatten() {
+        if !STATEMENT_TIMEOUT_TABLES.contains(&table.as_str()) {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The statement timeout table \"{}\" is not one of the supported tables: {:?}",
+                    table, STATEMENT_TIMEOUT_TABLES
+                ),
+            });
+        }
+        check_positive(
+            &format!("table_statement_timeouts_ms.{}", table),
+            Some(*timeout_ms),
+        )?;
+    }
+    Ok(())
+}
+
+/// Check the settings of the connection to the database.
+fn check_connection(config: &AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    check_positive("port", config.port)?;
+    check_positive(
+        "connection_keepalive_interval_secs",
+        config.connection_keepalive_interval_secs,
+    )?;
+    if let Some(tunnel) = &config.tunnel {
+        check_tunnel(config, tunnel)?;
+    }
+    if let Some(credentials) = &config.credentials {
+        check_credentials(config, credentials)?;
+    }
+    check_positive("startup_wait_secs", config.startup_wait_secs)?;
+    Ok(())
+}
+
+/// Check the settings of the verification of the data written.
+fn check_verification(config: &AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    check_positive(
+        "block_chain_verification_interval",
+        config.block_chain_verification_interval,
+    )?;
+    check_range(
+        "write_verification_percentage",
+        config.write_verification_percentage,
+        0.0,
+        100.0,
+    )?;
+    check_positive(
+        "verification_max_lag_secs",
+        config.verification_max_lag_secs,
+    )?;
+    if config.verification_max_lag_secs.is_some() && config.verification_connection_str.is_none() {
+        return Err(GeyserPluginError::ConfigFileReadError {
+            msg: "The \"verification_max_lag_secs\" requires the \"verification_connection_str\" to be set"
+                .to_string(),
+        });
+    }
+    if config.verification_connection_str.is_some() {
+        if config.write_verification_percentage.is_none()
+            && config.block_chain_verification_interval.is_none()
+        {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: "The \"verification_connection_str\" requires the \"write_verification_percentage\" or the \"block_chain_verification_interval\" to be set"
+                    .to_string(),
+            });
+        }
+        if config.sharding.is_some() {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: "The \"verification_connection_str\" must not be set along with the \"sharding\""
+                    .to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check the rates the accounts are sampled at.
+fn check_sampling(config: &AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    check_positive("sysvar_sampling_rate", config.sysvar_sampling_rate)?;
+    check_positive(
+        "account_sampling_percentage",
+        config.account_sampling_percentage,
+    )?;
+    check_range(
+        "account_sampling_percentage",
+        config.account_sampling_percentage,
+        0.0,
+        100.0,
+    )?;
+    Ok(())
+}
+
+/// Check the selectors of the accounts and the transactions.
+fn check_selectors(config: &AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    check_positive("selector_dry_run_secs", config.selector_dry_run_secs)?;
+    check_positive(
+        "transaction_allowlist_poll_interval_secs",
+        config.transaction_allowlist_poll_interval_secs,
+    )?;
+    check_positive(
+        "selector_reload_interval_secs",
+        config.selector_reload_interval_secs,
+    )?;
+    check_accounts_selector("accounts_selector", config.accounts_selector.as_ref())?;
+    check_high_volume(config)?;
+    check_accounts_selector(
+        "startup_accounts_selector",
+        config.startup_accounts_selector.as_ref(),
+    )?;
+    check_accounts_selector("priority_accounts", config.priority_accounts.as_ref())?;
+    check_accounts_selector(
+        "account_versions_selector",
+        config.account_versions_selector.as_ref(),
+    )?;
+    if let Some(transaction_selector) = &config.transaction_selector {
+        let exclude_votes = transaction_selector.exclude_votes.unwrap_or_default();
+        if exclude_votes && transaction_selector.include_only_votes.unwrap_or_default() {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: "The \"transaction_selector.exclude_votes\" and \"transaction_selector.include_only_votes\" must not both be 'true'".to_string(),
+            });
+        }
+        if exclude_votes
+            && transaction_selector
+                .mentions
+                .iter()
+                .flatten()
+                .any(|mention| mention == "all_votes")
+        {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: "The \"transaction_selector.exclude_votes\" leaves out the vote transactions the \"all_votes\" of the \"transaction_selector.mentions\" selects".to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check the settings of the reconnection to the database.
+fn check_reconnection(config: &AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    check_positive(
+        "reconnect_max_backoff_secs",
+        config.reconnect_max_backoff_secs,
+    )?;
+    check_positive("reconnect_buffer_size", config.reconnect_buffer_size)?;
+    check_positive("replay_window_secs", config.replay_window_secs)?;
+    if config.replay_window_secs.is_some() && config.reconnect_max_backoff_secs.is_none() {
+        return Err(GeyserPluginError::ConfigFileReadError {
+            msg: "The \"replay_window_secs\" requires the \"reconnect_max_backoff_secs\" to be set"
+                .to_string(),
+        });
+    }
+    check_positive("replay_window_size", config.replay_window_size)?;
+    if config.replay_window_size.is_some() && config.replay_window_secs.is_none() {
+        return Err(GeyserPluginError::ConfigFileReadError {
+            msg: "The \"replay_window_size\" requires the \"replay_window_secs\" to be set"
+                .to_string(),
+        });
+    }
+    if config.spill_path.as_ref().is_some_and(String::is_empty) {
+        return Err(GeyserPluginError::ConfigFileReadError {
+            msg: "The \"spill_path\" must not be empty".to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Check the settings monitoring what the plugin writes.
+fn check_monitoring(config: &AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    if config.capture_file.as_ref().is_some_and(String::is_empty) {
+        return Err(GeyserPluginError::ConfigFileReadError {
+            msg: "The \"capture_file\" must not be empty".to_string(),
+        });
+    }
+    check_positive("ingest_sla_secs", config.ingest_sla_secs)?;
+    check_positive(
+        "write_stats_interval_secs",
+        config.write_stats_interval_secs,
+    )?;
+    if let Some(metrics) = &config.metrics {
+        if metrics.bind.parse::<std::net::SocketAddr>().is_err() {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The value of \"metrics.bind\": {:?} is not a socket address",
+                    metrics.bind
+                ),
+            });
+        }
+    }
+    check_slot_checksums(config)?;
+    Ok(())
+}
+
+/// Check the settings failing over to the fallback database.
+fn check_failover(config: &AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    check_positive(
+        "health_check_interval_secs",
+        config.health_check_interval_secs,
+    )?;
+    check_positive("failover_after_secs", config.failover_after_secs)?;
+    for (name, value) in [
+        (
+            "health_check_interval_secs",
+            config.health_check_interval_secs,
+        ),
+        ("failover_after_secs", config.failover_after_secs),
+    ] {
+        if value.is_some() && config.fallback_connection_str.is_none() {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The \"{}\" requires the \"fallback_connection_str\" to be set",
+                    name
+                ),
+            });
+        }
+    }
+    if config.fallback_connection_str.is_some() {
+        if config.reconnect_max_backoff_secs.is_none() {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: "The \"fallback_connection_str\" requires the \"reconnect_max_backoff_secs\" to be set"
+                    .to_string(),
+            });
+        }
+        if config.sharding.is_some() {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: "The \"fallback_connection_str\" must not be set along with the \"sharding\""
+                    .to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check the settings of the maintenance tasks run on the database.
+fn check_maintenance(config: &AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    check_positive("analyze_interval_secs", config.analyze_interval_secs)?;
+    check_positive("analyze_row_count_delta", config.analyze_row_count_delta)?;
+    check_positive(
+        "index_advisor_interval_secs",
+        config.index_advisor_interval_secs,
+    )?;
+    check_positive("index_advisor_min_calls", config.index_advisor_min_calls)?;
+    check_positive(
+        "schema_drift_check_interval_secs",
+        config.schema_drift_check_interval_secs,
+    )?;
+    if config.index_advisor_min_calls.is_some() && config.index_advisor_interval_secs.is_none() {
+        return Err(GeyserPluginError::ConfigFileReadError {
+            msg: "The \"index_advisor_min_calls\" requires the \"index_advisor_interval_secs\" to be set"
+                .to_string(),
+        });
+    }
+    check_positive(
+        "fork_cleanup_interval_slots",
+        config.fork_cleanup_interval_slots,
+    )?;
+    Ok(())
+}
+
+/// Check the shards the updates are written to.
+fn check_sharding(config: &AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    if let Some(sharding) = &config.sharding {
+        if sharding.connection_strs.is_empty() {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: "The \"sharding.connection_strs\" must not be empty".to_string(),
+            });
+        }
+        if config.connection_str.is_some() {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: "The \"connection_str\" must not be set along with the \"sharding.connection_strs\""
+                    .to_string(),
+            });
+        }
+        if sharding.account.is_some()
+            && sharding.strategy.unwrap_or_default() == ShardingStrategy::PubkeyHash
+        {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: "The \"sharding.account\" requires the \"sharding.strategy\" set to 'by_kind'"
+                    .to_string(),
+            });
+        }
+        for (kind, index) in [
+            ("account", sharding.account),
+            ("transaction", sharding.transaction),
+            ("entry", sharding.entry),
+        ] {
+            check_range(
+                &format!("sharding.{}", kind),
+                index,
+                0,
+                sharding.connection_strs.len() - 1,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Check the settings of the queue of the updates.
+fn check_queue(config: &AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    check_positive("queue_capacity", config.queue_capacity)?;
+    if let Some(channel) = &config.flow_control_channel {
+        if !is_column_name(channel) {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The \"flow_control_channel\": {:?} must consist of lowercase letters, digits and underscores, starting with a letter or an underscore",
+                    channel
+                ),
+            });
+        }
+        if config.backpressure_queue_threshold.is_none() {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: "The \"flow_control_channel\" requires the \"backpressure_queue_threshold\" to be set"
+                    .to_string(),
+            });
+        }
+    }
+    check_range(
+        "backpressure_queue_threshold",
+        config.backpressure_queue_threshold,
+        1,
+        config.queue_capacity.unwrap_or(MAX_ASYNC_REQUESTS),
+    )?;
+    check_positive(
+        "backpressure_max_block_ms",
+        config.backpressure_max_block_ms,
+    )?;
+    if let Some(ladder) = &config.degradation_ladder {
+        check_degradation_ladder(config, ladder)?;
+    }
+    Ok(())
+}
+
+/// Check the settings of the layout and the storage of the tables.
+fn check_storage(config: &AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    if config.account_layout == Some(AccountLayout::Split)
+        && config
+            .use_schema_triggers
+            .unwrap_or(DEFAULT_USE_SCHEMA_TRIGGERS)
+    {
+        return Err(GeyserPluginError::ConfigFileReadError {
+            msg:
+                "The \"account_layout\" 'split' requires the \"use_schema_triggers\" set to 'false'"
+                    .to_string(),
+        });
+    }
+    check_positive("account_cold_tier_slots", config.account_cold_tier_slots)?;
+    if config.account_cold_tier_slots.is_some()
+        && (config.account_layout == Some(AccountLayout::Split)
+            || config
+                .use_schema_triggers
+                .unwrap_or(DEFAULT_USE_SCHEMA_TRIGGERS))
+    {
+        return Err(GeyserPluginError::ConfigFileReadError {
+            msg: "The \"account_cold_tier_slots\" requires the \"use_schema_triggers\" set to 'false' and the 'wide' \"account_layout\""
+                .to_string(),
+        });
+    }
+    if let Some(tablespace) = &config.account_cold_tier_tablespace {
+        if config.account_cold_tier_slots.is_none() {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: "The \"account_cold_tier_tablespace\" requires the \"account_cold_tier_slots\" to be set"
+                    .to_string(),
+            });
+        }
+        if !is_column_name(tablespace) {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The tablespace name \"{}\" must consist of lowercase letters, digits and underscores, starting with a letter or an underscore",
+                    tablespace
+                ),
+            });
+        }
+    }
+    if config.storage_engine.unwrap_or_default() != StorageEngine::TimescaleDb {
+        for (name, value) in [
+            ("hypertable_chunk_slots", config.hypertable_chunk_slots),
+            (
+                "hypertable_compress_after_slots",
+                config.hypertable_compress_after_slots,
+            ),
+        ] {
+            if value.is_some() {
+                return Err(GeyserPluginError::ConfigFileReadError {
+                    msg: format!(
+                        "The \"{}\" requires the \"storage_engine\" set to 'timescaledb'",
+                        name
+                    ),
+                });
+            }
+        }
+    }
+    check_positive("hypertable_chunk_slots", config.hypertable_chunk_slots)?;
+    if config.distributed == Some(Distributed::Citus) {
+        if config
+            .use_schema_triggers
+            .unwrap_or(DEFAULT_USE_SCHEMA_TRIGGERS)
+        {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: "The \"distributed\" 'citus' requires the \"use_schema_triggers\" set to 'false'"
+                    .to_string(),
+            });
+        }
+        for (name, is_set) in [
+            (
+                "storage_engine",
+                config.storage_engine == Some(StorageEngine::TimescaleDb),
+            ),
+            (
+                "account_cold_tier_slots",
+                config.account_cold_tier_slots.is_some(),
+            ),
+        ] {
+            if is_set {
+                return Err(GeyserPluginError::ConfigFileReadError {
+                    msg: format!(
+                        "The \"{}\" is not allowed with the \"distributed\" set to 'citus'",
+                        name
+                    ),
+                });
+            }
+        }
+    } else if config.distributed_shard_count.is_some() {
+        return Err(GeyserPluginError::ConfigFileReadError {
+            msg: "The \"distributed_shard_count\" requires the \"distributed\" to be set"
+                .to_string(),
+        });
+    }
+    check_positive("distributed_shard_count", config.distributed_shard_count)?;
+    check_positive(
+        "hypertable_compress_after_slots",
+        config.hypertable_compress_after_slots,
+    )?;
+    check_positive(
+        "continuous_aggregate_refresh_slots",
+        config.continuous_aggregate_refresh_slots,
+    )?;
+    if let Some(schema) = &config.schema {
+        if !is_column_name(schema) {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The schema name \"{}\" must consist of lowercase letters, digits and underscores, starting with a letter or an underscore",
+                    schema
+                ),
+            });
+        }
+    }
+    if let Some(schema_source) = &config.schema_source {
+        let is_url = schema_source.starts_with("http://") || schema_source.starts_with("https://");
+        if schema_source.is_empty() || (schema_source.contains("://") && !is_url) {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The \"schema_source\" \"{}\" must be a directory or an http(s) URL",
+                    schema_source
+                ),
+            });
+        }
+    }
+    if let Some(aggregates) = &config.continuous_aggregates {
+        if aggregates.is_empty() {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: "The \"continuous_aggregates\" must not be empty".to_string(),
+            });
+        }
+        if let Some(aggregate) = aggregates
+            .iter()
+            .find(|aggregate| !is_column_name(aggregate))
+        {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The continuous aggregate \"{}\" must consist of lowercase letters, digits and underscores, starting with a letter or an underscore",
+                    aggregate
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check the settings of the history of the accounts kept in the account_audit table.
+fn check_audit(config: &AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    check_positive(
+        "account_audit_retention_slots",
+        config.account_audit_retention_slots,
+    )?;
+    check_positive(
+        "account_audit_partition_slots",
+        config.account_audit_partition_slots,
+    )?;
+    if let Some(overrides) = &config.account_audit_retention_overrides {
+        check_audit_retention_overrides(config, overrides)?;
+    }
+    if config
+        .account_audit_archive_path
+        .as_ref()
+        .is_some_and(|archive_path| archive_path.is_empty())
+    {
+        return Err(GeyserPluginError::ConfigFileReadError {
+            msg: "The \"account_audit_archive_path\" must not be empty".to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Check the slots the plugin writes.
+fn check_slots(config: &AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    check_positive("slots_per_epoch", config.slots_per_epoch)?;
+    if let (Some(start_slot), Some(stop_slot)) = (config.start_slot, config.stop_slot) {
+        if start_slot > stop_slot {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The \"start_slot\": {} must not be greater than the \"stop_slot\": {}",
+                    start_slot, stop_slot
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check the settings of the columns of the account table.
+fn check_columns(config: &AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    for name in config.static_columns.iter().flatten().map(|(name, _)| name) {
+        if !is_column_name(name) {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The static column name \"{}\" must consist of lowercase letters, digits and underscores, starting with a letter or an underscore",
+                    name
+                ),
+            });
+        }
+    }
+    for (name, expression) in config.computed_columns.iter().flatten() {
+        if !is_column_name(name) {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The computed column name \"{}\" must consist of lowercase letters, digits and underscores, starting with a letter or an underscore",
+                    name
+                ),
+            });
+        }
+        if ACCOUNT_COLUMNS.iter().any(|(column, _)| column == name)
+            || config
+                .static_columns
+                .as_ref()
+                .is_some_and(|static_columns| static_columns.contains_key(name))
+        {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The computed column \"{}\" is already a column of the account table",
+                    name
+                ),
+            });
+        }
+        if let Err(placeholder) = check_expression(expression) {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The expression of the computed column \"{}\" refers to the unknown placeholder {:?}, the placeholders are: {:?}",
+                    name,
+                    placeholder,
+                    ACCOUNT_COLUMNS.map(|(column, _)| format!("{{{}}}", column))
+                ),
+            });
+        }
+    }
+    if let Some(owners) = &config.discriminator_owners {
+        if owners.is_empty() {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: "The \"discriminator_owners\" must not be empty".to_string(),
+            });
+        }
+        if let Some(owner) = owners
+            .iter()
+            .find(|owner| bs58::decode(owner).into_vec().is_err())
+        {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The value of \"discriminator_owners\": {:?} is not base58 encoded",
+                    owner
+                ),
+            });
+        }
+        let columns = [&config.static_columns, &config.computed_columns];
+        if columns.iter().any(|columns| {
+            columns
+                .as_ref()
+                .is_some_and(|columns| columns.contains_key(DISCRIMINATOR_COLUMN))
+        }) {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The \"{}\" column of the \"discriminator_owners\" must not be a static or computed column",
+                    DISCRIMINATOR_COLUMN
+                ),
+            });
+        }
+    }
+    if let Some(compression) = &config.account_data_compression {
+        check_range(
+            "account_data_compression.level",
+            compression.level,
+            1,
+            MAX_ZSTD_LEVEL,
+        )?;
+        let computed_data = config
+            .computed_columns
+            .iter()
+            .flatten()
+            .find(|(_, expression)| expression.contains("{data}"));
+        if let Some((column, _)) = computed_data {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The computed column \"{}\" of the data must not be set along with the \"account_data_compression\"",
+                    column
+                ),
+            });
+        }
+        if config.discriminator_owners.is_some() {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: "The \"discriminator_owners\" must not be set along with the \"account_data_compression\""
+                    .to_string(),
+            });
+        }
+    }
+    if let Some(decoders) = &config.account_data_decoders {
+        if decoders.is_empty() {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: "The \"account_data_decoders\" must not be empty".to_string(),
+            });
+        }
+        for (owner, decoder) in decoders {
+            if bs58::decode(owner).into_vec().is_err() {
+                return Err(GeyserPluginError::ConfigFileReadError {
+                    msg: format!(
+                        "The owner {:?} of the \"account_data_decoders\" is not base58 encoded",
+                        owner
+                    ),
+                });
+            }
+            if decoder.idl.is_some() == decoder.borsh_schema.is_some() {
+                return Err(GeyserPluginError::ConfigFileReadError {
+                    msg: format!(
+                        "The decoder of the owner {} must set either \"idl\" or \"borsh_schema\"",
+                        owner
+                    ),
+                });
+            }
+        }
+        let columns = [&config.static_columns, &config.computed_columns];
+        if columns.iter().any(|columns| {
+            columns
+                .as_ref()
+                .is_some_and(|columns| columns.contains_key(DATA_JSON_COLUMN))
+        }) {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The \"{}\" column of the \"account_data_decoders\" must not be a static or computed column",
+                    DATA_JSON_COLUMN
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check the programs the instructions are parsed for.
+fn check_instructions(config: &AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    if let Some(programs) = &config.parsed_instruction_programs {
+        if programs.is_empty() {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: "The \"parsed_instruction_programs\" must not be empty".to_string(),
+            });
+        }
+        if let Some(program) = programs
+            .iter()
+            .find(|program| !PARSED_INSTRUCTION_PROGRAMS.contains(&program.as_str()))
+        {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The program \"{}\" of the \"parsed_instruction_programs\" is not one of the supported programs: {:?}",
+                    program, PARSED_INSTRUCTION_PROGRAMS
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check the settings of the notifications of the updates committed.
+fn check_notifications(config: &AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    for (update_type, channel) in config.notify_channels.iter().flatten() {
+        if !POST_COMMIT_HOOK_TABLES.contains(&update_type.as_str()) {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The notify channel update type \"{}\" is not one of the supported types: {:?}",
+                    update_type, POST_COMMIT_HOOK_TABLES
+                ),
+            });
+        }
+        if !is_column_name(channel) {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The notify channel \"{}\" must consist of lowercase letters, digits and underscores, starting with a letter or an underscore",
+                    channel
+                ),
+            });
+        }
+    }
+    for table in config
+        .post_commit_hooks
+        .iter()
+        .flatten()
+        .map(|(table, _)| table)
+    {
+        if !POST_COMMIT_HOOK_TABLES.contains(&table.as_str()) {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: format!(
+                    "The post commit hook table \"{}\" is not one of the supported tables: {:?}",
+                    table, POST_COMMIT_HOOK_TABLES
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check the tunnel goes through either the SOCKS5 proxy or the SSH jump host, along
+/// with the settings of the one it goes through.
+fn check_tunnel(
+    config: &AccountsDbPluginPostgresConfig,
+    tunnel: &TunnelConfig,
+) -> Result<(), GeyserPluginError> {
+    let config_error = |msg: &str| GeyserPluginError::ConfigFileReadError {
+        msg: msg.to_string(),
+    };
+    match (&tunnel.socks5_proxy, &tunnel.ssh_jump_host) {
+        (Some(_), Some(_)) => {
+            return Err(config_error(
+                "The \"tunnel.socks5_proxy\" must not be set along with the \"tunnel.ssh_jump_host\"",
+            ))
+        }
+        (None, None) => {
+            return Err(config_error(
+                "The \"tunnel\" requires the \"tunnel.socks5_proxy\" or the \"tunnel.ssh_jump_host\" to be set",
+            ))
+        }
+        _ => {}
+    }
+    let is_proxy_set = tunnel.socks5_proxy.is_some();
+    let is_jump_host_set = tunnel.ssh_jump_host.is_some();
+    let is_user_set = tunnel.socks5_user.is_some();
+    let is_password_set = tunnel.socks5_password.is_some();
+    for (name, is_set, required, is_required_set) in [
+        (
+            "tunnel.socks5_user",
+            is_user_set,
+            "tunnel.socks5_proxy",
+            is_proxy_set,
+        ),
+        (
+            "tunnel.socks5_user",
+            is_user_set,
+            "tunnel.socks5_password",
+            is_password_set,
+        ),
+        (
+            "tunnel.socks5_password",
+            is_password_set,
+            "tunnel.socks5_user",
+            is_user_set,
+        ),
+        (
+            "tunnel.ssh_port",
+            tunnel.ssh_port.is_some(),
+            "tunnel.ssh_jump_host",
+            is_jump_host_set,
+        ),
+        (
+            "tunnel.ssh_identity_file",
+            tunnel.ssh_identity_file.is_some(),
+            "tunnel.ssh_jump_host",
+            is_jump_host_set,
+        ),
+    ] {
+        if is_set && !is_required_set {
+            return Err(config_error(&format!(
+                "The \"{}\" requires the \"{}\" to be set",
+                name, required
+            )));
+        }
+    }
+    if config.sharding.is_some() {
+        return Err(config_error(
+            "The \"tunnel\" must not be set along with the \"sharding\"",
+        ));
+    }
+    if config.fallback_connection_str.is_some() {
+        return Err(config_error(
+            "The \"tunnel\" must not be set along with the \"fallback_connection_str\"",
+        ));
+    }
+    if config.verification_connection_str.is_some() {
+        return Err(config_error(
+            "The \"tunnel\" must not be set along with the \"verification_connection_str\"",
+        ));
+    }
+    Ok(())
+}
+
+/// Parse the JSON config into the plugin config
+/// Check the rows checksummed per slot are stored as they are notified, once each, along
+/// with the previous versions of the accounts, for the checksums to be recomputed from
+/// the tables.
+fn check_slot_checksums(config: &AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    if config.store_slot_checksums != Some(true) {
+        return Ok(());
+    }
+    if config.store_account_historical_data != Some(true) {
+        return Err(GeyserPluginError::ConfigFileReadError {
+            msg: "The \"store_slot_checksums\" requires the \"store_account_historical_data\" set to 'true'"
+                .to_string(),
+        });
+    }
+    let altering = [
+        (
+            "\"account_data_compression\"",
+            config.account_data_compression.is_some(),
+        ),
+        (
+            "'split' \"account_layout\"",
+            config.account_layout == Some(AccountLayout::Split),
+        ),
+        (
+            "\"account_cold_tier_slots\"",
+            config.account_cold_tier_slots.is_some(),
+        ),
+        (
+            "'decoded' \"token_account_storage\"",
+            config.token_account_storage == Some(TokenAccountStorage::Decoded),
+        ),
+        (
+            "'base58' \"signature_storage\"",
+            config.signature_storage == Some(SignatureStorage::Base58),
+        ),
+        ("\"sharding\"", config.sharding.is_some()),
+        (
+            "\"replay_window_secs\"",
+            config.replay_window_secs.is_some(),
+        ),
+        (
+            "'disable_audit' step of the \"degradation_ladder\"",
+            config.degradation_ladder.as_ref().is_some_and(|ladder| {
+                ladder
+                    .steps
+                    .iter()
+                    .any(|step| step.action == DegradationAction::DisableAudit)
+            }),
+        ),
+    ];
+    match altering.iter().find(|(_, is_set)| *is_set) {
+        Some((setting, _)) => Err(GeyserPluginError::ConfigFileReadError {
+            msg: format!(
+                "The \"store_slot_checksums\" must not be set along with the {}",
+                setting
+            ),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Check the owners of the retention overrides are base58 encoded, and the overrides
+/// longer than the retention are kept by the partitions maintained by the plugin.
+fn check_audit_retention_overrides(
+    config: &AccountsDbPluginPostgresConfig,
+    overrides: &BTreeMap<String, u64>,
+) -> Result<(), GeyserPluginError> {
+    let config_error = |msg: String| GeyserPluginError::ConfigFileReadError { msg };
+    let Some(retention_slots) = config.account_audit_retention_slots else {
+        return Err(config_error(
+            "The \"account_audit_retention_overrides\" requires the \"account_audit_retention_slots\" to be set"
+                .to_string(),
+        ));
+    };
+    for (owner, owner_retention_slots) in overrides {
+        // The owners are matched against the 32 bytes of the owner column
+        if bs58::decode(owner)
+            .into_vec()
+            .map_or(true, |owner| owner.len() != 32)
+        {
+            return Err(config_error(format!(
+                "The owner {:?} of the \"account_audit_retention_overrides\" is not a base58 encoded pubkey",
+                owner
+            )));
+        }
+        if *owner_retention_slots == 0 {
+            return Err(config_error(format!(
+                "The retention of the owner {} of the \"account_audit_retention_overrides\" must be positive",
+                owner
+            )));
+        }
+        if *owner_retention_slots > retention_slots
+            && config.storage_engine.unwrap_or_default() == StorageEngine::TimescaleDb
+        {
+            return Err(config_error(format!(
+                "The retention of the owner {} of the \"account_audit_retention_overrides\" must not be longer than the \"account_audit_retention_slots\" with the \"storage_engine\" set to 'timescaledb'",
+                owner
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Check the steps of the degradation ladder are ordered by their queue depths within
+/// the capacity of the queue, and that only the sampling of the accounts is given a
+/// sampling rate.
+fn check_degradation_ladder(
+    config: &AccountsDbPluginPostgresConfig,
+    ladder: &DegradationLadderConfig,
+) -> Result<(), GeyserPluginError> {
+    let config_error = |msg: String| GeyserPluginError::ConfigFileReadError { msg };
+    if ladder.steps.is_empty() {
+        return Err(config_error(
+            "The \"degradation_ladder.steps\" must not be empty".to_string(),
+        ));
+    }
+    check_positive("degradation_ladder.sustain_secs", ladder.sustain_secs)?;
+    let queue_capacity = config.queue_capacity.unwrap_or(MAX_ASYNC_REQUESTS);
+    let mut previous_depth = 0;
+    for (i, step) in ladder.steps.iter().enumerate() {
+        let path = format!("degradation_ladder.steps[{}]", i);
+        check_range(
+            &format!("{}.queue_depth", path),
+            Some(step.queue_depth),
+            1,
+            queue_capacity,
+        )?;
+        if step.queue_depth <= previous_depth {
+            return Err(config_error(format!(
+                "The \"{}.queue_depth\": {} must be greater than the queue depth of the previous step",
+                path, step.queue_depth
+            )));
+        }
+        previous_depth = step.queue_depth;
+        match (step.action, step.sampling_rate) {
+            (DegradationAction::SampleAccounts, None) => {
+                return Err(config_error(format!(
+                    "The \"{}\" 'sample_accounts' requires the \"sampling_rate\" to be set",
+                    path
+                )))
+            }
+            (DegradationAction::SampleAccounts, sampling_rate) => check_range(
+                &format!("{}.sampling_rate", path),
+                sampling_rate,
+                2,
+                u64::MAX,
+            )?,
+            (_, Some(_)) => {
+                return Err(config_error(format!(
+                    "The \"{}.sampling_rate\" only applies to the 'sample_accounts' action",
+                    path
+                )))
+            }
+            (_, None) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Apply the preset of the minimal footprint, defaulting to the trigger-free schema
+/// and to the statement batches of the account updates, and rejecting the settings
+/// storing anything besides the latest state of the accounts and the slot statuses.
+fn apply_footprint(config: &mut AccountsDbPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+    if config.footprint.unwrap_or_default() != Footprint::Minimal {
+        return Ok(());
+    }
+    let is_true = |value: Option<bool>| value == Some(true);
+    let storing = [
+        (
+            "store_account_historical_data",
+            is_true(config.store_account_historical_data),
+        ),
+        ("use_schema_triggers", is_true(config.use_schema_triggers)),
+        (
+            "transaction_selector",
+            config.transaction_selector.is_some(),
+        ),
+        (
+            "transaction_allowlist_poll_interval_secs",
+            config.transaction_allowlist_poll_interval_secs.is_some(),
+        ),
+        (
+            "account_versions_selector",
+            config.account_versions_selector.is_some(),
+        ),
+        (
+            "token_account_storage",
+            config.token_account_storage.unwrap_or_default() != TokenAccountStorage::Raw,
+        ),
+        (
+            "token_secondary_indexes",
+            config.token_secondary_indexes.is_some(),
+        ),
+        ("store_vote_activity", is_true(config.store_vote_activity)),
+        ("store_block_json", is_true(config.store_block_json)),
+        ("store_slot_economics", is_true(config.store_slot_economics)),
+        ("store_block_rewards", is_true(config.store_block_rewards)),
+        (
+            "store_validator_rewards",
+            is_true(config.store_validator_rewards),
+        ),
+        ("store_stake_rewards", is_true(config.store_stake_rewards)),
+        ("store_entries", is_true(config.store_entries)),
+        ("write_epoch_barriers", is_true(config.write_epoch_barriers)),
+        (
+            "store_slot_transaction_counts",
+            is_true(config.store_slot_transaction_counts),
+        ),
+        ("store_slot_checksums", is_true(config.store_slot_checksums)),
+        (
+            "block_chain_verification_interval",
+            config.block_chain_verification_interval.is_some(),
+        ),
+    ];
+    if let Some((name, _)) = storing.iter().find(|(_, is_storing)| *is_storing) {
+        return Err(GeyserPluginError::ConfigFileReadError {
+            msg: format!(
+                "The \"{}\" is not allowed with the \"footprint\" set to 'minimal'",
+                name
+            ),
+        });
+    }
+    config.use_schema_triggers = Some(false);
+    if config.statement_batch_size.is_none() && config.batch.is_none() {
+        config.batch = Some(BatchConfig {
+            account: Some(BatchLimitConfig {
+                size: Some(MINIMAL_FOOTPRINT_ACCOUNT_BATCH_SIZE),
+                max_delay_ms: Some(MINIMAL_FOOTPRINT_ACCOUNT_BATCH_MAX_DELAY_MS),
+            }),
+            ..BatchConfig::default()
+        });
+    }
+    Ok(())
+}
+
+/// Check the credentials have a single provider of the password, and the connection
+/// strings do not contain a password of their own.
+fn check_credentials(
+    config: &AccountsDbPluginPostgresConfig,
+    credentials: &CredentialsConfig,
+) -> Result<(), GeyserPluginError> {
+    let config_error = |msg: String| GeyserPluginError::ConfigFileReadError { msg };
+    let providers: Vec<&str> = [
+        (
+            "credentials.password_env",
+            credentials.password_env.is_some(),
+        ),
+        (
+            "credentials.password_file",
+            credentials.password_file.is_some(),
+        ),
+        (
+            "credentials.password_command",
+            credentials.password_command.is_some(),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(name, is_set)| is_set.then_some(name))
+    .collect();
+    match providers[..] {
+        [] => {
+            return Err(config_error(
+                "The \"credentials\" requires the \"credentials.password_env\", the \"credentials.password_file\" or the \"credentials.password_command\" to be set"
+                    .to_string(),
+            ))
+        }
+        [_] => {}
+        [first, second, ..] => {
+            return Err(config_error(format!(
+                "The \"{}\" must not be set along with the \"{}\"",
+                first, second
+            )))
+        }
+    }
+    if credentials
+        .password_command
+        .as_ref()
+        .is_some_and(|command| command.is_empty())
+    {
+        return Err(config_error(
+            "The \"credentials.password_command\" must not be empty".to_string(),
+        ));
+    }
+    check_positive(
+        "credentials.refresh_interval_secs",
+        credentials.refresh_interval_secs,
+    )?;
+    let connection_strs = config
+        .connection_str
+        .iter()
+        .map(|connection_str| ("connection_str", connection_str))
+        .chain(
+            config
+                .fallback_connection_str
+                .iter()
+                .map(|connection_str| ("fallback_connection_str", connection_str)),
+        )
+        .chain(
+            config
+                .verification_connection_str
+                .iter()
+                .map(|connection_str| ("verification_connection_str", connection_str)),
+        )
+        .chain(config.sharding.iter().flat_map(|sharding| {
+            sharding
+                .connection_strs
+                .iter()
+                .map(|connection_str| ("sharding.connection_strs", connection_str))
+        }));
+    for (name, connection_str) in connection_strs {
+        let has_password = connection_str
+            .parse::<postgres::Config>()
+            .is_ok_and(|pg_config| pg_config.get_password().is_some());
+        if has_password {
+            return Err(config_error(format!(
+                "The \"credentials\" must not be set along with a password in the \"{}\"",
+                name
+            )));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn parse_config(
+    value: serde_json::Value,
+) -> Result<AccountsDbPluginPostgresConfig, GeyserPluginError> {
+    check_unknown_fields(&value, field_names::<AccountsDbPluginPostgresConfig>(), "")?;
+    for section in [
+        "accounts_selector",
+        "startup_accounts_selector",
+        "priority_accounts",
+        "account_versions_selector",
+    ] {
+        check_unknown_fields(
+            &value[section],
+            field_names::<AccountsSelectorConfig>(),
+            &format!("{}.", section),
+        )?;
+        for (i, rule) in value[section]["rules"]
+            .as_array()
+            .iter()
+            .copied()
+            .flatten()
+            .enumerate()
+        {
+            check_unknown_fields(
+                rule,
+                field_names::<AccountsSelectorRuleConfig>(),
+                &format!("{}.rules[{}].", section, i),
+            )?;
+        }
+    }
+    check_unknown_fields(
+        &value["transaction_selector"],
+        field_names::<TransactionSelectorConfig>(),
+        "transaction_selector.",
+    )?;
+    check_unknown_fields(
+        &value["metrics"],
+        field_names::<MetricsConfig>(),
+        "metrics.",
+    )?;
+    check_unknown_fields(&value["batch"], field_names::<BatchConfig>(), "batch.")?;
+    check_unknown_fields(
+        &value["account_data_compression"],
+        field_names::<AccountDataCompressionConfig>(),
+        "account_data_compression.",
+    )?;
+    check_unknown_fields(&value["tunnel"], field_names::<TunnelConfig>(), "tunnel.")?;
+    check_unknown_fields(
+        &value["credentials"],
+        field_names::<CredentialsConfig>(),
+        "credentials.",
+    )?;
+    check_unknown_fields(
+        &value["sharding"],
+        field_names::<ShardingConfig>(),
+        "sharding.",
+    )?;
+    for kind in field_names::<BatchConfig>() {
+        check_unknown_fields(
+            &value["batch"][kind],
+            field_names::<BatchLimitConfig>(),
+            &format!("batch.{}.", kind),
+        )?;
+    }
+    for (owner, decoder) in value["account_data_decoders"]
+        .as_object()
+        .into_iter()
+        .flatten()
+    {
+        check_unknown_fields(
+            decoder,
+            field_names::<AccountDataDecoderConfig>(),
+            &format!("account_data_decoders.{}.", owner),
+        )?;
+    }
+
+    check_client_fields(&value)?;
+
+    let mut config: AccountsDbPluginPostgresConfig =
+        serde_json::from_value(value).map_err(|err| GeyserPluginError::ConfigFileReadError {
+            msg: format!(
+                "The config file is not in the JSON format expected: {}",
+                err
+            ),
+        })?;
+    apply_footprint(&mut config)?;
+    validate_config(&config)?;
+    Ok(config)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, crate::accountsdb_plugin_postgres::SignatureStorage};
+
+    fn parse_error(contents: &str) -> String {
+        match parse_config(serde_json::from_str(contents).unwrap()) {
+            Err(GeyserPluginError::ConfigFileReadError { msg }) => msg,
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = parse_config(serde_json::from_str(
+            "{\"libpath\": \"/tmp/libplugin.so\", \"host\": \"localhost\", \"user\": \"solana\", \
+            \"threads\": 4, \"batch_size\": 20, \
+            \"accounts_selector\": {\"accounts\": [\"*\"]}, \
+            \"transaction_selector\": {\"mentions\": [\"all_votes\"]}}",
+        ).unwrap())
+        .unwrap();
+        assert_eq!(config.threads, Some(4));
+        assert_eq!(config.batch_size, Some(20));
+    }
+
+    #[test]
+    fn test_parse_config_unknown_fields() {
+        assert_eq!(
+            parse_error("{\"batchsize\": 20}"),
+            "Unknown field \"batchsize\" in the config file, did you mean \"batch_size\"?"
+        );
+        assert_eq!(
+            parse_error("{\"accounts_selector\": {\"owner\": []}}"),
+            "Unknown field \"accounts_selector.owner\" in the config file, did you mean \"accounts_selector.owners\"?"
+        );
+        assert!(parse_error("{\"completely_unrelated\": 1}").contains("the known fields are"));
+        assert_eq!(
+            parse_error("{\"metrics\": {\"bind\": \"0.0.0.0:9091\", \"binds\": 1}}"),
+            "Unknown field \"metrics.binds\" in the config file, did you mean \"metrics.bind\"?"
+        );
+        assert_eq!(
+            parse_error("{\"metrics\": {\"bind\": \"localhost\"}}"),
+            "The value of \"metrics.bind\": \"localhost\" is not a socket address"
+        );
+    }
+
+    #[test]
+    fn test_parse_config_ranges() {
+        assert_eq!(
+            parse_error("{\"threads\": 0}"),
+            "The value of \"threads\": 0 must be greater than 0"
+        );
+        assert_eq!(
+            parse_error("{\"batch_size\": 100000}"),
+            format!(
+                "The value of \"batch_size\": 100000 is out of the range [1, {}]",
+                MAX_ACCOUNTS_INSERT_BATCH_SIZE
+            )
+        );
+        assert_eq!(
+            parse_error("{\"backpressure_queue_threshold\": 100000}"),
+            format!(
+                "The value of \"backpressure_queue_threshold\": 100000 is out of the range [1, {}]",
+                MAX_ASYNC_REQUESTS
+            )
+        );
+        assert_eq!(
+            parse_error("{\"queue_capacity\": 1000, \"backpressure_queue_threshold\": 2000}"),
+            "The value of \"backpressure_queue_threshold\": 2000 is out of the range [1, 1000]"
+        );
+        assert!(parse_config(serde_json::json!({
+            "queue_capacity": 100000,
+            "backpressure_queue_threshold": 80000,
+            "queue_overflow_policy": "drop_account_updates_only"
+        }))
+        .is_ok());
+        assert!(parse_error("{\"queue_overflow_policy\": \"drop_newest\"}")
+            .contains("unknown variant `drop_newest`"));
+        assert_eq!(
+            parse_error("{\"account_sampling_percentage\": 150}"),
+            "The value of \"account_sampling_percentage\": 150 is out of the range [0, 100]"
+        );
+        assert_eq!(
+            parse_error("{\"capture_file\": \"\"}"),
+            "The \"capture_file\" must not be empty"
+        );
+        assert_eq!(
+            parse_error("{\"write_verification_percentage\": -1}"),
+            "The value of \"write_verification_percentage\": -1 is out of the range [0, 100]"
+        );
+        assert_eq!(
+            parse_error("{\"spill_path\": \"\"}"),
+            "The \"spill_path\" must not be empty"
+        );
+        assert_eq!(
+            parse_error("{\"verification_max_lag_secs\": 10}"),
+            "The \"verification_max_lag_secs\" requires the \"verification_connection_str\" to be set"
+        );
+        assert_eq!(
+            parse_error("{\"verification_connection_str\": \"host=replica\"}"),
+            "The \"verification_connection_str\" requires the \"write_verification_percentage\" or the \"block_chain_verification_interval\" to be set"
+        );
+        assert!(parse_config(serde_json::json!({
+            "write_verification_percentage": 1,
+            "verification_connection_str": "host=replica",
+            "verification_max_lag_secs": 10
+        }))
+        .is_ok());
+        assert_eq!(
+            parse_error("{\"worker_cpu_budget_percentage\": 0}"),
+            "The value of \"worker_cpu_budget_percentage\": 0 is out of the range [1, 100]"
+        );
+        assert!(parse_error("{\"threads\": \"4\"}")
+            .starts_with("The config file is not in the JSON format expected"));
+    }
+
+    #[test]
+    fn test_parse_config_slot_range() {
+        assert_eq!(
+            parse_error("{\"start_slot\": 20, \"stop_slot\": 10}"),
+            "The \"start_slot\": 20 must not be greater than the \"stop_slot\": 10"
+        );
+    }
+
+    #[test]
+    fn test_parse_config_commit_per_slot() {
+        let config = parse_config(serde_json::json!({
+            "commit_per_slot": true,
+            "slot_commit_chunk_size": 500,
+        }))
+        .unwrap();
+        assert_eq!(config.slot_commit_chunk_size, Some(500));
+        assert_eq!(
+            parse_error("{\"slot_commit_chunk_size\": 500}"),
+            "The \"slot_commit_chunk_size\" requires the \"commit_per_slot\" set to 'true'"
+        );
+        assert_eq!(
+            parse_error("{\"commit_per_slot\": true, \"slot_commit_chunk_size\": 0}"),
+            "The value of \"slot_commit_chunk_size\": 0 must be greater than 0"
+        );
+    }
+
+    #[test]
+    fn test_parse_config_transaction_selector_filters() {
+        assert_eq!(
+            parse_error(
+                "{\"transaction_selector\": {\"mentions\": [\"*\"], \
+                \"exclude_votes\": true, \"include_only_votes\": true}}"
+            ),
+            "The \"transaction_selector.exclude_votes\" and \"transaction_selector.include_only_votes\" must not both be 'true'"
+        );
+        assert!(parse_error(
+            "{\"transaction_selector\": {\"mentions\": [\"all_votes\"], \"exclude_votes\": true}}"
+        )
+        .contains("leaves out the vote transactions"));
+        assert!(parse_config(serde_json::json!({
+            "transaction_selector": {
+                "mentions": ["*"],
+                "exclude_votes": true,
+                "exclude_failed": true
+            }
+        }))
+        .is_ok());
+    }
+
+    #[test]
+    fn test_parse_config_high_volume() {
+        assert!(parse_error(
+            "{\"threads\": 8, \"accounts_selector\": {\"owners\": [\"*\"]}, \
+            \"transaction_selector\": {\"mentions\": [\"*\"]}}"
+        )
+        .contains("select all the accounts and the transactions, written by 8 \"threads\" in batches of 10"));
+        assert!(parse_error(
+            "{\"batch_size\": 2, \"transaction_selector\": {\"mentions\": [\"*\"]}}"
+        )
+        .contains("\"acknowledge_high_volume\""));
+        for config in [
+            serde_json::json!({
+                "threads": 8,
+                "transaction_selector": {"mentions": ["*"]},
+                "acknowledge_high_volume": true
+            }),
+            serde_json::json!({
+                "threads": 8,
+                "accounts_selector": {"accounts": ["*"]},
+                "transaction_selector": {"mentions": ["all_votes"]}
+            }),
+            serde_json::json!({
+                "accounts_selector": {"accounts": ["*"]},
+                "transaction_selector": {"mentions": ["*"]}
+            }),
+        ] {
+            assert!(parse_config(config).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_parse_config_flow_control() {
+        assert_eq!(
+            parse_error("{\"flow_control_channel\": \"flow_control\"}"),
+            "The \"flow_control_channel\" requires the \"backpressure_queue_threshold\" to be set"
+        );
+        assert!(parse_error(
+            "{\"flow_control_channel\": \"flow control\", \"backpressure_queue_threshold\": 1000}"
+        )
+        .contains("must consist of lowercase letters"));
+        assert!(parse_config(serde_json::json!({
+            "flow_control_channel": "flow_control",
+            "backpressure_queue_threshold": 1000
+        }))
+        .is_ok());
+    }
+
+    #[test]
+    fn test_parse_config_degradation_ladder() {
+        let config = parse_config(serde_json::json!({
+            "degradation_ladder": {
+                "steps": [
+                    {"queue_depth": 10000, "action": "disable_audit"},
+                    {"queue_depth": 30000, "action": "sample_accounts", "sampling_rate": 10},
+                    {"queue_depth": 40000, "action": "drop"}
+                ],
+                "sustain_secs": 5
+            }
+        }))
+        .unwrap();
+        assert_eq!(config.degradation_ladder.unwrap().steps.len(), 3);
+        assert_eq!(
+            parse_error("{\"degradation_ladder\": {\"steps\": []}}"),
+            "The \"degradation_ladder.steps\" must not be empty"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"degradation_ladder\": {\"steps\": [{\"queue_depth\": 2000, \"action\": \"disable_audit\"}, \
+                {\"queue_depth\": 1000, \"action\": \"drop\"}]}}"
+            ),
+            "The \"degradation_ladder.steps[1].queue_depth\": 1000 must be greater than the queue depth of the previous step"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"queue_capacity\": 1000, \"degradation_ladder\": {\"steps\": [{\"queue_depth\": 2000, \"action\": \"drop\"}]}}"
+            ),
+            "The value of \"degradation_ladder.steps[0].queue_depth\": 2000 is out of the range [1, 1000]"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"degradation_ladder\": {\"steps\": [{\"queue_depth\": 1000, \"action\": \"sample_accounts\"}]}}"
+            ),
+            "The \"degradation_ladder.steps[0]\" 'sample_accounts' requires the \"sampling_rate\" to be set"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"degradation_ladder\": {\"steps\": [{\"queue_depth\": 1000, \"action\": \"drop\", \"sampling_rate\": 10}]}}"
+            ),
+            "The \"degradation_ladder.steps[0].sampling_rate\" only applies to the 'sample_accounts' action"
+        );
+    }
+
+    #[test]
+    fn test_parse_config_slot_checksums() {
+        let config = parse_config(serde_json::json!({
+            "store_slot_checksums": true,
+            "store_account_historical_data": true,
+            "signature_storage": "both",
+        }))
+        .unwrap();
+        assert_eq!(config.store_slot_checksums, Some(true));
+        assert_eq!(
+            parse_error("{\"store_slot_checksums\": true}"),
+            "The \"store_slot_checksums\" requires the \"store_account_historical_data\" set to 'true'"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"store_slot_checksums\": true, \"store_account_historical_data\": true, \"token_account_storage\": \"decoded\"}"
+            ),
+            "The \"store_slot_checksums\" must not be set along with the 'decoded' \"token_account_storage\""
+        );
+        assert_eq!(
+            parse_error(
+                "{\"store_slot_checksums\": true, \"store_account_historical_data\": true, \"account_data_compression\": {\"level\": 3}}"
+            ),
+            "The \"store_slot_checksums\" must not be set along with the \"account_data_compression\""
+        );
+        assert_eq!(
+            parse_error(
+                "{\"store_slot_checksums\": true, \"store_account_historical_data\": true, \"reconnect_max_backoff_secs\": 30, \"replay_window_secs\": 10}"
+            ),
+            "The \"store_slot_checksums\" must not be set along with the \"replay_window_secs\""
+        );
+        assert_eq!(
+            parse_error(
+                "{\"store_slot_checksums\": true, \"store_account_historical_data\": true, \"degradation_ladder\": {\"steps\": [{\"queue_depth\": 1000, \"action\": \"disable_audit\"}]}}"
+            ),
+            "The \"store_slot_checksums\" must not be set along with the 'disable_audit' step of the \"degradation_ladder\""
+        );
+    }
+
+    #[test]
+    fn test_parse_config_batch() {
+        assert_eq!(
+            parse_error("{\"batch\": {\"account\": {\"size\": 0}}}"),
+            "The value of \"batch.account.size\": 0 must be greater than 0"
+        );
+        assert_eq!(
+            parse_error("{\"batch\": {\"slot\": {\"max_delay\": 10}}}"),
+            "Unknown field \"batch.slot.max_delay\" in the config file, did you mean \"batch.slot.max_delay_ms\"?"
+        );
+        assert!(parse_error("{\"batch\": {\"block\": {}}}").contains("\"batch.block\""));
+        assert!(parse_config(serde_json::json!({
+            "batch": {
+                "account": {"size": 500, "max_delay_ms": 20},
+                "slot": {"max_delay_ms": 0}
+            }
+        }))
+        .is_ok());
+    }
+
+    #[test]
+    fn test_parse_config_sharding() {
+        assert_eq!(
+            parse_error("{\"sharding\": {\"connection_strs\": []}}"),
+            "The \"sharding.connection_strs\" must not be empty"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"connection_str\": \"host=a\", \"sharding\": {\"connection_strs\": [\"host=b\"]}}"
+            ),
+            "The \"connection_str\" must not be set along with the \"sharding.connection_strs\""
+        );
+        assert_eq!(
+            parse_error("{\"sharding\": {\"connection_strs\": [\"host=a\"], \"account\": 0}}"),
+            "The \"sharding.account\" requires the \"sharding.strategy\" set to 'by_kind'"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"sharding\": {\"connection_strs\": [\"host=a\", \"host=b\"], \"transaction\": 2}}"
+            ),
+            "The value of \"sharding.transaction\": 2 is out of the range [0, 1]"
+        );
+        assert!(
+            parse_error("{\"sharding\": {\"connection_strs\": [], \"block\": 0}}")
+                .contains("\"sharding.block\"")
+        );
+        let config = parse_config(serde_json::json!({
+            "sharding": {
+                "connection_strs": ["host=a", "host=b"],
+                "strategy": "by_kind",
+                "account": 1
+            }
+        }))
+        .unwrap();
+        assert_eq!(
+            config.sharding.unwrap().strategy,
+            Some(ShardingStrategy::ByKind)
+        );
+    }
+
+    #[test]
+    fn test_parse_config_index_advisor() {
+        assert_eq!(
+            parse_error("{\"index_advisor_min_calls\": 10}"),
+            "The \"index_advisor_min_calls\" requires the \"index_advisor_interval_secs\" to be set"
+        );
+        assert!(parse_error("{\"index_advisor_interval_secs\": 0}").contains("greater than 0"));
+        assert!(parse_error("{\"schema_drift_check_interval_secs\": 0}").contains("greater than 0"));
+        assert!(parse_config(serde_json::json!({
+            "index_advisor_interval_secs": 3600,
+            "index_advisor_min_calls": 10
+        }))
+        .is_ok());
+    }
+
+    #[test]
+    fn test_parse_config_account_layout() {
+        assert_eq!(
+            parse_error("{\"account_layout\": \"split\"}"),
+            "The \"account_layout\" 'split' requires the \"use_schema_triggers\" set to 'false'"
+        );
+        assert_eq!(
+            parse_config(serde_json::json!({
+                "account_layout": "split",
+                "use_schema_triggers": false
+            }))
+            .unwrap()
+            .account_layout,
+            Some(AccountLayout::Split)
+        );
+        assert!(parse_config(serde_json::json!({"account_layout": "wide"})).is_ok());
+    }
+
+    #[test]
+    fn test_parse_config_account_cold_tier() {
+        let requirement = "The \"account_cold_tier_slots\" requires the \"use_schema_triggers\" set to 'false' and the 'wide' \"account_layout\"";
+        assert_eq!(
+            parse_error("{\"account_cold_tier_slots\": 432000}"),
+            requirement
+        );
+        assert_eq!(
+            parse_error(
+                "{\"account_cold_tier_slots\": 432000, \"use_schema_triggers\": false, \"account_layout\": \"split\"}"
+            ),
+            requirement
+        );
+        assert!(
+            parse_error("{\"account_cold_tier_slots\": 0, \"use_schema_triggers\": false}")
+                .contains("greater than 0")
+        );
+        assert!(
+            parse_error("{\"account_cold_tier_tablespace\": \"cold_disk\"}")
+                .contains("requires the \"account_cold_tier_slots\"")
+        );
+        assert!(parse_error(
+            "{\"account_cold_tier_slots\": 432000, \"use_schema_triggers\": false, \"account_cold_tier_tablespace\": \"Cold Disk\"}"
+        )
+        .starts_with("The tablespace name"));
+        let config = parse_config(serde_json::json!({
+            "account_cold_tier_slots": 432000,
+            "use_schema_triggers": false,
+            "account_cold_tier_tablespace": "cold_disk"
+        }))
+        .unwrap();
+        assert_eq!(config.account_cold_tier_slots, Some(432000));
+    }
+
+    #[test]
+    fn test_parse_config_account_data_compression() {
+        assert_eq!(
+            parse_config(serde_json::json!({
+                "account_data_compression": {"level": 19, "min_bytes": 256}
+            }))
+            .unwrap()
+            .account_data_compression,
+            Some(AccountDataCompressionConfig {
+                level: Some(19),
+                min_bytes: Some(256),
+            })
+        );
+        assert!(
+            parse_error("{\"account_data_compression\": {\"level\": 23}}")
+                .contains("out of the range [1, 22]")
+        );
+        assert!(
+            parse_error("{\"account_data_compression\": {\"levle\": 3}}")
+                .contains("account_data_compression.levle")
+        );
+        assert_eq!(
+            parse_error(
+                "{\"account_data_compression\": {}, \"computed_columns\": {\"data_len\": \"length({data})\"}}"
+            ),
+            "The computed column \"data_len\" of the data must not be set along with the \"account_data_compression\""
+        );
+        assert_eq!(
+            parse_error(
+                "{\"account_data_compression\": {}, \"discriminator_owners\": [\"whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc\"]}"
+            ),
+            "The \"discriminator_owners\" must not be set along with the \"account_data_compression\""
+        );
+    }
+
+    #[test]
+    fn test_parse_config_storage_engine() {
+        assert_eq!(
+            parse_error("{\"hypertable_chunk_slots\": 1000}"),
+            "The \"hypertable_chunk_slots\" requires the \"storage_engine\" set to 'timescaledb'"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"storage_engine\": \"timescaledb\", \"hypertable_compress_after_slots\": 0}"
+            ),
+            "The value of \"hypertable_compress_after_slots\": 0 must be greater than 0"
+        );
+        let config = parse_config(serde_json::json!({
+            "storage_engine": "timescaledb",
+            "hypertable_chunk_slots": 216000,
+            "hypertable_compress_after_slots": 864000
+        }))
+        .unwrap();
+        assert_eq!(config.storage_engine, Some(StorageEngine::TimescaleDb));
+        assert_eq!(config.hypertable_chunk_slots, Some(216000));
+        assert!(parse_config(serde_json::json!({"storage_engine": "postgres"})).is_ok());
+    }
+
+    #[test]
+    fn test_parse_config_distributed() {
+        assert_eq!(
+            parse_error("{\"distributed\": \"citus\"}"),
+            "The \"distributed\" 'citus' requires the \"use_schema_triggers\" set to 'false'"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"distributed\": \"citus\", \"use_schema_triggers\": false, \"storage_engine\": \"timescaledb\"}"
+            ),
+            "The \"storage_engine\" is not allowed with the \"distributed\" set to 'citus'"
+        );
+        assert_eq!(
+            parse_error("{\"distributed_shard_count\": 64}"),
+            "The \"distributed_shard_count\" requires the \"distributed\" to be set"
+        );
+        let config = parse_config(serde_json::json!({
+            "distributed": "citus",
+            "distributed_shard_count": 64,
+            "use_schema_triggers": false
+        }))
+        .unwrap();
+        assert_eq!(config.distributed, Some(Distributed::Citus));
+        assert_eq!(config.distributed_shard_count, Some(64));
+    }
+
+    #[test]
+    fn test_parse_config_audit_retention_overrides() {
+        let overrides = "\"account_audit_retention_overrides\": {\"Vote111111111111111111111111111111111111111\": 1512000}";
+        assert_eq!(
+            parse_error(&format!("{{{}}}", overrides)),
+            "The \"account_audit_retention_overrides\" requires the \"account_audit_retention_slots\" to be set"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"account_audit_retention_slots\": 216000, \"account_audit_retention_overrides\": {\"0OIl\": 1}}"
+            ),
+            "The owner \"0OIl\" of the \"account_audit_retention_overrides\" is not a base58 encoded pubkey"
+        );
+        // Base58 encoded, but not 32 bytes long
+        assert_eq!(
+            parse_error(
+                "{\"account_audit_retention_slots\": 216000, \"account_audit_retention_overrides\": {\"Vote111\": 1}}"
+            ),
+            "The owner \"Vote111\" of the \"account_audit_retention_overrides\" is not a base58 encoded pubkey"
+        );
+        assert_eq!(
+            parse_error(&format!(
+                "{{\"account_audit_retention_slots\": 216000, \"storage_engine\": \"timescaledb\", {}}}",
+                overrides
+            )),
+            "The retention of the owner Vote111111111111111111111111111111111111111 of the \"account_audit_retention_overrides\" must not be longer than the \"account_audit_retention_slots\" with the \"storage_engine\" set to 'timescaledb'"
+        );
+        assert!(parse_config(
+            serde_json::from_str(&format!(
+                "{{\"account_audit_retention_slots\": 216000, {}}}",
+                overrides
+            ))
+            .unwrap()
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_parse_config_footprint() {
+        assert_eq!(
+            parse_error("{\"footprint\": \"minimal\", \"store_account_historical_data\": true}"),
+            "The \"store_account_historical_data\" is not allowed with the \"footprint\" set to 'minimal'"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"footprint\": \"minimal\", \"transaction_selector\": {\"mentions\": [\"*\"]}}"
+            ),
+            "The \"transaction_selector\" is not allowed with the \"footprint\" set to 'minimal'"
+        );
+        assert_eq!(
+            parse_error("{\"footprint\": \"minimal\", \"token_account_storage\": \"decoded\"}"),
+            "The \"token_account_storage\" is not allowed with the \"footprint\" set to 'minimal'"
+        );
+
+        let config = parse_config(serde_json::json!({"footprint": "minimal"})).unwrap();
+        assert_eq!(config.use_schema_triggers, Some(false));
+        assert_eq!(
+            config.batch.unwrap().account,
+            Some(BatchLimitConfig {
+                size: Some(MINIMAL_FOOTPRINT_ACCOUNT_BATCH_SIZE),
+                max_delay_ms: Some(MINIMAL_FOOTPRINT_ACCOUNT_BATCH_MAX_DELAY_MS),
+            })
+        );
+        // The statement batches configured are kept
+        let config = parse_config(serde_json::json!({
+            "footprint": "minimal",
+            "statement_batch_size": 100,
+        }))
+        .unwrap();
+        assert_eq!(config.batch, None);
+
+        let config = parse_config(serde_json::json!({"footprint": "full"})).unwrap();
+        assert_eq!(config.use_schema_triggers, None);
+    }
+
+    #[test]
+    fn test_parse_config_replay_window() {
+        assert_eq!(
+            parse_error("{\"replay_window_secs\": 60}"),
+            "The \"replay_window_secs\" requires the \"reconnect_max_backoff_secs\" to be set"
+        );
+        assert_eq!(
+            parse_error("{\"replay_window_size\": 1000, \"reconnect_max_backoff_secs\": 30}"),
+            "The \"replay_window_size\" requires the \"replay_window_secs\" to be set"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"replay_window_size\": 0, \"replay_window_secs\": 60, \"reconnect_max_backoff_secs\": 30}"
+            ),
+            "The value of \"replay_window_size\": 0 must be greater than 0"
+        );
+        assert!(parse_config(serde_json::json!({
+            "replay_window_secs": 60,
+            "replay_window_size": 1000,
+            "reconnect_max_backoff_secs": 30
+        }))
+        .is_ok());
+    }
+
+    #[test]
+    fn test_parse_config_statement_timeouts() {
+        assert_eq!(
+            parse_error("{\"table_statement_timeouts_ms\": {\"owner_change\": 1000}}"),
+            "The statement timeout table \"owner_change\" is not one of the supported tables: [\"account\", \"account_audit\", \"slot\", \"transaction\", \"block\", \"entry\", \"vote_activity\"]"
+        );
+        assert_eq!(
+            parse_error("{\"table_statement_timeouts_ms\": {\"slot\": 0}}"),
+            "The value of \"table_statement_timeouts_ms.slot\": 0 must be greater than 0"
+        );
+        assert!(parse_config(serde_json::json!({
+            "statement_timeout_ms": 5000,
+            "table_statement_timeouts_ms": {"account_audit": 60000, "slot": 500}
+        }))
+        .is_ok());
+    }
+
+    #[test]
+    fn test_parse_config_failover() {
+        assert_eq!(
+            parse_error("{\"failover_after_secs\": 60}"),
+            "The \"failover_after_secs\" requires the \"fallback_connection_str\" to be set"
+        );
+        assert_eq!(
+            parse_error("{\"fallback_connection_str\": \"host=standby\"}"),
+            "The \"fallback_connection_str\" requires the \"reconnect_max_backoff_secs\" to be set"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"fallback_connection_str\": \"host=standby\", \"reconnect_max_backoff_secs\": 30, \
+                \"sharding\": {\"connection_strs\": [\"host=a\", \"host=b\"]}}"
+            ),
+            "The \"fallback_connection_str\" must not be set along with the \"sharding\""
+        );
+        assert!(parse_config(serde_json::json!({
+            "fallback_connection_str": "host=standby",
+            "reconnect_max_backoff_secs": 30,
+            "health_check_interval_secs": 5,
+            "failover_after_secs": 60
+        }))
+        .is_ok());
+    }
+
+    #[test]
+    fn test_parse_config_tunnel() {
+        let config = parse_config(serde_json::json!({
+            "host": "db.internal",
+            "user": "solana",
+            "tunnel": {"socks5_proxy": "10.0.0.5:1080", "socks5_user": "solana", "socks5_password": "pwd"}
+        }))
+        .unwrap();
+        assert_eq!(
+            config.tunnel.unwrap().socks5_proxy.as_deref(),
+            Some("10.0.0.5:1080")
+        );
+        assert_eq!(config.tunnel_local_port, None);
+        assert_eq!(
+            parse_error("{\"tunnel\": {}}"),
+            "The \"tunnel\" requires the \"tunnel.socks5_proxy\" or the \"tunnel.ssh_jump_host\" to be set"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"tunnel\": {\"socks5_proxy\": \"10.0.0.5:1080\", \"ssh_jump_host\": \"bastion\"}}"
+            ),
+            "The \"tunnel.socks5_proxy\" must not be set along with the \"tunnel.ssh_jump_host\""
+        );
+        assert_eq!(
+            parse_error(
+                "{\"tunnel\": {\"ssh_jump_host\": \"bastion\", \"socks5_user\": \"solana\"}}"
+            ),
+            "The \"tunnel.socks5_user\" requires the \"tunnel.socks5_proxy\" to be set"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"tunnel\": {\"socks5_proxy\": \"10.0.0.5:1080\", \"ssh_identity_file\": \"id\"}}"
+            ),
+            "The \"tunnel.ssh_identity_file\" requires the \"tunnel.ssh_jump_host\" to be set"
+        );
+        assert_eq!(
+            parse_error("{\"tunnel\": {\"ssh_jumphost\": \"bastion\"}}"),
+            "Unknown field \"tunnel.ssh_jumphost\" in the config file, did you mean \"tunnel.ssh_jump_host\"?"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"tunnel\": {\"ssh_jump_host\": \"bastion\"}, \
+                \"sharding\": {\"connection_strs\": [\"host=a\", \"host=b\"]}}"
+            ),
+            "The \"tunnel\" must not be set along with the \"sharding\""
+        );
+    }
+
+    #[test]
+    fn test_parse_config_credentials() {
+        let config = parse_config(serde_json::json!({
+            "connection_str": "host=db.internal user=solana",
+            "credentials": {"password_file": "/run/secrets/pg", "refresh_interval_secs": 300}
+        }))
+        .unwrap();
+        assert_eq!(
+            config.credentials.unwrap().password_file.as_deref(),
+            Some("/run/secrets/pg")
+        );
+        assert_eq!(
+            parse_error("{\"credentials\": {}}"),
+            "The \"credentials\" requires the \"credentials.password_env\", the \"credentials.password_file\" or the \"credentials.password_command\" to be set"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"credentials\": {\"password_env\": \"PGPASSWORD\", \"password_command\": [\"pass\"]}}"
+            ),
+            "The \"credentials.password_env\" must not be set along with the \"credentials.password_command\""
+        );
+        assert_eq!(
+            parse_error("{\"credentials\": {\"password_command\": []}}"),
+            "The \"credentials.password_command\" must not be empty"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"connection_str\": \"host=db user=solana password=pwd\", \
+                \"credentials\": {\"password_env\": \"PGPASSWORD\"}}"
+            ),
+            "The \"credentials\" must not be set along with a password in the \"connection_str\""
+        );
+        assert_eq!(
+            parse_error("{\"credentials\": {\"password_envs\": \"PGPASSWORD\"}}"),
+            "Unknown field \"credentials.password_envs\" in the config file, did you mean \"credentials.password_env\"?"
+        );
+    }
+
+    #[test]
+    fn test_parse_config_signature_storage() {
+        let config =
+            parse_config(serde_json::from_str("{\"signature_storage\": \"both\"}").unwrap())
+                .unwrap();
+        assert_eq!(config.signature_storage, Some(SignatureStorage::Both));
+        assert!(parse_error("{\"signature_storage\": \"hex\"}").contains("unknown variant `hex`"));
+    }
+
+    #[test]
+    fn test_parse_config_token_account_storage() {
+        let config = parse_config(serde_json::json!({"token_account_storage": "mints"})).unwrap();
+        assert_eq!(
+            config.token_account_storage,
+            Some(TokenAccountStorage::Mints)
+        );
+        assert_eq!(
+            parse_error("{\"footprint\": \"minimal\", \"token_account_storage\": \"mints\"}"),
+            "The \"token_account_storage\" is not allowed with the \"footprint\" set to 'minimal'"
+        );
+    }
+
+    #[test]
+    fn test_parse_config_static_columns() {
+        let config = parse_config(
+            serde_json::from_str(
+                "{\"static_columns\": {\"region\": \"us-east\", \"shard\": \"3\"}}",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(config.static_columns.unwrap().len(), 2);
+        assert!(
+            parse_error("{\"static_columns\": {\"region; DROP TABLE slot\": \"us\"}}")
+                .starts_with("The static column name")
+        );
+    }
+
+    #[test]
+    fn test_parse_config_computed_columns() {
+        assert!(parse_config(serde_json::json!({
+            "computed_columns": {"discriminator": "substring({data} from 1 for 8)"}
+        }))
+        .is_ok());
+        assert_eq!(
+            parse_error("{\"computed_columns\": {\"Discriminator\": \"{data}\"}}"),
+            "The computed column name \"Discriminator\" must consist of lowercase letters, digits and underscores, starting with a letter or an underscore"
+        );
+        assert_eq!(
+            parse_error("{\"computed_columns\": {\"data\": \"{data}\"}}"),
+            "The computed column \"data\" is already a column of the account table"
+        );
+        assert!(parse_error("{\"computed_columns\": {\"size\": \"length({dat})\"}}")
+            .starts_with(
+                "The expression of the computed column \"size\" refers to the unknown placeholder \"dat\""
+            ));
+    }
+
+    #[test]
+    fn test_parse_config_account_data_decoders() {
+        let config = parse_config(serde_json::json!({
+            "account_data_decoders": {
+                "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc": {"idl": "whirlpool.json"}
+            }
+        }))
+        .unwrap();
+        assert_eq!(config.account_data_decoders.unwrap().len(), 1);
+        assert_eq!(
+            parse_error("{\"account_data_decoders\": {}}"),
+            "The \"account_data_decoders\" must not be empty"
+        );
+        assert_eq!(
+            parse_error("{\"account_data_decoders\": {\"0x12\": {\"idl\": \"a.json\"}}}"),
+            "The owner \"0x12\" of the \"account_data_decoders\" is not base58 encoded"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"account_data_decoders\": {\"whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc\": {}}}"
+            ),
+            "The decoder of the owner whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc must set either \"idl\" or \"borsh_schema\""
+        );
+        assert!(parse_error(
+            "{\"account_data_decoders\": {\"whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc\": {\"idll\": \"a.json\"}}}"
+        )
+        .contains("account_data_decoders.whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc.idll"));
+    }
+
+    #[test]
+    fn test_parse_config_discriminator_owners() {
+        assert!(parse_config(serde_json::json!({
+            "discriminator_owners": ["whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc"]
+        }))
+        .is_ok());
+        assert_eq!(
+            parse_error("{\"discriminator_owners\": []}"),
+            "The \"discriminator_owners\" must not be empty"
+        );
+        assert_eq!(
+            parse_error("{\"discriminator_owners\": [\"0x12\"]}"),
+            "The value of \"discriminator_owners\": \"0x12\" is not base58 encoded"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"discriminator_owners\": [\"whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc\"], \
+                \"computed_columns\": {\"discriminator\": \"substring({data} from 1 for 8)\"}}"
+            ),
+            "The \"discriminator\" column of the \"discriminator_owners\" must not be a static or computed column"
+        );
+    }
+
+    #[test]
+    fn test_parse_config_schema() {
+        let config =
+            parse_config(serde_json::from_str("{\"schema\": \"testnet\"}").unwrap()).unwrap();
+        assert_eq!(config.schema.as_deref(), Some("testnet"));
+        assert!(parse_error("{\"schema\": \"mainnet-beta\"}").starts_with("The schema name"));
+
+        for schema_source in ["/etc/geyser/migrations", "https://example.com/migrations"] {
+            let config = parse_config(serde_json::json!({"schema_source": schema_source})).unwrap();
+            assert_eq!(config.schema_source.as_deref(), Some(schema_source));
+        }
+        assert_eq!(
+            parse_error("{\"schema_source\": \"s3://bucket/migrations\"}"),
+            "The \"schema_source\" \"s3://bucket/migrations\" must be a directory or an http(s) URL"
+        );
+    }
+
+    #[test]
+    fn test_parse_config_parsed_instruction_programs() {
+        let config = parse_config(serde_json::json!({
+            "parsed_instruction_programs": ["system", "spl-token"]
+        }))
+        .unwrap();
+        assert_eq!(config.parsed_instruction_programs.unwrap().len(), 2);
+        assert_eq!(
+            parse_error("{\"parsed_instruction_programs\": []}"),
+            "The \"parsed_instruction_programs\" must not be empty"
+        );
+        assert!(parse_error("{\"parsed_instruction_programs\": [\"token\"]}")
+            .starts_with("The program \"token\" of the \"parsed_instruction_programs\" is not one of the supported programs"));
+    }
+
+    #[test]
+    fn test_parse_config_continuous_aggregates() {
+        let config = parse_config(serde_json::json!({
+            "continuous_aggregates": ["transactions_hourly"],
+            "continuous_aggregate_refresh_slots": 64
+        }))
+        .unwrap();
+        assert_eq!(config.continuous_aggregates.unwrap().len(), 1);
+        assert_eq!(
+            parse_error("{\"continuous_aggregates\": []}"),
+            "The \"continuous_aggregates\" must not be empty"
+        );
+        assert!(parse_error("{\"continuous_aggregates\": [\"Fees\"]}")
+            .starts_with("The continuous aggregate \"Fees\" must consist of"));
+    }
+
+    #[test]
+    fn test_parse_config_notify_channels() {
+        let config = parse_config(serde_json::json!({
+            "notify_channels": {"account": "account_updates", "slot": "slot_updates"}
+        }))
+        .unwrap();
+        assert_eq!(config.notify_channels.unwrap().len(), 2);
+        assert_eq!(
+            parse_error("{\"notify_channels\": {\"entry\": \"entries\"}}"),
+            "The notify channel update type \"entry\" is not one of the supported types: [\"account\", \"slot\", \"transaction\", \"block\"]"
+        );
+        assert_eq!(
+            parse_error("{\"notify_channels\": {\"slot\": \"Slot Updates\"}}"),
+            "The notify channel \"Slot Updates\" must consist of lowercase letters, digits and underscores, starting with a letter or an underscore"
+        );
+    }
+
+    #[test]
+    fn test_parse_config_post_commit_hooks() {
+        let config = parse_config(
+            serde_json::from_str(
+                "{\"post_commit_hooks\": {\"block\": \"SELECT pg_notify('block_written', $3::text)\"}}",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(config.post_commit_hooks.unwrap().len(), 1);
+        assert!(
+            parse_error("{\"post_commit_hooks\": {\"vote_activity\": \"SELECT 1\"}}")
+                .starts_with("The post commit hook table \"vote_activity\"")
+        );
+    }
+
+    #[test]
+    fn test_parse_config_audit_archive_path() {
+        assert_eq!(
+            parse_error("{\"account_audit_archive_path\": \"\"}"),
+            "The \"account_audit_archive_path\" must not be empty"
+        );
+        assert!(parse_config(
+            serde_json::from_str("{\"account_audit_archive_path\": \"/var/archive\"}").unwrap()
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_parse_config_async_client() {
+        let config = parse_config(serde_json::json!({
+            "host": "localhost",
+            "user": "solana",
+            "client": "async",
+            "async_pool_size": 16,
+            "async_request_timeout_ms": 5000,
+            "statement_timeout_ms": 1000,
+            "threads": null
+        }))
+        .unwrap();
+        assert_eq!(config.client, Some(ClientKind::Async));
+        assert_eq!(config.async_pool_size, Some(16));
+        let fields = field_names::<AccountsDbPluginPostgresConfig>();
+        assert!(ASYNC_CLIENT_FIELDS
+            .iter()
+            .all(|field| fields.contains(field)));
+        assert_eq!(
+            parse_error("{\"client\": \"async\", \"threads\": 4}"),
+            "The \"threads\" is not supported with the \"client\" 'async'"
+        );
+        assert_eq!(
+            parse_error("{\"client\": \"blocking\", \"async_pool_size\": 4}"),
+            "The \"async_pool_size\" requires the \"client\" 'async'"
+        );
+        assert_eq!(
+            parse_error("{\"client\": \"async\", \"async_pool_size\": 0}"),
+            "The value of \"async_pool_size\": 0 must be greater than 0"
+        );
+        assert!(parse_error(
+            "{\"client\": \"async\", \"accounts_selector\": {\"accounts\": [\"*\"]}, \
+            \"transaction_selector\": {\"mentions\": [\"*\"]}}"
+        )
+        .contains("Set the \"async_pool_size\" to at least 32"));
+    }
+
+    #[test]
+    fn test_parse_config_accounts_selector_preset() {
+        let config =
+            parse_config(serde_json::json!({"accounts_selector": {"preset": "validator-ops"}}))
+                .unwrap();
+        assert_eq!(
+            config.accounts_selector.unwrap().preset.as_deref(),
+            Some("validator-ops")
+        );
+        assert_eq!(
+            parse_error("{\"priority_accounts\": {\"preset\": \"defi\"}}"),
+            "The preset \"priority_accounts.preset\": \"defi\" is not one of the presets: [\"spl-token\", \"all-defi-core\", \"validator-ops\"]"
+        );
+    }
+
+    #[test]
+    fn test_parse_config_accounts_selector_rules() {
+        let config = parse_config(
+            serde_json::from_str(
+                "{\"accounts_selector\": {\"rules\": [{\"owner\": \"9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin\", \
+                \"max_data_size\": 1023, \"discriminator\": \"Ldp\"}]}}",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(config.accounts_selector.unwrap().rules.unwrap().len(), 1);
+        assert_eq!(
+            parse_error("{\"accounts_selector\": {\"rules\": [{\"owners\": \"x\"}]}}"),
+            "Unknown field \"accounts_selector.rules[0].owners\" in the config file, did you mean \"accounts_selector.rules[0].owner\"?"
+        );
+        assert_eq!(
+            parse_error("{\"priority_accounts\": {\"rules\": [{}]}}"),
+            "The accounts selector rule \"priority_accounts.rules[0]\" has no condition"
+        );
+        assert!(parse_error(
+            "{\"accounts_selector\": {\"rules\": [{\"discriminator\": \"0OIl\"}]}}"
+        )
+        .ends_with("is not base58 encoded"));
+        assert!(parse_error(
+            "{\"accounts_selector\": {\"rules\": [{\"min_data_size\": 10, \"max_data_size\": 1}]}}"
+        )
+        .contains("must not be greater than"));
+        assert_eq!(
+            parse_error(
+                "{\"accounts_selector\": {\"accounts\": [\"*\"], \"exclude_owners\": [\"0OIl\"]}}"
+            ),
+            "The value of \"accounts_selector.exclude_owners\": \"0OIl\" is not base58 encoded"
+        );
+        assert_eq!(
+            parse_error(
+                "{\"startup_accounts_selector\": {\"min_lamports\": 10, \"max_lamports\": 1}}"
+            ),
+            "The \"startup_accounts_selector.min_lamports\": 10 must not be greater than the \"startup_accounts_selector.max_lamports\": 1"
+        );
+    }
+
+    #[test]
+    fn test_parse_config_account_versions_selector() {
+        let config = parse_config(serde_json::json!({
+            "account_versions_selector": {"owners": ["9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin"]}
+        }))
+        .unwrap();
+        assert!(config.account_versions_selector.is_some());
+        assert_eq!(
+            parse_error("{\"account_versions_selector\": {\"exclude_owners\": [\"0OIl\"]}}"),
+            "The value of \"account_versions_selector.exclude_owners\": \"0OIl\" is not base58 encoded"
+        );
+        assert_eq!(
+            parse_error("{\"account_versions_selector\": {\"owner\": []}}"),
+            "Unknown field \"account_versions_selector.owner\" in the config file, did you mean \"account_versions_selector.owners\"?"
+        );
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("batchsize", "batch_size"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+}
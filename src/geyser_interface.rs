@@ -0,0 +1,222 @@
+/// The geyser interface is the compatibility layer over the versions of the geyser
+/// plugin interface. The rest of the plugin uses the notification info of the latest
+/// versions supported through the aliases below, and the versioned notifications are
+/// unwrapped here. The plugin is built against a single version of the interface crate,
+/// and the versions of the notifications not supported are rejected. The account info
+/// is the plugin's own, as the versions of the interface convey the transaction causing
+/// an account update differently.
+use {
+    agave_geyser_plugin_interface::geyser_plugin_interface::{
+        GeyserPluginError, ReplicaAccountInfoVersions, ReplicaBlockInfoV4,
+        ReplicaBlockInfoVersions, ReplicaEntryInfoV2, ReplicaEntryInfoVersions,
+        ReplicaTransactionInfoV2, ReplicaTransactionInfoVersions,
+    },
+    solana_sdk::signature::Signature,
+};
+
+/// The account info of the versions of the interface, not the `ReplicaAccountInfo` of
+/// the first version of the interface
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplicaAccountInfo<'a> {
+    pub pubkey: &'a [u8],
+    pub lamports: u64,
+    pub owner: &'a [u8],
+    pub executable: bool,
+    pub rent_epoch: u64,
+    pub data: &'a [u8],
+    pub write_version: u64,
+    /// The signature of the transaction causing the update, none for the updates of
+    /// the startup and of the first version of the interface
+    pub txn_signature: Option<&'a Signature>,
+}
+/// The transaction info of the latest version supported
+pub type ReplicaTransactionInfo<'a> = ReplicaTransactionInfoV2<'a>;
+/// The block info of the latest version supported
+pub type ReplicaBlockInfo<'a> = ReplicaBlockInfoV4<'a>;
+/// The entry info of the latest version supported
+pub type ReplicaEntryInfo<'a> = ReplicaEntryInfoV2<'a>;
+
+/// Get the account info of any version, along with the signature of the transaction
+/// causing the update when the version conveys it: the second version carries the
+/// signature and the third one the transaction itself.
+pub(crate) fn account_info<'a>(account: ReplicaAccountInfoVersions<'a>) -> ReplicaAccountInfo<'a> {
+    match account {
+        ReplicaAccountInfoVersions::V0_0_1(account) => ReplicaAccountInfo {
+            pubkey: account.pubkey,
+            lamports: account.lamports,
+            owner: account.owner,
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+            data: account.data,
+            write_version: account.write_version,
+            txn_signature: None,
+        },
+        ReplicaAccountInfoVersions::V0_0_2(account) => ReplicaAccountInfo {
+            pubkey: account.pubkey,
+            lamports: account.lamports,
+            owner: account.owner,
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+            data: account.data,
+            write_version: account.write_version,
+            txn_signature: account.txn_signature,
+        },
+        ReplicaAccountInfoVersions::V0_0_3(account) => ReplicaAccountInfo {
+            pubkey: account.pubkey,
+            lamports: account.lamports,
+            owner: account.owner,
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+            data: account.data,
+            write_version: account.write_version,
+            txn_signature: account.txn.map(|txn| txn.signature()),
+        },
+    }
+}
+
+/// Get the transaction info of the latest version supported, the earlier versions
+/// lack the index of the transaction in the block.
+pub(crate) fn transaction_info<'a>(
+    transaction_info: ReplicaTransactionInfoVersions<'a>,
+) -> Result<&'a ReplicaTransactionInfo<'a>, GeyserPluginError> {
+    match transaction_info {
+        ReplicaTransactionInfoVersions::V0_0_2(transaction_info) => Ok(transaction_info),
+        ReplicaTransactionInfoVersions::V0_0_1(_) => {
+            Err(GeyserPluginError::SlotStatusUpdateError {
+                msg: "Wrong ReplicaTransactionInfoVersions version: V0_0_1.".to_string(),
+            })
+        }
+    }
+}
+
+/// Get the block info of the latest version supported, the earlier versions lack the
+/// partitions of the rewards.
+pub(crate) fn block_info<'a>(
+    block_info: ReplicaBlockInfoVersions<'a>,
+) -> Result<&'a ReplicaBlockInfo<'a>, GeyserPluginError> {
+    match block_info {
+        ReplicaBlockInfoVersions::V0_0_4(block_info) => Ok(block_info),
+        ReplicaBlockInfoVersions::V0_0_1(_) => Err(wrong_block_info_version("V0_0_1")),
+        ReplicaBlockInfoVersions::V0_0_2(_) => Err(wrong_block_info_version("V0_0_2")),
+        ReplicaBlockInfoVersions::V0_0_3(_) => Err(wrong_block_info_version("V0_0_3")),
+    }
+}
+
+fn wrong_block_info_version(version: &str) -> GeyserPluginError {
+    GeyserPluginError::SlotStatusUpdateError {
+        msg: format!("Wrong ReplicaBlockInfoVersions version: {}.", version),
+    }
+}
+
+/// Get the entry info of the latest version supported, the earlier version lacks the
+/// index of the first transaction of the entry.
+pub(crate) fn entry_info<'a>(
+    entry_info: ReplicaEntryInfoVersions<'a>,
+) -> Result<&'a ReplicaEntryInfo<'a>, GeyserPluginError> {
+    match entry_info {
+        ReplicaEntryInfoVersions::V0_0_2(entry_info) => Ok(entry_info),
+        ReplicaEntryInfoVersions::V0_0_1(_) => Err(GeyserPluginError::SlotStatusUpdateError {
+            msg: "Wrong ReplicaEntryInfoVersions version: V0_0_1.".to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        agave_geyser_plugin_interface::geyser_plugin_interface::{
+            ReplicaAccountInfo as ReplicaAccountInfoV1, ReplicaAccountInfoV2, ReplicaAccountInfoV3,
+            ReplicaBlockInfoV3,
+        },
+    };
+
+    #[test]
+    fn test_account_info() {
+        let pubkey = [1u8; 32];
+        let owner = [2u8; 32];
+        let data = [3u8; 4];
+        let signature = Signature::from([4u8; 64]);
+        let expected = ReplicaAccountInfo {
+            pubkey: &pubkey,
+            lamports: 1_000_000,
+            owner: &owner,
+            executable: false,
+            rent_epoch: 7,
+            data: &data,
+            write_version: 42,
+            txn_signature: None,
+        };
+
+        let account = ReplicaAccountInfoV1 {
+            pubkey: &pubkey,
+            lamports: 1_000_000,
+            owner: &owner,
+            executable: false,
+            rent_epoch: 7,
+            data: &data,
+            write_version: 42,
+        };
+        assert_eq!(
+            account_info(ReplicaAccountInfoVersions::V0_0_1(&account)),
+            expected
+        );
+
+        let mut account = ReplicaAccountInfoV2 {
+            pubkey: &pubkey,
+            lamports: 1_000_000,
+            owner: &owner,
+            executable: false,
+            rent_epoch: 7,
+            data: &data,
+            write_version: 42,
+            txn_signature: None,
+        };
+        assert_eq!(
+            account_info(ReplicaAccountInfoVersions::V0_0_2(&account)),
+            expected
+        );
+        account.txn_signature = Some(&signature);
+        assert_eq!(
+            account_info(ReplicaAccountInfoVersions::V0_0_2(&account)).txn_signature,
+            Some(&signature)
+        );
+
+        // The startup updates of the third version come without a transaction
+        let account = ReplicaAccountInfoV3 {
+            pubkey: &pubkey,
+            lamports: 1_000_000,
+            owner: &owner,
+            executable: false,
+            rent_epoch: 7,
+            data: &data,
+            write_version: 42,
+            txn: None,
+        };
+        assert_eq!(
+            account_info(ReplicaAccountInfoVersions::V0_0_3(&account)),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_block_info_wrong_version() {
+        let block = ReplicaBlockInfoV3 {
+            parent_slot: 1,
+            parent_blockhash: "parent",
+            slot: 2,
+            blockhash: "block",
+            rewards: &[],
+            block_time: None,
+            block_height: None,
+            executed_transaction_count: 0,
+            entry_count: 0,
+        };
+        match block_info(ReplicaBlockInfoVersions::V0_0_3(&block)) {
+            Err(GeyserPluginError::SlotStatusUpdateError { msg }) => {
+                assert_eq!(msg, "Wrong ReplicaBlockInfoVersions version: V0_0_3.")
+            }
+            _ => panic!("the version should be rejected"),
+        }
+    }
+}
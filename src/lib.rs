@@ -1,4 +1,13 @@
+mod account_sampler;
 pub mod accounts_selector;
 pub mod accountsdb_plugin_postgres;
+mod config_include;
+mod config_validation;
+pub mod geyser_interface;
+mod notification_capture;
 pub mod postgres_client;
+mod selector_dry_run;
+mod selector_reload;
+mod slot_range;
+mod sysvar_sampler;
 pub mod transaction_selector;
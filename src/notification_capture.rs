@@ -0,0 +1,471 @@
+/// The notification capture is responsible for capturing the notifications selected by
+/// the plugin into a file, as the length-delimited SubscribeUpdate messages of the
+/// Yellowstone gRPC geyser protobuf definitions, so that the captures are read and
+/// replayed by the tooling of the Geyser ecosystem.
+use {
+    crate::{
+        accountsdb_plugin_postgres::AccountsDbPluginPostgresError,
+        geyser_interface::{ReplicaAccountInfo, ReplicaBlockInfo, ReplicaTransactionInfo},
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::{GeyserPluginError, SlotStatus},
+    log::*,
+    prost::Message,
+    solana_storage_proto::convert::generated,
+    std::{
+        fs::{File, OpenOptions},
+        io::{BufWriter, Write},
+        sync::Mutex,
+    },
+};
+
+/// The subset of the messages of the geyser.proto definitions of Yellowstone gRPC the
+/// notifications are captured as, with the same tags.
+pub(crate) mod geyser {
+    use solana_storage_proto::convert::generated;
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SubscribeUpdate {
+        #[prost(string, repeated, tag = "1")]
+        pub filters: Vec<String>,
+        #[prost(oneof = "subscribe_update::UpdateOneof", tags = "2, 3, 4, 7")]
+        pub update_oneof: Option<subscribe_update::UpdateOneof>,
+    }
+
+    pub mod subscribe_update {
+        #[allow(clippy::large_enum_variant)]
+        #[derive(Clone, PartialEq, ::prost::Oneof)]
+        pub enum UpdateOneof {
+            #[prost(message, tag = "2")]
+            Account(super::SubscribeUpdateAccount),
+            #[prost(message, tag = "3")]
+            Slot(super::SubscribeUpdateSlot),
+            #[prost(message, tag = "4")]
+            Transaction(super::SubscribeUpdateTransaction),
+            #[prost(message, tag = "7")]
+            BlockMeta(super::SubscribeUpdateBlockMeta),
+        }
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SubscribeUpdateAccount {
+        #[prost(message, optional, tag = "1")]
+        pub account: Option<SubscribeUpdateAccountInfo>,
+        #[prost(uint64, tag = "2")]
+        pub slot: u64,
+        #[prost(bool, tag = "3")]
+        pub is_startup: bool,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SubscribeUpdateAccountInfo {
+        #[prost(bytes = "vec", tag = "1")]
+        pub pubkey: Vec<u8>,
+        #[prost(uint64, tag = "2")]
+        pub lamports: u64,
+        #[prost(bytes = "vec", tag = "3")]
+        pub owner: Vec<u8>,
+        #[prost(bool, tag = "4")]
+        pub executable: bool,
+        #[prost(uint64, tag = "5")]
+        pub rent_epoch: u64,
+        #[prost(bytes = "vec", tag = "6")]
+        pub data: Vec<u8>,
+        #[prost(uint64, tag = "7")]
+        pub write_version: u64,
+        #[prost(bytes = "vec", optional, tag = "8")]
+        pub txn_signature: Option<Vec<u8>>,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SubscribeUpdateSlot {
+        #[prost(uint64, tag = "1")]
+        pub slot: u64,
+        #[prost(uint64, optional, tag = "2")]
+        pub parent: Option<u64>,
+        #[prost(enumeration = "SlotStatus", tag = "3")]
+        pub status: i32,
+        #[prost(string, optional, tag = "4")]
+        pub dead_error: Option<String>,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    #[allow(clippy::enum_variant_names)]
+    pub enum SlotStatus {
+        SlotProcessed = 0,
+        SlotConfirmed = 1,
+        SlotFinalized = 2,
+        SlotFirstShredReceived = 3,
+        SlotCompleted = 4,
+        SlotCreatedBank = 5,
+        SlotDead = 6,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SubscribeUpdateTransaction {
+        #[prost(message, optional, tag = "1")]
+        pub transaction: Option<SubscribeUpdateTransactionInfo>,
+        #[prost(uint64, tag = "2")]
+        pub slot: u64,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SubscribeUpdateTransactionInfo {
+        #[prost(bytes = "vec", tag = "1")]
+        pub signature: Vec<u8>,
+        #[prost(bool, tag = "2")]
+        pub is_vote: bool,
+        #[prost(message, optional, tag = "3")]
+        pub transaction: Option<generated::Transaction>,
+        #[prost(message, optional, tag = "4")]
+        pub meta: Option<generated::TransactionStatusMeta>,
+        #[prost(uint64, tag = "5")]
+        pub index: u64,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct SubscribeUpdateBlockMeta {
+        #[prost(uint64, tag = "1")]
+        pub slot: u64,
+        #[prost(string, tag = "2")]
+        pub blockhash: String,
+        #[prost(message, optional, tag = "3")]
+        pub rewards: Option<generated::Rewards>,
+        #[prost(message, optional, tag = "4")]
+        pub block_time: Option<generated::UnixTimestamp>,
+        #[prost(message, optional, tag = "5")]
+        pub block_height: Option<generated::BlockHeight>,
+        #[prost(uint64, tag = "7")]
+        pub parent_slot: u64,
+        #[prost(string, tag = "8")]
+        pub parent_blockhash: String,
+        #[prost(uint64, tag = "9")]
+        pub executed_transaction_count: u64,
+        #[prost(uint64, tag = "10")]
+        pub entries_count: u64,
+    }
+}
+
+use geyser::{subscribe_update::UpdateOneof, SubscribeUpdate};
+
+pub(crate) struct NotificationCapture {
+    path: String,
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl NotificationCapture {
+    /// Open the capture file when the capture is configured, the notifications are
+    /// appended to the ones captured before.
+    pub(crate) fn new(capture_file: Option<&str>) -> Result<Option<Self>, GeyserPluginError> {
+        let Some(path) = capture_file else {
+            return Ok(None);
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| {
+                GeyserPluginError::Custom(Box::new(
+                    AccountsDbPluginPostgresError::ConfigurationError {
+                        msg: format!("Failed to open the capture file {}. Error: {:?}", path, err),
+                    },
+                ))
+            })?;
+        info!("Capturing the notifications into {}", path);
+        Ok(Some(Self {
+            path: path.to_string(),
+            writer: Mutex::new(BufWriter::new(file)),
+        }))
+    }
+
+    fn capture(&self, update: UpdateOneof) -> Result<(), GeyserPluginError> {
+        let update = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(update),
+        };
+        let mut writer = self.writer.lock().unwrap();
+        writer
+            .write_all(&update.encode_length_delimited_to_vec())
+            .map_err(|err| self.to_capture_error("write", err))
+    }
+
+    /// Write the notifications captured still buffered to the capture file.
+    pub(crate) fn flush(&self) -> Result<(), GeyserPluginError> {
+        self.writer
+            .lock()
+            .unwrap()
+            .flush()
+            .map_err(|err| self.to_capture_error("flush", err))
+    }
+
+    fn to_capture_error(&self, action: &str, err: std::io::Error) -> GeyserPluginError {
+        let msg = format!(
+            "Failed to {} the notifications captured into {}. Error: {:?}",
+            action, self.path, err
+        );
+        error!("{}", msg);
+        GeyserPluginError::Custom(Box::new(
+            AccountsDbPluginPostgresError::ConfigurationError { msg },
+        ))
+    }
+
+    pub(crate) fn capture_account(
+        &self,
+        account: &ReplicaAccountInfo,
+        slot: u64,
+        is_startup: bool,
+    ) -> Result<(), GeyserPluginError> {
+        self.capture(UpdateOneof::Account(geyser::SubscribeUpdateAccount {
+            account: Some(geyser::SubscribeUpdateAccountInfo {
+                pubkey: account.pubkey.to_vec(),
+                lamports: account.lamports,
+                owner: account.owner.to_vec(),
+                executable: account.executable,
+                rent_epoch: account.rent_epoch,
+                data: account.data.to_vec(),
+                write_version: account.write_version,
+                txn_signature: account
+                    .txn_signature
+                    .map(|signature| signature.as_ref().to_vec()),
+            }),
+            slot,
+            is_startup,
+        }))
+    }
+
+    /// Capture the slot status, flushing the capture file once the slot is rooted so
+    /// that the rooted slots are captured in full.
+    pub(crate) fn capture_slot_status(
+        &self,
+        slot: u64,
+        parent: Option<u64>,
+        status: &SlotStatus,
+    ) -> Result<(), GeyserPluginError> {
+        let (status_value, dead_error) = match status {
+            SlotStatus::Processed => (geyser::SlotStatus::SlotProcessed, None),
+            SlotStatus::Confirmed => (geyser::SlotStatus::SlotConfirmed, None),
+            SlotStatus::Rooted => (geyser::SlotStatus::SlotFinalized, None),
+            SlotStatus::FirstShredReceived => (geyser::SlotStatus::SlotFirstShredReceived, None),
+            SlotStatus::Completed => (geyser::SlotStatus::SlotCompleted, None),
+            SlotStatus::CreatedBank => (geyser::SlotStatus::SlotCreatedBank, None),
+            SlotStatus::Dead(error) => (geyser::SlotStatus::SlotDead, Some(error.clone())),
+        };
+        self.capture(UpdateOneof::Slot(geyser::SubscribeUpdateSlot {
+            slot,
+            parent,
+            status: status_value as i32,
+            dead_error,
+        }))?;
+        if *status == SlotStatus::Rooted {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn capture_transaction(
+        &self,
+        transaction_info: &ReplicaTransactionInfo,
+        slot: u64,
+    ) -> Result<(), GeyserPluginError> {
+        self.capture(UpdateOneof::Transaction(
+            geyser::SubscribeUpdateTransaction {
+                transaction: Some(geyser::SubscribeUpdateTransactionInfo {
+                    signature: transaction_info.signature.as_ref().to_vec(),
+                    is_vote: transaction_info.is_vote,
+                    transaction: Some(
+                        transaction_info
+                            .transaction
+                            .to_versioned_transaction()
+                            .into(),
+                    ),
+                    meta: Some(transaction_info.transaction_status_meta.clone().into()),
+                    index: transaction_info.index as u64,
+                }),
+                slot,
+            },
+        ))
+    }
+
+    pub(crate) fn capture_block_metadata(
+        &self,
+        block_info: &ReplicaBlockInfo,
+    ) -> Result<(), GeyserPluginError> {
+        self.capture(UpdateOneof::BlockMeta(geyser::SubscribeUpdateBlockMeta {
+            slot: block_info.slot,
+            blockhash: block_info.blockhash.to_string(),
+            rewards: Some(block_info.rewards.clone().into()),
+            block_time: block_info
+                .block_time
+                .map(|timestamp| generated::UnixTimestamp { timestamp }),
+            block_height: block_info
+                .block_height
+                .map(|block_height| generated::BlockHeight { block_height }),
+            parent_slot: block_info.parent_slot,
+            parent_blockhash: block_info.parent_blockhash.to_string(),
+            executed_transaction_count: block_info.executed_transaction_count,
+            entries_count: block_info.entry_count,
+        }))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        agave_reserved_account_keys::ReservedAccountKeys,
+        solana_sdk::{
+            hash::Hash,
+            message::SimpleAddressLoader,
+            signature::{Keypair, Signature, Signer},
+            transaction::{SanitizedTransaction, VersionedTransaction},
+        },
+        solana_transaction_status::{
+            Reward, RewardType, RewardsAndNumPartitions, TransactionStatusMeta,
+        },
+        std::io::Read,
+    };
+
+    fn read_captured(path: &std::path::Path) -> Vec<SubscribeUpdate> {
+        let mut bytes = vec![];
+        File::open(path).unwrap().read_to_end(&mut bytes).unwrap();
+        let mut buf = bytes.as_slice();
+        let mut updates = vec![];
+        while !buf.is_empty() {
+            updates.push(SubscribeUpdate::decode_length_delimited(&mut buf).unwrap());
+        }
+        updates
+    }
+
+    #[test]
+    fn test_notification_capture() {
+        assert!(NotificationCapture::new(None).unwrap().is_none());
+        let path =
+            std::env::temp_dir().join(format!("notification_capture_{}.bin", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let capture = NotificationCapture::new(path.to_str()).unwrap().unwrap();
+
+        let signature = Signature::from([7; 64]);
+        let account = ReplicaAccountInfo {
+            pubkey: &[1; 32],
+            lamports: 1000,
+            owner: &[2; 32],
+            executable: false,
+            rent_epoch: 3,
+            data: &[4, 5, 6],
+            write_version: 8,
+            txn_signature: Some(&signature),
+        };
+        capture.capture_account(&account, 10, false).unwrap();
+        capture
+            .capture_slot_status(10, Some(9), &SlotStatus::Dead("error".to_string()))
+            .unwrap();
+        let rewards = RewardsAndNumPartitions {
+            rewards: vec![Reward {
+                pubkey: "pubkey".to_string(),
+                lamports: 100,
+                post_balance: 1100,
+                reward_type: Some(RewardType::Fee),
+                commission: None,
+            }],
+            num_partitions: None,
+        };
+        let block_info = ReplicaBlockInfo {
+            parent_slot: 9,
+            parent_blockhash: "parent",
+            slot: 10,
+            blockhash: "block",
+            rewards: &rewards,
+            block_time: Some(1_700_000_000),
+            block_height: None,
+            executed_transaction_count: 2,
+            entry_count: 3,
+        };
+        capture.capture_block_metadata(&block_info).unwrap();
+        let keypair = Keypair::new();
+        let transaction = SanitizedTransaction::try_create(
+            VersionedTransaction::from(solana_system_transaction::transfer(
+                &keypair,
+                &keypair.pubkey(),
+                42,
+                Hash::default(),
+            )),
+            Hash::new_unique(),
+            None,
+            SimpleAddressLoader::Disabled,
+            &ReservedAccountKeys::empty_key_set(),
+        )
+        .unwrap();
+        let meta = TransactionStatusMeta {
+            fee: 5000,
+            ..TransactionStatusMeta::default()
+        };
+        let transaction_info = ReplicaTransactionInfo {
+            signature: transaction.signature(),
+            is_vote: false,
+            transaction: &transaction,
+            transaction_status_meta: &meta,
+            index: 4,
+        };
+        capture.capture_transaction(&transaction_info, 10).unwrap();
+        // The rooted slot flushes the notifications captured
+        capture
+            .capture_slot_status(10, Some(9), &SlotStatus::Rooted)
+            .unwrap();
+
+        let updates = read_captured(&path);
+        assert_eq!(updates.len(), 5);
+        match &updates[0].update_oneof {
+            Some(UpdateOneof::Account(update)) => {
+                assert_eq!(update.slot, 10);
+                assert!(!update.is_startup);
+                let info = update.account.as_ref().unwrap();
+                assert_eq!(info.pubkey, vec![1; 32]);
+                assert_eq!(info.lamports, 1000);
+                assert_eq!(info.data, vec![4, 5, 6]);
+                assert_eq!(info.txn_signature, Some(vec![7; 64]));
+            }
+            update => panic!("Unexpected update {:?}", update.is_some()),
+        }
+        assert_eq!(
+            updates[1].update_oneof,
+            Some(UpdateOneof::Slot(geyser::SubscribeUpdateSlot {
+                slot: 10,
+                parent: Some(9),
+                status: geyser::SlotStatus::SlotDead as i32,
+                dead_error: Some("error".to_string()),
+            }))
+        );
+        match &updates[2].update_oneof {
+            Some(UpdateOneof::BlockMeta(update)) => {
+                assert_eq!(update.blockhash, "block");
+                assert_eq!(update.parent_slot, 9);
+                assert_eq!(update.rewards.as_ref().unwrap().rewards.len(), 1);
+                assert_eq!(update.block_time.as_ref().unwrap().timestamp, 1_700_000_000);
+                assert!(update.block_height.is_none());
+                assert_eq!(update.entries_count, 3);
+            }
+            update => panic!("Unexpected update {:?}", update.is_some()),
+        }
+        match &updates[3].update_oneof {
+            Some(UpdateOneof::Transaction(update)) => {
+                assert_eq!(update.slot, 10);
+                let info = update.transaction.as_ref().unwrap();
+                assert_eq!(info.signature, transaction.signature().as_ref().to_vec());
+                assert_eq!(info.index, 4);
+                assert_eq!(info.meta.as_ref().unwrap().fee, 5000);
+                assert_eq!(
+                    info.transaction.as_ref().unwrap().signatures,
+                    vec![transaction.signature().as_ref().to_vec()]
+                );
+            }
+            update => panic!("Unexpected update {:?}", update.is_some()),
+        }
+        match &updates[4].update_oneof {
+            Some(UpdateOneof::Slot(update)) => {
+                assert_eq!(update.status, geyser::SlotStatus::SlotFinalized as i32)
+            }
+            update => panic!("Unexpected update {:?}", update.is_some()),
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
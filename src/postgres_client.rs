@@ -1,47 +1,262 @@
-#![allow(clippy::integer_arithmetic)]
-
+#![allow(clippy::arithmetic_side_effects)]
+
+mod postgres_client_account_decoder;
+mod postgres_client_account_tiering;
+mod postgres_client_account_version;
+mod postgres_client_address_tag;
+mod postgres_client_analyze;
+mod postgres_client_async;
+mod postgres_client_audit_archive;
+mod postgres_client_audit_retention;
+mod postgres_client_backpressure;
+mod postgres_client_block_chain;
+mod postgres_client_block_json;
 mod postgres_client_block_metadata;
+mod postgres_client_computed_columns;
+mod postgres_client_config_check;
+mod postgres_client_config_history;
+mod postgres_client_config_suggestion;
+mod postgres_client_continuous_aggregate;
+mod postgres_client_cpu_budget;
+mod postgres_client_credentials;
+mod postgres_client_data_compression;
+mod postgres_client_dead_letter;
+mod postgres_client_dedup_window;
+mod postgres_client_degradation;
+mod postgres_client_distributed;
+mod postgres_client_entry;
+mod postgres_client_epoch_barrier;
+mod postgres_client_failover;
+mod postgres_client_flow_control;
+mod postgres_client_fork_cleanup;
+mod postgres_client_hypertable;
+mod postgres_client_index_advisor;
+mod postgres_client_ingest_sla;
+mod postgres_client_keepalive;
+mod postgres_client_metrics;
+mod postgres_client_notify;
+mod postgres_client_plugin_run;
+mod postgres_client_post_commit_hook;
+mod postgres_client_program_label;
+mod postgres_client_queue_overflow;
+mod postgres_client_reconnect;
+mod postgres_client_schema;
+mod postgres_client_schema_drift;
+mod postgres_client_schema_source;
+mod postgres_client_sharding;
+mod postgres_client_shutdown_drain;
+mod postgres_client_slot_checksum;
+mod postgres_client_slot_commit;
+mod postgres_client_slot_economics;
+mod postgres_client_snapshot_rewind;
+mod postgres_client_sol_transfer;
+mod postgres_client_stake_reward;
+mod postgres_client_startup_barrier;
+mod postgres_client_startup_indexes;
+mod postgres_client_startup_report;
+mod postgres_client_statement_batch;
+mod postgres_client_statement_timeout;
+mod postgres_client_tls;
+mod postgres_client_token_account;
+mod postgres_client_token_index;
 mod postgres_client_transaction;
+mod postgres_client_transaction_address;
+mod postgres_client_transaction_allowlist;
+mod postgres_client_transaction_count;
+mod postgres_client_transaction_instruction;
+mod postgres_client_transaction_memo;
+mod postgres_client_transaction_meta;
+mod postgres_client_tunnel;
+mod postgres_client_validator_reward;
+mod postgres_client_verification_replica;
+mod postgres_client_vote_activity;
+mod postgres_client_write_stats;
+mod postgres_client_write_verification;
 
 /// A concurrent implementation for writing accounts into the PostgreSQL in parallel.
 use {
-    crate::accountsdb_plugin_postgres::{
-        AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
-    },
-    agave_geyser_plugin_interface::geyser_plugin_interface::{
-        GeyserPluginError, ReplicaAccountInfoV3, ReplicaBlockInfoV4, SlotStatus,
+    crate::{
+        accounts_selector::AccountsSelector,
+        accountsdb_plugin_postgres::{
+            AccountLayout, AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+            ClientKind, Footprint, ForkCleanup, SignatureStorage, TokenAccountStorage,
+        },
+        geyser_interface::{ReplicaAccountInfo, ReplicaBlockInfo},
     },
-    chrono::Utc,
+    agave_geyser_plugin_interface::geyser_plugin_interface::{GeyserPluginError, SlotStatus},
+    chrono::{NaiveDateTime, Utc},
     crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender},
     log::*,
-    openssl::ssl::{SslConnector, SslFiletype, SslMethod},
     postgres::{Client, NoTls, Statement},
-    postgres_client_block_metadata::DbBlockInfo,
-    postgres_client_transaction::LogTransactionRequest,
-    postgres_openssl::MakeTlsConnector,
+    postgres_client_account_decoder::{AccountDecoders, DataJsonColumn},
+    postgres_client_account_tiering::AccountTiering,
+    postgres_client_analyze::{AnalyzeRowCounts, AnalyzeScheduler, AnalyzeTable},
+    postgres_client_async::AsyncPostgresClient,
+    postgres_client_audit_retention::AuditRetention,
+    postgres_client_backpressure::Backpressure,
+    postgres_client_block_json::BlockJsonBuffer,
+    postgres_client_computed_columns::ComputedColumns,
+    postgres_client_continuous_aggregate::{ContinuousAggregateRefresher, RootedSlot},
+    postgres_client_cpu_budget::CpuBudget,
+    postgres_client_credentials::{forget_password, resolve_password},
+    postgres_client_data_compression::{data_codec, DataCompression},
+    postgres_client_dedup_window::DedupWindow,
+    postgres_client_degradation::{skip_audit_condition, Degradation, DegradationLadder},
+    postgres_client_entry::EntryBuffer,
+    postgres_client_epoch_barrier::{EpochBarriers, WriteEpochBarrierRequest},
+    postgres_client_failover::{Failover, FailoverDecision},
+    postgres_client_flow_control::FlowControlListener,
+    postgres_client_fork_cleanup::DEFAULT_FORK_CLEANUP_INTERVAL_SLOTS,
+    postgres_client_index_advisor::IndexAdvisor,
+    postgres_client_ingest_sla::{IngestSlaChecker, IngestSlaMonitor},
+    postgres_client_keepalive::is_connection_dropped,
+    postgres_client_metrics::{MetricStream, MetricsExporter, PluginMetrics},
+    postgres_client_notify::{account_payload, Notifier},
+    postgres_client_plugin_run::PluginRun,
+    postgres_client_post_commit_hook::PostCommitHooks,
+    postgres_client_queue_overflow::QueueOverflow,
+    postgres_client_reconnect::ReconnectBuffer,
+    postgres_client_schema_drift::SchemaDriftChecker,
+    postgres_client_shutdown_drain::{ShutdownDrain, SlotStatusDef},
+    postgres_client_slot_checksum::{SlotChecksumWriter, SlotChecksums},
+    postgres_client_slot_commit::{
+        CommitSlotRequest, SlotCommitBuffer, DEFAULT_SLOT_COMMIT_CHUNK_SIZE,
+    },
+    postgres_client_slot_economics::SlotFeeBuffer,
+    postgres_client_snapshot_rewind::SnapshotRewind,
+    postgres_client_startup_barrier::StartupBarrier,
+    postgres_client_statement_batch::StatementBatchLimits,
+    postgres_client_statement_timeout::StatementTimeouts,
+    postgres_client_tls::build_tls_connector,
+    postgres_client_token_index::{DbTokenIndexEntry, TokenIndexStatements},
+    postgres_client_transaction_allowlist::{TransactionAllowlist, TransactionAllowlistPoller},
+    postgres_client_transaction_count::{TransactionCountWriter, TransactionCounts},
+    postgres_client_transaction_meta::TransactionMetaStatements,
+    postgres_client_tunnel::tunneled_config,
+    postgres_client_verification_replica::{PendingWrite, VerificationReplica},
+    postgres_client_write_stats::WriteStats,
+    postgres_client_write_verification::WriteVerifier,
+    serde_derive::{Deserialize, Serialize},
+    serde_json::json,
     solana_measure::measure::Measure,
     solana_metrics::*,
     solana_sdk::timing::AtomicInterval,
     std::{
         sync::{
-            atomic::{AtomicBool, AtomicUsize, Ordering},
+            atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
             Arc, Mutex,
         },
         thread::{self, sleep, Builder, JoinHandle},
-        time::Duration,
+        time::{Duration, Instant},
     },
     tokio_postgres::types,
 };
 
+pub use postgres_client_audit_archive::AuditArchiveReport;
+
+// The fields of the config the async client supports, for the validation of the config
+pub(crate) use postgres_client_async::{ASYNC_CLIENT_FIELDS, DEFAULT_ASYNC_POOL_SIZE};
+
+pub use postgres_client_sharding::ShardedPostgresClient;
+
+// The tunnel to the database, started by the plugin and by the tools connecting to it
+pub use postgres_client_tunnel::Tunnel;
+
+// The rows written by the plugin and the requests writing them, so that the tools writing
+// to the database of the plugin encode the rows the same way.
+pub use {
+    postgres_client_block_chain::{DbBlockChainGap, VerifyBlockChainRequest},
+    postgres_client_block_metadata::{block_leader, DbBlockInfo},
+    postgres_client_config_check::DatabaseCheckReport,
+    postgres_client_config_suggestion::{
+        suggest_config, ConfigSuggestion, DatabaseProbe, MachineProbe,
+    },
+    postgres_client_data_compression::{decompress_account_data, DATA_CODEC_NONE, DATA_CODEC_ZSTD},
+    postgres_client_dead_letter::DeadLetterReplayReport,
+    postgres_client_entry::{DbEntry, UpdateEntryRequest},
+    postgres_client_fork_cleanup::CleanUpForksRequest,
+    postgres_client_slot_economics::DbSlotEconomics,
+    postgres_client_sol_transfer::{build_sol_transfers, DbSolTransfer},
+    postgres_client_stake_reward::{build_stake_rewards, DbStakeReward},
+    postgres_client_startup_report::{StartupStats, WriteStartupReportRequest},
+    postgres_client_token_account::{
+        decode_token_account, DbToken, DbTokenAccount, DbTokenAccountState, DbTokenMint,
+        TokenRedecodeReport, TOKEN_DECODER_VERSION,
+    },
+    postgres_client_transaction::{
+        build_db_transaction, DbCompiledInstruction, DbInnerInstructions, DbLoadedAddresses,
+        DbLoadedMessageV0, DbReward, DbRewardType, DbTransaction, DbTransactionError,
+        DbTransactionErrorCode, DbTransactionMessage, DbTransactionMessageAddressTableLookup,
+        DbTransactionMessageHeader, DbTransactionMessageV0, DbTransactionStatusMeta,
+        DbTransactionTokenBalance, LogTransactionRequest,
+    },
+    postgres_client_transaction_instruction::{
+        build_transaction_instructions, DbTransactionInstruction,
+    },
+    postgres_client_transaction_memo::{build_transaction_memos, DbTransactionMemo},
+    postgres_client_transaction_meta::{
+        build_account_balances, build_token_balance_changes, DbAccountBalance, DbTokenBalanceChange,
+    },
+    postgres_client_validator_reward::{build_validator_rewards, DbValidatorReward},
+    postgres_client_vote_activity::{
+        build_db_vote_activity, DbVoteActivity, LogVoteActivityRequest,
+    },
+};
+
+// The account columns the computed columns refer to, for the validation of the config
+pub(crate) use {
+    postgres_client_account_decoder::DATA_JSON_COLUMN,
+    postgres_client_computed_columns::{check_expression, ACCOUNT_COLUMNS, DISCRIMINATOR_COLUMN},
+    postgres_client_statement_timeout::STATEMENT_TIMEOUT_TABLES,
+    postgres_client_transaction_instruction::PARSED_INSTRUCTION_PROGRAMS,
+};
+
+// The configs of the databases of the sharding, for the selector dry run
+pub(crate) use postgres_client_sharding::shard_configs;
+
+// The recording of the settings changed, for the selector reload
+pub(crate) use postgres_client_config_history::ConfigChange;
+
 /// The maximum asynchronous requests allowed in the channel to avoid excessive
-/// memory usage. The downside -- calls after this threshold is reached can get blocked.
-const MAX_ASYNC_REQUESTS: usize = 40960;
+/// memory usage unless the queue capacity is configured. The downside -- calls after
+/// this threshold is reached can get blocked or dropped per the overflow policy.
+pub(crate) const MAX_ASYNC_REQUESTS: usize = 40960;
+/// The watchlisted accounts are expected to be few, keep the priority lane short so
+/// that a stalled priority connection is noticed quickly instead of buffering.
+const MAX_ASYNC_PRIORITY_REQUESTS: usize = 1024;
 const DEFAULT_POSTGRES_PORT: u16 = 5432;
-const DEFAULT_THREADS_COUNT: usize = 100;
-const DEFAULT_ACCOUNTS_INSERT_BATCH_SIZE: usize = 10;
-const ACCOUNT_COLUMN_COUNT: usize = 9;
+pub(crate) const DEFAULT_THREADS_COUNT: usize = 100;
+pub(crate) const DEFAULT_ACCOUNTS_INSERT_BATCH_SIZE: usize = 10;
+pub(crate) const ACCOUNT_COLUMN_COUNT: usize = 11;
+/// Bounded by the maximum number of the parameters of a PostgreSQL statement, including
+/// the data_json parameter of the rows when the account data decoders are configured
+pub(crate) const MAX_ACCOUNTS_INSERT_BATCH_SIZE: usize =
+    u16::MAX as usize / (ACCOUNT_COLUMN_COUNT + 1);
 const DEFAULT_PANIC_ON_DB_ERROR: bool = false;
 const DEFAULT_STORE_ACCOUNT_HISTORICAL_DATA: bool = false;
+pub(crate) const DEFAULT_STORE_VOTE_ACTIVITY: bool = false;
+pub(crate) const DEFAULT_UNLOAD_AT_STOP_SLOT: bool = false;
+pub(crate) const DEFAULT_SKIP_STARTUP_ACCOUNTS: bool = false;
+const DEFAULT_MANAGE_INDEXES_ON_STARTUP: bool = false;
+pub(crate) const DEFAULT_STORE_BLOCK_JSON: bool = false;
+const DEFAULT_STORE_TRANSACTION_JSON: bool = false;
+const DEFAULT_STORE_TRANSACTION_ADDRESSES: bool = false;
+const DEFAULT_STORE_SOL_TRANSFERS: bool = false;
+const DEFAULT_STORE_TRANSACTION_MEMOS: bool = false;
+const DEFAULT_STORE_NORMALIZED_TRANSACTION_META: bool = false;
+pub(crate) const DEFAULT_STORE_SLOT_ECONOMICS: bool = false;
+const DEFAULT_STORE_VALIDATOR_REWARDS: bool = false;
+const DEFAULT_STORE_BLOCK_REWARDS: bool = false;
+const DEFAULT_STORE_STAKE_REWARDS: bool = false;
+pub(crate) const DEFAULT_STORE_ENTRIES: bool = false;
+const DEFAULT_ENTRY_BATCH_SIZE: usize = 64;
+/// The slots per epoch of mainnet-beta, whose epochs have no warmup
+const DEFAULT_SLOTS_PER_EPOCH: u64 = 432000;
+pub(crate) const DEFAULT_USE_SCHEMA_TRIGGERS: bool = true;
+/// About a day of slots at 400 milliseconds per slot
+const DEFAULT_ACCOUNT_AUDIT_PARTITION_SLOTS: u64 = 216000;
+/// The tables whose committed writes can be followed by a post commit hook
+pub(crate) const POST_COMMIT_HOOK_TABLES: [&str; 4] = ["account", "slot", "transaction", "block"];
 
 struct PostgresSqlClientWrapper {
     client: Client,
@@ -51,12 +266,108 @@ struct PostgresSqlClientWrapper {
     update_slot_without_parent_stmt: Statement,
     update_transaction_log_stmt: Statement,
     update_block_metadata_stmt: Statement,
+    insert_block_reward_stmt: Option<Statement>,
     insert_account_audit_stmt: Option<Statement>,
+    insert_account_version_stmt: Option<Statement>,
+    update_vote_activity_stmt: Option<Statement>,
+    upsert_entry_stmt: Option<Statement>,
+    verify_block_chain_stmt: Option<Statement>,
+    upsert_block_chain_gap_stmt: Option<Statement>,
+    insert_transaction_address_stmt: Option<Statement>,
+    insert_sol_transfer_stmt: Option<Statement>,
+    insert_transaction_memo_stmt: Option<Statement>,
+    insert_transaction_instruction_stmt: Option<Statement>,
+    insert_transaction_meta_stmts: Option<TransactionMetaStatements>,
+    upsert_slot_economics_stmt: Option<Statement>,
+    insert_validator_reward_stmt: Option<Statement>,
+    upsert_stake_reward_stmt: Option<Statement>,
+    insert_dead_letter_stmt: Option<Statement>,
+    upsert_token_account_stmt: Option<Statement>,
+    upsert_token_mint_stmt: Option<Statement>,
+    delete_token_stmt: Option<Statement>,
+    token_index_stmts: Option<TokenIndexStatements>,
+    post_commit_hooks: PostCommitHooks,
+    notifier: Notifier,
+    write_stats: WriteStats,
+    statement_timeouts: StatementTimeouts,
+    /// Whether the session skips the audit, None when it is unknown, such as on a new
+    /// connection or after the rollback of a statement batch
+    audit_skipped: Option<bool>,
+}
+
+/// The static columns configured to be appended to the inserts into the account,
+/// account_audit, slot, transaction, block, vote_activity and entry tables, rendered as
+/// the column names and the literal values to append to the statements.
+pub(crate) struct StaticColumns {
+    /// The column names, e.g. ", region, shard"
+    pub names: String,
+    /// The quoted values, e.g. ", 'us-east', '3'"
+    pub values: String,
+    /// The column names qualified by the old alias, e.g. ", old.region, old.shard", for
+    /// the versions of the accounts copied into the account_audit table
+    pub old_names: String,
+}
+
+impl StaticColumns {
+    pub(crate) fn new(config: &AccountsDbPluginPostgresConfig) -> Self {
+        let mut names = String::default();
+        let mut values = String::default();
+        let mut old_names = String::default();
+        for (name, value) in config.static_columns.iter().flatten() {
+            names.push_str(&format!(", {}", name));
+            values.push_str(&format!(", '{}'", value.replace('\'', "''")));
+            old_names.push_str(&format!(", old.{}", name));
+        }
+        Self {
+            names,
+            values,
+            old_names,
+        }
+    }
 }
 
 pub struct SimplePostgresClient {
     batch_size: usize,
     pending_account_updates: Vec<DbAccountInfo>,
+    /// The statistics of the accounts written during the startup
+    startup_stats: StartupStats,
+    signature_storage: SignatureStorage,
+    /// Indicates if to store the block JSON along with the block metadata
+    store_block_json: bool,
+    /// Indicates if the transaction statement has the transaction_json column
+    store_transaction_json: bool,
+    /// The slots per epoch for the epochs of the validator and the stake rewards
+    slots_per_epoch: u64,
+    /// The attempts of the writes before they are written to the dead_letter table
+    /// when the dead letter is enabled
+    dead_letter_attempts: Option<u64>,
+    /// Indicates if the writes failing on a dropped connection are written again once
+    /// reconnected, the accounts batched are then kept for the next attempt
+    retry_on_reconnect: bool,
+    /// How the accounts of the token programs are stored
+    token_account_storage: TokenAccountStorage,
+    /// The layout of the account rows read back for the decoding again of the token
+    /// accounts
+    account_layout: AccountLayout,
+    /// The token accounts and mints decoded during the startup, written in batches
+    pending_token_accounts: Vec<DbTokenAccount>,
+    pending_token_mints: Vec<DbTokenMint>,
+    /// The entries of the token account secondary indexes during the startup, written
+    /// in batches
+    pending_token_index_entries: Vec<DbTokenIndexEntry>,
+    /// The decoders of the account data into the data_json column, when configured
+    account_decoders: AccountDecoders,
+    /// How the account data is compressed before it is written
+    data_compression: DataCompression,
+    /// How the slots of the abandoned forks are cleaned up, when configured
+    fork_cleanup: Option<ForkCleanup>,
+    /// The accounts whose every version is kept in the account_version table, when
+    /// configured
+    account_versions_selector: Option<AccountsSelector>,
+    /// The sampling of the rows read back to verify the writes, when configured
+    write_verifier: Option<WriteVerifier>,
+    /// The read replica the rows are read back from to verify them, when configured
+    verification_replica: Option<VerificationReplica>,
     client: Mutex<PostgresSqlClientWrapper>,
 }
 
@@ -64,11 +375,40 @@ struct PostgresClientWorker {
     client: SimplePostgresClient,
     /// Indicating if accounts notification during startup is done.
     is_startup_done: bool,
+    /// The config to rebuild the connection with when it is dropped
+    config: AccountsDbPluginPostgresConfig,
+    /// The idle time after which the connection is pinged, when the keepalive is enabled
+    keepalive_interval: Option<Duration>,
+    /// When the worker last wrote or pinged the connection
+    last_active: Instant,
+    /// The updates pending while reconnecting, when the reconnection is enabled
+    reconnect_buffer: Option<ReconnectBuffer>,
+    /// The connection to the standby failed over to, when the fallback is configured
+    failover: Option<Failover>,
+    /// The ingestion of the slots the commits are recorded to, when the SLA is monitored
+    ingest_sla_monitor: Option<Arc<IngestSlaMonitor>>,
+    /// The transactions per slot the commits are recorded to, when they are counted
+    transaction_counts: Option<Arc<TransactionCounts>>,
+    /// The rows per slot the commits are recorded to, when they are checksummed
+    slot_checksums: Option<Arc<SlotChecksums>>,
+    /// The steps of the degradation ladder entered, when the ladder is configured
+    degradation: Option<Arc<Degradation>>,
+    /// The CPU budget of the worker, when the CPU usage is capped
+    cpu_budget: Option<CpuBudget>,
+    /// The limits of the work items written in one database transaction, when the
+    /// statements are batched
+    statement_batch_limits: Option<StatementBatchLimits>,
+    /// The transactions committed per database transaction with the per-slot commit
+    slot_commit_chunk_size: usize,
+    /// The metrics the writes are recorded to, when the metrics are served
+    metrics: Option<Arc<PluginMetrics>>,
+    /// The drain of the queue of the worker on the shutdown, spilling the items left
+    shutdown_drain: Arc<ShutdownDrain>,
 }
 
 impl Eq for DbAccountInfo {}
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct DbAccountInfo {
     pub pubkey: Vec<u8>,
     pub lamports: i64,
@@ -78,6 +418,9 @@ pub struct DbAccountInfo {
     pub data: Vec<u8>,
     pub slot: i64,
     pub write_version: i64,
+    /// The signature of the transaction causing the update, none for the updates of the
+    /// startup and of the earlier versions of the interface
+    pub txn_signature: Option<Vec<u8>>,
 }
 
 pub(crate) fn abort() -> ! {
@@ -94,17 +437,19 @@ pub(crate) fn abort() -> ! {
 }
 
 impl DbAccountInfo {
-    fn new<T: ReadableAccountInfo>(account: &T, slot: u64) -> DbAccountInfo {
+    /// Build the account row of the account updated at the slot.
+    pub fn new<T: ReadableAccountInfo>(account: &T, slot: u64) -> DbAccountInfo {
         let data = account.data().to_vec();
         Self {
             pubkey: account.pubkey().to_vec(),
-            lamports: account.lamports() as i64,
+            lamports: account.lamports(),
             owner: account.owner().to_vec(),
             executable: account.executable(),
-            rent_epoch: account.rent_epoch() as i64,
+            rent_epoch: account.rent_epoch(),
             data,
             slot: slot as i64,
             write_version: account.write_version(),
+            txn_signature: account.txn_signature().map(<[u8]>::to_vec),
         }
     }
 }
@@ -117,6 +462,7 @@ pub trait ReadableAccountInfo: Sized {
     fn rent_epoch(&self) -> i64;
     fn data(&self) -> &[u8];
     fn write_version(&self) -> i64;
+    fn txn_signature(&self) -> Option<&[u8]>;
 }
 
 impl ReadableAccountInfo for DbAccountInfo {
@@ -147,9 +493,13 @@ impl ReadableAccountInfo for DbAccountInfo {
     fn write_version(&self) -> i64 {
         self.write_version
     }
+
+    fn txn_signature(&self) -> Option<&[u8]> {
+        self.txn_signature.as_deref()
+    }
 }
 
-impl<'a> ReadableAccountInfo for ReplicaAccountInfoV3<'a> {
+impl<'a> ReadableAccountInfo for ReplicaAccountInfo<'a> {
     fn pubkey(&self) -> &[u8] {
         self.pubkey
     }
@@ -177,6 +527,117 @@ impl<'a> ReadableAccountInfo for ReplicaAccountInfoV3<'a> {
     fn write_version(&self) -> i64 {
         self.write_version as i64
     }
+
+    fn txn_signature(&self) -> Option<&[u8]> {
+        self.txn_signature.map(|signature| signature.as_ref())
+    }
+}
+
+/// The values of an account row computed from the account, which the parameters of the
+/// upsert of the account borrow
+pub(crate) struct AccountRowValues {
+    updated_on: NaiveDateTime,
+    data_json: Option<serde_json::Value>,
+    compressed_data: Option<Vec<u8>>,
+    data_codec: i16,
+}
+
+impl AccountRowValues {
+    pub(crate) fn new(
+        account: &DbAccountInfo,
+        account_decoders: &AccountDecoders,
+        data_compression: &DataCompression,
+    ) -> Self {
+        let compressed_data = data_compression.compress(&account.data);
+        Self {
+            updated_on: Utc::now().naive_utc(),
+            data_json: account_decoders.decode(account.owner(), account.data()),
+            data_codec: data_codec(&compressed_data),
+            compressed_data,
+        }
+    }
+
+    /// The parameters of the upsert of the account, in the order of the account values
+    /// row.
+    pub(crate) fn params<'a>(
+        &'a self,
+        account: &'a DbAccountInfo,
+        account_decoders: &AccountDecoders,
+    ) -> Vec<&'a (dyn types::ToSql + Sync)> {
+        let mut params: Vec<&(dyn types::ToSql + Sync)> = vec![
+            &account.pubkey,
+            &account.slot,
+            &account.owner,
+            &account.lamports,
+            &account.executable,
+            &account.rent_epoch,
+            self.compressed_data.as_ref().unwrap_or(&account.data),
+            &account.write_version,
+            &self.updated_on,
+            &self.data_codec,
+            &account.txn_signature,
+        ];
+        if account_decoders.is_enabled() {
+            params.push(&self.data_json);
+        }
+        params
+    }
+}
+
+/// The values of a slot row, which the parameters of the upsert of the slot borrow
+pub(crate) struct SlotRowValues {
+    slot: i64,
+    parent: Option<i64>,
+    status: &'static str,
+    processed_on: Option<NaiveDateTime>,
+    confirmed_on: Option<NaiveDateTime>,
+    rooted_on: Option<NaiveDateTime>,
+    dead_reason: Option<String>,
+    updated_on: NaiveDateTime,
+}
+
+impl SlotRowValues {
+    pub(crate) fn new(slot: u64, parent: Option<u64>, status: SlotStatus) -> Self {
+        let updated_on = Utc::now().naive_utc();
+        // Record the first time the slot reaches each of the commitment levels, used
+        // to compute the confirmation latencies.
+        let reached_on = |expected: SlotStatus| (status == expected).then_some(updated_on);
+        Self {
+            slot: slot as i64, // postgres only supports i64
+            parent: parent.map(|parent| parent as i64),
+            status: status.as_str(),
+            processed_on: reached_on(SlotStatus::Processed),
+            confirmed_on: reached_on(SlotStatus::Confirmed),
+            rooted_on: reached_on(SlotStatus::Rooted),
+            dead_reason: match status {
+                SlotStatus::Dead(reason) => Some(reason),
+                _ => None,
+            },
+            updated_on,
+        }
+    }
+
+    /// The parameters of the upsert of the slot, with the parent when it is known and
+    /// without it otherwise.
+    pub(crate) fn params(&self) -> Vec<&(dyn types::ToSql + Sync)> {
+        let mut params: Vec<&(dyn types::ToSql + Sync)> = vec![&self.slot];
+        if let Some(parent) = &self.parent {
+            params.push(parent);
+        }
+        params.extend_from_slice(&[
+            &self.status,
+            &self.processed_on,
+            &self.confirmed_on,
+            &self.rooted_on,
+            &self.dead_reason,
+            &self.updated_on,
+        ]);
+        params
+    }
+
+    pub(crate) fn has_parent(&self) -> bool {
+        self.parent.is_some()
+    }
 }
 
 pub trait PostgresClient {
@@ -186,7 +647,7 @@ pub trait PostgresClient {
 
     fn update_account(
         &mut self,
-        account: DbAccountInfo,
+        account: &DbAccountInfo,
         is_startup: bool,
     ) -> Result<(), GeyserPluginError>;
 
@@ -194,111 +655,101 @@ pub trait PostgresClient {
         &mut self,
         slot: u64,
         parent: Option<u64>,
-        status: SlotStatus,
+        status: &SlotStatus,
     ) -> Result<(), GeyserPluginError>;
 
     fn notify_end_of_startup(&mut self) -> Result<(), GeyserPluginError>;
 
+    fn write_startup_report(
+        &mut self,
+        request: &WriteStartupReportRequest,
+    ) -> Result<(), GeyserPluginError>;
+
     fn log_transaction(
         &mut self,
-        transaction_log_info: LogTransactionRequest,
+        transaction_log_info: &LogTransactionRequest,
     ) -> Result<(), GeyserPluginError>;
 
     fn update_block_metadata(
         &mut self,
-        block_info: UpdateBlockMetadataRequest,
+        block_info: &UpdateBlockMetadataRequest,
+    ) -> Result<(), GeyserPluginError>;
+
+    fn log_vote_activity(
+        &mut self,
+        vote_activity_info: &LogVoteActivityRequest,
     ) -> Result<(), GeyserPluginError>;
+
+    fn update_entries(&mut self, request: &UpdateEntryRequest) -> Result<(), GeyserPluginError>;
+
+    fn verify_block_chain(
+        &mut self,
+        request: &VerifyBlockChainRequest,
+    ) -> Result<(), GeyserPluginError>;
+
+    fn clean_up_forks(&mut self, request: &CleanUpForksRequest) -> Result<(), GeyserPluginError>;
 }
 
 impl SimplePostgresClient {
+    /// Get the connection string of the config, the one configured or the one of the
+    /// host, the user and the port.
+    pub(crate) fn connection_str(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<String, GeyserPluginError> {
+        if let Some(connection_str) = &config.connection_str {
+            return Ok(connection_str.clone());
+        }
+        if config.host.is_none() || config.user.is_none() {
+            let msg = format!(
+                "\"connection_str\": {:?}, or \"host\": {:?} \"user\": {:?} must be specified",
+                config.connection_str, config.host, config.user
+            );
+            return Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::ConfigurationError { msg },
+            )));
+        }
+        Ok(format!(
+            "host={} user={} port={}",
+            config.host.as_ref().unwrap(),
+            config.user.as_ref().unwrap(),
+            config.port.unwrap_or(DEFAULT_POSTGRES_PORT)
+        ))
+    }
+
     pub fn connect_to_db(
         config: &AccountsDbPluginPostgresConfig,
     ) -> Result<Client, GeyserPluginError> {
-        let port = config.port.unwrap_or(DEFAULT_POSTGRES_PORT);
+        let connection_str = Self::connection_str(config)?;
 
-        let connection_str = if let Some(connection_str) = &config.connection_str {
-            connection_str.clone()
-        } else {
-            if config.host.is_none() || config.user.is_none() {
-                let msg = format!(
-                    "\"connection_str\": {:?}, or \"host\": {:?} \"user\": {:?} must be specified",
-                    config.connection_str, config.host, config.user
-                );
-                return Err(GeyserPluginError::Custom(Box::new(
-                    AccountsDbPluginPostgresError::ConfigurationError { msg },
-                )));
-            }
-            format!(
-                "host={} user={} port={}",
-                config.host.as_ref().unwrap(),
-                config.user.as_ref().unwrap(),
-                port
-            )
+        let tls_connector = build_tls_connector(config)?;
+        // Resolved on each connection so that the reconnections pick up a rotated password
+        let password = resolve_password(config).map_err(|err| {
+            let msg = format!(
+                "Error in resolving the password of the PostgreSQL database: {}",
+                err
+            );
+            error!("{}", msg);
+            GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+            ))
+        })?;
+        let pg_config = match config.tunnel_local_port {
+            Some(local_port) => tunneled_config(&connection_str, local_port),
+            None => connection_str.parse::<postgres::Config>(),
         };
-
-        let result = if let Some(true) = config.use_ssl {
-            if config.server_ca.is_none() {
-                let msg = "\"server_ca\" must be specified when \"use_ssl\" is set".to_string();
-                return Err(GeyserPluginError::Custom(Box::new(
-                    AccountsDbPluginPostgresError::ConfigurationError { msg },
-                )));
-            }
-            if config.client_cert.is_none() {
-                let msg = "\"client_cert\" must be specified when \"use_ssl\" is set".to_string();
-                return Err(GeyserPluginError::Custom(Box::new(
-                    AccountsDbPluginPostgresError::ConfigurationError { msg },
-                )));
+        let result = pg_config.and_then(|mut pg_config| {
+            if let Some(password) = &password {
+                pg_config.password(password);
             }
-            if config.client_key.is_none() {
-                let msg = "\"client_key\" must be specified when \"use_ssl\" is set".to_string();
-                return Err(GeyserPluginError::Custom(Box::new(
-                    AccountsDbPluginPostgresError::ConfigurationError { msg },
-                )));
-            }
-            let mut builder = SslConnector::builder(SslMethod::tls()).unwrap();
-            if let Err(err) = builder.set_ca_file(config.server_ca.as_ref().unwrap()) {
-                let msg = format!(
-                    "Failed to set the server certificate specified by \"server_ca\": {}. Error: ({})",
-                    config.server_ca.as_ref().unwrap(), err);
-                return Err(GeyserPluginError::Custom(Box::new(
-                    AccountsDbPluginPostgresError::ConfigurationError { msg },
-                )));
-            }
-            if let Err(err) =
-                builder.set_certificate_file(config.client_cert.as_ref().unwrap(), SslFiletype::PEM)
-            {
-                let msg = format!(
-                    "Failed to set the client certificate specified by \"client_cert\": {}. Error: ({})",
-                    config.client_cert.as_ref().unwrap(), err);
-                return Err(GeyserPluginError::Custom(Box::new(
-                    AccountsDbPluginPostgresError::ConfigurationError { msg },
-                )));
-            }
-            if let Err(err) =
-                builder.set_private_key_file(config.client_key.as_ref().unwrap(), SslFiletype::PEM)
-            {
-                let msg = format!(
-                    "Failed to set the client key specified by \"client_key\": {}. Error: ({})",
-                    config.client_key.as_ref().unwrap(),
-                    err
-                );
-                return Err(GeyserPluginError::Custom(Box::new(
-                    AccountsDbPluginPostgresError::ConfigurationError { msg },
-                )));
+            match tls_connector {
+                Some(connector) => pg_config.connect(connector),
+                None => pg_config.connect(NoTls),
             }
-
-            let mut connector = MakeTlsConnector::new(builder.build());
-            connector.set_callback(|connect_config, _domain| {
-                connect_config.set_verify_hostname(false);
-                Ok(())
-            });
-            Client::connect(&connection_str, connector)
-        } else {
-            Client::connect(&connection_str, NoTls)
-        };
+        });
 
         match result {
             Err(err) => {
+                forget_password(config);
                 let msg = format!(
                     "Error in connecting to the PostgreSQL database: {:?} connection_str: {:?}",
                     err, connection_str
@@ -308,150 +759,337 @@ impl SimplePostgresClient {
                     AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
                 )))
             }
-            Ok(client) => Ok(client),
+            Ok(mut client) => {
+                Self::set_search_path(&mut client, config)?;
+                Ok(client)
+            }
         }
     }
 
-    fn build_bulk_account_insert_statement(
+    /// The values of an account row from the parameter after `row`, typed so that the
+    /// rows can also be selected from a common table expression.
+    fn account_values_row(
+        row: usize,
+        static_columns: &StaticColumns,
+        computed_columns: &ComputedColumns,
+        data_json_column: &DataJsonColumn,
+    ) -> String {
+        format!(
+            "(${}::BYTEA, ${}::BIGINT, ${}::BYTEA, ${}::BIGINT, ${}::BOOL, ${}::BIGINT, ${}::BYTEA, ${}::BIGINT, ${}::TIMESTAMP, ${}::SMALLINT, ${}::BYTEA{}{}{})",
+            row + 1,
+            row + 2,
+            row + 3,
+            row + 4,
+            row + 5,
+            row + 6,
+            row + 7,
+            row + 8,
+            row + 9,
+            row + 10,
+            row + 11,
+            static_columns.values,
+            computed_columns.values(row),
+            data_json_column.values(row),
+        )
+    }
+
+    /// Build the upsert of the account rows. Without the schema triggers, the previous
+    /// versions of the accounts updated are copied into the account_audit table and the
+    /// owner changes recorded into the owner_change table, unless the footprint is
+    /// minimal, by the same statement. Either
+    /// way it yields a row per account inserted or updated, telling whether it was
+    /// inserted, for the upsert stats.
+    fn account_upsert_sql(
+        rows: &str,
+        static_columns: &StaticColumns,
+        computed_columns: &ComputedColumns,
+        data_json_column: &DataJsonColumn,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> String {
+        let columns = format!(
+            "pubkey, slot, owner, lamports, executable, rent_epoch, data, write_version, updated_on, data_codec, txn_signature{}{}{}",
+            static_columns.names,
+            computed_columns.names(),
+            data_json_column.names()
+        );
+        let is_split = config.account_layout.unwrap_or_default() == AccountLayout::Split;
+        // The data of the split layout is upserted into the account_data table instead
+        let (account_columns, data_update) = if is_split {
+            (
+                columns
+                    .replacen(" data,", "", 1)
+                    .replacen(", data_codec", "", 1),
+                "",
+            )
+        } else {
+            (
+                columns.clone(),
+                " data=excluded.data, data_codec=excluded.data_codec,",
+            )
+        };
+        // The write versions of a slot notified again after a restart are not comparable
+        // to the stored ones, the updates at the slots of the dedup window are ignored.
+        let handle_conflict = format!("ON CONFLICT (pubkey) DO UPDATE SET slot=excluded.slot, owner=excluded.owner, lamports=excluded.lamports, lamports_delta=excluded.lamports - acct.lamports, executable=excluded.executable, rent_epoch=excluded.rent_epoch,\
+            {} write_version=excluded.write_version, updated_on=excluded.updated_on, txn_signature=excluded.txn_signature{}{} WHERE acct.slot < excluded.slot OR (\
+            acct.slot = excluded.slot AND acct.write_version < excluded.write_version{})",
+            data_update,
+            computed_columns.updates(),
+            data_json_column.updates(),
+            DedupWindow::outside_condition(config, "excluded.slot")
+                .map(|condition| format!(" AND {}", condition))
+                .unwrap_or_default());
+
+        if config
+            .use_schema_triggers
+            .unwrap_or(DEFAULT_USE_SCHEMA_TRIGGERS)
+        {
+            return format!(
+                "INSERT INTO account AS acct ({}) VALUES {} {} RETURNING (acct.xmax = 0) AS inserted",
+                columns, rows, handle_conflict
+            );
+        }
+
+        let audit = if config
+            .store_account_historical_data
+            .unwrap_or(DEFAULT_STORE_ACCOUNT_HISTORICAL_DATA)
+        {
+            // The audit is skipped by the sessions set to by the degradation ladder
+            format!(", audit AS (INSERT INTO account_audit (pubkey, owner, lamports, lamports_delta, slot, executable, rent_epoch, data, write_version, updated_on, data_codec, txn_signature{}) \
+            SELECT old.pubkey, old.owner, old.lamports, old.lamports_delta, old.slot, old.executable, old.rent_epoch, old.data, old.write_version, old.updated_on, old.data_codec, old.txn_signature{} \
+            FROM old_account old JOIN upserted USING (pubkey){})",
+                static_columns.names,
+                static_columns.old_names,
+                skip_audit_condition(config)
+                    .map(|condition| format!(" WHERE {}", condition))
+                    .unwrap_or_default())
+        } else {
+            String::default()
+        };
+        // The minimal footprint stores the latest state of the accounts only
+        let owner_changed = if config.footprint.unwrap_or_default() == Footprint::Minimal {
+            ""
+        } else {
+            ", owner_changed AS (INSERT INTO owner_change (pubkey, old_owner, new_owner, slot, write_version, updated_on) \
+            SELECT old.pubkey, old.owner, upserted.owner, upserted.slot, upserted.write_version, upserted.updated_on \
+            FROM old_account old JOIN upserted USING (pubkey) WHERE old.owner IS DISTINCT FROM upserted.owner)"
+        };
+        // The data is only written along with the accounts upserted, not the stale ones
+        let (old_account, data_upserted) = if is_split {
+            (
+                "SELECT acct.*, d.data, d.data_codec FROM account acct JOIN new_account USING (pubkey) \
+                LEFT JOIN account_data d USING (pubkey)",
+                ", data_upserted AS (INSERT INTO account_data (pubkey, data, slot, write_version, updated_on, data_codec) \
+                SELECT n.pubkey, n.data, n.slot, n.write_version, n.updated_on, n.data_codec FROM new_account n JOIN upserted USING (pubkey) \
+                ON CONFLICT (pubkey) DO UPDATE SET data=excluded.data, slot=excluded.slot, \
+                write_version=excluded.write_version, updated_on=excluded.updated_on, data_codec=excluded.data_codec)",
+            )
+        } else {
+            (
+                "SELECT acct.* FROM account acct JOIN new_account USING (pubkey)",
+                "",
+            )
+        };
+        // The accounts of the account_cold table updated again are moved back into the
+        // account table, their stored versions being the ones of the account_cold table
+        let (old_account, cold_promoted) = if config.account_cold_tier_slots.is_some() {
+            (
+                format!("{old_account} UNION ALL SELECT cold.* FROM account_cold cold JOIN new_account USING (pubkey) \
+                WHERE NOT EXISTS (SELECT 1 FROM account a WHERE a.pubkey = cold.pubkey)"),
+                ", cold_promoted AS (DELETE FROM account_cold cold USING upserted WHERE cold.pubkey = upserted.pubkey)",
+            )
+        } else {
+            (old_account.to_string(), "")
+        };
+        // All the parts see the snapshot of the statement, in which old_account has the
+        // stored versions before the upsert.
+        format!(
+            "WITH new_account ({columns}) AS (VALUES {rows}), \
+            old_account AS ({old_account}), \
+            upserted AS (INSERT INTO account AS acct ({account_columns}) SELECT {account_columns} FROM new_account {handle_conflict} \
+            RETURNING acct.pubkey, acct.owner, acct.slot, acct.write_version, acct.updated_on, (acct.xmax = 0) AS inserted)\
+            {data_upserted}{cold_promoted}{audit}{owner_changed} SELECT inserted FROM upserted"
+        )
+    }
+
+    /// Build the statement upserting a batch of `batch_size` accounts into the account table.
+    pub fn build_bulk_account_insert_statement(
         client: &mut Client,
         config: &AccountsDbPluginPostgresConfig,
     ) -> Result<Statement, GeyserPluginError> {
         let batch_size = config
             .batch_size
             .unwrap_or(DEFAULT_ACCOUNTS_INSERT_BATCH_SIZE);
-        let mut stmt = String::from("INSERT INTO account AS acct (pubkey, slot, owner, lamports, executable, rent_epoch, data, write_version, updated_on) VALUES");
+        let static_columns = StaticColumns::new(config);
+        let computed_columns = ComputedColumns::new(config);
+        let data_json_column = DataJsonColumn::new(config);
+        let mut rows = String::default();
         for j in 0..batch_size {
-            let row = j * ACCOUNT_COLUMN_COUNT;
-            let val_str = format!(
-                "(${}, ${}, ${}, ${}, ${}, ${}, ${}, ${}, ${})",
-                row + 1,
-                row + 2,
-                row + 3,
-                row + 4,
-                row + 5,
-                row + 6,
-                row + 7,
-                row + 8,
-                row + 9,
+            let val_str = Self::account_values_row(
+                j * (ACCOUNT_COLUMN_COUNT + data_json_column.param_count()),
+                &static_columns,
+                &computed_columns,
+                &data_json_column,
             );
 
             if j == 0 {
-                stmt = format!("{} {}", &stmt, val_str);
+                rows = val_str;
             } else {
-                stmt = format!("{}, {}", &stmt, val_str);
+                rows = format!("{}, {}", &rows, val_str);
             }
         }
 
-        let handle_conflict = "ON CONFLICT (pubkey) DO UPDATE SET slot=excluded.slot, owner=excluded.owner, lamports=excluded.lamports, executable=excluded.executable, rent_epoch=excluded.rent_epoch, \
-            data=excluded.data, write_version=excluded.write_version, updated_on=excluded.updated_on WHERE acct.slot < excluded.slot OR (\
-            acct.slot = excluded.slot AND acct.write_version < excluded.write_version)";
-
-        stmt = format!("{} {}", stmt, handle_conflict);
+        let stmt = Self::account_upsert_sql(
+            &rows,
+            &static_columns,
+            &computed_columns,
+            &data_json_column,
+            config,
+        );
 
         info!("{}", stmt);
         let bulk_stmt = client.prepare(&stmt);
 
         match bulk_stmt {
             Err(err) => {
-                return Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
                     msg: format!(
                         "Error in preparing for the accounts update PostgreSQL database: {} host: {:?} user: {:?} config: {:?}",
                         err, config.host, config.user, config
                     ),
-                })));
+                })))
             }
             Ok(update_account_stmt) => Ok(update_account_stmt),
         }
     }
 
-    fn build_single_account_upsert_statement(
+    /// Build the upsert of one account row, its parameters being the ones of
+    /// `AccountRowValues::params`.
+    pub(crate) fn single_account_upsert_sql(config: &AccountsDbPluginPostgresConfig) -> String {
+        let static_columns = StaticColumns::new(config);
+        let computed_columns = ComputedColumns::new(config);
+        let data_json_column = DataJsonColumn::new(config);
+        let rows =
+            Self::account_values_row(0, &static_columns, &computed_columns, &data_json_column);
+        Self::account_upsert_sql(
+            &rows,
+            &static_columns,
+            &computed_columns,
+            &data_json_column,
+            config,
+        )
+    }
+
+    /// Build the statement upserting one account into the account table.
+    pub fn build_single_account_upsert_statement(
         client: &mut Client,
         config: &AccountsDbPluginPostgresConfig,
     ) -> Result<Statement, GeyserPluginError> {
-        let stmt = "INSERT INTO account AS acct (pubkey, slot, owner, lamports, executable, rent_epoch, data, write_version, updated_on) \
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) \
-        ON CONFLICT (pubkey) DO UPDATE SET slot=excluded.slot, owner=excluded.owner, lamports=excluded.lamports, executable=excluded.executable, rent_epoch=excluded.rent_epoch, \
-        data=excluded.data, write_version=excluded.write_version, updated_on=excluded.updated_on  WHERE acct.slot < excluded.slot OR (\
-        acct.slot = excluded.slot AND acct.write_version < excluded.write_version)";
-
-        let stmt = client.prepare(stmt);
+        let stmt = client.prepare(&Self::single_account_upsert_sql(config));
 
         match stmt {
             Err(err) => {
-                return Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
                     msg: format!(
                         "Error in preparing for the accounts update PostgreSQL database: {} host: {:?} user: {:?} config: {:?}",
                         err, config.host, config.user, config
                     ),
-                })));
+                })))
             }
             Ok(update_account_stmt) => Ok(update_account_stmt),
         }
     }
 
-    fn build_account_audit_insert_statement(
+    /// Build the statement inserting one account version into the account_audit table.
+    pub fn build_account_audit_insert_statement(
         client: &mut Client,
         config: &AccountsDbPluginPostgresConfig,
     ) -> Result<Statement, GeyserPluginError> {
-        let stmt = "INSERT INTO account_audit (pubkey, slot, owner, lamports, executable, rent_epoch, data, write_version, updated_on) \
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)";
+        let static_columns = StaticColumns::new(config);
+        let stmt = format!("INSERT INTO account_audit (pubkey, slot, owner, lamports, executable, rent_epoch, data, write_version, updated_on, data_codec, txn_signature{}) \
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11{})", static_columns.names, static_columns.values);
 
-        let stmt = client.prepare(stmt);
+        let stmt = client.prepare(&stmt);
 
         match stmt {
             Err(err) => {
-                return Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
                     msg: format!(
                         "Error in preparing for the account_audit update PostgreSQL database: {} host: {:?} user: {:?} config: {:?}",
                         err, config.host, config.user, config
                     ),
-                })));
+                })))
             }
             Ok(stmt) => Ok(stmt),
         }
     }
 
-    fn build_slot_upsert_statement_with_parent(
+    /// Build the upsert of the status of a slot along with its parent, its parameters
+    /// being the ones of `SlotRowValues::params`.
+    pub(crate) fn slot_upsert_with_parent_sql(config: &AccountsDbPluginPostgresConfig) -> String {
+        let static_columns = StaticColumns::new(config);
+        format!("INSERT INTO slot AS s (slot, parent, status, processed_on, confirmed_on, rooted_on, dead_reason, updated_on{}) \
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8{}) \
+        ON CONFLICT (slot) DO UPDATE SET parent=excluded.parent, status=excluded.status, \
+        processed_on=COALESCE(s.processed_on, excluded.processed_on), \
+        confirmed_on=COALESCE(s.confirmed_on, excluded.confirmed_on), \
+        rooted_on=COALESCE(s.rooted_on, excluded.rooted_on), \
+        dead_reason=COALESCE(excluded.dead_reason, s.dead_reason), \
+        updated_on=excluded.updated_on", static_columns.names, static_columns.values)
+    }
+
+    /// Build the statement upserting the status of a slot along with its parent into the
+    /// slot table.
+    pub fn build_slot_upsert_statement_with_parent(
         client: &mut Client,
         config: &AccountsDbPluginPostgresConfig,
     ) -> Result<Statement, GeyserPluginError> {
-        let stmt = "INSERT INTO slot (slot, parent, status, updated_on) \
-        VALUES ($1, $2, $3, $4) \
-        ON CONFLICT (slot) DO UPDATE SET parent=excluded.parent, status=excluded.status, updated_on=excluded.updated_on";
-
-        let stmt = client.prepare(stmt);
+        let stmt = client.prepare(&Self::slot_upsert_with_parent_sql(config));
 
         match stmt {
             Err(err) => {
-                return Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
                     msg: format!(
                         "Error in preparing for the slot update PostgreSQL database: {} host: {:?} user: {:?} config: {:?}",
                         err, config.host, config.user, config
                     ),
-                })));
+                })))
             }
             Ok(stmt) => Ok(stmt),
         }
     }
 
-    fn build_slot_upsert_statement_without_parent(
+    /// Build the upsert of the status of a slot without its parent, its parameters being
+    /// the ones of `SlotRowValues::params`.
+    pub(crate) fn slot_upsert_without_parent_sql(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> String {
+        let static_columns = StaticColumns::new(config);
+        format!("INSERT INTO slot AS s (slot, status, processed_on, confirmed_on, rooted_on, dead_reason, updated_on{}) \
+        VALUES ($1, $2, $3, $4, $5, $6, $7{}) \
+        ON CONFLICT (slot) DO UPDATE SET status=excluded.status, \
+        processed_on=COALESCE(s.processed_on, excluded.processed_on), \
+        confirmed_on=COALESCE(s.confirmed_on, excluded.confirmed_on), \
+        rooted_on=COALESCE(s.rooted_on, excluded.rooted_on), \
+        dead_reason=COALESCE(excluded.dead_reason, s.dead_reason), \
+        updated_on=excluded.updated_on", static_columns.names, static_columns.values)
+    }
+
+    /// Build the statement upserting the status of a slot without its parent into the slot
+    /// table.
+    pub fn build_slot_upsert_statement_without_parent(
         client: &mut Client,
         config: &AccountsDbPluginPostgresConfig,
     ) -> Result<Statement, GeyserPluginError> {
-        let stmt = "INSERT INTO slot (slot, status, updated_on) \
-        VALUES ($1, $2, $3) \
-        ON CONFLICT (slot) DO UPDATE SET status=excluded.status, updated_on=excluded.updated_on";
-
-        let stmt = client.prepare(stmt);
+        let stmt = client.prepare(&Self::slot_upsert_without_parent_sql(config));
 
         match stmt {
             Err(err) => {
-                return Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
                     msg: format!(
                         "Error in preparing for the slot update PostgreSQL database: {} host: {:?} user: {:?} config: {:?}",
                         err, config.host, config.user, config
                     ),
-                })));
+                })))
             }
             Ok(stmt) => Ok(stmt),
         }
@@ -462,24 +1100,27 @@ impl SimplePostgresClient {
         account: &DbAccountInfo,
         statement: &Statement,
         client: &mut Client,
+        write_stats: &WriteStats,
+        compressed_data: &Option<Vec<u8>>,
     ) -> Result<(), GeyserPluginError> {
-        let lamports = account.lamports() as i64;
-        let rent_epoch = account.rent_epoch() as i64;
+        let lamports = account.lamports();
+        let rent_epoch = account.rent_epoch();
         let updated_on = Utc::now().naive_utc();
-        let result = client.execute(
-            statement,
-            &[
-                &account.pubkey(),
-                &account.slot,
-                &account.owner(),
-                &lamports,
-                &account.executable(),
-                &rent_epoch,
-                &account.data(),
-                &account.write_version(),
-                &updated_on,
-            ],
-        );
+        let data_codec = data_codec(compressed_data);
+        let params: [&(dyn types::ToSql + Sync); ACCOUNT_COLUMN_COUNT] = [
+            &account.pubkey(),
+            &account.slot,
+            &account.owner(),
+            &lamports,
+            &account.executable(),
+            &rent_epoch,
+            compressed_data.as_ref().unwrap_or(&account.data),
+            &account.write_version(),
+            &updated_on,
+            &data_codec,
+            &account.txn_signature,
+        ];
+        let result = client.execute(statement, &params);
 
         if let Err(err) = result {
             let msg = format!(
@@ -489,6 +1130,7 @@ impl SimplePostgresClient {
             error!("{}", msg);
             return Err(GeyserPluginError::AccountsUpdateError { msg });
         }
+        write_stats.record("account_audit", 1, statement, &params);
         Ok(())
     }
 
@@ -498,37 +1140,38 @@ impl SimplePostgresClient {
         statement: &Statement,
         client: &mut Client,
         insert_account_audit_stmt: &Option<Statement>,
+        write_stats: &WriteStats,
+        account_decoders: &AccountDecoders,
+        data_compression: &DataCompression,
     ) -> Result<(), GeyserPluginError> {
-        let lamports = account.lamports() as i64;
-        let rent_epoch = account.rent_epoch() as i64;
-        let updated_on = Utc::now().naive_utc();
-        let result = client.execute(
-            statement,
-            &[
-                &account.pubkey(),
-                &account.slot,
-                &account.owner(),
-                &lamports,
-                &account.executable(),
-                &rent_epoch,
-                &account.data(),
-                &account.write_version(),
-                &updated_on,
-            ],
-        );
+        let values = AccountRowValues::new(account, account_decoders, data_compression);
+        let params = values.params(account, account_decoders);
+        let result = client.query(statement, &params);
 
-        if let Err(err) = result {
-            let msg = format!(
-                "Failed to persist the update of account to the PostgreSQL database. Error: {:?}",
-                err
-            );
-            error!("{}", msg);
-            return Err(GeyserPluginError::AccountsUpdateError { msg });
-        } else if result.unwrap() == 0 && insert_account_audit_stmt.is_some() {
+        let rows = match result {
+            Err(err) => {
+                let msg = format!(
+                    "Failed to persist the update of account to the PostgreSQL database. Error: {:?}",
+                    err
+                );
+                error!("{}", msg);
+                return Err(GeyserPluginError::AccountsUpdateError { msg });
+            }
+            Ok(rows) => rows,
+        };
+        write_stats.record("account", 1, statement, &params);
+        write_stats.record_account_upserts(1, &rows);
+        if rows.is_empty() && insert_account_audit_stmt.is_some() {
             // If no records modified (inserted or updated), it is because the account is updated
             // at an older slot, insert the record directly into the account_audit table.
             let statement = insert_account_audit_stmt.as_ref().unwrap();
-            Self::insert_account_audit(account, statement, client)?;
+            Self::insert_account_audit(
+                account,
+                statement,
+                client,
+                write_stats,
+                &values.compressed_data,
+            )?;
         }
 
         Ok(())
@@ -537,10 +1180,31 @@ impl SimplePostgresClient {
     /// Update or insert a single account
     fn upsert_account(&mut self, account: &DbAccountInfo) -> Result<(), GeyserPluginError> {
         let client = self.client.get_mut().unwrap();
-        let insert_account_audit_stmt = &client.insert_account_audit_stmt;
+        // The stale updates are not copied either while the audit is skipped
+        let insert_account_audit_stmt = match client.audit_skipped {
+            Some(true) => &None,
+            _ => &client.insert_account_audit_stmt,
+        };
         let statement = &client.update_account_stmt;
+        let post_commit_hooks = &client.post_commit_hooks;
+        let notifier = &client.notifier;
+        let write_stats = &client.write_stats;
         let client = &mut client.client;
-        Self::upsert_account_internal(account, statement, client, insert_account_audit_stmt)
+        Self::upsert_account_internal(
+            account,
+            statement,
+            client,
+            insert_account_audit_stmt,
+            write_stats,
+            &self.account_decoders,
+            &self.data_compression,
+        )?;
+        notifier.notify(
+            client,
+            "account",
+            std::iter::once_with(|| account_payload(account)),
+        )?;
+        post_commit_hooks.execute(client, "account", 1, account.slot, account.slot)
     }
 
     /// Insert accounts in batch to reduce network overhead
@@ -554,20 +1218,40 @@ impl SimplePostgresClient {
             let mut measure = Measure::start("accountsdb-plugin-postgres-prepare-values");
 
             let mut values: Vec<&(dyn types::ToSql + Sync)> =
-                Vec::with_capacity(self.batch_size * ACCOUNT_COLUMN_COUNT);
+                Vec::with_capacity(self.batch_size * (ACCOUNT_COLUMN_COUNT + 1));
             let updated_on = Utc::now().naive_utc();
-            for j in 0..self.batch_size {
-                let account = &self.pending_account_updates[j];
-
+            let data_json: Vec<_> = self
+                .pending_account_updates
+                .iter()
+                .map(|account| self.account_decoders.decode(&account.owner, &account.data))
+                .collect();
+            let compressed_data: Vec<_> = self
+                .pending_account_updates
+                .iter()
+                .map(|account| self.data_compression.compress(&account.data))
+                .collect();
+            let data_codecs: Vec<_> = compressed_data.iter().map(data_codec).collect();
+            for (((account, data_json), compressed_data), data_codec) in self
+                .pending_account_updates
+                .iter()
+                .zip(&data_json)
+                .zip(&compressed_data)
+                .zip(&data_codecs)
+            {
                 values.push(&account.pubkey);
                 values.push(&account.slot);
                 values.push(&account.owner);
                 values.push(&account.lamports);
                 values.push(&account.executable);
                 values.push(&account.rent_epoch);
-                values.push(&account.data);
+                values.push(compressed_data.as_ref().unwrap_or(&account.data));
                 values.push(&account.write_version);
                 values.push(&updated_on);
+                values.push(data_codec);
+                values.push(&account.txn_signature);
+                if self.account_decoders.is_enabled() {
+                    values.push(data_json);
+                }
             }
             measure.stop();
             inc_new_counter_debug!(
@@ -583,7 +1267,58 @@ impl SimplePostgresClient {
                 .client
                 .query(&client.bulk_account_insert_stmt, &values);
 
-            self.pending_account_updates.clear();
+            let first_slot = self
+                .pending_account_updates
+                .iter()
+                .map(|account| account.slot)
+                .min()
+                .unwrap_or_default();
+            let last_slot = self
+                .pending_account_updates
+                .iter()
+                .map(|account| account.slot)
+                .max()
+                .unwrap_or_default();
+            let payloads: Vec<_> = if client.notifier.is_enabled("account") {
+                self.pending_account_updates
+                    .iter()
+                    .map(account_payload)
+                    .collect()
+            } else {
+                vec![]
+            };
+            let mut sampled_accounts = vec![];
+            if let Ok(rows) = &result {
+                if let Some(verifier) = &self.write_verifier {
+                    sampled_accounts = self
+                        .pending_account_updates
+                        .iter()
+                        .filter(|account| verifier.is_sampled(&account.pubkey, account.slot))
+                        .cloned()
+                        .collect();
+                }
+                client.write_stats.record(
+                    "account",
+                    self.batch_size,
+                    &client.bulk_account_insert_stmt,
+                    &values,
+                );
+                client
+                    .write_stats
+                    .record_account_upserts(self.batch_size, rows);
+                self.startup_stats
+                    .record_accounts(&self.pending_account_updates);
+            } else if self.retry_on_reconnect && is_connection_dropped(&mut client.client) {
+                // The batch is kept, the account is batched again when written again
+                // once reconnected.
+                self.pending_account_updates.pop();
+            } else {
+                self.startup_stats
+                    .record_failed_accounts(self.pending_account_updates.len());
+            }
+            if self.pending_account_updates.len() == self.batch_size {
+                self.pending_account_updates.clear();
+            }
             if let Err(err) = result {
                 let msg = format!(
                     "Failed to persist the update of account to the PostgreSQL database. Error: {:?}",
@@ -592,6 +1327,16 @@ impl SimplePostgresClient {
                 error!("{}", msg);
                 return Err(GeyserPluginError::AccountsUpdateError { msg });
             }
+            client
+                .notifier
+                .notify(&mut client.client, "account", payloads)?;
+            client.post_commit_hooks.execute(
+                &mut client.client,
+                "account",
+                self.batch_size,
+                first_slot,
+                last_slot,
+            )?;
             measure.stop();
             inc_new_counter_debug!(
                 "accountsdb-plugin-postgres-update-account-us",
@@ -605,30 +1350,83 @@ impl SimplePostgresClient {
                 10000,
                 10000
             );
+            for account in &sampled_accounts {
+                self.verify_account_write(account);
+            }
         }
         Ok(())
     }
 
     /// Flush any left over accounts in batch which are not processed in the last batch
     fn flush_buffered_writes(&mut self) -> Result<(), GeyserPluginError> {
+        self.flush_token_accounts(1)?;
+        self.flush_token_index_entries(1)?;
         if self.pending_account_updates.is_empty() {
             return Ok(());
         }
 
         let client = self.client.get_mut().unwrap();
-        let insert_account_audit_stmt = &client.insert_account_audit_stmt;
+        // The stale updates are not copied either while the audit is skipped
+        let insert_account_audit_stmt = match client.audit_skipped {
+            Some(true) => &None,
+            _ => &client.insert_account_audit_stmt,
+        };
         let statement = &client.update_account_stmt;
+        let post_commit_hooks = &client.post_commit_hooks;
+        let notifier = &client.notifier;
+        let write_stats = &client.write_stats;
         let client = &mut client.client;
 
-        for account in self.pending_account_updates.drain(..) {
-            Self::upsert_account_internal(&account, statement, client, insert_account_audit_stmt)?;
+        let row_count = self.pending_account_updates.len();
+        let first_slot = self
+            .pending_account_updates
+            .iter()
+            .map(|account| account.slot)
+            .min()
+            .unwrap_or_default();
+        let last_slot = self
+            .pending_account_updates
+            .iter()
+            .map(|account| account.slot)
+            .max()
+            .unwrap_or_default();
+        let mut payloads = vec![];
+        let mut accounts = self.pending_account_updates.drain(..);
+        while let Some(account) = accounts.next() {
+            if let Err(err) = Self::upsert_account_internal(
+                &account,
+                statement,
+                client,
+                insert_account_audit_stmt,
+                write_stats,
+                &self.account_decoders,
+                &self.data_compression,
+            ) {
+                if self.retry_on_reconnect && is_connection_dropped(client) {
+                    // The accounts left are flushed again once reconnected
+                    let accounts_left = std::iter::once(account).chain(accounts).collect();
+                    self.pending_account_updates = accounts_left;
+                    return Err(err);
+                }
+                self.startup_stats
+                    .record_failed_accounts(1 + accounts.len());
+                return Err(err);
+            }
+            self.startup_stats.record_accounts([&account]);
+            if notifier.is_enabled("account") {
+                payloads.push(account_payload(&account));
+            }
         }
 
-        Ok(())
+        notifier.notify(client, "account", payloads)?;
+        post_commit_hooks.execute(client, "account", row_count, first_slot, last_slot)
     }
 
-    pub fn new(config: &AccountsDbPluginPostgresConfig) -> Result<Self, GeyserPluginError> {
-        info!("Creating SimplePostgresClient...");
+    /// Connect to the database and prepare the statements, also used to rebuild the
+    /// connections dropped.
+    fn connect_and_prepare(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<PostgresSqlClientWrapper, GeyserPluginError> {
         let mut client = Self::connect_to_db(config)?;
         let bulk_account_insert_stmt =
             Self::build_bulk_account_insert_statement(&mut client, config)?;
@@ -643,10 +1441,6 @@ impl SimplePostgresClient {
         let update_block_metadata_stmt =
             Self::build_block_metadata_upsert_statement(&mut client, config)?;
 
-        let batch_size = config
-            .batch_size
-            .unwrap_or(DEFAULT_ACCOUNTS_INSERT_BATCH_SIZE);
-
         let store_account_historical_data = config
             .store_account_historical_data
             .unwrap_or(DEFAULT_STORE_ACCOUNT_HISTORICAL_DATA);
@@ -658,20 +1452,250 @@ impl SimplePostgresClient {
             None
         };
 
+        let insert_account_version_stmt = if config.account_versions_selector.is_some() {
+            Some(Self::build_account_version_insert_statement(
+                &mut client,
+                config,
+            )?)
+        } else {
+            None
+        };
+
+        let store_vote_activity = config
+            .store_vote_activity
+            .unwrap_or(DEFAULT_STORE_VOTE_ACTIVITY);
+
+        let update_vote_activity_stmt = if store_vote_activity {
+            let stmt = Self::build_vote_activity_upsert_statement(&mut client, config)?;
+            Some(stmt)
+        } else {
+            None
+        };
+
+        let upsert_entry_stmt = if config.store_entries.unwrap_or(DEFAULT_STORE_ENTRIES) {
+            Some(Self::build_entry_upsert_statement(&mut client, config)?)
+        } else {
+            None
+        };
+
+        let (verify_block_chain_stmt, upsert_block_chain_gap_stmt) =
+            if config.block_chain_verification_interval.is_some() {
+                (
+                    Some(Self::build_block_chain_verification_statement(
+                        &mut client,
+                        config,
+                    )?),
+                    Some(Self::build_block_chain_gap_upsert_statement(
+                        &mut client,
+                        config,
+                    )?),
+                )
+            } else {
+                (None, None)
+            };
+
+        let store_transaction_addresses = config
+            .store_transaction_addresses
+            .unwrap_or(DEFAULT_STORE_TRANSACTION_ADDRESSES);
+
+        let insert_transaction_address_stmt = if store_transaction_addresses {
+            let stmt = Self::build_transaction_address_insert_statement(&mut client, config)?;
+            Some(stmt)
+        } else {
+            None
+        };
+
+        let store_sol_transfers = config
+            .store_sol_transfers
+            .unwrap_or(DEFAULT_STORE_SOL_TRANSFERS);
+
+        let insert_sol_transfer_stmt = if store_sol_transfers {
+            let stmt = Self::build_sol_transfer_insert_statement(&mut client, config)?;
+            Some(stmt)
+        } else {
+            None
+        };
+
+        let store_transaction_memos = config
+            .store_transaction_memos
+            .unwrap_or(DEFAULT_STORE_TRANSACTION_MEMOS);
+
+        let insert_transaction_memo_stmt = if store_transaction_memos {
+            let stmt = Self::build_transaction_memo_insert_statement(&mut client, config)?;
+            Some(stmt)
+        } else {
+            None
+        };
+
+        let insert_transaction_instruction_stmt = if config.parsed_instruction_programs.is_some() {
+            let stmt = Self::build_transaction_instruction_insert_statement(&mut client, config)?;
+            Some(stmt)
+        } else {
+            None
+        };
+
+        let store_normalized_transaction_meta = config
+            .store_normalized_transaction_meta
+            .unwrap_or(DEFAULT_STORE_NORMALIZED_TRANSACTION_META);
+
+        let insert_transaction_meta_stmts = if store_normalized_transaction_meta {
+            let stmts = Self::build_transaction_meta_statements(&mut client, config)?;
+            Some(stmts)
+        } else {
+            None
+        };
+
+        let store_slot_economics = config
+            .store_slot_economics
+            .unwrap_or(DEFAULT_STORE_SLOT_ECONOMICS);
+
+        let upsert_slot_economics_stmt = if store_slot_economics {
+            let stmt = Self::build_slot_economics_upsert_statement(&mut client, config)?;
+            Some(stmt)
+        } else {
+            None
+        };
+
+        let store_block_rewards = config
+            .store_block_rewards
+            .unwrap_or(DEFAULT_STORE_BLOCK_REWARDS);
+
+        let insert_block_reward_stmt = if store_block_rewards {
+            let stmt = Self::build_block_reward_insert_statement(&mut client, config)?;
+            Some(stmt)
+        } else {
+            None
+        };
+
+        let store_validator_rewards = config
+            .store_validator_rewards
+            .unwrap_or(DEFAULT_STORE_VALIDATOR_REWARDS);
+
+        let insert_validator_reward_stmt = if store_validator_rewards {
+            let stmt = Self::build_validator_reward_insert_statement(&mut client, config)?;
+            Some(stmt)
+        } else {
+            None
+        };
+
+        let store_stake_rewards = config
+            .store_stake_rewards
+            .unwrap_or(DEFAULT_STORE_STAKE_REWARDS);
+
+        let upsert_stake_reward_stmt = if store_stake_rewards {
+            let stmt = Self::build_stake_reward_upsert_statement(&mut client, config)?;
+            Some(stmt)
+        } else {
+            None
+        };
+
+        let insert_dead_letter_stmt = if config.dead_letter_attempts.is_some() {
+            let stmt = Self::build_dead_letter_insert_statement(&mut client, config)?;
+            Some(stmt)
+        } else {
+            None
+        };
+
+        let token_account_storage = config.token_account_storage.unwrap_or_default();
+        let (upsert_token_account_stmt, upsert_token_mint_stmt, delete_token_stmt) =
+            if token_account_storage != TokenAccountStorage::Raw {
+                (
+                    if token_account_storage != TokenAccountStorage::Mints {
+                        Some(Self::build_token_account_upsert_statement(
+                            &mut client,
+                            config,
+                        )?)
+                    } else {
+                        None
+                    },
+                    Some(Self::build_token_mint_upsert_statement(
+                        &mut client,
+                        config,
+                    )?),
+                    Some(Self::build_token_delete_statement(&mut client, config)?),
+                )
+            } else {
+                (None, None, None)
+            };
+
+        let token_index_stmts = Self::build_token_index_statements(&mut client, config)?;
+
+        let post_commit_hooks = Self::build_post_commit_hooks(&mut client, config)?;
+        let notifier = Self::build_notifier(&mut client, config)?;
+        let write_stats = Self::build_write_stats(&mut client, config)?;
+
+        Ok(PostgresSqlClientWrapper {
+            client,
+            update_account_stmt,
+            bulk_account_insert_stmt,
+            update_slot_with_parent_stmt,
+            update_slot_without_parent_stmt,
+            update_transaction_log_stmt,
+            update_block_metadata_stmt,
+            insert_block_reward_stmt,
+            insert_account_audit_stmt,
+            insert_account_version_stmt,
+            update_vote_activity_stmt,
+            upsert_entry_stmt,
+            verify_block_chain_stmt,
+            upsert_block_chain_gap_stmt,
+            insert_transaction_address_stmt,
+            insert_sol_transfer_stmt,
+            insert_transaction_memo_stmt,
+            insert_transaction_instruction_stmt,
+            insert_transaction_meta_stmts,
+            upsert_slot_economics_stmt,
+            insert_validator_reward_stmt,
+            upsert_stake_reward_stmt,
+            insert_dead_letter_stmt,
+            upsert_token_account_stmt,
+            upsert_token_mint_stmt,
+            delete_token_stmt,
+            token_index_stmts,
+            post_commit_hooks,
+            notifier,
+            write_stats,
+            statement_timeouts: StatementTimeouts::new(config),
+            audit_skipped: None,
+        })
+    }
+
+    pub fn new(config: &AccountsDbPluginPostgresConfig) -> Result<Self, GeyserPluginError> {
+        info!("Creating SimplePostgresClient...");
+        let account_decoders = AccountDecoders::new(config)?;
+        let client = Self::connect_and_prepare(config)?;
+        let batch_size = config
+            .batch_size
+            .unwrap_or(DEFAULT_ACCOUNTS_INSERT_BATCH_SIZE);
+
         info!("Created SimplePostgresClient.");
         Ok(Self {
             batch_size,
             pending_account_updates: Vec::with_capacity(batch_size),
-            client: Mutex::new(PostgresSqlClientWrapper {
-                client,
-                update_account_stmt,
-                bulk_account_insert_stmt,
-                update_slot_with_parent_stmt,
-                update_slot_without_parent_stmt,
-                update_transaction_log_stmt,
-                update_block_metadata_stmt,
-                insert_account_audit_stmt,
-            }),
+            startup_stats: StartupStats::default(),
+            signature_storage: config.signature_storage.unwrap_or_default(),
+            store_block_json: config.store_block_json.unwrap_or(DEFAULT_STORE_BLOCK_JSON),
+            store_transaction_json: config
+                .store_transaction_json
+                .unwrap_or(DEFAULT_STORE_TRANSACTION_JSON),
+            slots_per_epoch: config.slots_per_epoch.unwrap_or(DEFAULT_SLOTS_PER_EPOCH),
+            dead_letter_attempts: config.dead_letter_attempts,
+            retry_on_reconnect: config.reconnect_max_backoff_secs.is_some(),
+            token_account_storage: config.token_account_storage.unwrap_or_default(),
+            account_layout: config.account_layout.unwrap_or_default(),
+            pending_token_accounts: Vec::default(),
+            pending_token_mints: Vec::default(),
+            pending_token_index_entries: Vec::default(),
+            account_decoders,
+            data_compression: DataCompression::new(config),
+            fork_cleanup: config.fork_cleanup,
+            account_versions_selector: config
+                .account_versions_selector
+                .as_ref()
+                .map(AccountsSelector::from_config),
+            write_verifier: WriteVerifier::new(config),
+            verification_replica: VerificationReplica::new(config),
+            client: Mutex::new(client),
         })
     }
 }
@@ -679,7 +1703,7 @@ impl SimplePostgresClient {
 impl PostgresClient for SimplePostgresClient {
     fn update_account(
         &mut self,
-        account: DbAccountInfo,
+        account: &DbAccountInfo,
         is_startup: bool,
     ) -> Result<(), GeyserPluginError> {
         trace!(
@@ -688,36 +1712,36 @@ impl PostgresClient for SimplePostgresClient {
             bs58::encode(account.owner()).into_string(),
             account.slot,
         );
+        self.write_account_version(account)?;
+        self.write_token_index(account, is_startup)?;
+        if !self.write_token_account(account, is_startup)? {
+            return Ok(());
+        }
         if !is_startup {
-            return self.upsert_account(&account);
+            self.write_with_dead_letter("account", account.slot, account, Self::upsert_account)?;
+            self.verify_account_write(account);
+            return Ok(());
         }
-        self.insert_accounts_in_batch(account)
+        self.insert_accounts_in_batch(account.clone())
     }
 
     fn update_slot_status(
         &mut self,
         slot: u64,
         parent: Option<u64>,
-        status: SlotStatus,
+        status: &SlotStatus,
     ) -> Result<(), GeyserPluginError> {
         info!("Updating slot {:?} at with status {:?}", slot, status);
 
-        let slot = slot as i64; // postgres only supports i64
-        let parent = parent.map(|parent| parent as i64);
-        let updated_on = Utc::now().naive_utc();
-        let status_str = status.as_str();
+        let row = SlotRowValues::new(slot, parent, status.clone());
         let client = self.client.get_mut().unwrap();
-
-        let result = match parent {
-            Some(parent) => client.client.execute(
-                &client.update_slot_with_parent_stmt,
-                &[&slot, &parent, &status_str, &updated_on],
-            ),
-            None => client.client.execute(
-                &client.update_slot_without_parent_stmt,
-                &[&slot, &status_str, &updated_on],
-            ),
+        let statement = if row.has_parent() {
+            &client.update_slot_with_parent_stmt
+        } else {
+            &client.update_slot_without_parent_stmt
         };
+        let params = row.params();
+        let result = client.client.execute(statement, &params);
 
         match result {
             Err(err) => {
@@ -732,59 +1756,147 @@ impl PostgresClient for SimplePostgresClient {
                 assert_eq!(1, rows, "Expected one rows to be updated a time");
             }
         }
-
-        Ok(())
+        client.write_stats.record("slot", 1, statement, &params);
+
+        client.notifier.notify(
+            &mut client.client,
+            "slot",
+            [json!({"slot": row.slot, "parent": row.parent, "status": row.status})],
+        )?;
+        client
+            .post_commit_hooks
+            .execute(&mut client.client, "slot", 1, row.slot, row.slot)
     }
 
     fn notify_end_of_startup(&mut self) -> Result<(), GeyserPluginError> {
         self.flush_buffered_writes()
     }
 
+    fn write_startup_report(
+        &mut self,
+        request: &WriteStartupReportRequest,
+    ) -> Result<(), GeyserPluginError> {
+        self.write_startup_report_impl(request)
+    }
+
     fn log_transaction(
         &mut self,
-        transaction_log_info: LogTransactionRequest,
+        transaction_log_info: &LogTransactionRequest,
     ) -> Result<(), GeyserPluginError> {
-        self.log_transaction_impl(transaction_log_info)
+        self.write_with_dead_letter(
+            "transaction",
+            transaction_log_info.transaction_info.slot,
+            transaction_log_info,
+            Self::log_transaction_impl,
+        )?;
+        self.verify_transaction_write(&transaction_log_info.transaction_info);
+        Ok(())
     }
 
     fn update_block_metadata(
         &mut self,
-        block_info: UpdateBlockMetadataRequest,
+        block_info: &UpdateBlockMetadataRequest,
     ) -> Result<(), GeyserPluginError> {
         self.update_block_metadata_impl(block_info)
     }
+
+    fn log_vote_activity(
+        &mut self,
+        vote_activity_info: &LogVoteActivityRequest,
+    ) -> Result<(), GeyserPluginError> {
+        self.log_vote_activity_impl(vote_activity_info)
+    }
+
+    fn update_entries(&mut self, request: &UpdateEntryRequest) -> Result<(), GeyserPluginError> {
+        self.update_entries_impl(request)
+    }
+
+    fn verify_block_chain(
+        &mut self,
+        request: &VerifyBlockChainRequest,
+    ) -> Result<(), GeyserPluginError> {
+        self.verify_block_chain_impl(request)
+    }
+
+    fn clean_up_forks(&mut self, request: &CleanUpForksRequest) -> Result<(), GeyserPluginError> {
+        self.clean_up_forks_impl(request)
+    }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 struct UpdateAccountRequest {
     account: DbAccountInfo,
     is_startup: bool,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 struct UpdateSlotRequest {
     slot: u64,
     parent: Option<u64>,
+    #[serde(with = "SlotStatusDef")]
     slot_status: SlotStatus,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct UpdateBlockMetadataRequest {
     pub block_info: DbBlockInfo,
+    /// The getBlock-shaped JSON of the block when the block JSON is stored
+    pub block_json: Option<serde_json::Value>,
+    /// The fee burn and the rent collected of the slot when the slot economics is stored
+    pub slot_economics: Option<DbSlotEconomics>,
 }
 
 #[warn(clippy::large_enum_variant)]
+#[derive(Clone)]
 enum DbWorkItem {
     UpdateAccount(Box<UpdateAccountRequest>),
     UpdateSlot(Box<UpdateSlotRequest>),
     LogTransaction(Box<LogTransactionRequest>),
     UpdateBlockMetadata(Box<UpdateBlockMetadataRequest>),
+    LogVoteActivity(Box<LogVoteActivityRequest>),
+    UpdateEntry(Box<UpdateEntryRequest>),
+    VerifyBlockChain(Box<VerifyBlockChainRequest>),
+    WriteStartupReport(Box<WriteStartupReportRequest>),
+    CommitSlot(Box<CommitSlotRequest>),
+    CleanUpForks(Box<CleanUpForksRequest>),
+    WriteEpochBarrier(Box<WriteEpochBarrierRequest>),
 }
 
 impl PostgresClientWorker {
-    fn new(config: AccountsDbPluginPostgresConfig) -> Result<Self, GeyserPluginError> {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        config: AccountsDbPluginPostgresConfig,
+        failover_decision: Arc<FailoverDecision>,
+        ingest_sla_monitor: Option<Arc<IngestSlaMonitor>>,
+        transaction_counts: Option<Arc<TransactionCounts>>,
+        slot_checksums: Option<Arc<SlotChecksums>>,
+        degradation: Option<Arc<Degradation>>,
+        metrics: Option<Arc<PluginMetrics>>,
+        shutdown_drain: Arc<ShutdownDrain>,
+    ) -> Result<Self, GeyserPluginError> {
         let result = SimplePostgresClient::new(&config);
         match result {
             Ok(client) => Ok(PostgresClientWorker {
                 client,
                 is_startup_done: false,
+                keepalive_interval: config
+                    .connection_keepalive_interval_secs
+                    .map(Duration::from_secs),
+                reconnect_buffer: ReconnectBuffer::new(&config),
+                failover: Failover::new(&config, failover_decision),
+                ingest_sla_monitor,
+                transaction_counts,
+                slot_checksums,
+                degradation,
+                cpu_budget: CpuBudget::new(&config),
+                statement_batch_limits: StatementBatchLimits::new(&config),
+                slot_commit_chunk_size: config
+                    .slot_commit_chunk_size
+                    .unwrap_or(DEFAULT_SLOT_COMMIT_CHUNK_SIZE),
+                metrics,
+                shutdown_drain,
+                config,
+                last_active: Instant::now(),
             }),
             Err(err) => {
                 error!("Error in creating SimplePostgresClient: {}", err);
@@ -793,15 +1905,133 @@ impl PostgresClientWorker {
         }
     }
 
+    /// Write the work item, keeping it within the replay window once committed. The
+    /// item failed is returned along with the error, to be written again.
+    fn write(&mut self, work: DbWorkItem) -> Result<(), (DbWorkItem, GeyserPluginError)> {
+        let started = Instant::now();
+        let result = self.write_item(&work);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_write_duration(started.elapsed());
+        }
+        match result {
+            Ok(()) => {
+                self.record_committed(work);
+                Ok(())
+            }
+            Err(err) => {
+                if let Some(metrics) = &self.metrics {
+                    metrics.record_write(work.metric(), false);
+                }
+                Err((work, err))
+            }
+        }
+    }
+
+    /// Write the work item, within the statement batch when one is in progress.
+    fn write_item(&mut self, work: &DbWorkItem) -> Result<(), GeyserPluginError> {
+        self.client
+            .apply_statement_timeout(work.statement_timeout_tables())?;
+        if let (Some(degradation), DbWorkItem::UpdateAccount(_)) = (&self.degradation, work) {
+            self.client.apply_audit_skip(degradation.skips_audit())?;
+        }
+        match work {
+            DbWorkItem::UpdateAccount(request) => self
+                .client
+                .update_account(&request.account, request.is_startup),
+            DbWorkItem::UpdateSlot(request) => {
+                self.client
+                    .update_slot_status(request.slot, request.parent, &request.slot_status)
+            }
+            DbWorkItem::LogTransaction(transaction_log_info) => {
+                self.client.log_transaction(transaction_log_info)
+            }
+            DbWorkItem::UpdateBlockMetadata(block_info) => {
+                self.client.update_block_metadata(block_info)
+            }
+            DbWorkItem::LogVoteActivity(vote_activity_info) => {
+                self.client.log_vote_activity(vote_activity_info)
+            }
+            DbWorkItem::UpdateEntry(request) => self.client.update_entries(request),
+            DbWorkItem::VerifyBlockChain(request) => self.client.verify_block_chain(request),
+            DbWorkItem::WriteStartupReport(request) => self.client.write_startup_report(request),
+            DbWorkItem::CommitSlot(request) => self.write_slot_commit(request),
+            DbWorkItem::CleanUpForks(request) => self.client.clean_up_forks(request),
+            DbWorkItem::WriteEpochBarrier(request) => request.arrive(&mut self.client),
+        }
+    }
+
+    /// Record the work item committed, for the ingestion SLA, the transaction counts,
+    /// the slot checksums, the metrics and the replay window.
+    fn record_committed(&mut self, work: DbWorkItem) {
+        if let DbWorkItem::CommitSlot(request) = &work {
+            self.record_committed_transactions(request);
+        }
+        if let (Some(monitor), Some((stream, slot))) =
+            (&self.ingest_sla_monitor, work.ingest_stream())
+        {
+            monitor.record_committed(stream, slot);
+        }
+        if let (Some(transaction_counts), Some(slot)) =
+            (&self.transaction_counts, work.transaction_slot())
+        {
+            transaction_counts.record_committed(slot);
+        }
+        if let (Some(slot_checksums), Some((slot, digest))) =
+            (&self.slot_checksums, work.checksummed_row())
+        {
+            slot_checksums.record_committed(slot, digest);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.record_write(work.metric(), true);
+        }
+        if let Some(buffer) = &mut self.reconnect_buffer {
+            buffer.record_committed(work);
+        }
+    }
+
+    fn write_or_abort(&mut self, work: DbWorkItem, panic_on_db_errors: bool) {
+        let description = work.description();
+        if let Err((_, err)) = self.write(work) {
+            error!("Failed to {}: ({})", description, err);
+            if panic_on_db_errors {
+                abort();
+            }
+        }
+    }
+
     fn do_work(
         &mut self,
         receiver: Receiver<DbWorkItem>,
         exit_worker: Arc<AtomicBool>,
         is_startup_done: Arc<AtomicBool>,
         startup_done_count: Arc<AtomicUsize>,
+        startup_stats: Arc<Mutex<StartupStats>>,
         panic_on_db_errors: bool,
     ) -> Result<(), GeyserPluginError> {
+        let mut is_drained = false;
         while !exit_worker.load(Ordering::Relaxed) {
+            if self.shutdown_drain.is_draining() {
+                if self.is_drain_stalled() {
+                    break;
+                }
+                if receiver.is_empty()
+                    && self
+                        .reconnect_buffer
+                        .as_ref()
+                        .is_none_or(|buffer| buffer.pending.is_empty())
+                {
+                    is_drained = true;
+                    break;
+                }
+            }
+            self.check_health();
+            if let Err(err) = self.client.report_write_stats_if_due() {
+                error!("Failed to report the write stats: ({})", err);
+                if panic_on_db_errors {
+                    abort();
+                }
+            }
+            self.client.verify_replayed_writes();
             let mut measure = Measure::start("accountsdb-plugin-postgres-worker-recv");
             let work = receiver.recv_timeout(Duration::from_millis(500));
             measure.stop();
@@ -811,57 +2041,55 @@ impl PostgresClientWorker {
                 100000,
                 100000
             );
+            if work.is_ok() {
+                self.last_active = Instant::now();
+            }
             match work {
-                Ok(work) => match work {
-                    DbWorkItem::UpdateAccount(request) => {
-                        if let Err(err) = self
-                            .client
-                            .update_account(request.account, request.is_startup)
-                        {
-                            error!("Failed to update account: ({})", err);
-                            if panic_on_db_errors {
-                                abort();
-                            }
-                        }
-                    }
-                    DbWorkItem::UpdateSlot(request) => {
-                        if let Err(err) = self.client.update_slot_status(
-                            request.slot,
-                            request.parent,
-                            request.slot_status,
-                        ) {
-                            error!("Failed to update slot: ({})", err);
-                            if panic_on_db_errors {
-                                abort();
-                            }
-                        }
-                    }
-                    DbWorkItem::LogTransaction(transaction_log_info) => {
-                        if let Err(err) = self.client.log_transaction(*transaction_log_info) {
-                            error!("Failed to update transaction: ({})", err);
-                            if panic_on_db_errors {
-                                abort();
-                            }
-                        }
-                    }
-                    DbWorkItem::UpdateBlockMetadata(block_info) => {
-                        if let Err(err) = self.client.update_block_metadata(*block_info) {
-                            error!("Failed to update block metadata: ({})", err);
-                            if panic_on_db_errors {
-                                abort();
-                            }
-                        }
+                Ok(work) => {
+                    let work = match self.statement_batch_limits {
+                        Some(limits) if work.is_batchable() => self.write_batch(
+                            work,
+                            &limits,
+                            &receiver,
+                            &exit_worker,
+                            panic_on_db_errors,
+                        ),
+                        _ => Some(work),
+                    };
+                    let Some(work) = work else {
+                        continue;
+                    };
+                    if self
+                        .cpu_budget
+                        .as_mut()
+                        .is_none_or(|cpu_budget| cpu_budget.admit(&work))
+                    {
+                        self.write_with_reconnect(work, &receiver, &exit_worker, panic_on_db_errors)
                     }
-                },
+                }
                 Err(err) => match err {
                     RecvTimeoutError::Timeout => {
+                        self.write_pending(&receiver, &exit_worker, panic_on_db_errors);
+                        self.keep_alive_if_idle();
                         if !self.is_startup_done && is_startup_done.load(Ordering::Relaxed) {
-                            if let Err(err) = self.client.notify_end_of_startup() {
+                            if let Err(err) = self
+                                .client
+                                .apply_statement_timeout(&["account", "account_audit"])
+                                .and_then(|()| self.client.notify_end_of_startup())
+                            {
+                                // The accounts left are flushed again once reconnected
+                                if self.reconnect_if_dropped(&receiver, &exit_worker) {
+                                    continue;
+                                }
                                 error!("Error in notifying end of startup: ({})", err);
                                 if panic_on_db_errors {
                                     abort();
                                 }
                             }
+                            startup_stats
+                                .lock()
+                                .unwrap()
+                                .merge(std::mem::take(&mut self.client.startup_stats));
                             self.is_startup_done = true;
                             startup_done_count.fetch_add(1, Ordering::Relaxed);
                         }
@@ -878,6 +2106,8 @@ impl PostgresClientWorker {
                 },
             }
         }
+        self.exit_after_drain(&receiver, is_drained);
+        self.shutdown_drain.record_worker_exit();
         Ok(())
     }
 }
@@ -887,63 +2117,298 @@ pub struct ParallelPostgresClient {
     is_startup_done: Arc<AtomicBool>,
     startup_done_count: Arc<AtomicUsize>,
     initialized_worker_count: Arc<AtomicUsize>,
+    /// The statistics of the accounts written during the startup merged from the workers
+    startup_stats: Arc<Mutex<StartupStats>>,
+    /// When the client is created, the startup report measures the startup from it
+    created_on: NaiveDateTime,
     sender: Sender<DbWorkItem>,
     last_report: AtomicInterval,
+    block_chain_verification_interval: Option<u64>,
+    /// The slot the block chain was last verified up to, plus one
+    block_chain_verified_until: AtomicU64,
+    /// The rooted slots between the cleanups of the abandoned forks, when configured
+    fork_cleanup_interval: Option<u64>,
+    /// The slot the abandoned forks were last cleaned up to, plus one
+    fork_cleaned_until: AtomicU64,
+    priority_accounts_selector: Option<AccountsSelector>,
+    priority_sender: Option<Sender<DbWorkItem>>,
+    /// The transactions buffered for the block JSON when the block JSON is stored
+    block_json_buffer: Option<BlockJsonBuffer>,
+    /// The transaction fees buffered for the slot economics when it is stored
+    slot_fee_buffer: Option<SlotFeeBuffer>,
+    /// The transactions buffered until the block metadata of their slot is notified,
+    /// with the per-slot commit
+    slot_commit_buffer: Option<SlotCommitBuffer>,
+    /// The entries of a slot buffered to be written in batches when they are stored
+    entry_buffer: Option<EntryBuffer>,
+    /// Indicates if to encode the transaction JSON of the transactions logged
+    store_transaction_json: bool,
+    /// Indicates if to extract the SOL transfers of the transactions logged
+    store_sol_transfers: bool,
+    /// Indicates if to extract the memos of the transactions logged
+    store_transaction_memos: bool,
+    /// The programs whose top level instructions of the transactions logged are parsed
+    parsed_instruction_programs: Vec<String>,
+    /// Indicates if to extract the token balances of the transactions logged
+    store_normalized_transaction_meta: bool,
+    /// The rows sent for the tables analyzed when the ANALYZE is scheduled
+    analyze_row_counts: Option<Arc<AnalyzeRowCounts>>,
+    /// The allowlist of the transactions when the transaction_allowlist table is polled
+    transaction_allowlist: Option<Arc<TransactionAllowlist>>,
+    /// The ingestion of the slots when the ingest SLA is monitored
+    ingest_sla_monitor: Option<Arc<IngestSlaMonitor>>,
+    /// The transactions per slot the ones received are recorded to, when they are counted
+    transaction_counts: Option<Arc<TransactionCounts>>,
+    /// The steps of the degradation ladder entered, when the ladder is configured
+    degradation: Option<Arc<Degradation>>,
+    /// The metrics the notifications are recorded to, when the metrics are served
+    metrics: Option<Arc<PluginMetrics>>,
+    /// The blocking of the account updates past the queue threshold when configured
+    backpressure: Option<Backpressure>,
+    /// The overflow of the queue of the workers, blocking or dropping the work items
+    queue_overflow: QueueOverflow,
+    /// The highest rooted slot the continuous aggregates are refreshed up to, when they
+    /// are configured
+    rooted_slot: Option<Arc<RootedSlot>>,
+    /// The config connecting to recreate the indexes dropped for the startup, when the
+    /// indexes are managed on the startup
+    startup_indexes_config: Option<AccountsDbPluginPostgresConfig>,
+    /// The checkpoint watermark the snapshot is checked against at the end of the
+    /// startup, when a slot has been rooted
+    snapshot_rewind: Option<SnapshotRewind>,
+    /// The epochs of the slots notified when the epoch barriers are written
+    epoch_barriers: Option<EpochBarriers>,
+    /// The drain of the queues of the workers when the plugin is unloaded
+    shutdown_drain: Arc<ShutdownDrain>,
 }
 
 impl ParallelPostgresClient {
     pub fn new(config: &AccountsDbPluginPostgresConfig) -> Result<Self, GeyserPluginError> {
         info!("Creating ParallelPostgresClient...");
-        let (sender, receiver) = bounded(MAX_ASYNC_REQUESTS);
+        let created_on = Utc::now().naive_utc();
+        let startup_barrier = StartupBarrier::new(config);
+        startup_barrier.wait_for_db(config)?;
+        SimplePostgresClient::create_schema_if_missing(config)?;
+        SimplePostgresClient::migrate_schema(config)?;
+        SimplePostgresClient::create_hypertables(config)?;
+        SimplePostgresClient::create_distributed_tables(config)?;
+        let manage_indexes_on_startup = config
+            .manage_indexes_on_startup
+            .unwrap_or(DEFAULT_MANAGE_INDEXES_ON_STARTUP);
+        if manage_indexes_on_startup {
+            SimplePostgresClient::drop_startup_indexes(config)?;
+        } else {
+            // The indexes dropped by a previous load interrupted during the startup
+            SimplePostgresClient::recreate_startup_indexes(config)?;
+        }
+        let mut config = config.clone();
+        SimplePostgresClient::resolve_dedup_window(&mut config)?;
+        let config = &config;
+        let snapshot_rewind = SnapshotRewind::new(config)?;
+        SimplePostgresClient::record_config_history(config, ConfigChange::Load)?;
+        SimplePostgresClient::refresh_program_labels(config)?;
+        SimplePostgresClient::refresh_address_tags(config)?;
+        let allowlist_poller = TransactionAllowlistPoller::new(config)?;
+        let flow_control_listener = FlowControlListener::new(config)?;
+        let ingest_sla_checker = IngestSlaChecker::new(config)?;
+        let transaction_count_writer = TransactionCountWriter::new(config)?;
+        let slot_checksum_writer = SlotChecksumWriter::new(config)?;
+        let degradation_ladder = DegradationLadder::new(config)?;
+        let audit_retention = AuditRetention::new(config)?;
+        let account_tiering = AccountTiering::new(config)?;
+        let continuous_aggregate_refresher = ContinuousAggregateRefresher::new(config)?;
+        let index_advisor = IndexAdvisor::new(config)?;
+        let schema_drift_checker = SchemaDriftChecker::new(config)?;
+        let ingest_sla_monitor = ingest_sla_checker.as_ref().map(IngestSlaChecker::monitor);
+        let transaction_counts = transaction_count_writer
+            .as_ref()
+            .map(TransactionCountWriter::counts);
+        let slot_checksums = slot_checksum_writer
+            .as_ref()
+            .map(SlotChecksumWriter::checksums);
+        let degradation = degradation_ladder
+            .as_ref()
+            .map(DegradationLadder::degradation);
+        let metrics_exporter = MetricsExporter::new(config)?;
+        let metrics = metrics_exporter.as_ref().map(MetricsExporter::metrics);
+        let plugin_run = PluginRun::start(config)?;
+        let analyze_scheduler = AnalyzeScheduler::new(config)?;
+        let (sender, receiver) = bounded(config.queue_capacity.unwrap_or(MAX_ASYNC_REQUESTS));
         let exit_worker = Arc::new(AtomicBool::new(false));
         let mut workers = Vec::default();
         let is_startup_done = Arc::new(AtomicBool::new(false));
         let startup_done_count = Arc::new(AtomicUsize::new(0));
-        let worker_count = config.threads.unwrap_or(DEFAULT_THREADS_COUNT);
+        let is_async = config.client.unwrap_or_default() == ClientKind::Async;
+        // The async client writes through its pool from a single dispatcher
+        let worker_count = if is_async {
+            1
+        } else {
+            config.threads.unwrap_or(DEFAULT_THREADS_COUNT)
+        };
         let initialized_worker_count = Arc::new(AtomicUsize::new(0));
-        for i in 0..worker_count {
-            let cloned_receiver = receiver.clone();
-            let exit_clone = exit_worker.clone();
-            let is_startup_done_clone = is_startup_done.clone();
-            let startup_done_count_clone = startup_done_count.clone();
-            let initialized_worker_count_clone = initialized_worker_count.clone();
-            let config = config.clone();
-            let worker = Builder::new()
-                .name(format!("worker-{}", i))
-                .spawn(move || -> Result<(), GeyserPluginError> {
-                    let panic_on_db_errors = *config
-                        .panic_on_db_errors
-                        .as_ref()
-                        .unwrap_or(&DEFAULT_PANIC_ON_DB_ERROR);
-                    let result = PostgresClientWorker::new(config);
-
-                    match result {
-                        Ok(mut worker) => {
-                            initialized_worker_count_clone.fetch_add(1, Ordering::Relaxed);
-                            worker.do_work(
-                                cloned_receiver,
-                                exit_clone,
-                                is_startup_done_clone,
-                                startup_done_count_clone,
-                                panic_on_db_errors,
-                            )?;
-                            Ok(())
-                        }
-                        Err(err) => {
-                            error!("Error when making connection to database: ({})", err);
-                            if panic_on_db_errors {
-                                abort();
-                            }
-                            Err(err)
-                        }
-                    }
-                })
-                .unwrap();
+        let startup_stats = Arc::new(Mutex::new(StartupStats::default()));
+        // The workers fail over to the standby and back together
+        let failover_decision = Arc::new(FailoverDecision::default());
+        let shutdown_drain = Arc::new(ShutdownDrain::new(
+            config,
+            worker_count + usize::from(config.priority_accounts.is_some()),
+        ));
+        if is_async {
+            workers.push(AsyncPostgresClient::spawn_worker(
+                config.clone(),
+                receiver.clone(),
+                exit_worker.clone(),
+                is_startup_done.clone(),
+                startup_done_count.clone(),
+                initialized_worker_count.clone(),
+                startup_stats.clone(),
+                shutdown_drain.clone(),
+            ));
+        } else {
+            for i in 0..worker_count {
+                workers.push(Self::spawn_worker(
+                    format!("worker-{}", i),
+                    config.clone(),
+                    receiver.clone(),
+                    exit_worker.clone(),
+                    is_startup_done.clone(),
+                    startup_done_count.clone(),
+                    initialized_worker_count.clone(),
+                    startup_stats.clone(),
+                    failover_decision.clone(),
+                    ingest_sla_monitor.clone(),
+                    transaction_counts.clone(),
+                    slot_checksums.clone(),
+                    degradation.clone(),
+                    metrics.clone(),
+                    shutdown_drain.clone(),
+                ));
+            }
+        }
+
+        // The watchlisted accounts are written by a dedicated worker with its own
+        // connection so that they are not queued behind the bulk updates.
+        let (priority_accounts_selector, priority_sender) = match &config.priority_accounts {
+            Some(priority_accounts) => {
+                let selector = AccountsSelector::from_config(priority_accounts);
+                let (priority_sender, priority_receiver) = bounded(MAX_ASYNC_PRIORITY_REQUESTS);
+                workers.push(Self::spawn_worker(
+                    "priority-worker".to_string(),
+                    config.clone(),
+                    priority_receiver,
+                    exit_worker.clone(),
+                    is_startup_done.clone(),
+                    startup_done_count.clone(),
+                    initialized_worker_count.clone(),
+                    startup_stats.clone(),
+                    failover_decision.clone(),
+                    ingest_sla_monitor.clone(),
+                    transaction_counts.clone(),
+                    slot_checksums.clone(),
+                    degradation.clone(),
+                    metrics.clone(),
+                    shutdown_drain.clone(),
+                ));
+                (Some(selector), Some(priority_sender))
+            }
+            None => (None, None),
+        };
+        if let Err(err) = startup_barrier.wait_for_workers(&workers, &initialized_worker_count) {
+            exit_worker.store(true, Ordering::Relaxed);
+            for worker in workers {
+                let _ = worker.join();
+            }
+            return Err(err);
+        }
+        shutdown_drain.replay_spilled(&sender);
+
+        if let Some(plugin_run) = plugin_run {
+            workers.push(plugin_run.spawn_heartbeat(exit_worker.clone()));
+        }
+
+        let analyze_row_counts = analyze_scheduler.map(|analyze_scheduler| {
+            let analyze_row_counts = analyze_scheduler.row_counts();
+            workers.push(analyze_scheduler.spawn(exit_worker.clone(), is_startup_done.clone()));
+            analyze_row_counts
+        });
+
+        if let Some(ingest_sla_checker) = ingest_sla_checker {
+            workers.push(ingest_sla_checker.spawn(exit_worker.clone()));
+        }
+
+        if let Some(transaction_count_writer) = transaction_count_writer {
+            workers.push(transaction_count_writer.spawn(exit_worker.clone()));
+        }
+
+        if let Some(slot_checksum_writer) = slot_checksum_writer {
+            workers.push(slot_checksum_writer.spawn(exit_worker.clone()));
+        }
 
-            workers.push(worker);
+        if let Some(degradation_ladder) = degradation_ladder {
+            workers.push(degradation_ladder.spawn(
+                sender.clone(),
+                exit_worker.clone(),
+                is_startup_done.clone(),
+            ));
         }
 
+        if let Some(audit_retention) = audit_retention {
+            workers.push(audit_retention.spawn(exit_worker.clone()));
+        }
+
+        if let Some(account_tiering) = account_tiering {
+            workers.push(account_tiering.spawn(exit_worker.clone()));
+        }
+
+        if let Some(index_advisor) = index_advisor {
+            workers.push(index_advisor.spawn(exit_worker.clone()));
+        }
+
+        if let Some(schema_drift_checker) = schema_drift_checker {
+            workers.push(schema_drift_checker.spawn(
+                exit_worker.clone(),
+                is_startup_done.clone(),
+                metrics.clone(),
+            ));
+        }
+
+        let rooted_slot = continuous_aggregate_refresher.map(|refresher| {
+            let rooted_slot = refresher.rooted_slot();
+            workers.push(refresher.spawn(exit_worker.clone()));
+            rooted_slot
+        });
+
+        if let Some(metrics_exporter) = metrics_exporter {
+            let queues = std::iter::once(("shared", sender.clone()))
+                .chain(priority_sender.clone().map(|sender| ("priority", sender)))
+                .collect();
+            workers.push(metrics_exporter.spawn(queues, exit_worker.clone()));
+        }
+
+        let flow_control = flow_control_listener.map(|flow_control_listener| {
+            let flow_control = flow_control_listener.flow_control();
+            workers.push(flow_control_listener.spawn(config.clone(), exit_worker.clone()));
+            flow_control
+        });
+        let transaction_allowlist = allowlist_poller.map(|allowlist_poller| {
+            let transaction_allowlist = allowlist_poller.allowlist();
+            workers.push(allowlist_poller.spawn(exit_worker.clone()));
+            transaction_allowlist
+        });
+
+        let epoch_barriers = EpochBarriers::new(
+            config,
+            config.slots_per_epoch.unwrap_or(DEFAULT_SLOTS_PER_EPOCH),
+            worker_count + usize::from(priority_sender.is_some()),
+        );
         info!("Created ParallelPostgresClient.");
+        let queue_overflow = QueueOverflow::new(
+            config.queue_overflow_policy.unwrap_or_default(),
+            sender.clone(),
+            receiver,
+            shutdown_drain.clone(),
+            metrics.clone(),
+        );
         Ok(Self {
             last_report: AtomicInterval::default(),
             workers,
@@ -951,11 +2416,152 @@ impl ParallelPostgresClient {
             is_startup_done,
             startup_done_count,
             initialized_worker_count,
+            startup_stats,
+            created_on,
             sender,
+            block_chain_verification_interval: config.block_chain_verification_interval,
+            block_chain_verified_until: AtomicU64::default(),
+            fork_cleanup_interval: config.fork_cleanup.map(|_| {
+                config
+                    .fork_cleanup_interval_slots
+                    .unwrap_or(DEFAULT_FORK_CLEANUP_INTERVAL_SLOTS)
+            }),
+            fork_cleaned_until: AtomicU64::default(),
+            priority_accounts_selector,
+            priority_sender,
+            block_json_buffer: config
+                .store_block_json
+                .unwrap_or(DEFAULT_STORE_BLOCK_JSON)
+                .then(BlockJsonBuffer::default),
+            slot_fee_buffer: config
+                .store_slot_economics
+                .unwrap_or(DEFAULT_STORE_SLOT_ECONOMICS)
+                .then(SlotFeeBuffer::default),
+            slot_commit_buffer: SlotCommitBuffer::new(config),
+            entry_buffer: config
+                .store_entries
+                .unwrap_or(DEFAULT_STORE_ENTRIES)
+                .then(|| {
+                    EntryBuffer::new(config.entry_batch_size.unwrap_or(DEFAULT_ENTRY_BATCH_SIZE))
+                }),
+            store_transaction_json: config
+                .store_transaction_json
+                .unwrap_or(DEFAULT_STORE_TRANSACTION_JSON),
+            store_sol_transfers: config
+                .store_sol_transfers
+                .unwrap_or(DEFAULT_STORE_SOL_TRANSFERS),
+            store_transaction_memos: config
+                .store_transaction_memos
+                .unwrap_or(DEFAULT_STORE_TRANSACTION_MEMOS),
+            parsed_instruction_programs: config
+                .parsed_instruction_programs
+                .clone()
+                .unwrap_or_default(),
+            store_normalized_transaction_meta: config
+                .store_normalized_transaction_meta
+                .unwrap_or(DEFAULT_STORE_NORMALIZED_TRANSACTION_META),
+            analyze_row_counts,
+            transaction_allowlist,
+            ingest_sla_monitor,
+            transaction_counts,
+            degradation,
+            metrics,
+            backpressure: Backpressure::new(config, flow_control),
+            queue_overflow,
+            rooted_slot,
+            startup_indexes_config: manage_indexes_on_startup.then(|| config.clone()),
+            snapshot_rewind,
+            epoch_barriers,
+            shutdown_drain,
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_worker(
+        name: String,
+        config: AccountsDbPluginPostgresConfig,
+        receiver: Receiver<DbWorkItem>,
+        exit_worker: Arc<AtomicBool>,
+        is_startup_done: Arc<AtomicBool>,
+        startup_done_count: Arc<AtomicUsize>,
+        initialized_worker_count: Arc<AtomicUsize>,
+        startup_stats: Arc<Mutex<StartupStats>>,
+        failover_decision: Arc<FailoverDecision>,
+        ingest_sla_monitor: Option<Arc<IngestSlaMonitor>>,
+        transaction_counts: Option<Arc<TransactionCounts>>,
+        slot_checksums: Option<Arc<SlotChecksums>>,
+        degradation: Option<Arc<Degradation>>,
+        metrics: Option<Arc<PluginMetrics>>,
+        shutdown_drain: Arc<ShutdownDrain>,
+    ) -> JoinHandle<Result<(), GeyserPluginError>> {
+        Builder::new()
+            .name(name)
+            .spawn(move || -> Result<(), GeyserPluginError> {
+                let panic_on_db_errors = *config
+                    .panic_on_db_errors
+                    .as_ref()
+                    .unwrap_or(&DEFAULT_PANIC_ON_DB_ERROR);
+                let result = PostgresClientWorker::new(
+                    config,
+                    failover_decision,
+                    ingest_sla_monitor,
+                    transaction_counts,
+                    slot_checksums,
+                    degradation,
+                    metrics,
+                    shutdown_drain,
+                );
+
+                match result {
+                    Ok(mut worker) => {
+                        initialized_worker_count.fetch_add(1, Ordering::Relaxed);
+                        worker.do_work(
+                            receiver,
+                            exit_worker,
+                            is_startup_done,
+                            startup_done_count,
+                            startup_stats,
+                            panic_on_db_errors,
+                        )?;
+                        Ok(())
+                    }
+                    Err(err) => {
+                        error!("Error when making connection to database: ({})", err);
+                        if panic_on_db_errors {
+                            abort();
+                        }
+                        Err(err)
+                    }
+                }
+            })
+            .unwrap()
+    }
+
+    /// Get the sender for the account update, the updates of the watchlisted accounts
+    /// outside of the startup go through the priority lane.
+    fn account_sender(
+        &self,
+        account: &ReplicaAccountInfo,
+        is_startup: bool,
+    ) -> &Sender<DbWorkItem> {
+        match (&self.priority_accounts_selector, &self.priority_sender) {
+            (Some(selector), Some(priority_sender))
+                if !is_startup
+                    && selector.is_account_selected_with_lamports(
+                        account.pubkey,
+                        account.owner,
+                        account.lamports,
+                        account.data,
+                    ) =>
+            {
+                priority_sender
+            }
+            _ => &self.sender,
+        }
+    }
+
     pub fn join(&mut self) -> thread::Result<()> {
+        self.drain_on_shutdown();
         self.exit_worker.store(true, Ordering::Relaxed);
         while !self.workers.is_empty() {
             let worker = self.workers.pop();
@@ -974,7 +2580,7 @@ impl ParallelPostgresClient {
 
     pub fn update_account(
         &self,
-        account: &ReplicaAccountInfoV3,
+        account: &ReplicaAccountInfo,
         slot: u64,
         is_startup: bool,
     ) -> Result<(), GeyserPluginError> {
@@ -982,8 +2588,27 @@ impl ParallelPostgresClient {
             datapoint_debug!(
                 "postgres-plugin-stats",
                 ("message-queue-length", self.sender.len() as i64, i64),
+                (
+                    "priority-message-queue-length",
+                    self.priority_sender
+                        .as_ref()
+                        .map_or(0, |priority_sender| priority_sender.len())
+                        as i64,
+                    i64
+                ),
             );
         }
+        let sender = self.account_sender(account, is_startup);
+        // The watchlisted accounts of the priority lane are not degraded
+        if !is_startup
+            && sender.same_channel(&self.sender)
+            && self
+                .degradation
+                .as_ref()
+                .is_some_and(|degradation| !degradation.admits_account())
+        {
+            return Ok(());
+        }
         let mut measure = Measure::start("accountsdb-plugin-posgres-create-work-item");
         let wrk_item = DbWorkItem::UpdateAccount(Box::new(UpdateAccountRequest {
             account: DbAccountInfo::new(account, slot),
@@ -1001,7 +2626,15 @@ impl ParallelPostgresClient {
 
         let mut measure = Measure::start("accountsdb-plugin-posgres-send-msg");
 
-        if let Err(err) = self.sender.send(wrk_item) {
+        if let Some(backpressure) = &self.backpressure {
+            backpressure.wait(sender);
+        }
+        let result = if sender.same_channel(&self.sender) {
+            self.queue_overflow.send(wrk_item)
+        } else {
+            sender.send(wrk_item)
+        };
+        if let Err(err) = result {
             return Err(GeyserPluginError::AccountsUpdateError {
                 msg: format!(
                     "Failed to update the account {:?}, error: {:?}",
@@ -1019,6 +2652,10 @@ impl ParallelPostgresClient {
             100000
         );
 
+        if let Some(analyze_row_counts) = &self.analyze_row_counts {
+            analyze_row_counts.add(AnalyzeTable::Account, 1);
+        }
+        self.record_notification(MetricStream::Account);
         Ok(())
     }
 
@@ -1028,30 +2665,83 @@ impl ParallelPostgresClient {
         parent: Option<u64>,
         status: SlotStatus,
     ) -> Result<(), GeyserPluginError> {
-        if let Err(err) = self
-            .sender
-            .send(DbWorkItem::UpdateSlot(Box::new(UpdateSlotRequest {
-                slot,
-                parent,
-                slot_status: status,
-            })))
+        let is_rooted = status == SlotStatus::Rooted;
+        if let Some(block_json_buffer) = &self.block_json_buffer {
+            match &status {
+                SlotStatus::Rooted => block_json_buffer.remove_slots_up_to(slot),
+                SlotStatus::Dead(_) => block_json_buffer.remove_slot(slot),
+                _ => {}
+            }
+        }
+        if let Some(slot_fee_buffer) = &self.slot_fee_buffer {
+            match &status {
+                SlotStatus::Rooted => slot_fee_buffer.remove_slots_up_to(slot),
+                SlotStatus::Dead(_) => slot_fee_buffer.remove_slot(slot),
+                _ => {}
+            }
+        }
+        if let Some(slot_commit_buffer) = &self.slot_commit_buffer {
+            match &status {
+                SlotStatus::Rooted => slot_commit_buffer.remove_slots_up_to(slot),
+                SlotStatus::Dead(_) => slot_commit_buffer.remove_slot(slot),
+                _ => {}
+            }
+        }
+        self.record_ingest_slot_status(slot, &status);
+        self.record_notified_slot_status(slot);
+        self.write_epoch_barrier_on_new_epoch(slot)?;
+        // The entries of a slot are notified before its status is updated
+        self.flush_entries(slot)?;
+        if let Err(err) =
+            self.queue_overflow
+                .send(DbWorkItem::UpdateSlot(Box::new(UpdateSlotRequest {
+                    slot,
+                    parent,
+                    slot_status: status,
+                })))
         {
             return Err(GeyserPluginError::SlotStatusUpdateError {
                 msg: format!("Failed to update the slot {:?}, error: {:?}", slot, err),
             });
         }
+
+        if is_rooted {
+            if let Some(rooted_slot) = &self.rooted_slot {
+                rooted_slot.record(slot);
+            }
+            self.verify_block_chain_on_root(slot)?;
+            self.clean_up_forks_on_root(slot)?;
+        }
         Ok(())
     }
 
     pub fn update_block_metadata(
         &self,
-        block_info: &ReplicaBlockInfoV4,
+        block_info: &ReplicaBlockInfo,
     ) -> Result<(), GeyserPluginError> {
-        if let Err(err) = self.sender.send(DbWorkItem::UpdateBlockMetadata(Box::new(
-            UpdateBlockMetadataRequest {
-                block_info: DbBlockInfo::from(block_info),
-            },
-        ))) {
+        let block_json = self
+            .block_json_buffer
+            .as_ref()
+            .map(|block_json_buffer| block_json_buffer.take_block_json(block_info))
+            .transpose()?;
+        let slot_economics = self
+            .slot_fee_buffer
+            .as_ref()
+            .map(|slot_fee_buffer| slot_fee_buffer.take_slot_economics(block_info));
+        self.record_ingest_block_metadata(block_info.slot, block_info.executed_transaction_count);
+        // The transactions of the slot are all notified before its block metadata
+        self.commit_slot(block_info.slot)?;
+        self.record_notification(MetricStream::BlockMetadata);
+        if let Err(err) = self
+            .queue_overflow
+            .send(DbWorkItem::UpdateBlockMetadata(Box::new(
+                UpdateBlockMetadataRequest {
+                    block_info: DbBlockInfo::from(block_info),
+                    block_json,
+                    slot_economics,
+                },
+            )))
+        {
             return Err(GeyserPluginError::SlotStatusUpdateError {
                 msg: format!(
                     "Failed to update the block metadata at slot {:?}, error: {:?}",
@@ -1062,6 +2752,14 @@ impl ParallelPostgresClient {
         Ok(())
     }
 
+    /// Stop the workers once they have written the items queued and flushed their
+    /// pending batches, or once the drain times out, the items left being spilled.
+    pub fn stop(&self) {
+        info!("Stopping the workers");
+        self.drain_on_stop();
+        self.exit_worker.store(true, Ordering::Relaxed);
+    }
+
     pub fn notify_end_of_startup(&self) -> Result<(), GeyserPluginError> {
         info!("Notifying the end of startup");
         // Ensure all items in the queue has been received by the workers
@@ -1082,6 +2780,31 @@ impl ParallelPostgresClient {
             sleep(Duration::from_millis(100));
         }
 
+        if let Some(config) = &self.startup_indexes_config {
+            SimplePostgresClient::recreate_startup_indexes(config)?;
+        }
+
+        let stats = std::mem::take(&mut *self.startup_stats.lock().unwrap());
+        if let (Some(snapshot_rewind), Some(snapshot_slot)) =
+            (&self.snapshot_rewind, stats.highest_slot)
+        {
+            snapshot_rewind.check(snapshot_slot)?;
+        }
+        if let Err(err) = self
+            .queue_overflow
+            .send(DbWorkItem::WriteStartupReport(Box::new(
+                WriteStartupReportRequest {
+                    started_on: self.created_on,
+                    finished_on: Utc::now().naive_utc(),
+                    stats,
+                },
+            )))
+        {
+            return Err(GeyserPluginError::SlotStatusUpdateError {
+                msg: format!("Failed to write the startup report, error: {:?}", err),
+            });
+        }
+
         info!("Done with notifying the end of startup");
         Ok(())
     }
@@ -1096,9 +2819,206 @@ impl PostgresClientBuilder {
         ParallelPostgresClient::new(config)
     }
 
+    pub fn build_sharded_postgres_client(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<ShardedPostgresClient, GeyserPluginError> {
+        ShardedPostgresClient::new(config)
+    }
+
     pub fn build_simple_postgres_client(
         config: &AccountsDbPluginPostgresConfig,
     ) -> Result<SimplePostgresClient, GeyserPluginError> {
         SimplePostgresClient::new(config)
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, crate::accountsdb_plugin_postgres::AccountDataDecoderConfig};
+
+    #[test]
+    fn test_static_columns() {
+        let mut config: AccountsDbPluginPostgresConfig = serde_json::from_str(
+            "{\"static_columns\": {\"shard\": \"3\", \"region\": \"o'hare\"}}",
+        )
+        .unwrap();
+        let static_columns = StaticColumns::new(&config);
+        assert_eq!(static_columns.names, ", region, shard");
+        assert_eq!(static_columns.values, ", 'o''hare', '3'");
+        assert_eq!(static_columns.old_names, ", old.region, old.shard");
+
+        // The versions of the accounts copied into the account_audit table carry them
+        config.use_schema_triggers = Some(false);
+        config.store_account_historical_data = Some(true);
+        let stmt = SimplePostgresClient::account_upsert_sql(
+            "($1)",
+            &static_columns,
+            &ComputedColumns::new(&config),
+            &DataJsonColumn::new(&config),
+            &config,
+        );
+        assert!(stmt.contains(", region, shard) SELECT"));
+        assert!(stmt.contains(", old.region, old.shard FROM old_account"));
+
+        config.static_columns = None;
+        let static_columns = StaticColumns::new(&config);
+        assert!(static_columns.names.is_empty());
+        assert!(static_columns.values.is_empty());
+    }
+
+    #[test]
+    fn test_account_upsert_sql() {
+        let mut config: AccountsDbPluginPostgresConfig = serde_json::from_str("{}").unwrap();
+        let static_columns = StaticColumns::new(&config);
+        let computed_columns = ComputedColumns::new(&config);
+        let data_json_column = DataJsonColumn::new(&config);
+        let rows = SimplePostgresClient::account_values_row(
+            9,
+            &static_columns,
+            &computed_columns,
+            &data_json_column,
+        );
+        assert!(rows.starts_with("($10::BYTEA, $11::BIGINT"));
+
+        let stmt = SimplePostgresClient::account_upsert_sql(
+            &rows,
+            &static_columns,
+            &computed_columns,
+            &data_json_column,
+            &config,
+        );
+        assert!(stmt.starts_with("INSERT INTO account AS acct"));
+        assert!(stmt.ends_with("RETURNING (acct.xmax = 0) AS inserted"));
+        assert!(!stmt.contains("owner_change"));
+
+        config.use_schema_triggers = Some(false);
+        let stmt = SimplePostgresClient::account_upsert_sql(
+            &rows,
+            &static_columns,
+            &computed_columns,
+            &data_json_column,
+            &config,
+        );
+        assert!(stmt.starts_with("WITH new_account"));
+        assert!(stmt.contains("INSERT INTO owner_change"));
+        assert!(stmt.ends_with("SELECT inserted FROM upserted"));
+        assert!(!stmt.contains("INSERT INTO account_audit"));
+
+        // The minimal footprint does not record the owner changes
+        config.footprint = Some(Footprint::Minimal);
+        let stmt = SimplePostgresClient::account_upsert_sql(
+            &rows,
+            &static_columns,
+            &computed_columns,
+            &data_json_column,
+            &config,
+        );
+        assert!(!stmt.contains("owner_change"));
+        assert!(stmt.ends_with("(acct.xmax = 0) AS inserted) SELECT inserted FROM upserted"));
+        config.footprint = None;
+
+        config.store_account_historical_data = Some(true);
+        let stmt = SimplePostgresClient::account_upsert_sql(
+            &rows,
+            &static_columns,
+            &computed_columns,
+            &data_json_column,
+            &config,
+        );
+        assert!(stmt.contains("INSERT INTO account_audit"));
+        assert!(stmt.contains("old.data_codec, old.txn_signature FROM old_account"));
+
+        config.computed_columns = Some(
+            [(
+                "discriminator".to_string(),
+                "substring({data} from 1 for 8)".to_string(),
+            )]
+            .into(),
+        );
+        let computed_columns = ComputedColumns::new(&config);
+        let rows = SimplePostgresClient::account_values_row(
+            0,
+            &static_columns,
+            &computed_columns,
+            &data_json_column,
+        );
+        assert!(rows.ends_with("$10::SMALLINT, $11::BYTEA, (substring($7::BYTEA from 1 for 8)))"));
+        let stmt = SimplePostgresClient::account_upsert_sql(
+            &rows,
+            &static_columns,
+            &computed_columns,
+            &data_json_column,
+            &config,
+        );
+        assert!(stmt.contains("data_codec, txn_signature, discriminator) AS (VALUES"));
+        assert!(stmt.contains("discriminator=excluded.discriminator WHERE"));
+
+        config.account_data_decoders = Some(
+            [(
+                "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc".to_string(),
+                AccountDataDecoderConfig {
+                    idl: Some("whirlpool.json".to_string()),
+                    borsh_schema: None,
+                },
+            )]
+            .into(),
+        );
+        let data_json_column = DataJsonColumn::new(&config);
+        let rows = SimplePostgresClient::account_values_row(
+            ACCOUNT_COLUMN_COUNT + 1,
+            &static_columns,
+            &computed_columns,
+            &data_json_column,
+        );
+        assert!(rows.starts_with("($13::BYTEA"));
+        assert!(rows.ends_with("(substring($19::BYTEA from 1 for 8)), $24::JSONB)"));
+        let stmt = SimplePostgresClient::account_upsert_sql(
+            &rows,
+            &static_columns,
+            &computed_columns,
+            &data_json_column,
+            &config,
+        );
+        assert!(stmt.contains("data_codec, txn_signature, discriminator, data_json) AS (VALUES"));
+        assert!(stmt.contains("data_json=excluded.data_json WHERE"));
+        assert!(stmt.contains("data=excluded.data, data_codec=excluded.data_codec, write_version"));
+        assert!(!stmt.contains("account_cold"));
+
+        // The accounts of the account_cold table updated again are moved back
+        config.account_cold_tier_slots = Some(432000);
+        let stmt = SimplePostgresClient::account_upsert_sql(
+            &rows,
+            &static_columns,
+            &computed_columns,
+            &data_json_column,
+            &config,
+        );
+        assert!(stmt.contains(
+            "USING (pubkey) UNION ALL SELECT cold.* FROM account_cold cold JOIN new_account USING (pubkey)"
+        ));
+        assert!(stmt.contains(
+            "cold_promoted AS (DELETE FROM account_cold cold USING upserted WHERE cold.pubkey = upserted.pubkey), audit AS"
+        ));
+        config.account_cold_tier_slots = None;
+
+        // The data of the split layout is upserted into the account_data table
+        config.account_layout = Some(AccountLayout::Split);
+        let stmt = SimplePostgresClient::account_upsert_sql(
+            &rows,
+            &static_columns,
+            &computed_columns,
+            &data_json_column,
+            &config,
+        );
+        assert!(stmt.contains(
+            "WITH new_account (pubkey, slot, owner, lamports, executable, rent_epoch, data, write_version"
+        ));
+        assert!(stmt.contains(
+            "INSERT INTO account AS acct (pubkey, slot, owner, lamports, executable, rent_epoch, write_version"
+        ));
+        assert!(stmt.contains("rent_epoch=excluded.rent_epoch, write_version"));
+        assert!(stmt.contains("INSERT INTO account_data (pubkey, data"));
+        assert!(stmt.contains("LEFT JOIN account_data d USING (pubkey)"));
+        assert!(stmt.contains("INSERT INTO account_audit"));
+    }
+}
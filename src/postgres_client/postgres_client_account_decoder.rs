@@ -0,0 +1,594 @@
+/// Module responsible for decoding the data of the accounts of the owners configured
+/// into the data_json column of the account table, with the layouts of the Anchor IDL
+/// or the borsh schema files of the config, keyed by the owner. The raw data is still
+/// stored in the data column.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{AccountDataDecoderConfig, AccountsDbPluginPostgresConfig},
+        postgres_client::ACCOUNT_COLUMN_COUNT,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    serde_json::{json, Map, Value},
+    solana_metrics::*,
+    solana_sdk::hash::hashv,
+    std::{collections::HashMap, fs},
+};
+
+/// The column of the decoded data of the accounts
+pub(crate) const DATA_JSON_COLUMN: &str = "data_json";
+/// The length of the discriminators of the Anchor accounts
+const ANCHOR_DISCRIMINATOR_LEN: usize = 8;
+/// The nesting of the defined types decoded at most, bounding the recursive types
+const MAX_LAYOUT_DEPTH: usize = 64;
+
+/// The layout of a value in the type format of the IDL
+#[derive(Clone, Debug, PartialEq)]
+enum Layout {
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    F32,
+    F64,
+    String,
+    Bytes,
+    Pubkey,
+    Option(Box<Layout>),
+    /// The option with a 4-byte tag, as of the native programs
+    COption(Box<Layout>),
+    Vec(Box<Layout>),
+    Array(Box<Layout>, usize),
+    Defined(String),
+}
+
+/// The fields of a struct or an enum variant
+#[derive(Clone, Debug, PartialEq)]
+enum Fields {
+    Named(Vec<(String, Layout)>),
+    Tuple(Vec<Layout>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TypeDef {
+    Struct(Fields),
+    /// The variants, those without fields decoded as their names
+    Enum(Vec<(String, Option<Fields>)>),
+    Alias(Layout),
+}
+
+/// An account type of the program
+#[derive(Debug)]
+struct AccountLayout {
+    name: String,
+    /// The bytes the data of the accounts of the type starts with, skipped when decoded
+    discriminator: Vec<u8>,
+    type_def: TypeDef,
+}
+
+fn name_of(value: &Value) -> Result<&str, String> {
+    value["name"]
+        .as_str()
+        .ok_or_else(|| format!("missing the name of {}", value))
+}
+
+fn parse_layout(value: &Value) -> Result<Layout, String> {
+    if let Some(name) = value.as_str() {
+        return Ok(match name {
+            "bool" => Layout::Bool,
+            "u8" => Layout::U8,
+            "u16" => Layout::U16,
+            "u32" => Layout::U32,
+            "u64" => Layout::U64,
+            "u128" => Layout::U128,
+            "i8" => Layout::I8,
+            "i16" => Layout::I16,
+            "i32" => Layout::I32,
+            "i64" => Layout::I64,
+            "i128" => Layout::I128,
+            "f32" => Layout::F32,
+            "f64" => Layout::F64,
+            "string" => Layout::String,
+            "bytes" => Layout::Bytes,
+            "publicKey" | "pubkey" => Layout::Pubkey,
+            _ => return Err(format!("unsupported type {:?}", name)),
+        });
+    }
+    let Some((kind, inner)) = value
+        .as_object()
+        .filter(|object| object.len() == 1)
+        .and_then(|object| object.iter().next())
+    else {
+        return Err(format!("unsupported type {}", value));
+    };
+    match kind.as_str() {
+        "option" => Ok(Layout::Option(Box::new(parse_layout(inner)?))),
+        "coption" => Ok(Layout::COption(Box::new(parse_layout(inner)?))),
+        "vec" => Ok(Layout::Vec(Box::new(parse_layout(inner)?))),
+        "array" => match inner.as_array().map(Vec::as_slice) {
+            Some([layout, len]) => {
+                let len = len
+                    .as_u64()
+                    .ok_or_else(|| format!("unsupported array length {}", len))?;
+                Ok(Layout::Array(Box::new(parse_layout(layout)?), len as usize))
+            }
+            _ => Err(format!("unsupported type {}", value)),
+        },
+        // The legacy IDL names the type, the newer ones name it in an object
+        "defined" => inner
+            .as_str()
+            .or_else(|| inner["name"].as_str())
+            .map(|name| Layout::Defined(name.to_string()))
+            .ok_or_else(|| format!("unsupported type {}", value)),
+        _ => Err(format!("unsupported type {}", value)),
+    }
+}
+
+fn parse_fields(value: &Value) -> Result<Fields, String> {
+    let fields = value
+        .as_array()
+        .ok_or_else(|| format!("unsupported fields {}", value))?;
+    if fields.iter().all(|field| field.get("type").is_some()) {
+        let fields = fields
+            .iter()
+            .map(|field| Ok((name_of(field)?.to_string(), parse_layout(&field["type"])?)))
+            .collect::<Result<_, String>>()?;
+        return Ok(Fields::Named(fields));
+    }
+    let fields = fields.iter().map(parse_layout).collect::<Result<_, _>>()?;
+    Ok(Fields::Tuple(fields))
+}
+
+fn parse_type_def(value: &Value) -> Result<TypeDef, String> {
+    match value["kind"].as_str() {
+        Some("struct") => match value.get("fields") {
+            Some(fields) => Ok(TypeDef::Struct(parse_fields(fields)?)),
+            None => Ok(TypeDef::Struct(Fields::Named(vec![]))),
+        },
+        Some("enum") => {
+            let variants = value["variants"]
+                .as_array()
+                .ok_or_else(|| format!("missing the variants of {}", value))?
+                .iter()
+                .map(|variant| {
+                    let fields = variant.get("fields").map(parse_fields).transpose()?;
+                    Ok((name_of(variant)?.to_string(), fields))
+                })
+                .collect::<Result<_, String>>()?;
+            Ok(TypeDef::Enum(variants))
+        }
+        Some("type") => Ok(TypeDef::Alias(parse_layout(&value["alias"])?)),
+        _ => Err(format!("unsupported type definition {}", value)),
+    }
+}
+
+/// Take the next `len` bytes of the data
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], String> {
+    if data.len() < len {
+        return Err(format!("the data ends {} bytes short", len - data.len()));
+    }
+    let (taken, rest) = data.split_at(len);
+    *data = rest;
+    Ok(taken)
+}
+
+fn take_array<const N: usize>(data: &mut &[u8]) -> Result<[u8; N], String> {
+    Ok(take(data, N)?.try_into().unwrap())
+}
+
+/// The layouts of the account types of a program, along with the types they refer to
+#[derive(Debug, Default)]
+pub(crate) struct AccountDecoder {
+    accounts: Vec<AccountLayout>,
+    types: HashMap<String, TypeDef>,
+}
+
+impl AccountDecoder {
+    /// Parse the accounts and the types of the descriptor. The accounts of an Anchor
+    /// IDL without an explicit discriminator, as of the legacy IDL, start with the
+    /// discriminator Anchor derives from their names. Those of a borsh schema file
+    /// without a discriminator are decoded from the first byte.
+    fn parse(descriptor: &Value, is_anchor: bool) -> Result<Self, String> {
+        let mut decoder = AccountDecoder::default();
+        for type_def in descriptor["types"].as_array().into_iter().flatten() {
+            decoder.types.insert(
+                name_of(type_def)?.to_string(),
+                parse_type_def(&type_def["type"])?,
+            );
+        }
+        for account in descriptor["accounts"].as_array().into_iter().flatten() {
+            let name = name_of(account)?.to_string();
+            // The newer IDL defines the types of the accounts along with the other types
+            let type_def = match account.get("type") {
+                Some(type_def) => parse_type_def(type_def)?,
+                None if decoder.types.contains_key(&name) => {
+                    TypeDef::Alias(Layout::Defined(name.clone()))
+                }
+                None => return Err(format!("missing the type of the account {:?}", name)),
+            };
+            decoder
+                .types
+                .entry(name.clone())
+                .or_insert_with(|| type_def.clone());
+            let discriminator = match account.get("discriminator") {
+                Some(discriminator) => serde_json::from_value(discriminator.clone())
+                    .map_err(|err| format!("invalid discriminator of {:?}: {}", name, err))?,
+                None if is_anchor => hashv(&[b"account:", name.as_bytes()]).to_bytes()
+                    [..ANCHOR_DISCRIMINATOR_LEN]
+                    .to_vec(),
+                None => vec![],
+            };
+            decoder.accounts.push(AccountLayout {
+                name,
+                discriminator,
+                type_def,
+            });
+        }
+        if decoder.accounts.is_empty() {
+            return Err("no accounts are described".to_string());
+        }
+        Ok(decoder)
+    }
+
+    /// Decode the data with the first account type it starts with the discriminator of
+    /// and decodes as, into the name of the type along with the decoded fields, e.g.
+    /// {"account": "Whirlpool", "data": {"liquidity": "1000", ...}}. The bytes past the
+    /// layout, such as the space reserved by the program, are ignored.
+    pub(crate) fn decode(&self, data: &[u8]) -> Option<Value> {
+        self.accounts
+            .iter()
+            .filter(|account| data.starts_with(&account.discriminator))
+            .find_map(|account| {
+                let mut data = &data[account.discriminator.len()..];
+                let decoded = self.decode_type_def(&account.type_def, &mut data, 0).ok()?;
+                Some(json!({"account": account.name, "data": decoded}))
+            })
+    }
+
+    fn decode_type_def(
+        &self,
+        type_def: &TypeDef,
+        data: &mut &[u8],
+        depth: usize,
+    ) -> Result<Value, String> {
+        match type_def {
+            TypeDef::Struct(fields) => self.decode_fields(fields, data, depth),
+            TypeDef::Enum(variants) => {
+                let index = take_array::<1>(data)?[0];
+                let (name, fields) = variants
+                    .get(index as usize)
+                    .ok_or_else(|| format!("unknown variant {}", index))?;
+                match fields {
+                    None => Ok(Value::String(name.clone())),
+                    Some(fields) => Ok(json!({ name: self.decode_fields(fields, data, depth)? })),
+                }
+            }
+            TypeDef::Alias(layout) => self.decode_layout(layout, data, depth),
+        }
+    }
+
+    fn decode_fields(
+        &self,
+        fields: &Fields,
+        data: &mut &[u8],
+        depth: usize,
+    ) -> Result<Value, String> {
+        match fields {
+            Fields::Named(fields) => {
+                let mut decoded = Map::new();
+                for (name, layout) in fields {
+                    decoded.insert(name.clone(), self.decode_layout(layout, data, depth)?);
+                }
+                Ok(Value::Object(decoded))
+            }
+            Fields::Tuple(layouts) => layouts
+                .iter()
+                .map(|layout| self.decode_layout(layout, data, depth))
+                .collect(),
+        }
+    }
+
+    /// Decode a value of the layout, the 64 and 128-bit integers into strings as they
+    /// exceed the precision of the JSON numbers.
+    fn decode_layout(
+        &self,
+        layout: &Layout,
+        data: &mut &[u8],
+        depth: usize,
+    ) -> Result<Value, String> {
+        let value = match layout {
+            Layout::Bool => match take_array::<1>(data)?[0] {
+                0 => json!(false),
+                1 => json!(true),
+                byte => return Err(format!("invalid bool {}", byte)),
+            },
+            Layout::U8 => json!(take_array::<1>(data)?[0]),
+            Layout::U16 => json!(u16::from_le_bytes(take_array(data)?)),
+            Layout::U32 => json!(u32::from_le_bytes(take_array(data)?)),
+            Layout::U64 => json!(u64::from_le_bytes(take_array(data)?).to_string()),
+            Layout::U128 => json!(u128::from_le_bytes(take_array(data)?).to_string()),
+            Layout::I8 => json!(i8::from_le_bytes(take_array(data)?)),
+            Layout::I16 => json!(i16::from_le_bytes(take_array(data)?)),
+            Layout::I32 => json!(i32::from_le_bytes(take_array(data)?)),
+            Layout::I64 => json!(i64::from_le_bytes(take_array(data)?).to_string()),
+            Layout::I128 => json!(i128::from_le_bytes(take_array(data)?).to_string()),
+            Layout::F32 => json!(f32::from_le_bytes(take_array(data)?)),
+            Layout::F64 => json!(f64::from_le_bytes(take_array(data)?)),
+            Layout::String => {
+                let len = u32::from_le_bytes(take_array(data)?) as usize;
+                let string = std::str::from_utf8(take(data, len)?)
+                    .map_err(|err| format!("invalid string: {}", err))?;
+                json!(string)
+            }
+            Layout::Bytes => {
+                let len = u32::from_le_bytes(take_array(data)?) as usize;
+                json!(take(data, len)?)
+            }
+            Layout::Pubkey => json!(bs58::encode(take(data, 32)?).into_string()),
+            Layout::Option(layout) => match take_array::<1>(data)?[0] {
+                0 => Value::Null,
+                1 => self.decode_layout(layout, data, depth)?,
+                tag => return Err(format!("invalid option tag {}", tag)),
+            },
+            Layout::COption(layout) => match u32::from_le_bytes(take_array(data)?) {
+                0 => Value::Null,
+                1 => self.decode_layout(layout, data, depth)?,
+                tag => return Err(format!("invalid option tag {}", tag)),
+            },
+            Layout::Vec(layout) => {
+                let len = u32::from_le_bytes(take_array(data)?) as usize;
+                // Bounds the elements of a corrupted length, each taking a byte at least
+                if len > data.len() {
+                    return Err(format!("invalid vec length {}", len));
+                }
+                (0..len)
+                    .map(|_| self.decode_layout(layout, data, depth))
+                    .collect::<Result<_, _>>()?
+            }
+            Layout::Array(layout, len) => (0..*len)
+                .map(|_| self.decode_layout(layout, data, depth))
+                .collect::<Result<_, _>>()?,
+            Layout::Defined(name) => {
+                if depth >= MAX_LAYOUT_DEPTH {
+                    return Err(format!("the type {:?} nests too deep", name));
+                }
+                let type_def = self
+                    .types
+                    .get(name)
+                    .ok_or_else(|| format!("undefined type {:?}", name))?;
+                self.decode_type_def(type_def, data, depth + 1)?
+            }
+        };
+        Ok(value)
+    }
+}
+
+/// The decoders of the account data, keyed by the owner
+#[derive(Debug, Default)]
+pub(crate) struct AccountDecoders {
+    /// Indicates if the data_json column is written
+    enabled: bool,
+    decoders: HashMap<Vec<u8>, AccountDecoder>,
+}
+
+impl AccountDecoders {
+    /// Load the layout descriptors of the config, failing on the descriptors which
+    /// cannot be read or parsed.
+    pub(crate) fn new(config: &AccountsDbPluginPostgresConfig) -> Result<Self, GeyserPluginError> {
+        let mut decoders = HashMap::default();
+        for (owner, decoder_config) in config.account_data_decoders.iter().flatten() {
+            // Either of the paths is checked to be set when the config is loaded
+            let (path, is_anchor) = match decoder_config {
+                AccountDataDecoderConfig {
+                    idl: Some(path), ..
+                } => (path, true),
+                AccountDataDecoderConfig {
+                    borsh_schema: Some(path),
+                    ..
+                } => (path, false),
+                _ => continue,
+            };
+            let decoder = fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|descriptor| {
+                    serde_json::from_str(&descriptor).map_err(|err| err.to_string())
+                })
+                .and_then(|descriptor| AccountDecoder::parse(&descriptor, is_anchor))
+                .map_err(|err| GeyserPluginError::ConfigFileReadError {
+                    msg: format!(
+                        "Failed to load the layout descriptor {:?} of the owner {}: {}",
+                        path, owner, err
+                    ),
+                })?;
+            // The owners are checked to be base58 encoded when the config is loaded
+            if let Ok(owner) = bs58::decode(owner).into_vec() {
+                decoders.insert(owner, decoder);
+            }
+        }
+        Ok(Self {
+            enabled: config.account_data_decoders.is_some(),
+            decoders,
+        })
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Decode the data of an account of the owner, None when the owner has no decoder
+    /// or the data does not decode.
+    pub(crate) fn decode(&self, owner: &[u8], data: &[u8]) -> Option<Value> {
+        let decoder = self.decoders.get(owner)?;
+        let decoded = decoder.decode(data);
+        if decoded.is_none() {
+            debug!(
+                "The data of an account of the owner {} does not decode",
+                bs58::encode(owner).into_string()
+            );
+            inc_new_counter_debug!("accountsdb-plugin-postgres-account-decode-failures", 1);
+        }
+        decoded
+    }
+}
+
+/// The data_json column appended to the upserts of the account table, when the
+/// decoders are configured, with its value as the parameter following the account
+/// columns of a row.
+pub(crate) struct DataJsonColumn {
+    enabled: bool,
+}
+
+impl DataJsonColumn {
+    pub(crate) fn new(config: &AccountsDbPluginPostgresConfig) -> Self {
+        Self {
+            enabled: config.account_data_decoders.is_some(),
+        }
+    }
+
+    /// The parameters the column adds to an account row
+    pub(crate) fn param_count(&self) -> usize {
+        usize::from(self.enabled)
+    }
+
+    /// The column name, e.g. ", data_json"
+    pub(crate) fn names(&self) -> String {
+        if !self.enabled {
+            return String::default();
+        }
+        format!(", {}", DATA_JSON_COLUMN)
+    }
+
+    /// The parameter of the account row of the parameters after `row`, e.g. ", $10::JSONB"
+    pub(crate) fn values(&self, row: usize) -> String {
+        if !self.enabled {
+            return String::default();
+        }
+        format!(", ${}::JSONB", row + ACCOUNT_COLUMN_COUNT + 1)
+    }
+
+    /// The assignment of the conflict handling, e.g. ", data_json=excluded.data_json"
+    pub(crate) fn updates(&self) -> String {
+        if !self.enabled {
+            return String::default();
+        }
+        format!(", {}=excluded.{}", DATA_JSON_COLUMN, DATA_JSON_COLUMN)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_anchor_account() {
+        let idl = json!({
+            "version": "0.1.0",
+            "name": "pool",
+            "accounts": [{
+                "name": "Pool",
+                "type": {"kind": "struct", "fields": [
+                    {"name": "authority", "type": "publicKey"},
+                    {"name": "liquidity", "type": "u128"},
+                    {"name": "fee", "type": {"option": "u16"}},
+                    {"name": "status", "type": {"defined": "Status"}},
+                    {"name": "tag", "type": "string"},
+                    {"name": "ticks", "type": {"vec": {"array": ["i8", 2]}}}
+                ]}
+            }],
+            "types": [{
+                "name": "Status",
+                "type": {"kind": "enum", "variants": [
+                    {"name": "Open"},
+                    {"name": "Closed", "fields": [{"name": "slot", "type": "u64"}]}
+                ]}
+            }]
+        });
+        let decoder = AccountDecoder::parse(&idl, true).unwrap();
+
+        let mut data = hashv(&[b"account:Pool"]).to_bytes()[..8].to_vec();
+        data.extend_from_slice(&[1; 32]);
+        data.extend_from_slice(&1000u128.to_le_bytes());
+        data.extend_from_slice(&[0]);
+        data.extend_from_slice(&[1]);
+        data.extend_from_slice(&7u64.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(b"ab");
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&[1, 255]);
+        // The space reserved past the layout
+        data.extend_from_slice(&[0; 16]);
+        assert_eq!(
+            decoder.decode(&data),
+            Some(json!({"account": "Pool", "data": {
+                "authority": bs58::encode([1; 32]).into_string(),
+                "liquidity": "1000",
+                "fee": null,
+                "status": {"Closed": {"slot": "7"}},
+                "tag": "ab",
+                "ticks": [[1, -1]]
+            }}))
+        );
+
+        // The data of another type, and the data too short for the layout
+        assert_eq!(decoder.decode(&[0; 64]), None);
+        assert_eq!(decoder.decode(&data[..40]), None);
+    }
+
+    #[test]
+    fn test_decode_borsh_schema_account() {
+        // The newer IDL format, the account defined along with the other types
+        let schema = json!({
+            "accounts": [{"name": "Counter", "discriminator": [9]}],
+            "types": [{
+                "name": "Counter",
+                "type": {"kind": "struct", "fields": ["u32", "bool"]}
+            }]
+        });
+        let decoder = AccountDecoder::parse(&schema, false).unwrap();
+        assert_eq!(
+            decoder.decode(&[9, 5, 0, 0, 0, 1]),
+            Some(json!({"account": "Counter", "data": [5, true]}))
+        );
+        assert_eq!(decoder.decode(&[9, 5, 0, 0, 0, 2]), None);
+
+        let schema = json!({
+            "accounts": [{"name": "Config", "type": {"kind": "struct", "fields": [
+                {"name": "admin", "type": {"coption": "pubkey"}}
+            ]}}]
+        });
+        let decoder = AccountDecoder::parse(&schema, false).unwrap();
+        assert_eq!(
+            decoder.decode(&[0; 4]),
+            Some(json!({"account": "Config", "data": {"admin": null}}))
+        );
+
+        assert!(AccountDecoder::parse(&json!({"accounts": []}), false).is_err());
+        assert!(AccountDecoder::parse(&json!({"accounts": [{"name": "Missing"}]}), false).is_err());
+    }
+
+    #[test]
+    fn test_data_json_column() {
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str("{}").unwrap();
+        let column = DataJsonColumn::new(&config);
+        assert_eq!(column.param_count(), 0);
+        assert!(column.names().is_empty());
+        assert!(column.values(0).is_empty());
+
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str(
+            "{\"account_data_decoders\": {\"whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc\": {\"idl\": \"whirlpool.json\"}}}",
+        )
+        .unwrap();
+        let column = DataJsonColumn::new(&config);
+        assert_eq!(column.param_count(), 1);
+        assert_eq!(column.names(), ", data_json");
+        assert_eq!(column.values(10), ", $22::JSONB");
+        assert_eq!(column.updates(), ", data_json=excluded.data_json");
+    }
+}
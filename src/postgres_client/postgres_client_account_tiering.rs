@@ -0,0 +1,171 @@
+/// Module responsible for the tiering of the account rows by their age. The accounts not
+/// updated in the account_cold_tier_slots are moved from the account table into the
+/// account_cold table from a background thread, optionally in another tablespace, so that
+/// the frequently updated accounts stay in a small table whose pages stay cached. A cold
+/// account updated again is moved back into the account table by the upsert, and the
+/// account_tiered view unions both tables.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::SimplePostgresClient,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    postgres::Client,
+    std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread::{sleep, Builder, JoinHandle},
+        time::{Duration, Instant},
+    },
+};
+
+const ACCOUNT_TIERING_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+const ACCOUNT_TIERING_INTERVAL: Duration = Duration::from_secs(60);
+/// The max number of the accounts moved into the account_cold table per statement
+const ACCOUNT_TIERING_BATCH_SIZE: i64 = 10_000;
+
+/// The statements creating the account_cold table like the account table, in the
+/// tablespace when configured, and the account_tiered view over both tables. The stale
+/// copy of an account updated again while being moved is left out of the view until the
+/// next sweep deletes it.
+fn tiering_schema_sql(tablespace: Option<&str>) -> String {
+    let mut sql =
+        "CREATE TABLE IF NOT EXISTS account_cold (LIKE account INCLUDING ALL);".to_string();
+    if let Some(tablespace) = tablespace {
+        sql.push_str(&format!(
+            " ALTER TABLE account_cold SET TABLESPACE {};",
+            tablespace
+        ));
+    }
+    sql.push_str(
+        " CREATE OR REPLACE VIEW account_tiered AS SELECT * FROM account UNION ALL \
+        SELECT * FROM account_cold c WHERE NOT EXISTS (SELECT 1 FROM account a WHERE a.pubkey = c.pubkey);",
+    );
+    sql
+}
+
+fn to_tiering_error(action: &str, err: postgres::Error) -> GeyserPluginError {
+    let msg = format!(
+        "Failed to {} of the account tiering in the PostgreSQL database. Error: {:?}",
+        action, err
+    );
+    error!("{}", msg);
+    GeyserPluginError::Custom(Box::new(
+        AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+    ))
+}
+
+pub(crate) struct AccountTiering {
+    client: Client,
+    cold_tier_slots: i64,
+}
+
+impl AccountTiering {
+    /// Connect and create the account_cold table and the account_tiered view when the
+    /// tiering is configured.
+    pub(crate) fn new(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Option<Self>, GeyserPluginError> {
+        let Some(cold_tier_slots) = config.account_cold_tier_slots else {
+            return Ok(None);
+        };
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+        client
+            .batch_execute(&tiering_schema_sql(
+                config.account_cold_tier_tablespace.as_deref(),
+            ))
+            .map_err(|err| to_tiering_error("create the account_cold table", err))?;
+        Ok(Some(Self {
+            client,
+            cold_tier_slots: cold_tier_slots as i64,
+        }))
+    }
+
+    /// Delete the copies of the accounts updated again from the account_cold table, and
+    /// move the accounts not updated in the cold tier slots into it, in batches.
+    fn sweep(&mut self) -> Result<(), GeyserPluginError> {
+        let row = self
+            .client
+            .query_one("SELECT MAX(slot) FROM slot", &[])
+            .map_err(|err| to_tiering_error("query the current slot", err))?;
+        let Some(slot) = row.get::<_, Option<i64>>(0) else {
+            return Ok(());
+        };
+        // The account moved while being updated is inserted into the account table again
+        self.client
+            .execute(
+                "DELETE FROM account_cold c USING account a WHERE c.pubkey = a.pubkey",
+                &[],
+            )
+            .map_err(|err| to_tiering_error("delete the accounts updated again", err))?;
+
+        let cutoff = slot - self.cold_tier_slots;
+        let mut moved_count = 0;
+        loop {
+            let moved = self
+                .client
+                .execute(
+                    "WITH moved AS (DELETE FROM account WHERE pubkey IN \
+                    (SELECT pubkey FROM account WHERE slot < $1 LIMIT $2) RETURNING *) \
+                    INSERT INTO account_cold SELECT * FROM moved",
+                    &[&cutoff, &ACCOUNT_TIERING_BATCH_SIZE],
+                )
+                .map_err(|err| to_tiering_error("move the cold accounts", err))?;
+            moved_count += moved;
+            if moved < ACCOUNT_TIERING_BATCH_SIZE as u64 {
+                break;
+            }
+        }
+        if moved_count > 0 {
+            info!(
+                "Moved {} accounts not updated since the slot {} into the account_cold table",
+                moved_count, cutoff
+            );
+        }
+        Ok(())
+    }
+
+    /// Sweep the accounts at the interval until the exit.
+    pub(crate) fn spawn(
+        mut self,
+        exit_worker: Arc<AtomicBool>,
+    ) -> JoinHandle<Result<(), GeyserPluginError>> {
+        Builder::new()
+            .name("account-tiering".to_string())
+            .spawn(move || -> Result<(), GeyserPluginError> {
+                let mut last_swept = Instant::now();
+                while !exit_worker.load(Ordering::Relaxed) {
+                    sleep(ACCOUNT_TIERING_CHECK_INTERVAL);
+                    if last_swept.elapsed() >= ACCOUNT_TIERING_INTERVAL {
+                        // The errors are logged, the sweep is attempted again at the
+                        // next interval.
+                        let _ = self.sweep();
+                        last_swept = Instant::now();
+                    }
+                }
+                Ok(())
+            })
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tiering_schema_sql() {
+        let sql = tiering_schema_sql(None);
+        assert!(sql.starts_with("CREATE TABLE IF NOT EXISTS account_cold (LIKE account"));
+        assert!(!sql.contains("TABLESPACE"));
+        assert!(sql.contains("CREATE OR REPLACE VIEW account_tiered"));
+
+        let sql = tiering_schema_sql(Some("cold_disk"));
+        assert!(sql.contains(" ALTER TABLE account_cold SET TABLESPACE cold_disk;"));
+    }
+}
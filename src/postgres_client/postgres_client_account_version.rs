@@ -0,0 +1,140 @@
+/// Module responsible for keeping every version of the accounts selected by the
+/// account_versions_selector in the account_version table, keyed by the pubkey, the slot
+/// and the write version, so that the state of the accounts can be reconstructed at any
+/// slot, including the multiple writes to an account within a slot, while the account
+/// table only keeps the latest version.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::{
+            data_codec, postgres_client_dedup_window::DedupWindow, DbAccountInfo,
+            SimplePostgresClient,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    postgres::{Client, Statement},
+    postgres_types::ToSql,
+};
+
+/// The statement inserting an account version. The versions already stored are kept
+/// as they are, and in the dedup window the writes to an account at a slot already
+/// stored are ignored, their write versions being assigned again by the replay after a
+/// restart.
+fn account_version_insert_sql(config: &AccountsDbPluginPostgresConfig) -> String {
+    let dedup_condition = DedupWindow::outside_condition(config, "$2::BIGINT")
+        .map(|condition| {
+            format!(
+                " WHERE {} OR NOT EXISTS (SELECT 1 FROM account_version v WHERE v.pubkey = $1 AND v.slot = $2)",
+                condition
+            )
+        })
+        .unwrap_or_default();
+    format!(
+        "INSERT INTO account_version (pubkey, slot, write_version, owner, lamports, executable, \
+        rent_epoch, data, data_codec, updated_on) \
+        SELECT $1::BYTEA, $2::BIGINT, $3::BIGINT, $4::BYTEA, $5::BIGINT, $6::BOOL, $7::BIGINT, \
+        $8::BYTEA, $9::SMALLINT, $10::TIMESTAMP{} \
+        ON CONFLICT (pubkey, slot, write_version) DO NOTHING",
+        dedup_condition
+    )
+}
+
+impl SimplePostgresClient {
+    /// Build the statement inserting an account version into the account_version table.
+    pub fn build_account_version_insert_statement(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Statement, GeyserPluginError> {
+        match client.prepare(&account_version_insert_sql(config)) {
+            Err(err) => {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "Error in preparing for the account_version update PostgreSQL database: {} host: {:?} user: {:?} config: {:?}",
+                        err, config.host, config.user, config
+                    ),
+                })))
+            }
+            Ok(stmt) => Ok(stmt),
+        }
+    }
+
+    /// Write the version of the account into the account_version table when it is
+    /// selected by the account_versions_selector.
+    pub(crate) fn write_account_version(
+        &mut self,
+        account: &DbAccountInfo,
+    ) -> Result<(), GeyserPluginError> {
+        let is_selected = self
+            .account_versions_selector
+            .as_ref()
+            .is_some_and(|selector| {
+                selector.is_account_selected_with_lamports(
+                    &account.pubkey,
+                    &account.owner,
+                    account.lamports as u64,
+                    &account.data,
+                )
+            });
+        if !is_selected {
+            return Ok(());
+        }
+        let compressed_data = self.data_compression.compress(&account.data);
+        let client = self.client.get_mut().unwrap();
+        let Some(statement) = &client.insert_account_version_stmt else {
+            return Ok(());
+        };
+        let updated_on = Utc::now().naive_utc();
+        let data_codec = data_codec(&compressed_data);
+        let params: [&(dyn ToSql + Sync); 10] = [
+            &account.pubkey,
+            &account.slot,
+            &account.write_version,
+            &account.owner,
+            &account.lamports,
+            &account.executable,
+            &account.rent_epoch,
+            compressed_data.as_ref().unwrap_or(&account.data),
+            &data_codec,
+            &updated_on,
+        ];
+        if let Err(err) = client.client.execute(statement, &params) {
+            let msg = format!(
+                "Failed to persist the account version to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::AccountsUpdateError { msg });
+        }
+        client
+            .write_stats
+            .record("account_version", 1, statement, &params);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_version_insert_sql() {
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str("{}").unwrap();
+        let sql = account_version_insert_sql(&config);
+        assert!(sql.starts_with("INSERT INTO account_version (pubkey, slot, write_version,"));
+        assert!(sql.contains("$10::TIMESTAMP ON CONFLICT (pubkey, slot, write_version) DO NOTHING"));
+        assert!(!sql.contains("NOT EXISTS"));
+
+        let mut config = config;
+        config.dedup_window_slots = Some(10);
+        config.dedup_until_slot = Some(100);
+        let sql = account_version_insert_sql(&config);
+        assert!(sql.contains(
+            " WHERE $2::BIGINT NOT BETWEEN 91 AND 100 OR NOT EXISTS (SELECT 1 FROM account_version v \
+            WHERE v.pubkey = $1 AND v.slot = $2) ON CONFLICT"
+        ));
+    }
+}
@@ -0,0 +1,119 @@
+/// Module responsible for maintaining the address_tag table from the address tags of
+/// the config, so that the addresses such as of the exchanges and the bridges can be
+/// flagged in the tables written by the plugin.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::SimplePostgresClient,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    solana_sdk::pubkey::Pubkey,
+    std::{collections::BTreeMap, str::FromStr},
+};
+
+/// Flatten the address tags into the rows of the address_tag table, a row per address
+/// and tag.
+fn build_address_tags(
+    address_tags: &BTreeMap<String, Vec<String>>,
+) -> Result<Vec<(Vec<u8>, &str)>, GeyserPluginError> {
+    let mut rows = Vec::default();
+    for (address, tags) in address_tags {
+        let address = Pubkey::from_str(address).map_err(|err| {
+            GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::ConfigurationError {
+                    msg: format!(
+                        "The address tags of {:?} are not of a valid pubkey. Error: ({})",
+                        address, err
+                    ),
+                },
+            ))
+        })?;
+        for tag in tags {
+            rows.push((address.to_bytes().to_vec(), tag.as_str()));
+        }
+    }
+    Ok(rows)
+}
+
+impl SimplePostgresClient {
+    /// Replace the rows of the address_tag table with the address tags of the config
+    /// when they are configured, at every load of the plugin including the reloads.
+    pub(crate) fn refresh_address_tags(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        let Some(address_tags) = &config.address_tags else {
+            return Ok(());
+        };
+        let rows = build_address_tags(address_tags)?;
+        let addresses: Vec<&Vec<u8>> = rows.iter().map(|(address, _)| address).collect();
+        let tags: Vec<&str> = rows.iter().map(|(_, tag)| *tag).collect();
+        let updated_on = Utc::now().naive_utc();
+
+        let mut client = Self::connect_to_db(config)?;
+        let result = client.transaction().and_then(|mut transaction| {
+            transaction.execute(
+                "DELETE FROM address_tag WHERE (address, tag) NOT IN \
+                (SELECT * FROM UNNEST($1::BYTEA[], $2::VARCHAR[]))",
+                &[&addresses, &tags],
+            )?;
+            transaction.execute(
+                "INSERT INTO address_tag (address, tag, updated_on) \
+                SELECT t.address, t.tag, $3 FROM UNNEST($1::BYTEA[], $2::VARCHAR[]) AS t (address, tag) \
+                ON CONFLICT DO NOTHING",
+                &[&addresses, &tags, &updated_on],
+            )?;
+            transaction.commit()
+        });
+
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to persist the address tags to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+            )));
+        }
+        info!(
+            "Refreshed {} tags of {} addresses",
+            rows.len(),
+            address_tags.len()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_address_tags() {
+        let exchange = Pubkey::new_unique();
+        let bridge = Pubkey::new_unique();
+        let address_tags = BTreeMap::from([
+            (
+                exchange.to_string(),
+                vec!["exchange".to_string(), "hot_wallet".to_string()],
+            ),
+            (bridge.to_string(), vec!["bridge".to_string()]),
+        ]);
+        let mut rows = build_address_tags(&address_tags).unwrap();
+        rows.sort();
+        let mut expected = vec![
+            (exchange.to_bytes().to_vec(), "exchange"),
+            (exchange.to_bytes().to_vec(), "hot_wallet"),
+            (bridge.to_bytes().to_vec(), "bridge"),
+        ];
+        expected.sort();
+        assert_eq!(rows, expected);
+
+        let address_tags = BTreeMap::from([("exchange".to_string(), vec!["tag".to_string()])]);
+        assert!(build_address_tags(&address_tags).is_err());
+    }
+}
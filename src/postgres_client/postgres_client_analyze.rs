@@ -0,0 +1,214 @@
+/// Module responsible for running ANALYZE on the hot tables from the plugin, as the
+/// autovacuum frequently lags behind the write rate of the plugin and the query plans
+/// degrade with the stale statistics.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::SimplePostgresClient,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    postgres::Client,
+    std::{
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Arc,
+        },
+        thread::{sleep, Builder, JoinHandle},
+        time::{Duration, Instant},
+    },
+};
+
+const ANALYZE_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The hot tables analyzed by the plugin
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum AnalyzeTable {
+    Account,
+    Transaction,
+}
+
+impl AnalyzeTable {
+    const ALL: [AnalyzeTable; 2] = [AnalyzeTable::Account, AnalyzeTable::Transaction];
+
+    fn name(&self) -> &'static str {
+        match self {
+            AnalyzeTable::Account => "account",
+            AnalyzeTable::Transaction => "transaction",
+        }
+    }
+}
+
+/// The rows sent for the hot tables since their last ANALYZE, counted by the client
+/// sending the work items to the workers.
+#[derive(Default)]
+pub(crate) struct AnalyzeRowCounts {
+    counts: [AtomicU64; 2],
+}
+
+impl AnalyzeRowCounts {
+    pub(crate) fn add(&self, table: AnalyzeTable, rows: u64) {
+        self.counts[table as usize].fetch_add(rows, Ordering::Relaxed);
+    }
+
+    fn get(&self, table: AnalyzeTable) -> u64 {
+        self.counts[table as usize].load(Ordering::Relaxed)
+    }
+
+    fn subtract(&self, table: AnalyzeTable, rows: u64) {
+        self.counts[table as usize].fetch_sub(rows, Ordering::Relaxed);
+    }
+}
+
+/// The schedule of the ANALYZE of a table on a cadence or a row count delta, whichever
+/// comes first.
+#[derive(Clone, Copy, Debug)]
+struct AnalyzeSchedule {
+    interval: Option<Duration>,
+    row_count_delta: Option<u64>,
+}
+
+impl AnalyzeSchedule {
+    /// A table is due when rows have been written to it since its last ANALYZE, and
+    /// either the interval has elapsed or the rows reach the delta.
+    fn is_due(&self, since_analyzed: Duration, rows: u64) -> bool {
+        rows > 0
+            && (self
+                .interval
+                .is_some_and(|interval| since_analyzed >= interval)
+                || self.row_count_delta.is_some_and(|delta| rows >= delta))
+    }
+}
+
+pub(crate) struct AnalyzeScheduler {
+    client: Client,
+    schedule: AnalyzeSchedule,
+    row_counts: Arc<AnalyzeRowCounts>,
+    last_analyzed: [Instant; 2],
+}
+
+impl AnalyzeScheduler {
+    /// Connect for the ANALYZE when either the interval or the row count delta is
+    /// configured.
+    pub(crate) fn new(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Option<Self>, GeyserPluginError> {
+        if config.analyze_interval_secs.is_none() && config.analyze_row_count_delta.is_none() {
+            return Ok(None);
+        }
+        let client = SimplePostgresClient::connect_to_db(config)?;
+        Ok(Some(Self {
+            client,
+            schedule: AnalyzeSchedule {
+                interval: config.analyze_interval_secs.map(Duration::from_secs),
+                row_count_delta: config.analyze_row_count_delta,
+            },
+            row_counts: Arc::new(AnalyzeRowCounts::default()),
+            last_analyzed: [Instant::now(); 2],
+        }))
+    }
+
+    pub(crate) fn row_counts(&self) -> Arc<AnalyzeRowCounts> {
+        self.row_counts.clone()
+    }
+
+    /// Analyze the table, the table of a failed ANALYZE is analyzed again once due with
+    /// the rows written after it.
+    fn analyze(&mut self, table: AnalyzeTable, rows: u64) -> Result<(), GeyserPluginError> {
+        let measure = Instant::now();
+        let result = self
+            .client
+            .batch_execute(&format!("ANALYZE {}", table.name()));
+        // The rows sent during the ANALYZE are left for the next one
+        self.row_counts.subtract(table, rows);
+        self.last_analyzed[table as usize] = Instant::now();
+
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to analyze the {} table in the PostgreSQL database. Error: {:?}",
+                table.name(),
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+            )));
+        }
+        info!(
+            "Analyzed the {} table after {} rows written in {:?}",
+            table.name(),
+            rows,
+            measure.elapsed()
+        );
+        Ok(())
+    }
+
+    /// Analyze the hot tables when due until the exit. The tables are not analyzed
+    /// during the startup, the rows written by it are analyzed once it is done.
+    pub(crate) fn spawn(
+        mut self,
+        exit_worker: Arc<AtomicBool>,
+        is_startup_done: Arc<AtomicBool>,
+    ) -> JoinHandle<Result<(), GeyserPluginError>> {
+        Builder::new()
+            .name("analyze-scheduler".to_string())
+            .spawn(move || -> Result<(), GeyserPluginError> {
+                while !exit_worker.load(Ordering::Relaxed) {
+                    sleep(ANALYZE_CHECK_INTERVAL);
+                    if !is_startup_done.load(Ordering::Relaxed) {
+                        continue;
+                    }
+                    for table in AnalyzeTable::ALL {
+                        let rows = self.row_counts.get(table);
+                        let since_analyzed = self.last_analyzed[table as usize].elapsed();
+                        if self.schedule.is_due(since_analyzed, rows) {
+                            // The errors are logged, a failed ANALYZE is not fatal.
+                            let _ = self.analyze(table, rows);
+                        }
+                    }
+                }
+                Ok(())
+            })
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_schedule() {
+        let schedule = AnalyzeSchedule {
+            interval: Some(Duration::from_secs(600)),
+            row_count_delta: Some(1000),
+        };
+        assert!(!schedule.is_due(Duration::from_secs(10), 999));
+        assert!(schedule.is_due(Duration::from_secs(10), 1000));
+        assert!(schedule.is_due(Duration::from_secs(600), 1));
+        // The tables not written to since the last ANALYZE are not analyzed again
+        assert!(!schedule.is_due(Duration::from_secs(600), 0));
+
+        let schedule = AnalyzeSchedule {
+            interval: None,
+            row_count_delta: Some(1000),
+        };
+        assert!(!schedule.is_due(Duration::from_secs(3600), 999));
+
+        let schedule = AnalyzeSchedule {
+            interval: Some(Duration::from_secs(600)),
+            row_count_delta: None,
+        };
+        assert!(!schedule.is_due(Duration::from_secs(10), 1_000_000));
+
+        let row_counts = AnalyzeRowCounts::default();
+        row_counts.add(AnalyzeTable::Account, 5);
+        row_counts.add(AnalyzeTable::Transaction, 3);
+        row_counts.add(AnalyzeTable::Account, 2);
+        row_counts.subtract(AnalyzeTable::Account, 5);
+        assert_eq!(row_counts.get(AnalyzeTable::Account), 2);
+        assert_eq!(row_counts.get(AnalyzeTable::Transaction), 3);
+    }
+}
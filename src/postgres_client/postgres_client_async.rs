@@ -0,0 +1,772 @@
+/// Module responsible for the async client, selected by the "client" 'async', writing
+/// the updates concurrently through a pool of tokio-postgres connections on a tokio
+/// runtime instead of the blocking connections of the workers. A dispatcher thread takes
+/// the work items off the queue and spawns a task writing each through a connection of
+/// the pool, waiting for a free connection when all are writing, which is the
+/// backpressure of the queue. The async client writes the account, slot, transaction
+/// and block rows and the startup report; the other features of the workers are
+/// rejected along with it by the config validation.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError, SignatureStorage,
+        },
+        postgres_client::{
+            postgres_client_account_decoder::AccountDecoders,
+            postgres_client_block_metadata::block_metadata_params,
+            postgres_client_credentials::{forget_password, resolve_password},
+            postgres_client_data_compression::DataCompression,
+            postgres_client_fork_cleanup::CleanUpForksRequest,
+            postgres_client_shutdown_drain::ShutdownDrain,
+            postgres_client_startup_report::{
+                StartupReportRow, INSERT_STARTUP_REPORT, INSERT_STARTUP_REPORT_OWNERS,
+            },
+            postgres_client_tls::build_tls_connector,
+            postgres_client_transaction::TransactionRowValues,
+            AccountRowValues, DbAccountInfo, DbWorkItem, LogTransactionRequest,
+            LogVoteActivityRequest, PostgresClient, SimplePostgresClient, SlotRowValues,
+            StartupStats, UpdateBlockMetadataRequest, UpdateEntryRequest, VerifyBlockChainRequest,
+            WriteStartupReportRequest, DEFAULT_PANIC_ON_DB_ERROR, DEFAULT_STORE_TRANSACTION_JSON,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::{GeyserPluginError, SlotStatus},
+    chrono::Utc,
+    crossbeam_channel::{Receiver, RecvTimeoutError},
+    log::*,
+    postgres_openssl::MakeTlsConnector,
+    std::{
+        fmt,
+        future::Future,
+        process::abort,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        thread::{self, Builder, JoinHandle},
+        time::Duration,
+    },
+    tokio::{
+        runtime::{self, Runtime},
+        sync::{OwnedSemaphorePermit, Semaphore},
+        time::timeout,
+    },
+    tokio_postgres::{CancelToken, Client, NoTls, Statement},
+};
+
+pub(crate) const DEFAULT_ASYNC_POOL_SIZE: usize = 10;
+pub(crate) const DEFAULT_ASYNC_REQUEST_TIMEOUT_MS: u64 = 30_000;
+
+/// The fields of the config supported along with the async client: the connection,
+/// the selection of the updates, the storage of the rows the async client writes, the
+/// queue and the schema management done before the client starts.
+pub(crate) const ASYNC_CLIENT_FIELDS: &[&str] = &[
+    "libpath",
+    "name",
+    "host",
+    "user",
+    "port",
+    "connection_str",
+    "use_ssl",
+    "server_ca",
+    "client_cert",
+    "client_key",
+    "ssl_mode",
+    "credentials",
+    "panic_on_db_errors",
+    "acknowledge_high_volume",
+    "accounts_selector",
+    "startup_accounts_selector",
+    "transaction_selector",
+    "skip_startup_accounts",
+    "start_slot",
+    "stop_slot",
+    "unload_at_stop_slot",
+    "capture_file",
+    "static_columns",
+    "account_data_compression",
+    "signature_storage",
+    "store_transaction_json",
+    "statement_timeout_ms",
+    "queue_capacity",
+    "queue_overflow_policy",
+    "shutdown_drain_timeout_secs",
+    "spill_path",
+    "schema",
+    "create_schema",
+    "auto_migrate",
+    "startup_wait_secs",
+    "validator_identity",
+    "fail_on_duplicate_plugin_instance",
+    "client",
+    "async_pool_size",
+    "async_request_timeout_ms",
+];
+
+/// The error of a write of the async client
+#[derive(Debug)]
+enum AsyncWriteError {
+    /// The write did not complete within the request timeout
+    TimedOut(Duration),
+    Database(tokio_postgres::Error),
+}
+
+impl fmt::Display for AsyncWriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsyncWriteError::TimedOut(request_timeout) => {
+                write!(f, "The write timed out after {:?}", request_timeout)
+            }
+            AsyncWriteError::Database(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Wait for the request up to the request timeout.
+async fn within_request_timeout<T>(
+    request_timeout: Duration,
+    request: impl Future<Output = Result<T, tokio_postgres::Error>>,
+) -> Result<T, AsyncWriteError> {
+    match timeout(request_timeout, request).await {
+        Ok(result) => result.map_err(AsyncWriteError::Database),
+        Err(_) => Err(AsyncWriteError::TimedOut(request_timeout)),
+    }
+}
+
+fn connection_error(msg: String) -> GeyserPluginError {
+    error!("{}", msg);
+    GeyserPluginError::Custom(Box::new(
+        AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+    ))
+}
+
+fn unsupported(feature: &str) -> GeyserPluginError {
+    GeyserPluginError::Custom(Box::new(
+        AccountsDbPluginPostgresError::ConfigurationError {
+            msg: format!(
+                "The {} is not supported with the \"client\" 'async'",
+                feature
+            ),
+        },
+    ))
+}
+
+/// Drive the connection of a client until it is closed.
+async fn drive_connection(connection: impl Future<Output = Result<(), tokio_postgres::Error>>) {
+    if let Err(err) = connection.await {
+        error!(
+            "The async connection to the PostgreSQL database failed: ({})",
+            err
+        );
+    }
+}
+
+/// The statements prepared on a connection of the pool
+struct AsyncStatements {
+    account: Statement,
+    slot_with_parent: Statement,
+    slot_without_parent: Statement,
+    transaction: Statement,
+    block_metadata: Statement,
+}
+
+struct PooledConnection {
+    client: Client,
+    statements: AsyncStatements,
+}
+
+impl PooledConnection {
+    /// Connect to the database the way the blocking connections do, with the search
+    /// path and the statement timeout of the config set on the session.
+    async fn connect(
+        config: &AccountsDbPluginPostgresConfig,
+        tls_connector: Option<MakeTlsConnector>,
+    ) -> Result<Self, GeyserPluginError> {
+        let connection_str = SimplePostgresClient::connection_str(config)?;
+        // Resolved on each connection so that the reconnections pick up a rotated password
+        let password = resolve_password(config).map_err(|err| {
+            connection_error(format!(
+                "Error in resolving the password of the PostgreSQL database: {}",
+                err
+            ))
+        })?;
+        let mut pg_config: tokio_postgres::Config = connection_str.parse().map_err(|err| {
+            connection_error(format!(
+                "Error in parsing the connection string of the PostgreSQL database: {:?} connection_str: {:?}",
+                err, connection_str
+            ))
+        })?;
+        if let Some(password) = &password {
+            pg_config.password(password);
+        }
+        let result = match tls_connector {
+            Some(connector) => pg_config
+                .connect(connector)
+                .await
+                .map(|(client, connection)| {
+                    tokio::spawn(drive_connection(connection));
+                    client
+                }),
+            None => pg_config.connect(NoTls).await.map(|(client, connection)| {
+                tokio::spawn(drive_connection(connection));
+                client
+            }),
+        };
+        let client = result.map_err(|err| {
+            forget_password(config);
+            connection_error(format!(
+                "Error in connecting to the PostgreSQL database: {:?} connection_str: {:?}",
+                err, connection_str
+            ))
+        })?;
+
+        let mut session = vec![];
+        if let Some(schema) = &config.schema {
+            session.push(format!("SET search_path TO {}", schema));
+        }
+        if let Some(timeout_ms) = config.statement_timeout_ms {
+            session.push(format!("SET statement_timeout = {}", timeout_ms));
+        }
+        let to_schema_error = |err: tokio_postgres::Error| {
+            GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                msg: format!(
+                    "Error in preparing the async connection to the PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                    err, config.host, config.user, config
+                ),
+            }))
+        };
+        client
+            .batch_execute(&session.join("; "))
+            .await
+            .map_err(to_schema_error)?;
+        let statements = AsyncStatements {
+            account: client
+                .prepare(&SimplePostgresClient::single_account_upsert_sql(config))
+                .await
+                .map_err(to_schema_error)?,
+            slot_with_parent: client
+                .prepare(&SimplePostgresClient::slot_upsert_with_parent_sql(config))
+                .await
+                .map_err(to_schema_error)?,
+            slot_without_parent: client
+                .prepare(&SimplePostgresClient::slot_upsert_without_parent_sql(
+                    config,
+                ))
+                .await
+                .map_err(to_schema_error)?,
+            transaction: client
+                .prepare(&SimplePostgresClient::transaction_upsert_sql(config))
+                .await
+                .map_err(to_schema_error)?,
+            block_metadata: client
+                .prepare(&SimplePostgresClient::block_metadata_upsert_sql(config))
+                .await
+                .map_err(to_schema_error)?,
+        };
+        Ok(Self { client, statements })
+    }
+}
+
+/// The pool of the connections of the async client. The connections are created up to
+/// the size of the pool on its creation, and the connections closed are replaced by new
+/// ones when they are acquired.
+struct ConnectionPool {
+    config: AccountsDbPluginPostgresConfig,
+    tls_connector: Option<MakeTlsConnector>,
+    idle: Mutex<Vec<PooledConnection>>,
+    /// The permits of the connections, one per connection of the pool
+    permits: Arc<Semaphore>,
+    size: usize,
+}
+
+impl ConnectionPool {
+    async fn new(config: &AccountsDbPluginPostgresConfig) -> Result<Arc<Self>, GeyserPluginError> {
+        let size = config.async_pool_size.unwrap_or(DEFAULT_ASYNC_POOL_SIZE);
+        let tls_connector = build_tls_connector(config)?;
+        let mut idle = Vec::with_capacity(size);
+        for _ in 0..size {
+            idle.push(PooledConnection::connect(config, tls_connector.clone()).await?);
+        }
+        Ok(Arc::new(Self {
+            config: config.clone(),
+            tls_connector,
+            idle: Mutex::new(idle),
+            permits: Arc::new(Semaphore::new(size)),
+            size,
+        }))
+    }
+
+    /// Acquire a connection, waiting for one to be released when all are in use.
+    async fn acquire(self: &Arc<Self>) -> Result<PoolGuard, GeyserPluginError> {
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("The permits of the pool are never closed");
+        let idle = self.idle.lock().unwrap().pop();
+        let connection = match idle {
+            Some(connection) if !connection.client.is_closed() => connection,
+            _ => PooledConnection::connect(&self.config, self.tls_connector.clone()).await?,
+        };
+        Ok(PoolGuard {
+            pool: self.clone(),
+            connection: Some(connection),
+            _permit: permit,
+        })
+    }
+
+    /// Wait until all the connections are released, the writes in flight completed.
+    async fn wait_idle(&self) {
+        let _permits = self.permits.acquire_many(self.size as u32).await;
+    }
+
+    /// Cancel the query running on the connection discarded for its timeout.
+    fn cancel(&self, cancel_token: CancelToken) {
+        let tls_connector = self.tls_connector.clone();
+        tokio::spawn(async move {
+            let result = match tls_connector {
+                Some(connector) => cancel_token.cancel_query(connector).await,
+                None => cancel_token.cancel_query(NoTls).await,
+            };
+            if let Err(err) = result {
+                error!("Failed to cancel the write timed out: ({})", err);
+            }
+        });
+    }
+}
+
+/// A connection acquired from the pool, released back to it when dropped unless it is
+/// closed or discarded.
+struct PoolGuard {
+    pool: Arc<ConnectionPool>,
+    connection: Option<PooledConnection>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl PoolGuard {
+    /// Discard the connection of the write timed out, cancelling its query, so that
+    /// the next write does not wait behind it.
+    fn cancel_and_discard(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            self.pool.cancel(connection.client.cancel_token());
+        }
+    }
+}
+
+impl Drop for PoolGuard {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            if !connection.client.is_closed() {
+                self.pool.idle.lock().unwrap().push(connection);
+            }
+        }
+    }
+}
+
+/// The writes of the async client
+enum AsyncWrite {
+    Account {
+        account: DbAccountInfo,
+        is_startup: bool,
+    },
+    Slot(SlotRowValues),
+    Transaction(Box<LogTransactionRequest>),
+    BlockMetadata(Box<UpdateBlockMetadataRequest>),
+    StartupReport(StartupReportRow),
+}
+
+impl AsyncWrite {
+    fn description(&self) -> &'static str {
+        match self {
+            AsyncWrite::Account { .. } => "update the account",
+            AsyncWrite::Slot(_) => "update the slot status",
+            AsyncWrite::Transaction(_) => "log the transaction",
+            AsyncWrite::BlockMetadata(_) => "update the block metadata",
+            AsyncWrite::StartupReport(_) => "write the startup report",
+        }
+    }
+}
+
+/// What the tasks writing the updates share
+struct AsyncWriter {
+    signature_storage: SignatureStorage,
+    store_transaction_json: bool,
+    account_decoders: AccountDecoders,
+    data_compression: DataCompression,
+    request_timeout: Duration,
+    panic_on_db_errors: bool,
+    /// The statistics of the accounts written during the startup
+    startup_stats: Mutex<StartupStats>,
+}
+
+impl AsyncWriter {
+    async fn execute(
+        &self,
+        connection: &mut PooledConnection,
+        write: &AsyncWrite,
+    ) -> Result<(), tokio_postgres::Error> {
+        let statements = &connection.statements;
+        let client = &mut connection.client;
+        match write {
+            AsyncWrite::Account { account, .. } => {
+                let values =
+                    AccountRowValues::new(account, &self.account_decoders, &self.data_compression);
+                client
+                    .execute(
+                        &statements.account,
+                        &values.params(account, &self.account_decoders),
+                    )
+                    .await?;
+            }
+            AsyncWrite::Slot(row) => {
+                let statement = if row.has_parent() {
+                    &statements.slot_with_parent
+                } else {
+                    &statements.slot_without_parent
+                };
+                client.execute(statement, &row.params()).await?;
+            }
+            AsyncWrite::Transaction(request) => {
+                let values = TransactionRowValues::new(request, self.signature_storage);
+                client
+                    .execute(
+                        &statements.transaction,
+                        &values.params(self.store_transaction_json),
+                    )
+                    .await?;
+            }
+            AsyncWrite::BlockMetadata(request) => {
+                let updated_on = Utc::now().naive_utc();
+                client
+                    .execute(
+                        &statements.block_metadata,
+                        &block_metadata_params(
+                            &request.block_info,
+                            &updated_on,
+                            &request.block_json,
+                            false,
+                        ),
+                    )
+                    .await?;
+            }
+            AsyncWrite::StartupReport(row) => {
+                let transaction = client.transaction().await?;
+                let report_id: i64 = transaction
+                    .query_one(INSERT_STARTUP_REPORT, &row.report_params())
+                    .await?
+                    .get(0);
+                transaction
+                    .execute(INSERT_STARTUP_REPORT_OWNERS, &row.owner_params(&report_id))
+                    .await?;
+                transaction.commit().await?;
+                row.log_written(report_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the update through the connection, cancelling it past the request timeout.
+    async fn write(&self, mut connection: PoolGuard, write: AsyncWrite) {
+        let result = within_request_timeout(
+            self.request_timeout,
+            self.execute(connection.connection.as_mut().unwrap(), &write),
+        )
+        .await;
+        if let AsyncWrite::Account {
+            account,
+            is_startup: true,
+        } = &write
+        {
+            let mut startup_stats = self.startup_stats.lock().unwrap();
+            match &result {
+                Ok(()) => startup_stats.record_accounts([account]),
+                Err(_) => startup_stats.record_failed_accounts(1),
+            }
+        }
+        if let Err(err) = result {
+            if let AsyncWriteError::TimedOut(_) = err {
+                connection.cancel_and_discard();
+            }
+            error!("Failed to {}: ({})", write.description(), err);
+            if self.panic_on_db_errors {
+                abort();
+            }
+        }
+    }
+}
+
+pub(crate) struct AsyncPostgresClient {
+    runtime: Runtime,
+    pool: Arc<ConnectionPool>,
+    writer: Arc<AsyncWriter>,
+}
+
+impl AsyncPostgresClient {
+    pub(crate) fn new(config: &AccountsDbPluginPostgresConfig) -> Result<Self, GeyserPluginError> {
+        info!("Creating AsyncPostgresClient...");
+        let runtime = runtime::Builder::new_multi_thread()
+            .thread_name("pg-async-writer")
+            .enable_all()
+            .build()
+            .map_err(|err| {
+                connection_error(format!(
+                    "Error in creating the runtime of the async client: ({})",
+                    err
+                ))
+            })?;
+        let pool = runtime.block_on(ConnectionPool::new(config))?;
+        let writer = Arc::new(AsyncWriter {
+            signature_storage: config.signature_storage.unwrap_or_default(),
+            store_transaction_json: config
+                .store_transaction_json
+                .unwrap_or(DEFAULT_STORE_TRANSACTION_JSON),
+            account_decoders: AccountDecoders::new(config)?,
+            data_compression: DataCompression::new(config),
+            request_timeout: Duration::from_millis(
+                config
+                    .async_request_timeout_ms
+                    .unwrap_or(DEFAULT_ASYNC_REQUEST_TIMEOUT_MS),
+            ),
+            panic_on_db_errors: config
+                .panic_on_db_errors
+                .unwrap_or(DEFAULT_PANIC_ON_DB_ERROR),
+            startup_stats: Mutex::default(),
+        });
+        info!("Created AsyncPostgresClient.");
+        Ok(Self {
+            runtime,
+            pool,
+            writer,
+        })
+    }
+
+    /// Spawn the task of the write once a connection is acquired, blocking while all
+    /// the connections of the pool are in use.
+    fn spawn_write(&self, write: AsyncWrite) -> Result<(), GeyserPluginError> {
+        let connection = self.runtime.block_on(self.pool.acquire())?;
+        let writer = self.writer.clone();
+        self.runtime
+            .spawn(async move { writer.write(connection, write).await });
+        Ok(())
+    }
+
+    fn take_startup_stats(&self) -> StartupStats {
+        std::mem::take(&mut self.writer.startup_stats.lock().unwrap())
+    }
+
+    /// Spawn the write of the work item, moved into the task instead of being copied as
+    /// by the PostgresClient methods.
+    fn write_item(&mut self, work: DbWorkItem) -> Result<(), GeyserPluginError> {
+        let write = match work {
+            DbWorkItem::UpdateAccount(request) => AsyncWrite::Account {
+                account: request.account,
+                is_startup: request.is_startup,
+            },
+            DbWorkItem::UpdateSlot(request) => AsyncWrite::Slot(SlotRowValues::new(
+                request.slot,
+                request.parent,
+                request.slot_status,
+            )),
+            DbWorkItem::LogTransaction(request) => AsyncWrite::Transaction(request),
+            DbWorkItem::UpdateBlockMetadata(request) => AsyncWrite::BlockMetadata(request),
+            DbWorkItem::WriteStartupReport(request) => {
+                AsyncWrite::StartupReport(StartupReportRow::new(&request))
+            }
+            DbWorkItem::LogVoteActivity(request) => return self.log_vote_activity(&request),
+            DbWorkItem::UpdateEntry(request) => return self.update_entries(&request),
+            DbWorkItem::VerifyBlockChain(request) => return self.verify_block_chain(&request),
+            DbWorkItem::CleanUpForks(request) => return self.clean_up_forks(&request),
+            DbWorkItem::CommitSlot(_) => return Err(unsupported("per-slot commit")),
+            DbWorkItem::WriteEpochBarrier(_) => return Err(unsupported("epoch barrier")),
+        };
+        self.spawn_write(write)
+    }
+}
+
+impl PostgresClient for AsyncPostgresClient {
+    fn join(&mut self) -> thread::Result<()> {
+        self.runtime.block_on(self.pool.wait_idle());
+        Ok(())
+    }
+
+    fn update_account(
+        &mut self,
+        account: &DbAccountInfo,
+        is_startup: bool,
+    ) -> Result<(), GeyserPluginError> {
+        self.spawn_write(AsyncWrite::Account {
+            account: account.clone(),
+            is_startup,
+        })
+    }
+
+    fn update_slot_status(
+        &mut self,
+        slot: u64,
+        parent: Option<u64>,
+        status: &SlotStatus,
+    ) -> Result<(), GeyserPluginError> {
+        info!("Updating slot {:?} at with status {:?}", slot, status);
+        self.spawn_write(AsyncWrite::Slot(SlotRowValues::new(
+            slot,
+            parent,
+            status.clone(),
+        )))
+    }
+
+    fn notify_end_of_startup(&mut self) -> Result<(), GeyserPluginError> {
+        self.runtime.block_on(self.pool.wait_idle());
+        Ok(())
+    }
+
+    fn write_startup_report(
+        &mut self,
+        request: &WriteStartupReportRequest,
+    ) -> Result<(), GeyserPluginError> {
+        self.spawn_write(AsyncWrite::StartupReport(StartupReportRow::new(request)))
+    }
+
+    fn log_transaction(
+        &mut self,
+        transaction_log_info: &LogTransactionRequest,
+    ) -> Result<(), GeyserPluginError> {
+        self.spawn_write(AsyncWrite::Transaction(Box::new(
+            transaction_log_info.clone(),
+        )))
+    }
+
+    fn update_block_metadata(
+        &mut self,
+        block_info: &UpdateBlockMetadataRequest,
+    ) -> Result<(), GeyserPluginError> {
+        self.spawn_write(AsyncWrite::BlockMetadata(Box::new(block_info.clone())))
+    }
+
+    fn log_vote_activity(
+        &mut self,
+        _vote_activity_info: &LogVoteActivityRequest,
+    ) -> Result<(), GeyserPluginError> {
+        Err(unsupported("vote activity"))
+    }
+
+    fn update_entries(&mut self, _request: &UpdateEntryRequest) -> Result<(), GeyserPluginError> {
+        Err(unsupported("storage of the entries"))
+    }
+
+    fn verify_block_chain(
+        &mut self,
+        _request: &VerifyBlockChainRequest,
+    ) -> Result<(), GeyserPluginError> {
+        Err(unsupported("block chain verification"))
+    }
+
+    fn clean_up_forks(&mut self, _request: &CleanUpForksRequest) -> Result<(), GeyserPluginError> {
+        Err(unsupported("fork cleanup"))
+    }
+}
+
+impl AsyncPostgresClient {
+    /// Spawn the dispatcher of the async client in place of the workers of the
+    /// ParallelPostgresClient, spawning the writes of the work items queued. The writes
+    /// in flight complete before it exits, and the work items left queued are spilled.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn spawn_worker(
+        config: AccountsDbPluginPostgresConfig,
+        receiver: Receiver<DbWorkItem>,
+        exit_worker: Arc<AtomicBool>,
+        is_startup_done: Arc<AtomicBool>,
+        startup_done_count: Arc<AtomicUsize>,
+        initialized_worker_count: Arc<AtomicUsize>,
+        startup_stats: Arc<Mutex<StartupStats>>,
+        shutdown_drain: Arc<ShutdownDrain>,
+    ) -> JoinHandle<Result<(), GeyserPluginError>> {
+        Builder::new()
+            .name("async-worker".to_string())
+            .spawn(move || -> Result<(), GeyserPluginError> {
+                let panic_on_db_errors = config
+                    .panic_on_db_errors
+                    .unwrap_or(DEFAULT_PANIC_ON_DB_ERROR);
+                let mut client = match AsyncPostgresClient::new(&config) {
+                    Ok(client) => client,
+                    Err(err) => {
+                        error!("Error when making connection to database: ({})", err);
+                        if panic_on_db_errors {
+                            abort();
+                        }
+                        return Err(err);
+                    }
+                };
+                initialized_worker_count.fetch_add(1, Ordering::Relaxed);
+                let mut is_client_startup_done = false;
+                while !exit_worker.load(Ordering::Relaxed) {
+                    if shutdown_drain.is_draining() && receiver.is_empty() {
+                        break;
+                    }
+                    match receiver.recv_timeout(Duration::from_millis(500)) {
+                        Ok(work) => {
+                            let description = work.description();
+                            if let Err(err) = client.write_item(work) {
+                                error!("Failed to {}: ({})", description, err);
+                                if panic_on_db_errors {
+                                    abort();
+                                }
+                            }
+                        }
+                        Err(RecvTimeoutError::Timeout) => {
+                            if !is_client_startup_done && is_startup_done.load(Ordering::Relaxed) {
+                                if let Err(err) = client.notify_end_of_startup() {
+                                    error!("Error in notifying end of startup: ({})", err);
+                                }
+                                startup_stats
+                                    .lock()
+                                    .unwrap()
+                                    .merge(client.take_startup_stats());
+                                is_client_startup_done = true;
+                                startup_done_count.fetch_add(1, Ordering::Relaxed);
+                            }
+                        }
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+                let _ = client.join();
+                shutdown_drain.spill(receiver.try_iter().collect());
+                shutdown_drain.record_worker_exit();
+                Ok(())
+            })
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_within_request_timeout() {
+        let runtime = runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let request_timeout = Duration::from_millis(10);
+        assert_eq!(
+            runtime
+                .block_on(within_request_timeout(
+                    request_timeout,
+                    std::future::ready(Ok(1))
+                ))
+                .unwrap(),
+            1
+        );
+        let result = runtime.block_on(within_request_timeout(
+            request_timeout,
+            std::future::pending::<Result<(), tokio_postgres::Error>>(),
+        ));
+        assert!(
+            matches!(result, Err(AsyncWriteError::TimedOut(timeout)) if timeout == request_timeout)
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "The write timed out after 10ms"
+        );
+    }
+}
@@ -0,0 +1,227 @@
+/// Module responsible for archiving the history of the accounts, the rows of the
+/// account_audit table older than a slot, to files in the binary format of COPY before
+/// deleting them. The partitions of a partitioned account_audit table wholly older than
+/// the slot are archived and dropped as a whole.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::SimplePostgresClient,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    postgres::{GenericClient, IsolationLevel},
+    std::{
+        fs::{self, File},
+        io,
+        path::{Path, PathBuf},
+    },
+};
+
+/// The outcome of the archive of the account history
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuditArchiveReport {
+    /// The partitions archived and dropped
+    pub partitions: u64,
+    /// The rows archived and deleted from the partitions kept or the table
+    pub rows: u64,
+}
+
+/// The file the table or the rows are archived to, the names of the tables being
+/// qualified by their schema outside of the search path, along with the file it is
+/// written to until it is complete.
+fn archive_file_paths(archive_path: &str, name: &str) -> (PathBuf, PathBuf) {
+    let path = Path::new(archive_path).join(format!("{}.copy", name));
+    let partial_path = path.with_extension("copy.partial");
+    (path, partial_path)
+}
+
+/// The upper bound, exclusive, of the slot range of a partition from the expression of
+/// its bounds, such as "FOR VALUES FROM ('0') TO ('216000')". None for the default
+/// partition and the ranges unbounded above.
+fn partition_upper_bound(bound: &str) -> Option<i64> {
+    let (_, upper) = bound.split_once(" TO (")?;
+    upper.strip_suffix(')')?.trim_matches('\'').parse().ok()
+}
+
+fn to_archive_error(name: &str, err: io::Error) -> GeyserPluginError {
+    let msg = format!(
+        "Failed to archive the {} of the account_audit table. Error: {:?}",
+        name, err
+    );
+    error!("{}", msg);
+    GeyserPluginError::Custom(Box::new(
+        AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+    ))
+}
+
+fn to_archive_query_error(action: &str, err: postgres::Error) -> GeyserPluginError {
+    let msg = format!(
+        "Failed to {} of the account_audit table in the PostgreSQL database. Error: {:?}",
+        action, err
+    );
+    error!("{}", msg);
+    GeyserPluginError::Custom(Box::new(
+        AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+    ))
+}
+
+/// Copy the rows of the COPY TO STDOUT statement to the file named in the archive
+/// directory, in the binary format of COPY. The file is written under a temporary name
+/// renamed once it is complete and synced, so that a partial archive is never taken for
+/// a complete one. The rows are not to be deleted when it fails.
+pub(crate) fn archive_copy(
+    client: &mut impl GenericClient,
+    archive_path: &str,
+    name: &str,
+    copy_statement: &str,
+) -> Result<(), GeyserPluginError> {
+    let (path, partial_path) = archive_file_paths(archive_path, name);
+    let mut reader = client
+        .copy_out(copy_statement)
+        .map_err(|err| to_archive_query_error("copy the rows", err))?;
+    let bytes = File::create(&partial_path)
+        .and_then(|mut file| {
+            let bytes = io::copy(&mut reader, &mut file)?;
+            file.sync_all()?;
+            Ok(bytes)
+        })
+        .and_then(|bytes| fs::rename(&partial_path, &path).map(|()| bytes))
+        .map_err(|err| to_archive_error(name, err))?;
+    info!(
+        "Archived the {} of the account_audit table to {:?}, {} bytes",
+        name, path, bytes
+    );
+    Ok(())
+}
+
+/// Create the archive directory when it is missing.
+pub(crate) fn create_archive_dir(archive_path: &str) -> Result<(), GeyserPluginError> {
+    fs::create_dir_all(archive_path).map_err(|err| {
+        GeyserPluginError::Custom(Box::new(
+            AccountsDbPluginPostgresError::ConfigurationError {
+                msg: format!(
+                    "Failed to create the \"account_audit_archive_path\" {:?}: {:?}",
+                    archive_path, err
+                ),
+            },
+        ))
+    })
+}
+
+impl SimplePostgresClient {
+    /// Archive the history of the accounts older than the slot to the
+    /// account_audit_archive_path, then delete it. The partitions wholly older than the
+    /// slot are archived and dropped each, and the rows older than the slot left in the
+    /// other partitions or in the table not partitioned are archived together and
+    /// deleted.
+    pub fn archive_account_audit(
+        config: &AccountsDbPluginPostgresConfig,
+        before_slot: u64,
+    ) -> Result<AuditArchiveReport, GeyserPluginError> {
+        let Some(archive_path) = &config.account_audit_archive_path else {
+            return Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::ConfigurationError {
+                    msg: "The \"account_audit_archive_path\" must be set to archive the account history"
+                        .to_string(),
+                },
+            )));
+        };
+        create_archive_dir(archive_path)?;
+        let before_slot = before_slot as i64;
+        let mut client = Self::connect_to_db(config)?;
+        let mut report = AuditArchiveReport::default();
+
+        let partitions = client
+            .query(
+                "SELECT c.oid::regclass::TEXT, pg_get_expr(c.relpartbound, c.oid) FROM pg_inherits i \
+                JOIN pg_class c ON c.oid = i.inhrelid \
+                WHERE i.inhparent = 'account_audit'::regclass ORDER BY 1",
+                &[],
+            )
+            .map_err(|err| to_archive_query_error("query the partitions", err))?;
+        for partition in partitions {
+            let name: String = partition.get(0);
+            // The bounds are null for the tables inheriting outside of a partitioning
+            let bound: Option<String> = partition.get(1);
+            if bound
+                .as_deref()
+                .and_then(partition_upper_bound)
+                .is_some_and(|upper| upper <= before_slot)
+            {
+                archive_copy(
+                    &mut client,
+                    archive_path,
+                    &name,
+                    &format!("COPY {} TO STDOUT (FORMAT binary)", name),
+                )?;
+                client
+                    .batch_execute(&format!("DROP TABLE {}", name))
+                    .map_err(|err| to_archive_query_error("drop the partition", err))?;
+                report.partitions += 1;
+            }
+        }
+
+        // The snapshot of the repeatable read isolation makes the rows deleted the ones
+        // archived, the rows inserted meanwhile at older slots being left for later.
+        let mut transaction = client
+            .build_transaction()
+            .isolation_level(IsolationLevel::RepeatableRead)
+            .start()
+            .map_err(|err| to_archive_query_error("begin the archive", err))?;
+        archive_copy(
+            &mut transaction,
+            archive_path,
+            &format!("account_audit_before_{}", before_slot),
+            &format!(
+                "COPY (SELECT * FROM account_audit WHERE slot < {}) TO STDOUT (FORMAT binary)",
+                before_slot
+            ),
+        )?;
+        report.rows = transaction
+            .execute("DELETE FROM account_audit WHERE slot < $1", &[&before_slot])
+            .map_err(|err| to_archive_query_error("delete the rows archived", err))?;
+        transaction
+            .commit()
+            .map_err(|err| to_archive_query_error("commit the archive", err))?;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_file_paths() {
+        assert_eq!(
+            archive_file_paths("/var/archive", "account_audit_before_216000"),
+            (
+                PathBuf::from("/var/archive/account_audit_before_216000.copy"),
+                PathBuf::from("/var/archive/account_audit_before_216000.copy.partial")
+            )
+        );
+        assert_eq!(
+            archive_file_paths("archive", "history.account_audit_216000").0,
+            PathBuf::from("archive/history.account_audit_216000.copy")
+        );
+        assert_eq!(
+            archive_file_paths("archive", "_timescaledb_internal._hyper_1_2_chunk").0,
+            PathBuf::from("archive/_timescaledb_internal._hyper_1_2_chunk.copy")
+        );
+    }
+
+    #[test]
+    fn test_partition_upper_bound() {
+        assert_eq!(
+            partition_upper_bound("FOR VALUES FROM ('0') TO ('216000')"),
+            Some(216000)
+        );
+        assert_eq!(
+            partition_upper_bound("FOR VALUES FROM ('216000') TO (MAXVALUE)"),
+            None
+        );
+        assert_eq!(partition_upper_bound("DEFAULT"), None);
+    }
+}
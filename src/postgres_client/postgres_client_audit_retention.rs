@@ -0,0 +1,511 @@
+/// Module responsible for the retention of the account history. The account_audit table
+/// is partitioned by the slot ranges, the plugin creates the partitions ahead of the
+/// slots and drops the ones older than the retention from a background thread, so that
+/// the table does not grow unboundedly with the store_account_historical_data. With the
+/// TimescaleDB storage engine, the chunks of the account_audit hypertable older than the
+/// retention are dropped instead. When the account_audit table is registered with
+/// pg_partman, its partitions are left to the maintenance of pg_partman, which the plugin
+/// runs with the retention instead of creating and dropping the partitions itself. The
+/// history of the owners with a retention override is deleted at their own retention,
+/// the rows of the owners kept longer being moved to the default partition before the
+/// partitions holding them are dropped. With an archive directory, the partitions and
+/// the chunks are copied to a file each before they are dropped.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::{
+            postgres_client_audit_archive::{archive_copy, create_archive_dir},
+            postgres_client_hypertable::is_timescaledb,
+            SimplePostgresClient, DEFAULT_ACCOUNT_AUDIT_PARTITION_SLOTS,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    postgres::Client,
+    std::{
+        collections::BTreeMap,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread::{sleep, Builder, JoinHandle},
+        time::{Duration, Instant},
+    },
+};
+
+const AUDIT_RETENTION_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+const AUDIT_RETENTION_INTERVAL: Duration = Duration::from_secs(60);
+/// The partition of the audit rows of the slots without a partition
+const DEFAULT_PARTITION: &str = "account_audit_default";
+/// The parent table of the account_audit partitions as registered in the part_config of
+/// pg_partman, qualified by its schema
+const PARTMAN_PARENT_TABLE: &str = "current_schema() || '.account_audit'";
+
+/// The slot range of the partition of the slot, the end being exclusive
+fn partition_range(slot: i64, partition_slots: i64) -> (i64, i64) {
+    let start = slot - slot.rem_euclid(partition_slots);
+    (start, start + partition_slots)
+}
+
+/// Parse the exclusive end of the slot range of a partition from its bound, such as
+/// "FOR VALUES FROM ('0') TO ('216000')", None for the default partition.
+fn parse_partition_end(bound: &str) -> Option<i64> {
+    let (_, end) = bound.split_once(" TO (")?;
+    end.trim_end_matches(')').trim_matches('\'').parse().ok()
+}
+
+/// The statements of the pg_partman installed in the schema setting the retention of the
+/// account_audit partitions, and running their maintenance, which creates the partitions
+/// ahead of the slots and drops the ones older than the retention.
+fn partman_statements(partman_schema: &str) -> (String, String) {
+    (
+        format!(
+            "UPDATE \"{partman_schema}\".part_config SET retention = $1, retention_keep_table = false \
+            WHERE parent_table = {PARTMAN_PARENT_TABLE}"
+        ),
+        format!(
+            "SELECT \"{partman_schema}\".run_maintenance(p_parent_table => {PARTMAN_PARENT_TABLE})"
+        ),
+    )
+}
+
+/// The owners whose history is kept for the same slots, other than the retention
+#[derive(Debug, PartialEq, Eq)]
+struct RetentionOverride {
+    owners: Vec<Vec<u8>>,
+    retention_slots: i64,
+    /// The slot the history of the owners was deleted up to by the last maintenance
+    deleted_until: Option<i64>,
+}
+
+/// Group the owners of the retention overrides by their retention.
+fn retention_overrides(config: &AccountsDbPluginPostgresConfig) -> Vec<RetentionOverride> {
+    let mut owners_by_retention: BTreeMap<u64, Vec<Vec<u8>>> = BTreeMap::new();
+    for (owner, retention_slots) in config.account_audit_retention_overrides.iter().flatten() {
+        // The owners are validated as base58 with the config
+        if let Ok(owner) = bs58::decode(owner).into_vec() {
+            owners_by_retention
+                .entry(*retention_slots)
+                .or_default()
+                .push(owner);
+        }
+    }
+    owners_by_retention
+        .into_iter()
+        .map(|(retention_slots, owners)| RetentionOverride {
+            owners,
+            retention_slots: retention_slots as i64,
+            deleted_until: None,
+        })
+        .collect()
+}
+
+fn to_retention_error(action: &str, err: postgres::Error) -> GeyserPluginError {
+    let msg = format!(
+        "Failed to {} of the account_audit table in the PostgreSQL database. Error: {:?}",
+        action, err
+    );
+    error!("{}", msg);
+    GeyserPluginError::Custom(Box::new(
+        AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+    ))
+}
+
+pub(crate) struct AuditRetention {
+    client: Client,
+    partition_slots: i64,
+    retention_slots: i64,
+    /// The directory the partitions and the chunks are archived to before they are
+    /// dropped
+    archive_path: Option<String>,
+    /// Whether the account_audit table is a hypertable chunked by TimescaleDB
+    is_hypertable: bool,
+    /// The statement running the maintenance of pg_partman, when it manages the
+    /// partitions of the account_audit table
+    partman_maintenance: Option<String>,
+    /// The retention of the owners overriding the retention of the rest of the history
+    overrides: Vec<RetentionOverride>,
+}
+
+impl AuditRetention {
+    /// Connect and maintain the partitions once when the retention is configured, the
+    /// loading of the plugin fails when the account_audit table is neither partitioned nor
+    /// a hypertable. The retention of the account_audit table registered with pg_partman
+    /// is set in its part_config.
+    pub(crate) fn new(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Option<Self>, GeyserPluginError> {
+        let Some(retention_slots) = config.account_audit_retention_slots else {
+            return Ok(None);
+        };
+        let partition_slots = config
+            .account_audit_partition_slots
+            .unwrap_or(DEFAULT_ACCOUNT_AUDIT_PARTITION_SLOTS);
+        if let Some(archive_path) = &config.account_audit_archive_path {
+            create_archive_dir(archive_path)?;
+        }
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+        let is_hypertable = is_timescaledb(config);
+        let is_partitioned: bool = client
+            .query_one(
+                "SELECT relkind = 'p' FROM pg_class WHERE oid = 'account_audit'::regclass",
+                &[],
+            )
+            .map_err(|err| to_retention_error("check the partitioning", err))?
+            .get(0);
+        if !is_partitioned && !is_hypertable {
+            return Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::ConfigurationError {
+                    msg: "The account_audit table must be partitioned by the slot for the \
+                        \"account_audit_retention_slots\", as in scripts/create_schema.sql"
+                        .to_string(),
+                },
+            )));
+        }
+        let partman_maintenance = if is_hypertable {
+            None
+        } else {
+            Self::partman_maintenance(&mut client, retention_slots)?
+        };
+        if partman_maintenance.is_some() && config.account_audit_archive_path.is_some() {
+            return Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::ConfigurationError {
+                    msg: "The \"account_audit_archive_path\" is not supported when pg_partman \
+                        manages the partitions of the account_audit table, as it drops them itself"
+                        .to_string(),
+                },
+            )));
+        }
+        let overrides = retention_overrides(config);
+        if partman_maintenance.is_some()
+            && overrides
+                .iter()
+                .any(|retention| retention.retention_slots > retention_slots as i64)
+        {
+            return Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::ConfigurationError {
+                    msg: "The \"account_audit_retention_overrides\" must not be longer than the \
+                        \"account_audit_retention_slots\" when pg_partman manages the partitions \
+                        of the account_audit table"
+                        .to_string(),
+                },
+            )));
+        }
+        let mut retention = Self {
+            client,
+            partition_slots: partition_slots as i64,
+            retention_slots: retention_slots as i64,
+            archive_path: config.account_audit_archive_path.clone(),
+            is_hypertable,
+            partman_maintenance,
+            overrides,
+        };
+        retention.maintain()?;
+        Ok(Some(retention))
+    }
+
+    /// Set the retention of the account_audit table registered with the pg_partman
+    /// installed, yielding the statement running its maintenance. None when pg_partman is
+    /// not installed or does not manage the account_audit table, the plugin then
+    /// maintaining the partitions itself.
+    fn partman_maintenance(
+        client: &mut Client,
+        retention_slots: u64,
+    ) -> Result<Option<String>, GeyserPluginError> {
+        let partman_schema: Option<String> = client
+            .query_opt(
+                "SELECT n.nspname::TEXT FROM pg_extension e \
+                JOIN pg_namespace n ON n.oid = e.extnamespace WHERE e.extname = 'pg_partman'",
+                &[],
+            )
+            .map_err(|err| to_retention_error("look up the pg_partman extension", err))?
+            .map(|row| row.get(0));
+        let Some(partman_schema) = partman_schema else {
+            return Ok(None);
+        };
+        let (set_retention, run_maintenance) = partman_statements(&partman_schema);
+        let updated = client
+            .execute(&set_retention, &[&retention_slots.to_string()])
+            .map_err(|err| to_retention_error("set the retention of pg_partman", err))?;
+        if updated == 0 {
+            warn!(
+                "The pg_partman extension is installed but does not manage the account_audit table, \
+                its partitions are maintained by the plugin"
+            );
+            return Ok(None);
+        }
+        info!("The partitions of the account_audit table are maintained by pg_partman");
+        Ok(Some(run_maintenance))
+    }
+
+    /// Create the partitions of the current and the next slot ranges, and drop the
+    /// partitions whose slots are all older than the retention, once archived, or the
+    /// chunks of the hypertable older than the retention. A partition or a chunk failing
+    /// to be archived is kept until the next maintenance. The maintenance of pg_partman is
+    /// run instead when it manages the partitions. The history of the owners with a
+    /// retention override is then deleted at their retention.
+    fn maintain(&mut self) -> Result<(), GeyserPluginError> {
+        if let Some(run_maintenance) = &self.partman_maintenance {
+            self.client
+                .batch_execute(run_maintenance)
+                .map_err(|err| to_retention_error("run the maintenance of pg_partman", err))?;
+            if self.overrides.is_empty() {
+                return Ok(());
+            }
+        }
+        let row = self
+            .client
+            .query_one("SELECT MAX(slot) FROM slot", &[])
+            .map_err(|err| to_retention_error("query the current slot", err))?;
+        let Some(slot) = row.get::<_, Option<i64>>(0) else {
+            return Ok(());
+        };
+        if self.partman_maintenance.is_none() {
+            self.maintain_partitions(slot)?;
+        }
+        self.delete_overridden_history(slot)
+    }
+
+    /// Drop the chunks or the partitions older than the retention.
+    fn maintain_partitions(&mut self, slot: i64) -> Result<(), GeyserPluginError> {
+        if self.is_hypertable {
+            let cutoff = slot - self.retention_slots;
+            if let Some(archive_path) = &self.archive_path {
+                let chunks = self
+                    .client
+                    .query(
+                        "SELECT show_chunks('account_audit', older_than => $1::BIGINT)::TEXT",
+                        &[&cutoff],
+                    )
+                    .map_err(|err| to_retention_error("query the chunks", err))?;
+                for chunk in chunks {
+                    let name: String = chunk.get(0);
+                    archive_copy(
+                        &mut self.client,
+                        archive_path,
+                        &name,
+                        &format!("COPY {} TO STDOUT (FORMAT binary)", name),
+                    )?;
+                }
+            }
+            // TimescaleDB creates the chunks as the rows are inserted
+            self.client
+                .execute(
+                    "SELECT drop_chunks('account_audit', older_than => $1::BIGINT)",
+                    &[&cutoff],
+                )
+                .map_err(|err| to_retention_error("drop the chunks", err))?;
+            return Ok(());
+        }
+
+        let (start, end) = partition_range(slot, self.partition_slots);
+        for (start, end) in [(start, end), (end, end + self.partition_slots)] {
+            let stmt = format!(
+                "CREATE TABLE IF NOT EXISTS account_audit_{start} PARTITION OF account_audit \
+                FOR VALUES FROM ({start}) TO ({end})"
+            );
+            // The partition of the current slots cannot be created once the default
+            // partition holds some of its rows, they are left to the default partition.
+            if let Err(err) = self.client.batch_execute(&stmt) {
+                warn!(
+                    "Failed to create the partition of the slots {}..{} of the account_audit table: ({})",
+                    start, end, err
+                );
+            }
+        }
+
+        let cutoff = slot - self.retention_slots;
+        let partitions = self
+            .client
+            .query(
+                "SELECT c.relname::TEXT, pg_get_expr(c.relpartbound, c.oid) FROM pg_inherits i \
+                JOIN pg_class c ON c.oid = i.inhrelid WHERE i.inhparent = 'account_audit'::regclass",
+                &[],
+            )
+            .map_err(|err| to_retention_error("query the partitions", err))?;
+        for partition in partitions {
+            let name: String = partition.get(0);
+            let bound: String = partition.get(1);
+            if let Some(end) = parse_partition_end(&bound).filter(|end| *end <= cutoff) {
+                if let Some(archive_path) = &self.archive_path {
+                    archive_copy(
+                        &mut self.client,
+                        archive_path,
+                        &name,
+                        &format!("COPY {} TO STDOUT (FORMAT binary)", name),
+                    )?;
+                }
+                info!("Dropping the partition {} of the account_audit table", name);
+                self.drop_partition(&name, slot, end)?;
+            }
+        }
+        // The history of the owners with a retention override is deleted at theirs
+        let overridden_owners: Vec<&Vec<u8>> = self
+            .overrides
+            .iter()
+            .flat_map(|retention| &retention.owners)
+            .collect();
+        self.client
+            .execute(
+                &format!(
+                    "DELETE FROM {} WHERE slot < $1 AND (owner = ANY($2)) IS NOT TRUE",
+                    DEFAULT_PARTITION
+                ),
+                &[&cutoff, &overridden_owners],
+            )
+            .map_err(|err| to_retention_error("delete the old rows", err))?;
+        Ok(())
+    }
+
+    /// Drop the partition, moving the rows of the owners kept longer than the retention
+    /// to the default partition first.
+    fn drop_partition(&mut self, name: &str, slot: i64, end: i64) -> Result<(), GeyserPluginError> {
+        let kept: Vec<&RetentionOverride> = self
+            .overrides
+            .iter()
+            .filter(|retention| slot - retention.retention_slots < end)
+            .collect();
+        if kept.is_empty() {
+            return self
+                .client
+                .batch_execute(&format!("DROP TABLE {}", name))
+                .map_err(|err| to_retention_error("drop the partition", err));
+        }
+        let mut transaction = self
+            .client
+            .transaction()
+            .map_err(|err| to_retention_error("drop the partition", err))?;
+        // Detached, the rows inserted back into the slot range of the partition go to the
+        // default partition
+        transaction
+            .batch_execute(&format!(
+                "ALTER TABLE account_audit DETACH PARTITION {}",
+                name
+            ))
+            .map_err(|err| to_retention_error("detach the partition", err))?;
+        for retention in kept {
+            transaction
+                .execute(
+                    &format!(
+                        "INSERT INTO account_audit SELECT * FROM {} WHERE owner = ANY($1) AND slot >= $2",
+                        name
+                    ),
+                    &[&retention.owners, &(slot - retention.retention_slots)],
+                )
+                .map_err(|err| to_retention_error("keep the rows of the partition", err))?;
+        }
+        transaction
+            .batch_execute(&format!("DROP TABLE {}", name))
+            .map_err(|err| to_retention_error("drop the partition", err))?;
+        transaction
+            .commit()
+            .map_err(|err| to_retention_error("drop the partition", err))
+    }
+
+    /// Delete the history of the owners with a retention override older than their
+    /// retention, from the slot it was last deleted up to.
+    fn delete_overridden_history(&mut self, slot: i64) -> Result<(), GeyserPluginError> {
+        for retention in &mut self.overrides {
+            let cutoff = slot - retention.retention_slots;
+            let from_slot = retention.deleted_until.unwrap_or(i64::MIN);
+            if from_slot >= cutoff {
+                continue;
+            }
+            self.client
+                .execute(
+                    "DELETE FROM account_audit WHERE owner = ANY($1) AND slot >= $2 AND slot < $3",
+                    &[&retention.owners, &from_slot, &cutoff],
+                )
+                .map_err(|err| to_retention_error("delete the old rows of the owners", err))?;
+            retention.deleted_until = Some(cutoff);
+        }
+        Ok(())
+    }
+
+    /// Maintain the partitions at the interval until the exit.
+    pub(crate) fn spawn(
+        mut self,
+        exit_worker: Arc<AtomicBool>,
+    ) -> JoinHandle<Result<(), GeyserPluginError>> {
+        Builder::new()
+            .name("audit-retention".to_string())
+            .spawn(move || -> Result<(), GeyserPluginError> {
+                let mut last_maintained = Instant::now();
+                while !exit_worker.load(Ordering::Relaxed) {
+                    sleep(AUDIT_RETENTION_CHECK_INTERVAL);
+                    if last_maintained.elapsed() >= AUDIT_RETENTION_INTERVAL {
+                        // The errors are logged, the maintenance is attempted again at
+                        // the next interval.
+                        let _ = self.maintain();
+                        last_maintained = Instant::now();
+                    }
+                }
+                Ok(())
+            })
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retention_overrides() {
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_value(serde_json::json!({
+            "account_audit_retention_overrides": {
+                "11111111111111111111111111111111": 1512000,
+                "Vote111111111111111111111111111111111111111": 21600,
+                "Stake11111111111111111111111111111111111111": 1512000,
+            }
+        }))
+        .unwrap();
+        let overrides = retention_overrides(&config);
+        assert_eq!(overrides.len(), 2);
+        assert_eq!(overrides[0].retention_slots, 21600);
+        assert_eq!(overrides[0].owners.len(), 1);
+        assert_eq!(overrides[1].retention_slots, 1512000);
+        assert_eq!(
+            overrides[1].owners,
+            [
+                vec![0; 32],
+                bs58::decode("Stake11111111111111111111111111111111111111")
+                    .into_vec()
+                    .unwrap()
+            ]
+        );
+
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str("{}").unwrap();
+        assert!(retention_overrides(&config).is_empty());
+    }
+
+    #[test]
+    fn test_audit_partitions() {
+        assert_eq!(partition_range(0, 1000), (0, 1000));
+        assert_eq!(partition_range(1999, 1000), (1000, 2000));
+        assert_eq!(partition_range(2000, 1000), (2000, 3000));
+
+        assert_eq!(
+            parse_partition_end("FOR VALUES FROM ('0') TO ('216000')"),
+            Some(216000)
+        );
+        assert_eq!(
+            parse_partition_end("FOR VALUES FROM (216000) TO (432000)"),
+            Some(432000)
+        );
+        assert_eq!(parse_partition_end("DEFAULT"), None);
+
+        let (set_retention, run_maintenance) = partman_statements("partman");
+        assert_eq!(
+            set_retention,
+            "UPDATE \"partman\".part_config SET retention = $1, retention_keep_table = false \
+            WHERE parent_table = current_schema() || '.account_audit'"
+        );
+        assert_eq!(
+            run_maintenance,
+            "SELECT \"partman\".run_maintenance(p_parent_table => current_schema() || '.account_audit')"
+        );
+    }
+}
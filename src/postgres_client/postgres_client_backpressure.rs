@@ -0,0 +1,127 @@
+/// Module responsible for blocking the account notifications of the validator while
+/// the queue of the workers is past a threshold, for a bounded time, so that the nodes
+/// archiving the accounts trade the throughput of the validator for keeping up with it
+/// instead of buffering the updates up to the capacity of the queue.
+use {
+    crate::{
+        accountsdb_plugin_postgres::AccountsDbPluginPostgresConfig,
+        postgres_client::{postgres_client_flow_control::FlowControl, DbWorkItem},
+    },
+    crossbeam_channel::Sender,
+    log::*,
+    solana_metrics::*,
+    std::{
+        sync::Arc,
+        thread::sleep,
+        time::{Duration, Instant},
+    },
+};
+
+/// The interval the depth of the queue is checked at while blocked
+const BACKPRESSURE_POLL_INTERVAL: Duration = Duration::from_millis(1);
+const DEFAULT_BACKPRESSURE_MAX_BLOCK_MS: u64 = 1000;
+
+pub(crate) struct Backpressure {
+    /// The depth of the queue from which the account updates are blocked
+    queue_threshold: usize,
+    /// The longest an account update is blocked for
+    max_block: Duration,
+    /// The signal of the consumers tightening the threshold, when the flow control is
+    /// configured
+    flow_control: Option<Arc<FlowControl>>,
+}
+
+impl Backpressure {
+    pub(crate) fn new(
+        config: &AccountsDbPluginPostgresConfig,
+        flow_control: Option<Arc<FlowControl>>,
+    ) -> Option<Self> {
+        let queue_threshold = config.backpressure_queue_threshold?;
+        let max_block_ms = config
+            .backpressure_max_block_ms
+            .unwrap_or(DEFAULT_BACKPRESSURE_MAX_BLOCK_MS);
+        Some(Self {
+            queue_threshold,
+            max_block: Duration::from_millis(max_block_ms),
+            flow_control,
+        })
+    }
+
+    fn queue_threshold(&self) -> usize {
+        match &self.flow_control {
+            Some(flow_control) => flow_control.queue_threshold(self.queue_threshold),
+            None => self.queue_threshold,
+        }
+    }
+
+    /// Block while the queue the update is sent to holds at least the threshold of work
+    /// items, up to the max block time, after which the update is queued regardless.
+    /// Returns whether the queue drained below the threshold within the time.
+    pub(crate) fn wait(&self, sender: &Sender<DbWorkItem>) -> bool {
+        if sender.len() < self.queue_threshold() {
+            return true;
+        }
+        let started = Instant::now();
+        let drained = loop {
+            if sender.len() < self.queue_threshold() {
+                break true;
+            }
+            if started.elapsed() >= self.max_block {
+                break false;
+            }
+            sleep(BACKPRESSURE_POLL_INTERVAL);
+        };
+        inc_new_counter_info!(
+            "accountsdb-plugin-postgres-backpressure-blocked-us",
+            started.elapsed().as_micros() as usize,
+            1000,
+            1000
+        );
+        if !drained {
+            inc_new_counter_info!("accountsdb-plugin-postgres-backpressure-timeouts", 1);
+        }
+        drained
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{DbWorkItem, UpdateSlotRequest},
+        agave_geyser_plugin_interface::geyser_plugin_interface::SlotStatus,
+        crossbeam_channel::bounded,
+        std::thread,
+    };
+
+    #[test]
+    fn test_backpressure_wait() {
+        let backpressure = Backpressure {
+            queue_threshold: 2,
+            max_block: Duration::from_millis(50),
+            flow_control: None,
+        };
+        let (sender, receiver) = bounded(4);
+        let slot = |slot| {
+            DbWorkItem::UpdateSlot(Box::new(UpdateSlotRequest {
+                slot,
+                parent: None,
+                slot_status: SlotStatus::Processed,
+            }))
+        };
+        sender.send(slot(1)).unwrap();
+        assert!(backpressure.wait(&sender));
+
+        sender.send(slot(2)).unwrap();
+        let started = Instant::now();
+        assert!(!backpressure.wait(&sender));
+        assert!(started.elapsed() >= backpressure.max_block);
+
+        let consumer = thread::spawn(move || {
+            sleep(Duration::from_millis(10));
+            receiver.recv().unwrap();
+        });
+        assert!(backpressure.wait(&sender));
+        consumer.join().unwrap();
+    }
+}
@@ -0,0 +1,283 @@
+/// Module responsible for verifying the chain of blocks stored in the PostgreSQL
+/// database, flagging the slot ranges needing to be backfilled.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::{DbWorkItem, ParallelPostgresClient, SimplePostgresClient},
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    postgres::{Client, Statement},
+    std::sync::atomic::Ordering,
+};
+
+const MISSING_PARENT: &str = "missing_parent";
+/// Select the blocks in a range of slots whose parent block is missing or has another
+/// blockhash than their parent blockhash. The lowest block stored is left out, its
+/// parent being before the first slot stored rather than missing.
+const BLOCK_CHAIN_VERIFICATION_SQL: &str = "SELECT b.slot, b.parent_slot, \
+    p.slot IS NULL AS parent_missing, \
+    (SELECT MAX(l.slot) FROM block l WHERE l.slot < b.parent_slot) AS previous_stored_slot \
+    FROM block b LEFT JOIN block p ON p.slot = b.parent_slot \
+    WHERE b.slot > $1 AND b.slot <= $2 AND b.parent_slot IS NOT NULL \
+    AND b.slot > (SELECT MIN(f.slot) FROM block f) \
+    AND (p.slot IS NULL OR p.blockhash IS DISTINCT FROM b.parent_blockhash)";
+const PARENT_BLOCKHASH_MISMATCH: &str = "parent_blockhash_mismatch";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DbBlockChainGap {
+    pub start_slot: i64,
+    pub end_slot: i64,
+    pub reason: &'static str,
+}
+
+#[derive(Clone)]
+pub struct VerifyBlockChainRequest {
+    /// The verification covers the blocks after this slot
+    pub from_slot: u64,
+    /// The verification covers the blocks up to and including this slot
+    pub to_slot: u64,
+}
+
+/// The range of the blocks to verify once the slot is rooted, from the slot verified
+/// up to, or one interval before the range for the first verification, to the slots
+/// one interval behind the rooted slot. None until the range spans an interval, so
+/// that the range is verified whichever slots of the interval are skipped.
+fn verification_range(
+    rooted_slot: u64,
+    verified_until: Option<u64>,
+    interval: u64,
+) -> Option<VerifyBlockChainRequest> {
+    let to_slot = rooted_slot.checked_sub(interval)?;
+    let from_slot = verified_until.unwrap_or_else(|| to_slot.saturating_sub(interval));
+    (to_slot >= from_slot + interval).then_some(VerifyBlockChainRequest { from_slot, to_slot })
+}
+
+/// Build the range needing backfill for a block whose parent is either missing or has
+/// a different blockhash than the one the block refers to. When the parent is missing,
+/// the range spans from the block after the closest stored ancestor to the parent.
+fn build_block_chain_gap(
+    slot: i64,
+    parent_slot: i64,
+    parent_missing: bool,
+    previous_stored_slot: Option<i64>,
+) -> DbBlockChainGap {
+    if parent_missing {
+        DbBlockChainGap {
+            start_slot: previous_stored_slot.map_or(parent_slot, |previous| previous + 1),
+            end_slot: parent_slot,
+            reason: MISSING_PARENT,
+        }
+    } else {
+        DbBlockChainGap {
+            start_slot: parent_slot,
+            end_slot: slot,
+            reason: PARENT_BLOCKHASH_MISMATCH,
+        }
+    }
+}
+
+impl SimplePostgresClient {
+    fn prepare_block_chain_statement(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+        stmt: &str,
+    ) -> Result<Statement, GeyserPluginError> {
+        let stmt = client.prepare(stmt);
+
+        match stmt {
+            Err(err) => {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "Error in preparing for the block chain verification PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                        err, config.host, config.user, config
+                    ),
+                })))
+            }
+            Ok(stmt) => Ok(stmt),
+        }
+    }
+
+    /// Build the statement selecting the blocks in a range of slots whose parent block is
+    /// missing or has another blockhash than their parent blockhash.
+    pub fn build_block_chain_verification_statement(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Statement, GeyserPluginError> {
+        Self::prepare_block_chain_statement(client, config, BLOCK_CHAIN_VERIFICATION_SQL)
+    }
+
+    /// Build the statement recording a gap found in the chain of the blocks into the
+    /// block_chain_gap table.
+    pub fn build_block_chain_gap_upsert_statement(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Statement, GeyserPluginError> {
+        let stmt = "INSERT INTO block_chain_gap (start_slot, end_slot, reason, detected_on) \
+        VALUES ($1, $2, $3, $4) \
+        ON CONFLICT (start_slot, end_slot) DO UPDATE SET reason=excluded.reason, detected_on=excluded.detected_on";
+
+        Self::prepare_block_chain_statement(client, config, stmt)
+    }
+
+    pub(crate) fn verify_block_chain_impl(
+        &mut self,
+        request: &VerifyBlockChainRequest,
+    ) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        let (verification_stmt, gap_upsert_stmt) = match (
+            &client.verify_block_chain_stmt,
+            &client.upsert_block_chain_gap_stmt,
+        ) {
+            (Some(verification_stmt), Some(gap_upsert_stmt)) => {
+                (verification_stmt, gap_upsert_stmt)
+            }
+            _ => return Ok(()),
+        };
+        let client = &mut client.client;
+
+        let from_slot = request.from_slot as i64;
+        let to_slot = request.to_slot as i64;
+        // The blocks are read from the verification replica once it has caught up
+        let replica_rows = self.verification_replica.as_mut().and_then(|replica| {
+            replica.query_replayed(
+                client,
+                BLOCK_CHAIN_VERIFICATION_SQL,
+                &[&from_slot, &to_slot],
+            )
+        });
+        let rows = match replica_rows {
+            Some(rows) => Ok(rows),
+            None => client.query(verification_stmt, &[&from_slot, &to_slot]),
+        }
+        .map_err(|err| {
+            let msg = format!(
+                "Failed to verify the block chain in the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            GeyserPluginError::SlotStatusUpdateError { msg }
+        })?;
+
+        let detected_on = Utc::now().naive_utc();
+        for row in rows {
+            let gap = build_block_chain_gap(row.get(0), row.get(1), row.get(2), row.get(3));
+            warn!(
+                "Detected a block chain gap ({}) at slots [{}, {}], the range needs to be backfilled",
+                gap.reason, gap.start_slot, gap.end_slot
+            );
+            if let Err(err) = client.execute(
+                gap_upsert_stmt,
+                &[&gap.start_slot, &gap.end_slot, &gap.reason, &detected_on],
+            ) {
+                let msg = format!(
+                    "Failed to persist the block chain gap to the PostgreSQL database. Error: {:?}",
+                    err
+                );
+                error!("{}", msg);
+                return Err(GeyserPluginError::SlotStatusUpdateError { msg });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ParallelPostgresClient {
+    /// Verify the block chain periodically as slots are rooted, from the slot last
+    /// verified up to. The verified window trails the rooted slot by one interval to
+    /// let the block metadata of the recent slots be written first.
+    pub(crate) fn verify_block_chain_on_root(
+        &self,
+        rooted_slot: u64,
+    ) -> Result<(), GeyserPluginError> {
+        let interval = match self.block_chain_verification_interval {
+            Some(interval) if interval > 0 => interval,
+            _ => return Ok(()),
+        };
+        // The slot verified up to is stored plus one so that zero stands for none
+        let verified_until = self.block_chain_verified_until.load(Ordering::Relaxed);
+        let Some(request) =
+            verification_range(rooted_slot, verified_until.checked_sub(1), interval)
+        else {
+            return Ok(());
+        };
+        if self
+            .block_chain_verified_until
+            .compare_exchange(
+                verified_until,
+                request.to_slot + 1,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            return Ok(());
+        }
+        let to_slot = request.to_slot;
+        if let Err(err) = self
+            .queue_overflow
+            .send(DbWorkItem::VerifyBlockChain(Box::new(request)))
+        {
+            return Err(GeyserPluginError::SlotStatusUpdateError {
+                msg: format!(
+                    "Failed to verify the block chain up to slot {:?}, error: {:?}",
+                    to_slot, err
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verification_range() {
+        let range = |rooted_slot, verified_until| {
+            verification_range(rooted_slot, verified_until, 32)
+                .map(|request| (request.from_slot, request.to_slot))
+        };
+        assert_eq!(range(10, None), None);
+        assert_eq!(range(1000, None), Some((936, 968)));
+        assert_eq!(range(1031, Some(968)), None);
+        // The rooted slot completing the interval is skipped
+        assert_eq!(range(1033, Some(968)), Some((968, 1001)));
+        assert_eq!(range(1200, Some(968)), Some((968, 1168)));
+    }
+
+    #[test]
+    fn test_build_block_chain_gap() {
+        assert_eq!(
+            build_block_chain_gap(100, 98, true, Some(90)),
+            DbBlockChainGap {
+                start_slot: 91,
+                end_slot: 98,
+                reason: MISSING_PARENT,
+            }
+        );
+
+        assert_eq!(
+            build_block_chain_gap(100, 98, true, None),
+            DbBlockChainGap {
+                start_slot: 98,
+                end_slot: 98,
+                reason: MISSING_PARENT,
+            }
+        );
+
+        assert_eq!(
+            build_block_chain_gap(100, 98, false, Some(98)),
+            DbBlockChainGap {
+                start_slot: 98,
+                end_slot: 100,
+                reason: PARENT_BLOCKHASH_MISMATCH,
+            }
+        );
+    }
+}
@@ -0,0 +1,184 @@
+/// Module responsible for assembling the getBlock-shaped JSON document of a block from
+/// the transactions notified before the block metadata of the slot.
+use {
+    crate::{
+        geyser_interface::{ReplicaBlockInfo, ReplicaTransactionInfo},
+        postgres_client::{
+            postgres_client_transaction::encode_transaction, ParallelPostgresClient,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_transaction_status::{
+        EncodedTransactionWithStatusMeta, UiConfirmedBlock, UiTransactionEncoding,
+    },
+    std::{collections::HashMap, sync::Mutex},
+};
+
+/// The encoded transactions of the slots whose block metadata is not notified yet,
+/// keyed by the slot along with the index of the transaction in the block.
+#[derive(Default)]
+pub(crate) struct BlockJsonBuffer {
+    transactions: Mutex<HashMap<u64, Vec<(usize, EncodedTransactionWithStatusMeta)>>>,
+}
+
+impl BlockJsonBuffer {
+    pub(crate) fn add_transaction(
+        &self,
+        transaction_info: &ReplicaTransactionInfo,
+        slot: u64,
+    ) -> Result<(), GeyserPluginError> {
+        let transaction = encode_transaction(transaction_info, UiTransactionEncoding::Json)?;
+
+        self.transactions
+            .lock()
+            .unwrap()
+            .entry(slot)
+            .or_default()
+            .push((transaction_info.index, transaction));
+        Ok(())
+    }
+
+    /// Take the buffered transactions of the block and assemble the block JSON, in the
+    /// shape of the RPC getBlock response with the full transaction details.
+    pub(crate) fn take_block_json(
+        &self,
+        block_info: &ReplicaBlockInfo,
+    ) -> Result<serde_json::Value, GeyserPluginError> {
+        let mut transactions = self
+            .transactions
+            .lock()
+            .unwrap()
+            .remove(&block_info.slot)
+            .unwrap_or_default();
+        transactions.sort_by_key(|(index, _)| *index);
+
+        let block = UiConfirmedBlock {
+            previous_blockhash: block_info.parent_blockhash.to_string(),
+            blockhash: block_info.blockhash.to_string(),
+            parent_slot: block_info.parent_slot,
+            transactions: Some(
+                transactions
+                    .into_iter()
+                    .map(|(_, transaction)| transaction)
+                    .collect(),
+            ),
+            signatures: None,
+            rewards: Some(block_info.rewards.rewards.clone()),
+            num_reward_partitions: block_info.rewards.num_partitions,
+            block_time: block_info.block_time,
+            block_height: block_info.block_height,
+        };
+        serde_json::to_value(block).map_err(|err| GeyserPluginError::SlotStatusUpdateError {
+            msg: format!(
+                "Failed to serialize the block JSON at slot {}. Error: {:?}",
+                block_info.slot, err
+            ),
+        })
+    }
+
+    /// Drop the transactions of the slot which will not be notified the block metadata,
+    /// as it is dead.
+    pub(crate) fn remove_slot(&self, slot: u64) {
+        self.transactions.lock().unwrap().remove(&slot);
+    }
+
+    /// Drop the transactions of the slots up to the rooted slot, the slots left are on
+    /// the abandoned forks.
+    pub(crate) fn remove_slots_up_to(&self, rooted_slot: u64) {
+        self.transactions
+            .lock()
+            .unwrap()
+            .retain(|slot, _| *slot > rooted_slot);
+    }
+}
+
+impl ParallelPostgresClient {
+    /// Buffer the transaction for the block JSON and the fee for the slot economics of
+    /// the slot, a no-op when neither is stored. The transaction is counted as notified
+    /// for the ingest SLA too.
+    pub fn buffer_block_transaction(
+        &self,
+        transaction_info: &ReplicaTransactionInfo,
+        slot: u64,
+    ) -> Result<(), GeyserPluginError> {
+        self.record_ingest_notified_transaction(slot);
+        if let Some(slot_fee_buffer) = &self.slot_fee_buffer {
+            slot_fee_buffer.add_transaction(transaction_info, slot);
+        }
+        match &self.block_json_buffer {
+            Some(block_json_buffer) => block_json_buffer.add_transaction(transaction_info, slot),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::postgres_client_transaction::tests::TestTransaction,
+        solana_sdk::{hash::Hash, pubkey::Pubkey, signature::Keypair},
+        solana_transaction_status::RewardsAndNumPartitions,
+    };
+
+    #[test]
+    fn test_block_json() {
+        let buffer = BlockJsonBuffer::default();
+        let keypair = Keypair::new();
+        let mut signatures = vec![];
+        for index in [1, 0] {
+            let mut transaction = TestTransaction::new(solana_system_transaction::transfer(
+                &keypair,
+                &Pubkey::new_unique(),
+                index as u64 + 1,
+                Hash::default(),
+            ));
+            transaction.meta.fee = 5000;
+            transaction.index = index;
+            signatures.push(transaction.signature.to_string());
+            let transaction_info = transaction.transaction_info();
+            buffer.add_transaction(&transaction_info, 7).unwrap();
+            buffer.add_transaction(&transaction_info, 8).unwrap();
+        }
+
+        let rewards = RewardsAndNumPartitions {
+            rewards: vec![],
+            num_partitions: None,
+        };
+        let blockhash = Hash::new_unique().to_string();
+        let parent_blockhash = Hash::new_unique().to_string();
+        let block_info = ReplicaBlockInfo {
+            parent_slot: 6,
+            parent_blockhash: &parent_blockhash,
+            slot: 7,
+            blockhash: &blockhash,
+            rewards: &rewards,
+            block_time: Some(1_700_000_000),
+            block_height: Some(5),
+            executed_transaction_count: 2,
+            entry_count: 1,
+        };
+        let block_json = buffer.take_block_json(&block_info).unwrap();
+        assert_eq!(block_json["blockhash"], blockhash);
+        assert_eq!(block_json["previousBlockhash"], parent_blockhash);
+        assert_eq!(block_json["parentSlot"], 6);
+        assert_eq!(block_json["blockHeight"], 5);
+        let transactions = block_json["transactions"].as_array().unwrap();
+        assert_eq!(transactions.len(), 2);
+        // The transactions are ordered by the index in the block
+        assert_eq!(
+            transactions[0]["transaction"]["signatures"][0],
+            signatures[1]
+        );
+        assert_eq!(
+            transactions[1]["transaction"]["signatures"][0],
+            signatures[0]
+        );
+        assert_eq!(transactions[0]["meta"]["fee"], 5000);
+
+        // The slot is taken, the slot of the abandoned fork dropped at the root
+        assert!(buffer.transactions.lock().unwrap().contains_key(&8));
+        buffer.remove_slots_up_to(8);
+        assert!(buffer.transactions.lock().unwrap().is_empty());
+    }
+}
@@ -3,91 +3,244 @@ use {
         accountsdb_plugin_postgres::{
             AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
         },
+        geyser_interface::ReplicaBlockInfo,
         postgres_client::{
-            postgres_client_transaction::DbReward, SimplePostgresClient, UpdateBlockMetadataRequest,
+            build_stake_rewards, build_validator_rewards,
+            postgres_client_stake_reward::stake_reward_epoch,
+            postgres_client_transaction::{DbReward, DbRewardType},
+            postgres_client_write_stats::WriteStats,
+            SimplePostgresClient, StaticColumns, UpdateBlockMetadataRequest,
+            DEFAULT_STORE_BLOCK_JSON,
         },
     },
-    agave_geyser_plugin_interface::geyser_plugin_interface::{
-        GeyserPluginError, ReplicaBlockInfoV4,
-    },
-    chrono::Utc,
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::{NaiveDateTime, Utc},
     log::*,
     postgres::{Client, Statement},
+    postgres_types::ToSql,
+    serde_derive::{Deserialize, Serialize},
+    serde_json::json,
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DbBlockInfo {
     pub slot: i64,
     pub blockhash: String,
+    pub parent_slot: i64,
+    pub parent_blockhash: String,
     pub rewards: Vec<DbReward>,
+    /// The partitions the staking rewards of the epoch are distributed across
+    pub num_reward_partitions: Option<i64>,
     pub block_time: Option<i64>,
     pub block_height: Option<i64>,
+    /// The identity of the leader of the block, the one paid the fee reward
+    pub leader: Option<String>,
+    pub executed_transaction_count: i64,
+    pub entry_count: i64,
+}
+
+/// Get the identity of the leader of the block from its rewards, the fee reward being
+/// paid to the leader. None when the block has no fee reward, such as without any fee.
+pub fn block_leader(rewards: &[DbReward]) -> Option<String> {
+    rewards
+        .iter()
+        .find(|reward| reward.reward_type == Some(DbRewardType::Fee))
+        .map(|reward| reward.pubkey.clone())
 }
 
-impl<'a> From<&ReplicaBlockInfoV4<'a>> for DbBlockInfo {
-    fn from(block_info: &ReplicaBlockInfoV4) -> Self {
+impl<'a> From<&ReplicaBlockInfo<'a>> for DbBlockInfo {
+    fn from(block_info: &ReplicaBlockInfo) -> Self {
+        let rewards: Vec<DbReward> = block_info
+            .rewards
+            .rewards
+            .iter()
+            .map(DbReward::from)
+            .collect();
         Self {
             slot: block_info.slot as i64,
             blockhash: block_info.blockhash.to_string(),
-            rewards: block_info
-                .rewards
+            parent_slot: block_info.parent_slot as i64,
+            parent_blockhash: block_info.parent_blockhash.to_string(),
+            leader: block_leader(&rewards),
+            rewards,
+            num_reward_partitions: block_info
                 .rewards
-                .iter()
-                .map(DbReward::from)
-                .collect(),
+                .num_partitions
+                .map(|num_partitions| num_partitions as i64),
             block_time: block_info.block_time,
             block_height: block_info
                 .block_height
                 .map(|block_height| block_height as i64),
+            executed_transaction_count: block_info.executed_transaction_count as i64,
+            entry_count: block_info.entry_count as i64,
         }
     }
 }
 
+/// The parameters of the upsert of the metadata of a block, with the block JSON when it is
+/// stored.
+pub(crate) fn block_metadata_params<'a>(
+    block_info: &'a DbBlockInfo,
+    updated_on: &'a NaiveDateTime,
+    block_json: &'a Option<serde_json::Value>,
+    store_block_json: bool,
+) -> Vec<&'a (dyn ToSql + Sync)> {
+    let mut params: Vec<&(dyn ToSql + Sync)> = vec![
+        &block_info.slot,
+        &block_info.blockhash,
+        &block_info.parent_slot,
+        &block_info.parent_blockhash,
+        &block_info.rewards,
+        &block_info.block_time,
+        &block_info.block_height,
+        updated_on,
+        &block_info.leader,
+        &block_info.executed_transaction_count,
+        &block_info.entry_count,
+    ];
+    if store_block_json {
+        params.push(block_json);
+    }
+    params
+}
+
 impl SimplePostgresClient {
-    pub(crate) fn build_block_metadata_upsert_statement(
+    /// Build the upsert of the metadata of a block, its parameters being the ones of
+    /// `block_metadata_params`.
+    pub(crate) fn block_metadata_upsert_sql(config: &AccountsDbPluginPostgresConfig) -> String {
+        let static_columns = StaticColumns::new(config);
+        let (block_json_column, block_json_value) =
+            if config.store_block_json.unwrap_or(DEFAULT_STORE_BLOCK_JSON) {
+                (", block_json", ", $12")
+            } else {
+                ("", "")
+            };
+        format!(
+            "INSERT INTO block (slot, blockhash, parent_slot, parent_blockhash, rewards, block_time, block_height, updated_on, \
+        leader, executed_transaction_count, entry_count{}{}) \
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11{}{})", block_json_column, static_columns.names, block_json_value, static_columns.values)
+    }
+
+    /// Build the statement upserting the metadata of a block into the block table.
+    pub fn build_block_metadata_upsert_statement(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Statement, GeyserPluginError> {
+        let stmt = client.prepare(&Self::block_metadata_upsert_sql(config));
+
+        match stmt {
+            Err(err) => {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "Error in preparing for the block metadata update PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                        err, config.host, config.user, config
+                    ),
+                })))
+            }
+            Ok(stmt) => Ok(stmt),
+        }
+    }
+
+    /// Build the statement inserting the rewards of a block into the block_reward table.
+    pub fn build_block_reward_insert_statement(
         client: &mut Client,
         config: &AccountsDbPluginPostgresConfig,
     ) -> Result<Statement, GeyserPluginError> {
-        let stmt =
-            "INSERT INTO block (slot, blockhash, rewards, block_time, block_height, updated_on) \
-        VALUES ($1, $2, $3, $4, $5, $6)";
+        let stmt = "INSERT INTO block_reward (slot, reward_index, pubkey, lamports, post_balance, \
+            reward_type, commission, updated_on) \
+            SELECT $1, t.reward_index, t.pubkey, t.lamports, t.post_balance, t.reward_type, t.commission, $2 \
+            FROM UNNEST($3::INT[], $4::VARCHAR[], $5::BIGINT[], $6::BIGINT[], $7::\"RewardType\"[], $8::SMALLINT[]) \
+            AS t (reward_index, pubkey, lamports, post_balance, reward_type, commission) \
+            ON CONFLICT DO NOTHING";
 
         let stmt = client.prepare(stmt);
 
         match stmt {
             Err(err) => {
-                return Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
                     msg: format!(
-                        "Error in preparing for the block metadata update PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                        "Error in preparing for the block reward insert PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
                         err, config.host, config.user, config
                     ),
-                })));
+                })))
             }
             Ok(stmt) => Ok(stmt),
         }
     }
 
+    /// Insert the rewards of the block, one row per reward in the order of the block.
+    fn insert_block_rewards(
+        client: &mut Client,
+        statement: &Statement,
+        slot: i64,
+        rewards: &[DbReward],
+        write_stats: &WriteStats,
+    ) -> Result<(), GeyserPluginError> {
+        if rewards.is_empty() {
+            return Ok(());
+        }
+        let updated_on = Utc::now().naive_utc();
+        let reward_indexes: Vec<i32> = (0..rewards.len() as i32).collect();
+        let pubkeys: Vec<&str> = rewards
+            .iter()
+            .map(|reward| reward.pubkey.as_str())
+            .collect();
+        let lamports: Vec<i64> = rewards.iter().map(|reward| reward.lamports).collect();
+        let post_balances: Vec<i64> = rewards.iter().map(|reward| reward.post_balance).collect();
+        let reward_types: Vec<&Option<DbRewardType>> =
+            rewards.iter().map(|reward| &reward.reward_type).collect();
+        let commissions: Vec<Option<i16>> =
+            rewards.iter().map(|reward| reward.commission).collect();
+
+        let params: [&(dyn ToSql + Sync); 8] = [
+            &slot,
+            &updated_on,
+            &reward_indexes,
+            &pubkeys,
+            &lamports,
+            &post_balances,
+            &reward_types,
+            &commissions,
+        ];
+        let result = client.execute(statement, &params);
+
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to persist the block rewards to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::SlotStatusUpdateError { msg });
+        }
+        write_stats.record("block_reward", rewards.len(), statement, &params);
+        Ok(())
+    }
+
     pub(crate) fn update_block_metadata_impl(
         &mut self,
-        block_info: UpdateBlockMetadataRequest,
+        block_info: &UpdateBlockMetadataRequest,
     ) -> Result<(), GeyserPluginError> {
+        let store_block_json = self.store_block_json;
+        let slots_per_epoch = self.slots_per_epoch as i64;
         let client = self.client.get_mut().unwrap();
         let statement = &client.update_block_metadata_stmt;
+        let block_reward_statement = &client.insert_block_reward_stmt;
+        let slot_economics_statement = &client.upsert_slot_economics_stmt;
+        let validator_reward_statement = &client.insert_validator_reward_stmt;
+        let stake_reward_statement = &client.upsert_stake_reward_stmt;
+        let post_commit_hooks = &client.post_commit_hooks;
+        let notifier = &client.notifier;
+        let write_stats = &client.write_stats;
         let client = &mut client.client;
         let updated_on = Utc::now().naive_utc();
 
-        let block_info = block_info.block_info;
-        let result = client.query(
-            statement,
-            &[
-                &block_info.slot,
-                &block_info.blockhash,
-                &block_info.rewards,
-                &block_info.block_time,
-                &block_info.block_height,
-                &updated_on,
-            ],
-        );
+        let UpdateBlockMetadataRequest {
+            block_info,
+            block_json,
+            slot_economics,
+        } = block_info;
+        let params = block_metadata_params(block_info, &updated_on, block_json, store_block_json);
+        let result = client.query(statement, &params);
 
         if let Err(err) = result {
             let msg = format!(
@@ -96,7 +249,96 @@ impl SimplePostgresClient {
             error!("{}", msg);
             return Err(GeyserPluginError::AccountsUpdateError { msg });
         }
+        write_stats.record("block", 1, statement, &params);
 
-        Ok(())
+        if let Some(statement) = block_reward_statement {
+            Self::insert_block_rewards(
+                client,
+                statement,
+                block_info.slot,
+                &block_info.rewards,
+                write_stats,
+            )?;
+        }
+
+        if let (Some(statement), Some(slot_economics)) = (slot_economics_statement, slot_economics)
+        {
+            Self::upsert_slot_economics(client, statement, slot_economics)?;
+        }
+
+        if let Some(statement) = validator_reward_statement {
+            Self::insert_validator_rewards(
+                client,
+                statement,
+                block_info.slot,
+                block_info.slot / slots_per_epoch,
+                &build_validator_rewards(&block_info.rewards),
+                write_stats,
+            )?;
+        }
+
+        if let Some(statement) = stake_reward_statement {
+            Self::upsert_stake_rewards(
+                client,
+                statement,
+                stake_reward_epoch(block_info.slot, slots_per_epoch),
+                block_info.slot,
+                block_info.num_reward_partitions,
+                &build_stake_rewards(&block_info.rewards),
+                write_stats,
+            )?;
+        }
+
+        notifier.notify(
+            client,
+            "block",
+            [json!({"slot": block_info.slot, "blockhash": block_info.blockhash})],
+        )?;
+        post_commit_hooks.execute(client, "block", 1, block_info.slot, block_info.slot)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        solana_transaction_status::{Reward, RewardType, RewardsAndNumPartitions},
+    };
+
+    #[test]
+    fn test_block_info_from_replica_block_info() {
+        let reward = |pubkey: &str, reward_type| Reward {
+            pubkey: pubkey.to_string(),
+            lamports: 1000,
+            post_balance: 5000,
+            reward_type,
+            commission: None,
+        };
+        let rewards = RewardsAndNumPartitions {
+            rewards: vec![
+                reward("vote-account", Some(RewardType::Voting)),
+                reward("leader-identity", Some(RewardType::Fee)),
+            ],
+            num_partitions: None,
+        };
+        let block_info = ReplicaBlockInfo {
+            parent_slot: 99,
+            parent_blockhash: "parent-blockhash",
+            slot: 100,
+            blockhash: "blockhash",
+            rewards: &rewards,
+            block_time: Some(1700000000),
+            block_height: Some(90),
+            executed_transaction_count: 1200,
+            entry_count: 64,
+        };
+        let block_info = DbBlockInfo::from(&block_info);
+        assert_eq!(block_info.parent_slot, 99);
+        assert_eq!(block_info.leader.as_deref(), Some("leader-identity"));
+        assert_eq!(block_info.executed_transaction_count, 1200);
+        assert_eq!(block_info.entry_count, 64);
+        assert_eq!(block_info.rewards.len(), 2);
+
+        assert_eq!(block_leader(&block_info.rewards[..1]), None);
     }
 }
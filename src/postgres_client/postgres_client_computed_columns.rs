@@ -0,0 +1,188 @@
+/// Module responsible for the computed columns of the account table, the columns whose
+/// values are computed from the account by the SQL expressions of the config, such as
+/// the length of the data, so that the minor customizations of the schema do not
+/// require changing the statements. The discriminator column of the accounts of the
+/// discriminator_owners is computed the same way.
+use {
+    crate::{
+        accountsdb_plugin_postgres::AccountsDbPluginPostgresConfig,
+        postgres_client::ACCOUNT_COLUMN_COUNT,
+    },
+    std::collections::BTreeMap,
+};
+
+/// The columns of an account row the expressions can refer to, in the order of the
+/// parameters of the row, along with their types
+pub(crate) const ACCOUNT_COLUMNS: [(&str, &str); ACCOUNT_COLUMN_COUNT] = [
+    ("pubkey", "BYTEA"),
+    ("slot", "BIGINT"),
+    ("owner", "BYTEA"),
+    ("lamports", "BIGINT"),
+    ("executable", "BOOL"),
+    ("rent_epoch", "BIGINT"),
+    ("data", "BYTEA"),
+    ("write_version", "BIGINT"),
+    ("updated_on", "TIMESTAMP"),
+    ("data_codec", "SMALLINT"),
+    ("txn_signature", "BYTEA"),
+];
+
+/// The column of the discriminators of the accounts of the discriminator_owners
+pub(crate) const DISCRIMINATOR_COLUMN: &str = "discriminator";
+
+/// The expression of the discriminator, the first 8 bytes of the data of the accounts
+/// of the owners and null for the others
+fn discriminator_expression(owners: &[String]) -> String {
+    let owners = owners
+        .iter()
+        // The owners are checked to be base58 encoded when the config is loaded
+        .filter_map(|owner| bs58::decode(owner).into_vec().ok())
+        .map(|owner| {
+            let owner: String = owner.iter().map(|byte| format!("{:02x}", byte)).collect();
+            format!("'\\x{}'::BYTEA", owner)
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "CASE WHEN {{owner}} IN ({}) THEN substring({{data}} from 1 for 8) END",
+        owners
+    )
+}
+
+/// Render the expression of a computed column for the account row of the parameters
+/// after `row`, replacing the `{column}` placeholders with the typed parameters.
+/// Returns the name of the first unknown or unterminated placeholder on error.
+fn render_expression(expression: &str, row: usize) -> Result<String, String> {
+    let mut rendered = String::default();
+    let mut rest = expression;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            return Err(rest[start..].to_string());
+        };
+        let name = &rest[start + 1..start + end];
+        let Some(index) = ACCOUNT_COLUMNS
+            .iter()
+            .position(|(column, _)| *column == name)
+        else {
+            return Err(name.to_string());
+        };
+        rendered.push_str(&format!(
+            "${}::{}",
+            row + index + 1,
+            ACCOUNT_COLUMNS[index].1
+        ));
+        rest = &rest[start + end + 1..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// Check the expression of a computed column refers to the account columns only,
+/// returning the invalid placeholder otherwise.
+pub(crate) fn check_expression(expression: &str) -> Result<(), String> {
+    render_expression(expression, 0).map(|_| ())
+}
+
+/// The computed columns configured to be appended to the upserts of the account table
+#[derive(Debug)]
+pub(crate) struct ComputedColumns {
+    columns: BTreeMap<String, String>,
+}
+
+impl ComputedColumns {
+    pub(crate) fn new(config: &AccountsDbPluginPostgresConfig) -> Self {
+        let mut columns = config.computed_columns.clone().unwrap_or_default();
+        if let Some(owners) = &config.discriminator_owners {
+            columns.insert(
+                DISCRIMINATOR_COLUMN.to_string(),
+                discriminator_expression(owners),
+            );
+        }
+        Self { columns }
+    }
+
+    /// The column names, e.g. ", discriminator"
+    pub(crate) fn names(&self) -> String {
+        self.columns
+            .keys()
+            .map(|name| format!(", {}", name))
+            .collect()
+    }
+
+    /// The expressions of the account row of the parameters after `row`, e.g.
+    /// ", (substring($7::BYTEA from 1 for 8))"
+    pub(crate) fn values(&self, row: usize) -> String {
+        self.columns
+            .values()
+            .map(|expression| {
+                // The expressions are checked when the config is loaded
+                let expression = render_expression(expression, row).unwrap();
+                format!(", ({})", expression)
+            })
+            .collect()
+    }
+
+    /// The assignments of the conflict handling, e.g. ", discriminator=excluded.discriminator"
+    pub(crate) fn updates(&self) -> String {
+        self.columns
+            .keys()
+            .map(|name| format!(", {}=excluded.{}", name, name))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_computed_columns() {
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str(
+            "{\"computed_columns\": {\"discriminator\": \"substring({data} from 1 for 8)\", \
+            \"data_len\": \"length({data})\"}}",
+        )
+        .unwrap();
+        let computed_columns = ComputedColumns::new(&config);
+        assert_eq!(computed_columns.names(), ", data_len, discriminator");
+        assert_eq!(
+            computed_columns.values(9),
+            ", (length($16::BYTEA)), (substring($16::BYTEA from 1 for 8))"
+        );
+        assert_eq!(
+            computed_columns.updates(),
+            ", data_len=excluded.data_len, discriminator=excluded.discriminator"
+        );
+
+        assert_eq!(
+            render_expression("{lamports} > 0 AND {owner} = {pubkey}", 0).unwrap(),
+            "$4::BIGINT > 0 AND $3::BYTEA = $1::BYTEA"
+        );
+        assert_eq!(check_expression("{lamport}"), Err("lamport".to_string()));
+        assert_eq!(check_expression("length({data"), Err("{data".to_string()));
+
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str("{}").unwrap();
+        let computed_columns = ComputedColumns::new(&config);
+        assert!(computed_columns.names().is_empty());
+        assert!(computed_columns.values(0).is_empty());
+    }
+
+    #[test]
+    fn test_discriminator_column() {
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str(
+            "{\"discriminator_owners\": [\"11111111111111111111111111111111\"], \
+            \"computed_columns\": {\"data_len\": \"length({data})\"}}",
+        )
+        .unwrap();
+        let computed_columns = ComputedColumns::new(&config);
+        assert_eq!(computed_columns.names(), ", data_len, discriminator");
+        assert_eq!(
+            computed_columns.values(0),
+            format!(
+                ", (length($7::BYTEA)), (CASE WHEN $3::BYTEA IN ('\\x{}'::BYTEA) \
+                THEN substring($7::BYTEA from 1 for 8) END)",
+                "00".repeat(32)
+            )
+        );
+    }
+}
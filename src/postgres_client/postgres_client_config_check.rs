@@ -0,0 +1,117 @@
+/// Module responsible for checking the databases of a config outside of a validator, for
+/// the check_config tool, with the checks the plugin runs when it is loaded: connecting
+/// to each database, checking the version of its schema, and comparing its tables and
+/// indexes with the ones the plugin expects.
+use {
+    crate::{
+        accountsdb_plugin_postgres::AccountsDbPluginPostgresConfig,
+        postgres_client::{
+            postgres_client_schema::{
+                schema_version, DEFAULT_AUTO_MIGRATE, DEFAULT_CREATE_SCHEMA, SCHEMA_VERSION,
+            },
+            postgres_client_schema_drift::schema_drifts,
+            postgres_client_sharding::shard_configs,
+            SimplePostgresClient,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+};
+
+/// The check of a database of the config
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DatabaseCheckReport {
+    /// The version of the schema, None when the account table is missing
+    pub schema_version: Option<i32>,
+    /// The problems failing the loading of the plugin, or breaking its writes
+    pub problems: Vec<String>,
+}
+
+/// The problem of the version of the schema failing the loading of the plugin, the
+/// missing schema being created and the older one migrated when configured so.
+fn schema_version_problem(
+    config: &AccountsDbPluginPostgresConfig,
+    version: Option<i32>,
+) -> Option<String> {
+    let schema = config.schema.as_deref().unwrap_or("public");
+    match version {
+        None if !config.create_schema.unwrap_or(DEFAULT_CREATE_SCHEMA) => Some(format!(
+            "The schema {} has no account table, create it with scripts/create_schema.sql \
+            or set \"create_schema\" to 'true'",
+            schema
+        )),
+        Some(version)
+            if version < SCHEMA_VERSION
+                && !config.auto_migrate.unwrap_or(DEFAULT_AUTO_MIGRATE) =>
+        {
+            Some(format!(
+                "The schema {} is at the version {}, older than the version {} the plugin writes to, \
+                apply the migrations after the version {} in scripts/migrations or set \"auto_migrate\" to 'true'",
+                schema, version, SCHEMA_VERSION, version
+            ))
+        }
+        _ => None,
+    }
+}
+
+impl SimplePostgresClient {
+    /// Check the database of the config. The tables and the indexes are compared with
+    /// the expected ones once the schema is at the version of the plugin, the scratch
+    /// schema the expected schema is read from being rolled back.
+    fn check_database(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<DatabaseCheckReport, GeyserPluginError> {
+        let mut client = Self::connect_to_db(config)?;
+        let version = schema_version(&mut client)?;
+        let mut problems: Vec<_> = schema_version_problem(config, version)
+            .into_iter()
+            .collect();
+        if version == Some(SCHEMA_VERSION) {
+            problems.extend(
+                schema_drifts(&mut client, config)?
+                    .into_iter()
+                    .map(|drift| format!("The schema drifted, {}", drift)),
+            );
+        }
+        Ok(DatabaseCheckReport {
+            schema_version: version,
+            problems,
+        })
+    }
+
+    /// Check the databases of the config, in the order of the connection strings of the
+    /// sharding.
+    pub fn check_databases(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Vec<Result<DatabaseCheckReport, GeyserPluginError>> {
+        shard_configs(config)
+            .iter()
+            .map(Self::check_database)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_version_problem() {
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(schema_version_problem(&config, Some(SCHEMA_VERSION)), None);
+        assert_eq!(
+            schema_version_problem(&config, Some(SCHEMA_VERSION + 1)),
+            None
+        );
+        assert!(schema_version_problem(&config, None)
+            .unwrap()
+            .contains("has no account table"));
+        assert!(schema_version_problem(&config, Some(0))
+            .unwrap()
+            .contains("apply the migrations after the version 0"));
+
+        let config: AccountsDbPluginPostgresConfig =
+            serde_json::from_str("{\"create_schema\": true, \"auto_migrate\": true}").unwrap();
+        assert_eq!(schema_version_problem(&config, None), None);
+        assert_eq!(schema_version_problem(&config, Some(0)), None);
+    }
+}
@@ -0,0 +1,262 @@
+/// Module responsible for the config_history table, recording the settings altering
+/// which tables and columns are written, such as the selectors and the storage modes,
+/// whenever they change: when the plugin is loaded with settings different from the
+/// ones last recorded, and when the selectors are reloaded. Along with the settings,
+/// the changes versus the previous ones are recorded, so that the analysts can explain
+/// the discontinuities of the data.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::SimplePostgresClient,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    serde_json::{json, Map, Value},
+};
+
+pub(crate) const DEFAULT_RECORD_CONFIG_HISTORY: bool = false;
+
+/// The settings altering which tables and columns are written
+const SCHEMA_SETTINGS: [&str; 44] = [
+    "accounts_selector",
+    "startup_accounts_selector",
+    "transaction_selector",
+    "account_versions_selector",
+    "start_slot",
+    "stop_slot",
+    "skip_startup_accounts",
+    "sysvar_sampling_rate",
+    "account_sampling_percentage",
+    "store_account_historical_data",
+    "use_schema_triggers",
+    "account_layout",
+    "account_cold_tier_slots",
+    "storage_engine",
+    "distributed",
+    "static_columns",
+    "computed_columns",
+    "discriminator_owners",
+    "account_data_decoders",
+    "account_data_compression",
+    "token_account_storage",
+    "token_secondary_indexes",
+    "signature_storage",
+    "store_vote_activity",
+    "store_block_json",
+    "store_transaction_json",
+    "store_transaction_addresses",
+    "store_sol_transfers",
+    "store_transaction_memos",
+    "parsed_instruction_programs",
+    "store_normalized_transaction_meta",
+    "store_slot_economics",
+    "store_block_rewards",
+    "store_validator_rewards",
+    "store_stake_rewards",
+    "store_entries",
+    "write_epoch_barriers",
+    "commit_per_slot",
+    "store_slot_transaction_counts",
+    "store_slot_checksums",
+    "fork_cleanup",
+    "account_audit_retention_slots",
+    "account_audit_retention_overrides",
+    "account_audit_archive_path",
+];
+
+/// What the settings recorded are changed by
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ConfigChange {
+    /// The plugin is loaded
+    Load,
+    /// The selectors are reloaded from the config file
+    SelectorReload,
+}
+
+impl ConfigChange {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ConfigChange::Load => "load",
+            ConfigChange::SelectorReload => "selector_reload",
+        }
+    }
+}
+
+/// The value with the fields of its objects not set left out.
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(name, value)| (name, without_nulls(value)))
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.into_iter().map(without_nulls).collect()),
+        value => value,
+    }
+}
+
+/// The settings of the config altering the tables and columns written, the ones not
+/// set being left out.
+fn schema_settings(config: &AccountsDbPluginPostgresConfig) -> Value {
+    let Ok(Value::Object(config)) = serde_json::to_value(config) else {
+        return Value::Object(Map::default());
+    };
+    without_nulls(Value::Object(
+        config
+            .into_iter()
+            .filter(|(name, _)| SCHEMA_SETTINGS.contains(&name.as_str()))
+            .collect(),
+    ))
+}
+
+/// The settings changed from the previous ones, with their previous and new values, a
+/// setting no longer set having a null new value.
+fn settings_diff(previous: &Value, settings: &Value) -> Value {
+    let null = Value::Null;
+    let mut diff = Map::default();
+    for name in SCHEMA_SETTINGS {
+        let previous = previous.get(name).unwrap_or(&null);
+        let value = settings.get(name).unwrap_or(&null);
+        if previous != value {
+            diff.insert(
+                name.to_string(),
+                json!({"previous": previous, "new": value}),
+            );
+        }
+    }
+    Value::Object(diff)
+}
+
+impl SimplePostgresClient {
+    /// Record the settings of the config into the config_history table when they differ
+    /// from the ones last recorded for the validator identity, when the config history
+    /// is enabled. Returns whether the settings were recorded.
+    pub(crate) fn record_config_history(
+        config: &AccountsDbPluginPostgresConfig,
+        change: ConfigChange,
+    ) -> Result<bool, GeyserPluginError> {
+        if !config
+            .record_config_history
+            .unwrap_or(DEFAULT_RECORD_CONFIG_HISTORY)
+        {
+            return Ok(false);
+        }
+        let settings = schema_settings(config);
+        let changed_on = Utc::now().naive_utc();
+
+        let mut client = Self::connect_to_db(config)?;
+        let result = client.transaction().and_then(|mut transaction| {
+            // Serialize the concurrent loads of the plugins sharing the database
+            transaction.execute("LOCK TABLE config_history IN EXCLUSIVE MODE", &[])?;
+            let previous = transaction
+                .query_opt(
+                    "SELECT settings FROM config_history \
+                    WHERE validator_identity IS NOT DISTINCT FROM $1 ORDER BY id DESC LIMIT 1",
+                    &[&config.validator_identity],
+                )?
+                .map(|row| row.get::<_, Value>(0));
+            let diff = settings_diff(
+                previous.as_ref().unwrap_or(&Value::Object(Map::default())),
+                &settings,
+            );
+            if previous.is_some() && diff.as_object().is_some_and(Map::is_empty) {
+                return Ok(false);
+            }
+            transaction.execute(
+                "INSERT INTO config_history (validator_identity, change, settings, diff, changed_on) \
+                VALUES ($1, $2, $3, $4, $5)",
+                &[
+                    &config.validator_identity,
+                    &change.as_str(),
+                    &settings,
+                    &diff,
+                    &changed_on,
+                ],
+            )?;
+            transaction.commit()?;
+            Ok(true)
+        });
+
+        match result {
+            Ok(recorded) => {
+                if recorded {
+                    info!(
+                        "Recorded the settings changed by the {} into the config_history table",
+                        change.as_str()
+                    );
+                }
+                Ok(recorded)
+            }
+            Err(err) => {
+                let msg = format!(
+                    "Failed to record the config history to the PostgreSQL database. Error: {:?}",
+                    err
+                );
+                error!("{}", msg);
+                Err(GeyserPluginError::Custom(Box::new(
+                    AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+                )))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_settings() {
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_value(json!({
+            "host": "localhost",
+            "threads": 10,
+            "store_account_historical_data": true,
+            "accounts_selector": {"owners": ["9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin"]},
+        }))
+        .unwrap();
+        let settings = schema_settings(&config);
+        assert_eq!(
+            settings,
+            json!({
+                "store_account_historical_data": true,
+                "accounts_selector": {"owners": ["9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin"]},
+            })
+        );
+        // Every setting is a field of the config
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str("{}").unwrap();
+        let config = serde_json::to_value(config).unwrap();
+        for name in SCHEMA_SETTINGS {
+            assert!(config.get(name).is_some(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_settings_diff() {
+        let previous = json!({
+            "store_account_historical_data": true,
+            "accounts_selector": {"owners": ["owner-1"]},
+        });
+        assert_eq!(settings_diff(&previous, &previous), json!({}));
+
+        let settings = json!({
+            "accounts_selector": {"owners": ["owner-2"]},
+            "store_block_json": true,
+        });
+        assert_eq!(
+            settings_diff(&previous, &settings),
+            json!({
+                "accounts_selector": {
+                    "previous": {"owners": ["owner-1"]},
+                    "new": {"owners": ["owner-2"]},
+                },
+                "store_account_historical_data": {"previous": true, "new": null},
+                "store_block_json": {"previous": null, "new": true},
+            })
+        );
+    }
+}
@@ -0,0 +1,345 @@
+/// Module responsible for suggesting the write settings of a config from the databases
+/// and the machine, for the check_config tool: the round trip latency and the
+/// connections available of the databases, and the cores and the memory of the
+/// machine, size the workers, their batches and their queue, the defaults being sized
+/// for neither a laptop nor a server with a local database.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::{
+            postgres_client_sharding::shard_configs, SimplePostgresClient,
+            DEFAULT_ACCOUNTS_INSERT_BATCH_SIZE, MAX_ACCOUNTS_INSERT_BATCH_SIZE, MAX_ASYNC_REQUESTS,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    postgres::Client,
+    std::{
+        fs,
+        thread::available_parallelism,
+        time::{Duration, Instant},
+    },
+};
+
+/// The round trips timed per database, the median one being kept
+const ROUND_TRIP_PROBES: usize = 11;
+
+/// The connections the plugin opens besides the workers, for the slot, block and
+/// background writers
+const PLUGIN_EXTRA_CONNECTIONS: usize = 16;
+
+/// The share of the connections available left to the other clients of the database
+const OTHER_CLIENTS_CONNECTION_SHARE: usize = 2;
+
+/// The workers per core hiding the round trips, from a local database to a remote one
+const MIN_THREADS_PER_CORE: usize = 2;
+const MAX_THREADS_PER_CORE: usize = 8;
+
+/// The rows per batch per millisecond of round trip, so that a remote database is
+/// written in fewer, larger statements
+const BATCH_SIZE_PER_ROUND_TRIP_MS: usize = 10;
+const MAX_SUGGESTED_BATCH_SIZE: usize = 1000;
+
+/// The round trips a statement batch waits for its updates, bounded so that a local
+/// database is not written in batches too small and the updates not delayed too long
+const FLUSH_ROUND_TRIPS: u64 = 4;
+const MIN_FLUSH_DELAY_MS: u64 = 5;
+const MAX_FLUSH_DELAY_MS: u64 = 100;
+
+/// The estimated size of a queued update, and the share of the memory of the machine
+/// the queue may take
+const QUEUED_UPDATE_BYTES: u64 = 16 * 1024;
+const QUEUE_MEMORY_SHARE: u64 = 16;
+
+/// The probe of a database of the config
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DatabaseProbe {
+    /// The server_version of the database
+    pub version: String,
+    /// The median round trip of a query
+    pub round_trip: Duration,
+    pub max_connections: usize,
+    /// The connections reserved to the superusers and taken by the other clients
+    pub connections_used: usize,
+}
+
+impl DatabaseProbe {
+    fn connections_available(&self) -> usize {
+        self.max_connections.saturating_sub(self.connections_used)
+    }
+}
+
+/// The probe of the machine the plugin runs on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MachineProbe {
+    pub cores: usize,
+    /// The memory of the machine, none when it cannot be read
+    pub memory_bytes: Option<u64>,
+}
+
+impl MachineProbe {
+    /// Probe the cores and the memory of the machine, the memory being read from
+    /// /proc/meminfo.
+    pub fn probe() -> Self {
+        let cores = available_parallelism().map_or(1, |cores| cores.get());
+        let memory_bytes = fs::read_to_string("/proc/meminfo")
+            .ok()
+            .and_then(|meminfo| parse_mem_total(&meminfo));
+        Self {
+            cores,
+            memory_bytes,
+        }
+    }
+}
+
+/// Parse the MemTotal line of /proc/meminfo, in kB, into bytes.
+fn parse_mem_total(meminfo: &str) -> Option<u64> {
+    let line = meminfo.lines().find(|line| line.starts_with("MemTotal:"))?;
+    let kilobytes: u64 = line
+        .trim_start_matches("MemTotal:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// The write settings suggested for a config
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigSuggestion {
+    pub threads: usize,
+    pub batch_size: usize,
+    pub statement_batch_size: usize,
+    /// The max delay of the statement batches of the account and transaction updates
+    pub flush_delay_ms: u64,
+    /// The queue capacity, none to keep the default one
+    pub queue_capacity: Option<usize>,
+}
+
+impl ConfigSuggestion {
+    /// The settings as the fields of the config file, to merge into it
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut value = serde_json::json!({
+            "threads": self.threads,
+            "batch_size": self.batch_size,
+            "statement_batch_size": self.statement_batch_size,
+            "batch": {
+                "account": {
+                    "size": self.statement_batch_size,
+                    "max_delay_ms": self.flush_delay_ms,
+                },
+                "transaction": {
+                    "size": self.statement_batch_size,
+                    "max_delay_ms": self.flush_delay_ms,
+                },
+            },
+        });
+        if let Some(queue_capacity) = self.queue_capacity {
+            value["queue_capacity"] = serde_json::json!(queue_capacity);
+        }
+        value
+    }
+}
+
+/// Suggest the write settings of the config for the databases, sized by the slowest
+/// one and the one with the fewest connections available, and for the machine.
+pub fn suggest_config(
+    config: &AccountsDbPluginPostgresConfig,
+    databases: &[DatabaseProbe],
+    machine: &MachineProbe,
+) -> ConfigSuggestion {
+    let round_trip = databases
+        .iter()
+        .map(|database| database.round_trip)
+        .max()
+        .unwrap_or_default();
+    let round_trip_ms = round_trip.as_micros().div_ceil(1000).max(1) as usize;
+    let connections_available = databases
+        .iter()
+        .map(DatabaseProbe::connections_available)
+        .min()
+        .unwrap_or_default();
+
+    let threads_per_core = round_trip_ms.clamp(MIN_THREADS_PER_CORE, MAX_THREADS_PER_CORE);
+    let thread_connections = connections_available.saturating_sub(PLUGIN_EXTRA_CONNECTIONS)
+        / OTHER_CLIENTS_CONNECTION_SHARE;
+    let threads = (machine.cores * threads_per_core)
+        .min(thread_connections)
+        .max(1);
+
+    let batch_size = (round_trip_ms * BATCH_SIZE_PER_ROUND_TRIP_MS)
+        .clamp(DEFAULT_ACCOUNTS_INSERT_BATCH_SIZE, MAX_SUGGESTED_BATCH_SIZE)
+        .min(MAX_ACCOUNTS_INSERT_BATCH_SIZE);
+    let flush_delay_ms =
+        (round_trip_ms as u64 * FLUSH_ROUND_TRIPS).clamp(MIN_FLUSH_DELAY_MS, MAX_FLUSH_DELAY_MS);
+
+    // The queue is not made shorter than the depths of the backpressure and of the
+    // degradation ladder configured
+    let min_queue_capacity = config
+        .degradation_ladder
+        .iter()
+        .flat_map(|ladder| ladder.steps.iter().map(|step| step.queue_depth))
+        .chain(config.backpressure_queue_threshold)
+        .max()
+        .unwrap_or(1);
+    let queue_capacity = machine
+        .memory_bytes
+        .map(|memory_bytes| (memory_bytes / QUEUE_MEMORY_SHARE / QUEUED_UPDATE_BYTES) as usize)
+        .filter(|queue_capacity| *queue_capacity < MAX_ASYNC_REQUESTS)
+        .map(|queue_capacity| queue_capacity.max(min_queue_capacity));
+
+    ConfigSuggestion {
+        threads,
+        batch_size,
+        statement_batch_size: batch_size,
+        flush_delay_ms,
+        queue_capacity,
+    }
+}
+
+/// Query a setting of the database as a number.
+fn show_number(client: &mut Client, setting: &str) -> Result<usize, GeyserPluginError> {
+    let value: String = client
+        .query_one(&format!("SHOW {}", setting), &[])
+        .map_err(|err| probe_error(setting, err))?
+        .get(0);
+    value.parse().map_err(|err| probe_error(setting, err))
+}
+
+fn probe_error(setting: &str, err: impl std::fmt::Display) -> GeyserPluginError {
+    GeyserPluginError::Custom(Box::new(
+        AccountsDbPluginPostgresError::DataStoreConnectionError {
+            msg: format!("Failed to probe the {} of the database: {}", setting, err),
+        },
+    ))
+}
+
+impl SimplePostgresClient {
+    /// Probe the database of the config: its version, the median of the round trips of
+    /// a query, and its connections.
+    fn probe_database(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<DatabaseProbe, GeyserPluginError> {
+        let mut client = Self::connect_to_db(config)?;
+        let mut round_trips = Vec::with_capacity(ROUND_TRIP_PROBES);
+        for _ in 0..ROUND_TRIP_PROBES {
+            let start = Instant::now();
+            client
+                .simple_query("SELECT 1")
+                .map_err(|err| probe_error("round trip", err))?;
+            round_trips.push(start.elapsed());
+        }
+        round_trips.sort();
+        let version: String = client
+            .query_one("SHOW server_version", &[])
+            .map_err(|err| probe_error("server_version", err))?
+            .get(0);
+        let max_connections = show_number(&mut client, "max_connections")?;
+        let reserved_connections = show_number(&mut client, "superuser_reserved_connections")?;
+        let active_connections: i64 = client
+            .query_one("SELECT COUNT(*) FROM pg_stat_activity", &[])
+            .map_err(|err| probe_error("connections", err))?
+            .get(0);
+        Ok(DatabaseProbe {
+            version,
+            round_trip: round_trips[ROUND_TRIP_PROBES / 2],
+            max_connections,
+            connections_used: reserved_connections + active_connections as usize,
+        })
+    }
+
+    /// Probe the databases of the config, in the order of the connection strings of the
+    /// sharding.
+    pub fn probe_databases(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Vec<Result<DatabaseProbe, GeyserPluginError>> {
+        shard_configs(config)
+            .iter()
+            .map(Self::probe_database)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn database(round_trip_micros: u64, max_connections: usize) -> DatabaseProbe {
+        DatabaseProbe {
+            version: "16.4".to_string(),
+            round_trip: Duration::from_micros(round_trip_micros),
+            max_connections,
+            connections_used: 3,
+        }
+    }
+
+    #[test]
+    fn test_parse_mem_total() {
+        let meminfo = "MemTotal:       16318500 kB\nMemFree:         1234 kB\n";
+        assert_eq!(parse_mem_total(meminfo), Some(16318500 * 1024));
+        assert_eq!(parse_mem_total("MemFree: 1234 kB\n"), None);
+    }
+
+    #[test]
+    fn test_suggest_config() {
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str("{}").unwrap();
+        let laptop = MachineProbe {
+            cores: 8,
+            memory_bytes: Some(8 << 30),
+        };
+        // A local database is written by a few workers per core in small batches
+        assert_eq!(
+            suggest_config(&config, &[database(200, 100)], &laptop),
+            ConfigSuggestion {
+                threads: 16,
+                batch_size: 10,
+                statement_batch_size: 10,
+                flush_delay_ms: 5,
+                queue_capacity: Some(32768),
+            }
+        );
+        // The workers are bounded by the connections left to the plugin
+        assert_eq!(
+            suggest_config(&config, &[database(200, 40)], &laptop).threads,
+            10
+        );
+
+        // A remote database is written by more workers in larger batches, sized by the
+        // slowest database, and the queue of a large machine is kept
+        let server = MachineProbe {
+            cores: 64,
+            memory_bytes: Some(512 << 30),
+        };
+        let suggestion = suggest_config(
+            &config,
+            &[database(200, 2000), database(5_500, 2000)],
+            &server,
+        );
+        assert_eq!(
+            suggestion,
+            ConfigSuggestion {
+                threads: 384,
+                batch_size: 60,
+                statement_batch_size: 60,
+                flush_delay_ms: 24,
+                queue_capacity: None,
+            }
+        );
+        assert_eq!(suggestion.to_json()["batch"]["account"]["size"], 60);
+        assert!(suggestion.to_json().get("queue_capacity").is_none());
+
+        // The queue is not shorter than the backpressure threshold
+        let config: AccountsDbPluginPostgresConfig =
+            serde_json::from_str("{\"backpressure_queue_threshold\": 20000}").unwrap();
+        let small = MachineProbe {
+            cores: 2,
+            memory_bytes: Some(1 << 30),
+        };
+        assert_eq!(
+            suggest_config(&config, &[database(200, 100)], &small).queue_capacity,
+            Some(20000)
+        );
+    }
+}
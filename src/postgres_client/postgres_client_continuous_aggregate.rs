@@ -0,0 +1,183 @@
+/// Module responsible for refreshing the TimescaleDB continuous aggregates as the rooted
+/// slot progresses rather than on the wall time, so that the aggregates over the
+/// hypertables partitioned by the slot only materialize the buckets of rooted slots.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::SimplePostgresClient,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    postgres::Client,
+    std::{
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Arc,
+        },
+        thread::{sleep, Builder, JoinHandle},
+        time::Duration,
+    },
+};
+
+const CONTINUOUS_AGGREGATE_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+pub(crate) const DEFAULT_CONTINUOUS_AGGREGATE_REFRESH_SLOTS: u64 = 32;
+
+/// The exclusive end of the window of the next refresh, the slot after the rooted slot,
+/// once the rooted slot is refresh_slots past the end of the last refresh, or on the
+/// first rooted slot after the load.
+fn refresh_window_end(
+    rooted_slot: Option<u64>,
+    refreshed_until: Option<u64>,
+    refresh_slots: u64,
+) -> Option<u64> {
+    let window_end = rooted_slot? + 1;
+    match refreshed_until {
+        Some(refreshed_until) if window_end < refreshed_until + refresh_slots => None,
+        _ => Some(window_end),
+    }
+}
+
+fn to_refresh_error(action: &str, err: postgres::Error) -> GeyserPluginError {
+    let msg = format!(
+        "Failed to {} of the continuous aggregates in the PostgreSQL database. Error: {:?}",
+        action, err
+    );
+    error!("{}", msg);
+    GeyserPluginError::Custom(Box::new(
+        AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+    ))
+}
+
+/// The highest rooted slot notified, recorded by the client sending the slot statuses
+/// to the workers.
+#[derive(Default)]
+pub(crate) struct RootedSlot(AtomicU64);
+
+impl RootedSlot {
+    pub(crate) fn record(&self, slot: u64) {
+        // The slot is stored plus one so that zero stands for no rooted slot
+        self.0.fetch_max(slot + 1, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> Option<u64> {
+        self.0.load(Ordering::Relaxed).checked_sub(1)
+    }
+}
+
+pub(crate) struct ContinuousAggregateRefresher {
+    client: Client,
+    aggregates: Vec<String>,
+    refresh_slots: u64,
+    rooted_slot: Arc<RootedSlot>,
+}
+
+impl ContinuousAggregateRefresher {
+    /// Connect when the continuous aggregates are configured, the loading of the plugin
+    /// fails when TimescaleDB is not installed or any of the aggregates is missing.
+    pub(crate) fn new(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Option<Self>, GeyserPluginError> {
+        let Some(aggregates) = &config.continuous_aggregates else {
+            return Ok(None);
+        };
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+        let rows = client
+            .query(
+                "SELECT view_name::TEXT FROM timescaledb_information.continuous_aggregates \
+                WHERE view_name = ANY($1)",
+                &[aggregates],
+            )
+            .map_err(|err| to_refresh_error("look up the TimescaleDB views", err))?;
+        let existing: Vec<String> = rows.iter().map(|row| row.get(0)).collect();
+        if let Some(aggregate) = aggregates
+            .iter()
+            .find(|aggregate| !existing.contains(aggregate))
+        {
+            return Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::ConfigurationError {
+                    msg: format!(
+                        "The continuous aggregate \"{}\" of the \"continuous_aggregates\" does not exist",
+                        aggregate
+                    ),
+                },
+            )));
+        }
+        Ok(Some(Self {
+            client,
+            aggregates: aggregates.clone(),
+            refresh_slots: config
+                .continuous_aggregate_refresh_slots
+                .unwrap_or(DEFAULT_CONTINUOUS_AGGREGATE_REFRESH_SLOTS),
+            rooted_slot: Arc::default(),
+        }))
+    }
+
+    pub(crate) fn rooted_slot(&self) -> Arc<RootedSlot> {
+        self.rooted_slot.clone()
+    }
+
+    /// Refresh the aggregates up to the window end, the buckets already materialized
+    /// being refreshed only when their rows have changed since.
+    fn refresh(&mut self, window_end: u64) -> Result<(), GeyserPluginError> {
+        for aggregate in &self.aggregates {
+            self.client
+                .batch_execute(&format!(
+                    "CALL refresh_continuous_aggregate('{}', NULL, {})",
+                    aggregate, window_end
+                ))
+                .map_err(|err| to_refresh_error("refresh one", err))?;
+        }
+        Ok(())
+    }
+
+    /// Refresh the aggregates as the rooted slot progresses until the exit.
+    pub(crate) fn spawn(
+        mut self,
+        exit_worker: Arc<AtomicBool>,
+    ) -> JoinHandle<Result<(), GeyserPluginError>> {
+        Builder::new()
+            .name("continuous-aggregate".to_string())
+            .spawn(move || -> Result<(), GeyserPluginError> {
+                let mut refreshed_until = None;
+                while !exit_worker.load(Ordering::Relaxed) {
+                    sleep(CONTINUOUS_AGGREGATE_CHECK_INTERVAL);
+                    let Some(window_end) = refresh_window_end(
+                        self.rooted_slot.get(),
+                        refreshed_until,
+                        self.refresh_slots,
+                    ) else {
+                        continue;
+                    };
+                    // The errors are logged, the refresh of the whole window up to the
+                    // rooted slot is attempted again once it is refresh_slots further.
+                    let _ = self.refresh(window_end);
+                    refreshed_until = Some(window_end);
+                }
+                Ok(())
+            })
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refresh_window_end() {
+        assert_eq!(refresh_window_end(None, None, 32), None);
+        assert_eq!(refresh_window_end(Some(100), None, 32), Some(101));
+        assert_eq!(refresh_window_end(Some(131), Some(101), 32), None);
+        assert_eq!(refresh_window_end(Some(132), Some(101), 32), Some(133));
+
+        let rooted_slot = RootedSlot::default();
+        assert_eq!(rooted_slot.get(), None);
+        rooted_slot.record(0);
+        assert_eq!(rooted_slot.get(), Some(0));
+        rooted_slot.record(12);
+        rooted_slot.record(10);
+        assert_eq!(rooted_slot.get(), Some(12));
+    }
+}
@@ -0,0 +1,132 @@
+/// Module responsible for capping the CPU time of each worker to a budget, protecting the
+/// replay of the validators with a tight CPU headroom. A worker over its budget delays
+/// or sheds its low-priority work, while the account, slot, transaction and block
+/// writes are not affected.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{AccountsDbPluginPostgresConfig, CpuBudgetAction},
+        postgres_client::DbWorkItem,
+    },
+    log::*,
+    std::{
+        fs,
+        thread::{self, sleep},
+        time::{Duration, Instant},
+    },
+};
+
+/// The window the CPU usage of a worker is measured over
+const CPU_BUDGET_WINDOW: Duration = Duration::from_secs(1);
+
+/// The CPU time of the calling thread, from the time spent on the CPU in its schedstat.
+fn thread_cpu_time() -> Option<Duration> {
+    let schedstat = fs::read_to_string("/proc/thread-self/schedstat").ok()?;
+    let nanos = schedstat.split_whitespace().next()?.parse().ok()?;
+    Some(Duration::from_nanos(nanos))
+}
+
+/// The delay bringing the usage of the CPU time within the elapsed time back down to the
+/// budget, none when it is within the budget.
+fn budget_delay(cpu_time: Duration, elapsed: Duration, budget: f64) -> Option<Duration> {
+    let delay = cpu_time.as_secs_f64() / budget - elapsed.as_secs_f64();
+    (delay > 0.0).then(|| Duration::from_secs_f64(delay).min(CPU_BUDGET_WINDOW))
+}
+
+impl DbWorkItem {
+    /// Whether the work item is delayed or shed when the worker is over its CPU budget
+    fn is_low_priority(&self) -> bool {
+        matches!(
+            self,
+            DbWorkItem::LogVoteActivity(_)
+                | DbWorkItem::UpdateEntry(_)
+                | DbWorkItem::VerifyBlockChain(_)
+                | DbWorkItem::CleanUpForks(_)
+        )
+    }
+}
+
+/// The CPU budget of a worker, measured in the thread of the worker
+pub(crate) struct CpuBudget {
+    /// The fraction of a core the worker may use
+    budget: f64,
+    action: CpuBudgetAction,
+    /// When the current window started, along with the CPU time of the thread then
+    window: Option<(Instant, Duration)>,
+    /// The low-priority work items shed in the current window
+    shed_count: usize,
+}
+
+impl CpuBudget {
+    pub(crate) fn new(config: &AccountsDbPluginPostgresConfig) -> Option<Self> {
+        let percentage = config.worker_cpu_budget_percentage?;
+        if thread_cpu_time().is_none() {
+            warn!("The CPU time of the workers cannot be measured, the CPU budget is disabled");
+            return None;
+        }
+        Some(Self {
+            budget: percentage as f64 / 100.0,
+            action: config.cpu_budget_exceeded_action.unwrap_or_default(),
+            window: None,
+            shed_count: 0,
+        })
+    }
+
+    /// Check the work item against the budget, delaying the low-priority items until the
+    /// usage is back within the budget, or returning false for the ones to shed.
+    pub(crate) fn admit(&mut self, work: &DbWorkItem) -> bool {
+        let Some(cpu_time) = thread_cpu_time() else {
+            return true;
+        };
+        let now = Instant::now();
+        let (window_start, window_cpu_time) = *self.window.get_or_insert((now, cpu_time));
+        let elapsed = now.duration_since(window_start);
+        if elapsed >= CPU_BUDGET_WINDOW {
+            if self.shed_count > 0 {
+                warn!(
+                    "The worker {:?} over its CPU budget shed {} low-priority work items",
+                    thread::current().name(),
+                    self.shed_count
+                );
+            }
+            self.window = Some((now, cpu_time));
+            self.shed_count = 0;
+            return true;
+        }
+        if !work.is_low_priority() {
+            return true;
+        }
+        let Some(delay) = budget_delay(cpu_time - window_cpu_time, elapsed, self.budget) else {
+            return true;
+        };
+        match self.action {
+            CpuBudgetAction::Delay => {
+                sleep(delay);
+                true
+            }
+            CpuBudgetAction::Shed => {
+                self.shed_count += 1;
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_delay() {
+        let millis = Duration::from_millis;
+        assert_eq!(budget_delay(millis(100), millis(400), 0.5), None);
+        assert_eq!(budget_delay(millis(200), millis(400), 0.5), None);
+        assert_eq!(
+            budget_delay(millis(375), millis(500), 0.5),
+            Some(millis(250))
+        );
+        assert_eq!(
+            budget_delay(millis(900), millis(900), 0.1),
+            Some(CPU_BUDGET_WINDOW)
+        );
+    }
+}
@@ -0,0 +1,231 @@
+/// Module responsible for the credentials of the database kept out of the config file,
+/// the password being read from an environment variable, from a file such as a mounted
+/// secret, or from the output of a command such as the client of a secret manager. The
+/// password is resolved on each connection, reconnection included, so that a rotated
+/// password is picked up without a restart, or reused for the refresh interval when
+/// one is configured. The connections made with it remain open after a rotation.
+use {
+    crate::accountsdb_plugin_postgres::{AccountsDbPluginPostgresConfig, CredentialsConfig},
+    log::*,
+    std::{
+        collections::BTreeMap,
+        fs,
+        io::Read,
+        process::{Command, Stdio},
+        sync::Mutex,
+        thread::sleep,
+        time::{Duration, Instant},
+    },
+};
+
+/// The time the password command is given to print the password
+const PASSWORD_COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+const PASSWORD_COMMAND_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The passwords resolved by their source, along with the time they were resolved at,
+/// shared by the connections of the workers and of the background tasks
+static RESOLVED_PASSWORDS: Mutex<BTreeMap<String, (Instant, String)>> = Mutex::new(BTreeMap::new());
+
+/// The source the password is read from
+#[derive(Debug, PartialEq, Eq)]
+enum PasswordSource<'a> {
+    Env(&'a str),
+    File(&'a str),
+    Command(&'a [String]),
+}
+
+impl<'a> PasswordSource<'a> {
+    fn new(credentials: &'a CredentialsConfig) -> Option<Self> {
+        credentials
+            .password_env
+            .as_deref()
+            .map(PasswordSource::Env)
+            .or_else(|| {
+                credentials
+                    .password_file
+                    .as_deref()
+                    .map(PasswordSource::File)
+            })
+            .or_else(|| {
+                credentials
+                    .password_command
+                    .as_deref()
+                    .map(PasswordSource::Command)
+            })
+    }
+
+    /// The key of the passwords resolved, which does not contain the password
+    fn key(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    fn resolve(&self) -> Result<String, String> {
+        let password = match self {
+            PasswordSource::Env(name) => std::env::var(name).map_err(|err| {
+                format!("the environment variable {} cannot be read: {}", name, err)
+            })?,
+            PasswordSource::File(path) => fs::read_to_string(path)
+                .map_err(|err| format!("the file {} cannot be read: {}", path, err))?,
+            PasswordSource::Command(command) => run_password_command(command)?,
+        };
+        // The trailing newline of the files and of the output of the commands
+        let password = password.trim_end_matches(['\r', '\n']);
+        if password.is_empty() {
+            return Err(format!("the password read from {:?} is empty", self));
+        }
+        Ok(password.to_string())
+    }
+}
+
+/// Run the password command, the password being its standard output.
+fn run_password_command(command: &[String]) -> Result<String, String> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| "the password command is empty".to_string())?;
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("the password command {} cannot be run: {}", program, err))?;
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => {}
+            Err(err) => return Err(format!("the password command {} failed: {}", program, err)),
+        }
+        if started.elapsed() >= PASSWORD_COMMAND_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!(
+                "the password command {} has not exited after {:?}",
+                program, PASSWORD_COMMAND_TIMEOUT
+            ));
+        }
+        sleep(PASSWORD_COMMAND_POLL_INTERVAL);
+    };
+    let mut stdout = String::new();
+    let mut stderr = String::new();
+    if let Some(mut output) = child.stdout.take() {
+        output
+            .read_to_string(&mut stdout)
+            .map_err(|err| format!("the output of the password command is invalid: {}", err))?;
+    }
+    if let Some(mut output) = child.stderr.take() {
+        let _ = output.read_to_string(&mut stderr);
+    }
+    if !status.success() {
+        return Err(format!(
+            "the password command {} exited with {}: {}",
+            program,
+            status,
+            stderr.trim()
+        ));
+    }
+    Ok(stdout)
+}
+
+/// Get the password of the connections to the database from the credentials provider
+/// configured, None when there is none. The password resolved is reused for the refresh
+/// interval, it is resolved again on each connection when there is no refresh interval.
+pub(crate) fn resolve_password(
+    config: &AccountsDbPluginPostgresConfig,
+) -> Result<Option<String>, String> {
+    let Some(source) = config.credentials.as_ref().and_then(PasswordSource::new) else {
+        return Ok(None);
+    };
+    let refresh_interval = config
+        .credentials
+        .as_ref()
+        .and_then(|credentials| credentials.refresh_interval_secs)
+        .map(Duration::from_secs);
+    let key = source.key();
+    if let Some(refresh_interval) = refresh_interval {
+        if let Some((resolved_at, password)) = RESOLVED_PASSWORDS.lock().unwrap().get(&key) {
+            if resolved_at.elapsed() < refresh_interval {
+                return Ok(Some(password.clone()));
+            }
+        }
+    }
+    let password = source.resolve()?;
+    if refresh_interval.is_some() {
+        RESOLVED_PASSWORDS
+            .lock()
+            .unwrap()
+            .insert(key, (Instant::now(), password.clone()));
+    }
+    Ok(Some(password))
+}
+
+/// Forget the password resolved for the refresh interval after a failure to connect,
+/// so that the next attempt picks up the password rotated in the meantime.
+pub(crate) fn forget_password(config: &AccountsDbPluginPostgresConfig) {
+    if let Some(source) = config.credentials.as_ref().and_then(PasswordSource::new) {
+        if RESOLVED_PASSWORDS
+            .lock()
+            .unwrap()
+            .remove(&source.key())
+            .is_some()
+        {
+            info!("Forgot the password resolved from {:?}", source);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_password() {
+        let mut config: AccountsDbPluginPostgresConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(resolve_password(&config), Ok(None));
+
+        let path = std::env::temp_dir().join(format!("pg-password-{}", std::process::id()));
+        fs::write(&path, "secret\n").unwrap();
+        config.credentials = Some(CredentialsConfig {
+            password_file: Some(path.to_str().unwrap().to_string()),
+            refresh_interval_secs: Some(3600),
+            ..CredentialsConfig::default()
+        });
+        assert_eq!(resolve_password(&config), Ok(Some("secret".to_string())));
+        // Reused for the refresh interval, until forgotten
+        fs::write(&path, "rotated\n").unwrap();
+        assert_eq!(resolve_password(&config), Ok(Some("secret".to_string())));
+        forget_password(&config);
+        assert_eq!(resolve_password(&config), Ok(Some("rotated".to_string())));
+        fs::remove_file(&path).unwrap();
+
+        config.credentials = Some(CredentialsConfig {
+            password_command: Some(vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo from-command".to_string(),
+            ]),
+            ..CredentialsConfig::default()
+        });
+        assert_eq!(
+            resolve_password(&config),
+            Ok(Some("from-command".to_string()))
+        );
+        config.credentials = Some(CredentialsConfig {
+            password_command: Some(vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo denied >&2; exit 3".to_string(),
+            ]),
+            ..CredentialsConfig::default()
+        });
+        let err = resolve_password(&config).unwrap_err();
+        assert!(err.ends_with("exit status: 3: denied"), "{}", err);
+
+        config.credentials = Some(CredentialsConfig {
+            password_env: Some("PG_PASSWORD_UNSET_FOR_THE_TEST".to_string()),
+            ..CredentialsConfig::default()
+        });
+        assert!(resolve_password(&config).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+/// Module responsible for the compression of the account data, compressing the data of
+/// the accounts with zstd before it is written into the data column when configured,
+/// the data_codec column telling how the data of each row is encoded. The data shorter
+/// than the threshold, or which does not shrink, is written as it is.
+use {
+    crate::accountsdb_plugin_postgres::AccountsDbPluginPostgresConfig,
+    std::io::{self, ErrorKind},
+};
+
+/// The data_codec of the data written as it is
+pub const DATA_CODEC_NONE: i16 = 0;
+/// The data_codec of the data compressed into a zstd frame
+pub const DATA_CODEC_ZSTD: i16 = 1;
+const DEFAULT_ACCOUNT_DATA_COMPRESSION_LEVEL: i32 = 3;
+/// Below this length the frame overhead outweighs the savings
+const DEFAULT_ACCOUNT_DATA_COMPRESSION_MIN_BYTES: usize = 128;
+
+/// How the account data is compressed before it is written
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct DataCompression {
+    /// The zstd level and the least length of the data compressed, when configured
+    zstd: Option<(i32, usize)>,
+}
+
+impl DataCompression {
+    pub(crate) fn new(config: &AccountsDbPluginPostgresConfig) -> Self {
+        Self {
+            zstd: config.account_data_compression.as_ref().map(|compression| {
+                (
+                    compression
+                        .level
+                        .unwrap_or(DEFAULT_ACCOUNT_DATA_COMPRESSION_LEVEL),
+                    compression
+                        .min_bytes
+                        .unwrap_or(DEFAULT_ACCOUNT_DATA_COMPRESSION_MIN_BYTES),
+                )
+            }),
+        }
+    }
+
+    /// The data compressed, None when it is to be written as it is.
+    pub(crate) fn compress(&self, data: &[u8]) -> Option<Vec<u8>> {
+        let (level, min_bytes) = self.zstd?;
+        if data.len() < min_bytes {
+            return None;
+        }
+        zstd::bulk::compress(data, level)
+            .ok()
+            .filter(|compressed| compressed.len() < data.len())
+    }
+}
+
+/// The data_codec of the data compressed or not by DataCompression::compress
+pub(crate) fn data_codec(compressed: &Option<Vec<u8>>) -> i16 {
+    match compressed {
+        Some(_) => DATA_CODEC_ZSTD,
+        None => DATA_CODEC_NONE,
+    }
+}
+
+/// Decode the data of an account row read back from the database by its data_codec.
+pub fn decompress_account_data(data: Vec<u8>, data_codec: i16) -> io::Result<Vec<u8>> {
+    match data_codec {
+        DATA_CODEC_NONE => Ok(data),
+        DATA_CODEC_ZSTD => zstd::stream::decode_all(data.as_slice()),
+        _ => Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("Unknown data_codec {}", data_codec),
+        )),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_compression() {
+        let config: AccountsDbPluginPostgresConfig =
+            serde_json::from_str("{\"account_data_compression\": {\"min_bytes\": 64}}").unwrap();
+        let compression = DataCompression::new(&config);
+        let data = vec![7u8; 1024];
+        let compressed = compression.compress(&data);
+        assert_eq!(data_codec(&compressed), DATA_CODEC_ZSTD);
+        assert!(compressed.as_ref().unwrap().len() < 64);
+        assert_eq!(
+            decompress_account_data(compressed.unwrap(), DATA_CODEC_ZSTD).unwrap(),
+            data
+        );
+
+        // The data below the threshold, or which does not shrink, is left as it is
+        assert_eq!(compression.compress(&data[..63]), None);
+        let random: Vec<u8> = (0..16)
+            .flat_map(|index: u64| solana_sdk::hash::hash(&index.to_le_bytes()).to_bytes())
+            .collect();
+        assert_eq!(compression.compress(&random), None);
+        assert_eq!(
+            decompress_account_data(random.clone(), DATA_CODEC_NONE).unwrap(),
+            random
+        );
+        assert!(decompress_account_data(random, 9).is_err());
+
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(DataCompression::new(&config).compress(&data), None);
+    }
+}
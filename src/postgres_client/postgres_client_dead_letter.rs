@@ -0,0 +1,301 @@
+/// Module responsible for writing the rows failing repeatedly to be written to the
+/// dead_letter table along with the errors, instead of dropping them or aborting, so
+/// that they can be repaired and replayed later.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::{DbAccountInfo, LogTransactionRequest, SimplePostgresClient},
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    postgres::{Client, Statement},
+    postgres_types::ToSql,
+    serde::Serialize,
+    std::{thread::sleep, time::Duration},
+};
+
+/// The delay before the second attempt of a write, doubled for each next attempt up to
+/// the max delay, so that a transient failure such as a lock timeout can clear
+const DEAD_LETTER_RETRY_INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const DEAD_LETTER_RETRY_MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+/// The delay after the failed attempt of a write, the first attempt being 1.
+fn retry_backoff(attempt: u64) -> Duration {
+    let doublings = attempt.saturating_sub(1).min(u32::BITS as u64) as u32;
+    DEAD_LETTER_RETRY_INITIAL_BACKOFF
+        .saturating_mul(2u32.saturating_pow(doublings))
+        .min(DEAD_LETTER_RETRY_MAX_BACKOFF)
+}
+
+/// The dead letters replayed per query
+const DEAD_LETTER_REPLAY_BATCH_SIZE: i64 = 1000;
+
+/// The outcome of the replay of the dead letters
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeadLetterReplayReport {
+    /// The dead letters written by the replay, marked as replayed
+    pub replayed: u64,
+    /// The dead letters failed again, with their errors and attempts updated
+    pub failed: u64,
+}
+
+impl SimplePostgresClient {
+    /// Build the statement inserting a failed write into the dead_letter table.
+    pub fn build_dead_letter_insert_statement(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Statement, GeyserPluginError> {
+        let stmt = "INSERT INTO dead_letter (stream, slot, payload, error, attempts, failed_on) \
+            VALUES ($1, $2, $3, $4, $5, $6)";
+
+        let stmt = client.prepare(stmt);
+
+        match stmt {
+            Err(err) => {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "Error in preparing for the dead letter insert PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                        err, config.host, config.user, config
+                    ),
+                })))
+            }
+            Ok(stmt) => Ok(stmt),
+        }
+    }
+
+    /// Write the payload of the stream, attempting it up to the dead letter attempts
+    /// when configured, with a backoff between the attempts, and then writing the
+    /// payload along with the error to the dead_letter table. The error is returned
+    /// when the dead letter fails too, and right away when the connection is dropped,
+    /// as the payload is not at fault and the dead letter cannot be written either.
+    pub(crate) fn write_with_dead_letter<T: Serialize>(
+        &mut self,
+        stream: &str,
+        slot: i64,
+        payload: &T,
+        write: impl Fn(&mut Self, &T) -> Result<(), GeyserPluginError>,
+    ) -> Result<(), GeyserPluginError> {
+        let Some(attempts) = self.dead_letter_attempts else {
+            return write(self, payload);
+        };
+        let mut attempt = 1;
+        loop {
+            match write(self, payload) {
+                Ok(()) => return Ok(()),
+                Err(err) if self.is_connection_dropped() => return Err(err),
+                Err(err) if attempt < attempts => {
+                    let delay = retry_backoff(attempt);
+                    warn!(
+                        "Attempt {} of {} to write the {} at slot {} failed, retrying in {:?}: ({})",
+                        attempt, attempts, stream, slot, delay, err
+                    );
+                    sleep(delay);
+                    attempt += 1;
+                }
+                Err(err) => return self.write_dead_letter(stream, slot, payload, attempts, err),
+            }
+        }
+    }
+
+    fn write_dead_letter<T: Serialize>(
+        &mut self,
+        stream: &str,
+        slot: i64,
+        payload: &T,
+        attempts: u64,
+        err: GeyserPluginError,
+    ) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        let Some(statement) = &client.insert_dead_letter_stmt else {
+            return Err(err);
+        };
+        let payload = match serde_json::to_value(payload) {
+            Ok(payload) => payload,
+            Err(json_err) => {
+                error!(
+                    "Failed to serialize the {} at slot {} for the dead letter: ({})",
+                    stream, slot, json_err
+                );
+                return Err(err);
+            }
+        };
+        let error = err.to_string();
+        let attempts = attempts as i32;
+        let failed_on = Utc::now().naive_utc();
+        let params: [&(dyn ToSql + Sync); 6] =
+            [&stream, &slot, &payload, &error, &attempts, &failed_on];
+        if let Err(dead_letter_err) = client.client.execute(statement, &params) {
+            error!(
+                "Failed to persist the dead letter of the {} at slot {} to the PostgreSQL database. Error: {:?}",
+                stream, slot, dead_letter_err
+            );
+            return Err(err);
+        }
+        client
+            .write_stats
+            .record("dead_letter", 1, statement, &params);
+        warn!(
+            "Wrote the {} at slot {} to the dead_letter table after {} attempts: ({})",
+            stream, slot, attempts, error
+        );
+        Ok(())
+    }
+
+    fn replay_dead_letter(
+        &mut self,
+        stream: &str,
+        payload: serde_json::Value,
+    ) -> Result<(), GeyserPluginError> {
+        let to_payload_error = |err: serde_json::Error| {
+            GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                msg: format!("Invalid {} dead letter payload. Error: ({})", stream, err),
+            }))
+        };
+        match stream {
+            "account" => {
+                let account: DbAccountInfo =
+                    serde_json::from_value(payload).map_err(to_payload_error)?;
+                self.upsert_account(&account)
+            }
+            "transaction" => {
+                let request: LogTransactionRequest =
+                    serde_json::from_value(payload).map_err(to_payload_error)?;
+                self.log_transaction_impl(&request)
+            }
+            _ => Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!("Unknown dead letter stream {:?}", stream),
+                },
+            ))),
+        }
+    }
+
+    /// Replay the dead letters not replayed yet, of the stream when given, in the order
+    /// they failed, such as after a fix of the schema or of the payloads. The dead
+    /// letters written are marked as replayed, and the ones failing again are updated
+    /// with the error. The client is expected to be built with the dead letter disabled.
+    pub fn replay_dead_letters(
+        &mut self,
+        stream: Option<&str>,
+    ) -> Result<DeadLetterReplayReport, GeyserPluginError> {
+        let to_replay_error = |action: &str, err: postgres::Error| {
+            let msg = format!(
+                "Failed to {} in the PostgreSQL database. Error: {:?}",
+                action, err
+            );
+            error!("{}", msg);
+            GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+            ))
+        };
+        let mut report = DeadLetterReplayReport::default();
+        let mut last_dead_letter_id = 0i64;
+        loop {
+            let rows = self
+                .client
+                .get_mut()
+                .unwrap()
+                .client
+                .query(
+                    "SELECT dead_letter_id, stream, payload FROM dead_letter \
+                    WHERE dead_letter_id > $1 AND replayed_on IS NULL AND ($2::VARCHAR IS NULL OR stream = $2) \
+                    ORDER BY dead_letter_id LIMIT $3",
+                    &[&last_dead_letter_id, &stream, &DEAD_LETTER_REPLAY_BATCH_SIZE],
+                )
+                .map_err(|err| to_replay_error("query the dead letters", err))?;
+            if rows.is_empty() {
+                return Ok(report);
+            }
+            for row in rows {
+                let dead_letter_id: i64 = row.get(0);
+                let row_stream: String = row.get(1);
+                let payload: serde_json::Value = row.get(2);
+                last_dead_letter_id = dead_letter_id;
+
+                let now = Utc::now().naive_utc();
+                let result = self.replay_dead_letter(&row_stream, payload);
+                let client = &mut self.client.get_mut().unwrap().client;
+                match result {
+                    Ok(()) => {
+                        client
+                            .execute(
+                                "UPDATE dead_letter SET replayed_on = $2 WHERE dead_letter_id = $1",
+                                &[&dead_letter_id, &now],
+                            )
+                            .map_err(|err| to_replay_error("mark the dead letter replayed", err))?;
+                        report.replayed += 1;
+                    }
+                    Err(err) => {
+                        warn!(
+                            "Failed to replay the dead letter {}: ({})",
+                            dead_letter_id, err
+                        );
+                        client
+                            .execute(
+                                "UPDATE dead_letter SET error = $2, attempts = attempts + 1, failed_on = $3 \
+                                WHERE dead_letter_id = $1",
+                                &[&dead_letter_id, &err.to_string(), &now],
+                            )
+                            .map_err(|err| to_replay_error("update the dead letter", err))?;
+                        report.failed += 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, crate::postgres_client::DbSolTransfer};
+
+    #[test]
+    fn test_retry_backoff() {
+        assert_eq!(retry_backoff(1), Duration::from_millis(100));
+        assert_eq!(retry_backoff(2), Duration::from_millis(200));
+        assert_eq!(retry_backoff(5), Duration::from_millis(1600));
+        assert_eq!(retry_backoff(6), DEAD_LETTER_RETRY_MAX_BACKOFF);
+        assert_eq!(retry_backoff(u64::MAX), DEAD_LETTER_RETRY_MAX_BACKOFF);
+    }
+
+    #[test]
+    fn test_dead_letter_payload() {
+        let account = DbAccountInfo {
+            pubkey: vec![1; 32],
+            lamports: 1_000_000,
+            owner: vec![2; 32],
+            executable: false,
+            rent_epoch: 7,
+            data: vec![3; 4],
+            slot: 42,
+            write_version: 5,
+            txn_signature: None,
+        };
+        let payload = serde_json::to_value(&account).unwrap();
+        assert_eq!(payload["slot"], 42);
+        assert_eq!(
+            serde_json::from_value::<DbAccountInfo>(payload).unwrap(),
+            account
+        );
+
+        let sol_transfer = DbSolTransfer {
+            instruction_index: 0,
+            inner_instruction_index: None,
+            kind: "createAccount",
+            source: vec![1; 32],
+            destination: vec![2; 32],
+            lamports: 42,
+        };
+        let mut payload = serde_json::to_value(&sol_transfer).unwrap();
+        assert_eq!(
+            serde_json::from_value::<DbSolTransfer>(payload.clone()).unwrap(),
+            sol_transfer
+        );
+        // The repaired payloads are checked for the known kinds
+        payload["kind"] = "burn".into();
+        assert!(serde_json::from_value::<DbSolTransfer>(payload).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+/// Module responsible for the dedup window across the restarts. The validator notifies
+/// again the slots replayed from its snapshot after a restart, and the writes at the
+/// slots stored before the restart are to be ignored instead of appending the audit
+/// rows and the counts again.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::SimplePostgresClient,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+};
+
+/// The slots of the dedup window, the last `dedup_window_slots` slots up to the
+/// highest slot stored at the load of the plugin
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct DedupWindow {
+    from_slot: u64,
+    until_slot: u64,
+}
+
+impl DedupWindow {
+    fn new(window_slots: u64, until_slot: u64) -> Self {
+        Self {
+            from_slot: until_slot.saturating_sub(window_slots.saturating_sub(1)),
+            until_slot,
+        }
+    }
+
+    /// Render the condition of the slot column being outside of the dedup window, for
+    /// the conflict handling of the statements, None when there is no window.
+    pub(crate) fn outside_condition(
+        config: &AccountsDbPluginPostgresConfig,
+        column: &str,
+    ) -> Option<String> {
+        let window = Self::new(config.dedup_window_slots?, config.dedup_until_slot?);
+        Some(format!(
+            "{} NOT BETWEEN {} AND {}",
+            column, window.from_slot, window.until_slot
+        ))
+    }
+}
+
+impl SimplePostgresClient {
+    /// Resolve the end of the dedup window to the highest slot stored when the window
+    /// is configured, before the workers prepare their statements with it. There is no
+    /// window when no slot has been stored.
+    pub(crate) fn resolve_dedup_window(
+        config: &mut AccountsDbPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        let Some(window_slots) = config.dedup_window_slots else {
+            return Ok(());
+        };
+        let mut client = Self::connect_to_db(config)?;
+        let until_slot: Option<i64> = match client.query_one("SELECT MAX(slot) FROM slot", &[]) {
+            Ok(row) => row.get(0),
+            Err(err) => {
+                let msg = format!(
+                    "Failed to query the highest slot stored for the dedup window from the PostgreSQL database. Error: {:?}",
+                    err
+                );
+                error!("{}", msg);
+                return Err(GeyserPluginError::Custom(Box::new(
+                    AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+                )));
+            }
+        };
+        config.dedup_until_slot = until_slot.map(|slot| slot as u64);
+        if let Some(until_slot) = config.dedup_until_slot {
+            let window = DedupWindow::new(window_slots, until_slot);
+            info!(
+                "Deduplicating the writes at the slots {} to {} stored before the load",
+                window.from_slot, window.until_slot
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedup_window() {
+        assert_eq!(
+            DedupWindow::new(100, 1000),
+            DedupWindow {
+                from_slot: 901,
+                until_slot: 1000
+            }
+        );
+        assert_eq!(DedupWindow::new(100, 10).from_slot, 0);
+
+        let mut config: AccountsDbPluginPostgresConfig =
+            serde_json::from_str("{\"dedup_window_slots\": 1}").unwrap();
+        // There is no window before the highest slot stored is resolved
+        assert_eq!(DedupWindow::outside_condition(&config, "acct.slot"), None);
+        config.dedup_until_slot = Some(42);
+        assert_eq!(
+            DedupWindow::outside_condition(&config, "acct.slot").as_deref(),
+            Some("acct.slot NOT BETWEEN 42 AND 42")
+        );
+    }
+}
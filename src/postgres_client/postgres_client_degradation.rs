@@ -0,0 +1,386 @@
+/// Module responsible for the degradation ladder, the steps the plugin gives up work by
+/// as the backlog of the queue of the workers grows, such as the copies of the previous
+/// versions of the accounts into the account_audit table, the transactions, or all but
+/// a sample of the account updates. A walker samples the depth of the queue, entering
+/// the next step once the depth is sustained at its queue depth and leaving the last
+/// step entered once the depth is sustained below half of it, and records every
+/// transition into the degradation_transition table.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError, DegradationAction,
+            DegradationStepConfig,
+        },
+        postgres_client::{DbWorkItem, SimplePostgresClient},
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    crossbeam_channel::Sender,
+    log::*,
+    postgres::{Client, Statement},
+    std::{
+        sync::{
+            atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+            Arc,
+        },
+        thread::{sleep, Builder, JoinHandle},
+        time::{Duration, Instant},
+    },
+};
+
+pub(crate) const DEFAULT_DEGRADATION_SUSTAIN_SECS: u64 = 10;
+
+const DEGRADATION_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// A step is left once the depth of the queue is below its queue depth divided by this
+const DEGRADATION_DRAIN_DIVISOR: usize = 2;
+/// The session setting skipping the copies into the account_audit table, read by the
+/// audit_account_update trigger and the account upserts of the trigger-free schema
+const SKIP_AUDIT_SETTING: &str = "accountsdb_plugin.skip_audit";
+
+impl DegradationAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DegradationAction::DisableAudit => "disable_audit",
+            DegradationAction::DisableTransactions => "disable_transactions",
+            DegradationAction::SampleAccounts => "sample_accounts",
+            DegradationAction::Drop => "drop",
+        }
+    }
+}
+
+/// The condition of the copies into the account_audit table by the account upserts of
+/// the trigger-free schema, when the ladder disables the audit.
+pub(crate) fn skip_audit_condition(config: &AccountsDbPluginPostgresConfig) -> Option<String> {
+    config
+        .degradation_ladder
+        .as_ref()?
+        .steps
+        .iter()
+        .any(|step| step.action == DegradationAction::DisableAudit)
+        .then(|| {
+            format!(
+                "current_setting('{}', true) IS DISTINCT FROM 'on'",
+                SKIP_AUDIT_SETTING
+            )
+        })
+}
+
+/// The steps of the ladder entered, shared by the client notified, the workers and the
+/// walker
+pub(crate) struct Degradation {
+    steps: Vec<DegradationStepConfig>,
+    /// The count of the steps entered
+    level: AtomicUsize,
+    /// The account updates counted for the sampling
+    account_count: AtomicU64,
+}
+
+impl Degradation {
+    fn new(steps: Vec<DegradationStepConfig>) -> Self {
+        Self {
+            steps,
+            level: AtomicUsize::default(),
+            account_count: AtomicU64::default(),
+        }
+    }
+
+    fn entered_steps(&self) -> &[DegradationStepConfig] {
+        &self.steps[..self.level.load(Ordering::Relaxed)]
+    }
+
+    fn is_entered(&self, action: DegradationAction) -> bool {
+        self.entered_steps()
+            .iter()
+            .any(|step| step.action == action)
+    }
+
+    pub(crate) fn skips_audit(&self) -> bool {
+        self.is_entered(DegradationAction::DisableAudit)
+    }
+
+    pub(crate) fn skips_transactions(&self) -> bool {
+        self.is_entered(DegradationAction::DisableTransactions)
+            || self.is_entered(DegradationAction::Drop)
+    }
+
+    /// Check if the account update is to be queued, one in every sampling rate of the
+    /// updates while they are sampled, the highest rate of the steps entered applying.
+    pub(crate) fn admits_account(&self) -> bool {
+        let steps = self.entered_steps();
+        if steps
+            .iter()
+            .any(|step| step.action == DegradationAction::Drop)
+        {
+            return false;
+        }
+        match steps
+            .iter()
+            .filter(|step| step.action == DegradationAction::SampleAccounts)
+            .filter_map(|step| step.sampling_rate)
+            .max()
+        {
+            Some(sampling_rate) => self
+                .account_count
+                .fetch_add(1, Ordering::Relaxed)
+                .is_multiple_of(sampling_rate),
+            None => true,
+        }
+    }
+}
+
+/// The walk of the ladder from the depths of the queue sampled
+struct LadderWalk {
+    queue_depths: Vec<usize>,
+    sustain: Duration,
+    level: usize,
+    /// Whether the depth is rising to the next step or draining from the last step
+    /// entered, and since when
+    pending: Option<(bool, Instant)>,
+}
+
+impl LadderWalk {
+    /// Record the depth of the queue sampled at the time. Returns the new level when a
+    /// step is entered or left.
+    fn record(&mut self, depth: usize, now: Instant) -> Option<usize> {
+        let is_rising = self
+            .queue_depths
+            .get(self.level)
+            .is_some_and(|queue_depth| depth >= *queue_depth);
+        let is_draining =
+            self.level > 0 && depth < self.queue_depths[self.level - 1] / DEGRADATION_DRAIN_DIVISOR;
+        if !is_rising && !is_draining {
+            self.pending = None;
+            return None;
+        }
+        let since = match self.pending {
+            Some((was_rising, since)) if was_rising == is_rising => since,
+            _ => {
+                self.pending = Some((is_rising, now));
+                now
+            }
+        };
+        if now.duration_since(since) < self.sustain {
+            return None;
+        }
+        self.level = if is_rising {
+            self.level + 1
+        } else {
+            self.level - 1
+        };
+        self.pending = None;
+        Some(self.level)
+    }
+}
+
+pub(crate) struct DegradationLadder {
+    client: Client,
+    statement: Statement,
+    validator_identity: Option<String>,
+    walk: LadderWalk,
+    degradation: Arc<Degradation>,
+}
+
+impl DegradationLadder {
+    pub(crate) fn new(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Option<Self>, GeyserPluginError> {
+        let Some(ladder) = &config.degradation_ladder else {
+            return Ok(None);
+        };
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+        let stmt =
+            "INSERT INTO degradation_transition (validator_identity, previous_level, level, \
+            action, queue_depth, transitioned_on) VALUES ($1, $2, $3, $4, $5, $6)";
+        let statement = match client.prepare(stmt) {
+            Ok(statement) => statement,
+            Err(err) => {
+                return Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "Error in preparing for the degradation transition PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                        err, config.host, config.user, config
+                    ),
+                })));
+            }
+        };
+        Ok(Some(Self {
+            client,
+            statement,
+            validator_identity: config.validator_identity.clone(),
+            walk: LadderWalk {
+                queue_depths: ladder.steps.iter().map(|step| step.queue_depth).collect(),
+                sustain: Duration::from_secs(
+                    ladder
+                        .sustain_secs
+                        .unwrap_or(DEFAULT_DEGRADATION_SUSTAIN_SECS),
+                ),
+                level: 0,
+                pending: None,
+            },
+            degradation: Arc::new(Degradation::new(ladder.steps.clone())),
+        }))
+    }
+
+    pub(crate) fn degradation(&self) -> Arc<Degradation> {
+        self.degradation.clone()
+    }
+
+    /// Apply the transition to the level, logging it and recording it into the
+    /// degradation_transition table. The failures to record are logged.
+    fn transition(&mut self, previous_level: usize, level: usize, depth: usize) {
+        self.degradation.level.store(level, Ordering::Relaxed);
+        let step = &self.degradation.steps[previous_level.max(level) - 1];
+        let action = step.action.as_str();
+        if level > previous_level {
+            warn!(
+                "Entered the step {} '{}' of the degradation ladder, the queue of the workers holding {} work items",
+                level, action, depth
+            );
+        } else {
+            info!(
+                "Left the step {} '{}' of the degradation ladder, the queue of the workers holding {} work items",
+                previous_level, action, depth
+            );
+        }
+        let result = self.client.execute(
+            &self.statement,
+            &[
+                &self.validator_identity,
+                &(previous_level as i16),
+                &(level as i16),
+                &action,
+                &(depth as i64),
+                &Utc::now().naive_utc(),
+            ],
+        );
+        if let Err(err) = result {
+            error!(
+                "Failed to record the degradation transition to the PostgreSQL database. Error: {:?}",
+                err
+            );
+        }
+    }
+
+    /// Walk the ladder from the depth of the queue sampled at the interval until the
+    /// exit, once the startup is done.
+    pub(crate) fn spawn(
+        mut self,
+        sender: Sender<DbWorkItem>,
+        exit_worker: Arc<AtomicBool>,
+        is_startup_done: Arc<AtomicBool>,
+    ) -> JoinHandle<Result<(), GeyserPluginError>> {
+        Builder::new()
+            .name("degradation-ladder".to_string())
+            .spawn(move || -> Result<(), GeyserPluginError> {
+                while !exit_worker.load(Ordering::Relaxed) {
+                    sleep(DEGRADATION_CHECK_INTERVAL);
+                    if !is_startup_done.load(Ordering::Relaxed) {
+                        continue;
+                    }
+                    let depth = sender.len();
+                    let previous_level = self.walk.level;
+                    if let Some(level) = self.walk.record(depth, Instant::now()) {
+                        self.transition(previous_level, level, depth);
+                    }
+                }
+                Ok(())
+            })
+            .unwrap()
+    }
+}
+
+impl SimplePostgresClient {
+    /// Set the session to skip the copies into the account_audit table or not, when it
+    /// is not already set to.
+    pub(crate) fn apply_audit_skip(&mut self, skip: bool) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        if client.audit_skipped == Some(skip) {
+            return Ok(());
+        }
+        let statement = format!(
+            "SET {} = {}",
+            SKIP_AUDIT_SETTING,
+            if skip { "on" } else { "off" }
+        );
+        if let Err(err) = client.client.batch_execute(&statement) {
+            let msg = format!(
+                "Failed to set the skipping of the audit in the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+            )));
+        }
+        client.audit_skipped = Some(skip);
+        Ok(())
+    }
+
+    /// Forget the skipping of the audit the session is set to, after the rollback of a
+    /// statement batch reverting the one set within it.
+    pub(crate) fn forget_audit_skip(&mut self) {
+        self.client.get_mut().unwrap().audit_skipped = None;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ladder_walk() {
+        let sustain = Duration::from_secs(10);
+        let mut walk = LadderWalk {
+            queue_depths: vec![1000, 2000],
+            sustain,
+            level: 0,
+            pending: None,
+        };
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        assert_eq!(walk.record(1500, at(0)), None);
+        assert_eq!(walk.record(1500, at(9)), None);
+        assert_eq!(walk.record(1500, at(10)), Some(1));
+        // The next step is entered once its depth is sustained in turn
+        assert_eq!(walk.record(2500, at(11)), None);
+        assert_eq!(walk.record(1500, at(15)), None);
+        assert_eq!(walk.record(2500, at(16)), None);
+        assert_eq!(walk.record(2500, at(26)), Some(2));
+        assert_eq!(walk.record(2500, at(40)), None);
+        // The steps are left one at a time once drained below half of their depth
+        assert_eq!(walk.record(1500, at(41)), None);
+        assert_eq!(walk.record(999, at(42)), None);
+        assert_eq!(walk.record(999, at(52)), Some(1));
+        assert_eq!(walk.record(499, at(53)), None);
+        assert_eq!(walk.record(499, at(63)), Some(0));
+        assert_eq!(walk.record(0, at(100)), None);
+    }
+
+    #[test]
+    fn test_degradation() {
+        let step = |queue_depth, action, sampling_rate| DegradationStepConfig {
+            queue_depth,
+            action,
+            sampling_rate,
+        };
+        let degradation = Degradation::new(vec![
+            step(1000, DegradationAction::DisableAudit, None),
+            step(2000, DegradationAction::DisableTransactions, None),
+            step(3000, DegradationAction::SampleAccounts, Some(10)),
+            step(4000, DegradationAction::Drop, None),
+        ]);
+        assert!(!degradation.skips_audit());
+        assert!(degradation.admits_account());
+
+        degradation.level.store(2, Ordering::Relaxed);
+        assert!(degradation.skips_audit());
+        assert!(degradation.skips_transactions());
+        assert!((0..10).all(|_| degradation.admits_account()));
+
+        degradation.level.store(3, Ordering::Relaxed);
+        let admitted = (0..100).filter(|_| degradation.admits_account()).count();
+        assert_eq!(admitted, 10);
+
+        degradation.level.store(4, Ordering::Relaxed);
+        assert!(!degradation.admits_account());
+    }
+}
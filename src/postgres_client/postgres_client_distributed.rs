@@ -0,0 +1,164 @@
+/// Module responsible for the Citus distributed tables, distributing the tables written
+/// by the plugin across the worker nodes when the plugin is loaded. The tables of the
+/// accounts are distributed by the pubkey and the tables of the slots and the
+/// transactions by the slot, each group being colocated, so that the statements writing
+/// an account or a transaction along with its related rows are routed to one node.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError, Distributed,
+        },
+        postgres_client::SimplePostgresClient,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+};
+
+/// The tables distributed, along with their distribution column and the table they are
+/// colocated with, the account and slot tables each starting a colocation group. The
+/// account_data table only exists with the split account layout.
+const DISTRIBUTED_TABLES: [(&str, &str, Option<&str>); 26] = [
+    ("account", "pubkey", None),
+    ("account_data", "pubkey", Some("account")),
+    ("account_audit", "pubkey", Some("account")),
+    ("owner_change", "pubkey", Some("account")),
+    ("account_version", "pubkey", Some("account")),
+    ("spl_token_account", "pubkey", Some("account")),
+    ("spl_token_mint", "pubkey", Some("account")),
+    ("spl_token_owner_index", "account_key", Some("account")),
+    ("spl_token_mint_index", "account_key", Some("account")),
+    ("stake_reward", "pubkey", Some("account")),
+    ("slot", "slot", None),
+    ("slot_economics", "slot", Some("slot")),
+    ("block", "slot", Some("slot")),
+    ("block_reward", "slot", Some("slot")),
+    ("entry", "slot", Some("slot")),
+    ("vote_activity", "slot", Some("slot")),
+    ("validator_reward", "slot", Some("slot")),
+    ("transaction", "slot", Some("slot")),
+    ("transaction_address", "slot", Some("slot")),
+    ("sol_transfer", "slot", Some("slot")),
+    ("transaction_memo", "slot", Some("slot")),
+    ("transaction_instruction", "slot", Some("slot")),
+    ("transaction_balance", "slot", Some("slot")),
+    ("transaction_token_balance", "slot", Some("slot")),
+    ("transaction_reward", "slot", Some("slot")),
+    ("transaction_log", "slot", Some("slot")),
+];
+
+pub(crate) fn is_citus(config: &AccountsDbPluginPostgresConfig) -> bool {
+    config.distributed == Some(Distributed::Citus)
+}
+
+/// The statement distributing the table by the column, colocated with the table, or in
+/// a new colocation group of the shard count when configured.
+fn distribute_statement(
+    table: &str,
+    column: &str,
+    colocate_with: Option<&str>,
+    shard_count: Option<u64>,
+) -> String {
+    let colocation = match (colocate_with, shard_count) {
+        (Some(colocate_with), _) => format!(", colocate_with => '{}'", colocate_with),
+        (None, Some(shard_count)) => {
+            format!(", colocate_with => 'none', shard_count => {}", shard_count)
+        }
+        (None, None) => ", colocate_with => 'none'".to_string(),
+    };
+    format!("SELECT create_distributed_table('{table}', '{column}'{colocation})")
+}
+
+fn to_distributed_error(action: &str, err: postgres::Error) -> GeyserPluginError {
+    let msg = format!(
+        "Failed to {} of the Citus distributed tables in the PostgreSQL database. Error: {:?}",
+        action, err
+    );
+    error!("{}", msg);
+    GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+        msg,
+    }))
+}
+
+impl SimplePostgresClient {
+    /// Distribute the tables not distributed yet when they are distributed with Citus,
+    /// under the advisory lock of the schema. The tables missing, such as the ones of
+    /// the features not set up, are left out, and the other tables stay local on the
+    /// coordinator. The loading of the plugin fails when the citus extension is not
+    /// installed.
+    pub(crate) fn create_distributed_tables(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        if !is_citus(config) {
+            return Ok(());
+        }
+        let mut client = Self::connect_to_db(config)?;
+        let mut transaction = client.transaction().map_err(|err| {
+            to_distributed_error("start the creation of the distributed tables", err)
+        })?;
+        let schema = config.schema.as_deref().unwrap_or("public");
+        transaction
+            .execute("SELECT pg_advisory_xact_lock(hashtext($1))", &[&schema])
+            .map_err(|err| to_distributed_error("acquire the schema lock", err))?;
+        let is_installed: bool = transaction
+            .query_one(
+                "SELECT EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'citus')",
+                &[],
+            )
+            .map_err(|err| to_distributed_error("look up the citus extension", err))?
+            .get(0);
+        if !is_installed {
+            return Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::ConfigurationError {
+                    msg: "The \"distributed\" 'citus' requires the citus extension to be \
+                        installed in the database"
+                        .to_string(),
+                },
+            )));
+        }
+        for (table, column, colocate_with) in DISTRIBUTED_TABLES {
+            let row = transaction
+                .query_one(
+                    "SELECT to_regclass($1) IS NOT NULL, EXISTS (SELECT 1 FROM pg_dist_partition \
+                    WHERE logicalrelid = to_regclass($1))",
+                    &[&table],
+                )
+                .map_err(|err| to_distributed_error("look up the distributed tables", err))?;
+            let (exists, is_distributed): (bool, bool) = (row.get(0), row.get(1));
+            if exists && !is_distributed {
+                info!("Distributing the {} table by the {}", table, column);
+                transaction
+                    .batch_execute(&distribute_statement(
+                        table,
+                        column,
+                        colocate_with,
+                        config.distributed_shard_count,
+                    ))
+                    .map_err(|err| to_distributed_error("distribute the tables", err))?;
+            }
+        }
+        transaction.commit().map_err(|err| {
+            to_distributed_error("commit the creation of the distributed tables", err)
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distribute_statement() {
+        assert_eq!(
+            distribute_statement("account", "pubkey", None, None),
+            "SELECT create_distributed_table('account', 'pubkey', colocate_with => 'none')"
+        );
+        assert_eq!(
+            distribute_statement("slot", "slot", None, Some(64)),
+            "SELECT create_distributed_table('slot', 'slot', colocate_with => 'none', shard_count => 64)"
+        );
+        assert_eq!(
+            distribute_statement("account_audit", "pubkey", Some("account"), Some(64)),
+            "SELECT create_distributed_table('account_audit', 'pubkey', colocate_with => 'account')"
+        );
+    }
+}
@@ -0,0 +1,256 @@
+/// Module responsible for persisting the entries of the blocks notified by notify_entry
+/// into the entry table. The entries are high volume, so they are batched per slot by
+/// the ParallelPostgresClient and each batch is written by a single statement.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        geyser_interface::ReplicaEntryInfo,
+        postgres_client::{
+            postgres_client_metrics::MetricStream, DbWorkItem, ParallelPostgresClient,
+            SimplePostgresClient, StaticColumns,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    postgres::{Client, Statement},
+    postgres_types::ToSql,
+    serde_derive::{Deserialize, Serialize},
+    std::sync::Mutex,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DbEntry {
+    pub slot: i64,
+    pub entry_index: i64,
+    pub num_hashes: i64,
+    /// The base58 encoded hash of the entry
+    pub hash: String,
+    pub executed_transaction_count: i64,
+    pub starting_transaction_index: i64,
+}
+
+impl<'a> From<&ReplicaEntryInfo<'a>> for DbEntry {
+    fn from(entry_info: &ReplicaEntryInfo<'a>) -> Self {
+        Self {
+            slot: entry_info.slot as i64,
+            entry_index: entry_info.index as i64,
+            num_hashes: entry_info.num_hashes as i64,
+            hash: bs58::encode(entry_info.hash).into_string(),
+            executed_transaction_count: entry_info.executed_transaction_count as i64,
+            starting_transaction_index: entry_info.starting_transaction_index as i64,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct UpdateEntryRequest {
+    pub entries: Vec<DbEntry>,
+}
+
+/// The entries of a slot buffered until the batch is full, the entries of another slot
+/// are notified or the status of the slot is updated.
+pub(crate) struct EntryBuffer {
+    batch_size: usize,
+    entries: Mutex<Vec<DbEntry>>,
+}
+
+impl EntryBuffer {
+    pub(crate) fn new(batch_size: usize) -> Self {
+        Self {
+            batch_size,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Buffer the entry, returning the batch to write if any.
+    pub(crate) fn add(&self, entry: DbEntry) -> Option<Vec<DbEntry>> {
+        let mut entries = self.entries.lock().unwrap();
+        let batch = match entries.first() {
+            Some(first) if first.slot != entry.slot => Some(std::mem::take(&mut *entries)),
+            _ => None,
+        };
+        entries.push(entry);
+        if batch.is_none() && entries.len() >= self.batch_size {
+            return Some(std::mem::take(&mut *entries));
+        }
+        batch
+    }
+
+    /// Take the buffered entries when they are of the slot or an earlier one.
+    pub(crate) fn take_up_to(&self, slot: u64) -> Option<Vec<DbEntry>> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.first() {
+            Some(first) if first.slot <= slot as i64 => Some(std::mem::take(&mut *entries)),
+            _ => None,
+        }
+    }
+}
+
+impl SimplePostgresClient {
+    /// Build the statement upserting a batch of entries into the entry table.
+    pub fn build_entry_upsert_statement(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Statement, GeyserPluginError> {
+        let static_columns = StaticColumns::new(config);
+        let stmt = format!("INSERT INTO entry AS e (slot, entry_index, num_hashes, hash, executed_transaction_count, \
+            starting_transaction_index, updated_on{}) \
+            SELECT u.*, $1{} FROM UNNEST($2::BIGINT[], $3::BIGINT[], $4::BIGINT[], $5::VARCHAR[], $6::BIGINT[], $7::BIGINT[]) \
+            AS u (slot, entry_index, num_hashes, hash, executed_transaction_count, starting_transaction_index) \
+            ON CONFLICT (slot, entry_index) DO UPDATE SET num_hashes=excluded.num_hashes, hash=excluded.hash, \
+            executed_transaction_count=excluded.executed_transaction_count, \
+            starting_transaction_index=excluded.starting_transaction_index, updated_on=excluded.updated_on",
+            static_columns.names, static_columns.values);
+
+        let stmt = client.prepare(&stmt);
+
+        match stmt {
+            Err(err) => {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "Error in preparing for the entry update PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                        err, config.host, config.user, config
+                    ),
+                })))
+            }
+            Ok(stmt) => Ok(stmt),
+        }
+    }
+
+    pub(crate) fn update_entries_impl(
+        &mut self,
+        request: &UpdateEntryRequest,
+    ) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        let Some(statement) = &client.upsert_entry_stmt else {
+            return Ok(());
+        };
+        let updated_on = Utc::now().naive_utc();
+        let entries = &request.entries;
+        let slots: Vec<i64> = entries.iter().map(|entry| entry.slot).collect();
+        let entry_indexes: Vec<i64> = entries.iter().map(|entry| entry.entry_index).collect();
+        let num_hashes: Vec<i64> = entries.iter().map(|entry| entry.num_hashes).collect();
+        let hashes: Vec<&String> = entries.iter().map(|entry| &entry.hash).collect();
+        let executed_transaction_counts: Vec<i64> = entries
+            .iter()
+            .map(|entry| entry.executed_transaction_count)
+            .collect();
+        let starting_transaction_indexes: Vec<i64> = entries
+            .iter()
+            .map(|entry| entry.starting_transaction_index)
+            .collect();
+
+        let params: [&(dyn ToSql + Sync); 7] = [
+            &updated_on,
+            &slots,
+            &entry_indexes,
+            &num_hashes,
+            &hashes,
+            &executed_transaction_counts,
+            &starting_transaction_indexes,
+        ];
+        if let Err(err) = client.client.execute(statement, &params) {
+            let msg = format!(
+                "Failed to persist the entries to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::SlotStatusUpdateError { msg });
+        }
+        client
+            .write_stats
+            .record("entry", entries.len(), statement, &params);
+
+        Ok(())
+    }
+}
+
+impl ParallelPostgresClient {
+    pub fn update_entry(&self, entry_info: &ReplicaEntryInfo) -> Result<(), GeyserPluginError> {
+        let Some(entry_buffer) = &self.entry_buffer else {
+            return Ok(());
+        };
+        self.record_notification(MetricStream::Entry);
+        match entry_buffer.add(DbEntry::from(entry_info)) {
+            Some(entries) => self.send_entries(entries),
+            None => Ok(()),
+        }
+    }
+
+    /// Send the buffered entries of the slot or an earlier one to be written.
+    pub(crate) fn flush_entries(&self, slot: u64) -> Result<(), GeyserPluginError> {
+        match self
+            .entry_buffer
+            .as_ref()
+            .and_then(|entry_buffer| entry_buffer.take_up_to(slot))
+        {
+            Some(entries) => self.send_entries(entries),
+            None => Ok(()),
+        }
+    }
+
+    fn send_entries(&self, entries: Vec<DbEntry>) -> Result<(), GeyserPluginError> {
+        let slot = entries[0].slot;
+        if let Err(err) =
+            self.queue_overflow
+                .send(DbWorkItem::UpdateEntry(Box::new(UpdateEntryRequest {
+                    entries,
+                })))
+        {
+            return Err(GeyserPluginError::SlotStatusUpdateError {
+                msg: format!(
+                    "Failed to update the entries at slot {:?}, error: {:?}",
+                    slot, err
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn entry(slot: i64, entry_index: i64) -> DbEntry {
+        DbEntry {
+            slot,
+            entry_index,
+            num_hashes: 12500,
+            hash: "11111111111111111111111111111111".to_string(),
+            executed_transaction_count: 1,
+            starting_transaction_index: entry_index,
+        }
+    }
+
+    #[test]
+    fn test_entry_buffer() {
+        let entry_buffer = EntryBuffer::new(3);
+        assert_eq!(entry_buffer.add(entry(10, 0)), None);
+        assert_eq!(entry_buffer.add(entry(10, 1)), None);
+        assert_eq!(
+            entry_buffer.add(entry(10, 2)),
+            Some(vec![entry(10, 0), entry(10, 1), entry(10, 2)])
+        );
+        assert_eq!(entry_buffer.add(entry(10, 3)), None);
+        // The entries of the next slot start another batch
+        assert_eq!(entry_buffer.add(entry(11, 0)), Some(vec![entry(10, 3)]));
+        assert_eq!(entry_buffer.take_up_to(10), None);
+        assert_eq!(entry_buffer.take_up_to(11), Some(vec![entry(11, 0)]));
+        assert_eq!(entry_buffer.take_up_to(u64::MAX), None);
+
+        let hash = [0u8; 32];
+        let entry_info = ReplicaEntryInfo {
+            slot: 10,
+            index: 2,
+            num_hashes: 12500,
+            hash: &hash,
+            executed_transaction_count: 1,
+            starting_transaction_index: 2,
+        };
+        assert_eq!(DbEntry::from(&entry_info), entry(10, 2));
+    }
+}
@@ -0,0 +1,282 @@
+/// Module responsible for the write-ordering barriers at the epoch boundaries. Before the
+/// first slot of a new epoch is queued, each worker is handed a barrier it holds until
+/// all the workers have reached it, so that the updates queued before are all committed.
+/// The last worker reaching it writes the epoch_barrier row with a synchronous commit,
+/// flushing the write-ahead log of the updates before it, for a clean cut per epoch.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::{DbWorkItem, ParallelPostgresClient, SimplePostgresClient},
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    std::{
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Condvar, Mutex,
+        },
+        time::Duration,
+    },
+};
+
+const DEFAULT_WRITE_EPOCH_BARRIERS: bool = false;
+
+/// The longest the workers hold a barrier waiting for the others, after which it is
+/// abandoned so that a worker stuck on its connection does not stall the others
+const EPOCH_BARRIER_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Default)]
+struct EpochBarrierState {
+    arrived: usize,
+    /// Set once the barrier is written or abandoned
+    released: bool,
+}
+
+/// The barrier of an epoch shared by the barrier items handed to the workers
+pub(crate) struct EpochBarrier {
+    epoch: u64,
+    worker_count: usize,
+    timeout: Duration,
+    state: Mutex<EpochBarrierState>,
+    released: Condvar,
+}
+
+impl EpochBarrier {
+    fn new(epoch: u64, worker_count: usize, timeout: Duration) -> Self {
+        Self {
+            epoch,
+            worker_count,
+            timeout,
+            state: Mutex::default(),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Hold the barrier until all the workers have reached it, the last one reaching it
+    /// writing the barrier. A barrier released already, written again after a lost
+    /// connection or abandoned, is not written.
+    fn arrive(
+        &self,
+        write: impl FnOnce() -> Result<(), GeyserPluginError>,
+    ) -> Result<(), GeyserPluginError> {
+        let mut state = self.state.lock().unwrap();
+        if state.released {
+            return Ok(());
+        }
+        state.arrived += 1;
+        if state.arrived == self.worker_count {
+            let result = write();
+            state.released = true;
+            self.released.notify_all();
+            return result;
+        }
+        let (mut state, wait) = self
+            .released
+            .wait_timeout_while(state, self.timeout, |state| !state.released)
+            .unwrap();
+        if wait.timed_out() {
+            warn!(
+                "Abandoned the barrier of the epoch {} reached by {} of the {} workers within {:?}",
+                self.epoch, state.arrived, self.worker_count, self.timeout
+            );
+            state.released = true;
+            self.released.notify_all();
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct WriteEpochBarrierRequest {
+    /// The first slot of the epoch notified
+    slot: u64,
+    barrier: Arc<EpochBarrier>,
+}
+
+impl WriteEpochBarrierRequest {
+    pub(crate) fn slot(&self) -> u64 {
+        self.slot
+    }
+
+    /// Hold the barrier, writing it with the client when the last worker reaching it.
+    pub(crate) fn arrive(
+        &self,
+        client: &mut SimplePostgresClient,
+    ) -> Result<(), GeyserPluginError> {
+        self.barrier
+            .arrive(|| client.write_epoch_barrier_impl(self.barrier.epoch, self.slot))
+    }
+}
+
+/// The epochs of the slots notified, when the epoch barriers are written
+pub(crate) struct EpochBarriers {
+    slots_per_epoch: u64,
+    /// The workers of the shared queue, each holding a barrier item, along with the
+    /// priority worker when configured
+    worker_count: usize,
+    /// The highest epoch of the slots notified, plus one so that zero stands for none
+    epoch: AtomicU64,
+}
+
+impl EpochBarriers {
+    pub(crate) fn new(
+        config: &AccountsDbPluginPostgresConfig,
+        slots_per_epoch: u64,
+        worker_count: usize,
+    ) -> Option<Self> {
+        config
+            .write_epoch_barriers
+            .unwrap_or(DEFAULT_WRITE_EPOCH_BARRIERS)
+            .then(|| Self {
+                slots_per_epoch,
+                worker_count,
+                epoch: AtomicU64::default(),
+            })
+    }
+
+    /// The epoch of the slot when the slot is the first notified of a new epoch, the
+    /// epoch of the first slot notified is not a new one.
+    fn new_epoch(&self, slot: u64) -> Option<u64> {
+        let epoch = slot / self.slots_per_epoch;
+        let previous = self.epoch.fetch_max(epoch + 1, Ordering::Relaxed);
+        (previous != 0 && previous <= epoch).then_some(epoch)
+    }
+}
+
+impl SimplePostgresClient {
+    pub(crate) fn write_epoch_barrier_impl(
+        &mut self,
+        epoch: u64,
+        slot: u64,
+    ) -> Result<(), GeyserPluginError> {
+        let client = &mut self.client.get_mut().unwrap().client;
+        let to_plugin_error = |err: postgres::Error| {
+            let msg = format!(
+                "Failed to write the barrier of the epoch {} to the PostgreSQL database. Error: {:?}",
+                epoch, err
+            );
+            error!("{}", msg);
+            GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+            ))
+        };
+        let mut transaction = client.transaction().map_err(to_plugin_error)?;
+        // The commit waits for the write-ahead log to be flushed, up to the updates
+        // committed asynchronously before it
+        transaction
+            .batch_execute("SET LOCAL synchronous_commit TO on")
+            .map_err(to_plugin_error)?;
+        transaction
+            .execute(
+                "INSERT INTO epoch_barrier (epoch, slot, written_on) VALUES ($1, $2, $3) \
+                ON CONFLICT (epoch) DO NOTHING",
+                &[&(epoch as i64), &(slot as i64), &Utc::now().naive_utc()],
+            )
+            .map_err(to_plugin_error)?;
+        transaction.commit().map_err(to_plugin_error)?;
+        info!("Wrote the barrier of the epoch {} at slot {}", epoch, slot);
+        Ok(())
+    }
+}
+
+impl ParallelPostgresClient {
+    /// Hand a barrier to each worker before the first slot of a new epoch is queued,
+    /// once the startup is done. The barrier items are not subject to the queue
+    /// overflow policy.
+    pub(crate) fn write_epoch_barrier_on_new_epoch(
+        &self,
+        slot: u64,
+    ) -> Result<(), GeyserPluginError> {
+        let Some(epoch) = self
+            .epoch_barriers
+            .as_ref()
+            .and_then(|epoch_barriers| epoch_barriers.new_epoch(slot))
+        else {
+            return Ok(());
+        };
+        if !self.is_startup_done.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        // The entries of the slots before are written ahead of the barrier
+        self.flush_entries(slot.saturating_sub(1))?;
+        let worker_count = self.epoch_barriers.as_ref().unwrap().worker_count;
+        let request = WriteEpochBarrierRequest {
+            slot,
+            barrier: Arc::new(EpochBarrier::new(
+                epoch,
+                worker_count,
+                EPOCH_BARRIER_TIMEOUT,
+            )),
+        };
+        let priority_count = usize::from(self.priority_sender.is_some());
+        let senders = std::iter::repeat_n(&self.sender, worker_count - priority_count)
+            .chain(self.priority_sender.as_ref());
+        for sender in senders {
+            if let Err(err) = sender.send(DbWorkItem::WriteEpochBarrier(Box::new(request.clone())))
+            {
+                return Err(GeyserPluginError::SlotStatusUpdateError {
+                    msg: format!(
+                        "Failed to write the barrier of the epoch {:?}, error: {:?}",
+                        epoch, err
+                    ),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, std::thread};
+
+    #[test]
+    fn test_epoch_barriers_new_epoch() {
+        let config: AccountsDbPluginPostgresConfig =
+            serde_json::from_str("{\"write_epoch_barriers\": true}").unwrap();
+        let epoch_barriers = EpochBarriers::new(&config, 32, 2).unwrap();
+        assert_eq!(epoch_barriers.new_epoch(40), None);
+        assert_eq!(epoch_barriers.new_epoch(63), None);
+        assert_eq!(epoch_barriers.new_epoch(64), Some(2));
+        assert_eq!(epoch_barriers.new_epoch(65), None);
+        // The slots of the forks of the epoch before are left alone
+        assert_eq!(epoch_barriers.new_epoch(60), None);
+        assert_eq!(epoch_barriers.new_epoch(200), Some(6));
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str("{}").unwrap();
+        assert!(EpochBarriers::new(&config, 32, 2).is_none());
+    }
+
+    #[test]
+    fn test_epoch_barrier_arrive() {
+        let barrier = Arc::new(EpochBarrier::new(1, 3, Duration::from_secs(10)));
+        let written = Arc::new(AtomicU64::default());
+        let workers: Vec<_> = (0..3)
+            .map(|_| {
+                let barrier = barrier.clone();
+                let written = written.clone();
+                thread::spawn(move || {
+                    barrier
+                        .arrive(|| {
+                            written.fetch_add(1, Ordering::Relaxed);
+                            Ok(())
+                        })
+                        .unwrap();
+                    // The barrier is released once written
+                    assert_eq!(written.load(Ordering::Relaxed), 1);
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(written.load(Ordering::Relaxed), 1);
+
+        // A barrier abandoned is not written by the workers reaching it late
+        let barrier = EpochBarrier::new(1, 2, Duration::from_millis(10));
+        barrier.arrive(|| panic!("written early")).unwrap();
+        barrier.arrive(|| panic!("written late")).unwrap();
+    }
+}
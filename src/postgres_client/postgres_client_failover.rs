@@ -0,0 +1,338 @@
+/// Module responsible for the health checks of the connections of the workers and the
+/// failover to the standby. Each worker pings its connection at the health check
+/// interval, and once the primary has been unreachable for longer than the failover
+/// threshold, the workers reconnect to the read-write standby of the
+/// fallback_connection_str, failing back to the primary once it accepts the writes
+/// again. The decision to fail over or back is shared by the workers, so that they all
+/// write to the same database, the ones whose connections are still up following the
+/// decision at their next health check.
+use {
+    crate::{
+        accountsdb_plugin_postgres::AccountsDbPluginPostgresConfig,
+        postgres_client::{PostgresClientWorker, SimplePostgresClient},
+    },
+    log::*,
+    solana_metrics::*,
+    std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        time::{Duration, Instant},
+    },
+};
+
+pub(crate) const DEFAULT_HEALTH_CHECK_INTERVAL_SECS: u64 = 5;
+pub(crate) const DEFAULT_FAILOVER_AFTER_SECS: u64 = 30;
+
+/// The failover decision shared by the workers of a ParallelPostgresClient
+#[derive(Default)]
+pub(crate) struct FailoverDecision {
+    /// Indicates if the workers are to write to the standby
+    is_on_fallback: AtomicBool,
+    /// When the primary was found unreachable by a worker, since it was last reachable
+    unreachable_since: Mutex<Option<Instant>>,
+}
+
+impl FailoverDecision {
+    fn is_on_fallback(&self) -> bool {
+        self.is_on_fallback.load(Ordering::Relaxed)
+    }
+
+    /// Record the primary reachable by a worker.
+    fn record_reachable(&self) {
+        *self.unreachable_since.lock().unwrap() = None;
+    }
+
+    /// Record the primary unreachable by a worker, returning whether it has been
+    /// unreachable for longer than the failover threshold.
+    fn is_failover_due(&self, now: Instant, failover_after: Duration) -> bool {
+        let unreachable_since = *self.unreachable_since.lock().unwrap().get_or_insert(now);
+        now.duration_since(unreachable_since) >= failover_after
+    }
+
+    /// Decide to write to the standby, or to the primary again.
+    fn decide(&self, is_on_fallback: bool) {
+        if self.is_on_fallback.swap(is_on_fallback, Ordering::Relaxed) != is_on_fallback {
+            self.record_reachable();
+        }
+    }
+}
+
+/// The connections of a worker to the primary and to the standby
+pub(crate) struct Failover {
+    primary: AccountsDbPluginPostgresConfig,
+    fallback: AccountsDbPluginPostgresConfig,
+    check_interval: Duration,
+    failover_after: Duration,
+    last_checked: Instant,
+    /// The decision shared with the other workers
+    decision: Arc<FailoverDecision>,
+    /// Indicates if the worker is connected to the standby
+    is_on_fallback: bool,
+}
+
+impl Failover {
+    /// Build the failover when the fallback connection is configured.
+    pub(crate) fn new(
+        config: &AccountsDbPluginPostgresConfig,
+        decision: Arc<FailoverDecision>,
+    ) -> Option<Self> {
+        let mut fallback = config.clone();
+        fallback.connection_str = Some(config.fallback_connection_str.clone()?);
+        Some(Self {
+            primary: config.clone(),
+            fallback,
+            check_interval: Duration::from_secs(
+                config
+                    .health_check_interval_secs
+                    .unwrap_or(DEFAULT_HEALTH_CHECK_INTERVAL_SECS),
+            ),
+            failover_after: Duration::from_secs(
+                config
+                    .failover_after_secs
+                    .unwrap_or(DEFAULT_FAILOVER_AFTER_SECS),
+            ),
+            last_checked: Instant::now(),
+            decision,
+            is_on_fallback: false,
+        })
+    }
+
+    /// Record the primary reachable again, after a reconnection to it.
+    pub(crate) fn record_reachable(&self) {
+        if !self.is_on_fallback {
+            self.decision.record_reachable();
+        }
+    }
+
+    /// Check if the worker is to reconnect to follow the decision of another worker.
+    pub(crate) fn is_behind_decision(&self) -> bool {
+        self.is_on_fallback != self.decision.is_on_fallback()
+    }
+
+    fn is_failover_due(&self, now: Instant) -> bool {
+        self.decision.is_failover_due(now, self.failover_after)
+    }
+}
+
+/// Check if the database of the config accepts the connections and the writes, the
+/// primary recovering as a standby of the promoted one rejecting the writes.
+fn is_writable(config: &AccountsDbPluginPostgresConfig) -> bool {
+    let Ok(mut client) = SimplePostgresClient::connect_to_db(config) else {
+        return false;
+    };
+    client
+        .query_one("SELECT pg_is_in_recovery()", &[])
+        .is_ok_and(|row| !row.get::<_, bool>(0))
+}
+
+impl PostgresClientWorker {
+    /// Check the health of the connection at the health check interval. The worker
+    /// behind the failover decision of another worker reconnects to follow it. On the
+    /// primary, the dropped connection is rebuilt, failing over to the standby once the
+    /// primary has been unreachable for longer than the threshold. On the standby, the
+    /// workers fail back once the primary accepts the writes again.
+    pub(crate) fn check_health(&mut self) {
+        let Some(failover) = &mut self.failover else {
+            return;
+        };
+        let now = Instant::now();
+        if now.duration_since(failover.last_checked) < failover.check_interval {
+            return;
+        }
+        failover.last_checked = now;
+        if failover.is_behind_decision() {
+            self.follow_failover_decision();
+            return;
+        }
+        if failover.is_on_fallback {
+            if is_writable(&failover.primary) {
+                failover.decision.decide(false);
+                self.switch_database(false);
+            }
+            return;
+        }
+        if !self.client.is_connection_dropped() {
+            failover.decision.record_reachable();
+            return;
+        }
+        warn!(
+            "The health check of the connection to the PostgreSQL database failed, reconnecting."
+        );
+        match self.client.reconnect(&self.config) {
+            Ok(()) => {
+                if let Some(failover) = &self.failover {
+                    failover.record_reachable();
+                }
+            }
+            Err(err) => {
+                error!(
+                    "Failed to reconnect to the primary PostgreSQL database: ({})",
+                    err
+                );
+                self.fail_over_if_due();
+            }
+        }
+    }
+
+    /// Reconnect to the database decided by another worker when the worker is not
+    /// connected to it. Returns whether the worker is connected to it.
+    pub(crate) fn follow_failover_decision(&mut self) -> bool {
+        match &self.failover {
+            Some(failover) if failover.is_behind_decision() => {
+                let is_on_fallback = failover.decision.is_on_fallback();
+                self.switch_database(is_on_fallback)
+            }
+            _ => false,
+        }
+    }
+
+    /// Fail over to the standby when the primary has been unreachable for longer than
+    /// the threshold, after a failed reconnection, or when another worker has failed
+    /// over. Returns whether the worker is connected to the standby.
+    pub(crate) fn fail_over_if_due(&mut self) -> bool {
+        let Some(failover) = &self.failover else {
+            return false;
+        };
+        if failover.is_on_fallback {
+            return false;
+        }
+        if !failover.decision.is_on_fallback() && !failover.is_failover_due(Instant::now()) {
+            return false;
+        }
+        if !self.switch_database(true) {
+            return false;
+        }
+        if let Some(failover) = &self.failover {
+            failover.decision.decide(true);
+        }
+        true
+    }
+
+    /// Reconnect to the standby, or to the primary. The updates committed within the
+    /// replay window are written again to the database switched to, as the standby may
+    /// lag behind the primary, and the primary misses the updates written to the
+    /// standby. Returns whether the worker is connected to it.
+    fn switch_database(&mut self, to_fallback: bool) -> bool {
+        let Some(failover) = &mut self.failover else {
+            return false;
+        };
+        let config = if to_fallback {
+            &failover.fallback
+        } else {
+            &failover.primary
+        };
+        if let Err(err) = self.client.reconnect(config) {
+            error!(
+                "Failed to {} the {} PostgreSQL database: ({})",
+                if to_fallback {
+                    "fail over to"
+                } else {
+                    "fail back to"
+                },
+                if to_fallback { "standby" } else { "primary" },
+                err
+            );
+            return false;
+        }
+        self.config = config.clone();
+        failover.is_on_fallback = to_fallback;
+        if to_fallback {
+            warn!("Failed over to the standby PostgreSQL database");
+            inc_new_counter_info!("accountsdb-plugin-postgres-failover", 1);
+        } else {
+            info!("Failed back to the primary PostgreSQL database");
+            inc_new_counter_info!("accountsdb-plugin-postgres-failback", 1);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.record_failover(to_fallback);
+        }
+        if let Some(buffer) = &mut self.reconnect_buffer {
+            let replayed = buffer.replay_committed();
+            if replayed > 0 {
+                info!(
+                    "Replaying {} updates committed within the replay window to the {}",
+                    replayed,
+                    if to_fallback { "standby" } else { "primary" }
+                );
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failover() {
+        let config: AccountsDbPluginPostgresConfig =
+            serde_json::from_str("{\"connection_str\": \"host=primary\"}").unwrap();
+        assert!(Failover::new(&config, Arc::default()).is_none());
+
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str(
+            "{\"connection_str\": \"host=primary\", \"fallback_connection_str\": \"host=standby\", \
+            \"failover_after_secs\": 10}",
+        )
+        .unwrap();
+        let failover = Failover::new(&config, Arc::default()).unwrap();
+        assert_eq!(
+            failover.primary.connection_str.as_deref(),
+            Some("host=primary")
+        );
+        assert_eq!(
+            failover.fallback.connection_str.as_deref(),
+            Some("host=standby")
+        );
+        assert_eq!(
+            failover.check_interval,
+            Duration::from_secs(DEFAULT_HEALTH_CHECK_INTERVAL_SECS)
+        );
+
+        let start = Instant::now();
+        assert!(!failover.is_failover_due(start));
+        assert!(!failover.is_failover_due(start + Duration::from_secs(9)));
+        assert!(failover.is_failover_due(start + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn test_failover_decision() {
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str(
+            "{\"connection_str\": \"host=primary\", \"fallback_connection_str\": \"host=standby\", \
+            \"failover_after_secs\": 10}",
+        )
+        .unwrap();
+        let decision = Arc::new(FailoverDecision::default());
+        let mut failover = Failover::new(&config, decision.clone()).unwrap();
+        let mut other = Failover::new(&config, decision.clone()).unwrap();
+
+        // The primary found unreachable by a worker is so for the others
+        let start = Instant::now();
+        assert!(!failover.is_failover_due(start));
+        assert!(other.is_failover_due(start + Duration::from_secs(10)));
+        // Until a worker reaches it
+        other.record_reachable();
+        assert!(!failover.is_failover_due(start + Duration::from_secs(10)));
+
+        // The worker failing over leaves the others behind until they follow it
+        decision.decide(true);
+        failover.is_on_fallback = true;
+        assert!(!failover.is_behind_decision());
+        assert!(other.is_behind_decision());
+        other.is_on_fallback = true;
+        assert!(!other.is_behind_decision());
+        // The primary reached from the standby is not recorded as reachable
+        failover.is_failover_due(start);
+        failover.record_reachable();
+        assert!(decision.unreachable_since.lock().unwrap().is_some());
+
+        // The worker failing back leaves the others behind as well
+        decision.decide(false);
+        assert!(decision.unreachable_since.lock().unwrap().is_none());
+        other.is_on_fallback = false;
+        assert!(failover.is_behind_decision());
+        assert!(!other.is_behind_decision());
+    }
+}
@@ -0,0 +1,230 @@
+/// Module responsible for the cooperative flow control of the downstream consumers,
+/// listening on a PostgreSQL notification channel for the consumers signaling to slow
+/// down or to pause, and tightening the backpressure of the account updates meanwhile.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::SimplePostgresClient,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    postgres::{fallible_iterator::FallibleIterator, Client},
+    std::{
+        sync::{
+            atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering},
+            Arc,
+        },
+        thread::{sleep, Builder, JoinHandle},
+        time::{Duration, Instant},
+    },
+};
+
+const FLOW_CONTROL_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+/// A signal lapses unless repeated within this time, so that a consumer exiting
+/// without resuming does not hold back the plugin
+const FLOW_CONTROL_SIGNAL_TTL: Duration = Duration::from_secs(60);
+/// The backpressure threshold is divided by this while the consumers slow down
+const SLOW_DOWN_THRESHOLD_DIVISOR: usize = 4;
+
+/// The signals of the consumers, the payloads of their notifications
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+enum FlowSignal {
+    Resume,
+    SlowDown,
+    Paused,
+}
+
+impl FlowSignal {
+    fn parse(payload: &str) -> Option<Self> {
+        match payload.trim() {
+            "resume" => Some(Self::Resume),
+            "slow_down" => Some(Self::SlowDown),
+            "paused" => Some(Self::Paused),
+            _ => None,
+        }
+    }
+}
+
+/// The signal of the consumers shared by the listener and the backpressure
+pub(crate) struct FlowControl {
+    started: Instant,
+    signal: AtomicU8,
+    /// The milliseconds since the start the signal lapses at
+    expires_at_ms: AtomicU64,
+}
+
+impl Default for FlowControl {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            signal: AtomicU8::new(FlowSignal::Resume as u8),
+            expires_at_ms: AtomicU64::default(),
+        }
+    }
+}
+
+impl FlowControl {
+    fn elapsed_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    fn record(&self, signal: FlowSignal) {
+        self.signal.store(signal as u8, Ordering::Relaxed);
+        self.expires_at_ms.store(
+            self.elapsed_ms() + FLOW_CONTROL_SIGNAL_TTL.as_millis() as u64,
+            Ordering::Relaxed,
+        );
+    }
+
+    fn signal(&self) -> FlowSignal {
+        if self.elapsed_ms() >= self.expires_at_ms.load(Ordering::Relaxed) {
+            return FlowSignal::Resume;
+        }
+        match self.signal.load(Ordering::Relaxed) {
+            signal if signal == FlowSignal::SlowDown as u8 => FlowSignal::SlowDown,
+            signal if signal == FlowSignal::Paused as u8 => FlowSignal::Paused,
+            _ => FlowSignal::Resume,
+        }
+    }
+
+    /// The backpressure threshold per the signal of the consumers, a quarter of it while
+    /// they slow down and none while they are paused, the updates then blocking up to
+    /// the max block time.
+    pub(crate) fn queue_threshold(&self, queue_threshold: usize) -> usize {
+        match self.signal() {
+            FlowSignal::Resume => queue_threshold,
+            FlowSignal::SlowDown => (queue_threshold / SLOW_DOWN_THRESHOLD_DIVISOR).max(1),
+            FlowSignal::Paused => 0,
+        }
+    }
+}
+
+pub(crate) struct FlowControlListener {
+    client: Client,
+    channel: String,
+    flow_control: Arc<FlowControl>,
+}
+
+impl FlowControlListener {
+    /// Connect and listen on the channel when the flow control is configured, a failure
+    /// to listen fails the loading of the plugin.
+    pub(crate) fn new(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Option<Self>, GeyserPluginError> {
+        let Some(channel) = &config.flow_control_channel else {
+            return Ok(None);
+        };
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+        if let Err(err) = client.batch_execute(&format!("LISTEN {}", channel)) {
+            let msg = format!(
+                "Failed to listen on the flow control channel \"{}\" of the PostgreSQL database. Error: {:?}",
+                channel, err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+            )));
+        }
+        Ok(Some(Self {
+            client,
+            channel: channel.clone(),
+            flow_control: Arc::default(),
+        }))
+    }
+
+    pub(crate) fn flow_control(&self) -> Arc<FlowControl> {
+        self.flow_control.clone()
+    }
+
+    /// Record the signals notified on the channel within the check interval. Returns
+    /// whether the connection is still usable.
+    fn receive(&mut self) -> bool {
+        let mut notifications = self.client.notifications();
+        let mut iter = notifications.timeout_iter(FLOW_CONTROL_CHECK_INTERVAL);
+        loop {
+            match iter.next() {
+                Ok(Some(notification)) => match FlowSignal::parse(notification.payload()) {
+                    Some(signal) => {
+                        if signal != self.flow_control.signal() {
+                            info!(
+                                "The consumers signaled {:?} on the flow control channel \"{}\"",
+                                signal, self.channel
+                            );
+                        }
+                        self.flow_control.record(signal);
+                    }
+                    None => warn!(
+                        "Ignored the flow control signal {:?}, neither 'resume', 'slow_down' nor 'paused'",
+                        notification.payload()
+                    ),
+                },
+                Ok(None) => return true,
+                Err(err) => {
+                    error!(
+                        "Failed to receive the flow control signals from the PostgreSQL database. Error: {:?}",
+                        err
+                    );
+                    return false;
+                }
+            }
+        }
+    }
+
+    /// Listen for the signals of the consumers until the exit. The signals lapse while
+    /// the connection is lost, it is connected again after the check interval.
+    pub(crate) fn spawn(
+        mut self,
+        config: AccountsDbPluginPostgresConfig,
+        exit_worker: Arc<AtomicBool>,
+    ) -> JoinHandle<Result<(), GeyserPluginError>> {
+        Builder::new()
+            .name("flow-control".to_string())
+            .spawn(move || -> Result<(), GeyserPluginError> {
+                while !exit_worker.load(Ordering::Relaxed) {
+                    if self.receive() {
+                        continue;
+                    }
+                    self.flow_control.record(FlowSignal::Resume);
+                    while !exit_worker.load(Ordering::Relaxed) {
+                        sleep(FLOW_CONTROL_CHECK_INTERVAL);
+                        // The errors are logged, the connection is attempted again
+                        if let Ok(Some(listener)) = Self::new(&config) {
+                            self.client = listener.client;
+                            break;
+                        }
+                    }
+                }
+                Ok(())
+            })
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flow_control_queue_threshold() {
+        assert_eq!(FlowSignal::parse("slow_down\n"), Some(FlowSignal::SlowDown));
+        assert_eq!(FlowSignal::parse("stop"), None);
+
+        let flow_control = FlowControl::default();
+        assert_eq!(flow_control.queue_threshold(1000), 1000);
+        flow_control.record(FlowSignal::SlowDown);
+        assert_eq!(flow_control.queue_threshold(1000), 250);
+        assert_eq!(flow_control.queue_threshold(2), 1);
+        flow_control.record(FlowSignal::Paused);
+        assert_eq!(flow_control.queue_threshold(1000), 0);
+        flow_control.record(FlowSignal::Resume);
+        assert_eq!(flow_control.queue_threshold(1000), 1000);
+
+        // The signal lapses
+        flow_control.record(FlowSignal::Paused);
+        flow_control.expires_at_ms.store(0, Ordering::Relaxed);
+        assert_eq!(flow_control.queue_threshold(1000), 1000);
+    }
+}
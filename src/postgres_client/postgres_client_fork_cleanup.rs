@@ -0,0 +1,185 @@
+/// Module responsible for cleaning up the slots of the abandoned forks, which are never
+/// rooted, marking them as abandoned in the slot table and purging their rows when
+/// configured so, so that the writes of the minority forks do not show up in queries.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{AccountsDbPluginPostgresError, ForkCleanup},
+        postgres_client::{DbWorkItem, ParallelPostgresClient, SimplePostgresClient},
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    std::sync::atomic::Ordering,
+};
+
+pub(crate) const DEFAULT_FORK_CLEANUP_INTERVAL_SLOTS: u64 = 32;
+
+/// The tables whose rows of the abandoned slots are purged
+pub(crate) const FORK_CLEANUP_TABLES: [&str; 15] = [
+    "account_audit",
+    "account_version",
+    "owner_change",
+    "transaction",
+    "transaction_address",
+    "sol_transfer",
+    "transaction_memo",
+    "transaction_instruction",
+    "transaction_balance",
+    "transaction_token_balance",
+    "transaction_reward",
+    "transaction_log",
+    "block",
+    "block_reward",
+    "entry",
+];
+
+/// Select the slots of a range which are not on the chain of the parents of the highest
+/// rooted slot. The slots below a parent missing from the slot table, whose chain is
+/// unknown, and the ones above the highest rooted slot stored are left out.
+const ABANDONED_SLOTS_QUERY: &str = "WITH RECURSIVE chain (slot, parent) AS ( \
+    SELECT slot, parent FROM slot WHERE slot = (SELECT MAX(slot) FROM slot WHERE rooted_on IS NOT NULL) \
+    UNION ALL \
+    SELECT s.slot, s.parent FROM slot s JOIN chain c ON s.slot = c.parent WHERE c.parent > $1), \
+    bounds AS (SELECT MAX(slot) AS top, \
+    (SELECT COALESCE(c.parent, c.slot) FROM chain c ORDER BY c.slot LIMIT 1) AS bottom FROM chain) \
+    SELECT s.slot FROM slot s, bounds b \
+    WHERE s.slot > GREATEST($1, b.bottom) AND s.slot <= LEAST($2, b.top) \
+    AND s.rooted_on IS NULL AND s.slot NOT IN (SELECT slot FROM chain)";
+
+#[derive(Clone)]
+pub struct CleanUpForksRequest {
+    /// The cleanup covers the slots after this slot
+    pub from_slot: u64,
+    /// The cleanup covers the slots up to and including this slot
+    pub to_slot: u64,
+}
+
+/// The range of the next cleanup once the rooted slot trails the one cleaned up to by
+/// two intervals, the range trailing the rooted slot by one interval to let the slot
+/// statuses and the rows of the recent slots be written first.
+fn cleanup_range(
+    rooted_slot: u64,
+    cleaned_until: Option<u64>,
+    interval: u64,
+) -> Option<CleanUpForksRequest> {
+    let to_slot = rooted_slot.checked_sub(interval)?;
+    let from_slot = cleaned_until.unwrap_or_else(|| to_slot.saturating_sub(interval));
+    (to_slot >= from_slot + interval).then_some(CleanUpForksRequest { from_slot, to_slot })
+}
+
+impl SimplePostgresClient {
+    pub(crate) fn clean_up_forks_impl(
+        &mut self,
+        request: &CleanUpForksRequest,
+    ) -> Result<(), GeyserPluginError> {
+        let Some(fork_cleanup) = self.fork_cleanup else {
+            return Ok(());
+        };
+        let client = &mut self.client.get_mut().unwrap().client;
+        let to_plugin_error = |err: postgres::Error| {
+            let msg = format!(
+                "Failed to clean up the abandoned forks in the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+            ))
+        };
+
+        let from_slot = request.from_slot as i64;
+        let to_slot = request.to_slot as i64;
+        let mut transaction = client.transaction().map_err(to_plugin_error)?;
+        let slots: Vec<i64> = transaction
+            .query(ABANDONED_SLOTS_QUERY, &[&from_slot, &to_slot])
+            .map_err(to_plugin_error)?
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        if slots.is_empty() {
+            return Ok(());
+        }
+        // The dead slots keep their status along with the reason
+        transaction
+            .execute(
+                "UPDATE slot SET status = 'abandoned' WHERE slot = ANY($1) AND status <> 'dead'",
+                &[&slots],
+            )
+            .map_err(to_plugin_error)?;
+        if fork_cleanup == ForkCleanup::Purge {
+            for table in FORK_CLEANUP_TABLES {
+                transaction
+                    .execute(
+                        &format!("DELETE FROM {} WHERE slot = ANY($1)", table),
+                        &[&slots],
+                    )
+                    .map_err(to_plugin_error)?;
+            }
+        }
+        transaction.commit().map_err(to_plugin_error)?;
+
+        info!(
+            "Cleaned up the abandoned slots {:?} in the slots ({}, {}]",
+            slots, from_slot, to_slot
+        );
+        Ok(())
+    }
+}
+
+impl ParallelPostgresClient {
+    /// Clean up the abandoned forks every interval of rooted slots, up to the slots one
+    /// interval behind the rooted slot.
+    pub(crate) fn clean_up_forks_on_root(&self, rooted_slot: u64) -> Result<(), GeyserPluginError> {
+        let Some(interval) = self.fork_cleanup_interval else {
+            return Ok(());
+        };
+        // The slot cleaned up to is stored plus one so that zero stands for none
+        let cleaned_until = self.fork_cleaned_until.load(Ordering::Relaxed);
+        let Some(request) = cleanup_range(rooted_slot, cleaned_until.checked_sub(1), interval)
+        else {
+            return Ok(());
+        };
+        if self
+            .fork_cleaned_until
+            .compare_exchange(
+                cleaned_until,
+                request.to_slot + 1,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            return Ok(());
+        }
+        let to_slot = request.to_slot;
+        if let Err(err) = self
+            .queue_overflow
+            .send(DbWorkItem::CleanUpForks(Box::new(request)))
+        {
+            return Err(GeyserPluginError::SlotStatusUpdateError {
+                msg: format!(
+                    "Failed to clean up the abandoned forks up to slot {:?}, error: {:?}",
+                    to_slot, err
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cleanup_range() {
+        let range = |rooted_slot, cleaned_until| {
+            cleanup_range(rooted_slot, cleaned_until, 32)
+                .map(|request| (request.from_slot, request.to_slot))
+        };
+        assert_eq!(range(10, None), None);
+        assert_eq!(range(1000, None), Some((936, 968)));
+        assert_eq!(range(1031, Some(968)), None);
+        assert_eq!(range(1032, Some(968)), Some((968, 1000)));
+        assert_eq!(range(1200, Some(968)), Some((968, 1168)));
+    }
+}
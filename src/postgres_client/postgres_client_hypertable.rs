@@ -0,0 +1,196 @@
+/// Module responsible for the TimescaleDB storage engine, converting the account_audit,
+/// transaction and block tables into hypertables chunked by the slot when the plugin is
+/// loaded, along with the compression policies of their chunks when configured.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError, StorageEngine,
+        },
+        postgres_client::SimplePostgresClient,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+};
+
+/// The slots of a chunk, about two days
+pub(crate) const DEFAULT_HYPERTABLE_CHUNK_SLOTS: u64 = 432000;
+
+/// The tables converted into hypertables, along with the column their compressed chunks
+/// are segmented by
+const HYPERTABLES: [(&str, Option<&str>); 3] = [
+    ("account_audit", Some("pubkey")),
+    ("transaction", None),
+    ("block", None),
+];
+
+/// The current time of the integer partitioning by the slot, for the compression
+/// policies and the continuous aggregates
+const HIGHEST_SLOT_FUNCTION: &str = "CREATE OR REPLACE FUNCTION highest_slot() RETURNS BIGINT \
+    LANGUAGE SQL STABLE AS $$ SELECT COALESCE(MAX(slot), 0) FROM slot $$";
+
+pub(crate) fn is_timescaledb(config: &AccountsDbPluginPostgresConfig) -> bool {
+    config.storage_engine.unwrap_or_default() == StorageEngine::TimescaleDb
+}
+
+/// Replace the account_audit table partitioned by the slot ranges by a table without the
+/// partitions, as a partitioned table cannot be a hypertable
+const UNPARTITION_ACCOUNT_AUDIT_SQL: &str = "CREATE TABLE account_audit_hypertable \
+    (LIKE account_audit INCLUDING ALL EXCLUDING INDEXES); \
+    INSERT INTO account_audit_hypertable SELECT * FROM account_audit; \
+    DROP TABLE account_audit; \
+    ALTER TABLE account_audit_hypertable RENAME TO account_audit; \
+    CREATE INDEX account_audit_account_key ON account_audit (pubkey, write_version); ";
+
+/// The statements converting the table into a hypertable, the partitioned account_audit
+/// table being replaced first.
+fn hypertable_statement(table: &str, is_partitioned: bool, chunk_slots: u64) -> String {
+    let unpartition = if is_partitioned && table == "account_audit" {
+        UNPARTITION_ACCOUNT_AUDIT_SQL
+    } else {
+        ""
+    };
+    format!(
+        "{unpartition}SELECT create_hypertable('{table}', by_range('slot', {chunk_slots}), migrate_data => true); \
+        SELECT set_integer_now_func('{table}', 'highest_slot', replace_if_exists => true)"
+    )
+}
+
+/// The statements compressing the chunks of the table older than the slots.
+fn compression_statement(
+    table: &str,
+    segment_by: Option<&str>,
+    compress_after_slots: u64,
+) -> String {
+    let segment_by = segment_by
+        .map(|column| format!(", timescaledb.compress_segmentby = '{}'", column))
+        .unwrap_or_default();
+    format!(
+        "ALTER TABLE {table} SET (timescaledb.compress, timescaledb.compress_orderby = 'slot DESC'{segment_by}); \
+        SELECT add_compression_policy('{table}', compress_after => {compress_after_slots}::BIGINT, if_not_exists => true)"
+    )
+}
+
+fn to_hypertable_error(action: &str, err: postgres::Error) -> GeyserPluginError {
+    let msg = format!(
+        "Failed to {} of the TimescaleDB storage engine in the PostgreSQL database. Error: {:?}",
+        action, err
+    );
+    error!("{}", msg);
+    GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+        msg,
+    }))
+}
+
+impl SimplePostgresClient {
+    /// Convert the tables into hypertables when the storage engine is TimescaleDB, under
+    /// the advisory lock of the schema. The chunk interval of the hypertables is set to
+    /// the one configured, and the compression policies are added once. The loading of
+    /// the plugin fails when the timescaledb extension is not installed.
+    pub(crate) fn create_hypertables(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        if !is_timescaledb(config) {
+            return Ok(());
+        }
+        let chunk_slots = config
+            .hypertable_chunk_slots
+            .unwrap_or(DEFAULT_HYPERTABLE_CHUNK_SLOTS);
+        let mut client = Self::connect_to_db(config)?;
+        let mut transaction = client
+            .transaction()
+            .map_err(|err| to_hypertable_error("start the creation of the hypertables", err))?;
+        let schema = config.schema.as_deref().unwrap_or("public");
+        transaction
+            .execute("SELECT pg_advisory_xact_lock(hashtext($1))", &[&schema])
+            .map_err(|err| to_hypertable_error("acquire the schema lock", err))?;
+        let is_installed: bool = transaction
+            .query_one(
+                "SELECT EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'timescaledb')",
+                &[],
+            )
+            .map_err(|err| to_hypertable_error("look up the timescaledb extension", err))?
+            .get(0);
+        if !is_installed {
+            return Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::ConfigurationError {
+                    msg: "The \"storage_engine\" 'timescaledb' requires the timescaledb extension \
+                        to be installed in the database"
+                        .to_string(),
+                },
+            )));
+        }
+        transaction
+            .batch_execute(HIGHEST_SLOT_FUNCTION)
+            .map_err(|err| to_hypertable_error("create the highest_slot function", err))?;
+        for (table, segment_by) in HYPERTABLES {
+            let row = transaction
+                .query_one(
+                    "SELECT (SELECT relkind = 'p' FROM pg_class WHERE oid = $1::TEXT::regclass), \
+                    (SELECT compression_enabled FROM timescaledb_information.hypertables \
+                    WHERE hypertable_schema = current_schema() AND hypertable_name = $1)",
+                    &[&table],
+                )
+                .map_err(|err| to_hypertable_error("look up the hypertables", err))?;
+            let is_partitioned: bool = row.get(0);
+            let compression_enabled: Option<bool> = row.get(1);
+            match compression_enabled {
+                Some(_) => transaction
+                    .execute(
+                        "SELECT set_chunk_time_interval($1::TEXT::regclass, $2::BIGINT)",
+                        &[&table, &(chunk_slots as i64)],
+                    )
+                    .map(|_| ())
+                    .map_err(|err| to_hypertable_error("set the chunk interval", err))?,
+                None => {
+                    info!("Converting the {} table into a hypertable", table);
+                    transaction
+                        .batch_execute(&hypertable_statement(table, is_partitioned, chunk_slots))
+                        .map_err(|err| to_hypertable_error("create the hypertables", err))?;
+                }
+            }
+            // The compression is left alone once enabled, its settings cannot change
+            // while some chunks are compressed
+            let compress_after_slots = config
+                .hypertable_compress_after_slots
+                .filter(|_| !compression_enabled.unwrap_or_default());
+            if let Some(compress_after_slots) = compress_after_slots {
+                transaction
+                    .batch_execute(&compression_statement(
+                        table,
+                        segment_by,
+                        compress_after_slots,
+                    ))
+                    .map_err(|err| to_hypertable_error("add the compression policies", err))?;
+            }
+        }
+        transaction
+            .commit()
+            .map_err(|err| to_hypertable_error("commit the creation of the hypertables", err))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hypertable_statement() {
+        assert_eq!(
+            hypertable_statement("block", false, 1000),
+            "SELECT create_hypertable('block', by_range('slot', 1000), migrate_data => true); \
+            SELECT set_integer_now_func('block', 'highest_slot', replace_if_exists => true)"
+        );
+        let stmt = hypertable_statement("account_audit", true, 1000);
+        assert!(stmt.starts_with(UNPARTITION_ACCOUNT_AUDIT_SQL));
+        assert!(stmt.contains("CREATE INDEX account_audit_account_key ON account_audit"));
+        assert!(stmt.contains("SELECT create_hypertable('account_audit'"));
+
+        assert_eq!(
+            compression_statement("account_audit", Some("pubkey"), 864000),
+            "ALTER TABLE account_audit SET (timescaledb.compress, timescaledb.compress_orderby = 'slot DESC', \
+            timescaledb.compress_segmentby = 'pubkey'); \
+            SELECT add_compression_policy('account_audit', compress_after => 864000::BIGINT, if_not_exists => true)"
+        );
+        assert!(!compression_statement("transaction", None, 864000).contains("segmentby"));
+    }
+}
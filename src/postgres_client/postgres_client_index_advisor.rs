@@ -0,0 +1,590 @@
+/// Module responsible for advising the indexes and the partitioning of the tables of the
+/// plugin from the queries observed by pg_stat_statements, reporting the predicates of
+/// the frequent queries not served by an index into the index_advice table, so that the
+/// operators can tune the schema for their actual workloads.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::SimplePostgresClient,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    postgres::Client,
+    std::{
+        collections::{BTreeMap, HashMap},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread::{sleep, Builder, JoinHandle},
+        time::{Duration, Instant},
+    },
+};
+
+const INDEX_ADVISOR_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+const DEFAULT_INDEX_ADVISOR_MIN_CALLS: u64 = 100;
+/// The most frequent queries inspected per run
+const INDEX_ADVISOR_MAX_QUERIES: i64 = 1000;
+/// The size of the tables above which the ones queried by slot ranges are advised to be
+/// partitioned by the slot
+const PARTITION_ADVICE_MIN_BYTES: i64 = 64 << 30;
+/// The table of the report, left out of the tables advised for
+const INDEX_ADVICE_TABLE: &str = "index_advice";
+
+/// The SQL keywords ending the WHERE clause of a query
+const WHERE_CLAUSE_ENDS: [&str; 6] = [
+    " group by ",
+    " order by ",
+    " limit ",
+    " offset ",
+    " returning ",
+    " for update",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum AdviceKind {
+    Index,
+    Partition,
+}
+
+impl AdviceKind {
+    fn name(&self) -> &'static str {
+        match self {
+            AdviceKind::Index => "index",
+            AdviceKind::Partition => "partition",
+        }
+    }
+}
+
+/// The columns of a table compared by the WHERE clause of a query, the ones compared
+/// for equality first, as they lead the indexes serving the query
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Predicates {
+    equality: Vec<String>,
+    range: Vec<String>,
+}
+
+impl Predicates {
+    /// The columns of the index recommended for the predicates, the equality columns
+    /// followed by the first range column
+    fn index_columns(&self) -> Vec<String> {
+        self.equality
+            .iter()
+            .chain(self.range.first())
+            .cloned()
+            .collect()
+    }
+
+    /// Whether an index of the table serves the predicates, one led by an equality
+    /// column, or by the range column without equality columns
+    fn is_covered(&self, indexes: &[Vec<String>]) -> bool {
+        indexes.iter().any(|index| {
+            index.first().is_some_and(|column| {
+                if self.equality.is_empty() {
+                    self.range.contains(column)
+                } else {
+                    self.equality.contains(column)
+                }
+            })
+        })
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Whether the normalized query mentions the table after FROM or JOIN.
+fn mentions_table(query: &str, table: &str) -> bool {
+    ["from ", "join "].iter().any(|keyword| {
+        query.match_indices(keyword).any(|(position, _)| {
+            let rest = &query[position + keyword.len()..];
+            rest.strip_prefix(table)
+                .is_some_and(|rest| !rest.starts_with(is_identifier_char))
+                && query[..position]
+                    .chars()
+                    .next_back()
+                    .is_none_or(|c| !is_identifier_char(c))
+        })
+    })
+}
+
+/// The WHERE clause of the normalized query, None when it has none.
+fn where_clause(query: &str) -> Option<&str> {
+    let (_, clause) = query.split_once(" where ")?;
+    let end = WHERE_CLAUSE_ENDS
+        .iter()
+        .filter_map(|keyword| clause.find(keyword))
+        .min()
+        .unwrap_or(clause.len());
+    Some(&clause[..end])
+}
+
+/// The columns of the table compared in the WHERE clause of the query, lowercased, in
+/// the order of the columns of the table.
+fn predicate_columns(query: &str, columns: &[String]) -> Predicates {
+    let query = query.to_lowercase();
+    let mut predicates = Predicates::default();
+    let Some(clause) = where_clause(&query) else {
+        return predicates;
+    };
+    for column in columns {
+        let kinds = clause
+            .match_indices(column.as_str())
+            .filter_map(|(position, _)| {
+                let before = clause[..position].chars().next_back();
+                if before.is_some_and(|c| is_identifier_char(c) || c == '$') {
+                    return None;
+                }
+                let rest = clause[position + column.len()..].trim_start();
+                if ["= any", "in ", "in("]
+                    .iter()
+                    .any(|operator| rest.starts_with(operator))
+                {
+                    Some(true)
+                } else if ["between ", "like ", "ilike "]
+                    .iter()
+                    .any(|keyword| rest.starts_with(keyword))
+                {
+                    Some(false)
+                } else if rest.starts_with("<>") || rest.starts_with("!=") {
+                    None
+                } else if rest.starts_with('=') {
+                    Some(true)
+                } else if rest.starts_with('<') || rest.starts_with('>') {
+                    Some(false)
+                } else {
+                    // A longer identifier, or a column not compared
+                    None
+                }
+            });
+        // A column compared for equality anywhere in the clause counts as an equality
+        let mut is_range = false;
+        for is_equality in kinds {
+            if is_equality {
+                predicates.equality.push(column.clone());
+                is_range = false;
+                break;
+            }
+            is_range = true;
+        }
+        if is_range {
+            predicates.range.push(column.clone());
+        }
+    }
+    predicates
+}
+
+/// A table of the plugin as found in the catalog
+#[derive(Debug, Default)]
+struct TableInfo {
+    columns: Vec<String>,
+    indexes: Vec<Vec<String>>,
+    is_partitioned: bool,
+    size: i64,
+}
+
+/// A query observed by pg_stat_statements
+struct ObservedQuery {
+    query: String,
+    calls: i64,
+    total_exec_time: f64,
+}
+
+/// An advice of the report, aggregating the queries advised for
+#[derive(Debug, PartialEq)]
+struct Advice {
+    calls: i64,
+    total_exec_time: f64,
+    /// The slowest query in total of the ones advised for
+    query: String,
+    query_exec_time: f64,
+}
+
+/// Advise the indexes of the predicates of the queries on a single table not served by
+/// an index, and the partitioning by the slot of the large tables queried by slot
+/// ranges, keyed by the table, the kind of advice and the columns.
+fn advise(
+    tables: &HashMap<String, TableInfo>,
+    queries: &[ObservedQuery],
+) -> BTreeMap<(String, AdviceKind, Vec<String>), Advice> {
+    let mut advices = BTreeMap::<_, Advice>::default();
+    for observed in queries {
+        let query = observed.query.to_lowercase();
+        let mut mentioned = tables
+            .iter()
+            .filter(|(table, _)| mentions_table(&query, table));
+        // The plans of the joins are left to the operators
+        let (Some((table, info)), None) = (mentioned.next(), mentioned.next()) else {
+            continue;
+        };
+        let predicates = predicate_columns(&query, &info.columns);
+        let mut keys = vec![];
+        if !predicates.index_columns().is_empty() && !predicates.is_covered(&info.indexes) {
+            keys.push((table.clone(), AdviceKind::Index, predicates.index_columns()));
+        }
+        if !info.is_partitioned
+            && info.size >= PARTITION_ADVICE_MIN_BYTES
+            && predicates.range.iter().any(|column| column == "slot")
+        {
+            keys.push((
+                table.clone(),
+                AdviceKind::Partition,
+                vec!["slot".to_string()],
+            ));
+        }
+        for key in keys {
+            let advice = advices.entry(key).or_insert_with(|| Advice {
+                calls: 0,
+                total_exec_time: 0.0,
+                query: observed.query.clone(),
+                query_exec_time: observed.total_exec_time,
+            });
+            advice.calls += observed.calls;
+            advice.total_exec_time += observed.total_exec_time;
+            if observed.total_exec_time > advice.query_exec_time {
+                advice.query = observed.query.clone();
+                advice.query_exec_time = observed.total_exec_time;
+            }
+        }
+    }
+    advices
+}
+
+/// The DDL carrying out the advice, None for the partitioning, which requires the
+/// table to be recreated.
+fn advice_statement(table: &str, kind: AdviceKind, columns: &[String]) -> Option<String> {
+    (kind == AdviceKind::Index).then(|| {
+        format!(
+            "CREATE INDEX CONCURRENTLY ON {} ({})",
+            table,
+            columns.join(", ")
+        )
+    })
+}
+
+fn to_advisor_error(action: &str, err: postgres::Error) -> GeyserPluginError {
+    let msg = format!(
+        "Failed to {} for the index advice in the PostgreSQL database. Error: {:?}",
+        action, err
+    );
+    error!("{}", msg);
+    GeyserPluginError::Custom(Box::new(
+        AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+    ))
+}
+
+pub(crate) struct IndexAdvisor {
+    client: Client,
+    interval: Duration,
+    min_calls: i64,
+}
+
+impl IndexAdvisor {
+    /// Connect when the index advisor is configured, the loading of the plugin fails
+    /// when the pg_stat_statements extension is not installed in the database.
+    pub(crate) fn new(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Option<Self>, GeyserPluginError> {
+        let Some(interval_secs) = config.index_advisor_interval_secs else {
+            return Ok(None);
+        };
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+        let is_installed = !client
+            .query(
+                "SELECT 1 FROM pg_extension WHERE extname = 'pg_stat_statements'",
+                &[],
+            )
+            .map_err(|err| to_advisor_error("look up the pg_stat_statements extension", err))?
+            .is_empty();
+        if !is_installed {
+            return Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::ConfigurationError {
+                    msg: "The \"index_advisor_interval_secs\" requires the pg_stat_statements \
+                        extension to be created in the database"
+                        .to_string(),
+                },
+            )));
+        }
+        Ok(Some(Self {
+            client,
+            interval: Duration::from_secs(interval_secs),
+            min_calls: config
+                .index_advisor_min_calls
+                .unwrap_or(DEFAULT_INDEX_ADVISOR_MIN_CALLS) as i64,
+        }))
+    }
+
+    /// Look up the tables of the schema written to, along with their columns and the
+    /// leading columns of their indexes.
+    fn load_tables(&mut self) -> Result<HashMap<String, TableInfo>, GeyserPluginError> {
+        let mut tables = HashMap::<String, TableInfo>::default();
+        let rows = self
+            .client
+            .query(
+                "SELECT c.relname::TEXT, c.relkind = 'p', pg_total_relation_size(c.oid) \
+                FROM pg_class c WHERE c.relnamespace = current_schema()::regnamespace \
+                AND c.relkind IN ('r', 'p') AND NOT c.relispartition AND c.relname <> $1",
+                &[&INDEX_ADVICE_TABLE],
+            )
+            .map_err(|err| to_advisor_error("look up the tables", err))?;
+        for row in rows {
+            tables.insert(
+                row.get(0),
+                TableInfo {
+                    is_partitioned: row.get(1),
+                    size: row.get(2),
+                    ..TableInfo::default()
+                },
+            );
+        }
+
+        let rows = self
+            .client
+            .query(
+                "SELECT table_name::TEXT, column_name::TEXT FROM information_schema.columns \
+                WHERE table_schema = current_schema() ORDER BY table_name, ordinal_position",
+                &[],
+            )
+            .map_err(|err| to_advisor_error("look up the columns", err))?;
+        for row in rows {
+            if let Some(table) = tables.get_mut(row.get::<_, &str>(0)) {
+                table.columns.push(row.get::<_, &str>(1).to_lowercase());
+            }
+        }
+
+        let rows = self
+            .client
+            .query(
+                "SELECT t.relname::TEXT, ARRAY(SELECT a.attname::TEXT \
+                FROM UNNEST(i.indkey::SMALLINT[]) WITH ORDINALITY AS k (attnum, ord) \
+                JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = k.attnum \
+                ORDER BY k.ord) \
+                FROM pg_index i JOIN pg_class t ON t.oid = i.indrelid \
+                WHERE t.relnamespace = current_schema()::regnamespace",
+                &[],
+            )
+            .map_err(|err| to_advisor_error("look up the indexes", err))?;
+        for row in rows {
+            if let Some(table) = tables.get_mut(row.get::<_, &str>(0)) {
+                table.indexes.push(row.get(1));
+            }
+        }
+        Ok(tables)
+    }
+
+    /// Replace the report with the advices of the queries observed so far.
+    fn report(&mut self) -> Result<usize, GeyserPluginError> {
+        let tables = self.load_tables()?;
+        let queries: Vec<ObservedQuery> = self
+            .client
+            .query(
+                "SELECT s.query, s.calls, s.total_exec_time FROM pg_stat_statements s \
+                WHERE s.dbid = (SELECT oid FROM pg_database WHERE datname = current_database()) \
+                AND s.calls >= $1 AND s.query ILIKE 'select%' \
+                ORDER BY s.total_exec_time DESC LIMIT $2",
+                &[&self.min_calls, &INDEX_ADVISOR_MAX_QUERIES],
+            )
+            .map_err(|err| to_advisor_error("query pg_stat_statements", err))?
+            .iter()
+            .map(|row| ObservedQuery {
+                query: row.get(0),
+                calls: row.get(1),
+                total_exec_time: row.get(2),
+            })
+            .collect();
+        let advices = advise(&tables, &queries);
+
+        let advised_on = Utc::now().naive_utc();
+        let mut transaction = self
+            .client
+            .transaction()
+            .map_err(|err| to_advisor_error("begin the report", err))?;
+        transaction
+            .batch_execute(&format!("DELETE FROM {}", INDEX_ADVICE_TABLE))
+            .map_err(|err| to_advisor_error("clear the report", err))?;
+        for ((table, kind, columns), advice) in &advices {
+            let statement = advice_statement(table, *kind, columns);
+            let mean_exec_time = advice.total_exec_time / advice.calls.max(1) as f64;
+            transaction
+                .execute(
+                    "INSERT INTO index_advice (table_name, kind, columns, statement, calls, \
+                    total_exec_time_ms, mean_exec_time_ms, query, advised_on) \
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+                    &[
+                        table,
+                        &kind.name(),
+                        columns,
+                        &statement,
+                        &advice.calls,
+                        &advice.total_exec_time,
+                        &mean_exec_time,
+                        &advice.query,
+                        &advised_on,
+                    ],
+                )
+                .map_err(|err| to_advisor_error("insert the advice", err))?;
+        }
+        transaction
+            .commit()
+            .map_err(|err| to_advisor_error("commit the report", err))?;
+        Ok(advices.len())
+    }
+
+    /// Report the advices every interval until the exit.
+    pub(crate) fn spawn(
+        mut self,
+        exit_worker: Arc<AtomicBool>,
+    ) -> JoinHandle<Result<(), GeyserPluginError>> {
+        Builder::new()
+            .name("index-advisor".to_string())
+            .spawn(move || -> Result<(), GeyserPluginError> {
+                let mut last_report = Instant::now();
+                while !exit_worker.load(Ordering::Relaxed) {
+                    sleep(INDEX_ADVISOR_CHECK_INTERVAL);
+                    if last_report.elapsed() < self.interval {
+                        continue;
+                    }
+                    last_report = Instant::now();
+                    // The errors are logged, the report is attempted again next interval
+                    if let Ok(count) = self.report() {
+                        info!("Reported {} advices to the index_advice table", count);
+                    }
+                }
+                Ok(())
+            })
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn columns(columns: &[&str]) -> Vec<String> {
+        columns.iter().map(|column| column.to_string()).collect()
+    }
+
+    #[test]
+    fn test_predicate_columns() {
+        let table_columns = columns(&["slot", "signature", "account", "pre_balance"]);
+        let predicates = predicate_columns(
+            "SELECT slot, signature FROM transaction_balance \
+            WHERE account = $1 AND slot BETWEEN $2 AND $3 ORDER BY slot LIMIT $4",
+            &table_columns,
+        );
+        assert_eq!(
+            predicates,
+            Predicates {
+                equality: columns(&["account"]),
+                range: columns(&["slot"]),
+            }
+        );
+        assert_eq!(predicates.index_columns(), columns(&["account", "slot"]));
+
+        // The column aliases, the inequalities and the longer identifiers are told apart
+        let predicates = predicate_columns(
+            "select * from transaction_balance t where t.signature = any($1) \
+            and pre_balance <> $2 and slot_count > $3 and t.slot >= $4",
+            &table_columns,
+        );
+        assert_eq!(
+            predicates,
+            Predicates {
+                equality: columns(&["signature"]),
+                range: columns(&["slot"]),
+            }
+        );
+        assert_eq!(
+            predicate_columns("select count(*) from slot", &table_columns),
+            Predicates::default()
+        );
+    }
+
+    #[test]
+    fn test_advise() {
+        let tables = HashMap::from([
+            (
+                "transaction_balance".to_string(),
+                TableInfo {
+                    columns: columns(&["slot", "signature", "account"]),
+                    indexes: vec![columns(&["slot", "signature"])],
+                    is_partitioned: false,
+                    size: PARTITION_ADVICE_MIN_BYTES,
+                },
+            ),
+            (
+                "slot".to_string(),
+                TableInfo {
+                    columns: columns(&["slot", "parent", "status"]),
+                    indexes: vec![columns(&["slot"])],
+                    ..TableInfo::default()
+                },
+            ),
+        ]);
+        let observed = |query: &str, calls, total_exec_time| ObservedQuery {
+            query: query.to_string(),
+            calls,
+            total_exec_time,
+        };
+        let advices = advise(
+            &tables,
+            &[
+                observed(
+                    "select * from transaction_balance where account = $1",
+                    100,
+                    50.0,
+                ),
+                observed(
+                    "select * from transaction_balance where account = $1 limit $2",
+                    300,
+                    90.0,
+                ),
+                observed("select * from transaction_balance where slot > $1", 10, 5.0),
+                // Served by the primary key
+                observed("select * from slot where slot = $1", 1000, 10.0),
+                // The joins are left out
+                observed(
+                    "select * from slot s join transaction_balance b on b.slot = s.slot \
+                    where b.account = $1",
+                    1000,
+                    10.0,
+                ),
+            ],
+        );
+        let keys: Vec<_> = advices.keys().cloned().collect();
+        assert_eq!(
+            keys,
+            vec![
+                (
+                    "transaction_balance".to_string(),
+                    AdviceKind::Index,
+                    columns(&["account"])
+                ),
+                (
+                    "transaction_balance".to_string(),
+                    AdviceKind::Partition,
+                    columns(&["slot"])
+                ),
+            ]
+        );
+        let advice = &advices[&keys[0]];
+        assert_eq!(advice.calls, 400);
+        assert_eq!(advice.total_exec_time, 140.0);
+        assert_eq!(
+            advice.query,
+            "select * from transaction_balance where account = $1 limit $2"
+        );
+        assert_eq!(
+            advice_statement("transaction_balance", AdviceKind::Index, &keys[0].2).as_deref(),
+            Some("CREATE INDEX CONCURRENTLY ON transaction_balance (account)")
+        );
+        assert_eq!(
+            advice_statement("transaction_balance", AdviceKind::Partition, &keys[1].2),
+            None
+        );
+    }
+}
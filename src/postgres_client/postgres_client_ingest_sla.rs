@@ -0,0 +1,434 @@
+/// Module responsible for monitoring the ingestion of the slots, checking that the slot
+/// status, the block metadata and the transactions of each slot processed arrived and
+/// were committed within the SLA, and writing the violations to the
+/// ingest_sla_violation table for alerting on the partial ingestion.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::{DbWorkItem, ParallelPostgresClient, SimplePostgresClient},
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::{GeyserPluginError, SlotStatus},
+    chrono::Utc,
+    log::*,
+    postgres::{Client, Statement},
+    std::{
+        collections::{BTreeSet, HashMap},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        thread::{sleep, Builder, JoinHandle},
+        time::{Duration, Instant},
+    },
+};
+
+const INGEST_SLA_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The streams of a slot committed by the workers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum IngestStream {
+    SlotStatus,
+    BlockMetadata,
+    Transaction,
+}
+
+impl DbWorkItem {
+    /// Get the stream and the slot of the work item monitored by the ingest SLA
+    pub(crate) fn ingest_stream(&self) -> Option<(IngestStream, u64)> {
+        match self {
+            DbWorkItem::UpdateSlot(request) => Some((IngestStream::SlotStatus, request.slot)),
+            DbWorkItem::UpdateBlockMetadata(request) => {
+                Some((IngestStream::BlockMetadata, request.block_info.slot as u64))
+            }
+            DbWorkItem::LogTransaction(request) => Some((
+                IngestStream::Transaction,
+                request.transaction_info.slot as u64,
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// The ingestion of a slot since its first notification
+#[derive(Debug)]
+struct SlotIngest {
+    /// When the slot was processed, the SLA is counted from then on
+    processed_at: Option<Instant>,
+    slot_status_committed: bool,
+    block_metadata_notified: bool,
+    block_metadata_committed: bool,
+    /// The executed transactions of the block, known with the block metadata
+    expected_transactions: Option<u64>,
+    notified_transactions: u64,
+    /// The transactions selected to be stored
+    stored_transactions: u64,
+    committed_transactions: u64,
+}
+
+impl SlotIngest {
+    fn new() -> Self {
+        Self {
+            processed_at: None,
+            slot_status_committed: false,
+            block_metadata_notified: false,
+            block_metadata_committed: false,
+            expected_transactions: None,
+            notified_transactions: 0,
+            stored_transactions: 0,
+            committed_transactions: 0,
+        }
+    }
+
+    fn processed(&mut self) {
+        self.processed_at.get_or_insert_with(Instant::now);
+    }
+
+    /// Check if any of the streams expected is missing, the transactions are only
+    /// expected to arrive when they are notified to the plugin.
+    fn is_violated(&self, transactions_notified: bool) -> bool {
+        !self.slot_status_committed
+            || !self.block_metadata_committed
+            || (transactions_notified
+                && self
+                    .expected_transactions
+                    .is_some_and(|expected| self.notified_transactions < expected))
+            || self.committed_transactions < self.stored_transactions
+    }
+}
+
+/// The violation of the ingest SLA of a slot
+#[derive(Debug, PartialEq, Eq)]
+struct DbIngestSlaViolation {
+    slot: i64,
+    slot_status_committed: bool,
+    block_metadata_arrived: bool,
+    block_metadata_committed: bool,
+    expected_transactions: Option<i64>,
+    notified_transactions: i64,
+    stored_transactions: i64,
+    committed_transactions: i64,
+}
+
+impl DbIngestSlaViolation {
+    fn new(slot: u64, ingest: &SlotIngest) -> Self {
+        Self {
+            slot: slot as i64,
+            slot_status_committed: ingest.slot_status_committed,
+            block_metadata_arrived: ingest.block_metadata_notified,
+            block_metadata_committed: ingest.block_metadata_committed,
+            expected_transactions: ingest.expected_transactions.map(|count| count as i64),
+            notified_transactions: ingest.notified_transactions as i64,
+            stored_transactions: ingest.stored_transactions as i64,
+            committed_transactions: ingest.committed_transactions as i64,
+        }
+    }
+}
+
+/// The slots monitored, and the ones checked already whose later notifications, such
+/// as of their roots, are ignored
+#[derive(Default)]
+struct IngestSlots {
+    slots: HashMap<u64, SlotIngest>,
+    checked: BTreeSet<u64>,
+}
+
+/// The ingestion of the slots, shared by the client notified and the workers
+pub(crate) struct IngestSlaMonitor {
+    sla: Duration,
+    transactions_notified: AtomicBool,
+    slots: Mutex<IngestSlots>,
+}
+
+impl IngestSlaMonitor {
+    /// Build the monitor when the SLA is configured
+    pub(crate) fn new(config: &AccountsDbPluginPostgresConfig) -> Option<Self> {
+        let sla = Duration::from_secs(config.ingest_sla_secs?);
+        Some(Self {
+            sla,
+            transactions_notified: AtomicBool::new(false),
+            slots: Mutex::default(),
+        })
+    }
+
+    fn update(&self, slot: u64, update: impl FnOnce(&mut SlotIngest)) {
+        let mut slots = self.slots.lock().unwrap();
+        if !slots.checked.contains(&slot) {
+            update(slots.slots.entry(slot).or_insert_with(SlotIngest::new));
+        }
+    }
+
+    /// Record the status of the slot notified. The dead slots are no longer monitored,
+    /// and neither are the slots before the root which were never processed.
+    fn record_slot_status(&self, slot: u64, status: &SlotStatus) {
+        match status {
+            SlotStatus::Processed | SlotStatus::Confirmed => {
+                self.update(slot, SlotIngest::processed)
+            }
+            SlotStatus::Rooted => {
+                self.update(slot, SlotIngest::processed);
+                let mut slots = self.slots.lock().unwrap();
+                slots.slots.retain(|ingest_slot, ingest| {
+                    *ingest_slot >= slot || ingest.processed_at.is_some()
+                });
+                // No later notification is expected for the slots checked up to the root
+                slots.checked = slots.checked.split_off(&slot);
+            }
+            SlotStatus::Dead(_) => {
+                self.slots.lock().unwrap().slots.remove(&slot);
+            }
+            _ => self.update(slot, |_| {}),
+        }
+    }
+
+    fn record_block_metadata(&self, slot: u64, executed_transaction_count: u64) {
+        self.update(slot, |ingest| {
+            ingest.processed();
+            ingest.block_metadata_notified = true;
+            ingest.expected_transactions = Some(executed_transaction_count);
+        });
+    }
+
+    fn record_notified_transaction(&self, slot: u64) {
+        self.update(slot, |ingest| ingest.notified_transactions += 1);
+    }
+
+    fn record_stored_transaction(&self, slot: u64) {
+        self.update(slot, |ingest| ingest.stored_transactions += 1);
+    }
+
+    /// Record the work item committed by a worker, the slots already checked are not
+    /// monitored again.
+    pub(crate) fn record_committed(&self, stream: IngestStream, slot: u64) {
+        let mut slots = self.slots.lock().unwrap();
+        let Some(ingest) = slots.slots.get_mut(&slot) else {
+            return;
+        };
+        match stream {
+            IngestStream::SlotStatus => ingest.slot_status_committed = true,
+            IngestStream::BlockMetadata => ingest.block_metadata_committed = true,
+            IngestStream::Transaction => ingest.committed_transactions += 1,
+        }
+    }
+
+    /// Take the violations of the slots processed for longer than the SLA, which are
+    /// then no longer monitored.
+    fn take_violations(&self) -> Vec<DbIngestSlaViolation> {
+        let transactions_notified = self.transactions_notified.load(Ordering::Relaxed);
+        let mut violations = Vec::new();
+        let mut slots = self.slots.lock().unwrap();
+        let IngestSlots { slots, checked } = &mut *slots;
+        slots.retain(|slot, ingest| {
+            let is_due = ingest
+                .processed_at
+                .is_some_and(|processed_at| processed_at.elapsed() >= self.sla);
+            if is_due {
+                if ingest.is_violated(transactions_notified) {
+                    violations.push(DbIngestSlaViolation::new(*slot, ingest));
+                }
+                checked.insert(*slot);
+            }
+            !is_due
+        });
+        violations.sort_by_key(|violation| violation.slot);
+        violations
+    }
+}
+
+pub(crate) struct IngestSlaChecker {
+    client: Client,
+    statement: Statement,
+    monitor: Arc<IngestSlaMonitor>,
+}
+
+impl IngestSlaChecker {
+    pub(crate) fn new(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Option<Self>, GeyserPluginError> {
+        let Some(monitor) = IngestSlaMonitor::new(config) else {
+            return Ok(None);
+        };
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+        let stmt = "INSERT INTO ingest_sla_violation (slot, sla_secs, slot_status_committed, \
+            block_metadata_arrived, block_metadata_committed, expected_transactions, \
+            notified_transactions, stored_transactions, committed_transactions, detected_on) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) \
+            ON CONFLICT (slot) DO NOTHING";
+        let statement = match client.prepare(stmt) {
+            Ok(statement) => statement,
+            Err(err) => {
+                return Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "Error in preparing for the ingest SLA violation PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                        err, config.host, config.user, config
+                    ),
+                })));
+            }
+        };
+        Ok(Some(Self {
+            client,
+            statement,
+            monitor: Arc::new(monitor),
+        }))
+    }
+
+    pub(crate) fn monitor(&self) -> Arc<IngestSlaMonitor> {
+        self.monitor.clone()
+    }
+
+    fn check(&mut self) {
+        let sla_secs = self.monitor.sla.as_secs() as i64;
+        for violation in self.monitor.take_violations() {
+            warn!(
+                "The ingestion of the slot violated the SLA: {:?}",
+                violation
+            );
+            let detected_on = Utc::now().naive_utc();
+            if let Err(err) = self.client.execute(
+                &self.statement,
+                &[
+                    &violation.slot,
+                    &sla_secs,
+                    &violation.slot_status_committed,
+                    &violation.block_metadata_arrived,
+                    &violation.block_metadata_committed,
+                    &violation.expected_transactions,
+                    &violation.notified_transactions,
+                    &violation.stored_transactions,
+                    &violation.committed_transactions,
+                    &detected_on,
+                ],
+            ) {
+                error!(
+                    "Failed to persist the ingest SLA violation of the slot {} to the PostgreSQL database. Error: {:?}",
+                    violation.slot, err
+                );
+            }
+        }
+    }
+
+    /// Check the slots due at the interval until the exit.
+    pub(crate) fn spawn(
+        mut self,
+        exit_worker: Arc<AtomicBool>,
+    ) -> JoinHandle<Result<(), GeyserPluginError>> {
+        Builder::new()
+            .name("ingest-sla-checker".to_string())
+            .spawn(move || -> Result<(), GeyserPluginError> {
+                while !exit_worker.load(Ordering::Relaxed) {
+                    sleep(INGEST_SLA_CHECK_INTERVAL);
+                    self.check();
+                }
+                Ok(())
+            })
+            .unwrap()
+    }
+}
+
+impl ParallelPostgresClient {
+    /// Set if the transactions are notified to the plugin, for the ingest SLA to
+    /// expect the transactions executed in the blocks.
+    pub fn set_transactions_notified(&self, transactions_notified: bool) {
+        if let Some(monitor) = &self.ingest_sla_monitor {
+            monitor
+                .transactions_notified
+                .store(transactions_notified, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_ingest_slot_status(&self, slot: u64, status: &SlotStatus) {
+        if let Some(monitor) = &self.ingest_sla_monitor {
+            monitor.record_slot_status(slot, status);
+        }
+    }
+
+    pub(crate) fn record_ingest_block_metadata(&self, slot: u64, executed_transaction_count: u64) {
+        if let Some(monitor) = &self.ingest_sla_monitor {
+            monitor.record_block_metadata(slot, executed_transaction_count);
+        }
+    }
+
+    pub(crate) fn record_ingest_notified_transaction(&self, slot: u64) {
+        if let Some(monitor) = &self.ingest_sla_monitor {
+            monitor.record_notified_transaction(slot);
+        }
+    }
+
+    pub(crate) fn record_ingest_stored_transaction(&self, slot: u64) {
+        if let Some(monitor) = &self.ingest_sla_monitor {
+            monitor.record_stored_transaction(slot);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ingest_sla_violations() {
+        let config: AccountsDbPluginPostgresConfig =
+            serde_json::from_str("{\"ingest_sla_secs\": 1}").unwrap();
+        let mut monitor = IngestSlaMonitor::new(&config).unwrap();
+        monitor.sla = Duration::ZERO;
+        monitor.transactions_notified.store(true, Ordering::Relaxed);
+
+        // Slot 1 is ingested completely
+        monitor.record_slot_status(1, &SlotStatus::Processed);
+        monitor.record_block_metadata(1, 1);
+        monitor.record_notified_transaction(1);
+        monitor.record_stored_transaction(1);
+        for stream in [
+            IngestStream::SlotStatus,
+            IngestStream::BlockMetadata,
+            IngestStream::Transaction,
+        ] {
+            monitor.record_committed(stream, 1);
+        }
+        // Slot 2 misses a transaction and the commit of the block metadata
+        monitor.record_slot_status(2, &SlotStatus::Processed);
+        monitor.record_block_metadata(2, 2);
+        monitor.record_notified_transaction(2);
+        monitor.record_committed(IngestStream::SlotStatus, 2);
+        // Slot 3 is dead, and slot 4 is not processed yet
+        monitor.record_slot_status(3, &SlotStatus::Processed);
+        monitor.record_slot_status(3, &SlotStatus::Dead("reason".to_string()));
+        monitor.record_slot_status(4, &SlotStatus::FirstShredReceived);
+
+        assert_eq!(
+            monitor.take_violations(),
+            vec![DbIngestSlaViolation {
+                slot: 2,
+                slot_status_committed: true,
+                block_metadata_arrived: true,
+                block_metadata_committed: false,
+                expected_transactions: Some(2),
+                notified_transactions: 1,
+                stored_transactions: 0,
+                committed_transactions: 0,
+            }]
+        );
+        assert_eq!(
+            monitor
+                .slots
+                .lock()
+                .unwrap()
+                .slots
+                .keys()
+                .collect::<Vec<_>>(),
+            vec![&4]
+        );
+        // The slots checked are not monitored again on their roots
+        monitor.record_slot_status(1, &SlotStatus::Rooted);
+        assert!(monitor.take_violations().is_empty());
+
+        // The slots before the root which were never processed are abandoned
+        monitor.record_slot_status(5, &SlotStatus::Rooted);
+        monitor.record_committed(IngestStream::SlotStatus, 5);
+        assert_eq!(monitor.take_violations().len(), 1);
+        let slots = monitor.slots.lock().unwrap();
+        assert!(slots.slots.is_empty());
+        assert_eq!(slots.checked.iter().collect::<Vec<_>>(), vec![&5]);
+    }
+}
@@ -0,0 +1,101 @@
+/// Module responsible for keeping the idle connections of the workers alive, and for
+/// rebuilding the connections dropped by the firewalls or the idle timeouts of the
+/// connection poolers, before the next write finds them dropped.
+use {
+    crate::{
+        accountsdb_plugin_postgres::AccountsDbPluginPostgresConfig,
+        postgres_client::{PostgresClientWorker, SimplePostgresClient},
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    postgres::Client,
+    std::time::{Duration, Instant},
+};
+
+/// The time to wait for the reply of the ping before the connection is considered dropped
+const KEEPALIVE_PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Check if the connection idle for the time is to be pinged, once per keepalive
+/// interval when set.
+fn is_keepalive_due(keepalive_interval: Option<Duration>, idle: Duration) -> bool {
+    keepalive_interval.is_some_and(|keepalive_interval| idle >= keepalive_interval)
+}
+
+/// Check if the connection is closed or no longer replies to a ping.
+pub(crate) fn is_connection_dropped(client: &mut Client) -> bool {
+    client.is_closed() || client.is_valid(KEEPALIVE_PING_TIMEOUT).is_err()
+}
+
+impl SimplePostgresClient {
+    pub(crate) fn is_connection_dropped(&mut self) -> bool {
+        is_connection_dropped(&mut self.client.get_mut().unwrap().client)
+    }
+
+    /// Rebuild the connection along with its statements, keeping the write stats.
+    pub(crate) fn reconnect(
+        &mut self,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        let write_stats = std::mem::take(&mut client.write_stats);
+        let result = Self::connect_and_prepare(config);
+        match result {
+            Ok(new_client) => {
+                *client = new_client;
+                client.write_stats.carry_over(write_stats);
+                info!("Reconnected to the PostgreSQL database.");
+                Ok(())
+            }
+            Err(err) => {
+                client.write_stats = write_stats;
+                Err(err)
+            }
+        }
+    }
+
+    /// Ping the connection, rebuilding it along with its statements when it is dropped.
+    pub(crate) fn keep_alive(
+        &mut self,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        if !self.is_connection_dropped() {
+            return Ok(());
+        }
+
+        warn!("The idle connection to the PostgreSQL database is dropped, reconnecting.");
+        self.reconnect(config)
+    }
+}
+
+impl PostgresClientWorker {
+    /// Keep the connection alive once it has been idle for the keepalive interval. A
+    /// failed reconnection is retried after another interval instead of subjecting it
+    /// to panic_on_db_errors, as no write has failed yet.
+    pub(crate) fn keep_alive_if_idle(&mut self) {
+        if !is_keepalive_due(self.keepalive_interval, self.last_active.elapsed()) {
+            return;
+        }
+        if let Err(err) = self.client.keep_alive(&self.config) {
+            error!(
+                "Failed to rebuild the connection to the PostgreSQL database: ({})",
+                err
+            );
+        }
+        self.last_active = Instant::now();
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_keepalive_due() {
+        let interval = Some(Duration::from_secs(60));
+        assert!(!is_keepalive_due(None, Duration::from_secs(3600)));
+        assert!(!is_keepalive_due(interval, Duration::ZERO));
+        assert!(!is_keepalive_due(interval, Duration::from_secs(59)));
+        assert!(is_keepalive_due(interval, Duration::from_secs(60)));
+        assert!(is_keepalive_due(interval, Duration::from_secs(3600)));
+    }
+}
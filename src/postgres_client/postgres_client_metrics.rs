@@ -0,0 +1,530 @@
+/// Module responsible for exposing the health of the plugin as Prometheus metrics: the
+/// depth of the queues, the latency of the writes, the work items written and failed,
+/// and the lag of the slots committed behind the slots notified, so that the operators
+/// can alert when the plugin falls behind the validator.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::{
+            postgres_client_schema_drift::{DriftKind, DRIFT_KINDS},
+            DbWorkItem, ParallelPostgresClient,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    crossbeam_channel::Sender,
+    log::*,
+    std::{
+        fmt::Write as _,
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Arc,
+        },
+        thread::{sleep, Builder, JoinHandle},
+        time::Duration,
+    },
+};
+
+const METRICS_ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+const METRICS_REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+/// The upper bounds of the buckets of the write duration histogram in seconds
+const WRITE_DURATION_BUCKETS: [f64; 10] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5, 1.0,
+];
+
+/// The streams of the work items, labelling their metrics
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MetricStream {
+    Account,
+    Slot,
+    Transaction,
+    BlockMetadata,
+    VoteActivity,
+    Entry,
+    BlockChainVerification,
+    StartupReport,
+    SlotCommit,
+    ForkCleanup,
+    EpochBarrier,
+}
+
+const METRIC_STREAMS: [MetricStream; 11] = [
+    MetricStream::Account,
+    MetricStream::Slot,
+    MetricStream::Transaction,
+    MetricStream::BlockMetadata,
+    MetricStream::VoteActivity,
+    MetricStream::Entry,
+    MetricStream::BlockChainVerification,
+    MetricStream::StartupReport,
+    MetricStream::SlotCommit,
+    MetricStream::ForkCleanup,
+    MetricStream::EpochBarrier,
+];
+
+impl MetricStream {
+    fn name(self) -> &'static str {
+        match self {
+            MetricStream::Account => "account",
+            MetricStream::Slot => "slot",
+            MetricStream::Transaction => "transaction",
+            MetricStream::BlockMetadata => "block_metadata",
+            MetricStream::VoteActivity => "vote_activity",
+            MetricStream::Entry => "entry",
+            MetricStream::BlockChainVerification => "block_chain_verification",
+            MetricStream::StartupReport => "startup_report",
+            MetricStream::SlotCommit => "slot_commit",
+            MetricStream::ForkCleanup => "fork_cleanup",
+            MetricStream::EpochBarrier => "epoch_barrier",
+        }
+    }
+}
+
+impl DbWorkItem {
+    /// Get the stream of the work item, along with the slot of the slot status
+    pub(crate) fn metric(&self) -> (MetricStream, Option<u64>) {
+        let stream = match self {
+            DbWorkItem::UpdateAccount(_) => MetricStream::Account,
+            DbWorkItem::UpdateSlot(request) => return (MetricStream::Slot, Some(request.slot)),
+            DbWorkItem::LogTransaction(_) => MetricStream::Transaction,
+            DbWorkItem::UpdateBlockMetadata(_) => MetricStream::BlockMetadata,
+            DbWorkItem::LogVoteActivity(_) => MetricStream::VoteActivity,
+            DbWorkItem::UpdateEntry(_) => MetricStream::Entry,
+            DbWorkItem::VerifyBlockChain(_) => MetricStream::BlockChainVerification,
+            DbWorkItem::WriteStartupReport(_) => MetricStream::StartupReport,
+            DbWorkItem::CommitSlot(request) => {
+                return (MetricStream::SlotCommit, Some(request.slot))
+            }
+            DbWorkItem::CleanUpForks(_) => MetricStream::ForkCleanup,
+            DbWorkItem::WriteEpochBarrier(request) => {
+                return (MetricStream::EpochBarrier, Some(request.slot()))
+            }
+        };
+        (stream, None)
+    }
+}
+
+/// A counter per stream
+#[derive(Default)]
+struct StreamCounters([AtomicU64; METRIC_STREAMS.len()]);
+
+impl StreamCounters {
+    fn increment(&self, stream: MetricStream) {
+        self.0[stream as usize].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The histogram of the durations of the writes, the counts of the buckets are not
+/// cumulative until rendered
+#[derive(Default)]
+struct DurationHistogram {
+    buckets: [AtomicU64; WRITE_DURATION_BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl DurationHistogram {
+    fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(bucket) = WRITE_DURATION_BUCKETS
+            .iter()
+            .position(|upper_bound| seconds <= *upper_bound)
+        {
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+/// The metrics shared by the client notified and the workers
+#[derive(Default)]
+pub(crate) struct PluginMetrics {
+    /// The updates notified to the plugin and selected to be written
+    notifications: StreamCounters,
+    written: StreamCounters,
+    errors: StreamCounters,
+    /// The work items dropped on the overflow of the queue
+    dropped: StreamCounters,
+    /// The durations of the writes of the work items and of the statement batches
+    write_duration: DurationHistogram,
+    notified_slot: AtomicU64,
+    committed_slot: AtomicU64,
+    /// The drifts of the schema found by the last check, per kind
+    schema_drifts: [AtomicU64; DRIFT_KINDS.len()],
+    /// The failovers of the workers to the standby and their failbacks to the primary
+    failovers: AtomicU64,
+    failbacks: AtomicU64,
+}
+
+impl PluginMetrics {
+    pub(crate) fn record_notification(&self, stream: MetricStream) {
+        self.notifications.increment(stream);
+    }
+
+    pub(crate) fn record_drop(&self, stream: MetricStream) {
+        self.dropped.increment(stream);
+    }
+
+    pub(crate) fn record_notified_slot(&self, slot: u64) {
+        self.notified_slot.fetch_max(slot, Ordering::Relaxed);
+    }
+
+    /// Record the work item written or failed by a worker, along with the slot of its
+    /// slot status.
+    pub(crate) fn record_write(
+        &self,
+        (stream, slot): (MetricStream, Option<u64>),
+        is_written: bool,
+    ) {
+        if !is_written {
+            self.errors.increment(stream);
+            return;
+        }
+        self.written.increment(stream);
+        if let Some(slot) = slot {
+            self.committed_slot.fetch_max(slot, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_write_duration(&self, duration: Duration) {
+        self.write_duration.observe(duration);
+    }
+
+    /// Record the failover of a worker to the standby, or its failback to the primary.
+    pub(crate) fn record_failover(&self, is_failover: bool) {
+        if is_failover {
+            self.failovers.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.failbacks.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_schema_drifts(&self, kind: DriftKind, count: usize) {
+        self.schema_drifts[kind as usize].store(count as u64, Ordering::Relaxed);
+    }
+
+    /// Render the metrics in the Prometheus text format, along with the depths of the
+    /// queues.
+    fn render(&self, queue_depths: &[(&str, usize)]) -> String {
+        let mut text = String::new();
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+        let _ = writeln!(
+            text,
+            "# HELP geyser_postgres_queue_depth The work items queued to the workers\n\
+            # TYPE geyser_postgres_queue_depth gauge"
+        );
+        for (queue, depth) in queue_depths {
+            let _ = writeln!(
+                text,
+                "geyser_postgres_queue_depth{{queue=\"{}\"}} {}",
+                queue, depth
+            );
+        }
+
+        for (name, help, counters) in [
+            (
+                "geyser_postgres_notifications_total",
+                "The updates notified to the plugin and selected to be written",
+                &self.notifications,
+            ),
+            (
+                "geyser_postgres_work_items_written_total",
+                "The work items written by the workers",
+                &self.written,
+            ),
+            (
+                "geyser_postgres_db_errors_total",
+                "The work items failed to be written by the workers",
+                &self.errors,
+            ),
+            (
+                "geyser_postgres_work_items_dropped_total",
+                "The work items dropped on the overflow of the queue of the workers",
+                &self.dropped,
+            ),
+        ] {
+            let _ = writeln!(text, "# HELP {} {}\n# TYPE {} counter", name, help, name);
+            for stream in METRIC_STREAMS {
+                let _ = writeln!(
+                    text,
+                    "{}{{stream=\"{}\"}} {}",
+                    name,
+                    stream.name(),
+                    load(&counters.0[stream as usize])
+                );
+            }
+        }
+
+        let histogram = &self.write_duration;
+        let _ = writeln!(
+            text,
+            "# HELP geyser_postgres_write_duration_seconds The durations of the writes of the work items and the statement batches\n\
+            # TYPE geyser_postgres_write_duration_seconds histogram"
+        );
+        let mut cumulative = 0;
+        for (upper_bound, bucket) in WRITE_DURATION_BUCKETS.iter().zip(&histogram.buckets) {
+            cumulative += load(bucket);
+            let _ = writeln!(
+                text,
+                "geyser_postgres_write_duration_seconds_bucket{{le=\"{}\"}} {}",
+                upper_bound, cumulative
+            );
+        }
+        let count = load(&histogram.count);
+        let _ = writeln!(
+            text,
+            "geyser_postgres_write_duration_seconds_bucket{{le=\"+Inf\"}} {}\n\
+            geyser_postgres_write_duration_seconds_sum {}\n\
+            geyser_postgres_write_duration_seconds_count {}",
+            count,
+            load(&histogram.sum_micros) as f64 / 1_000_000.0,
+            count
+        );
+
+        let failovers = load(&self.failovers);
+        let failbacks = load(&self.failbacks);
+        for (name, help, value) in [
+            (
+                "geyser_postgres_failovers_total",
+                "The failovers of the workers to the standby database",
+                failovers,
+            ),
+            (
+                "geyser_postgres_failbacks_total",
+                "The failbacks of the workers to the primary database",
+                failbacks,
+            ),
+        ] {
+            let _ = writeln!(
+                text,
+                "# HELP {} {}\n# TYPE {} counter\n{} {}",
+                name, help, name, name, value
+            );
+        }
+        let _ = writeln!(
+            text,
+            "# HELP geyser_postgres_workers_on_fallback The workers connected to the standby database\n\
+            # TYPE geyser_postgres_workers_on_fallback gauge\n\
+            geyser_postgres_workers_on_fallback {}",
+            failovers.saturating_sub(failbacks)
+        );
+
+        let notified_slot = load(&self.notified_slot);
+        let committed_slot = load(&self.committed_slot);
+        for (name, help, value) in [
+            (
+                "geyser_postgres_notified_slot",
+                "The highest slot whose status is notified",
+                notified_slot,
+            ),
+            (
+                "geyser_postgres_committed_slot",
+                "The highest slot whose status is committed",
+                committed_slot,
+            ),
+            (
+                "geyser_postgres_slot_lag",
+                "The slots committed behind the slots notified",
+                notified_slot.saturating_sub(committed_slot),
+            ),
+        ] {
+            let _ = writeln!(
+                text,
+                "# HELP {} {}\n# TYPE {} gauge\n{} {}",
+                name, help, name, name, value
+            );
+        }
+
+        let _ = writeln!(
+            text,
+            "# HELP geyser_postgres_schema_drifts The drifts of the schema from the expected one found by the last check\n\
+            # TYPE geyser_postgres_schema_drifts gauge"
+        );
+        for kind in DRIFT_KINDS {
+            let _ = writeln!(
+                text,
+                "geyser_postgres_schema_drifts{{kind=\"{}\"}} {}",
+                kind.name(),
+                load(&self.schema_drifts[kind as usize])
+            );
+        }
+        text
+    }
+}
+
+/// The endpoint serving the metrics to the Prometheus scrapes
+pub(crate) struct MetricsExporter {
+    listener: TcpListener,
+    metrics: Arc<PluginMetrics>,
+}
+
+impl MetricsExporter {
+    /// Bind the endpoint when the metrics are configured
+    pub(crate) fn new(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Option<Self>, GeyserPluginError> {
+        let Some(metrics_config) = &config.metrics else {
+            return Ok(None);
+        };
+        let listener = TcpListener::bind(&metrics_config.bind)
+            .and_then(|listener| listener.set_nonblocking(true).map(|()| listener))
+            .map_err(|err| {
+                GeyserPluginError::Custom(Box::new(
+                    AccountsDbPluginPostgresError::ConfigurationError {
+                        msg: format!(
+                            "Failed to bind the metrics endpoint to {}: ({})",
+                            metrics_config.bind, err
+                        ),
+                    },
+                ))
+            })?;
+        info!(
+            "Serving the metrics at http://{}/metrics",
+            metrics_config.bind
+        );
+        Ok(Some(Self {
+            listener,
+            metrics: Arc::default(),
+        }))
+    }
+
+    pub(crate) fn metrics(&self) -> Arc<PluginMetrics> {
+        self.metrics.clone()
+    }
+
+    fn serve(&self, mut stream: TcpStream, queues: &[(&'static str, Sender<DbWorkItem>)]) {
+        let _ = stream.set_nonblocking(false);
+        let _ = stream.set_read_timeout(Some(METRICS_REQUEST_TIMEOUT));
+        let _ = stream.set_write_timeout(Some(METRICS_REQUEST_TIMEOUT));
+        let mut request = [0u8; 1024];
+        let Ok(len) = stream.read(&mut request) else {
+            return;
+        };
+        let request = String::from_utf8_lossy(&request[..len]);
+        let path = request.split_whitespace().nth(1).unwrap_or_default();
+        let response = if path == "/metrics" {
+            let queue_depths: Vec<(&str, usize)> = queues
+                .iter()
+                .map(|(queue, sender)| (*queue, sender.len()))
+                .collect();
+            let body = self.metrics.render(&queue_depths);
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        } else {
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+        };
+        if let Err(err) = stream.write_all(response.as_bytes()) {
+            debug!("Failed to respond to the metrics request: ({})", err);
+        }
+    }
+
+    /// Serve the metrics until the exit, along with the depths of the queues.
+    pub(crate) fn spawn(
+        self,
+        queues: Vec<(&'static str, Sender<DbWorkItem>)>,
+        exit_worker: Arc<AtomicBool>,
+    ) -> JoinHandle<Result<(), GeyserPluginError>> {
+        Builder::new()
+            .name("metrics-exporter".to_string())
+            .spawn(move || -> Result<(), GeyserPluginError> {
+                while !exit_worker.load(Ordering::Relaxed) {
+                    match self.listener.accept() {
+                        Ok((stream, _)) => self.serve(stream, &queues),
+                        Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                            sleep(METRICS_ACCEPT_INTERVAL)
+                        }
+                        Err(err) => {
+                            warn!("Failed to accept the metrics request: ({})", err);
+                            sleep(METRICS_ACCEPT_INTERVAL);
+                        }
+                    }
+                }
+                Ok(())
+            })
+            .unwrap()
+    }
+}
+
+impl ParallelPostgresClient {
+    /// Record the update notified to the plugin, when the metrics are served.
+    pub(crate) fn record_notification(&self, stream: MetricStream) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_notification(stream);
+        }
+    }
+
+    /// Record the slot status notified to the plugin, when the metrics are served.
+    pub(crate) fn record_notified_slot_status(&self, slot: u64) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_notification(MetricStream::Slot);
+            metrics.record_notified_slot(slot);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*, crate::postgres_client::UpdateSlotRequest,
+        agave_geyser_plugin_interface::geyser_plugin_interface::SlotStatus,
+    };
+
+    #[test]
+    fn test_render_metrics() {
+        let metrics = PluginMetrics::default();
+        let update_slot = |slot| {
+            DbWorkItem::UpdateSlot(Box::new(UpdateSlotRequest {
+                slot,
+                parent: None,
+                slot_status: SlotStatus::Processed,
+            }))
+        };
+        metrics.record_notification(MetricStream::Slot);
+        metrics.record_notified_slot(12);
+        metrics.record_write(update_slot(10).metric(), true);
+        metrics.record_write(update_slot(11).metric(), false);
+        metrics.record_drop(MetricStream::Account);
+        metrics.record_write_duration(Duration::from_millis(3));
+        metrics.record_write_duration(Duration::from_secs(2));
+        metrics.record_schema_drifts(DriftKind::MissingIndex, 3);
+        metrics.record_failover(true);
+        metrics.record_failover(true);
+        metrics.record_failover(false);
+
+        let text = metrics.render(&[("shared", 5)]);
+        let lines: Vec<&str> = text.lines().collect();
+        for line in [
+            "geyser_postgres_queue_depth{queue=\"shared\"} 5",
+            "geyser_postgres_notifications_total{stream=\"slot\"} 1",
+            "geyser_postgres_notifications_total{stream=\"account\"} 0",
+            "geyser_postgres_work_items_written_total{stream=\"slot\"} 1",
+            "geyser_postgres_db_errors_total{stream=\"slot\"} 1",
+            "geyser_postgres_work_items_dropped_total{stream=\"account\"} 1",
+            "geyser_postgres_write_duration_seconds_bucket{le=\"0.0025\"} 0",
+            "geyser_postgres_write_duration_seconds_bucket{le=\"0.005\"} 1",
+            "geyser_postgres_write_duration_seconds_bucket{le=\"1\"} 1",
+            "geyser_postgres_write_duration_seconds_bucket{le=\"+Inf\"} 2",
+            "geyser_postgres_write_duration_seconds_sum 2.003",
+            "geyser_postgres_write_duration_seconds_count 2",
+            "geyser_postgres_notified_slot 12",
+            "geyser_postgres_committed_slot 10",
+            "geyser_postgres_slot_lag 2",
+            "geyser_postgres_schema_drifts{kind=\"missing_index\"} 3",
+            "geyser_postgres_schema_drifts{kind=\"missing_table\"} 0",
+            "geyser_postgres_failovers_total 2",
+            "geyser_postgres_failbacks_total 1",
+            "geyser_postgres_workers_on_fallback 1",
+        ] {
+            assert!(lines.contains(&line), "{} is not in {}", line, text);
+        }
+    }
+}
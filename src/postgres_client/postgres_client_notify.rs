@@ -0,0 +1,194 @@
+/// Module responsible for notifying the configured channels of the rows written, with
+/// `pg_notify` after the writes are committed, so that the applications can LISTEN on
+/// the channels instead of polling the tables.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::{DbAccountInfo, SimplePostgresClient},
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    postgres::{Client, Statement},
+    serde_json::{json, Value},
+    std::{collections::HashMap, sync::Mutex},
+};
+
+/// The payload of an account written, e.g.
+/// {"pubkey": "...", "owner": "...", "slot": 10, "write_version": 3}
+pub(crate) fn account_payload(account: &DbAccountInfo) -> Value {
+    json!({
+        "pubkey": bs58::encode(&account.pubkey).into_string(),
+        "owner": bs58::encode(&account.owner).into_string(),
+        "slot": account.slot,
+        "write_version": account.write_version,
+    })
+}
+
+/// The payloads of the statement batch open per update type, in the order the update
+/// types were first written
+type BatchPayloads = Vec<(String, Vec<String>)>;
+
+/// The channels notified of the rows written, keyed by the update type. The
+/// notifications of the rows written within a statement batch are held until the batch
+/// is committed.
+#[derive(Default)]
+pub(crate) struct Notifier {
+    /// The statement notifying the channel of each of the payloads, when any channel is
+    /// configured
+    statement: Option<Statement>,
+    channels: HashMap<String, String>,
+    batch: Mutex<Option<BatchPayloads>>,
+}
+
+impl Notifier {
+    /// Indicates if the rows of the update type are notified, so that the payloads are
+    /// only built when needed
+    pub(crate) fn is_enabled(&self, update_type: &str) -> bool {
+        self.channels.contains_key(update_type)
+    }
+
+    /// Notify the channel of the update type of the payloads of the rows committed, in
+    /// one round trip, a no-op when no channel is configured for the update type. Within
+    /// a statement batch, the payloads are notified once the batch is committed.
+    pub(crate) fn notify(
+        &self,
+        client: &mut Client,
+        update_type: &str,
+        payloads: impl IntoIterator<Item = Value>,
+    ) -> Result<(), GeyserPluginError> {
+        if self.statement.is_none() || !self.is_enabled(update_type) {
+            return Ok(());
+        }
+        let payloads: Vec<String> = payloads
+            .into_iter()
+            .map(|payload| payload.to_string())
+            .collect();
+        if let Some(batch) = self.batch.lock().unwrap().as_mut() {
+            match batch.iter_mut().find(|(batched, _)| batched == update_type) {
+                Some((_, batched)) => batched.extend(payloads),
+                None => batch.push((update_type.to_string(), payloads)),
+            }
+            return Ok(());
+        }
+        self.execute(client, update_type, &payloads)
+    }
+
+    fn execute(
+        &self,
+        client: &mut Client,
+        update_type: &str,
+        payloads: &[String],
+    ) -> Result<(), GeyserPluginError> {
+        let (Some(statement), Some(channel)) = (&self.statement, self.channels.get(update_type))
+        else {
+            return Ok(());
+        };
+        let result = client.execute(statement, &[channel, &payloads]);
+
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to notify the {} channel of the {} updates in the PostgreSQL database. Error: {:?}",
+                channel, update_type, err
+            );
+            error!("{}", msg);
+            return Err(match update_type {
+                "account" => GeyserPluginError::AccountsUpdateError { msg },
+                "transaction" => GeyserPluginError::TransactionUpdateError { msg },
+                _ => GeyserPluginError::SlotStatusUpdateError { msg },
+            });
+        }
+        Ok(())
+    }
+
+    /// Hold the notifications from now on until the statement batch begun is committed
+    /// or rolled back.
+    pub(crate) fn begin_batch(&self) {
+        if self.statement.is_some() {
+            *self.batch.lock().unwrap() = Some(Vec::new());
+        }
+    }
+
+    /// Send the notifications of the statement batch committed, one round trip per
+    /// update type.
+    pub(crate) fn commit_batch(&self, client: &mut Client) -> Result<(), GeyserPluginError> {
+        let Some(batch) = self.batch.lock().unwrap().take() else {
+            return Ok(());
+        };
+        for (update_type, payloads) in batch {
+            self.execute(client, &update_type, &payloads)?;
+        }
+        Ok(())
+    }
+
+    /// Drop the notifications of the statement batch rolled back, its items being
+    /// written again one by one.
+    pub(crate) fn discard_batch(&self) {
+        *self.batch.lock().unwrap() = None;
+    }
+}
+
+impl SimplePostgresClient {
+    pub(crate) fn build_notifier(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Notifier, GeyserPluginError> {
+        let Some(channels) = &config.notify_channels else {
+            return Ok(Notifier::default());
+        };
+        let stmt = "SELECT pg_notify($1, payload) FROM unnest($2::TEXT[]) AS payload";
+
+        let stmt = client.prepare(stmt);
+
+        match stmt {
+            Err(err) => {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "Error in preparing for the notification PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                        err, config.host, config.user, config
+                    ),
+                })))
+            }
+            Ok(stmt) => Ok(Notifier {
+                statement: Some(stmt),
+                channels: channels.clone().into_iter().collect(),
+                batch: Mutex::default(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_payload() {
+        let account = DbAccountInfo {
+            pubkey: vec![1; 32],
+            lamports: 1,
+            owner: vec![2; 32],
+            executable: false,
+            rent_epoch: 0,
+            data: vec![],
+            slot: 10,
+            write_version: 3,
+            txn_signature: None,
+        };
+        assert_eq!(
+            account_payload(&account),
+            json!({
+                "pubkey": bs58::encode([1; 32]).into_string(),
+                "owner": bs58::encode([2; 32]).into_string(),
+                "slot": 10,
+                "write_version": 3,
+            })
+        );
+        let notifier = Notifier::default();
+        assert!(!notifier.is_enabled("account"));
+        // The notifications are not held without any channel configured
+        notifier.begin_batch();
+        assert!(notifier.batch.lock().unwrap().is_none());
+    }
+}
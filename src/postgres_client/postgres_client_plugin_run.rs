@@ -0,0 +1,201 @@
+/// Module responsible for recording the plugin runs in the PostgreSQL database and
+/// detecting another live plugin instance writing for the same validator identity.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::SimplePostgresClient,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    postgres::Client,
+    std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread::{sleep, Builder, JoinHandle},
+        time::{Duration, Instant},
+    },
+};
+
+const DEFAULT_FAIL_ON_DUPLICATE_PLUGIN_INSTANCE: bool = true;
+const PLUGIN_RUN_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The plugin run holding the advisory lock of the validator identity for as long as
+/// its connection is open.
+pub(crate) struct PluginRun {
+    client: Client,
+    run_id: i64,
+}
+
+/// Derive the advisory lock key from the validator identity, the key space is shared
+/// with any other advisory lock users of the database.
+fn plugin_run_lock_key(validator_identity: &str) -> Result<i64, GeyserPluginError> {
+    let identity = bs58::decode(validator_identity).into_vec().map_err(|err| {
+        GeyserPluginError::Custom(Box::new(
+            AccountsDbPluginPostgresError::ConfigurationError {
+                msg: format!(
+                    "\"validator_identity\": {:?} is not a valid pubkey. Error: ({})",
+                    validator_identity, err
+                ),
+            },
+        ))
+    })?;
+    let mut key = [0u8; 8];
+    for (i, byte) in identity.iter().enumerate() {
+        key[i % key.len()] ^= byte;
+    }
+    Ok(i64::from_le_bytes(key))
+}
+
+impl PluginRun {
+    /// Start the plugin run when the validator identity is configured. Another live
+    /// instance is detected by the advisory lock being already held.
+    pub(crate) fn start(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Option<Self>, GeyserPluginError> {
+        let validator_identity = match &config.validator_identity {
+            Some(validator_identity) => validator_identity,
+            None => return Ok(None),
+        };
+        let lock_key = plugin_run_lock_key(validator_identity)?;
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+
+        let locked: bool = client
+            .query_one("SELECT pg_try_advisory_lock($1)", &[&lock_key])
+            .map_err(|err| Self::to_plugin_error("acquire the plugin run lock", err))?
+            .get(0);
+
+        if !locked {
+            let live_run = client
+                .query_opt(
+                    "SELECT run_id, process_id, heartbeat_on FROM plugin_run \
+                    WHERE validator_identity = $1 AND stopped_on IS NULL \
+                    ORDER BY heartbeat_on DESC LIMIT 1",
+                    &[validator_identity],
+                )
+                .map_err(|err| Self::to_plugin_error("query the live plugin run", err))?
+                .map(|row| {
+                    let run_id: i64 = row.get(0);
+                    let process_id: i64 = row.get(1);
+                    let heartbeat_on: chrono::NaiveDateTime = row.get(2);
+                    format!(
+                        "run_id: {}, process_id: {}, last heartbeat: {}",
+                        run_id, process_id, heartbeat_on
+                    )
+                })
+                .unwrap_or_else(|| "no plugin_run record".to_string());
+
+            let msg = format!(
+                "Another plugin instance is writing to the PostgreSQL database for the validator identity {} ({})",
+                validator_identity, live_run
+            );
+            if config
+                .fail_on_duplicate_plugin_instance
+                .unwrap_or(DEFAULT_FAIL_ON_DUPLICATE_PLUGIN_INSTANCE)
+            {
+                error!("{}", msg);
+                return Err(GeyserPluginError::Custom(Box::new(
+                    AccountsDbPluginPostgresError::DuplicateInstanceError { msg },
+                )));
+            }
+            warn!("{}, the data may be written twice!", msg);
+        }
+
+        let started_on = Utc::now().naive_utc();
+        let process_id = std::process::id() as i64;
+        let run_id: i64 = client
+            .query_one(
+                "INSERT INTO plugin_run (validator_identity, process_id, started_on, heartbeat_on) \
+                VALUES ($1, $2, $3, $3) RETURNING run_id",
+                &[validator_identity, &process_id, &started_on],
+            )
+            .map_err(|err| Self::to_plugin_error("record the plugin run", err))?
+            .get(0);
+
+        info!(
+            "Started plugin run {} for the validator identity {}",
+            run_id, validator_identity
+        );
+        Ok(Some(Self { client, run_id }))
+    }
+
+    fn to_plugin_error(action: &str, err: postgres::Error) -> GeyserPluginError {
+        let msg = format!(
+            "Failed to {} in the PostgreSQL database. Error: {:?}",
+            action, err
+        );
+        error!("{}", msg);
+        GeyserPluginError::Custom(Box::new(
+            AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+        ))
+    }
+
+    fn heartbeat(&mut self) -> Result<(), GeyserPluginError> {
+        let heartbeat_on = Utc::now().naive_utc();
+        self.client
+            .execute(
+                "UPDATE plugin_run SET heartbeat_on = $2 WHERE run_id = $1",
+                &[&self.run_id, &heartbeat_on],
+            )
+            .map_err(|err| Self::to_plugin_error("update the plugin run heartbeat", err))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), GeyserPluginError> {
+        let stopped_on = Utc::now().naive_utc();
+        self.client
+            .execute(
+                "UPDATE plugin_run SET heartbeat_on = $2, stopped_on = $2 WHERE run_id = $1",
+                &[&self.run_id, &stopped_on],
+            )
+            .map_err(|err| Self::to_plugin_error("stop the plugin run", err))?;
+        Ok(())
+    }
+
+    /// Keep the plugin run alive until the exit, the advisory lock is released when
+    /// the connection is closed.
+    pub(crate) fn spawn_heartbeat(
+        mut self,
+        exit_worker: Arc<AtomicBool>,
+    ) -> JoinHandle<Result<(), GeyserPluginError>> {
+        Builder::new()
+            .name("plugin-run-heartbeat".to_string())
+            .spawn(move || -> Result<(), GeyserPluginError> {
+                let mut last_heartbeat = Instant::now();
+                while !exit_worker.load(Ordering::Relaxed) {
+                    sleep(Duration::from_millis(500));
+                    if last_heartbeat.elapsed() >= PLUGIN_RUN_HEARTBEAT_INTERVAL {
+                        // The errors are logged, a missed heartbeat is not fatal.
+                        let _ = self.heartbeat();
+                        last_heartbeat = Instant::now();
+                    }
+                }
+                self.stop()
+            })
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plugin_run_lock_key() {
+        let identity = "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin";
+        let other_identity = "9oT9R5ZyRovSVnt37QvVoBttGpNqR3J7unkb567NP8k3";
+        assert_eq!(
+            plugin_run_lock_key(identity).unwrap(),
+            plugin_run_lock_key(identity).unwrap()
+        );
+        assert_ne!(
+            plugin_run_lock_key(identity).unwrap(),
+            plugin_run_lock_key(other_identity).unwrap()
+        );
+        assert!(plugin_run_lock_key("not-a-pubkey-0OIl").is_err());
+    }
+}
@@ -0,0 +1,207 @@
+/// Module responsible for executing the configured SQL hooks after the writes of the
+/// tables are committed, for the lightweight downstream pipelines without triggers.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::SimplePostgresClient,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    postgres::{types::Type, Client, Statement},
+    std::{collections::HashMap, sync::Mutex},
+};
+
+/// The types of the batch metadata bound to the hooks: the table, the count of the rows
+/// written, the lowest and the highest slot of the rows and the commit time.
+const POST_COMMIT_HOOK_PARAMETER_TYPES: [Type; 5] = [
+    Type::VARCHAR,
+    Type::INT8,
+    Type::INT8,
+    Type::INT8,
+    Type::TIMESTAMP,
+];
+
+/// The rows of a table written within the statement batch open
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct BatchRows {
+    row_count: usize,
+    first_slot: i64,
+    last_slot: i64,
+}
+
+impl BatchRows {
+    fn add(&mut self, other: BatchRows) {
+        self.row_count += other.row_count;
+        self.first_slot = self.first_slot.min(other.first_slot);
+        self.last_slot = self.last_slot.max(other.last_slot);
+    }
+}
+
+/// The prepared hooks keyed by the table. The hooks of the rows written within a
+/// statement batch are held until the batch is committed.
+#[derive(Default)]
+pub(crate) struct PostCommitHooks {
+    statements: HashMap<String, Statement>,
+    /// The rows of the statement batch open per table, in the order the tables were
+    /// first written
+    batch: Mutex<Option<Vec<(String, BatchRows)>>>,
+}
+
+impl PostCommitHooks {
+    /// Execute the hook of the table after a batch of its rows is committed, a no-op when
+    /// no hook is configured for the table. Within a statement batch, the hook is
+    /// executed once for the rows of the table once the batch is committed.
+    pub(crate) fn execute(
+        &self,
+        client: &mut Client,
+        table: &str,
+        row_count: usize,
+        first_slot: i64,
+        last_slot: i64,
+    ) -> Result<(), GeyserPluginError> {
+        if !self.statements.contains_key(table) {
+            return Ok(());
+        }
+        let rows = BatchRows {
+            row_count,
+            first_slot,
+            last_slot,
+        };
+        if let Some(batch) = self.batch.lock().unwrap().as_mut() {
+            match batch.iter_mut().find(|(batched, _)| batched == table) {
+                Some((_, batched)) => batched.add(rows),
+                None => batch.push((table.to_string(), rows)),
+            }
+            return Ok(());
+        }
+        self.execute_rows(client, table, rows)
+    }
+
+    fn execute_rows(
+        &self,
+        client: &mut Client,
+        table: &str,
+        rows: BatchRows,
+    ) -> Result<(), GeyserPluginError> {
+        let Some(statement) = self.statements.get(table) else {
+            return Ok(());
+        };
+        let committed_on = Utc::now().naive_utc();
+        let result = client.execute(
+            statement,
+            &[
+                &table,
+                &(rows.row_count as i64),
+                &rows.first_slot,
+                &rows.last_slot,
+                &committed_on,
+            ],
+        );
+
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to execute the post commit hook of the {} table in the PostgreSQL database. Error: {:?}",
+                table, err
+            );
+            error!("{}", msg);
+            return Err(match table {
+                "account" => GeyserPluginError::AccountsUpdateError { msg },
+                "transaction" => GeyserPluginError::TransactionUpdateError { msg },
+                _ => GeyserPluginError::SlotStatusUpdateError { msg },
+            });
+        }
+        Ok(())
+    }
+
+    /// Hold the hooks from now on until the statement batch begun is committed or
+    /// rolled back.
+    pub(crate) fn begin_batch(&self) {
+        if !self.statements.is_empty() {
+            *self.batch.lock().unwrap() = Some(Vec::new());
+        }
+    }
+
+    /// Execute the hooks of the tables written by the statement batch committed, once
+    /// per table for all its rows of the batch.
+    pub(crate) fn commit_batch(&self, client: &mut Client) -> Result<(), GeyserPluginError> {
+        let Some(batch) = self.batch.lock().unwrap().take() else {
+            return Ok(());
+        };
+        for (table, rows) in batch {
+            self.execute_rows(client, &table, rows)?;
+        }
+        Ok(())
+    }
+
+    /// Drop the hooks of the statement batch rolled back, its items being written again
+    /// one by one.
+    pub(crate) fn discard_batch(&self) {
+        *self.batch.lock().unwrap() = None;
+    }
+}
+
+impl SimplePostgresClient {
+    pub(crate) fn build_post_commit_hooks(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<PostCommitHooks, GeyserPluginError> {
+        let mut statements = HashMap::default();
+        for (table, stmt) in config.post_commit_hooks.iter().flatten() {
+            // The types are declared so that the hooks can use any of the parameters
+            let stmt = client.prepare_typed(stmt, &POST_COMMIT_HOOK_PARAMETER_TYPES);
+
+            match stmt {
+                Err(err) => {
+                    return Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                        msg: format!(
+                            "Error in preparing for the post commit hook of the {} table PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                            table, err, config.host, config.user, config
+                        ),
+                    })));
+                }
+                Ok(stmt) => {
+                    statements.insert(table.clone(), stmt);
+                }
+            }
+        }
+        Ok(PostCommitHooks {
+            statements,
+            batch: Mutex::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_rows() {
+        let mut rows = BatchRows {
+            row_count: 2,
+            first_slot: 10,
+            last_slot: 12,
+        };
+        rows.add(BatchRows {
+            row_count: 1,
+            first_slot: 9,
+            last_slot: 11,
+        });
+        assert_eq!(
+            rows,
+            BatchRows {
+                row_count: 3,
+                first_slot: 9,
+                last_slot: 12,
+            }
+        );
+
+        // The hooks are not held without any hook configured
+        let hooks = PostCommitHooks::default();
+        hooks.begin_batch();
+        assert!(hooks.batch.lock().unwrap().is_none());
+    }
+}
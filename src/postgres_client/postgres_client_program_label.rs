@@ -0,0 +1,200 @@
+/// Module responsible for maintaining the program_label table from the mapping of the
+/// program ids to the human-readable names and categories, so that the dashboards can
+/// display the names of the programs.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::SimplePostgresClient,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    serde_derive::Deserialize,
+    solana_sdk::pubkey::Pubkey,
+    std::{collections::BTreeMap, fs, path::Path, str::FromStr},
+};
+
+/// The label of a program in the JSON mapping
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProgramLabelEntry {
+    name: String,
+    category: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DbProgramLabel {
+    pub program_id: Vec<u8>,
+    pub name: String,
+    pub category: Option<String>,
+}
+
+fn to_label_error(msg: String) -> GeyserPluginError {
+    GeyserPluginError::Custom(Box::new(
+        AccountsDbPluginPostgresError::ConfigurationError { msg },
+    ))
+}
+
+fn parse_program_id(program_id: &str) -> Result<Vec<u8>, GeyserPluginError> {
+    Pubkey::from_str(program_id)
+        .map(|pubkey| pubkey.to_bytes().to_vec())
+        .map_err(|err| {
+            to_label_error(format!(
+                "The program label of {:?} is not of a valid pubkey. Error: ({})",
+                program_id, err
+            ))
+        })
+}
+
+/// Parse the program labels of the mapping, a JSON object keyed by the base58 program
+/// id, or CSV lines of the program id, the name and the optional category when `is_csv`.
+fn parse_program_labels(
+    contents: &str,
+    is_csv: bool,
+) -> Result<Vec<DbProgramLabel>, GeyserPluginError> {
+    if !is_csv {
+        let entries: BTreeMap<String, ProgramLabelEntry> = serde_json::from_str(contents)
+            .map_err(|err| to_label_error(format!("Invalid program labels. Error: ({})", err)))?;
+        return entries
+            .into_iter()
+            .map(|(program_id, entry)| {
+                Ok(DbProgramLabel {
+                    program_id: parse_program_id(&program_id)?,
+                    name: entry.name,
+                    category: entry.category,
+                })
+            })
+            .collect();
+    }
+
+    let mut labels = Vec::default();
+    for line in contents.lines().map(str::trim) {
+        // The empty lines and the header line are skipped
+        if line.is_empty() || line.starts_with("program_id,") {
+            continue;
+        }
+        let mut fields = line.split(',').map(str::trim);
+        let (Some(program_id), Some(name)) = (fields.next(), fields.next()) else {
+            return Err(to_label_error(format!(
+                "The program label line {:?} must consist of the program id, the name and the optional category",
+                line
+            )));
+        };
+        labels.push(DbProgramLabel {
+            program_id: parse_program_id(program_id)?,
+            name: name.to_string(),
+            category: fields
+                .next()
+                .filter(|category| !category.is_empty())
+                .map(str::to_string),
+        });
+    }
+    Ok(labels)
+}
+
+/// Load the program labels of the file, a CSV file by the .csv extension and a JSON
+/// file otherwise.
+fn load_program_labels(path: &str) -> Result<Vec<DbProgramLabel>, GeyserPluginError> {
+    let contents = fs::read_to_string(path).map_err(|err| {
+        to_label_error(format!(
+            "Failed to read the program labels file {:?}. Error: ({})",
+            path, err
+        ))
+    })?;
+    let is_csv = Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+    parse_program_labels(&contents, is_csv)
+}
+
+impl SimplePostgresClient {
+    /// Replace the rows of the program_label table with the labels of the file when it
+    /// is configured, at every load of the plugin including the reloads.
+    pub(crate) fn refresh_program_labels(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        let Some(path) = &config.program_labels_file else {
+            return Ok(());
+        };
+        let labels = load_program_labels(path)?;
+        let program_ids: Vec<&Vec<u8>> = labels.iter().map(|label| &label.program_id).collect();
+        let names: Vec<&str> = labels.iter().map(|label| label.name.as_str()).collect();
+        let categories: Vec<Option<&str>> = labels
+            .iter()
+            .map(|label| label.category.as_deref())
+            .collect();
+        let updated_on = Utc::now().naive_utc();
+
+        let mut client = Self::connect_to_db(config)?;
+        let result = client.transaction().and_then(|mut transaction| {
+            transaction.execute(
+                "DELETE FROM program_label WHERE program_id <> ALL($1::BYTEA[])",
+                &[&program_ids],
+            )?;
+            transaction.execute(
+                "INSERT INTO program_label AS label (program_id, name, category, updated_on) \
+                SELECT t.program_id, t.name, t.category, $4 \
+                FROM UNNEST($1::BYTEA[], $2::VARCHAR[], $3::VARCHAR[]) AS t (program_id, name, category) \
+                ON CONFLICT (program_id) DO UPDATE SET name=excluded.name, category=excluded.category, \
+                updated_on=excluded.updated_on \
+                WHERE (label.name, label.category) IS DISTINCT FROM (excluded.name, excluded.category)",
+                &[&program_ids, &names, &categories, &updated_on],
+            )?;
+            transaction.commit()
+        });
+
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to persist the program labels to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+            )));
+        }
+        info!("Refreshed {} program labels from {:?}", labels.len(), path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_program_labels() {
+        let jupiter = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4";
+        let memo = "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr";
+        let expected = vec![
+            DbProgramLabel {
+                program_id: parse_program_id(jupiter).unwrap(),
+                name: "Jupiter v6".to_string(),
+                category: Some("dex".to_string()),
+            },
+            DbProgramLabel {
+                program_id: parse_program_id(memo).unwrap(),
+                name: "SPL Memo".to_string(),
+                category: None,
+            },
+        ];
+
+        let json = format!(
+            "{{\"{}\": {{\"name\": \"Jupiter v6\", \"category\": \"dex\"}}, \"{}\": {{\"name\": \"SPL Memo\"}}}}",
+            jupiter, memo
+        );
+        assert_eq!(parse_program_labels(&json, false).unwrap(), expected);
+
+        let csv = format!(
+            "program_id,name,category\n{}, Jupiter v6, dex\n\n{},SPL Memo,\n",
+            jupiter, memo
+        );
+        assert_eq!(parse_program_labels(&csv, true).unwrap(), expected);
+
+        assert!(parse_program_labels("not-a-pubkey,Name,", true).is_err());
+        assert!(parse_program_labels(jupiter, true).is_err());
+        assert!(parse_program_labels("{\"x\": {\"label\": \"y\"}}", false).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+/// Module responsible for the overflow of the queue of the workers, blocking the
+/// notifications of the validator until there is room, or dropping work items instead
+/// so that a database falling behind does not stall the validator. Only the account
+/// updates and the transactions are dropped, the slot statuses, the block metadata and
+/// the other work items the consistency of the slots depends on always being queued.
+use {
+    crate::{
+        accountsdb_plugin_postgres::QueueOverflowPolicy,
+        postgres_client::{postgres_client_metrics::PluginMetrics, DbWorkItem, ShutdownDrain},
+    },
+    crossbeam_channel::{Receiver, SendError, Sender, TrySendError},
+    log::*,
+    solana_metrics::*,
+    std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+impl DbWorkItem {
+    /// Whether the work item is dropped when the queue overflows, per the policy
+    /// dropping the oldest work items
+    fn is_droppable(&self) -> bool {
+        matches!(
+            self,
+            DbWorkItem::UpdateAccount(_) | DbWorkItem::LogTransaction(_)
+        )
+    }
+}
+
+pub(crate) struct QueueOverflow {
+    policy: QueueOverflowPolicy,
+    sender: Sender<DbWorkItem>,
+    /// The receiving end of the queue, for dropping the oldest work items with the
+    /// drop_oldest policy only, as it keeps the queue connected once the workers exit
+    receiver: Option<Receiver<DbWorkItem>>,
+    /// The exits of the workers, the queue being disconnected once they all exited
+    shutdown_drain: Arc<ShutdownDrain>,
+    dropped_count: AtomicU64,
+    /// The metrics the dropped work items are recorded to, when the metrics are served
+    metrics: Option<Arc<PluginMetrics>>,
+}
+
+impl QueueOverflow {
+    pub(crate) fn new(
+        policy: QueueOverflowPolicy,
+        sender: Sender<DbWorkItem>,
+        receiver: Receiver<DbWorkItem>,
+        shutdown_drain: Arc<ShutdownDrain>,
+        metrics: Option<Arc<PluginMetrics>>,
+    ) -> Self {
+        Self {
+            policy,
+            sender,
+            receiver: (policy == QueueOverflowPolicy::DropOldest).then_some(receiver),
+            shutdown_drain,
+            dropped_count: AtomicU64::default(),
+            metrics,
+        }
+    }
+
+    /// Queue the work item to the workers. When the queue is full, block until there
+    /// is room, drop the oldest account updates and transactions queued, or drop the
+    /// work item when it is an account update, per the overflow policy.
+    pub(crate) fn send(&self, item: DbWorkItem) -> Result<(), SendError<DbWorkItem>> {
+        let drops_item = match self.policy {
+            QueueOverflowPolicy::Block => return self.sender.send(item),
+            QueueOverflowPolicy::DropOldest => false,
+            QueueOverflowPolicy::DropAccountUpdatesOnly => {
+                if !matches!(item, DbWorkItem::UpdateAccount(_)) {
+                    return self.sender.send(item);
+                }
+                true
+            }
+        };
+        // The work items taken off the queue which are not dropped are queued again,
+        // behind the others, up to the whole queue before blocking
+        let mut requeued_count = 0;
+        let mut item = item;
+        loop {
+            match self.sender.try_send(item) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Disconnected(item)) => return Err(SendError(item)),
+                Err(TrySendError::Full(item)) if drops_item => {
+                    self.record_drop(&item);
+                    return Ok(());
+                }
+                Err(TrySendError::Full(returned)) => {
+                    item = returned;
+                    // The queue is kept connected by its receiving end held here
+                    if self.shutdown_drain.are_workers_exited() {
+                        return Err(SendError(item));
+                    }
+                    if requeued_count >= self.sender.capacity().unwrap_or_default() {
+                        return self.sender.send(item);
+                    }
+                    // The workers may have drained the queue in the meantime
+                    let Some(oldest) = self.receiver.as_ref().and_then(|r| r.try_recv().ok())
+                    else {
+                        continue;
+                    };
+                    if oldest.is_droppable() {
+                        self.record_drop(&oldest);
+                    } else {
+                        requeued_count += 1;
+                        if self.sender.send(oldest).is_err() {
+                            return Err(SendError(item));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn record_drop(&self, item: &DbWorkItem) {
+        let dropped_count = self.dropped_count.fetch_add(1, Ordering::Relaxed) + 1;
+        // Log the first drop and then every 10000 drops, not to flood the log
+        if dropped_count % 10000 == 1 {
+            warn!(
+                "The queue of the workers is full, dropped {} work items so far per the \"queue_overflow_policy\": {:?}",
+                dropped_count, self.policy
+            );
+        }
+        inc_new_counter_info!("accountsdb-plugin-postgres-queue-dropped", 1);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_drop(item.metric().0);
+        }
+    }
+
+    #[cfg(test)]
+    fn dropped_count(&self) -> u64 {
+        self.dropped_count.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::{
+            accountsdb_plugin_postgres::AccountsDbPluginPostgresConfig,
+            postgres_client::{DbAccountInfo, UpdateAccountRequest, UpdateSlotRequest},
+        },
+        agave_geyser_plugin_interface::geyser_plugin_interface::SlotStatus,
+        crossbeam_channel::bounded,
+    };
+
+    fn update_slot(slot: u64) -> DbWorkItem {
+        DbWorkItem::UpdateSlot(Box::new(UpdateSlotRequest {
+            slot,
+            parent: None,
+            slot_status: SlotStatus::Processed,
+        }))
+    }
+
+    fn update_account(slot: i64) -> DbWorkItem {
+        DbWorkItem::UpdateAccount(Box::new(UpdateAccountRequest {
+            account: DbAccountInfo {
+                pubkey: vec![1; 32],
+                lamports: 1,
+                owner: vec![2; 32],
+                executable: false,
+                rent_epoch: 0,
+                data: Vec::default(),
+                slot,
+                write_version: 0,
+                txn_signature: None,
+            },
+            is_startup: false,
+        }))
+    }
+
+    fn shutdown_drain() -> Arc<ShutdownDrain> {
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str("{}").unwrap();
+        Arc::new(ShutdownDrain::new(&config, 1))
+    }
+
+    fn queued_slots(receiver: &Receiver<DbWorkItem>) -> Vec<i64> {
+        receiver
+            .try_iter()
+            .map(|item| match item {
+                DbWorkItem::UpdateSlot(request) => request.slot as i64,
+                DbWorkItem::UpdateAccount(request) => -request.account.slot,
+                _ => panic!("Unexpected work item"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_queue_overflow_drop_oldest() {
+        let (sender, receiver) = bounded(2);
+        let overflow = QueueOverflow::new(
+            QueueOverflowPolicy::DropOldest,
+            sender,
+            receiver.clone(),
+            shutdown_drain(),
+            None,
+        );
+        overflow.send(update_account(1)).unwrap();
+        overflow.send(update_account(2)).unwrap();
+        overflow.send(update_slot(3)).unwrap();
+        overflow.send(update_slot(4)).unwrap();
+        assert_eq!(overflow.dropped_count(), 2);
+        assert_eq!(queued_slots(&receiver), vec![3, 4]);
+
+        // The slot statuses are queued again instead of being dropped
+        overflow.send(update_slot(5)).unwrap();
+        overflow.send(update_account(6)).unwrap();
+        overflow.send(update_slot(7)).unwrap();
+        assert_eq!(overflow.dropped_count(), 3);
+        assert_eq!(queued_slots(&receiver), vec![5, 7]);
+    }
+
+    #[test]
+    fn test_queue_overflow_disconnected() {
+        // The queue without the receiving end of the workers is disconnected
+        let (sender, receiver) = bounded(1);
+        let overflow = QueueOverflow::new(
+            QueueOverflowPolicy::Block,
+            sender,
+            receiver.clone(),
+            shutdown_drain(),
+            None,
+        );
+        drop(receiver);
+        assert!(overflow.send(update_slot(1)).is_err());
+
+        // The queue dropping the oldest work items is full once the workers exited
+        let (sender, receiver) = bounded(1);
+        let shutdown_drain = shutdown_drain();
+        let overflow = QueueOverflow::new(
+            QueueOverflowPolicy::DropOldest,
+            sender,
+            receiver.clone(),
+            shutdown_drain.clone(),
+            None,
+        );
+        overflow.send(update_account(1)).unwrap();
+        overflow.send(update_account(2)).unwrap();
+        assert_eq!(overflow.dropped_count(), 1);
+        shutdown_drain.record_worker_exit();
+        assert!(overflow.send(update_account(3)).is_err());
+        assert_eq!(queued_slots(&receiver), vec![-2]);
+    }
+
+    #[test]
+    fn test_queue_overflow_drop_account_updates_only() {
+        let (sender, receiver) = bounded(2);
+        let overflow = QueueOverflow::new(
+            QueueOverflowPolicy::DropAccountUpdatesOnly,
+            sender,
+            receiver.clone(),
+            shutdown_drain(),
+            None,
+        );
+        overflow.send(update_account(1)).unwrap();
+        overflow.send(update_slot(1)).unwrap();
+        overflow.send(update_account(2)).unwrap();
+        assert_eq!(overflow.dropped_count(), 1);
+        assert_eq!(queued_slots(&receiver), vec![-1, 1]);
+    }
+}
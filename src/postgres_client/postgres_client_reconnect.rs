@@ -0,0 +1,418 @@
+/// Module responsible for reconnecting the workers whose connections are dropped by a
+/// restart of the PostgreSQL server or by a network blip, with an exponential backoff,
+/// and for buffering the updates received meanwhile so that they are written once
+/// reconnected instead of being lost across the short outages. The updates committed
+/// within the replay window are written again once reconnected, so that a standby
+/// promoted after lagging behind the failed primary is left without a gap.
+use {
+    crate::{
+        accountsdb_plugin_postgres::AccountsDbPluginPostgresConfig,
+        postgres_client::{
+            abort, DbWorkItem, PostgresClientWorker, DEFAULT_STORE_ACCOUNT_HISTORICAL_DATA,
+        },
+    },
+    crossbeam_channel::Receiver,
+    log::*,
+    std::{
+        collections::VecDeque,
+        sync::atomic::{AtomicBool, Ordering},
+        thread::sleep,
+        time::{Duration, Instant},
+    },
+};
+
+/// The backoff of the first reconnection attempt, doubled after each failed attempt
+const RECONNECT_INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+pub(crate) const DEFAULT_RECONNECT_BUFFER_SIZE: usize = 10000;
+pub(crate) const DEFAULT_REPLAY_WINDOW_SIZE: usize = 100000;
+
+/// The exponential backoff between the reconnection attempts
+#[derive(Debug)]
+struct Backoff {
+    next: Duration,
+    max: Duration,
+}
+
+impl Backoff {
+    fn new(max: Duration) -> Self {
+        Self {
+            next: RECONNECT_INITIAL_BACKOFF.min(max),
+            max,
+        }
+    }
+
+    fn next_delay(&mut self) -> Duration {
+        let delay = self.next;
+        self.next = (self.next * 2).min(self.max);
+        delay
+    }
+}
+
+/// The updates committed by a worker within the replay window, in the order they were
+/// committed, up to the max number of the updates kept
+struct ReplayWindow {
+    duration: Duration,
+    capacity: usize,
+    /// The account updates are left out when the account history is stored, as the
+    /// versions already stored would be copied into the account_audit table again
+    replay_accounts: bool,
+    committed: VecDeque<(Instant, DbWorkItem)>,
+}
+
+impl ReplayWindow {
+    fn new(config: &AccountsDbPluginPostgresConfig) -> Option<Self> {
+        let duration = Duration::from_secs(config.replay_window_secs?);
+        let capacity = config
+            .replay_window_size
+            .unwrap_or(DEFAULT_REPLAY_WINDOW_SIZE);
+        let replay_accounts = !config
+            .store_account_historical_data
+            .unwrap_or(DEFAULT_STORE_ACCOUNT_HISTORICAL_DATA);
+        Some(Self {
+            duration,
+            capacity,
+            replay_accounts,
+            committed: VecDeque::new(),
+        })
+    }
+
+    fn record(&mut self, work: DbWorkItem, now: Instant) {
+        if work.is_replayable(self.replay_accounts) {
+            self.committed.push_back((now, work));
+        }
+        while let Some((committed_at, _)) = self.committed.front() {
+            if now.duration_since(*committed_at) <= self.duration
+                && self.committed.len() <= self.capacity
+            {
+                break;
+            }
+            self.committed.pop_front();
+        }
+    }
+}
+
+/// The updates pending to be written by a worker reconnecting, the update failed on
+/// the dropped connection first
+pub(crate) struct ReconnectBuffer {
+    max_backoff: Duration,
+    capacity: usize,
+    pub(crate) pending: VecDeque<DbWorkItem>,
+    replay_window: Option<ReplayWindow>,
+}
+
+impl ReconnectBuffer {
+    /// Build the buffer when the reconnection is enabled
+    pub(crate) fn new(config: &AccountsDbPluginPostgresConfig) -> Option<Self> {
+        let max_backoff = Duration::from_secs(config.reconnect_max_backoff_secs?);
+        let capacity = config
+            .reconnect_buffer_size
+            .unwrap_or(DEFAULT_RECONNECT_BUFFER_SIZE);
+        Some(Self {
+            max_backoff,
+            capacity,
+            pending: VecDeque::new(),
+            replay_window: ReplayWindow::new(config),
+        })
+    }
+
+    fn is_full(&self) -> bool {
+        self.pending.len() >= self.capacity
+    }
+
+    /// Keep the committed item within the replay window
+    pub(crate) fn record_committed(&mut self, work: DbWorkItem) {
+        if let Some(replay_window) = &mut self.replay_window {
+            replay_window.record(work, Instant::now());
+        }
+    }
+
+    /// Move the items of the replay window ahead of the pending ones, returning the
+    /// number of the items to be replayed.
+    pub(crate) fn replay_committed(&mut self) -> usize {
+        let Some(replay_window) = &mut self.replay_window else {
+            return 0;
+        };
+        let count = replay_window.committed.len();
+        while let Some((_, work)) = replay_window.committed.pop_back() {
+            self.pending.push_front(work);
+        }
+        count
+    }
+}
+
+impl DbWorkItem {
+    /// Describe the write of the work item for the logs
+    pub(crate) fn description(&self) -> &'static str {
+        match self {
+            DbWorkItem::UpdateAccount(_) => "update account",
+            DbWorkItem::UpdateSlot(_) => "update slot",
+            DbWorkItem::LogTransaction(_) => "update transaction",
+            DbWorkItem::UpdateBlockMetadata(_) => "update block metadata",
+            DbWorkItem::LogVoteActivity(_) => "update vote activity",
+            DbWorkItem::UpdateEntry(_) => "update entries",
+            DbWorkItem::VerifyBlockChain(_) => "verify the block chain",
+            DbWorkItem::WriteStartupReport(_) => "write the startup report",
+            DbWorkItem::CommitSlot(_) => "commit the transactions of the slot",
+            DbWorkItem::CleanUpForks(_) => "clean up the abandoned forks",
+            DbWorkItem::WriteEpochBarrier(_) => "write the epoch barrier",
+        }
+    }
+
+    /// Whether writing the committed item again leaves the database unchanged. The
+    /// vote activity counts the votes and the block metadata is inserted only once,
+    /// while the accounts of the startup are committed by their batches later on.
+    fn is_replayable(&self, replay_accounts: bool) -> bool {
+        match self {
+            DbWorkItem::UpdateAccount(request) => replay_accounts && !request.is_startup,
+            DbWorkItem::UpdateSlot(_)
+            | DbWorkItem::LogTransaction(_)
+            | DbWorkItem::UpdateEntry(_)
+            | DbWorkItem::CommitSlot(_)
+            | DbWorkItem::CleanUpForks(_) => true,
+            DbWorkItem::UpdateBlockMetadata(_)
+            | DbWorkItem::LogVoteActivity(_)
+            | DbWorkItem::VerifyBlockChain(_)
+            | DbWorkItem::WriteStartupReport(_)
+            | DbWorkItem::WriteEpochBarrier(_) => false,
+        }
+    }
+}
+
+impl PostgresClientWorker {
+    /// Write the work item, along with the ones buffered before it. When a write fails
+    /// on a dropped connection, the worker reconnects and writes the item again, the
+    /// items failing otherwise are subject to panic_on_db_errors.
+    pub(crate) fn write_with_reconnect(
+        &mut self,
+        work: DbWorkItem,
+        receiver: &Receiver<DbWorkItem>,
+        exit_worker: &AtomicBool,
+        panic_on_db_errors: bool,
+    ) {
+        let Some(buffer) = &mut self.reconnect_buffer else {
+            self.write_or_abort(work, panic_on_db_errors);
+            return;
+        };
+        buffer.pending.push_back(work);
+        self.write_pending(receiver, exit_worker, panic_on_db_errors);
+    }
+
+    /// Write the items buffered while reconnecting, in the order they were received.
+    pub(crate) fn write_pending(
+        &mut self,
+        receiver: &Receiver<DbWorkItem>,
+        exit_worker: &AtomicBool,
+        panic_on_db_errors: bool,
+    ) {
+        while let Some(work) = self
+            .reconnect_buffer
+            .as_mut()
+            .and_then(|buffer| buffer.pending.pop_front())
+        {
+            let description = work.description();
+            match self.write(work) {
+                Ok(()) => {}
+                Err((work, err)) if self.client.is_connection_dropped() => {
+                    warn!(
+                        "Failed to {} as the connection to the PostgreSQL database is dropped: ({})",
+                        description, err
+                    );
+                    // The item is written again once reconnected
+                    if let Some(buffer) = &mut self.reconnect_buffer {
+                        buffer.pending.push_front(work);
+                    }
+                    if !self.reconnect_with_backoff(receiver, exit_worker) {
+                        return;
+                    }
+                }
+                Err((_, err)) => {
+                    error!("Failed to {}: ({})", description, err);
+                    if panic_on_db_errors {
+                        abort();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reconnect when the connection is dropped and the reconnection is enabled, after
+    /// a failure outside of the work items. Returns false when the failure is to be
+    /// handled by the caller.
+    pub(crate) fn reconnect_if_dropped(
+        &mut self,
+        receiver: &Receiver<DbWorkItem>,
+        exit_worker: &AtomicBool,
+    ) -> bool {
+        if self.reconnect_buffer.is_none() || !self.client.is_connection_dropped() {
+            return false;
+        }
+        warn!("The connection to the PostgreSQL database is dropped.");
+        self.reconnect_with_backoff(receiver, exit_worker);
+        true
+    }
+
+    /// Reconnect until it succeeds, buffering the items received during the backoff
+    /// up to the buffer size, after which the items are left queued to the worker.
+    /// Once reconnected, the items of the replay window are written again ahead of the
+    /// pending ones. Returns false when the worker exits before reconnecting.
+    fn reconnect_with_backoff(
+        &mut self,
+        receiver: &Receiver<DbWorkItem>,
+        exit_worker: &AtomicBool,
+    ) -> bool {
+        let Some(buffer) = &self.reconnect_buffer else {
+            return false;
+        };
+        let mut backoff = Backoff::new(buffer.max_backoff);
+        let mut attempt = 1;
+        loop {
+            if exit_worker.load(Ordering::Relaxed) {
+                let pending = self
+                    .reconnect_buffer
+                    .as_ref()
+                    .map_or(0, |buffer| buffer.pending.len());
+                error!(
+                    "Exiting before reconnecting to the PostgreSQL database with {} pending updates",
+                    pending
+                );
+                return false;
+            }
+            // Another worker may have failed over or back in the meantime
+            if self.follow_failover_decision() {
+                return true;
+            }
+            let delay = match self.client.reconnect(&self.config) {
+                Ok(()) => {
+                    if let Some(failover) = &mut self.failover {
+                        failover.record_reachable();
+                    }
+                    if let Some(buffer) = &mut self.reconnect_buffer {
+                        let replayed = buffer.replay_committed();
+                        if replayed > 0 {
+                            info!(
+                                "Reconnected to the PostgreSQL database, replaying {} updates committed within the replay window",
+                                replayed
+                            );
+                        }
+                    }
+                    return true;
+                }
+                Err(_) if self.fail_over_if_due() => return true,
+                Err(err) => {
+                    let delay = backoff.next_delay();
+                    warn!(
+                        "Reconnection attempt {} to the PostgreSQL database failed, retrying in {:?}: ({})",
+                        attempt, delay, err
+                    );
+                    delay
+                }
+            };
+            self.buffer_during(delay, receiver);
+            attempt += 1;
+        }
+    }
+
+    /// Buffer the items received during the delay while the buffer is not full.
+    fn buffer_during(&mut self, delay: Duration, receiver: &Receiver<DbWorkItem>) {
+        let deadline = Instant::now() + delay;
+        if let Some(buffer) = &mut self.reconnect_buffer {
+            while !buffer.is_full() {
+                let now = Instant::now();
+                if now >= deadline {
+                    return;
+                }
+                match receiver.recv_timeout(deadline - now) {
+                    Ok(work) => {
+                        buffer.pending.push_back(work);
+                        if buffer.is_full() {
+                            warn!(
+                                "The reconnect buffer of {} updates is full, queueing the updates to the worker",
+                                buffer.capacity
+                            );
+                        }
+                    }
+                    Err(_) => return,
+                }
+            }
+        }
+        sleep(deadline.saturating_duration_since(Instant::now()));
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{UpdateSlotRequest, VerifyBlockChainRequest},
+        agave_geyser_plugin_interface::geyser_plugin_interface::SlotStatus,
+    };
+
+    #[test]
+    fn test_reconnect_backoff() {
+        let mut backoff = Backoff::new(Duration::from_millis(500));
+        let delays: Vec<Duration> = (0..5).map(|_| backoff.next_delay()).collect();
+        assert_eq!(delays, [100, 200, 400, 500, 500].map(Duration::from_millis));
+
+        // The initial backoff is capped by the max backoff too
+        let mut backoff = Backoff::new(Duration::from_millis(50));
+        assert_eq!(backoff.next_delay(), Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_replay_window() {
+        let config: AccountsDbPluginPostgresConfig =
+            serde_json::from_str("{\"reconnect_max_backoff_secs\": 1, \"replay_window_secs\": 10}")
+                .unwrap();
+        let mut buffer = ReconnectBuffer::new(&config).unwrap();
+        let update_slot = |slot| {
+            DbWorkItem::UpdateSlot(Box::new(UpdateSlotRequest {
+                slot,
+                parent: None,
+                slot_status: SlotStatus::Processed,
+            }))
+        };
+        let slot_of = |work: &DbWorkItem| match work {
+            DbWorkItem::UpdateSlot(request) => request.slot,
+            _ => panic!("Unexpected work item"),
+        };
+
+        let start = Instant::now();
+        let replay_window = buffer.replay_window.as_mut().unwrap();
+        replay_window.record(update_slot(1), start);
+        replay_window.record(update_slot(2), start + Duration::from_secs(5));
+        replay_window.record(
+            DbWorkItem::VerifyBlockChain(Box::new(VerifyBlockChainRequest {
+                from_slot: 0,
+                to_slot: 2,
+            })),
+            start + Duration::from_secs(6),
+        );
+        // The slot 1 is out of the window
+        replay_window.record(update_slot(3), start + Duration::from_secs(11));
+
+        buffer.pending.push_back(update_slot(4));
+        assert_eq!(buffer.replay_committed(), 2);
+        let slots: Vec<u64> = buffer.pending.iter().map(slot_of).collect();
+        assert_eq!(slots, [2, 3, 4]);
+        assert_eq!(buffer.replay_committed(), 0);
+
+        // The oldest updates are evicted past the size of the window
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str(
+            "{\"reconnect_max_backoff_secs\": 1, \"replay_window_secs\": 10, \"replay_window_size\": 2}",
+        )
+        .unwrap();
+        let mut buffer = ReconnectBuffer::new(&config).unwrap();
+        for slot in 1..=3 {
+            buffer.record_committed(update_slot(slot));
+        }
+        assert_eq!(buffer.replay_committed(), 2);
+        let slots: Vec<u64> = buffer.pending.iter().map(slot_of).collect();
+        assert_eq!(slots, [2, 3]);
+
+        let config: AccountsDbPluginPostgresConfig =
+            serde_json::from_str("{\"reconnect_max_backoff_secs\": 1}").unwrap();
+        let mut buffer = ReconnectBuffer::new(&config).unwrap();
+        buffer.record_committed(update_slot(1));
+        assert_eq!(buffer.replay_committed(), 0);
+    }
+}
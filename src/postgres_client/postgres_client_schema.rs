@@ -0,0 +1,439 @@
+/// Module responsible for the schema of the cluster when one database serves the
+/// validators of several clusters, setting the search path of the connections to the
+/// schema and creating the schema along with its tables when it is missing, and for
+/// the versions of the schema, applying the migrations of scripts/migrations or of the
+/// schema source.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountLayout, AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::{
+            postgres_client_schema_source::{load_schema_source, sha256_hex},
+            SimplePostgresClient, DEFAULT_USE_SCHEMA_TRIGGERS,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    postgres::{Client, GenericClient},
+    std::borrow::Cow,
+};
+
+pub(crate) const DEFAULT_CREATE_SCHEMA: bool = false;
+const CREATE_SCHEMA_SQL: &str = include_str!("../../scripts/create_schema.sql");
+const DROP_SCHEMA_TRIGGERS_SQL: &str = include_str!("../../scripts/drop_schema_triggers.sql");
+const SPLIT_ACCOUNT_DATA_SQL: &str = include_str!("../../scripts/split_account_data.sql");
+pub(crate) const DEFAULT_AUTO_MIGRATE: bool = false;
+
+/// The migrations of the schema by their versions, in the order applied
+const MIGRATIONS: [(i32, &str); 17] = [
+    (
+        1,
+        include_str!("../../scripts/migrations/0001_unversioned_schema.sql"),
+    ),
+    (
+        2,
+        include_str!("../../scripts/migrations/0002_block_metadata.sql"),
+    ),
+    (
+        3,
+        include_str!("../../scripts/migrations/0003_transaction_balance.sql"),
+    ),
+    (
+        4,
+        include_str!("../../scripts/migrations/0004_index_advice.sql"),
+    ),
+    (
+        5,
+        include_str!("../../scripts/migrations/0005_startup_dropped_index.sql"),
+    ),
+    (
+        6,
+        include_str!("../../scripts/migrations/0006_epoch_barrier.sql"),
+    ),
+    (
+        7,
+        include_str!("../../scripts/migrations/0007_account_data_codec.sql"),
+    ),
+    (
+        8,
+        include_str!("../../scripts/migrations/0008_transaction_error.sql"),
+    ),
+    (
+        9,
+        include_str!("../../scripts/migrations/0009_account_version.sql"),
+    ),
+    (
+        10,
+        include_str!("../../scripts/migrations/0010_token_secondary_index.sql"),
+    ),
+    (
+        11,
+        include_str!("../../scripts/migrations/0011_account_txn_signature.sql"),
+    ),
+    (
+        12,
+        include_str!("../../scripts/migrations/0012_config_history.sql"),
+    ),
+    (
+        13,
+        include_str!("../../scripts/migrations/0013_slot_transaction_count.sql"),
+    ),
+    (
+        14,
+        include_str!("../../scripts/migrations/0014_schema_version_checksum.sql"),
+    ),
+    (
+        15,
+        include_str!("../../scripts/migrations/0015_spl_token_ui_amount.sql"),
+    ),
+    (
+        16,
+        include_str!("../../scripts/migrations/0016_degradation_ladder.sql"),
+    ),
+    (
+        17,
+        include_str!("../../scripts/migrations/0017_slot_ingest_checksum.sql"),
+    ),
+];
+
+/// The version of the schema the plugin writes to, the one of the schemas created by
+/// scripts/create_schema.sql
+pub(crate) const SCHEMA_VERSION: i32 = MIGRATIONS[MIGRATIONS.len() - 1].0;
+
+/// The scripts creating the tables of the schema for the config, along with the actions
+/// they carry out.
+pub(crate) fn create_schema_scripts(
+    config: &AccountsDbPluginPostgresConfig,
+) -> Vec<(&'static str, &'static str)> {
+    let mut scripts = vec![("create the tables", CREATE_SCHEMA_SQL)];
+    if !config
+        .use_schema_triggers
+        .unwrap_or(DEFAULT_USE_SCHEMA_TRIGGERS)
+    {
+        scripts.push(("drop the schema triggers", DROP_SCHEMA_TRIGGERS_SQL));
+    }
+    if config.account_layout.unwrap_or_default() == AccountLayout::Split {
+        scripts.push(("split the account data", SPLIT_ACCOUNT_DATA_SQL));
+    }
+    scripts
+}
+
+/// The version of the schema, 0 for a schema without the schema_version table which
+/// predates the versions, None for a schema without the account table.
+pub(crate) fn schema_version(
+    client: &mut impl GenericClient,
+) -> Result<Option<i32>, GeyserPluginError> {
+    let row = client
+        .query_one(
+            "SELECT to_regclass('account') IS NOT NULL, to_regclass('schema_version') IS NOT NULL",
+            &[],
+        )
+        .map_err(|err| {
+            SimplePostgresClient::to_schema_error("look up the schema_version table", err)
+        })?;
+    let (is_created, is_versioned): (bool, bool) = (row.get(0), row.get(1));
+    if !is_created {
+        return Ok(None);
+    }
+    if !is_versioned {
+        return Ok(Some(0));
+    }
+    client
+        .query_one("SELECT COALESCE(MAX(version), 0) FROM schema_version", &[])
+        .map(|row| Some(row.get(0)))
+        .map_err(|err| SimplePostgresClient::to_schema_error("look up the schema version", err))
+}
+
+fn schema_source_error(msg: String) -> GeyserPluginError {
+    error!("{}", msg);
+    GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+        msg,
+    }))
+}
+
+/// The migrations of the schema source when configured, those compiled into the
+/// plugin otherwise, sorted by their versions.
+fn migrations(
+    config: &AccountsDbPluginPostgresConfig,
+) -> Result<Vec<(i32, Cow<'static, str>)>, GeyserPluginError> {
+    let Some(source) = &config.schema_source else {
+        return Ok(MIGRATIONS
+            .iter()
+            .map(|(version, migration)| (*version, Cow::Borrowed(*migration)))
+            .collect());
+    };
+    let migrations = load_schema_source(source).map_err(|err| {
+        schema_source_error(format!(
+            "Failed to load the \"schema_source\" {}: {}",
+            source, err
+        ))
+    })?;
+    let latest = migrations.last().map(|migration| migration.version);
+    if latest < Some(SCHEMA_VERSION) {
+        return Err(schema_source_error(format!(
+            "The \"schema_source\" {} goes up to the version {:?}, older than the version {} the plugin writes to",
+            source, latest, SCHEMA_VERSION
+        )));
+    }
+    Ok(migrations
+        .into_iter()
+        .map(|migration| (migration.version, Cow::Owned(migration.sql)))
+        .collect())
+}
+
+/// The migrations after the version, which must follow each other from the version.
+fn pending_migrations<'a>(
+    migrations: &'a [(i32, Cow<'static, str>)],
+    version: i32,
+) -> Result<Vec<(i32, &'a str)>, String> {
+    let mut expected = version + 1;
+    let mut pending = Vec::new();
+    for (migration_version, migration) in migrations {
+        if *migration_version <= version {
+            continue;
+        }
+        if *migration_version != expected {
+            return Err(format!("The migration {} is missing", expected));
+        }
+        pending.push((*migration_version, migration.as_ref()));
+        expected += 1;
+    }
+    Ok(pending)
+}
+
+/// Check the checksums recorded for the migrations applied against the ones of the
+/// migrations, the migrations applied without a checksum, such as by hand, being left
+/// out.
+fn verify_checksums(
+    client: &mut impl GenericClient,
+    migrations: &[(i32, Cow<'static, str>)],
+) -> Result<(), GeyserPluginError> {
+    let rows = client
+        .query(
+            "SELECT version, to_jsonb(s) ->> 'checksum' FROM schema_version s",
+            &[],
+        )
+        .map_err(|err| {
+            SimplePostgresClient::to_schema_error("look up the checksums of the migrations", err)
+        })?;
+    for row in rows {
+        let (version, recorded): (i32, Option<String>) = (row.get(0), row.get(1));
+        let Some(recorded) = recorded else {
+            continue;
+        };
+        let Some((_, migration)) = migrations
+            .iter()
+            .find(|(migration_version, _)| *migration_version == version)
+        else {
+            continue;
+        };
+        let checksum = sha256_hex(migration.as_bytes());
+        if checksum != recorded {
+            return Err(schema_source_error(format!(
+                "The migration {} was applied with the checksum {}, differing from the checksum {} of the migration now",
+                version, recorded, checksum
+            )));
+        }
+    }
+    Ok(())
+}
+
+impl SimplePostgresClient {
+    fn to_schema_error(action: &str, err: postgres::Error) -> GeyserPluginError {
+        let msg = format!(
+            "Failed to {} in the PostgreSQL database. Error: {:?}",
+            action, err
+        );
+        error!("{}", msg);
+        GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+            msg,
+        }))
+    }
+
+    /// Set the search path of the connection to the schema when it is configured, the
+    /// name of the schema is validated along with the config.
+    pub(crate) fn set_search_path(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        let Some(schema) = &config.schema else {
+            return Ok(());
+        };
+        client
+            .batch_execute(&format!("SET search_path TO {}", schema))
+            .map_err(|err| Self::to_schema_error("set the search path", err))
+    }
+
+    /// Create the schema along with the tables of scripts/create_schema.sql when the
+    /// account table is missing from it, under an advisory lock so that the validators
+    /// of the same cluster starting together create it once.
+    pub(crate) fn create_schema_if_missing(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        if !config.create_schema.unwrap_or(DEFAULT_CREATE_SCHEMA) {
+            return Ok(());
+        }
+        let mut client = Self::connect_to_db(config)?;
+        let mut transaction = client
+            .transaction()
+            .map_err(|err| Self::to_schema_error("start the schema creation", err))?;
+        let schema = config.schema.as_deref().unwrap_or("public");
+        transaction
+            .execute("SELECT pg_advisory_xact_lock(hashtext($1))", &[&schema])
+            .map_err(|err| Self::to_schema_error("acquire the schema creation lock", err))?;
+        if config.schema.is_some() {
+            transaction
+                .batch_execute(&format!("CREATE SCHEMA IF NOT EXISTS {}", schema))
+                .map_err(|err| Self::to_schema_error("create the schema", err))?;
+        }
+        let is_created: bool = transaction
+            .query_one("SELECT to_regclass('account') IS NOT NULL", &[])
+            .map_err(|err| Self::to_schema_error("look up the account table", err))?
+            .get(0);
+        if !is_created {
+            info!("Creating the tables in the schema {}", schema);
+            for (action, script) in create_schema_scripts(config) {
+                transaction
+                    .batch_execute(script)
+                    .map_err(|err| Self::to_schema_error(action, err))?;
+            }
+        }
+        transaction
+            .commit()
+            .map_err(|err| Self::to_schema_error("commit the schema creation", err))
+    }
+
+    /// Check the schema is at the version the plugin writes to, applying the pending
+    /// migrations when auto_migrate is set and failing otherwise. A schema without the
+    /// schema_version table predates the versions and is at the version 0, while one
+    /// without the account table is left to the preparing of the statements to report.
+    pub(crate) fn migrate_schema(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        let mut client = Self::connect_to_db(config)?;
+        let mut transaction = client
+            .transaction()
+            .map_err(|err| Self::to_schema_error("start the schema migration", err))?;
+        let schema = config.schema.as_deref().unwrap_or("public");
+        transaction
+            .execute("SELECT pg_advisory_xact_lock(hashtext($1))", &[&schema])
+            .map_err(|err| Self::to_schema_error("acquire the schema migration lock", err))?;
+        let Some(version) = schema_version(&mut transaction)? else {
+            return Ok(());
+        };
+        let migrations = migrations(config)?;
+        if version > 0 {
+            verify_checksums(&mut transaction, &migrations)?;
+        }
+        let latest = migrations.last().map_or(0, |(version, _)| *version);
+        if version > latest {
+            warn!(
+                "The schema {} is at the version {}, newer than the version {} of {}",
+                schema,
+                version,
+                latest,
+                config.schema_source.as_deref().unwrap_or("the plugin")
+            );
+            return Ok(());
+        }
+        if version == latest {
+            return Ok(());
+        }
+        if !config.auto_migrate.unwrap_or(DEFAULT_AUTO_MIGRATE) {
+            let msg = format!(
+                "The schema {} is at the version {}, older than the version {} the plugin writes to. \
+                Apply the migrations after the version {} in {}, or set \"auto_migrate\" to 'true' to apply them when the plugin is loaded",
+                schema,
+                version,
+                latest,
+                version,
+                config.schema_source.as_deref().unwrap_or("scripts/migrations")
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::DataSchemaError { msg },
+            )));
+        }
+        let pending = pending_migrations(&migrations, version).map_err(schema_source_error)?;
+        for (migration_version, migration) in pending {
+            info!(
+                "Applying the migration {} to the schema {}",
+                migration_version, schema
+            );
+            transaction
+                .batch_execute(migration)
+                .map_err(|err| Self::to_schema_error("apply the migration", err))?;
+            transaction
+                .execute(
+                    "INSERT INTO schema_version (version, applied_on) VALUES ($1, now())",
+                    &[&migration_version],
+                )
+                .map_err(|err| Self::to_schema_error("record the schema version", err))?;
+            // The checksums are recorded once the migration adding their column is
+            // applied
+            let has_checksum: bool = transaction
+                .query_one(
+                    "SELECT to_jsonb(s) ? 'checksum' FROM schema_version s WHERE version = $1",
+                    &[&migration_version],
+                )
+                .map_err(|err| Self::to_schema_error("record the schema version", err))?
+                .get(0);
+            if has_checksum {
+                transaction
+                    .execute(
+                        "UPDATE schema_version SET checksum = $2 WHERE version = $1",
+                        &[&migration_version, &sha256_hex(migration.as_bytes())],
+                    )
+                    .map_err(|err| Self::to_schema_error("record the schema version", err))?;
+            }
+        }
+        transaction
+            .commit()
+            .map_err(|err| Self::to_schema_error("commit the schema migration", err))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_version() {
+        for (i, (version, _)) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(*version, i as i32 + 1);
+        }
+        assert!(CREATE_SCHEMA_SQL.contains(&format!(
+            "INSERT INTO schema_version (version, applied_on) VALUES ({}, now());",
+            SCHEMA_VERSION
+        )));
+    }
+
+    #[test]
+    fn test_pending_migrations() {
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str("{}").unwrap();
+        let migrations = migrations(&config).unwrap();
+        assert_eq!(migrations.len(), MIGRATIONS.len());
+        assert_eq!(
+            pending_migrations(&migrations, SCHEMA_VERSION - 2)
+                .unwrap()
+                .iter()
+                .map(|(version, _)| *version)
+                .collect::<Vec<_>>(),
+            vec![SCHEMA_VERSION - 1, SCHEMA_VERSION]
+        );
+        assert!(pending_migrations(&migrations, SCHEMA_VERSION)
+            .unwrap()
+            .is_empty());
+
+        let migrations = vec![(12, Cow::Borrowed("")), (14, Cow::Borrowed(""))];
+        assert_eq!(
+            pending_migrations(&migrations, 11).unwrap_err(),
+            "The migration 13 is missing"
+        );
+        assert_eq!(
+            pending_migrations(&migrations, 12).unwrap_err(),
+            "The migration 13 is missing"
+        );
+    }
+}
@@ -0,0 +1,462 @@
+/// Module responsible for detecting the drift of the schema written to from the schema
+/// the plugin expects, as created by scripts/create_schema.sql. The expected tables,
+/// columns and indexes are read from the catalog of a scratch schema the scripts are
+/// run in and rolled back, and are compared periodically with the live ones, reporting
+/// the missing tables, columns and indexes and the altered column types by the logs and
+/// the metrics, as the schema changes made by hand may break the assumptions the writes
+/// and the queries rely on.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::{
+            postgres_client_metrics::PluginMetrics, postgres_client_schema::create_schema_scripts,
+            SimplePostgresClient,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    postgres::{Client, GenericClient},
+    solana_metrics::datapoint_info,
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread::{sleep, Builder, JoinHandle},
+        time::{Duration, Instant},
+    },
+};
+
+const SCHEMA_DRIFT_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+/// The scratch schema the expected schema is created in, never committed
+const EXPECTED_SCHEMA: &str = "geyser_expected_schema";
+
+/// The kinds of the differences of the live schema from the expected one, the tables,
+/// the columns and the indexes added to the live schema are not drifts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum DriftKind {
+    MissingTable,
+    MissingColumn,
+    AlteredColumnType,
+    MissingIndex,
+}
+
+pub(crate) const DRIFT_KINDS: [DriftKind; 4] = [
+    DriftKind::MissingTable,
+    DriftKind::MissingColumn,
+    DriftKind::AlteredColumnType,
+    DriftKind::MissingIndex,
+];
+
+impl DriftKind {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            DriftKind::MissingTable => "missing_table",
+            DriftKind::MissingColumn => "missing_column",
+            DriftKind::AlteredColumnType => "altered_column_type",
+            DriftKind::MissingIndex => "missing_index",
+        }
+    }
+}
+
+/// A difference of the live schema from the expected one
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Drift {
+    kind: DriftKind,
+    table: String,
+    /// The column, the index or the types of the altered column
+    detail: String,
+}
+
+impl Drift {
+    fn describe(&self) -> String {
+        format!(
+            "{} on the table {}: {}",
+            self.kind.name(),
+            self.table,
+            self.detail
+        )
+    }
+}
+
+/// The tables of a schema, the partitions left out
+#[derive(Debug, Default)]
+struct SchemaDefinition {
+    /// The types of the columns by the tables
+    columns: BTreeMap<String, BTreeMap<String, String>>,
+    /// The definitions of the indexes by the tables, without their names and schemas
+    indexes: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// The definition of the index as given by pg_get_indexdef, without the name of the
+/// index and the schema of the table, so that an index renamed is not a drift, such
+/// as "UNIQUE btree (pubkey)".
+fn index_signature(definition: &str) -> String {
+    let unique = if definition.starts_with("CREATE UNIQUE INDEX ") {
+        "UNIQUE "
+    } else {
+        ""
+    };
+    let method = definition
+        .split_once(" USING ")
+        .map_or(definition, |(_, method)| method);
+    format!("{}{}", unique, method)
+}
+
+/// The differences of the live schema from the expected one, the columns and the indexes
+/// of the tables missing being left out.
+fn compare(expected: &SchemaDefinition, live: &SchemaDefinition) -> BTreeSet<Drift> {
+    let mut drifts = BTreeSet::default();
+    let drift = |kind, table: &str, detail: String| Drift {
+        kind,
+        table: table.to_string(),
+        detail,
+    };
+    for (table, columns) in &expected.columns {
+        let Some(live_columns) = live.columns.get(table) else {
+            drifts.insert(drift(DriftKind::MissingTable, table, String::default()));
+            continue;
+        };
+        for (column, data_type) in columns {
+            match live_columns.get(column) {
+                None => {
+                    drifts.insert(drift(DriftKind::MissingColumn, table, column.clone()));
+                }
+                Some(live_type) if live_type != data_type => {
+                    drifts.insert(drift(
+                        DriftKind::AlteredColumnType,
+                        table,
+                        format!("{} {} is {}", column, data_type, live_type),
+                    ));
+                }
+                Some(_) => (),
+            }
+        }
+        let live_indexes = live.indexes.get(table);
+        for index in expected.indexes.get(table).into_iter().flatten() {
+            if !live_indexes.is_some_and(|indexes| indexes.contains(index)) {
+                drifts.insert(drift(DriftKind::MissingIndex, table, index.clone()));
+            }
+        }
+    }
+    drifts
+}
+
+/// The number of the drifts of each kind, in the order of DRIFT_KINDS
+fn drift_counts(drifts: &BTreeSet<Drift>) -> [usize; DRIFT_KINDS.len()] {
+    let mut counts = [0; DRIFT_KINDS.len()];
+    for drift in drifts {
+        counts[drift.kind as usize] += 1;
+    }
+    counts
+}
+
+fn to_drift_error(action: &str, err: postgres::Error) -> GeyserPluginError {
+    let msg = format!(
+        "Failed to {} for the schema drift in the PostgreSQL database. Error: {:?}",
+        action, err
+    );
+    error!("{}", msg);
+    GeyserPluginError::Custom(Box::new(
+        AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+    ))
+}
+
+/// Look up the tables of the schema along with their columns and indexes.
+fn load_definition(
+    client: &mut impl GenericClient,
+    schema: &str,
+) -> Result<SchemaDefinition, GeyserPluginError> {
+    let mut definition = SchemaDefinition::default();
+    let rows = client
+        .query(
+            "SELECT c.relname::TEXT, a.attname::TEXT, format_type(a.atttypid, a.atttypmod) \
+            FROM pg_attribute a JOIN pg_class c ON c.oid = a.attrelid \
+            WHERE c.relnamespace = $1::TEXT::regnamespace AND c.relkind IN ('r', 'p') \
+            AND NOT c.relispartition AND a.attnum > 0 AND NOT a.attisdropped",
+            &[&schema],
+        )
+        .map_err(|err| to_drift_error("look up the columns", err))?;
+    for row in rows {
+        definition
+            .columns
+            .entry(row.get(0))
+            .or_default()
+            .insert(row.get(1), row.get(2));
+    }
+    let rows = client
+        .query(
+            "SELECT t.relname::TEXT, pg_get_indexdef(i.indexrelid) FROM pg_index i \
+            JOIN pg_class t ON t.oid = i.indrelid \
+            WHERE t.relnamespace = $1::TEXT::regnamespace AND NOT t.relispartition",
+            &[&schema],
+        )
+        .map_err(|err| to_drift_error("look up the indexes", err))?;
+    for row in rows {
+        definition
+            .indexes
+            .entry(row.get(0))
+            .or_default()
+            .insert(index_signature(row.get(1)));
+    }
+    Ok(definition)
+}
+
+/// Read the expected schema of the config, running the scripts creating the schema in a
+/// scratch schema rolled back.
+fn load_expected_definition(
+    client: &mut Client,
+    config: &AccountsDbPluginPostgresConfig,
+) -> Result<SchemaDefinition, GeyserPluginError> {
+    let mut transaction = client
+        .transaction()
+        .map_err(|err| to_drift_error("begin reading the expected schema", err))?;
+    transaction
+        .batch_execute(&format!(
+            "CREATE SCHEMA {schema}; SET LOCAL search_path TO {schema}",
+            schema = EXPECTED_SCHEMA
+        ))
+        .map_err(|err| to_drift_error("create the scratch schema of the expected schema", err))?;
+    for (action, script) in create_schema_scripts(config) {
+        transaction
+            .batch_execute(script)
+            .map_err(|err| to_drift_error(action, err))?;
+    }
+    let expected = load_definition(&mut transaction, EXPECTED_SCHEMA)?;
+    transaction
+        .rollback()
+        .map_err(|err| to_drift_error("roll back the expected schema", err))?;
+    Ok(expected)
+}
+
+fn current_schema(client: &mut Client) -> Result<String, GeyserPluginError> {
+    client
+        .query_one("SELECT current_schema()::TEXT", &[])
+        .map(|row| row.get(0))
+        .map_err(|err| to_drift_error("look up the current schema", err))
+}
+
+/// The drifts of the schema connected to from the expected schema of the config,
+/// described, for the checks outside of a validator.
+pub(crate) fn schema_drifts(
+    client: &mut Client,
+    config: &AccountsDbPluginPostgresConfig,
+) -> Result<Vec<String>, GeyserPluginError> {
+    let expected = load_expected_definition(client, config)?;
+    let schema = current_schema(client)?;
+    let live = load_definition(client, &schema)?;
+    Ok(compare(&expected, &live)
+        .iter()
+        .map(Drift::describe)
+        .collect())
+}
+
+pub(crate) struct SchemaDriftChecker {
+    client: Client,
+    interval: Duration,
+    expected: SchemaDefinition,
+    /// The drifts of the last check, only the changes are logged
+    drifts: BTreeSet<Drift>,
+}
+
+impl SchemaDriftChecker {
+    /// Connect and read the expected schema when the drift check is configured, running
+    /// the scripts creating the schema in a scratch schema rolled back. The loading of
+    /// the plugin fails when the user cannot create the scratch schema.
+    pub(crate) fn new(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Option<Self>, GeyserPluginError> {
+        let Some(interval_secs) = config.schema_drift_check_interval_secs else {
+            return Ok(None);
+        };
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+        let expected = load_expected_definition(&mut client, config)?;
+        Ok(Some(Self {
+            client,
+            interval: Duration::from_secs(interval_secs),
+            expected,
+            drifts: BTreeSet::default(),
+        }))
+    }
+
+    /// Compare the live schema with the expected one, logging the drifts found and the
+    /// ones resolved since the last check, and reporting their numbers to the metrics.
+    fn check(&mut self, metrics: Option<&PluginMetrics>) -> Result<(), GeyserPluginError> {
+        let schema = current_schema(&mut self.client)?;
+        let live = load_definition(&mut self.client, &schema)?;
+        let drifts = compare(&self.expected, &live);
+        for drift in drifts.difference(&self.drifts) {
+            warn!("The schema {} drifted, {}", schema, drift.describe());
+        }
+        for drift in self.drifts.difference(&drifts) {
+            info!(
+                "The schema {} no longer drifts, {}",
+                schema,
+                drift.describe()
+            );
+        }
+        let counts = drift_counts(&drifts);
+        if let Some(metrics) = metrics {
+            for kind in DRIFT_KINDS {
+                metrics.record_schema_drifts(kind, counts[kind as usize]);
+            }
+        }
+        datapoint_info!(
+            "accountsdb-plugin-postgres-schema-drift",
+            (
+                "missing_tables",
+                counts[DriftKind::MissingTable as usize],
+                i64
+            ),
+            (
+                "missing_columns",
+                counts[DriftKind::MissingColumn as usize],
+                i64
+            ),
+            (
+                "altered_column_types",
+                counts[DriftKind::AlteredColumnType as usize],
+                i64
+            ),
+            (
+                "missing_indexes",
+                counts[DriftKind::MissingIndex as usize],
+                i64
+            ),
+        );
+        self.drifts = drifts;
+        Ok(())
+    }
+
+    /// Check the schema every interval once the startup is done until the exit, as the
+    /// secondary indexes of the account table may be dropped during the startup.
+    pub(crate) fn spawn(
+        mut self,
+        exit_worker: Arc<AtomicBool>,
+        is_startup_done: Arc<AtomicBool>,
+        metrics: Option<Arc<PluginMetrics>>,
+    ) -> JoinHandle<Result<(), GeyserPluginError>> {
+        Builder::new()
+            .name("schema-drift".to_string())
+            .spawn(move || -> Result<(), GeyserPluginError> {
+                let mut last_check: Option<Instant> = None;
+                while !exit_worker.load(Ordering::Relaxed) {
+                    sleep(SCHEMA_DRIFT_CHECK_INTERVAL);
+                    if !is_startup_done.load(Ordering::Relaxed)
+                        || last_check.is_some_and(|last_check| last_check.elapsed() < self.interval)
+                    {
+                        continue;
+                    }
+                    last_check = Some(Instant::now());
+                    // The errors are logged, the schema is checked again next interval
+                    let _ = self.check(metrics.as_deref());
+                }
+                Ok(())
+            })
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn definition(columns: &[(&str, &str, &str)], indexes: &[(&str, &str)]) -> SchemaDefinition {
+        let mut definition = SchemaDefinition::default();
+        for (table, column, data_type) in columns {
+            definition
+                .columns
+                .entry(table.to_string())
+                .or_default()
+                .insert(column.to_string(), data_type.to_string());
+        }
+        for (table, index) in indexes {
+            definition
+                .indexes
+                .entry(table.to_string())
+                .or_default()
+                .insert(index_signature(index));
+        }
+        definition
+    }
+
+    #[test]
+    fn test_index_signature() {
+        assert_eq!(
+            index_signature(
+                "CREATE UNIQUE INDEX account_pkey ON geyser_expected_schema.account USING btree (pubkey)"
+            ),
+            "UNIQUE btree (pubkey)"
+        );
+        assert_eq!(
+            index_signature(
+                "CREATE INDEX account_owner ON ONLY public.account USING btree (owner) WHERE (owner IS NOT NULL)"
+            ),
+            "btree (owner) WHERE (owner IS NOT NULL)"
+        );
+    }
+
+    #[test]
+    fn test_compare() {
+        let expected = definition(
+            &[
+                ("account", "pubkey", "bytea"),
+                ("account", "slot", "bigint"),
+                ("account", "owner", "bytea"),
+                ("slot", "slot", "bigint"),
+            ],
+            &[
+                (
+                    "account",
+                    "CREATE UNIQUE INDEX account_pkey ON expected.account USING btree (pubkey)",
+                ),
+                (
+                    "account",
+                    "CREATE INDEX account_owner ON expected.account USING btree (owner)",
+                ),
+            ],
+        );
+        let live = definition(
+            &[
+                ("account", "pubkey", "bytea"),
+                ("account", "slot", "integer"),
+                ("account", "data_json", "jsonb"),
+                ("transaction", "slot", "bigint"),
+            ],
+            &[
+                (
+                    "account",
+                    "CREATE UNIQUE INDEX account_pkey ON public.account USING btree (pubkey)",
+                ),
+                (
+                    "account",
+                    "CREATE INDEX account_slot ON public.account USING btree (slot)",
+                ),
+            ],
+        );
+        let drift = |kind, table: &str, detail: &str| Drift {
+            kind,
+            table: table.to_string(),
+            detail: detail.to_string(),
+        };
+        let drifts = compare(&expected, &live);
+        assert_eq!(
+            drifts.iter().cloned().collect::<Vec<_>>(),
+            vec![
+                drift(DriftKind::MissingTable, "slot", ""),
+                drift(DriftKind::MissingColumn, "account", "owner"),
+                drift(
+                    DriftKind::AlteredColumnType,
+                    "account",
+                    "slot bigint is integer"
+                ),
+                drift(DriftKind::MissingIndex, "account", "btree (owner)"),
+            ]
+        );
+        assert_eq!(drift_counts(&drifts), [1, 1, 1, 1]);
+        assert!(compare(&expected, &expected).is_empty());
+    }
+}
@@ -0,0 +1,294 @@
+/// Module responsible for the schema source, the bundle of the versioned migrations
+/// read from a directory or an HTTP server in place of the migrations compiled into
+/// the plugin, so that the schema changes of a fleet are rolled out by updating one
+/// bundle. The bundle lists its migrations along with their SHA-256 checksums in a
+/// SHA256SUMS manifest, in the format of the sha256sum tool, and the files read are
+/// checked against the checksums of the manifest.
+use {
+    openssl::{
+        sha::sha256,
+        ssl::{SslConnector, SslMethod},
+    },
+    std::{
+        fs,
+        io::{self, Read, Write},
+        net::TcpStream,
+        path::Path,
+        time::Duration,
+    },
+};
+
+/// The manifest of the bundle listing the checksums of its migrations
+const MANIFEST_FILE: &str = "SHA256SUMS";
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A migration of the schema source
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct SourceMigration {
+    pub version: i32,
+    pub sql: String,
+}
+
+/// The SHA-256 checksum of the content in lowercase hex.
+pub(crate) fn sha256_hex(content: &[u8]) -> String {
+    sha256(content)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+/// Parse the manifest, the checksum and the file name of each migration per line,
+/// the version of a migration being the number its file name starts with, such as
+/// 0013_slot_transaction_count.sql. The migrations are sorted by their versions.
+fn parse_manifest(manifest: &str) -> Result<Vec<(i32, String, String)>, String> {
+    let mut migrations = Vec::new();
+    for line in manifest
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        let Some((checksum, file)) = line.split_once(char::is_whitespace) else {
+            return Err(format!("Invalid line of the manifest: {:?}", line));
+        };
+        // The binary mode of sha256sum marks the file names with a '*'
+        let file = file.trim_start().trim_start_matches('*');
+        if checksum.len() != 64 || !checksum.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Invalid checksum of the migration {:?}", file));
+        }
+        if file.contains('/') || file.contains('\\') || !file.ends_with(".sql") {
+            return Err(format!(
+                "The migration {:?} must be a .sql file of the bundle",
+                file
+            ));
+        }
+        let digits = file.chars().take_while(char::is_ascii_digit).count();
+        let version = match file[..digits].parse::<i32>() {
+            Ok(version) if version > 0 && file[digits..].starts_with('_') => version,
+            _ => {
+                return Err(format!(
+                    "The migration {:?} must be named after its version, such as 0001_name.sql",
+                    file
+                ))
+            }
+        };
+        migrations.push((version, file.to_string(), checksum.to_ascii_lowercase()));
+    }
+    migrations.sort_by_key(|(version, _, _)| *version);
+    if let Some(window) = migrations
+        .windows(2)
+        .find(|window| window[0].0 == window[1].0)
+    {
+        return Err(format!(
+            "The migrations {:?} and {:?} have the same version",
+            window[0].1, window[1].1
+        ));
+    }
+    Ok(migrations)
+}
+
+/// Check if the response read so far is complete as per its content length, for the
+/// servers closing the TLS connection without notifying it.
+fn is_complete(response: &[u8]) -> bool {
+    let Some(header_end) = response.windows(4).position(|window| window == b"\r\n\r\n") else {
+        return false;
+    };
+    let headers = String::from_utf8_lossy(&response[..header_end]);
+    headers
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, length)| length.trim().parse::<usize>().ok())
+        .is_some_and(|length| response.len() >= header_end + 4 + length)
+}
+
+/// Send the request and read the response until the server closes the connection.
+fn exchange(stream: &mut (impl Read + Write), request: &str) -> io::Result<Vec<u8>> {
+    stream.write_all(request.as_bytes())?;
+    let mut response = Vec::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        match stream.read(&mut buf) {
+            Ok(0) => return Ok(response),
+            Ok(count) => response.extend_from_slice(&buf[..count]),
+            Err(_) if is_complete(&response) => return Ok(response),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// The body of the HTTP response, which must be successful.
+fn response_body(response: &[u8]) -> Result<&[u8], String> {
+    let header_end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or("Incomplete HTTP response")?;
+    let headers = String::from_utf8_lossy(&response[..header_end]);
+    let status_line = headers.lines().next().unwrap_or_default();
+    if status_line.split_whitespace().nth(1) != Some("200") {
+        return Err(format!("The server responded {:?}", status_line));
+    }
+    Ok(&response[header_end + 4..])
+}
+
+/// Get the content of the URL, over HTTP/1.0 so that the response is not chunked.
+fn http_get(url: &str) -> Result<Vec<u8>, String> {
+    let (is_https, rest) = match url.strip_prefix("https://") {
+        Some(rest) => (true, rest),
+        None => (false, url.strip_prefix("http://").unwrap_or(url)),
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse::<u16>()
+                .map_err(|_| format!("Invalid port of the URL {:?}", url))?,
+        ),
+        None => (authority, if is_https { 443 } else { 80 }),
+    };
+    let request = format!(
+        "GET {} HTTP/1.0\r\nHost: {}\r\nAccept: */*\r\n\r\n",
+        path, authority
+    );
+    let get = || -> Result<Vec<u8>, String> {
+        let mut stream = TcpStream::connect((host, port)).map_err(|err| err.to_string())?;
+        stream
+            .set_read_timeout(Some(HTTP_TIMEOUT))
+            .and_then(|_| stream.set_write_timeout(Some(HTTP_TIMEOUT)))
+            .map_err(|err| err.to_string())?;
+        if !is_https {
+            return exchange(&mut stream, &request).map_err(|err| err.to_string());
+        }
+        let connector = SslConnector::builder(SslMethod::tls())
+            .map_err(|err| err.to_string())?
+            .build();
+        let mut stream = connector
+            .connect(host, stream)
+            .map_err(|err| err.to_string())?;
+        exchange(&mut stream, &request).map_err(|err| err.to_string())
+    };
+    let response = get().map_err(|err| format!("Failed to get {}: {}", url, err))?;
+    response_body(&response)
+        .map(<[u8]>::to_vec)
+        .map_err(|err| format!("Failed to get {}: {}", url, err))
+}
+
+/// Read the file of the schema source, a directory or the base URL of an HTTP server.
+fn read_source_file(source: &str, file: &str) -> Result<Vec<u8>, String> {
+    if is_url(source) {
+        http_get(&format!("{}/{}", source.trim_end_matches('/'), file))
+    } else {
+        let path = Path::new(source).join(file);
+        fs::read(&path).map_err(|err| format!("Failed to read {}: {}", path.display(), err))
+    }
+}
+
+/// Load the migrations of the schema source, sorted by their versions, checking each
+/// against its checksum in the manifest.
+pub(crate) fn load_schema_source(source: &str) -> Result<Vec<SourceMigration>, String> {
+    let manifest = read_source_file(source, MANIFEST_FILE)?;
+    let manifest = String::from_utf8(manifest)
+        .map_err(|_| format!("The {} of {} is not UTF-8", MANIFEST_FILE, source))?;
+    let mut migrations = Vec::new();
+    for (version, file, checksum) in parse_manifest(&manifest)? {
+        let content = read_source_file(source, &file)?;
+        let actual = sha256_hex(&content);
+        if actual != checksum {
+            return Err(format!(
+                "The checksum {} of the migration {} differs from the checksum {} of the {}",
+                actual, file, checksum, MANIFEST_FILE
+            ));
+        }
+        let sql = String::from_utf8(content)
+            .map_err(|_| format!("The migration {} is not UTF-8", file))?;
+        migrations.push(SourceMigration { version, sql });
+    }
+    if migrations.is_empty() {
+        return Err(format!(
+            "The {} of {} lists no migration",
+            MANIFEST_FILE, source
+        ));
+    }
+    Ok(migrations)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let checksum = sha256_hex(b"SELECT 1;");
+        assert_eq!(
+            checksum,
+            "17db4fd369edb9244b9f91d9aeed145c3d04ad8ba6e95d06247f07a63527d11a"
+        );
+        let manifest = format!(
+            "{checksum}  0014_index.sql\n\n{checksum} *0013_view.sql\n",
+            checksum = checksum.to_uppercase()
+        );
+        assert_eq!(
+            parse_manifest(&manifest).unwrap(),
+            vec![
+                (13, "0013_view.sql".to_string(), checksum.clone()),
+                (14, "0014_index.sql".to_string(), checksum.clone()),
+            ]
+        );
+        assert!(parse_manifest(&format!("{}  ../0013_view.sql", checksum)).is_err());
+        assert!(parse_manifest(&format!("{}  view.sql", checksum)).is_err());
+        assert!(parse_manifest("abc  0013_view.sql").is_err());
+        assert_eq!(
+            parse_manifest(&format!(
+                "{checksum}  0013_view.sql\n{checksum}  13_index.sql"
+            ))
+            .unwrap_err(),
+            "The migrations \"0013_view.sql\" and \"13_index.sql\" have the same version"
+        );
+    }
+
+    #[test]
+    fn test_response_body() {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\nSELECT 1;";
+        assert!(is_complete(response));
+        assert!(!is_complete(&response[..response.len() - 1]));
+        assert_eq!(response_body(response).unwrap(), b"SELECT 1;");
+        assert_eq!(
+            response_body(b"HTTP/1.1 404 Not Found\r\n\r\n").unwrap_err(),
+            "The server responded \"HTTP/1.1 404 Not Found\""
+        );
+    }
+
+    #[test]
+    fn test_load_schema_source() {
+        let dir = std::env::temp_dir().join(format!("schema-source-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let sql = "CREATE INDEX IF NOT EXISTS block_height ON block (block_height);";
+        fs::write(dir.join("0014_block_height.sql"), sql).unwrap();
+        fs::write(
+            dir.join(MANIFEST_FILE),
+            format!("{}  0014_block_height.sql\n", sha256_hex(sql.as_bytes())),
+        )
+        .unwrap();
+        let source = dir.to_str().unwrap();
+        assert_eq!(
+            load_schema_source(source).unwrap(),
+            vec![SourceMigration {
+                version: 14,
+                sql: sql.to_string(),
+            }]
+        );
+
+        fs::write(dir.join("0014_block_height.sql"), "DROP TABLE block;").unwrap();
+        assert!(load_schema_source(source)
+            .unwrap_err()
+            .starts_with("The checksum"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,300 @@
+/// Module responsible for spreading the updates over several PostgreSQL databases, each
+/// written by the workers of its own ParallelPostgresClient. The accounts are spread by
+/// the hash of their pubkeys or routed to one database, and the other kinds of update
+/// are routed per kind, the slot statuses being written to all the databases.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{AccountsDbPluginPostgresConfig, ShardingStrategy},
+        geyser_interface::{
+            ReplicaAccountInfo, ReplicaBlockInfo, ReplicaEntryInfo, ReplicaTransactionInfo,
+        },
+        postgres_client::ParallelPostgresClient,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::{GeyserPluginError, SlotStatus},
+    log::*,
+    std::thread,
+};
+
+/// The database of the account with the pubkey_hash strategy, from the FNV-1a hash of
+/// its pubkey, which is stable so that an account stays in its database across the
+/// restarts
+fn pubkey_shard(pubkey: &[u8], shard_count: usize) -> usize {
+    let hash = pubkey.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    });
+    // The high bits of the hash are mixed the most
+    ((hash as u128 * shard_count as u128) >> 64) as usize
+}
+
+/// Clear the settings of the checks over the blocks and the slots from the config of a
+/// database other than the one of the transactions, as it is notified neither the
+/// blocks nor the transactions: the block chain verification, the fork cleanup, the
+/// ingest SLA and the epoch barriers.
+fn clear_transaction_shard_settings(shard_config: &mut AccountsDbPluginPostgresConfig) {
+    shard_config.block_chain_verification_interval = None;
+    shard_config.fork_cleanup = None;
+    shard_config.ingest_sla_secs = None;
+    shard_config.write_epoch_barriers = None;
+}
+
+/// The configs of the databases the updates are written to, in the order of the
+/// connection strings of the sharding, or the config itself unless the sharding is
+/// configured. The checks over the blocks and the slots are run by the database of the
+/// transactions only.
+pub(crate) fn shard_configs(
+    config: &AccountsDbPluginPostgresConfig,
+) -> Vec<AccountsDbPluginPostgresConfig> {
+    let Some(sharding) = &config.sharding else {
+        return vec![config.clone()];
+    };
+    sharding
+        .connection_strs
+        .iter()
+        .enumerate()
+        .map(|(index, connection_str)| {
+            let mut shard_config = config.clone();
+            shard_config.connection_str = Some(connection_str.clone());
+            shard_config.sharding = None;
+            if index != sharding.transaction.unwrap_or_default() {
+                clear_transaction_shard_settings(&mut shard_config);
+            }
+            // The metrics endpoint is bound once, serving the metrics of the first database
+            if index > 0 {
+                shard_config.metrics = None;
+            }
+            // The updates of each database are spilled to their own file on the shutdown
+            shard_config.spill_path = config
+                .spill_path
+                .as_ref()
+                .map(|spill_path| format!("{}.{}", spill_path, index));
+            shard_config
+        })
+        .collect()
+}
+
+/// The clients of the databases the updates are written to, a single one unless the
+/// sharding is configured
+pub struct ShardedPostgresClient {
+    shards: Vec<ParallelPostgresClient>,
+    /// The database of the accounts, they are spread by the hash of their pubkeys when
+    /// not set
+    account_shard: Option<usize>,
+    /// The database of the transactions, along with the blocks and the vote activity
+    transaction_shard: usize,
+    entry_shard: usize,
+}
+
+impl ShardedPostgresClient {
+    pub fn new(config: &AccountsDbPluginPostgresConfig) -> Result<Self, GeyserPluginError> {
+        let Some(sharding) = &config.sharding else {
+            return Ok(Self {
+                shards: vec![ParallelPostgresClient::new(config)?],
+                account_shard: Some(0),
+                transaction_shard: 0,
+                entry_shard: 0,
+            });
+        };
+        let shard_configs = shard_configs(config);
+        let mut shards = Vec::with_capacity(shard_configs.len());
+        for (index, shard_config) in shard_configs.iter().enumerate() {
+            info!(
+                "Connecting to the database {} of {}",
+                index + 1,
+                shard_configs.len()
+            );
+            match ParallelPostgresClient::new(shard_config) {
+                Ok(shard) => shards.push(shard),
+                Err(err) => {
+                    for shard in &mut shards {
+                        let _ = shard.join();
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        Ok(Self {
+            shards,
+            account_shard: match sharding.strategy.unwrap_or_default() {
+                ShardingStrategy::PubkeyHash => None,
+                ShardingStrategy::ByKind => Some(sharding.account.unwrap_or_default()),
+            },
+            transaction_shard: sharding.transaction.unwrap_or_default(),
+            entry_shard: sharding.entry.unwrap_or_default(),
+        })
+    }
+
+    fn transaction_client(&self) -> &ParallelPostgresClient {
+        &self.shards[self.transaction_shard]
+    }
+
+    pub fn join(&mut self) -> thread::Result<()> {
+        for shard in &mut self.shards {
+            shard.join()?;
+        }
+        Ok(())
+    }
+
+    pub fn update_account(
+        &self,
+        account: &ReplicaAccountInfo,
+        slot: u64,
+        is_startup: bool,
+    ) -> Result<(), GeyserPluginError> {
+        let shard = self
+            .account_shard
+            .unwrap_or_else(|| pubkey_shard(account.pubkey, self.shards.len()));
+        self.shards[shard].update_account(account, slot, is_startup)
+    }
+
+    /// Each database resolves the confirmation of its rows by its own slot table
+    pub fn update_slot_status(
+        &self,
+        slot: u64,
+        parent: Option<u64>,
+        status: SlotStatus,
+    ) -> Result<(), GeyserPluginError> {
+        for shard in &self.shards {
+            shard.update_slot_status(slot, parent, status.clone())?;
+        }
+        Ok(())
+    }
+
+    pub fn update_block_metadata(
+        &self,
+        block_info: &ReplicaBlockInfo,
+    ) -> Result<(), GeyserPluginError> {
+        self.transaction_client().update_block_metadata(block_info)
+    }
+
+    pub fn update_entry(&self, entry_info: &ReplicaEntryInfo) -> Result<(), GeyserPluginError> {
+        self.shards[self.entry_shard].update_entry(entry_info)
+    }
+
+    pub fn log_transaction_info(
+        &self,
+        transaction_info: &ReplicaTransactionInfo,
+        slot: u64,
+    ) -> Result<(), GeyserPluginError> {
+        self.transaction_client()
+            .log_transaction_info(transaction_info, slot)
+    }
+
+    pub fn buffer_block_transaction(
+        &self,
+        transaction_info: &ReplicaTransactionInfo,
+        slot: u64,
+    ) -> Result<(), GeyserPluginError> {
+        self.transaction_client()
+            .buffer_block_transaction(transaction_info, slot)
+    }
+
+    pub fn log_vote_activity(
+        &self,
+        transaction_info: &ReplicaTransactionInfo,
+        slot: u64,
+    ) -> Result<(), GeyserPluginError> {
+        self.transaction_client()
+            .log_vote_activity(transaction_info, slot)
+    }
+
+    /// The transactions are only notified to the database of the transactions
+    pub fn set_transactions_notified(&self, transactions_notified: bool) {
+        for (index, shard) in self.shards.iter().enumerate() {
+            shard.set_transactions_notified(
+                transactions_notified && index == self.transaction_shard,
+            );
+        }
+    }
+
+    pub fn is_transaction_allowlisted(&self, transaction_info: &ReplicaTransactionInfo) -> bool {
+        self.transaction_client()
+            .is_transaction_allowlisted(transaction_info)
+    }
+
+    pub fn has_transaction_allowlist(&self) -> bool {
+        self.transaction_client().has_transaction_allowlist()
+    }
+
+    pub fn stop(&self) {
+        for shard in &self.shards {
+            shard.stop();
+        }
+    }
+
+    pub fn notify_end_of_startup(&self) -> Result<(), GeyserPluginError> {
+        for shard in &self.shards {
+            shard.notify_end_of_startup()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shard_configs() {
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_value(serde_json::json!({
+            "sharding": {
+                "connection_strs": ["host=a", "host=b", "host=c"],
+                "transaction": 1,
+            },
+            "block_chain_verification_interval": 1000,
+            "fork_cleanup": "purge",
+            "ingest_sla_secs": 10,
+            "write_epoch_barriers": true,
+            "spill_path": "spill.jsonl",
+        }))
+        .unwrap();
+        let shard_configs = shard_configs(&config);
+        assert_eq!(
+            shard_configs
+                .iter()
+                .map(|shard_config| shard_config.connection_str.as_deref().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["host=a", "host=b", "host=c"]
+        );
+        assert_eq!(
+            shard_configs[2].spill_path.as_deref(),
+            Some("spill.jsonl.2")
+        );
+        // The checks over the blocks and the slots are run by the database of the
+        // transactions only
+        for (index, shard_config) in shard_configs.iter().enumerate() {
+            let is_transaction_shard = index == 1;
+            assert_eq!(
+                shard_config.block_chain_verification_interval.is_some(),
+                is_transaction_shard
+            );
+            assert_eq!(shard_config.fork_cleanup.is_some(), is_transaction_shard);
+            assert_eq!(shard_config.ingest_sla_secs.is_some(), is_transaction_shard);
+            assert_eq!(
+                shard_config.write_epoch_barriers.is_some(),
+                is_transaction_shard
+            );
+        }
+    }
+
+    #[test]
+    fn test_pubkey_shard() {
+        let pubkey = [7u8; 32];
+        assert_eq!(pubkey_shard(&pubkey, 1), 0);
+        // The databases of the accounts must not change across the versions
+        assert_eq!(pubkey_shard(&pubkey, 3), 2);
+        assert_eq!(pubkey_shard(&pubkey, 4), 3);
+
+        // The accounts are spread over the databases
+        let mut counts = [0; 4];
+        for index in 0..4096u64 {
+            let pubkey = solana_sdk::hash::hash(&index.to_le_bytes()).to_bytes();
+            counts[pubkey_shard(&pubkey, counts.len())] += 1;
+        }
+        assert!(counts.iter().all(|count| *count > 900), "{:?}", counts);
+        let mut counts = [0; 2];
+        for byte in 0..=255u8 {
+            counts[pubkey_shard(&[byte; 32], counts.len())] += 1;
+        }
+        assert!(counts.iter().all(|count| *count > 96), "{:?}", counts);
+    }
+}
@@ -0,0 +1,507 @@
+/// Module responsible for the drain of the work items queued to the workers when the
+/// plugin is unloaded, so that the updates notified before the unload are written
+/// instead of being dropped along with the queues. The workers write the queued items
+/// and flush their pending batches until the drain timeout, and the work items left
+/// then, such as when the database is unreachable, are spilled to the spill file, one
+/// JSON line per item, which is replayed to the workers on the next load.
+use {
+    crate::{
+        accountsdb_plugin_postgres::AccountsDbPluginPostgresConfig,
+        postgres_client::{
+            CommitSlotRequest, DbWorkItem, LogTransactionRequest, LogVoteActivityRequest,
+            ParallelPostgresClient, PostgresClientWorker, UpdateAccountRequest,
+            UpdateBlockMetadataRequest, UpdateEntryRequest, UpdateSlotRequest,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::SlotStatus,
+    crossbeam_channel::{Receiver, Sender},
+    log::*,
+    serde_derive::{Deserialize, Serialize},
+    solana_metrics::*,
+    std::{
+        fs::{self, File, OpenOptions},
+        io::{self, BufRead, BufReader, Write},
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Mutex,
+        },
+        thread::sleep,
+        time::{Duration, Instant},
+    },
+};
+
+pub(crate) const DEFAULT_SHUTDOWN_DRAIN_TIMEOUT_SECS: u64 = 30;
+
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The serialization of the slot statuses of the slot updates spilled
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "SlotStatus")]
+pub(crate) enum SlotStatusDef {
+    Processed,
+    Rooted,
+    Confirmed,
+    FirstShredReceived,
+    Completed,
+    CreatedBank,
+    Dead(String),
+}
+
+/// The work items written to the spill file. The verifications, the cleanups, the
+/// startup report and the epoch barriers are left out, as they are triggered again by
+/// the slots notified after the next load.
+#[derive(Serialize, Deserialize)]
+enum SpilledWorkItem {
+    UpdateAccount(Box<UpdateAccountRequest>),
+    UpdateSlot(Box<UpdateSlotRequest>),
+    LogTransaction(Box<LogTransactionRequest>),
+    UpdateBlockMetadata(Box<UpdateBlockMetadataRequest>),
+    LogVoteActivity(Box<LogVoteActivityRequest>),
+    UpdateEntry(Box<UpdateEntryRequest>),
+    CommitSlot(Box<CommitSlotRequest>),
+}
+
+impl SpilledWorkItem {
+    fn new(work: DbWorkItem) -> Option<Self> {
+        match work {
+            // The accounts of the startup are written as the updates of the next load
+            DbWorkItem::UpdateAccount(mut request) => {
+                request.is_startup = false;
+                Some(SpilledWorkItem::UpdateAccount(request))
+            }
+            DbWorkItem::UpdateSlot(request) => Some(SpilledWorkItem::UpdateSlot(request)),
+            DbWorkItem::LogTransaction(request) => Some(SpilledWorkItem::LogTransaction(request)),
+            DbWorkItem::UpdateBlockMetadata(request) => {
+                Some(SpilledWorkItem::UpdateBlockMetadata(request))
+            }
+            DbWorkItem::LogVoteActivity(request) => Some(SpilledWorkItem::LogVoteActivity(request)),
+            DbWorkItem::UpdateEntry(request) => Some(SpilledWorkItem::UpdateEntry(request)),
+            DbWorkItem::CommitSlot(request) => Some(SpilledWorkItem::CommitSlot(request)),
+            DbWorkItem::VerifyBlockChain(_)
+            | DbWorkItem::WriteStartupReport(_)
+            | DbWorkItem::CleanUpForks(_)
+            | DbWorkItem::WriteEpochBarrier(_) => None,
+        }
+    }
+
+    fn into_work_item(self) -> DbWorkItem {
+        match self {
+            SpilledWorkItem::UpdateAccount(request) => DbWorkItem::UpdateAccount(request),
+            SpilledWorkItem::UpdateSlot(request) => DbWorkItem::UpdateSlot(request),
+            SpilledWorkItem::LogTransaction(request) => DbWorkItem::LogTransaction(request),
+            SpilledWorkItem::UpdateBlockMetadata(request) => {
+                DbWorkItem::UpdateBlockMetadata(request)
+            }
+            SpilledWorkItem::LogVoteActivity(request) => DbWorkItem::LogVoteActivity(request),
+            SpilledWorkItem::UpdateEntry(request) => DbWorkItem::UpdateEntry(request),
+            SpilledWorkItem::CommitSlot(request) => DbWorkItem::CommitSlot(request),
+        }
+    }
+}
+
+/// The version of the format of the spilled work items, to be bumped along with the
+/// changes of the work items, so that the lines spilled by another version are rejected
+/// instead of being written as mistaken updates
+const SPILL_FORMAT_VERSION: u32 = 1;
+
+/// The version of the format of a line of the spill file, read ahead of its work item
+#[derive(Deserialize)]
+struct SpilledLineVersion {
+    version: Option<u32>,
+}
+
+/// A line of the spill file
+#[derive(Serialize, Deserialize)]
+struct SpilledLine {
+    version: u32,
+    item: SpilledWorkItem,
+}
+
+impl SpilledLine {
+    fn new(item: SpilledWorkItem) -> Self {
+        Self {
+            version: SPILL_FORMAT_VERSION,
+            item,
+        }
+    }
+
+    /// Parse the line, rejecting the lines of another format version.
+    fn parse(line: &str) -> Result<SpilledWorkItem, String> {
+        let version = serde_json::from_str::<SpilledLineVersion>(line)
+            .map_err(|err| err.to_string())?
+            .version;
+        if version != Some(SPILL_FORMAT_VERSION) {
+            return Err(format!(
+                "The line is spilled in the format version {:?}, the version {} is expected",
+                version, SPILL_FORMAT_VERSION
+            ));
+        }
+        serde_json::from_str::<SpilledLine>(line)
+            .map(|line| line.item)
+            .map_err(|err| err.to_string())
+    }
+}
+
+/// The drain of the workers of a ParallelPostgresClient on the shutdown
+pub(crate) struct ShutdownDrain {
+    /// The time the workers are given to write the queued items, they are not drained
+    /// when zero
+    timeout: Duration,
+    spill_path: Option<String>,
+    /// The workers writing the updates, the drain is over once all have exited
+    worker_count: usize,
+    draining: AtomicBool,
+    exited_worker_count: AtomicUsize,
+    /// The spill file, opened by the first worker spilling
+    spill_file: Mutex<Option<File>>,
+}
+
+impl ShutdownDrain {
+    pub(crate) fn new(config: &AccountsDbPluginPostgresConfig, worker_count: usize) -> Self {
+        Self {
+            timeout: Duration::from_secs(
+                config
+                    .shutdown_drain_timeout_secs
+                    .unwrap_or(DEFAULT_SHUTDOWN_DRAIN_TIMEOUT_SECS),
+            ),
+            spill_path: config.spill_path.clone(),
+            worker_count,
+            draining: AtomicBool::new(false),
+            exited_worker_count: AtomicUsize::new(0),
+            spill_file: Mutex::new(None),
+        }
+    }
+
+    /// Check if the workers are to exit once their queues are drained
+    pub(crate) fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record_worker_exit(&self) {
+        self.exited_worker_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Check if the workers have all exited, whether drained or on an error
+    pub(crate) fn are_workers_exited(&self) -> bool {
+        self.exited_worker_count.load(Ordering::Relaxed) >= self.worker_count
+    }
+
+    fn append(&self, items: &[SpilledLine]) -> io::Result<()> {
+        let Some(spill_path) = &self.spill_path else {
+            return Ok(());
+        };
+        let mut spill_file = self.spill_file.lock().unwrap();
+        if spill_file.is_none() {
+            *spill_file = Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(spill_path)?,
+            );
+        }
+        let spill_file = spill_file.as_mut().unwrap();
+        for item in items {
+            let mut line = serde_json::to_vec(item)?;
+            line.push(b'\n');
+            spill_file.write_all(&line)?;
+        }
+        spill_file.sync_data()
+    }
+
+    /// Spill the work items left, or drop them when there is no spill file.
+    pub(crate) fn spill(&self, items: Vec<DbWorkItem>) {
+        let item_count = items.len();
+        let items: Vec<SpilledLine> = items
+            .into_iter()
+            .filter_map(SpilledWorkItem::new)
+            .map(SpilledLine::new)
+            .collect();
+        if items.is_empty() {
+            return;
+        }
+        if self.spill_path.is_none() {
+            error!(
+                "Dropping {} updates not written before the shutdown, set the \"spill_path\" to keep them",
+                items.len()
+            );
+            return;
+        }
+        match self.append(&items) {
+            Ok(()) => {
+                warn!(
+                    "Spilled {} of the {} work items not written before the shutdown to {:?}",
+                    items.len(),
+                    item_count,
+                    self.spill_path
+                );
+                inc_new_counter_info!("accountsdb-plugin-postgres-spilled-updates", items.len());
+            }
+            Err(err) => error!(
+                "Failed to spill {} updates to {:?}, dropping them: ({})",
+                items.len(),
+                self.spill_path,
+                err
+            ),
+        }
+    }
+
+    /// Read the work items spilled by the previous shutdown, removing the spill file.
+    /// A spill file with invalid lines, such as the ones spilled in another format
+    /// version, is kept renamed with the .rejected suffix.
+    fn take_spilled(&self) -> Vec<DbWorkItem> {
+        let Some(spill_path) = &self.spill_path else {
+            return vec![];
+        };
+        let file = match File::open(spill_path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return vec![],
+            Err(err) => {
+                error!("Failed to open the spill file {}: ({})", spill_path, err);
+                return vec![];
+            }
+        };
+        let mut items = vec![];
+        let mut invalid_count = 0;
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let item = line
+                .map_err(|err| err.to_string())
+                .and_then(|line| SpilledLine::parse(&line));
+            match item {
+                Ok(item) => items.push(item.into_work_item()),
+                Err(err) => {
+                    error!(
+                        "Invalid line {} of the spill file {}: ({})",
+                        index + 1,
+                        spill_path,
+                        err
+                    );
+                    invalid_count += 1;
+                }
+            }
+        }
+        let result = if invalid_count > 0 {
+            fs::rename(spill_path, format!("{}.rejected", spill_path))
+        } else {
+            fs::remove_file(spill_path)
+        };
+        if let Err(err) = result {
+            error!(
+                "Failed to remove the spill file {} replayed: ({})",
+                spill_path, err
+            );
+        }
+        items
+    }
+
+    /// Queue the work items spilled by the previous shutdown to the workers.
+    pub(crate) fn replay_spilled(&self, sender: &Sender<DbWorkItem>) {
+        let items = self.take_spilled();
+        if items.is_empty() {
+            return;
+        }
+        info!(
+            "Replaying {} updates spilled by the previous shutdown",
+            items.len()
+        );
+        for item in items {
+            if sender.send(item).is_err() {
+                error!("Failed to queue the updates spilled, the workers have exited");
+                return;
+            }
+        }
+    }
+}
+
+impl ParallelPostgresClient {
+    /// Let the workers write the items queued and flush their pending batches before
+    /// they exit, until the drain timeout.
+    pub(crate) fn drain_on_shutdown(&self) {
+        if !self.shutdown_drain.timeout.is_zero() {
+            self.drain_workers(self.shutdown_drain.timeout);
+        }
+    }
+
+    /// Let the workers write the items queued and flush their pending batches before
+    /// they exit at the stop slot, until the drain timeout, or the default one when the
+    /// drain on the shutdown is disabled.
+    pub(crate) fn drain_on_stop(&self) {
+        let timeout = match self.shutdown_drain.timeout {
+            timeout if timeout.is_zero() => {
+                Duration::from_secs(DEFAULT_SHUTDOWN_DRAIN_TIMEOUT_SECS)
+            }
+            timeout => timeout,
+        };
+        self.drain_workers(timeout);
+    }
+
+    fn drain_workers(&self, timeout: Duration) {
+        let drain = &self.shutdown_drain;
+        if let Err(err) = self.flush_entries(u64::MAX) {
+            error!("Failed to flush the buffered entries: ({})", err);
+        }
+        info!(
+            "Draining the {} work items queued to the workers",
+            self.sender.len()
+                + self
+                    .priority_sender
+                    .as_ref()
+                    .map_or(0, |priority_sender| priority_sender.len())
+        );
+        let started = Instant::now();
+        drain.draining.store(true, Ordering::Relaxed);
+        while drain.exited_worker_count.load(Ordering::Relaxed) < drain.worker_count {
+            if started.elapsed() >= timeout {
+                warn!(
+                    "The workers have not drained their queues after {:?}",
+                    timeout
+                );
+                return;
+            }
+            sleep(DRAIN_POLL_INTERVAL);
+        }
+        info!(
+            "Drained the queues of the workers in {:?}",
+            started.elapsed()
+        );
+    }
+}
+
+impl PostgresClientWorker {
+    /// Check if the worker draining its queue is to stop writing as the connection is
+    /// closed, with no reconnection to write the items once reconnected.
+    pub(crate) fn is_drain_stalled(&mut self) -> bool {
+        self.shutdown_drain.is_draining()
+            && self.reconnect_buffer.is_none()
+            && self.client.client.get_mut().unwrap().client.is_closed()
+    }
+
+    /// Finish the work of the exiting worker. Once drained, the pending batches are
+    /// flushed, and the items left otherwise are spilled: the ones pending a
+    /// reconnection, the accounts of the pending batches and the ones still queued.
+    pub(crate) fn exit_after_drain(&mut self, receiver: &Receiver<DbWorkItem>, is_drained: bool) {
+        if is_drained {
+            if let Err(err) = self.client.flush_buffered_writes() {
+                error!(
+                    "Failed to flush the pending batches on the shutdown: ({})",
+                    err
+                );
+            }
+        }
+        let mut items = vec![];
+        if let Some(buffer) = &mut self.reconnect_buffer {
+            items.extend(buffer.pending.drain(..));
+        }
+        items.extend(
+            self.client
+                .pending_account_updates
+                .drain(..)
+                .map(|account| {
+                    DbWorkItem::UpdateAccount(Box::new(UpdateAccountRequest {
+                        account,
+                        is_startup: false,
+                    }))
+                }),
+        );
+        items.extend(receiver.try_iter());
+        self.shutdown_drain.spill(items);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{DbAccountInfo, VerifyBlockChainRequest},
+        crossbeam_channel::unbounded,
+    };
+
+    #[test]
+    fn test_spill_and_replay() {
+        let spill_path = std::env::temp_dir().join(format!("pg-spill-{}", std::process::id()));
+        let spill_path = spill_path.to_str().unwrap().to_string();
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_value(serde_json::json!({
+            "spill_path": spill_path,
+        }))
+        .unwrap();
+        let drain = ShutdownDrain::new(&config, 1);
+        assert_eq!(drain.timeout, Duration::from_secs(30));
+        let account = DbAccountInfo {
+            pubkey: vec![1; 32],
+            lamports: 42,
+            owner: vec![2; 32],
+            executable: false,
+            rent_epoch: 7,
+            data: vec![3; 10],
+            slot: 10,
+            write_version: 1,
+            txn_signature: None,
+        };
+        drain.spill(vec![
+            DbWorkItem::UpdateAccount(Box::new(UpdateAccountRequest {
+                account: account.clone(),
+                is_startup: true,
+            })),
+            DbWorkItem::VerifyBlockChain(Box::new(VerifyBlockChainRequest {
+                from_slot: 0,
+                to_slot: 10,
+            })),
+        ]);
+        drain.spill(vec![DbWorkItem::UpdateSlot(Box::new(UpdateSlotRequest {
+            slot: 10,
+            parent: Some(9),
+            slot_status: SlotStatus::Dead("error".to_string()),
+        }))]);
+
+        let (sender, receiver) = unbounded();
+        drain.replay_spilled(&sender);
+        let items: Vec<DbWorkItem> = receiver.try_iter().collect();
+        assert_eq!(items.len(), 2);
+        match &items[0] {
+            DbWorkItem::UpdateAccount(request) => {
+                assert_eq!(request.account, account);
+                assert!(!request.is_startup);
+            }
+            _ => panic!("Unexpected work item"),
+        }
+        match &items[1] {
+            DbWorkItem::UpdateSlot(request) => {
+                assert_eq!(request.parent, Some(9));
+                assert_eq!(request.slot_status, SlotStatus::Dead("error".to_string()));
+            }
+            _ => panic!("Unexpected work item"),
+        }
+        // The spill file is removed once replayed
+        drain.replay_spilled(&sender);
+        assert!(receiver.is_empty());
+        assert!(!std::path::Path::new(&spill_path).exists());
+    }
+
+    #[test]
+    fn test_spilled_line_version() {
+        let item = SpilledWorkItem::UpdateSlot(Box::new(UpdateSlotRequest {
+            slot: 10,
+            parent: None,
+            slot_status: SlotStatus::Rooted,
+        }));
+        let line = serde_json::to_string(&SpilledLine::new(item)).unwrap();
+        assert!(matches!(
+            SpilledLine::parse(&line),
+            Ok(SpilledWorkItem::UpdateSlot(request)) if request.slot == 10
+        ));
+
+        // The lines of another version, or without any, are rejected
+        let mut value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        value["version"] = serde_json::json!(SPILL_FORMAT_VERSION + 1);
+        assert_eq!(
+            SpilledLine::parse(&value.to_string()).err().unwrap(),
+            format!(
+                "The line is spilled in the format version Some({}), the version {} is expected",
+                SPILL_FORMAT_VERSION + 1,
+                SPILL_FORMAT_VERSION
+            )
+        );
+        let unversioned = serde_json::to_string(&value["item"]).unwrap();
+        assert!(SpilledLine::parse(&unversioned).is_err());
+        assert!(SpilledLine::parse("not json").is_err());
+    }
+}
@@ -0,0 +1,340 @@
+/// Module responsible for the checksums of the rows ingested per slot, a digest of each
+/// account and transaction row committed by the workers summed per slot and stored on
+/// the slot row. The digests are computed from the columns as they are stored, and the
+/// sum is independent of the order of the rows, so that the slot_ingest_checksum SQL
+/// function recomputing it from the tables proves that the rows of the slot were not
+/// lost or mutated after their ingestion.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::{DbAccountInfo, DbTransaction, DbWorkItem, SimplePostgresClient},
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    openssl::sha::{sha256, Sha256},
+    postgres::{Client, Statement},
+    std::{
+        collections::BTreeMap,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        thread::{sleep, Builder, JoinHandle},
+        time::Duration,
+    },
+};
+
+pub(crate) const DEFAULT_STORE_SLOT_CHECKSUMS: bool = false;
+
+const SLOT_CHECKSUM_WRITE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The slots checksummed, the lowest ones are no longer checksummed past
+const MAX_CHECKSUMMED_SLOTS: usize = 1000;
+
+/// The tags of the rows digested, so that the rows of the tables do not collide
+const ACCOUNT_ROW_TAG: u8 = 0;
+const TRANSACTION_ROW_TAG: u8 = 1;
+
+/// The digest of the row, the first 8 bytes of the SHA-256 of its content as a big
+/// endian integer, as computed by the ingest_row_digest SQL function.
+fn row_digest(content: &[&[u8]]) -> i64 {
+    let mut hasher = Sha256::new();
+    for part in content {
+        hasher.update(part);
+    }
+    let digest = hasher.finish();
+    i64::from_be_bytes(digest[..8].try_into().unwrap())
+}
+
+/// The digest of the account row, as computed by the slot_ingest_checksum SQL function.
+fn account_digest(account: &DbAccountInfo) -> i64 {
+    row_digest(&[
+        &[ACCOUNT_ROW_TAG],
+        &account.pubkey,
+        &account.owner,
+        &account.lamports.to_be_bytes(),
+        &[account.executable as u8],
+        &account.rent_epoch.to_be_bytes(),
+        &account.write_version.to_be_bytes(),
+        &sha256(&account.data),
+    ])
+}
+
+/// The digest of the transaction row, as computed by the slot_ingest_checksum SQL
+/// function.
+pub(crate) fn transaction_digest(transaction: &DbTransaction) -> i64 {
+    row_digest(&[
+        &[TRANSACTION_ROW_TAG],
+        &transaction.signature,
+        &transaction.message_hash,
+        &[transaction.is_vote as u8],
+        &transaction.index.to_be_bytes(),
+        &transaction.meta.fee.to_be_bytes(),
+        &[transaction.meta.error.is_some() as u8],
+    ])
+}
+
+impl DbWorkItem {
+    /// Get the slot and the digest of the row written by the work item, for the account
+    /// updates and the transactions
+    pub(crate) fn checksummed_row(&self) -> Option<(u64, i64)> {
+        match self {
+            DbWorkItem::UpdateAccount(request) => Some((
+                request.account.slot as u64,
+                account_digest(&request.account),
+            )),
+            DbWorkItem::LogTransaction(request) => Some((
+                request.transaction_info.slot as u64,
+                transaction_digest(&request.transaction_info),
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// The checksum of the rows of a slot committed since its first row
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct SlotChecksum {
+    /// The wrapping sum of the digests of the rows
+    checksum: i64,
+    rows: u64,
+    /// Indicates if the checksum is written to the slot row
+    written: bool,
+}
+
+/// The slots checksummed, and the lowest slot still checksummed, the rows of the slots
+/// before it being ignored so that their checksums written are not overwritten
+#[derive(Default)]
+struct ChecksummedSlots {
+    slots: BTreeMap<u64, SlotChecksum>,
+    checksummed_from: u64,
+}
+
+/// The checksums of the rows per slot, shared by the workers
+#[derive(Default)]
+pub(crate) struct SlotChecksums {
+    slots: Mutex<ChecksummedSlots>,
+}
+
+impl SlotChecksums {
+    /// Record the row of the digest committed by a worker.
+    pub(crate) fn record_committed(&self, slot: u64, digest: i64) {
+        let mut slots = self.slots.lock().unwrap();
+        if slot < slots.checksummed_from {
+            return;
+        }
+        let checksum = slots.slots.entry(slot).or_default();
+        checksum.checksum = checksum.checksum.wrapping_add(digest);
+        checksum.rows += 1;
+        checksum.written = false;
+        while slots.slots.len() > MAX_CHECKSUMMED_SLOTS {
+            if let Some((slot, _)) = slots.slots.pop_first() {
+                slots.checksummed_from = slot + 1;
+            }
+        }
+    }
+
+    /// Take the checksums of the slots changed since they were last written, which are
+    /// then marked as written.
+    fn take_changed(&self) -> Vec<(u64, SlotChecksum)> {
+        let mut slots = self.slots.lock().unwrap();
+        slots
+            .slots
+            .iter_mut()
+            .filter(|(_, checksum)| !checksum.written)
+            .map(|(slot, checksum)| {
+                checksum.written = true;
+                (*slot, *checksum)
+            })
+            .collect()
+    }
+
+    /// Mark the checksum of the slot as not written, for it to be written again.
+    fn mark_unwritten(&self, slot: u64) {
+        if let Some(checksum) = self.slots.lock().unwrap().slots.get_mut(&slot) {
+            checksum.written = false;
+        }
+    }
+}
+
+pub(crate) struct SlotChecksumWriter {
+    client: Client,
+    statement: Statement,
+    checksums: Arc<SlotChecksums>,
+}
+
+impl SlotChecksumWriter {
+    pub(crate) fn new(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Option<Self>, GeyserPluginError> {
+        if !config
+            .store_slot_checksums
+            .unwrap_or(DEFAULT_STORE_SLOT_CHECKSUMS)
+        {
+            return Ok(None);
+        }
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+        let stmt = "UPDATE slot SET ingest_checksum = $2, ingest_checksum_rows = $3 \
+            WHERE slot = $1";
+        let statement = match client.prepare(stmt) {
+            Ok(statement) => statement,
+            Err(err) => {
+                return Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "Error in preparing for the slot checksum PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                        err, config.host, config.user, config
+                    ),
+                })));
+            }
+        };
+        Ok(Some(Self {
+            client,
+            statement,
+            checksums: Arc::new(SlotChecksums::default()),
+        }))
+    }
+
+    pub(crate) fn checksums(&self) -> Arc<SlotChecksums> {
+        self.checksums.clone()
+    }
+
+    /// Write the checksums changed to the slot rows, the slots not written yet by the
+    /// workers being written again at the next interval.
+    fn write(&mut self) {
+        for (slot, checksum) in self.checksums.take_changed() {
+            let result = self.client.execute(
+                &self.statement,
+                &[&(slot as i64), &checksum.checksum, &(checksum.rows as i64)],
+            );
+            match result {
+                Ok(0) => self.checksums.mark_unwritten(slot),
+                Ok(_) => {}
+                Err(err) => {
+                    error!(
+                        "Failed to persist the ingest checksum of the slot {} to the PostgreSQL database. Error: {:?}",
+                        slot, err
+                    );
+                    self.checksums.mark_unwritten(slot);
+                }
+            }
+        }
+    }
+
+    /// Write the checksums changed at the interval until the exit, and once more then.
+    pub(crate) fn spawn(
+        mut self,
+        exit_worker: Arc<AtomicBool>,
+    ) -> JoinHandle<Result<(), GeyserPluginError>> {
+        Builder::new()
+            .name("slot-checksum-writer".to_string())
+            .spawn(move || -> Result<(), GeyserPluginError> {
+                while !exit_worker.load(Ordering::Relaxed) {
+                    sleep(SLOT_CHECKSUM_WRITE_INTERVAL);
+                    self.write();
+                }
+                self.write();
+                Ok(())
+            })
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_row_digest() {
+        // The first 8 bytes of the SHA-256 of "SELECT 1;"
+        assert_eq!(row_digest(&[b"SELECT", b" 1;"]), 0x17db4fd369edb924);
+        let account = DbAccountInfo {
+            pubkey: vec![1; 32],
+            lamports: 10,
+            owner: vec![2; 32],
+            executable: false,
+            rent_epoch: 0,
+            data: vec![3; 4],
+            slot: 5,
+            write_version: 7,
+            txn_signature: None,
+        };
+        let digest = account_digest(&account);
+        // The slot and the signature of the transaction are not digested
+        assert_eq!(
+            account_digest(&DbAccountInfo {
+                slot: 6,
+                txn_signature: Some(vec![4; 64]),
+                ..account.clone()
+            }),
+            digest
+        );
+        assert_ne!(
+            account_digest(&DbAccountInfo {
+                data: vec![3; 5],
+                ..account
+            }),
+            digest
+        );
+    }
+
+    #[test]
+    fn test_slot_checksums() {
+        let checksums = SlotChecksums::default();
+        checksums.record_committed(1, i64::MAX);
+        checksums.record_committed(1, 2);
+        checksums.record_committed(2, -1);
+        assert_eq!(
+            checksums.take_changed(),
+            vec![
+                (
+                    1,
+                    SlotChecksum {
+                        checksum: i64::MIN + 1,
+                        rows: 2,
+                        written: true,
+                    }
+                ),
+                (
+                    2,
+                    SlotChecksum {
+                        checksum: -1,
+                        rows: 1,
+                        written: true,
+                    }
+                ),
+            ]
+        );
+        assert!(checksums.take_changed().is_empty());
+
+        // The sum is independent of the order of the rows
+        let reordered = SlotChecksums::default();
+        reordered.record_committed(1, 2);
+        reordered.record_committed(1, i64::MAX);
+        assert_eq!(reordered.take_changed()[0].1.checksum, i64::MIN + 1);
+
+        // The checksums changed, or not written, are taken again
+        checksums.record_committed(1, 1);
+        checksums.mark_unwritten(2);
+        assert_eq!(
+            checksums
+                .take_changed()
+                .into_iter()
+                .map(|(slot, checksum)| (slot, checksum.rows))
+                .collect::<Vec<_>>(),
+            vec![(1, 3), (2, 1)]
+        );
+
+        // The lowest slots are no longer checksummed past the max slots checksummed
+        for slot in 3..MAX_CHECKSUMMED_SLOTS as u64 + 3 {
+            checksums.record_committed(slot, 1);
+        }
+        let slots = checksums.slots.lock().unwrap();
+        assert_eq!(slots.slots.len(), MAX_CHECKSUMMED_SLOTS);
+        assert_eq!(slots.checksummed_from, 3);
+        drop(slots);
+        checksums.record_committed(1, 1);
+        assert!(!checksums.slots.lock().unwrap().slots.contains_key(&1));
+    }
+}
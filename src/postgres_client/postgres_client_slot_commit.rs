@@ -0,0 +1,288 @@
+/// Module responsible for the per-slot commit mode, committing the transactions of each
+/// slot together once the block metadata of the slot is notified. The transactions of
+/// the slots are buffered until then, and a worker commits them in chunks of a bounded
+/// number of transactions, one database transaction per chunk, so that the blocks with
+/// thousands of transactions do not make for huge database transactions. The slot_commit
+/// row of the slot is written within the database transaction of the final chunk, so
+/// that the readers waiting for it see all the transactions of the slot committed.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::{
+            postgres_client_ingest_sla::IngestStream, postgres_client_metrics::MetricStream,
+            postgres_client_slot_checksum::transaction_digest, DbWorkItem, LogTransactionRequest,
+            ParallelPostgresClient, PostgresClientWorker, SimplePostgresClient,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    serde_derive::{Deserialize, Serialize},
+    std::{collections::HashMap, sync::Mutex},
+};
+
+pub(crate) const DEFAULT_COMMIT_PER_SLOT: bool = false;
+pub(crate) const DEFAULT_SLOT_COMMIT_CHUNK_SIZE: usize = 1000;
+
+const UPSERT_SLOT_COMMIT: &str =
+    "INSERT INTO slot_commit (slot, transaction_count, chunk_count, committed_on) \
+    VALUES ($1, $2, $3, $4) \
+    ON CONFLICT (slot) DO UPDATE SET transaction_count = excluded.transaction_count, \
+    chunk_count = excluded.chunk_count, committed_on = excluded.committed_on";
+
+/// The transactions of a slot committed together, in the order of the block
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct CommitSlotRequest {
+    pub slot: u64,
+    pub transactions: Vec<LogTransactionRequest>,
+}
+
+impl CommitSlotRequest {
+    /// The chunks of the transactions committed one database transaction each, a slot
+    /// without any transaction having a single empty chunk for its slot_commit row.
+    pub(crate) fn chunks(&self, chunk_size: usize) -> Vec<&[LogTransactionRequest]> {
+        if self.transactions.is_empty() {
+            return vec![&[]];
+        }
+        self.transactions.chunks(chunk_size).collect()
+    }
+}
+
+/// The transactions of the slots whose block metadata is not notified yet, keyed by
+/// the slot
+#[derive(Default)]
+pub(crate) struct SlotCommitBuffer {
+    transactions: Mutex<HashMap<u64, Vec<LogTransactionRequest>>>,
+}
+
+impl SlotCommitBuffer {
+    pub(crate) fn new(config: &AccountsDbPluginPostgresConfig) -> Option<Self> {
+        config
+            .commit_per_slot
+            .unwrap_or(DEFAULT_COMMIT_PER_SLOT)
+            .then(Self::default)
+    }
+
+    pub(crate) fn add_transaction(&self, slot: u64, request: LogTransactionRequest) {
+        self.transactions
+            .lock()
+            .unwrap()
+            .entry(slot)
+            .or_default()
+            .push(request);
+    }
+
+    /// Take the buffered transactions of the slot, in the order of the block.
+    fn take_slot(&self, slot: u64) -> CommitSlotRequest {
+        let mut transactions = self
+            .transactions
+            .lock()
+            .unwrap()
+            .remove(&slot)
+            .unwrap_or_default();
+        transactions.sort_by_key(|request| request.transaction_info.index);
+        CommitSlotRequest { slot, transactions }
+    }
+
+    /// Drop the transactions of the slot which will not be notified the block metadata,
+    /// as it is dead.
+    pub(crate) fn remove_slot(&self, slot: u64) {
+        self.transactions.lock().unwrap().remove(&slot);
+    }
+
+    /// Drop the transactions of the slots up to the rooted slot, the slots left are on
+    /// the abandoned forks.
+    pub(crate) fn remove_slots_up_to(&self, rooted_slot: u64) {
+        self.transactions
+            .lock()
+            .unwrap()
+            .retain(|slot, _| *slot > rooted_slot);
+    }
+}
+
+impl SimplePostgresClient {
+    /// Write the slot_commit row of the slot, within the database transaction of the
+    /// final chunk of its transactions.
+    pub(crate) fn write_slot_commit_marker(
+        &mut self,
+        slot: u64,
+        transaction_count: usize,
+        chunk_count: usize,
+    ) -> Result<(), GeyserPluginError> {
+        let client = &mut self.client.get_mut().unwrap().client;
+        let result = client.execute(
+            UPSERT_SLOT_COMMIT,
+            &[
+                &(slot as i64),
+                &(transaction_count as i64),
+                &(chunk_count as i64),
+                &Utc::now().naive_utc(),
+            ],
+        );
+
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to persist the commit of the slot {} to the PostgreSQL database. Error: {:?}",
+                slot, err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl PostgresClientWorker {
+    /// Record the transactions of the slot committed, for the ingestion SLA, the
+    /// transaction counts, the slot checksums and the metrics, as if committed one by one.
+    pub(crate) fn record_committed_transactions(&self, request: &CommitSlotRequest) {
+        for transaction in &request.transactions {
+            if let Some(monitor) = &self.ingest_sla_monitor {
+                monitor.record_committed(IngestStream::Transaction, request.slot);
+            }
+            if let Some(transaction_counts) = &self.transaction_counts {
+                transaction_counts.record_committed(request.slot);
+            }
+            if let Some(slot_checksums) = &self.slot_checksums {
+                slot_checksums.record_committed(
+                    request.slot,
+                    transaction_digest(&transaction.transaction_info),
+                );
+            }
+            if let Some(metrics) = &self.metrics {
+                metrics.record_write((MetricStream::Transaction, None), true);
+            }
+        }
+    }
+}
+
+impl ParallelPostgresClient {
+    /// Queue the transactions of the slot buffered for the per-slot commit, once its
+    /// block metadata is notified, a no-op without the per-slot commit.
+    pub(crate) fn commit_slot(&self, slot: u64) -> Result<(), GeyserPluginError> {
+        let Some(slot_commit_buffer) = &self.slot_commit_buffer else {
+            return Ok(());
+        };
+        let request = slot_commit_buffer.take_slot(slot);
+        if let Err(err) = self
+            .queue_overflow
+            .send(DbWorkItem::CommitSlot(Box::new(request)))
+        {
+            return Err(GeyserPluginError::SlotStatusUpdateError {
+                msg: format!(
+                    "Failed to commit the transactions of the slot {:?}, error: {:?}",
+                    slot, err
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::postgres_client_transaction::{
+            DbTransaction, DbTransactionStatusMeta,
+        },
+    };
+
+    fn transaction(slot: u64, index: i64) -> LogTransactionRequest {
+        let transaction_info = DbTransaction {
+            signature: vec![index as u8; 64],
+            is_vote: false,
+            slot: slot as i64,
+            message_type: 0,
+            legacy_message: None,
+            v0_loaded_message: None,
+            message_hash: vec![0; 32],
+            meta: DbTransactionStatusMeta {
+                error: None,
+                fee: 5000,
+                pre_balances: vec![],
+                post_balances: vec![],
+                inner_instructions: None,
+                log_messages: None,
+                pre_token_balances: None,
+                post_token_balances: None,
+                rewards: None,
+            },
+            index,
+            signatures: vec![vec![index as u8; 64]],
+            decoded_error: None,
+        };
+        LogTransactionRequest {
+            transaction_info,
+            transaction_json: None,
+            sol_transfers: vec![],
+            memos: vec![],
+            instructions: vec![],
+            token_balance_changes: vec![],
+            account_balances: vec![],
+        }
+    }
+
+    #[test]
+    fn test_slot_commit_buffer() {
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str("{}").unwrap();
+        assert!(SlotCommitBuffer::new(&config).is_none());
+        let config: AccountsDbPluginPostgresConfig =
+            serde_json::from_str("{\"commit_per_slot\": true}").unwrap();
+        let buffer = SlotCommitBuffer::new(&config).unwrap();
+
+        buffer.add_transaction(10, transaction(10, 2));
+        buffer.add_transaction(10, transaction(10, 0));
+        buffer.add_transaction(11, transaction(11, 0));
+        buffer.add_transaction(12, transaction(12, 0));
+        buffer.add_transaction(10, transaction(10, 1));
+
+        // The transactions are taken in the order of the block
+        let request = buffer.take_slot(10);
+        assert_eq!(request.slot, 10);
+        assert_eq!(
+            request
+                .transactions
+                .iter()
+                .map(|request| request.transaction_info.index)
+                .collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+        assert!(buffer.take_slot(10).transactions.is_empty());
+
+        buffer.remove_slot(12);
+        assert!(buffer.take_slot(12).transactions.is_empty());
+        buffer.remove_slots_up_to(11);
+        assert!(buffer.transactions.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_commit_slot_request_chunks() {
+        let request = CommitSlotRequest {
+            slot: 10,
+            transactions: (0..5).map(|index| transaction(10, index)).collect(),
+        };
+        let chunk_lens = |chunk_size| {
+            request
+                .chunks(chunk_size)
+                .iter()
+                .map(|chunk| chunk.len())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(chunk_lens(2), vec![2, 2, 1]);
+        assert_eq!(chunk_lens(5), vec![5]);
+        assert_eq!(chunk_lens(1000), vec![5]);
+
+        // The slot without any transaction is committed by a single empty chunk
+        let request = CommitSlotRequest {
+            slot: 11,
+            transactions: vec![],
+        };
+        assert_eq!(request.chunks(2).len(), 1);
+        assert!(request.chunks(2)[0].is_empty());
+    }
+}
@@ -0,0 +1,241 @@
+/// Module responsible for deriving the fee burn and the rent collected per slot from
+/// the transaction fees and the block rewards into the PostgreSQL database.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        geyser_interface::{ReplicaBlockInfo, ReplicaTransactionInfo},
+        postgres_client::SimplePostgresClient,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    postgres::{Client, Statement},
+    serde_derive::{Deserialize, Serialize},
+    solana_transaction_status::RewardType,
+    std::{collections::HashMap, sync::Mutex},
+};
+
+/// The transaction fees of the slots whose block metadata is not notified yet, as the
+/// total and the count of the transactions.
+#[derive(Default)]
+pub(crate) struct SlotFeeBuffer {
+    fees: Mutex<HashMap<u64, (u64, u64)>>,
+}
+
+impl SlotFeeBuffer {
+    pub(crate) fn add_transaction(&self, transaction_info: &ReplicaTransactionInfo, slot: u64) {
+        let mut fees = self.fees.lock().unwrap();
+        let (total_fees, transaction_count) = fees.entry(slot).or_default();
+        *total_fees += transaction_info.transaction_status_meta.fee;
+        *transaction_count += 1;
+    }
+
+    /// Take the transaction fees of the block and derive the economics of the slot.
+    pub(crate) fn take_slot_economics(&self, block_info: &ReplicaBlockInfo) -> DbSlotEconomics {
+        let (total_fees, transaction_count) = self
+            .fees
+            .lock()
+            .unwrap()
+            .remove(&block_info.slot)
+            .unwrap_or_default();
+        DbSlotEconomics::new(block_info, total_fees, transaction_count)
+    }
+
+    /// Drop the transaction fees of the slot which will not be notified the block
+    /// metadata, as it is dead.
+    pub(crate) fn remove_slot(&self, slot: u64) {
+        self.fees.lock().unwrap().remove(&slot);
+    }
+
+    /// Drop the transaction fees of the slots up to the rooted slot, the slots left are
+    /// on the abandoned forks.
+    pub(crate) fn remove_slots_up_to(&self, rooted_slot: u64) {
+        self.fees
+            .lock()
+            .unwrap()
+            .retain(|slot, _| *slot > rooted_slot);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DbSlotEconomics {
+    pub slot: i64,
+    pub transaction_count: i64,
+    /// The fees paid by all the transactions of the block, including the priority fees
+    pub transaction_fees: i64,
+    /// The share of the fees credited to the leader in the fee rewards of the block
+    pub fee_rewards: i64,
+    /// The fees not credited to the leader are burnt
+    pub fee_burn: i64,
+    /// The rent collected credited to the validators in the rent rewards of the block
+    pub rent_rewards: i64,
+    pub rent_reward_count: i64,
+}
+
+impl DbSlotEconomics {
+    fn new(block_info: &ReplicaBlockInfo, total_fees: u64, transaction_count: u64) -> Self {
+        let rewards = |reward_type: RewardType| {
+            block_info
+                .rewards
+                .rewards
+                .iter()
+                .filter(move |reward| reward.reward_type == Some(reward_type))
+        };
+        let fee_rewards: i64 = rewards(RewardType::Fee).map(|reward| reward.lamports).sum();
+        let rent_rewards: i64 = rewards(RewardType::Rent)
+            .map(|reward| reward.lamports)
+            .sum();
+
+        Self {
+            slot: block_info.slot as i64,
+            transaction_count: transaction_count as i64,
+            transaction_fees: total_fees as i64,
+            fee_rewards,
+            fee_burn: total_fees as i64 - fee_rewards,
+            rent_rewards,
+            rent_reward_count: rewards(RewardType::Rent).count() as i64,
+        }
+    }
+}
+
+impl SimplePostgresClient {
+    /// Build the statement upserting the economics of a slot into the slot_economics table.
+    pub fn build_slot_economics_upsert_statement(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Statement, GeyserPluginError> {
+        let stmt =
+            "INSERT INTO slot_economics (slot, transaction_count, transaction_fees, fee_rewards, \
+            fee_burn, rent_rewards, rent_reward_count, updated_on) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8) \
+            ON CONFLICT (slot) DO UPDATE SET transaction_count=excluded.transaction_count, \
+            transaction_fees=excluded.transaction_fees, fee_rewards=excluded.fee_rewards, \
+            fee_burn=excluded.fee_burn, rent_rewards=excluded.rent_rewards, \
+            rent_reward_count=excluded.rent_reward_count, updated_on=excluded.updated_on";
+
+        let stmt = client.prepare(stmt);
+
+        match stmt {
+            Err(err) => {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "Error in preparing for the slot economics update PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                        err, config.host, config.user, config
+                    ),
+                })))
+            }
+            Ok(stmt) => Ok(stmt),
+        }
+    }
+
+    pub(crate) fn upsert_slot_economics(
+        client: &mut Client,
+        statement: &Statement,
+        slot_economics: &DbSlotEconomics,
+    ) -> Result<(), GeyserPluginError> {
+        let updated_on = Utc::now().naive_utc();
+        let result = client.execute(
+            statement,
+            &[
+                &slot_economics.slot,
+                &slot_economics.transaction_count,
+                &slot_economics.transaction_fees,
+                &slot_economics.fee_rewards,
+                &slot_economics.fee_burn,
+                &slot_economics.rent_rewards,
+                &slot_economics.rent_reward_count,
+                &updated_on,
+            ],
+        );
+
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to persist the slot economics to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::SlotStatusUpdateError { msg });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::postgres_client_transaction::tests::TestTransaction,
+        solana_sdk::{hash::Hash, pubkey::Pubkey, signature::Keypair},
+        solana_transaction_status::{Reward, RewardsAndNumPartitions},
+    };
+
+    fn reward(lamports: i64, reward_type: RewardType) -> Reward {
+        Reward {
+            pubkey: Pubkey::new_unique().to_string(),
+            lamports,
+            post_balance: 1_000_000,
+            reward_type: Some(reward_type),
+            commission: None,
+        }
+    }
+
+    #[test]
+    fn test_slot_economics() {
+        let buffer = SlotFeeBuffer::default();
+        let keypair = Keypair::new();
+        let mut transaction = TestTransaction::new(solana_system_transaction::transfer(
+            &keypair,
+            &Pubkey::new_unique(),
+            1,
+            Hash::default(),
+        ));
+        for fee in [5000, 15000] {
+            transaction.meta.fee = fee;
+            let transaction_info = transaction.transaction_info();
+            buffer.add_transaction(&transaction_info, 7);
+            buffer.add_transaction(&transaction_info, 8);
+        }
+
+        let rewards = RewardsAndNumPartitions {
+            rewards: vec![
+                reward(12500, RewardType::Fee),
+                reward(30, RewardType::Rent),
+                reward(12, RewardType::Rent),
+                reward(1000, RewardType::Staking),
+            ],
+            num_partitions: None,
+        };
+        let blockhash = Hash::new_unique().to_string();
+        let parent_blockhash = Hash::new_unique().to_string();
+        let block_info = ReplicaBlockInfo {
+            parent_slot: 6,
+            parent_blockhash: &parent_blockhash,
+            slot: 7,
+            blockhash: &blockhash,
+            rewards: &rewards,
+            block_time: None,
+            block_height: None,
+            executed_transaction_count: 2,
+            entry_count: 1,
+        };
+        assert_eq!(
+            buffer.take_slot_economics(&block_info),
+            DbSlotEconomics {
+                slot: 7,
+                transaction_count: 2,
+                transaction_fees: 20000,
+                fee_rewards: 12500,
+                fee_burn: 7500,
+                rent_rewards: 42,
+                rent_reward_count: 2,
+            }
+        );
+
+        // The slot is taken, the slot of the abandoned fork dropped at the root
+        assert!(buffer.fees.lock().unwrap().contains_key(&8));
+        buffer.remove_slots_up_to(8);
+        assert!(buffer.fees.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+/// Module responsible for detecting the restarts of the validator from a snapshot older
+/// than the slots already stored. The accounts of the snapshot are then notified at a
+/// slot lower than the highest rooted slot stored at the load, the checkpoint watermark,
+/// and the slots after the snapshot are replayed again. The rewind is alerted, or the
+/// rows of the slots after the snapshot are purged for the replay to write them again.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError, SnapshotRewindAction,
+        },
+        postgres_client::{
+            postgres_client_fork_cleanup::FORK_CLEANUP_TABLES, SimplePostgresClient,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    solana_metrics::datapoint_error,
+};
+
+fn to_rewind_error(action: &str, err: postgres::Error) -> GeyserPluginError {
+    let msg = format!(
+        "Failed to {} of the snapshot rewind in the PostgreSQL database. Error: {:?}",
+        action, err
+    );
+    error!("{}", msg);
+    GeyserPluginError::Custom(Box::new(
+        AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+    ))
+}
+
+/// The checkpoint watermark resolved at the load, against which the slot of the
+/// snapshot is compared at the end of the startup
+pub(crate) struct SnapshotRewind {
+    /// The highest rooted slot stored at the load
+    watermark_slot: u64,
+    action: SnapshotRewindAction,
+    /// The config to connect with for the reconciliation
+    config: AccountsDbPluginPostgresConfig,
+}
+
+impl SnapshotRewind {
+    /// Resolve the checkpoint watermark, None when no slot has been rooted.
+    pub(crate) fn new(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Option<Self>, GeyserPluginError> {
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+        let watermark_slot: Option<i64> = client
+            .query_one(
+                "SELECT MAX(slot) FROM slot WHERE rooted_on IS NOT NULL",
+                &[],
+            )
+            .map_err(|err| to_rewind_error("query the checkpoint watermark", err))?
+            .get(0);
+        Ok(watermark_slot.map(|watermark_slot| Self {
+            watermark_slot: watermark_slot as u64,
+            action: config.snapshot_rewind_action.unwrap_or_default(),
+            config: config.clone(),
+        }))
+    }
+
+    /// Whether the validator restarted from a snapshot older than the watermark
+    fn is_rewound(&self, snapshot_slot: u64) -> bool {
+        snapshot_slot < self.watermark_slot
+    }
+
+    /// Check the slot of the accounts of the snapshot, the highest slot of the accounts
+    /// written during the startup, against the watermark at the end of the startup. A
+    /// rewind is logged and reported as an error datapoint, and the rows of the slots
+    /// after the snapshot are purged when reconciled, before the replay writes them.
+    pub(crate) fn check(&self, snapshot_slot: u64) -> Result<(), GeyserPluginError> {
+        if !self.is_rewound(snapshot_slot) {
+            return Ok(());
+        }
+        datapoint_error!(
+            "accountsdb-plugin-postgres-snapshot-rewind",
+            ("snapshot_slot", snapshot_slot as i64, i64),
+            ("watermark_slot", self.watermark_slot as i64, i64),
+        );
+        if self.action == SnapshotRewindAction::Alert {
+            error!(
+                "The validator restarted from the snapshot of the slot {}, older than the rooted slot {} \
+                already stored, the slots after the snapshot are written again over the rows stored",
+                snapshot_slot, self.watermark_slot
+            );
+            return Ok(());
+        }
+
+        warn!(
+            "The validator restarted from the snapshot of the slot {}, older than the rooted slot {} \
+            already stored, purging the rows of the slots after the snapshot",
+            snapshot_slot, self.watermark_slot
+        );
+        let mut client = SimplePostgresClient::connect_to_db(&self.config)?;
+        let mut transaction = client
+            .transaction()
+            .map_err(|err| to_rewind_error("start the reconciliation", err))?;
+        let snapshot_slot = snapshot_slot as i64;
+        // The slot table goes last, for the replay to write the statuses again
+        for table in FORK_CLEANUP_TABLES.iter().chain(&["slot"]) {
+            let count = transaction
+                .execute(
+                    &format!("DELETE FROM {} WHERE slot > $1", table),
+                    &[&snapshot_slot],
+                )
+                .map_err(|err| to_rewind_error("purge the slots after the snapshot", err))?;
+            if count > 0 {
+                info!(
+                    "Purged {} rows of the slots after {} from the {} table",
+                    count, snapshot_slot, table
+                );
+            }
+        }
+        transaction
+            .commit()
+            .map_err(|err| to_rewind_error("commit the reconciliation", err))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_rewind() {
+        let rewind = SnapshotRewind {
+            watermark_slot: 1000,
+            action: SnapshotRewindAction::Alert,
+            config: serde_json::from_str("{}").unwrap(),
+        };
+        assert!(rewind.is_rewound(999));
+        assert!(!rewind.is_rewound(1000));
+        assert!(!rewind.is_rewound(1001));
+        // The alert does not connect to the database
+        assert!(rewind.check(999).is_ok());
+    }
+}
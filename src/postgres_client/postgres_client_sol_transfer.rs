@@ -0,0 +1,282 @@
+/// Module responsible for extracting the SOL transfers of the System Program
+/// instructions of the transactions into the PostgreSQL database.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        geyser_interface::ReplicaTransactionInfo,
+        postgres_client::{
+            postgres_client_transaction::iter_instructions,
+            postgres_client_write_stats::WriteStats, SimplePostgresClient,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    postgres::{Client, Statement},
+    postgres_types::ToSql,
+    serde::{de::Error, Deserialize as _, Deserializer},
+    serde_derive::{Deserialize, Serialize},
+    solana_program::system_instruction::SystemInstruction,
+    solana_sdk::{instruction::CompiledInstruction, message::AccountKeys},
+    solana_sdk_ids::system_program,
+};
+
+/// The type of a System Program instruction as in the 'jsonParsed' encoding, aliased so
+/// that the deserialization does not borrow it from the input
+type SolTransferKind = &'static str;
+
+/// The types of the System Program instructions moving lamports
+const SOL_TRANSFER_KINDS: [&str; 5] = [
+    "transfer",
+    "transferWithSeed",
+    "createAccount",
+    "createAccountWithSeed",
+    "withdrawFromNonce",
+];
+
+/// A SOL transfer of a System Program instruction, at the top level or invoked by
+/// another program
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DbSolTransfer {
+    pub instruction_index: i16,
+    /// The index among the inner instructions, None for the top level instructions
+    pub inner_instruction_index: Option<i16>,
+    /// The type of the instruction as in the 'jsonParsed' encoding, e.g. "transfer"
+    #[serde(deserialize_with = "deserialize_sol_transfer_kind")]
+    pub kind: SolTransferKind,
+    pub source: Vec<u8>,
+    pub destination: Vec<u8>,
+    pub lamports: i64,
+}
+
+fn deserialize_sol_transfer_kind<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<SolTransferKind, D::Error> {
+    let kind = String::deserialize(deserializer)?;
+    SOL_TRANSFER_KINDS
+        .into_iter()
+        .find(|known_kind| *known_kind == kind)
+        .ok_or_else(|| D::Error::custom(format!("unknown SOL transfer kind {:?}", kind)))
+}
+
+/// Parse the SOL transfer of the instruction, None if it is not a System Program
+/// instruction moving lamports.
+fn parse_sol_transfer(
+    instruction: &CompiledInstruction,
+    account_keys: &AccountKeys,
+) -> Option<(&'static str, Vec<u8>, Vec<u8>, u64)> {
+    if account_keys.get(instruction.program_id_index as usize) != Some(&system_program::ID) {
+        return None;
+    }
+    let account = |position: usize| {
+        instruction
+            .accounts
+            .get(position)
+            .and_then(|index| account_keys.get(*index as usize))
+            .map(|pubkey| pubkey.to_bytes().to_vec())
+    };
+    // The source and the destination positions in the accounts of the instruction
+    let (kind, source, destination, lamports) = match bincode::deserialize(&instruction.data)
+        .ok()?
+    {
+        SystemInstruction::Transfer { lamports } => ("transfer", 0, 1, lamports),
+        SystemInstruction::TransferWithSeed { lamports, .. } => {
+            ("transferWithSeed", 0, 2, lamports)
+        }
+        SystemInstruction::CreateAccount { lamports, .. } => ("createAccount", 0, 1, lamports),
+        SystemInstruction::CreateAccountWithSeed { lamports, .. } => {
+            ("createAccountWithSeed", 0, 1, lamports)
+        }
+        SystemInstruction::WithdrawNonceAccount(lamports) => ("withdrawFromNonce", 0, 1, lamports),
+        _ => return None,
+    };
+    Some((kind, account(source)?, account(destination)?, lamports))
+}
+
+/// Get the SOL transfers of the transaction, none for the failed transactions as
+/// their instructions had no effect.
+pub fn build_sol_transfers(transaction_info: &ReplicaTransactionInfo) -> Vec<DbSolTransfer> {
+    let meta = transaction_info.transaction_status_meta;
+    if meta.status.is_err() {
+        return Vec::default();
+    }
+    let message = transaction_info.transaction.message();
+    let account_keys = message.account_keys();
+
+    let mut sol_transfers = Vec::default();
+    for (instruction_index, inner_instruction_index, instruction) in
+        iter_instructions(transaction_info)
+    {
+        if let Some((kind, source, destination, lamports)) =
+            parse_sol_transfer(instruction, &account_keys)
+        {
+            sol_transfers.push(DbSolTransfer {
+                instruction_index: instruction_index as i16,
+                inner_instruction_index,
+                kind,
+                source,
+                destination,
+                lamports: lamports as i64,
+            });
+        }
+    }
+    sol_transfers
+}
+
+impl SimplePostgresClient {
+    /// Build the statement inserting all the SOL transfers of a transaction into the
+    /// sol_transfer table at once, one row per element of the arrays of the transfers.
+    pub fn build_sol_transfer_insert_statement(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Statement, GeyserPluginError> {
+        let stmt = "INSERT INTO sol_transfer (slot, signature, transfer_index, instruction_index, \
+            inner_instruction_index, kind, source, destination, lamports, updated_on) \
+            SELECT $1, $2, t.transfer_index - 1, t.instruction_index, t.inner_instruction_index, \
+            t.kind, t.source, t.destination, t.lamports, $3 \
+            FROM UNNEST($4::SMALLINT[], $5::SMALLINT[], $6::VARCHAR[], $7::BYTEA[], $8::BYTEA[], $9::BIGINT[]) \
+            WITH ORDINALITY AS t (instruction_index, inner_instruction_index, kind, source, destination, lamports, transfer_index) \
+            ON CONFLICT DO NOTHING";
+
+        let stmt = client.prepare(stmt);
+
+        match stmt {
+            Err(err) => {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "Error in preparing for the SOL transfer insert PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                        err, config.host, config.user, config
+                    ),
+                })))
+            }
+            Ok(stmt) => Ok(stmt),
+        }
+    }
+
+    /// Insert the SOL transfers of the transaction, the signature is in the same
+    /// storage as in the transaction table.
+    pub(crate) fn insert_sol_transfers(
+        client: &mut Client,
+        statement: &Statement,
+        slot: i64,
+        signature: &(dyn ToSql + Sync),
+        sol_transfers: &[DbSolTransfer],
+        write_stats: &WriteStats,
+    ) -> Result<(), GeyserPluginError> {
+        if sol_transfers.is_empty() {
+            return Ok(());
+        }
+        let updated_on = Utc::now().naive_utc();
+        let instruction_indexes: Vec<i16> = sol_transfers
+            .iter()
+            .map(|transfer| transfer.instruction_index)
+            .collect();
+        let inner_instruction_indexes: Vec<Option<i16>> = sol_transfers
+            .iter()
+            .map(|transfer| transfer.inner_instruction_index)
+            .collect();
+        let kinds: Vec<&str> = sol_transfers.iter().map(|transfer| transfer.kind).collect();
+        let sources: Vec<&Vec<u8>> = sol_transfers
+            .iter()
+            .map(|transfer| &transfer.source)
+            .collect();
+        let destinations: Vec<&Vec<u8>> = sol_transfers
+            .iter()
+            .map(|transfer| &transfer.destination)
+            .collect();
+        let lamports: Vec<i64> = sol_transfers
+            .iter()
+            .map(|transfer| transfer.lamports)
+            .collect();
+
+        let params: [&(dyn ToSql + Sync); 9] = [
+            &slot,
+            signature,
+            &updated_on,
+            &instruction_indexes,
+            &inner_instruction_indexes,
+            &kinds,
+            &sources,
+            &destinations,
+            &lamports,
+        ];
+        let result = client.execute(statement, &params);
+
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to persist the SOL transfers to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::TransactionUpdateError { msg });
+        }
+        write_stats.record("sol_transfer", sol_transfers.len(), statement, &params);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::postgres_client_transaction::tests::TestTransaction,
+        solana_sdk::{
+            hash::Hash,
+            pubkey::Pubkey,
+            signature::{Keypair, Signer},
+            transaction::TransactionError,
+        },
+        solana_transaction_status::{InnerInstruction, InnerInstructions},
+    };
+
+    #[test]
+    fn test_build_sol_transfers() {
+        let keypair = Keypair::new();
+        let destination = Pubkey::new_unique();
+        let mut transaction = TestTransaction::new(solana_system_transaction::transfer(
+            &keypair,
+            &destination,
+            42,
+            Hash::default(),
+        ));
+        // An inner transfer back to the payer, as if invoked by another program
+        let inner_instruction =
+            CompiledInstruction::new(2, &SystemInstruction::Transfer { lamports: 7 }, vec![1, 0]);
+        transaction.meta.inner_instructions = Some(vec![InnerInstructions {
+            index: 0,
+            instructions: vec![InnerInstruction {
+                instruction: inner_instruction,
+                stack_height: Some(2),
+            }],
+        }]);
+
+        let payer = keypair.pubkey().to_bytes().to_vec();
+        let destination = destination.to_bytes().to_vec();
+        assert_eq!(
+            build_sol_transfers(&transaction.transaction_info()),
+            vec![
+                DbSolTransfer {
+                    instruction_index: 0,
+                    inner_instruction_index: None,
+                    kind: "transfer",
+                    source: payer.clone(),
+                    destination: destination.clone(),
+                    lamports: 42,
+                },
+                DbSolTransfer {
+                    instruction_index: 0,
+                    inner_instruction_index: Some(0),
+                    kind: "transfer",
+                    source: destination,
+                    destination: payer,
+                    lamports: 7,
+                },
+            ]
+        );
+
+        transaction.meta.status = Err(TransactionError::AccountNotFound);
+        assert!(build_sol_transfers(&transaction.transaction_info()).is_empty());
+    }
+}
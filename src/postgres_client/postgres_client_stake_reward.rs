@@ -0,0 +1,174 @@
+/// Module responsible for storing the staking rewards distributed at the epoch
+/// boundaries, possibly partitioned across the first blocks of the epoch, so that the
+/// rewards of the stake accounts can be queried from the PostgreSQL database instead of
+/// getInflationReward.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::{
+            postgres_client_transaction::{DbReward, DbRewardType},
+            postgres_client_write_stats::WriteStats,
+            SimplePostgresClient,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    postgres::{Client, Statement},
+    postgres_types::ToSql,
+    solana_sdk::pubkey::Pubkey,
+    std::str::FromStr,
+};
+
+/// A staking reward of a stake account in a block
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DbStakeReward {
+    pub pubkey: Vec<u8>,
+    pub lamports: i64,
+    pub post_balance: i64,
+    /// The commission of the vote account the stake is delegated to
+    pub commission: Option<i16>,
+}
+
+/// Get the staking rewards among the rewards of the block.
+pub fn build_stake_rewards(rewards: &[DbReward]) -> Vec<DbStakeReward> {
+    rewards
+        .iter()
+        .filter(|reward| matches!(reward.reward_type, Some(DbRewardType::Staking)))
+        .filter_map(|reward| match Pubkey::from_str(&reward.pubkey) {
+            Ok(pubkey) => Some(DbStakeReward {
+                pubkey: pubkey.to_bytes().to_vec(),
+                lamports: reward.lamports,
+                post_balance: reward.post_balance,
+                commission: reward.commission,
+            }),
+            Err(err) => {
+                warn!(
+                    "Ignored the stake reward of the invalid pubkey {:?}: ({})",
+                    reward.pubkey, err
+                );
+                None
+            }
+        })
+        .collect()
+}
+
+/// The epoch the staking rewards distributed at the slot are earned in, the epoch
+/// before the one of the slot.
+pub(crate) fn stake_reward_epoch(slot: i64, slots_per_epoch: i64) -> i64 {
+    (slot / slots_per_epoch - 1).max(0)
+}
+
+impl SimplePostgresClient {
+    /// Build the statement upserting the staking rewards of a block into the
+    /// stake_reward table, the rewards distributed again on another fork replace the
+    /// ones of the fork before.
+    pub fn build_stake_reward_upsert_statement(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Statement, GeyserPluginError> {
+        let stmt = "INSERT INTO stake_reward (epoch, pubkey, slot, num_partitions, lamports, \
+            post_balance, commission, updated_on) \
+            SELECT $1, t.pubkey, $2, $3, t.lamports, t.post_balance, t.commission, $4 \
+            FROM UNNEST($5::BYTEA[], $6::BIGINT[], $7::BIGINT[], $8::SMALLINT[]) \
+            AS t (pubkey, lamports, post_balance, commission) \
+            ON CONFLICT (epoch, pubkey) DO UPDATE SET slot = excluded.slot, \
+            num_partitions = excluded.num_partitions, lamports = excluded.lamports, \
+            post_balance = excluded.post_balance, commission = excluded.commission, \
+            updated_on = excluded.updated_on";
+
+        let stmt = client.prepare(stmt);
+
+        match stmt {
+            Err(err) => {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "Error in preparing for the stake reward upsert PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                        err, config.host, config.user, config
+                    ),
+                })))
+            }
+            Ok(stmt) => Ok(stmt),
+        }
+    }
+
+    pub(crate) fn upsert_stake_rewards(
+        client: &mut Client,
+        statement: &Statement,
+        epoch: i64,
+        slot: i64,
+        num_partitions: Option<i64>,
+        rewards: &[DbStakeReward],
+        write_stats: &WriteStats,
+    ) -> Result<(), GeyserPluginError> {
+        if rewards.is_empty() {
+            return Ok(());
+        }
+        let updated_on = Utc::now().naive_utc();
+        let pubkeys: Vec<&Vec<u8>> = rewards.iter().map(|reward| &reward.pubkey).collect();
+        let lamports: Vec<i64> = rewards.iter().map(|reward| reward.lamports).collect();
+        let post_balances: Vec<i64> = rewards.iter().map(|reward| reward.post_balance).collect();
+        let commissions: Vec<Option<i16>> =
+            rewards.iter().map(|reward| reward.commission).collect();
+
+        let params: [&(dyn ToSql + Sync); 8] = [
+            &epoch,
+            &slot,
+            &num_partitions,
+            &updated_on,
+            &pubkeys,
+            &lamports,
+            &post_balances,
+            &commissions,
+        ];
+        let result = client.execute(statement, &params);
+
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to persist the stake rewards to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::SlotStatusUpdateError { msg });
+        }
+        write_stats.record("stake_reward", rewards.len(), statement, &params);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_stake_rewards() {
+        let stake_account = Pubkey::new_unique();
+        let reward = |pubkey: String, reward_type| DbReward {
+            pubkey,
+            lamports: 1000,
+            post_balance: 5000,
+            reward_type,
+            commission: Some(7),
+        };
+        let rewards = vec![
+            reward(stake_account.to_string(), Some(DbRewardType::Staking)),
+            reward(Pubkey::new_unique().to_string(), Some(DbRewardType::Voting)),
+            reward(Pubkey::new_unique().to_string(), None),
+            reward("not-a-pubkey".to_string(), Some(DbRewardType::Staking)),
+        ];
+        assert_eq!(
+            build_stake_rewards(&rewards),
+            vec![DbStakeReward {
+                pubkey: stake_account.to_bytes().to_vec(),
+                lamports: 1000,
+                post_balance: 5000,
+                commission: Some(7),
+            }]
+        );
+
+        assert_eq!(stake_reward_epoch(432000 * 700 + 12, 432000), 699);
+        assert_eq!(stake_reward_epoch(12, 432000), 0);
+    }
+}
@@ -0,0 +1,160 @@
+/// Module responsible for holding the loading of the plugin until the database accepts
+/// the connections with the schema in place and the workers are connected, so that a
+/// database starting slightly after the validator does not fail the workers.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::{postgres_client_schema::DEFAULT_CREATE_SCHEMA, SimplePostgresClient},
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    postgres::Client,
+    std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread::{sleep, JoinHandle},
+        time::{Duration, Instant},
+    },
+};
+
+const STARTUP_BARRIER_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The barrier of the startup, open unless the startup wait is configured.
+pub(crate) struct StartupBarrier {
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+}
+
+impl StartupBarrier {
+    pub(crate) fn new(config: &AccountsDbPluginPostgresConfig) -> Self {
+        let timeout = config.startup_wait_secs.map(Duration::from_secs);
+        Self {
+            timeout,
+            deadline: timeout.map(|timeout| Instant::now() + timeout),
+        }
+    }
+
+    fn is_configuration_error(err: &GeyserPluginError) -> bool {
+        matches!(
+            err,
+            GeyserPluginError::Custom(err) if matches!(
+                err.downcast_ref::<AccountsDbPluginPostgresError>(),
+                Some(AccountsDbPluginPostgresError::ConfigurationError { .. })
+            )
+        )
+    }
+
+    /// Check the account table is in place, unless the plugin creates the schema.
+    fn verify_schema(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        if config.create_schema.unwrap_or(DEFAULT_CREATE_SCHEMA) {
+            return Ok(());
+        }
+        let is_created = client
+            .query_one("SELECT to_regclass('account') IS NOT NULL", &[])
+            .map(|row| row.get::<_, bool>(0));
+        match is_created {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "The account table is missing from the schema {:?}",
+                        config.schema
+                    ),
+                },
+            ))),
+            Err(err) => Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "Failed to look up the account table in the PostgreSQL database. Error: {:?}",
+                        err
+                    ),
+                },
+            ))),
+        }
+    }
+
+    /// Wait until a connection to the database is made and the schema is verified,
+    /// retrying until the deadline. The configuration errors are not retried.
+    pub(crate) fn wait_for_db(
+        &self,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        let Some(deadline) = self.deadline else {
+            return Ok(());
+        };
+        loop {
+            let result = SimplePostgresClient::connect_to_db(config)
+                .and_then(|mut client| Self::verify_schema(&mut client, config));
+            match result {
+                Ok(()) => return Ok(()),
+                Err(err) if Instant::now() < deadline && !Self::is_configuration_error(&err) => {
+                    warn!(
+                        "Waiting for the PostgreSQL database at the startup: {}",
+                        err
+                    );
+                    sleep(STARTUP_BARRIER_RETRY_INTERVAL);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Wait until the workers are connected to the database, failing when one of them
+    /// fails to connect or the deadline passes.
+    pub(crate) fn wait_for_workers(
+        &self,
+        workers: &[JoinHandle<Result<(), GeyserPluginError>>],
+        initialized_worker_count: &AtomicUsize,
+    ) -> Result<(), GeyserPluginError> {
+        let (Some(timeout), Some(deadline)) = (self.timeout, self.deadline) else {
+            return Ok(());
+        };
+        while initialized_worker_count.load(Ordering::Relaxed) < workers.len() {
+            let msg = if workers.iter().any(|worker| worker.is_finished()) {
+                "A worker failed to connect to the PostgreSQL database at the startup".to_string()
+            } else if Instant::now() >= deadline {
+                format!(
+                    "The workers failed to connect to the PostgreSQL database within {:?} at the startup",
+                    timeout
+                )
+            } else {
+                sleep(Duration::from_millis(10));
+                continue;
+            };
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_startup_barrier() {
+        let mut config: AccountsDbPluginPostgresConfig =
+            serde_json::from_str("{\"host\": \"127.0.0.1\", \"user\": \"solana\", \"port\": 1}")
+                .unwrap();
+        // The barrier is open unless the startup wait is configured
+        assert!(StartupBarrier::new(&config).wait_for_db(&config).is_ok());
+
+        config.startup_wait_secs = Some(1);
+        let measure = Instant::now();
+        assert!(StartupBarrier::new(&config).wait_for_db(&config).is_err());
+        assert!(measure.elapsed() >= Duration::from_secs(1));
+
+        // The configuration errors are not retried
+        config.host = None;
+        let measure = Instant::now();
+        assert!(StartupBarrier::new(&config).wait_for_db(&config).is_err());
+        assert!(measure.elapsed() < Duration::from_secs(1));
+    }
+}
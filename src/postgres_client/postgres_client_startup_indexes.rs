@@ -0,0 +1,140 @@
+/// Module responsible for the secondary indexes of the account table during the startup,
+/// dropping them before the accounts of the snapshot are ingested and recreating them at
+/// the end of the startup, as maintaining them row by row slows down the bulk loading.
+/// The definitions of the indexes dropped are kept in the startup_dropped_index table, so
+/// that the indexes are recreated after a restart during the startup.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::SimplePostgresClient,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    std::time::Instant,
+};
+
+/// The table whose secondary indexes are managed
+const STARTUP_INDEXES_TABLE: &str = "account";
+
+/// The definition of the index recreating it, the index being left alone when it has
+/// been recreated in the meantime.
+fn recreate_statement(definition: &str) -> String {
+    match definition.strip_prefix("CREATE INDEX ") {
+        Some(definition) => format!("CREATE INDEX IF NOT EXISTS {}", definition),
+        None => definition.to_string(),
+    }
+}
+
+fn to_startup_indexes_error(action: &str, err: postgres::Error) -> GeyserPluginError {
+    let msg = format!(
+        "Failed to {} of the {} table in the PostgreSQL database. Error: {:?}",
+        action, STARTUP_INDEXES_TABLE, err
+    );
+    error!("{}", msg);
+    GeyserPluginError::Custom(Box::new(
+        AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+    ))
+}
+
+impl SimplePostgresClient {
+    /// Drop the secondary indexes of the account table, keeping their definitions. The
+    /// primary key and the unique indexes are kept, as the upserts rely on them.
+    pub(crate) fn drop_startup_indexes(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        let mut client = Self::connect_to_db(config)?;
+        let dropped_on = Utc::now().naive_utc();
+        let mut transaction = client
+            .transaction()
+            .map_err(|err| to_startup_indexes_error("begin dropping the indexes", err))?;
+        let indexes = transaction
+            .query(
+                "SELECT c.relname::TEXT, pg_get_indexdef(i.indexrelid) FROM pg_index i \
+                JOIN pg_class c ON c.oid = i.indexrelid \
+                WHERE i.indrelid = $1::TEXT::regclass AND NOT i.indisprimary AND NOT i.indisunique",
+                &[&STARTUP_INDEXES_TABLE],
+            )
+            .map_err(|err| to_startup_indexes_error("look up the indexes", err))?;
+        for index in &indexes {
+            let name: &str = index.get(0);
+            let definition: &str = index.get(1);
+            transaction
+                .execute(
+                    "INSERT INTO startup_dropped_index (index_name, table_name, definition, dropped_on) \
+                    VALUES ($1, $2, $3, $4) ON CONFLICT (index_name) DO NOTHING",
+                    &[&name, &STARTUP_INDEXES_TABLE, &definition, &dropped_on],
+                )
+                .map_err(|err| to_startup_indexes_error("keep the index definitions", err))?;
+            transaction
+                .batch_execute(&format!("DROP INDEX \"{}\"", name))
+                .map_err(|err| to_startup_indexes_error("drop the indexes", err))?;
+        }
+        transaction
+            .commit()
+            .map_err(|err| to_startup_indexes_error("commit dropping the indexes", err))?;
+        info!(
+            "Dropped the indexes {:?} of the {} table for the startup",
+            indexes
+                .iter()
+                .map(|index| index.get::<_, &str>(0))
+                .collect::<Vec<_>>(),
+            STARTUP_INDEXES_TABLE
+        );
+        Ok(())
+    }
+
+    /// Recreate the indexes dropped for the startup, forgetting each once recreated.
+    pub(crate) fn recreate_startup_indexes(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        let mut client = Self::connect_to_db(config)?;
+        let indexes = client
+            .query(
+                "SELECT index_name::TEXT, definition FROM startup_dropped_index ORDER BY index_name",
+                &[],
+            )
+            .map_err(|err| to_startup_indexes_error("look up the indexes dropped", err))?;
+        for index in indexes {
+            let name: &str = index.get(0);
+            let definition: &str = index.get(1);
+            let measure = Instant::now();
+            client
+                .batch_execute(&recreate_statement(definition))
+                .map_err(|err| to_startup_indexes_error("recreate the indexes", err))?;
+            client
+                .execute(
+                    "DELETE FROM startup_dropped_index WHERE index_name = $1",
+                    &[&name],
+                )
+                .map_err(|err| to_startup_indexes_error("forget the index recreated", err))?;
+            info!(
+                "Recreated the index {} of the {} table in {:?}",
+                name,
+                STARTUP_INDEXES_TABLE,
+                measure.elapsed()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recreate_statement() {
+        assert_eq!(
+            recreate_statement("CREATE INDEX account_owner ON public.account USING btree (owner)"),
+            "CREATE INDEX IF NOT EXISTS account_owner ON public.account USING btree (owner)"
+        );
+        // The definitions other than the plain indexes are kept as is
+        assert_eq!(
+            recreate_statement("CREATE UNIQUE INDEX account_key ON public.account (key)"),
+            "CREATE UNIQUE INDEX account_key ON public.account (key)"
+        );
+    }
+}
@@ -0,0 +1,194 @@
+/// Module responsible for summarizing the accounts loaded during the startup into a
+/// report in the PostgreSQL database.
+use {
+    crate::{
+        accountsdb_plugin_postgres::AccountsDbPluginPostgresError,
+        postgres_client::{DbAccountInfo, SimplePostgresClient},
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::NaiveDateTime,
+    log::*,
+    postgres_types::ToSql,
+    std::collections::HashMap,
+};
+
+/// Insert the report, returning its id
+pub(crate) const INSERT_STARTUP_REPORT: &str = "INSERT INTO startup_report (started_on, finished_on, duration_ms, account_count, failed_account_count) \
+    VALUES ($1, $2, $3, $4, $5) RETURNING report_id";
+
+/// Insert the account counts of the owners of the report, from the arrays of the owners
+/// and of their counts
+pub(crate) const INSERT_STARTUP_REPORT_OWNERS: &str = "INSERT INTO startup_report_owner (report_id, owner, account_count) \
+    SELECT $1, owner, account_count FROM UNNEST($2::BYTEA[], $3::BIGINT[]) AS o (owner, account_count)";
+
+/// The statistics of the accounts loaded during the startup
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StartupStats {
+    pub account_count: u64,
+    /// The accounts failed to be written to the database
+    pub failed_account_count: u64,
+    pub owner_account_counts: HashMap<Vec<u8>, u64>,
+    /// The highest slot of the accounts written, the slot of the snapshot
+    pub highest_slot: Option<u64>,
+}
+
+impl StartupStats {
+    pub fn record_accounts<'a>(&mut self, accounts: impl IntoIterator<Item = &'a DbAccountInfo>) {
+        for account in accounts {
+            self.account_count += 1;
+            self.highest_slot = self.highest_slot.max(Some(account.slot as u64));
+            *self
+                .owner_account_counts
+                .entry(account.owner.clone())
+                .or_default() += 1;
+        }
+    }
+
+    pub fn record_failed_accounts(&mut self, count: usize) {
+        self.failed_account_count += count as u64;
+    }
+
+    pub fn merge(&mut self, other: StartupStats) {
+        self.account_count += other.account_count;
+        self.failed_account_count += other.failed_account_count;
+        self.highest_slot = self.highest_slot.max(other.highest_slot);
+        for (owner, count) in other.owner_account_counts {
+            *self.owner_account_counts.entry(owner).or_default() += count;
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct WriteStartupReportRequest {
+    pub started_on: NaiveDateTime,
+    pub finished_on: NaiveDateTime,
+    pub stats: StartupStats,
+}
+
+/// The values of the rows of a startup report, which the parameters of the inserts of
+/// the report borrow
+pub(crate) struct StartupReportRow {
+    started_on: NaiveDateTime,
+    finished_on: NaiveDateTime,
+    duration_ms: i64,
+    account_count: i64,
+    failed_account_count: i64,
+    owners: Vec<Vec<u8>>,
+    owner_account_counts: Vec<i64>,
+}
+
+impl StartupReportRow {
+    pub(crate) fn new(request: &WriteStartupReportRequest) -> Self {
+        let (owners, owner_account_counts) = request
+            .stats
+            .owner_account_counts
+            .iter()
+            .map(|(owner, count)| (owner.clone(), *count as i64))
+            .unzip();
+        Self {
+            started_on: request.started_on,
+            finished_on: request.finished_on,
+            duration_ms: (request.finished_on - request.started_on).num_milliseconds(),
+            account_count: request.stats.account_count as i64,
+            failed_account_count: request.stats.failed_account_count as i64,
+            owners,
+            owner_account_counts,
+        }
+    }
+
+    /// The parameters of the INSERT_STARTUP_REPORT
+    pub(crate) fn report_params(&self) -> [&(dyn ToSql + Sync); 5] {
+        [
+            &self.started_on,
+            &self.finished_on,
+            &self.duration_ms,
+            &self.account_count,
+            &self.failed_account_count,
+        ]
+    }
+
+    /// The parameters of the INSERT_STARTUP_REPORT_OWNERS of the report inserted
+    pub(crate) fn owner_params<'a>(&'a self, report_id: &'a i64) -> [&'a (dyn ToSql + Sync); 3] {
+        [report_id, &self.owners, &self.owner_account_counts]
+    }
+
+    pub(crate) fn log_written(&self, report_id: i64) {
+        info!(
+            "Wrote the startup report {}: {} accounts of {} owners loaded in {} ms, {} accounts failed",
+            report_id,
+            self.account_count,
+            self.owners.len(),
+            self.duration_ms,
+            self.failed_account_count
+        );
+    }
+}
+
+impl SimplePostgresClient {
+    pub(crate) fn write_startup_report_impl(
+        &mut self,
+        request: &WriteStartupReportRequest,
+    ) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        let to_plugin_error = |err: postgres::Error| {
+            let msg = format!(
+                "Failed to persist the startup report to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+            ))
+        };
+
+        let row = StartupReportRow::new(request);
+        let mut transaction = client.client.transaction().map_err(to_plugin_error)?;
+        let report_id: i64 = transaction
+            .query_one(INSERT_STARTUP_REPORT, &row.report_params())
+            .map_err(to_plugin_error)?
+            .get(0);
+        transaction
+            .execute(INSERT_STARTUP_REPORT_OWNERS, &row.owner_params(&report_id))
+            .map_err(to_plugin_error)?;
+        transaction.commit().map_err(to_plugin_error)?;
+        row.log_written(report_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn db_account_info(owner: u8) -> DbAccountInfo {
+        DbAccountInfo {
+            pubkey: vec![0; 32],
+            lamports: 1,
+            owner: vec![owner; 32],
+            executable: false,
+            rent_epoch: 0,
+            data: vec![],
+            slot: 1,
+            write_version: 0,
+            txn_signature: None,
+        }
+    }
+
+    #[test]
+    fn test_startup_stats() {
+        let mut stats = StartupStats::default();
+        stats.record_accounts(&[db_account_info(1), db_account_info(2)]);
+
+        let mut other_stats = StartupStats::default();
+        other_stats.record_accounts(&[db_account_info(1)]);
+        other_stats.record_failed_accounts(3);
+
+        stats.merge(other_stats);
+        assert_eq!(stats.account_count, 3);
+        assert_eq!(stats.failed_account_count, 3);
+        assert_eq!(stats.owner_account_counts[&vec![1; 32]], 2);
+        assert_eq!(stats.owner_account_counts[&vec![2; 32]], 1);
+        assert_eq!(stats.highest_slot, Some(1));
+        assert_eq!(StartupStats::default().highest_slot, None);
+    }
+}
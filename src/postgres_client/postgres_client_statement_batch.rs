@@ -0,0 +1,464 @@
+/// Module responsible for grouping the account, transaction and slot statements of the
+/// work items queued together to a worker into a single database transaction, so that
+/// the server flushes its write-ahead log once per batch instead of once per statement.
+/// The batches are sized per kind of update, and optionally wait for the updates to
+/// fill them up to a max delay. With the minimal footprint, the updates of an account
+/// within a batch are coalesced into its latest one.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError, BatchLimitConfig,
+            Footprint,
+        },
+        postgres_client::{
+            abort, CommitSlotRequest, DbWorkItem, PostgresClient, PostgresClientWorker,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    crossbeam_channel::Receiver,
+    log::*,
+    solana_metrics::*,
+    std::{
+        collections::{HashMap, HashSet},
+        sync::atomic::AtomicBool,
+        time::{Duration, Instant},
+    },
+};
+
+/// The size and the max delay of the batches of a kind of update
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct BatchLimit {
+    size: usize,
+    /// The longest a batch waits for the updates to fill it, from its first update
+    max_delay: Duration,
+}
+
+/// The limits of the batches per kind of update, the one of the first update of a
+/// batch capping its size
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct StatementBatchLimits {
+    account: BatchLimit,
+    transaction: BatchLimit,
+    slot: BatchLimit,
+    /// Indicates if to coalesce the updates of an account within a batch
+    coalesce_accounts: bool,
+}
+
+impl StatementBatchLimits {
+    /// The limits when the statements are batched, the kinds of updates without their
+    /// own limit are batched up to the statement_batch_size without waiting.
+    pub(crate) fn new(config: &AccountsDbPluginPostgresConfig) -> Option<Self> {
+        if config.statement_batch_size.is_none() && config.batch.is_none() {
+            return None;
+        }
+        let default_size = config.statement_batch_size.unwrap_or(1);
+        let batch = config.batch.clone().unwrap_or_default();
+        let limit = |limit: Option<BatchLimitConfig>| {
+            let limit = limit.unwrap_or_default();
+            BatchLimit {
+                size: limit.size.unwrap_or(default_size),
+                max_delay: Duration::from_millis(limit.max_delay_ms.unwrap_or_default()),
+            }
+        };
+        Some(Self {
+            account: limit(batch.account),
+            transaction: limit(batch.transaction),
+            slot: limit(batch.slot),
+            coalesce_accounts: config.footprint.unwrap_or_default() == Footprint::Minimal,
+        })
+    }
+
+    fn limit(&self, work: &DbWorkItem) -> &BatchLimit {
+        match work {
+            DbWorkItem::UpdateAccount(_) => &self.account,
+            DbWorkItem::LogTransaction(_) => &self.transaction,
+            _ => &self.slot,
+        }
+    }
+}
+
+/// Split the account updates superseded by a later version of the same account off the
+/// batch, keeping the order of the updates left. Returns the batch left along with the
+/// updates superseded.
+fn coalesce_account_updates(batch: Vec<DbWorkItem>) -> (Vec<DbWorkItem>, Vec<DbWorkItem>) {
+    let version = |work: &DbWorkItem| match work {
+        DbWorkItem::UpdateAccount(request) => Some((
+            request.account.pubkey.clone(),
+            (request.account.slot, request.account.write_version),
+        )),
+        _ => None,
+    };
+    let mut latest = HashMap::new();
+    for (pubkey, version) in batch.iter().filter_map(version) {
+        let latest = latest.entry(pubkey).or_insert(version);
+        *latest = (*latest).max(version);
+    }
+    let mut kept = HashSet::new();
+    batch.into_iter().partition(|work| match version(work) {
+        Some((pubkey, version)) => latest[&pubkey] == version && kept.insert(pubkey),
+        None => true,
+    })
+}
+
+/// Receive the next update queued, waiting for it until the deadline.
+fn receive_until(receiver: &Receiver<DbWorkItem>, deadline: Instant) -> Option<DbWorkItem> {
+    match deadline.checked_duration_since(Instant::now()) {
+        Some(timeout) if !timeout.is_zero() => receiver.recv_timeout(timeout).ok(),
+        _ => receiver.try_recv().ok(),
+    }
+}
+
+impl DbWorkItem {
+    /// Whether the work item is written within the statement batches. The accounts of
+    /// the startup are left out, they are already written by the bulk inserts whose
+    /// buffered rows could not be written again once rolled back.
+    pub(crate) fn is_batchable(&self) -> bool {
+        match self {
+            DbWorkItem::UpdateAccount(request) => !request.is_startup,
+            DbWorkItem::UpdateSlot(_) | DbWorkItem::LogTransaction(_) => true,
+            DbWorkItem::UpdateBlockMetadata(_)
+            | DbWorkItem::LogVoteActivity(_)
+            | DbWorkItem::UpdateEntry(_)
+            | DbWorkItem::VerifyBlockChain(_)
+            | DbWorkItem::WriteStartupReport(_)
+            | DbWorkItem::CommitSlot(_)
+            | DbWorkItem::CleanUpForks(_)
+            | DbWorkItem::WriteEpochBarrier(_) => false,
+        }
+    }
+}
+
+impl PostgresClientWorker {
+    /// Execute a transaction control statement on the connection of the worker.
+    fn execute_transaction_control(&mut self, statement: &str) -> Result<(), GeyserPluginError> {
+        let client = self.client.client.get_mut().unwrap();
+        client.client.batch_execute(statement).map_err(|err| {
+            GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::DataStoreConnectionError {
+                    msg: format!(
+                        "Failed to {} the statement batch in the PostgreSQL database. Error: {:?}",
+                        statement, err
+                    ),
+                },
+            ))
+        })
+    }
+
+    /// Write the statements in one database transaction, rolled back on the first
+    /// failure. The notifications and the post commit hooks of the transaction are held
+    /// until it is committed.
+    fn commit_in_transaction(
+        &mut self,
+        write: impl FnOnce(&mut Self) -> Result<(), GeyserPluginError>,
+    ) -> Result<(), GeyserPluginError> {
+        self.execute_transaction_control("BEGIN")?;
+        let client = self.client.client.get_mut().unwrap();
+        client.write_stats.begin_batch();
+        client.notifier.begin_batch();
+        client.post_commit_hooks.begin_batch();
+        let result = write(self).and_then(|()| self.execute_transaction_control("COMMIT"));
+        let client = self.client.client.get_mut().unwrap();
+        if result.is_ok() {
+            client.write_stats.commit_batch();
+        } else {
+            client.write_stats.discard_batch();
+            client.notifier.discard_batch();
+            client.post_commit_hooks.discard_batch();
+            // The rollback fails along with the connection dropped, which is handled
+            // when the items are written again.
+            let _ = self.execute_transaction_control("ROLLBACK");
+            self.client.forget_statement_timeout();
+            self.client.forget_audit_skip();
+        }
+        result
+    }
+
+    /// Write the batch in one database transaction, rolled back on the first failure.
+    fn commit_batch(&mut self, batch: &[DbWorkItem]) -> Result<(), GeyserPluginError> {
+        self.commit_in_transaction(|worker| {
+            batch.iter().try_for_each(|work| worker.write_item(work))
+        })
+    }
+
+    /// Write the transactions of the slot in chunks of the slot_commit_chunk_size, one
+    /// database transaction each, the slot_commit row being written within the one of
+    /// the final chunk. When a chunk fails, the slot written again writes the chunks
+    /// committed already again, their transactions being upserted.
+    pub(crate) fn write_slot_commit(
+        &mut self,
+        request: &CommitSlotRequest,
+    ) -> Result<(), GeyserPluginError> {
+        let chunks = request.chunks(self.slot_commit_chunk_size);
+        let chunk_count = chunks.len();
+        for (chunk_index, chunk) in chunks.into_iter().enumerate() {
+            self.commit_in_transaction(|worker| {
+                for transaction in chunk {
+                    worker.client.log_transaction(transaction)?;
+                }
+                if chunk_index + 1 == chunk_count {
+                    worker.client.write_slot_commit_marker(
+                        request.slot,
+                        request.transactions.len(),
+                        chunk_count,
+                    )?;
+                }
+                Ok(())
+            })?;
+            self.notify_batch_committed()?;
+        }
+        Ok(())
+    }
+
+    /// Send the notifications and execute the post commit hooks of the batch committed.
+    fn notify_batch_committed(&mut self) -> Result<(), GeyserPluginError> {
+        let client = self.client.client.get_mut().unwrap();
+        client.notifier.commit_batch(&mut client.client)?;
+        client.post_commit_hooks.commit_batch(&mut client.client)
+    }
+
+    /// Write the work item along with the batchable ones queued behind it, up to the
+    /// batch size of the kind of the work item, in one database transaction. The batch
+    /// waits for the updates up to the shortest max delay of the kinds of its updates.
+    /// When the batch fails, its items are written again one by one, subject to the
+    /// reconnection and panic_on_db_errors. Returns the item received ending the batch,
+    /// to be written on its own.
+    pub(crate) fn write_batch(
+        &mut self,
+        work: DbWorkItem,
+        limits: &StatementBatchLimits,
+        receiver: &Receiver<DbWorkItem>,
+        exit_worker: &AtomicBool,
+        panic_on_db_errors: bool,
+    ) -> Option<DbWorkItem> {
+        // The items buffered while reconnecting are written ahead of the batch
+        self.write_pending(receiver, exit_worker, panic_on_db_errors);
+
+        let batch_started = Instant::now();
+        let limit = limits.limit(&work);
+        let batch_size = limit.size;
+        let mut deadline = batch_started + limit.max_delay;
+        let mut batch = vec![work];
+        let mut next = None;
+        while batch.len() < batch_size {
+            match receive_until(receiver, deadline) {
+                Some(work) if work.is_batchable() => {
+                    deadline = deadline.min(batch_started + limits.limit(&work).max_delay);
+                    batch.push(work);
+                }
+                Some(work) => {
+                    next = Some(work);
+                    break;
+                }
+                None => break,
+            }
+        }
+        if limits.coalesce_accounts {
+            let coalesced;
+            (batch, coalesced) = coalesce_account_updates(batch);
+            if !coalesced.is_empty() {
+                inc_new_counter_info!(
+                    "accountsdb-plugin-postgres-accounts-coalesced",
+                    coalesced.len()
+                );
+            }
+        }
+        if batch.len() == 1 {
+            let work = batch.pop().unwrap();
+            self.write_with_reconnect(work, receiver, exit_worker, panic_on_db_errors);
+            return next;
+        }
+
+        let started = Instant::now();
+        match self.commit_batch(&batch) {
+            Ok(()) => {
+                let batch_len = batch.len();
+                if let Some(metrics) = &self.metrics {
+                    metrics.record_write_duration(started.elapsed());
+                }
+                for work in batch {
+                    self.record_committed(work);
+                }
+                // The batch is committed already, it is not written again
+                if let Err(err) = self.notify_batch_committed() {
+                    error!(
+                        "Failed to notify the batch of {} updates committed: ({})",
+                        batch_len, err
+                    );
+                    if panic_on_db_errors {
+                        abort();
+                    }
+                }
+            }
+            Err(err) => {
+                warn!(
+                    "Failed to write the batch of {} updates in one transaction, writing them one by one: ({})",
+                    batch.len(),
+                    err
+                );
+                for work in batch {
+                    self.write_with_reconnect(work, receiver, exit_worker, panic_on_db_errors);
+                }
+            }
+        }
+        next
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            DbAccountInfo, UpdateAccountRequest, UpdateSlotRequest, VerifyBlockChainRequest,
+        },
+        agave_geyser_plugin_interface::geyser_plugin_interface::SlotStatus,
+    };
+
+    #[test]
+    fn test_statement_batch_limits() {
+        let limits = |config: serde_json::Value| {
+            StatementBatchLimits::new(&serde_json::from_value(config).unwrap())
+        };
+        assert_eq!(limits(serde_json::json!({})), None);
+
+        let limits = limits(serde_json::json!({
+            "statement_batch_size": 50,
+            "batch": {
+                "account": {"size": 500, "max_delay_ms": 20},
+                "slot": {"max_delay_ms": 0}
+            }
+        }))
+        .unwrap();
+        assert_eq!(
+            limits.account,
+            BatchLimit {
+                size: 500,
+                max_delay: Duration::from_millis(20)
+            }
+        );
+        assert_eq!(
+            limits.transaction,
+            BatchLimit {
+                size: 50,
+                max_delay: Duration::ZERO
+            }
+        );
+        assert_eq!(limits.slot, limits.transaction);
+        assert!(!limits.coalesce_accounts);
+    }
+
+    #[test]
+    fn test_coalesce_account_updates() {
+        let account = |pubkey, slot, write_version| {
+            DbWorkItem::UpdateAccount(Box::new(UpdateAccountRequest {
+                account: DbAccountInfo {
+                    pubkey: vec![pubkey; 32],
+                    lamports: 1,
+                    owner: vec![2; 32],
+                    executable: false,
+                    rent_epoch: 0,
+                    data: Vec::default(),
+                    slot,
+                    write_version,
+                    txn_signature: None,
+                },
+                is_startup: false,
+            }))
+        };
+        let version = |work: &DbWorkItem| match work {
+            DbWorkItem::UpdateAccount(request) => (
+                request.account.pubkey[0],
+                request.account.slot,
+                request.account.write_version,
+            ),
+            _ => (0, 0, 0),
+        };
+        let batch = vec![
+            account(1, 10, 1),
+            account(2, 10, 2),
+            DbWorkItem::UpdateSlot(Box::new(UpdateSlotRequest {
+                slot: 10,
+                parent: None,
+                slot_status: SlotStatus::Processed,
+            })),
+            account(1, 11, 3),
+            // Queued out of order behind a later version
+            account(2, 9, 4),
+            account(1, 11, 3),
+        ];
+        let (batch, coalesced) = coalesce_account_updates(batch);
+        assert_eq!(
+            batch.iter().map(version).collect::<Vec<_>>(),
+            vec![(2, 10, 2), (0, 0, 0), (1, 11, 3)]
+        );
+        assert_eq!(
+            coalesced.iter().map(version).collect::<Vec<_>>(),
+            vec![(1, 10, 1), (2, 9, 4), (1, 11, 3)]
+        );
+    }
+
+    #[test]
+    fn test_receive_until() {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let slot = |slot| {
+            DbWorkItem::UpdateSlot(Box::new(UpdateSlotRequest {
+                slot,
+                parent: None,
+                slot_status: SlotStatus::Processed,
+            }))
+        };
+        assert!(receive_until(&receiver, Instant::now()).is_none());
+        sender.send(slot(1)).unwrap();
+        assert!(receive_until(&receiver, Instant::now()).is_some());
+
+        let started = Instant::now();
+        let delayed_sender = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            sender.send(slot(2)).unwrap();
+        });
+        assert!(receive_until(&receiver, started + Duration::from_secs(5)).is_some());
+        assert!(started.elapsed() < Duration::from_secs(5));
+        delayed_sender.join().unwrap();
+    }
+
+    #[test]
+    fn test_is_batchable() {
+        let account = |is_startup| {
+            DbWorkItem::UpdateAccount(Box::new(UpdateAccountRequest {
+                account: DbAccountInfo {
+                    pubkey: vec![1; 32],
+                    lamports: 1,
+                    owner: vec![2; 32],
+                    executable: false,
+                    rent_epoch: 0,
+                    data: Vec::default(),
+                    slot: 1,
+                    write_version: 1,
+                    txn_signature: None,
+                },
+                is_startup,
+            }))
+        };
+        assert!(account(false).is_batchable());
+        assert!(!account(true).is_batchable());
+        assert!(DbWorkItem::UpdateSlot(Box::new(UpdateSlotRequest {
+            slot: 1,
+            parent: None,
+            slot_status: SlotStatus::Rooted,
+        }))
+        .is_batchable());
+        assert!(
+            !DbWorkItem::VerifyBlockChain(Box::new(VerifyBlockChainRequest {
+                from_slot: 0,
+                to_slot: 1,
+            }))
+            .is_batchable()
+        );
+        // The transactions of a slot are committed in their own chunks
+        assert!(!DbWorkItem::CommitSlot(Box::new(CommitSlotRequest {
+            slot: 1,
+            transactions: vec![],
+        }))
+        .is_batchable());
+    }
+}
@@ -0,0 +1,162 @@
+/// Module responsible for the statement timeouts of the writes, overridden per table, so
+/// that the bulky writes such as the account_audit inserts can be given a longer timeout
+/// than the latency sensitive slot updates. The timeout of the session is set before the
+/// write of a work item when it differs from the one the session is set to.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::{DbWorkItem, SimplePostgresClient},
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    std::collections::BTreeMap,
+};
+
+/// The tables whose writes can be given their own statement timeout
+pub(crate) const STATEMENT_TIMEOUT_TABLES: [&str; 7] = [
+    "account",
+    "account_audit",
+    "slot",
+    "transaction",
+    "block",
+    "entry",
+    "vote_activity",
+];
+
+/// The statement timeouts of the writes of a connection
+#[derive(Debug)]
+pub(crate) struct StatementTimeouts {
+    default_ms: Option<u64>,
+    table_ms: BTreeMap<String, u64>,
+    /// The timeout the session is set to, None when it is unknown, such as on a new
+    /// connection or after the rollback of a statement batch
+    current_ms: Option<Option<u64>>,
+}
+
+impl StatementTimeouts {
+    pub(crate) fn new(config: &AccountsDbPluginPostgresConfig) -> Self {
+        Self {
+            default_ms: config.statement_timeout_ms,
+            table_ms: config
+                .table_statement_timeouts_ms
+                .clone()
+                .unwrap_or_default(),
+            current_ms: None,
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.default_ms.is_some() || !self.table_ms.is_empty()
+    }
+
+    /// The timeout of the writes to the tables, the longest of their timeouts, None
+    /// for the default timeout of the session.
+    fn timeout_ms(&self, tables: &[&str]) -> Option<u64> {
+        tables
+            .iter()
+            .filter_map(|table| self.table_ms.get(*table).copied().or(self.default_ms))
+            .max()
+            .or(self.default_ms)
+    }
+}
+
+impl DbWorkItem {
+    /// Get the tables written by the work item whose statement timeouts apply. The
+    /// account updates copy the versions replaced into the account_audit table.
+    pub(crate) fn statement_timeout_tables(&self) -> &'static [&'static str] {
+        match self {
+            DbWorkItem::UpdateAccount(_) => &["account", "account_audit"],
+            DbWorkItem::UpdateSlot(_) => &["slot"],
+            DbWorkItem::LogTransaction(_) | DbWorkItem::CommitSlot(_) => &["transaction"],
+            DbWorkItem::UpdateBlockMetadata(_) => &["block"],
+            DbWorkItem::LogVoteActivity(_) => &["vote_activity"],
+            DbWorkItem::UpdateEntry(_) => &["entry"],
+            DbWorkItem::VerifyBlockChain(_)
+            | DbWorkItem::WriteStartupReport(_)
+            | DbWorkItem::CleanUpForks(_)
+            | DbWorkItem::WriteEpochBarrier(_) => &[],
+        }
+    }
+}
+
+impl SimplePostgresClient {
+    /// Set the statement timeout of the session to the one of the writes to the tables,
+    /// when the statement timeouts are configured and it is not already set to it.
+    pub(crate) fn apply_statement_timeout(
+        &mut self,
+        tables: &[&str],
+    ) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        let timeouts = &client.statement_timeouts;
+        if !timeouts.is_enabled() {
+            return Ok(());
+        }
+        let timeout_ms = timeouts.timeout_ms(tables);
+        if timeouts.current_ms == Some(timeout_ms) {
+            return Ok(());
+        }
+        let statement = match timeout_ms {
+            Some(timeout_ms) => format!("SET statement_timeout = {}", timeout_ms),
+            None => "SET statement_timeout TO DEFAULT".to_string(),
+        };
+        if let Err(err) = client.client.batch_execute(&statement) {
+            let msg = format!(
+                "Failed to set the statement timeout in the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+            )));
+        }
+        client.statement_timeouts.current_ms = Some(timeout_ms);
+        Ok(())
+    }
+
+    /// Forget the statement timeout the session is set to, after the rollback of a
+    /// statement batch reverting the ones set within it.
+    pub(crate) fn forget_statement_timeout(&mut self) {
+        self.client.get_mut().unwrap().statement_timeouts.current_ms = None;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statement_timeouts() {
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str("{}").unwrap();
+        assert!(!StatementTimeouts::new(&config).is_enabled());
+
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str(
+            "{\"table_statement_timeouts_ms\": {\"account_audit\": 60000, \"slot\": 500}}",
+        )
+        .unwrap();
+        let timeouts = StatementTimeouts::new(&config);
+        assert!(timeouts.is_enabled());
+        assert_eq!(timeouts.timeout_ms(&["slot"]), Some(500));
+        assert_eq!(
+            timeouts.timeout_ms(&["account", "account_audit"]),
+            Some(60000)
+        );
+        assert_eq!(timeouts.timeout_ms(&["transaction"]), None);
+        assert_eq!(timeouts.timeout_ms(&[]), None);
+
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str(
+            "{\"statement_timeout_ms\": 5000, \"table_statement_timeouts_ms\": {\"slot\": 500, \"account\": 1000}}",
+        )
+        .unwrap();
+        let timeouts = StatementTimeouts::new(&config);
+        assert_eq!(timeouts.timeout_ms(&["slot"]), Some(500));
+        // The account updates take the default timeout of the account_audit inserts
+        assert_eq!(
+            timeouts.timeout_ms(&["account", "account_audit"]),
+            Some(5000)
+        );
+        assert_eq!(timeouts.timeout_ms(&["block"]), Some(5000));
+        assert_eq!(timeouts.timeout_ms(&[]), Some(5000));
+    }
+}
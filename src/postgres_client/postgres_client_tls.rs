@@ -0,0 +1,113 @@
+/// Module responsible for building the TLS connector of the connections, verifying the
+/// server certificate and its hostname as configured by the ssl_mode, such as for the
+/// managed PostgreSQL instances requiring verify-full connections.
+use {
+    crate::accountsdb_plugin_postgres::{
+        AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError, SslMode,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    openssl::ssl::{SslConnector, SslFiletype, SslMethod, SslVerifyMode},
+    postgres_openssl::MakeTlsConnector,
+};
+
+impl SslMode {
+    /// Get the mode of the config, the use_ssl set without a mode verifies the server
+    /// certificate without its hostname.
+    pub(crate) fn from_config(config: &AccountsDbPluginPostgresConfig) -> Self {
+        config.ssl_mode.unwrap_or(match config.use_ssl {
+            Some(true) => SslMode::VerifyCa,
+            _ => SslMode::Disable,
+        })
+    }
+}
+
+fn configuration_error(msg: String) -> GeyserPluginError {
+    GeyserPluginError::Custom(Box::new(
+        AccountsDbPluginPostgresError::ConfigurationError { msg },
+    ))
+}
+
+/// Build the TLS connector for the mode, none when the TLS is disabled. The server
+/// certificate is verified against the server_ca when set, and against the default
+/// trusted certificates of the system otherwise.
+pub(crate) fn build_tls_connector(
+    config: &AccountsDbPluginPostgresConfig,
+) -> Result<Option<MakeTlsConnector>, GeyserPluginError> {
+    let ssl_mode = SslMode::from_config(config);
+    if ssl_mode == SslMode::Disable {
+        return Ok(None);
+    }
+
+    let mut builder = SslConnector::builder(SslMethod::tls()).unwrap();
+    if ssl_mode == SslMode::Require {
+        builder.set_verify(SslVerifyMode::NONE);
+    }
+    if let Some(server_ca) = &config.server_ca {
+        if let Err(err) = builder.set_ca_file(server_ca) {
+            return Err(configuration_error(format!(
+                "Failed to set the server certificate specified by \"server_ca\": {}. Error: ({})",
+                server_ca, err
+            )));
+        }
+    }
+    match (&config.client_cert, &config.client_key) {
+        (Some(client_cert), Some(client_key)) => {
+            if let Err(err) = builder.set_certificate_file(client_cert, SslFiletype::PEM) {
+                return Err(configuration_error(format!(
+                    "Failed to set the client certificate specified by \"client_cert\": {}. Error: ({})",
+                    client_cert, err
+                )));
+            }
+            if let Err(err) = builder.set_private_key_file(client_key, SslFiletype::PEM) {
+                return Err(configuration_error(format!(
+                    "Failed to set the client key specified by \"client_key\": {}. Error: ({})",
+                    client_key, err
+                )));
+            }
+        }
+        (None, None) => {}
+        (Some(_), None) => {
+            return Err(configuration_error(
+                "\"client_key\" must be specified along with \"client_cert\"".to_string(),
+            ));
+        }
+        (None, Some(_)) => {
+            return Err(configuration_error(
+                "\"client_cert\" must be specified along with \"client_key\"".to_string(),
+            ));
+        }
+    }
+
+    let mut connector = MakeTlsConnector::new(builder.build());
+    let verify_hostname = ssl_mode == SslMode::VerifyFull;
+    connector.set_callback(move |connect_config, _domain| {
+        connect_config.set_verify_hostname(verify_hostname);
+        Ok(())
+    });
+    Ok(Some(connector))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ssl_mode() {
+        let ssl_mode = |config: &str| {
+            let config: AccountsDbPluginPostgresConfig = serde_json::from_str(config).unwrap();
+            SslMode::from_config(&config)
+        };
+        assert_eq!(ssl_mode("{}"), SslMode::Disable);
+        assert_eq!(ssl_mode("{\"use_ssl\": true}"), SslMode::VerifyCa);
+        assert_eq!(
+            ssl_mode("{\"use_ssl\": true, \"ssl_mode\": \"verify-full\"}"),
+            SslMode::VerifyFull
+        );
+        assert_eq!(ssl_mode("{\"ssl_mode\": \"require\"}"), SslMode::Require);
+
+        let config: AccountsDbPluginPostgresConfig =
+            serde_json::from_str("{\"ssl_mode\": \"require\", \"client_cert\": \"cert.pem\"}")
+                .unwrap();
+        assert!(build_tls_connector(&config).is_err());
+    }
+}
@@ -0,0 +1,741 @@
+/// Module responsible for decoding the SPL Token and Token-2022 accounts and mints into
+/// the spl_token_account and spl_token_mint tables, so that the balances, owners and
+/// supplies of the tokens can be queried without decoding the account data in SQL.
+/// Each row records the version of the decoding, and the rows decoded by an earlier
+/// version are decoded again from the raw accounts on demand.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountLayout, AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+            TokenAccountStorage,
+        },
+        postgres_client::{
+            decompress_account_data, is_connection_dropped,
+            postgres_client_dedup_window::DedupWindow, DbAccountInfo, SimplePostgresClient,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    postgres::{Client, Statement},
+    postgres_types::{FromSql, ToSql},
+    solana_account_decoder::parse_token::is_known_spl_token_id,
+    solana_sdk::pubkey::{Pubkey, PUBKEY_BYTES},
+};
+
+/// The length of the token accounts, the Token-2022 accounts with extensions are longer
+const TOKEN_ACCOUNT_LENGTH: usize = 165;
+/// The length of the mints, the Token-2022 mints with extensions are padded to the
+/// length of the token accounts before their account type
+const TOKEN_MINT_LENGTH: usize = 82;
+/// The length of the multisig accounts, which have no account type
+const TOKEN_MULTISIG_LENGTH: usize = 355;
+/// The account types of the Token-2022 accounts with extensions, following the base
+/// state padded to the length of the token accounts
+const ACCOUNT_TYPE_MINT: u8 = 1;
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+
+/// The version of the decoding of the token accounts and mints, recorded along with
+/// each row. It is bumped whenever the decoded fields change, so that the rows decoded
+/// by the earlier versions can be told apart and decoded again.
+pub const TOKEN_DECODER_VERSION: i16 = 1;
+/// The raw accounts of the rows decoded by an earlier version read per query
+const TOKEN_REDECODE_BATCH_SIZE: i64 = 1000;
+
+/// The outcome of decoding again the rows decoded by an earlier version
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TokenRedecodeReport {
+    /// The token accounts and mints decoded again from their raw accounts
+    pub redecoded: u64,
+    /// The rows still decoded by an earlier version, whose raw accounts are not stored
+    /// or are no longer decoded as token accounts or mints
+    pub remaining: u64,
+}
+
+#[derive(Clone, Copy, Debug, FromSql, ToSql, PartialEq, Eq)]
+#[postgres(name = "TokenAccountState")]
+pub enum DbTokenAccountState {
+    Initialized,
+    Frozen,
+}
+
+/// A token account decoded from its account data
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DbTokenAccount {
+    pub pubkey: Vec<u8>,
+    pub program_id: Vec<u8>,
+    pub mint: Vec<u8>,
+    pub owner: Vec<u8>,
+    pub amount: u64,
+    pub delegate: Option<Vec<u8>>,
+    pub state: DbTokenAccountState,
+    /// The rent-exempt reserve of the wrapped SOL accounts
+    pub is_native: Option<i64>,
+    pub delegated_amount: u64,
+    pub close_authority: Option<Vec<u8>>,
+    pub slot: i64,
+    pub write_version: i64,
+}
+
+/// A mint decoded from its account data
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DbTokenMint {
+    pub pubkey: Vec<u8>,
+    pub program_id: Vec<u8>,
+    pub mint_authority: Option<Vec<u8>>,
+    pub supply: u64,
+    pub decimals: i16,
+    pub freeze_authority: Option<Vec<u8>>,
+    pub slot: i64,
+    pub write_version: i64,
+}
+
+/// The state of an account of the token programs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DbToken {
+    Account(DbTokenAccount),
+    Mint(DbTokenMint),
+}
+
+/// A reader of the little endian fields of the token states
+struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    fn take(&mut self, len: usize) -> &'a [u8] {
+        let (field, rest) = self.data.split_at(len);
+        self.data = rest;
+        field
+    }
+
+    fn pubkey(&mut self) -> Vec<u8> {
+        self.take(PUBKEY_BYTES).to_vec()
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.take(1)[0]
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.take(8).try_into().unwrap())
+    }
+
+    /// Read a COption, a 4 byte tag followed by the value present or not
+    fn option<T>(&mut self, read: impl FnOnce(&mut Self) -> T) -> Option<T> {
+        let is_some = self.take(4)[0] == 1;
+        let value = read(self);
+        is_some.then_some(value)
+    }
+}
+
+/// Decode the initialized token account or mint, None for the other accounts, including
+/// the multisig accounts of the token programs.
+pub fn decode_token_account(account: &DbAccountInfo) -> Option<DbToken> {
+    let program_id = Pubkey::try_from(account.owner.as_slice()).ok()?;
+    if !is_known_spl_token_id(&program_id) {
+        return None;
+    }
+    let data = &account.data;
+    let account_type = match data.len() {
+        TOKEN_ACCOUNT_LENGTH => ACCOUNT_TYPE_ACCOUNT,
+        TOKEN_MINT_LENGTH => ACCOUNT_TYPE_MINT,
+        TOKEN_MULTISIG_LENGTH => return None,
+        len if len > TOKEN_ACCOUNT_LENGTH => data[TOKEN_ACCOUNT_LENGTH],
+        _ => return None,
+    };
+    let mut reader = StateReader { data };
+    match account_type {
+        ACCOUNT_TYPE_ACCOUNT => {
+            let mint = reader.pubkey();
+            let owner = reader.pubkey();
+            let amount = reader.u64();
+            let delegate = reader.option(StateReader::pubkey);
+            let state = match reader.u8() {
+                1 => DbTokenAccountState::Initialized,
+                2 => DbTokenAccountState::Frozen,
+                _ => return None,
+            };
+            Some(DbToken::Account(DbTokenAccount {
+                pubkey: account.pubkey.clone(),
+                program_id: account.owner.clone(),
+                mint,
+                owner,
+                amount,
+                delegate,
+                state,
+                is_native: reader.option(StateReader::u64).map(|amount| amount as i64),
+                delegated_amount: reader.u64(),
+                close_authority: reader.option(StateReader::pubkey),
+                slot: account.slot,
+                write_version: account.write_version,
+            }))
+        }
+        ACCOUNT_TYPE_MINT => {
+            let mint_authority = reader.option(StateReader::pubkey);
+            let supply = reader.u64();
+            let decimals = reader.u8() as i16;
+            if reader.u8() != 1 {
+                return None;
+            }
+            Some(DbToken::Mint(DbTokenMint {
+                pubkey: account.pubkey.clone(),
+                program_id: account.owner.clone(),
+                mint_authority,
+                supply,
+                decimals,
+                freeze_authority: reader.option(StateReader::pubkey),
+                slot: account.slot,
+                write_version: account.write_version,
+            }))
+        }
+        _ => None,
+    }
+}
+
+fn prepare_statement(
+    client: &mut Client,
+    config: &AccountsDbPluginPostgresConfig,
+    stmt: &str,
+    description: &str,
+) -> Result<Statement, GeyserPluginError> {
+    match client.prepare(stmt) {
+        Err(err) => {
+            Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                msg: format!(
+                    "Error in preparing for the {} PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                    description, err, config.host, config.user, config
+                ),
+            })))
+        }
+        Ok(stmt) => Ok(stmt),
+    }
+}
+
+/// The condition of the conflict handling of the token tables, the states are updated
+/// by the newer versions of the accounts, or by the same versions decoded by a newer
+/// version of the decoding.
+fn newer_version_condition(config: &AccountsDbPluginPostgresConfig) -> String {
+    format!(
+        "t.slot < excluded.slot OR (t.slot = excluded.slot AND t.write_version < excluded.write_version){} \
+        OR (t.slot = excluded.slot AND t.write_version = excluded.write_version AND t.decoder_version < excluded.decoder_version)",
+        DedupWindow::outside_condition(config, "excluded.slot")
+            .map(|condition| format!(" AND {}", condition))
+            .unwrap_or_default()
+    )
+}
+
+impl SimplePostgresClient {
+    /// Build the statement upserting the token accounts into the spl_token_account
+    /// table, the amounts being passed as text for the NUMERIC columns. The latest
+    /// version of each account is kept when an account is repeated.
+    pub fn build_token_account_upsert_statement(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Statement, GeyserPluginError> {
+        let stmt = format!(
+            "INSERT INTO spl_token_account AS t (pubkey, program_id, mint, owner, amount, delegate, \
+            state, is_native, delegated_amount, close_authority, slot, write_version, decoder_version, updated_on) \
+            SELECT DISTINCT ON (u.pubkey) u.pubkey, u.program_id, u.mint, u.owner, u.amount::NUMERIC(20), \
+            u.delegate, u.state, u.is_native, u.delegated_amount::NUMERIC(20), u.close_authority, \
+            u.slot, u.write_version, {}, $1 \
+            FROM UNNEST($2::BYTEA[], $3::BYTEA[], $4::BYTEA[], $5::BYTEA[], $6::TEXT[], $7::BYTEA[], \
+            $8::\"TokenAccountState\"[], $9::BIGINT[], $10::TEXT[], $11::BYTEA[], $12::BIGINT[], $13::BIGINT[]) \
+            AS u (pubkey, program_id, mint, owner, amount, delegate, state, is_native, delegated_amount, \
+            close_authority, slot, write_version) \
+            ORDER BY u.pubkey, u.slot DESC, u.write_version DESC \
+            ON CONFLICT (pubkey) DO UPDATE SET program_id=excluded.program_id, mint=excluded.mint, \
+            owner=excluded.owner, amount=excluded.amount, delegate=excluded.delegate, state=excluded.state, \
+            is_native=excluded.is_native, delegated_amount=excluded.delegated_amount, \
+            close_authority=excluded.close_authority, slot=excluded.slot, \
+            write_version=excluded.write_version, decoder_version=excluded.decoder_version, \
+            updated_on=excluded.updated_on WHERE {}",
+            TOKEN_DECODER_VERSION,
+            newer_version_condition(config)
+        );
+        prepare_statement(client, config, &stmt, "token account upsert")
+    }
+
+    /// Build the statement upserting the mints into the spl_token_mint table.
+    pub fn build_token_mint_upsert_statement(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Statement, GeyserPluginError> {
+        let stmt = format!(
+            "INSERT INTO spl_token_mint AS t (pubkey, program_id, mint_authority, supply, decimals, \
+            freeze_authority, slot, write_version, decoder_version, updated_on) \
+            SELECT DISTINCT ON (u.pubkey) u.pubkey, u.program_id, u.mint_authority, u.supply::NUMERIC(20), \
+            u.decimals, u.freeze_authority, u.slot, u.write_version, {}, $1 \
+            FROM UNNEST($2::BYTEA[], $3::BYTEA[], $4::BYTEA[], $5::TEXT[], $6::SMALLINT[], $7::BYTEA[], \
+            $8::BIGINT[], $9::BIGINT[]) \
+            AS u (pubkey, program_id, mint_authority, supply, decimals, freeze_authority, slot, write_version) \
+            ORDER BY u.pubkey, u.slot DESC, u.write_version DESC \
+            ON CONFLICT (pubkey) DO UPDATE SET program_id=excluded.program_id, \
+            mint_authority=excluded.mint_authority, supply=excluded.supply, decimals=excluded.decimals, \
+            freeze_authority=excluded.freeze_authority, slot=excluded.slot, \
+            write_version=excluded.write_version, decoder_version=excluded.decoder_version, \
+            updated_on=excluded.updated_on WHERE {}",
+            TOKEN_DECODER_VERSION,
+            newer_version_condition(config)
+        );
+        prepare_statement(client, config, &stmt, "token mint upsert")
+    }
+
+    /// Build the statement deleting the token account or mint of a closed account.
+    pub fn build_token_delete_statement(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Statement, GeyserPluginError> {
+        let stmt = "WITH deleted_account AS (DELETE FROM spl_token_account WHERE pubkey = $1 AND slot <= $2) \
+            DELETE FROM spl_token_mint WHERE pubkey = $1 AND slot <= $2";
+        prepare_statement(client, config, stmt, "token delete")
+    }
+
+    fn upsert_token_accounts(
+        &mut self,
+        token_accounts: &[DbTokenAccount],
+    ) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        let Some(statement) = &client.upsert_token_account_stmt else {
+            return Ok(());
+        };
+        let updated_on = Utc::now().naive_utc();
+        let pubkeys: Vec<&Vec<u8>> = token_accounts.iter().map(|token| &token.pubkey).collect();
+        let program_ids: Vec<&Vec<u8>> = token_accounts
+            .iter()
+            .map(|token| &token.program_id)
+            .collect();
+        let mints: Vec<&Vec<u8>> = token_accounts.iter().map(|token| &token.mint).collect();
+        let owners: Vec<&Vec<u8>> = token_accounts.iter().map(|token| &token.owner).collect();
+        let amounts: Vec<String> = token_accounts
+            .iter()
+            .map(|token| token.amount.to_string())
+            .collect();
+        let delegates: Vec<Option<&Vec<u8>>> = token_accounts
+            .iter()
+            .map(|token| token.delegate.as_ref())
+            .collect();
+        let states: Vec<DbTokenAccountState> =
+            token_accounts.iter().map(|token| token.state).collect();
+        let is_natives: Vec<Option<i64>> =
+            token_accounts.iter().map(|token| token.is_native).collect();
+        let delegated_amounts: Vec<String> = token_accounts
+            .iter()
+            .map(|token| token.delegated_amount.to_string())
+            .collect();
+        let close_authorities: Vec<Option<&Vec<u8>>> = token_accounts
+            .iter()
+            .map(|token| token.close_authority.as_ref())
+            .collect();
+        let slots: Vec<i64> = token_accounts.iter().map(|token| token.slot).collect();
+        let write_versions: Vec<i64> = token_accounts
+            .iter()
+            .map(|token| token.write_version)
+            .collect();
+
+        let params: [&(dyn ToSql + Sync); 13] = [
+            &updated_on,
+            &pubkeys,
+            &program_ids,
+            &mints,
+            &owners,
+            &amounts,
+            &delegates,
+            &states,
+            &is_natives,
+            &delegated_amounts,
+            &close_authorities,
+            &slots,
+            &write_versions,
+        ];
+        if let Err(err) = client.client.execute(statement, &params) {
+            let msg = format!(
+                "Failed to persist the token accounts to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::AccountsUpdateError { msg });
+        }
+        client.write_stats.record(
+            "spl_token_account",
+            token_accounts.len(),
+            statement,
+            &params,
+        );
+        Ok(())
+    }
+
+    fn upsert_token_mints(&mut self, token_mints: &[DbTokenMint]) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        let Some(statement) = &client.upsert_token_mint_stmt else {
+            return Ok(());
+        };
+        let updated_on = Utc::now().naive_utc();
+        let pubkeys: Vec<&Vec<u8>> = token_mints.iter().map(|mint| &mint.pubkey).collect();
+        let program_ids: Vec<&Vec<u8>> = token_mints.iter().map(|mint| &mint.program_id).collect();
+        let mint_authorities: Vec<Option<&Vec<u8>>> = token_mints
+            .iter()
+            .map(|mint| mint.mint_authority.as_ref())
+            .collect();
+        let supplies: Vec<String> = token_mints
+            .iter()
+            .map(|mint| mint.supply.to_string())
+            .collect();
+        let decimals: Vec<i16> = token_mints.iter().map(|mint| mint.decimals).collect();
+        let freeze_authorities: Vec<Option<&Vec<u8>>> = token_mints
+            .iter()
+            .map(|mint| mint.freeze_authority.as_ref())
+            .collect();
+        let slots: Vec<i64> = token_mints.iter().map(|mint| mint.slot).collect();
+        let write_versions: Vec<i64> = token_mints.iter().map(|mint| mint.write_version).collect();
+
+        let params: [&(dyn ToSql + Sync); 9] = [
+            &updated_on,
+            &pubkeys,
+            &program_ids,
+            &mint_authorities,
+            &supplies,
+            &decimals,
+            &freeze_authorities,
+            &slots,
+            &write_versions,
+        ];
+        if let Err(err) = client.client.execute(statement, &params) {
+            let msg = format!(
+                "Failed to persist the token mints to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::AccountsUpdateError { msg });
+        }
+        client
+            .write_stats
+            .record("spl_token_mint", token_mints.len(), statement, &params);
+        Ok(())
+    }
+
+    fn delete_token(&mut self, account: &DbAccountInfo) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        let Some(statement) = &client.delete_token_stmt else {
+            return Ok(());
+        };
+        if let Err(err) = client
+            .client
+            .execute(statement, &[&account.pubkey, &account.slot])
+        {
+            let msg = format!(
+                "Failed to delete the token account of the closed account from the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::AccountsUpdateError { msg });
+        }
+        Ok(())
+    }
+
+    /// Write the token account or mint decoded from the account, batched during the
+    /// startup, and delete the ones of the closed accounts. Returns whether the account
+    /// is to be written into the account table too.
+    pub(crate) fn write_token_account(
+        &mut self,
+        account: &DbAccountInfo,
+        is_startup: bool,
+    ) -> Result<bool, GeyserPluginError> {
+        if self.token_account_storage == TokenAccountStorage::Raw {
+            return Ok(true);
+        }
+        let keep_raw = matches!(
+            self.token_account_storage,
+            TokenAccountStorage::Both | TokenAccountStorage::Mints
+        );
+        match decode_token_account(account) {
+            // Only the mints are decoded along with the raw accounts
+            Some(DbToken::Account(_))
+                if self.token_account_storage == TokenAccountStorage::Mints => {}
+            Some(DbToken::Account(token_account)) if is_startup => {
+                self.pending_token_accounts.push(token_account);
+                self.flush_token_accounts(self.batch_size)?;
+            }
+            Some(DbToken::Mint(token_mint)) if is_startup => {
+                self.pending_token_mints.push(token_mint);
+                self.flush_token_accounts(self.batch_size)?;
+            }
+            Some(DbToken::Account(token_account)) => {
+                self.upsert_token_accounts(&[token_account])?;
+            }
+            Some(DbToken::Mint(token_mint)) => self.upsert_token_mints(&[token_mint])?,
+            None => {
+                if account.lamports == 0 && !is_startup {
+                    self.delete_token(account)?;
+                }
+                return Ok(true);
+            }
+        }
+        Ok(keep_raw)
+    }
+
+    /// Write the token accounts and mints batched during the startup once there are at
+    /// least `min_count` of them, all of them with a `min_count` of 1. The batches are
+    /// kept when the connection is dropped and the writes are retried, the repeated
+    /// accounts being deduplicated by the statements.
+    pub(crate) fn flush_token_accounts(
+        &mut self,
+        min_count: usize,
+    ) -> Result<(), GeyserPluginError> {
+        if self.pending_token_accounts.len() >= min_count {
+            let token_accounts = std::mem::take(&mut self.pending_token_accounts);
+            if let Err(err) = self.upsert_token_accounts(&token_accounts) {
+                self.keep_token_batch_if_retried(|client| {
+                    client.pending_token_accounts = token_accounts
+                });
+                return Err(err);
+            }
+        }
+        if self.pending_token_mints.len() >= min_count {
+            let token_mints = std::mem::take(&mut self.pending_token_mints);
+            if let Err(err) = self.upsert_token_mints(&token_mints) {
+                self.keep_token_batch_if_retried(|client| client.pending_token_mints = token_mints);
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn keep_token_batch_if_retried(&mut self, keep: impl FnOnce(&mut Self)) {
+        if self.retry_on_reconnect
+            && is_connection_dropped(&mut self.client.get_mut().unwrap().client)
+        {
+            keep(self);
+        }
+    }
+
+    /// Decode again the token accounts and mints decoded by an earlier version of the
+    /// decoding, from their raw accounts in the account table, in batches ordered by
+    /// the pubkeys. The rows without a raw account are left as they are.
+    pub fn redecode_token_accounts(&mut self) -> Result<TokenRedecodeReport, GeyserPluginError> {
+        if self.token_account_storage == TokenAccountStorage::Raw {
+            return Err(GeyserPluginError::ConfigFileReadError {
+                msg: "The token accounts are not decoded with the \"token_account_storage\" 'raw'"
+                    .to_string(),
+            });
+        }
+        let stale_rows = "SELECT pubkey FROM spl_token_account WHERE decoder_version < $1 \
+            UNION ALL SELECT pubkey FROM spl_token_mint WHERE decoder_version < $1";
+        // The data of the split layout is in the account_data table
+        let (data, data_join) = match self.account_layout {
+            AccountLayout::Wide => ("a.data, a.data_codec", ""),
+            AccountLayout::Split => (
+                "d.data, d.data_codec",
+                " JOIN account_data d USING (pubkey)",
+            ),
+        };
+        let select_accounts = format!(
+            "SELECT a.pubkey, a.lamports, a.owner, a.executable, a.rent_epoch, {}, a.slot, a.write_version \
+            FROM account a{} JOIN ({}) stale USING (pubkey) WHERE a.pubkey > $2 ORDER BY a.pubkey LIMIT $3",
+            data, data_join, stale_rows
+        );
+        let count_remaining = format!("SELECT count(*) FROM ({}) stale", stale_rows);
+        let to_error = |err: postgres::Error| {
+            let msg = format!(
+                "Failed to query the token accounts decoded by an earlier version from the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            GeyserPluginError::AccountsUpdateError { msg }
+        };
+
+        let mut report = TokenRedecodeReport::default();
+        let mut last_pubkey = Vec::default();
+        loop {
+            let rows = self
+                .client
+                .get_mut()
+                .unwrap()
+                .client
+                .query(
+                    &select_accounts,
+                    &[
+                        &TOKEN_DECODER_VERSION,
+                        &last_pubkey,
+                        &TOKEN_REDECODE_BATCH_SIZE,
+                    ],
+                )
+                .map_err(to_error)?;
+            let Some(last_row) = rows.last() else {
+                break;
+            };
+            last_pubkey = last_row.get(0);
+
+            let mut token_accounts = Vec::default();
+            let mut token_mints = Vec::default();
+            for row in &rows {
+                let data = decompress_account_data(row.get(5), row.get(6)).map_err(|err| {
+                    GeyserPluginError::AccountsUpdateError {
+                        msg: format!("Failed to decompress the account data. Error: {:?}", err),
+                    }
+                })?;
+                let account = DbAccountInfo {
+                    pubkey: row.get(0),
+                    lamports: row.get(1),
+                    owner: row.get(2),
+                    executable: row.get(3),
+                    rent_epoch: row.get(4),
+                    data,
+                    slot: row.get(7),
+                    write_version: row.get(8),
+                    txn_signature: None,
+                };
+                match decode_token_account(&account) {
+                    Some(DbToken::Account(token_account)) => token_accounts.push(token_account),
+                    Some(DbToken::Mint(token_mint)) => token_mints.push(token_mint),
+                    None => {}
+                }
+            }
+            self.upsert_token_accounts(&token_accounts)?;
+            self.upsert_token_mints(&token_mints)?;
+            report.redecoded += (token_accounts.len() + token_mints.len()) as u64;
+            info!(
+                "Decoded again {} token accounts and mints up to the pubkey {}",
+                report.redecoded,
+                bs58::encode(&last_pubkey).into_string()
+            );
+        }
+
+        let remaining: i64 = self
+            .client
+            .get_mut()
+            .unwrap()
+            .client
+            .query_one(&count_remaining, &[&TOKEN_DECODER_VERSION])
+            .map_err(to_error)?
+            .get(0);
+        report.remaining = remaining as u64;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, solana_account_decoder::parse_token::spl_token_ids};
+
+    fn token_program_account(program_id: &Pubkey, data: Vec<u8>) -> DbAccountInfo {
+        DbAccountInfo {
+            pubkey: Pubkey::new_unique().to_bytes().to_vec(),
+            lamports: 2039280,
+            owner: program_id.to_bytes().to_vec(),
+            executable: false,
+            rent_epoch: 0,
+            data,
+            slot: 10,
+            write_version: 3,
+            txn_signature: None,
+        }
+    }
+
+    fn coption(value: Option<&[u8]>, len: usize) -> Vec<u8> {
+        match value {
+            Some(value) => [&[1, 0, 0, 0], value].concat(),
+            None => vec![0; 4 + len],
+        }
+    }
+
+    #[test]
+    fn test_decode_token_account() {
+        let mint = Pubkey::new_unique().to_bytes();
+        let owner = Pubkey::new_unique().to_bytes();
+        let delegate = Pubkey::new_unique().to_bytes();
+        let data = [
+            &mint[..],
+            &owner,
+            &u64::MAX.to_le_bytes(),
+            &coption(Some(&delegate), 32),
+            &[2],
+            &coption(Some(&2039280u64.to_le_bytes()), 8),
+            &7u64.to_le_bytes(),
+            &coption(None, 32),
+        ]
+        .concat();
+        assert_eq!(data.len(), TOKEN_ACCOUNT_LENGTH);
+
+        for program_id in spl_token_ids() {
+            let account = token_program_account(&program_id, data.clone());
+            assert_eq!(
+                decode_token_account(&account),
+                Some(DbToken::Account(DbTokenAccount {
+                    pubkey: account.pubkey.clone(),
+                    program_id: program_id.to_bytes().to_vec(),
+                    mint: mint.to_vec(),
+                    owner: owner.to_vec(),
+                    amount: u64::MAX,
+                    delegate: Some(delegate.to_vec()),
+                    state: DbTokenAccountState::Frozen,
+                    is_native: Some(2039280),
+                    delegated_amount: 7,
+                    close_authority: None,
+                    slot: 10,
+                    write_version: 3,
+                }))
+            );
+        }
+
+        // A Token-2022 account with extensions
+        let token_2022 = spl_token_ids()[1];
+        let mut extended = data.clone();
+        extended.extend([ACCOUNT_TYPE_ACCOUNT, 7, 0, 0, 0]);
+        let account = token_program_account(&token_2022, extended);
+        assert!(matches!(
+            decode_token_account(&account),
+            Some(DbToken::Account(_))
+        ));
+
+        // The uninitialized accounts and the accounts of the other programs are left out
+        let mut uninitialized = data.clone();
+        uninitialized[108] = 0;
+        let account = token_program_account(&token_2022, uninitialized);
+        assert_eq!(decode_token_account(&account), None);
+        let account = token_program_account(&Pubkey::new_unique(), data);
+        assert_eq!(decode_token_account(&account), None);
+    }
+
+    #[test]
+    fn test_decode_token_mint() {
+        let mint_authority = Pubkey::new_unique().to_bytes();
+        let data = [
+            &coption(Some(&mint_authority), 32)[..],
+            &1_000_000u64.to_le_bytes(),
+            &[6, 1],
+            &coption(None, 32),
+        ]
+        .concat();
+        assert_eq!(data.len(), TOKEN_MINT_LENGTH);
+        let expected_mint = |account: &DbAccountInfo| {
+            Some(DbToken::Mint(DbTokenMint {
+                pubkey: account.pubkey.clone(),
+                program_id: account.owner.clone(),
+                mint_authority: Some(mint_authority.to_vec()),
+                supply: 1_000_000,
+                decimals: 6,
+                freeze_authority: None,
+                slot: 10,
+                write_version: 3,
+            }))
+        };
+
+        let account = token_program_account(&spl_token_ids()[0], data.clone());
+        assert_eq!(decode_token_account(&account), expected_mint(&account));
+
+        // A Token-2022 mint with extensions is padded to the length of the accounts
+        let mut extended = data.clone();
+        extended.resize(TOKEN_ACCOUNT_LENGTH, 0);
+        extended.extend([ACCOUNT_TYPE_MINT, 3, 0, 0, 0]);
+        let account = token_program_account(&spl_token_ids()[1], extended);
+        assert_eq!(decode_token_account(&account), expected_mint(&account));
+
+        // The multisig accounts have no account type
+        let account = token_program_account(
+            &spl_token_ids()[1],
+            vec![ACCOUNT_TYPE_MINT; TOKEN_MULTISIG_LENGTH],
+        );
+        assert_eq!(decode_token_account(&account), None);
+    }
+}
@@ -0,0 +1,280 @@
+/// Module responsible for the secondary indexes of the token accounts, the
+/// spl_token_owner_index and spl_token_mint_index tables mapping the owners and the
+/// mints to the token accounts of the SPL Token and Token-2022 programs, like the
+/// spl-token-owner and spl-token-mint account indexes of the RPC nodes. The indexes are
+/// maintained from the account updates whatever the token_account_storage, each token
+/// account having a row keyed by its pubkey, moved to the new owner on a change of the
+/// owner and deleted once the account is closed.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError, TokenSecondaryIndex,
+        },
+        postgres_client::{
+            decode_token_account, postgres_client_dedup_window::DedupWindow, DbAccountInfo,
+            DbToken, SimplePostgresClient,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    postgres::{Client, Statement},
+    postgres_types::ToSql,
+};
+
+/// The entry of a token account in the secondary indexes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct DbTokenIndexEntry {
+    account_key: Vec<u8>,
+    owner_key: Vec<u8>,
+    mint_key: Vec<u8>,
+    slot: i64,
+    write_version: i64,
+}
+
+/// The statements maintaining the secondary indexes configured
+pub(crate) struct TokenIndexStatements {
+    upsert_owner_index_stmt: Option<Statement>,
+    upsert_mint_index_stmt: Option<Statement>,
+    delete_stmt: Statement,
+}
+
+fn prepare_statement(
+    client: &mut Client,
+    config: &AccountsDbPluginPostgresConfig,
+    stmt: &str,
+    description: &str,
+) -> Result<Statement, GeyserPluginError> {
+    match client.prepare(stmt) {
+        Err(err) => {
+            Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                msg: format!(
+                    "Error in preparing for the {} PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                    description, err, config.host, config.user, config
+                ),
+            })))
+        }
+        Ok(stmt) => Ok(stmt),
+    }
+}
+
+/// The statement upserting the entries of the token accounts into the index table of
+/// the key column, the latest version of each account being kept when an account is
+/// repeated. In the dedup window the write versions of a slot already stored are
+/// assigned again by the replay after a restart, the entries of the slot are kept.
+fn index_upsert_sql(config: &AccountsDbPluginPostgresConfig, table: &str, key: &str) -> String {
+    format!(
+        "INSERT INTO {table} AS t ({key}, account_key, slot, write_version) \
+        SELECT DISTINCT ON (u.account_key) u.{key}, u.account_key, u.slot, u.write_version \
+        FROM UNNEST($1::BYTEA[], $2::BYTEA[], $3::BIGINT[], $4::BIGINT[]) \
+        AS u ({key}, account_key, slot, write_version) \
+        ORDER BY u.account_key, u.slot DESC, u.write_version DESC \
+        ON CONFLICT (account_key) DO UPDATE SET {key}=excluded.{key}, slot=excluded.slot, \
+        write_version=excluded.write_version \
+        WHERE t.slot < excluded.slot OR (t.slot = excluded.slot AND t.write_version < excluded.write_version{})",
+        DedupWindow::outside_condition(config, "excluded.slot")
+            .map(|condition| format!(" AND {}", condition))
+            .unwrap_or_default()
+    )
+}
+
+impl SimplePostgresClient {
+    /// Build the statements maintaining the secondary indexes of the token accounts,
+    /// when any is configured.
+    pub(crate) fn build_token_index_statements(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Option<TokenIndexStatements>, GeyserPluginError> {
+        let indexes = config
+            .token_secondary_indexes
+            .as_deref()
+            .unwrap_or_default();
+        if indexes.is_empty() {
+            return Ok(None);
+        }
+        let upsert_owner_index_stmt = if indexes.contains(&TokenSecondaryIndex::SplTokenOwner) {
+            let stmt = index_upsert_sql(config, "spl_token_owner_index", "owner_key");
+            Some(prepare_statement(
+                client,
+                config,
+                &stmt,
+                "spl_token_owner_index upsert",
+            )?)
+        } else {
+            None
+        };
+        let upsert_mint_index_stmt = if indexes.contains(&TokenSecondaryIndex::SplTokenMint) {
+            let stmt = index_upsert_sql(config, "spl_token_mint_index", "mint_key");
+            Some(prepare_statement(
+                client,
+                config,
+                &stmt,
+                "spl_token_mint_index upsert",
+            )?)
+        } else {
+            None
+        };
+        // Both tables are created by the schema, the delete is the same for any of them
+        let delete_stmt = prepare_statement(
+            client,
+            config,
+            "WITH deleted_owner_index AS (DELETE FROM spl_token_owner_index WHERE account_key = $1 AND slot <= $2) \
+            DELETE FROM spl_token_mint_index WHERE account_key = $1 AND slot <= $2",
+            "token index delete",
+        )?;
+        Ok(Some(TokenIndexStatements {
+            upsert_owner_index_stmt,
+            upsert_mint_index_stmt,
+            delete_stmt,
+        }))
+    }
+
+    fn upsert_token_index_entries(
+        &mut self,
+        entries: &[DbTokenIndexEntry],
+    ) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        let Some(statements) = &client.token_index_stmts else {
+            return Ok(());
+        };
+        let account_keys: Vec<&Vec<u8>> = entries.iter().map(|entry| &entry.account_key).collect();
+        let slots: Vec<i64> = entries.iter().map(|entry| entry.slot).collect();
+        let write_versions: Vec<i64> = entries.iter().map(|entry| entry.write_version).collect();
+        let owner_keys: Vec<&Vec<u8>> = entries.iter().map(|entry| &entry.owner_key).collect();
+        let mint_keys: Vec<&Vec<u8>> = entries.iter().map(|entry| &entry.mint_key).collect();
+        for (table, statement, keys) in [
+            (
+                "spl_token_owner_index",
+                &statements.upsert_owner_index_stmt,
+                &owner_keys,
+            ),
+            (
+                "spl_token_mint_index",
+                &statements.upsert_mint_index_stmt,
+                &mint_keys,
+            ),
+        ] {
+            let Some(statement) = statement else {
+                continue;
+            };
+            let params: [&(dyn ToSql + Sync); 4] = [keys, &account_keys, &slots, &write_versions];
+            if let Err(err) = client.client.execute(statement, &params) {
+                let msg = format!(
+                    "Failed to persist the {} entries to the PostgreSQL database. Error: {:?}",
+                    table, err
+                );
+                error!("{}", msg);
+                return Err(GeyserPluginError::AccountsUpdateError { msg });
+            }
+            client
+                .write_stats
+                .record(table, entries.len(), statement, &params);
+        }
+        Ok(())
+    }
+
+    fn delete_token_index_entries(
+        &mut self,
+        account: &DbAccountInfo,
+    ) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        let Some(statements) = &client.token_index_stmts else {
+            return Ok(());
+        };
+        if let Err(err) = client
+            .client
+            .execute(&statements.delete_stmt, &[&account.pubkey, &account.slot])
+        {
+            let msg = format!(
+                "Failed to delete the token index entries of the closed account from the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::AccountsUpdateError { msg });
+        }
+        Ok(())
+    }
+
+    /// Write the entries of the token account into the secondary indexes configured,
+    /// batched during the startup, and delete the ones of the closed accounts.
+    pub(crate) fn write_token_index(
+        &mut self,
+        account: &DbAccountInfo,
+        is_startup: bool,
+    ) -> Result<(), GeyserPluginError> {
+        if self.client.get_mut().unwrap().token_index_stmts.is_none() {
+            return Ok(());
+        }
+        match decode_token_account(account) {
+            Some(DbToken::Account(token_account)) => {
+                let entry = DbTokenIndexEntry {
+                    account_key: token_account.pubkey,
+                    owner_key: token_account.owner,
+                    mint_key: token_account.mint,
+                    slot: token_account.slot,
+                    write_version: token_account.write_version,
+                };
+                if is_startup {
+                    self.pending_token_index_entries.push(entry);
+                    self.flush_token_index_entries(self.batch_size)?;
+                } else {
+                    self.upsert_token_index_entries(&[entry])?;
+                }
+            }
+            Some(DbToken::Mint(_)) => {}
+            None => {
+                if account.lamports == 0 && !is_startup {
+                    self.delete_token_index_entries(account)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the index entries batched during the startup once there are at least
+    /// `min_count` of them, all of them with a `min_count` of 1. The batch is kept when
+    /// the connection is dropped and the writes are retried.
+    pub(crate) fn flush_token_index_entries(
+        &mut self,
+        min_count: usize,
+    ) -> Result<(), GeyserPluginError> {
+        if self.pending_token_index_entries.is_empty()
+            || self.pending_token_index_entries.len() < min_count
+        {
+            return Ok(());
+        }
+        let entries = std::mem::take(&mut self.pending_token_index_entries);
+        if let Err(err) = self.upsert_token_index_entries(&entries) {
+            self.keep_token_batch_if_retried(|client| client.pending_token_index_entries = entries);
+            return Err(err);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_upsert_sql() {
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str("{}").unwrap();
+        let sql = index_upsert_sql(&config, "spl_token_owner_index", "owner_key");
+        assert!(sql.starts_with(
+            "INSERT INTO spl_token_owner_index AS t (owner_key, account_key, slot, write_version) \
+            SELECT DISTINCT ON (u.account_key) u.owner_key,"
+        ));
+        assert!(
+            sql.contains("ON CONFLICT (account_key) DO UPDATE SET owner_key=excluded.owner_key,")
+        );
+        assert!(sql.ends_with("t.write_version < excluded.write_version)"));
+
+        let mut config = config;
+        config.dedup_window_slots = Some(10);
+        config.dedup_until_slot = Some(100);
+        let sql = index_upsert_sql(&config, "spl_token_mint_index", "mint_key");
+        assert!(sql.contains("SET mint_key=excluded.mint_key,"));
+        assert!(sql.ends_with(
+            "t.write_version < excluded.write_version AND excluded.slot NOT BETWEEN 91 AND 100)"
+        ));
+    }
+}
@@ -3,20 +3,36 @@
 use {
     crate::{
         accountsdb_plugin_postgres::{
-            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError, SignatureStorage,
+        },
+        geyser_interface::ReplicaTransactionInfo,
+        postgres_client::{
+            postgres_client_analyze::AnalyzeTable,
+            postgres_client_hypertable::is_timescaledb,
+            postgres_client_metrics::MetricStream,
+            postgres_client_sol_transfer::{build_sol_transfers, DbSolTransfer},
+            postgres_client_transaction_instruction::{
+                build_transaction_instructions, DbTransactionInstruction,
+            },
+            postgres_client_transaction_memo::{build_transaction_memos, DbTransactionMemo},
+            postgres_client_transaction_meta::{
+                build_account_balances, build_token_balance_changes, DbAccountBalance,
+                DbTokenBalanceChange,
+            },
+            DbWorkItem, ParallelPostgresClient, SimplePostgresClient, StaticColumns,
+            DEFAULT_STORE_NORMALIZED_TRANSACTION_META, DEFAULT_STORE_SOL_TRANSFERS,
+            DEFAULT_STORE_TRANSACTION_JSON, DEFAULT_STORE_TRANSACTION_MEMOS,
         },
-        postgres_client::{DbWorkItem, ParallelPostgresClient, SimplePostgresClient},
-    },
-    agave_geyser_plugin_interface::geyser_plugin_interface::{
-        GeyserPluginError, ReplicaTransactionInfoV2,
     },
-    chrono::Utc,
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::{NaiveDateTime, Utc},
     log::*,
     postgres::{Client, Statement},
     postgres_types::{FromSql, ToSql},
-    solana_runtime::bank::RewardType,
+    serde_derive::{Deserialize, Serialize},
+    serde_json::json,
     solana_sdk::{
-        instruction::CompiledInstruction,
+        instruction::{CompiledInstruction, InstructionError},
         message::{
             v0::{self, LoadedAddresses, MessageAddressTableLookup},
             Message, MessageHeader, SanitizedMessage,
@@ -24,14 +40,19 @@ use {
         transaction::TransactionError,
     },
     solana_transaction_status::{
-        InnerInstructions, Reward, TransactionStatusMeta, TransactionTokenBalance,
+        EncodedConfirmedTransactionWithStatusMeta, EncodedTransactionWithStatusMeta,
+        InnerInstructions, Reward, RewardType, TransactionStatusMeta, TransactionTokenBalance,
+        UiTransactionEncoding, VersionedTransactionWithStatusMeta,
     },
     bs58,
 };
 
 const MAX_TRANSACTION_STATUS_LEN: usize = 256;
+/// The highest transaction version encoded, as the RPC requests with
+/// `maxSupportedTransactionVersion: 0`
+const MAX_SUPPORTED_TRANSACTION_VERSION: Option<u8> = Some(0);
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize, Deserialize)]
 #[postgres(name = "CompiledInstruction")]
 pub struct DbCompiledInstruction {
     pub program_id_index: i16,
@@ -39,14 +60,14 @@ pub struct DbCompiledInstruction {
     pub data: Vec<u8>,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize, Deserialize)]
 #[postgres(name = "InnerInstructions")]
 pub struct DbInnerInstructions {
     pub index: i16,
     pub instructions: Vec<DbCompiledInstruction>,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize, Deserialize)]
 #[postgres(name = "TransactionTokenBalance")]
 pub struct DbTransactionTokenBalance {
     pub account_index: i16,
@@ -55,7 +76,7 @@ pub struct DbTransactionTokenBalance {
     pub owner: String,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql, PartialEq)]
+#[derive(Clone, Debug, FromSql, ToSql, PartialEq, Serialize, Deserialize)]
 #[postgres(name = "RewardType")]
 pub enum DbRewardType {
     Fee,
@@ -64,7 +85,7 @@ pub enum DbRewardType {
     Voting,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize, Deserialize)]
 #[postgres(name = "Reward")]
 pub struct DbReward {
     pub pubkey: String,
@@ -74,7 +95,7 @@ pub struct DbReward {
     pub commission: Option<i16>,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize, Deserialize)]
 #[postgres(name = "TransactionStatusMeta")]
 pub struct DbTransactionStatusMeta {
     pub error: Option<DbTransactionError>,
@@ -88,7 +109,7 @@ pub struct DbTransactionStatusMeta {
     pub rewards: Option<Vec<DbReward>>,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize, Deserialize)]
 #[postgres(name = "TransactionMessageHeader")]
 pub struct DbTransactionMessageHeader {
     pub num_required_signatures: i16,
@@ -96,7 +117,7 @@ pub struct DbTransactionMessageHeader {
     pub num_readonly_unsigned_accounts: i16,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize, Deserialize)]
 #[postgres(name = "TransactionMessage")]
 pub struct DbTransactionMessage {
     pub header: DbTransactionMessageHeader,
@@ -105,7 +126,7 @@ pub struct DbTransactionMessage {
     pub instructions: Vec<DbCompiledInstruction>,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize, Deserialize)]
 #[postgres(name = "TransactionMessageAddressTableLookup")]
 pub struct DbTransactionMessageAddressTableLookup {
     pub account_key: Vec<u8>,
@@ -113,7 +134,7 @@ pub struct DbTransactionMessageAddressTableLookup {
     pub readonly_indexes: Vec<i16>,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize, Deserialize)]
 #[postgres(name = "TransactionMessageV0")]
 pub struct DbTransactionMessageV0 {
     pub header: DbTransactionMessageHeader,
@@ -123,20 +144,21 @@ pub struct DbTransactionMessageV0 {
     pub address_table_lookups: Vec<DbTransactionMessageAddressTableLookup>,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize, Deserialize)]
 #[postgres(name = "LoadedAddresses")]
 pub struct DbLoadedAddresses {
     pub writable: Vec<Vec<u8>>,
     pub readonly: Vec<Vec<u8>>,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize, Deserialize)]
 #[postgres(name = "LoadedMessageV0")]
 pub struct DbLoadedMessageV0 {
     pub message: DbTransactionMessageV0,
     pub loaded_addresses: DbLoadedAddresses,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct DbTransaction {
     pub signature: Vec<u8>,
     pub is_vote: bool,
@@ -148,10 +170,106 @@ pub struct DbTransaction {
     pub meta: DbTransactionStatusMeta,
     pub index: i64,
     pub signatures: Vec<Vec<u8>>,
+    /// The error of the failed transaction decoded for the error columns
+    #[serde(default)]
+    pub decoded_error: Option<DbDecodedTransactionError>,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct LogTransactionRequest {
     pub transaction_info: DbTransaction,
+    /// The getTransaction-shaped JSON of the transaction when the transaction JSON
+    /// is stored
+    pub transaction_json: Option<serde_json::Value>,
+    /// The SOL transfers of the transaction when the SOL transfers are stored
+    pub sol_transfers: Vec<DbSolTransfer>,
+    /// The memos of the transaction when the transaction memos are stored
+    pub memos: Vec<DbTransactionMemo>,
+    /// The parsed top level instructions of the programs whose instructions are parsed
+    #[serde(default)]
+    pub instructions: Vec<DbTransactionInstruction>,
+    /// The token balance changes of the transaction when the normalized transaction
+    /// meta is stored
+    pub token_balance_changes: Vec<DbTokenBalanceChange>,
+    /// The balances of the accounts of the transaction when the normalized transaction
+    /// meta is stored
+    #[serde(default)]
+    pub account_balances: Vec<DbAccountBalance>,
+}
+
+impl LogTransactionRequest {
+    /// Build the request logging the transaction the way the plugin does for the config.
+    pub fn new(
+        slot: u64,
+        transaction_info: &ReplicaTransactionInfo,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Self, GeyserPluginError> {
+        Self::build(
+            slot,
+            transaction_info,
+            config
+                .store_transaction_json
+                .unwrap_or(DEFAULT_STORE_TRANSACTION_JSON),
+            config
+                .store_sol_transfers
+                .unwrap_or(DEFAULT_STORE_SOL_TRANSFERS),
+            config
+                .store_transaction_memos
+                .unwrap_or(DEFAULT_STORE_TRANSACTION_MEMOS),
+            config
+                .parsed_instruction_programs
+                .as_deref()
+                .unwrap_or_default(),
+            config
+                .store_normalized_transaction_meta
+                .unwrap_or(DEFAULT_STORE_NORMALIZED_TRANSACTION_META),
+        )
+    }
+
+    fn build(
+        slot: u64,
+        transaction_info: &ReplicaTransactionInfo,
+        store_transaction_json: bool,
+        store_sol_transfers: bool,
+        store_transaction_memos: bool,
+        parsed_instruction_programs: &[String],
+        store_normalized_transaction_meta: bool,
+    ) -> Result<Self, GeyserPluginError> {
+        let transaction_json = if store_transaction_json {
+            Some(build_transaction_json(slot, transaction_info)?)
+        } else {
+            None
+        };
+        let sol_transfers = if store_sol_transfers {
+            build_sol_transfers(transaction_info)
+        } else {
+            Vec::default()
+        };
+        let memos = if store_transaction_memos {
+            build_transaction_memos(transaction_info)
+        } else {
+            Vec::default()
+        };
+        let instructions =
+            build_transaction_instructions(transaction_info, parsed_instruction_programs);
+        let (token_balance_changes, account_balances) = if store_normalized_transaction_meta {
+            (
+                build_token_balance_changes(transaction_info),
+                build_account_balances(transaction_info),
+            )
+        } else {
+            (Vec::default(), Vec::default())
+        };
+        Ok(Self {
+            transaction_info: build_db_transaction(slot, transaction_info),
+            transaction_json,
+            sol_transfers,
+            memos,
+            instructions,
+            token_balance_changes,
+            account_balances,
+        })
+    }
 }
 
 impl From<&MessageAddressTableLookup> for DbTransactionMessageAddressTableLookup {
@@ -297,7 +415,7 @@ impl From<&Reward> for DbReward {
     fn from(reward: &Reward) -> Self {
         Self {
             pubkey: reward.pubkey.clone(),
-            lamports: reward.lamports as i64,
+            lamports: reward.lamports,
             post_balance: reward.post_balance as i64,
             reward_type: get_reward_type(&reward.reward_type),
             commission: reward
@@ -308,7 +426,7 @@ impl From<&Reward> for DbReward {
     }
 }
 
-#[derive(Clone, Debug, FromSql, ToSql, PartialEq)]
+#[derive(Clone, Debug, FromSql, ToSql, PartialEq, Serialize, Deserialize)]
 #[postgres(name = "TransactionErrorCode")]
 pub enum DbTransactionErrorCode {
     AccountInUse,
@@ -416,7 +534,7 @@ impl From<&TransactionError> for DbTransactionErrorCode {
     }
 }
 
-#[derive(Clone, Debug, FromSql, ToSql, PartialEq)]
+#[derive(Clone, Debug, FromSql, ToSql, PartialEq, Serialize, Deserialize)]
 #[postgres(name = "TransactionError")]
 pub struct DbTransactionError {
     error_code: DbTransactionErrorCode,
@@ -450,6 +568,66 @@ fn get_transaction_error(result: &Result<(), TransactionError>) -> Option<DbTran
     })
 }
 
+/// The error of a failed transaction decoded into the columns it is filtered on
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DbDecodedTransactionError {
+    /// The variant of the error, qualified with the variant of the instruction error
+    /// for the instruction errors, such as 'InstructionError::Custom'
+    pub error_code: String,
+    /// The index of the failing instruction
+    pub instruction_index: Option<i16>,
+    /// The error number of the program of the custom instruction errors
+    pub custom_code: Option<i64>,
+    /// The base58 encoded program of the failing instruction
+    pub program: Option<String>,
+}
+
+/// The name of the variant of the error, without its fields
+fn variant_name(error: &impl std::fmt::Debug) -> String {
+    let error = format!("{:?}", error);
+    error
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Decode the error of the failed transaction, the program of the failing instruction
+/// is looked up in the message.
+fn decode_transaction_error(
+    error: &TransactionError,
+    message: &SanitizedMessage,
+) -> DbDecodedTransactionError {
+    let TransactionError::InstructionError(index, instruction_error) = error else {
+        return DbDecodedTransactionError {
+            error_code: variant_name(error),
+            ..DbDecodedTransactionError::default()
+        };
+    };
+    let program = message
+        .instructions()
+        .get(*index as usize)
+        .and_then(|instruction| {
+            message
+                .account_keys()
+                .get(instruction.program_id_index as usize)
+        })
+        .map(|program| program.to_string());
+    DbDecodedTransactionError {
+        error_code: format!(
+            "{}::{}",
+            variant_name(error),
+            variant_name(instruction_error)
+        ),
+        instruction_index: Some(*index as i16),
+        custom_code: match instruction_error {
+            InstructionError::Custom(code) => Some(*code as i64),
+            _ => None,
+        },
+        program,
+    }
+}
+
 impl From<&TransactionTokenBalance> for DbTransactionTokenBalance {
     fn from(token_balance: &TransactionTokenBalance) -> Self {
         Self {
@@ -501,7 +679,41 @@ impl From<&TransactionStatusMeta> for DbTransactionStatusMeta {
     }
 }
 
-fn build_db_transaction(slot: u64, transaction_info: &ReplicaTransactionInfoV2) -> DbTransaction {
+/// Iterate over the instructions of the transaction, each top level instruction followed
+/// by its inner instructions, along with the index of the top level instruction and the
+/// index of the inner instruction, none for the top level one.
+pub(crate) fn iter_instructions<'a>(
+    transaction_info: &ReplicaTransactionInfo<'a>,
+) -> impl Iterator<Item = (usize, Option<i16>, &'a CompiledInstruction)> {
+    let inner_instructions = &transaction_info.transaction_status_meta.inner_instructions;
+    transaction_info
+        .transaction
+        .message()
+        .instructions()
+        .iter()
+        .enumerate()
+        .flat_map(move |(instruction_index, instruction)| {
+            let inner_instructions = inner_instructions
+                .iter()
+                .flatten()
+                .filter(move |inner_instructions| {
+                    inner_instructions.index as usize == instruction_index
+                })
+                .flat_map(|inner_instructions| inner_instructions.instructions.iter())
+                .enumerate()
+                .map(move |(inner_index, inner_instruction)| {
+                    (
+                        instruction_index,
+                        Some(inner_index as i16),
+                        &inner_instruction.instruction,
+                    )
+                });
+            std::iter::once((instruction_index, None, instruction)).chain(inner_instructions)
+        })
+}
+
+/// Build the transaction row of the transaction notified at the slot.
+pub fn build_db_transaction(slot: u64, transaction_info: &ReplicaTransactionInfo) -> DbTransaction {
     DbTransaction {
         signature: transaction_info.signature.as_ref().to_vec(),
         is_vote: transaction_info.is_vote,
@@ -533,36 +745,216 @@ fn build_db_transaction(slot: u64, transaction_info: &ReplicaTransactionInfoV2)
             .as_ref()
             .to_vec(),
         meta: DbTransactionStatusMeta::from(transaction_info.transaction_status_meta),
+        decoded_error: transaction_info
+            .transaction_status_meta
+            .status
+            .as_ref()
+            .err()
+            .map(|error| decode_transaction_error(error, transaction_info.transaction.message())),
+    }
+}
+
+/// Encode the transaction with its status meta in the shape of the RPC responses
+pub(crate) fn encode_transaction(
+    transaction_info: &ReplicaTransactionInfo,
+    encoding: UiTransactionEncoding,
+) -> Result<EncodedTransactionWithStatusMeta, GeyserPluginError> {
+    VersionedTransactionWithStatusMeta {
+        transaction: transaction_info.transaction.to_versioned_transaction(),
+        meta: transaction_info.transaction_status_meta.clone(),
+    }
+    .encode(encoding, MAX_SUPPORTED_TRANSACTION_VERSION, true)
+    .map_err(|err| GeyserPluginError::TransactionUpdateError {
+        msg: format!(
+            "Failed to encode the transaction {} in the {:?} encoding. Error: {:?}",
+            transaction_info.signature, encoding, err
+        ),
+    })
+}
+
+/// Build the transaction JSON in the shape of the RPC getTransaction response in the
+/// 'jsonParsed' encoding, the block time is not known yet when the transaction is
+/// notified.
+fn build_transaction_json(
+    slot: u64,
+    transaction_info: &ReplicaTransactionInfo,
+) -> Result<serde_json::Value, GeyserPluginError> {
+    let transaction = EncodedConfirmedTransactionWithStatusMeta {
+        slot,
+        transaction: encode_transaction(transaction_info, UiTransactionEncoding::JsonParsed)?,
+        block_time: None,
+    };
+    serde_json::to_value(transaction).map_err(|err| GeyserPluginError::TransactionUpdateError {
+        msg: format!(
+            "Failed to serialize the JSON of the transaction {}. Error: {:?}",
+            transaction_info.signature, err
+        ),
+    })
+}
+
+/// The values of a transaction row computed from the transaction, which the parameters
+/// of the upsert of the transaction borrow
+pub(crate) struct TransactionRowValues<'a> {
+    request: &'a LogTransactionRequest,
+    signature_storage: SignatureStorage,
+    /// The base58 encoded pubkey of the signer, empty without any account key
+    signer: String,
+    failed: bool,
+    signature_text: String,
+    updated_on: NaiveDateTime,
+    error_code: Option<&'a String>,
+    error_instruction_index: Option<i16>,
+    error_custom_code: Option<i64>,
+    error_program: Option<&'a String>,
+}
+
+impl<'a> TransactionRowValues<'a> {
+    pub(crate) fn new(
+        request: &'a LogTransactionRequest,
+        signature_storage: SignatureStorage,
+    ) -> Self {
+        let transaction_info = &request.transaction_info;
+        let account_keys = match (
+            &transaction_info.legacy_message,
+            &transaction_info.v0_loaded_message,
+        ) {
+            (Some(legacy_message), _) => &legacy_message.account_keys,
+            (None, Some(v0_loaded_message)) => &v0_loaded_message.message.account_keys,
+            (None, None) => &Vec::new(),
+        };
+        let signer = account_keys
+            .first()
+            .map(|signer| bs58::encode(signer).into_string())
+            .unwrap_or_default();
+        let decoded_error = transaction_info.decoded_error.as_ref();
+        Self {
+            request,
+            signature_storage,
+            signer,
+            failed: transaction_info.meta.error.is_some(),
+            signature_text: bs58::encode(&transaction_info.signature).into_string(),
+            updated_on: Utc::now().naive_utc(),
+            error_code: decoded_error.map(|error| &error.error_code),
+            error_instruction_index: decoded_error.and_then(|error| error.instruction_index),
+            error_custom_code: decoded_error.and_then(|error| error.custom_code),
+            error_program: decoded_error.and_then(|error| error.program.as_ref()),
+        }
+    }
+
+    /// The signature as stored, base58 encoded or as bytes per the signature storage
+    pub(crate) fn signature(&self) -> &(dyn ToSql + Sync) {
+        match self.signature_storage {
+            SignatureStorage::Base58 => &self.signature_text,
+            SignatureStorage::Bytea | SignatureStorage::Both => {
+                &self.request.transaction_info.signature
+            }
+        }
+    }
+
+    /// The parameters of the upsert of the transaction, with the optional columns of the
+    /// signature storage and of the transaction JSON.
+    pub(crate) fn params(&self, store_transaction_json: bool) -> Vec<&(dyn ToSql + Sync)> {
+        let transaction_info = &self.request.transaction_info;
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![
+            &transaction_info.index,
+            &self.failed,
+            &self.signer,
+            self.signature(),
+            &transaction_info.is_vote,
+            &transaction_info.slot,
+            &transaction_info.message_type,
+            &transaction_info.legacy_message,
+            &transaction_info.v0_loaded_message,
+            &transaction_info.signatures,
+            &transaction_info.message_hash,
+            &transaction_info.meta,
+            &self.updated_on,
+            &self.error_code,
+            &self.error_instruction_index,
+            &self.error_custom_code,
+            &self.error_program,
+        ];
+        if self.signature_storage == SignatureStorage::Both {
+            params.push(&self.signature_text);
+        }
+        // The transactions queued without the JSON, such as the ones spilled before it was
+        // configured, store it as NULL
+        if store_transaction_json {
+            params.push(&self.request.transaction_json);
+        }
+        params
     }
 }
 
 impl SimplePostgresClient {
-    pub(crate) fn build_transaction_info_upsert_statement(
+    /// Build the upsert of a transaction into the transaction table, with the optional
+    /// columns of the config, its parameters being the ones of
+    /// `TransactionRowValues::params`.
+    pub(crate) fn transaction_upsert_sql(config: &AccountsDbPluginPostgresConfig) -> String {
+        let static_columns = StaticColumns::new(config);
+        // The optional columns follow the 17 columns always inserted
+        let mut optional_columns = String::default();
+        let mut optional_values = String::default();
+        let mut optional_updates = String::default();
+        let mut param_count = 17;
+        if config.signature_storage.unwrap_or_default() == SignatureStorage::Both {
+            param_count += 1;
+            optional_columns.push_str(", signature_text");
+            optional_values.push_str(&format!(", ${}", param_count));
+        }
+        if config
+            .store_transaction_json
+            .unwrap_or(DEFAULT_STORE_TRANSACTION_JSON)
+        {
+            param_count += 1;
+            optional_columns.push_str(", transaction_json");
+            optional_values.push_str(&format!(", ${}", param_count));
+            optional_updates.push_str(", transaction_json=excluded.transaction_json");
+        }
+        // The rows of the compressed chunks of a hypertable are costly to update, a
+        // transaction notified again is left as it is
+        let on_conflict = if is_timescaledb(config) {
+            "DO NOTHING".to_string()
+        } else {
+            format!(
+                "DO UPDATE SET is_vote=excluded.is_vote, \
+            message_type=excluded.message_type, \
+            legacy_message=excluded.legacy_message, \
+            v0_loaded_message=excluded.v0_loaded_message, \
+            signatures=excluded.signatures, \
+            message_hash=excluded.message_hash, \
+            meta=excluded.meta, \
+            error_code=excluded.error_code, \
+            error_instruction_index=excluded.error_instruction_index, \
+            error_custom_code=excluded.error_custom_code, \
+            error_program=excluded.error_program{}, \
+            updated_on=excluded.updated_on",
+                optional_updates
+            )
+        };
+        format!("INSERT INTO transaction AS txn (index, failed, signer, signature, is_vote, slot, message_type, legacy_message, \
+        v0_loaded_message, signatures, message_hash, meta, updated_on, error_code, error_instruction_index, error_custom_code, \
+        error_program{}{}) \
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17{}{}) \
+        ON CONFLICT (slot, signature) {}", optional_columns, static_columns.names, optional_values, static_columns.values, on_conflict)
+    }
+
+    /// Build the statement upserting a transaction into the transaction table, with the
+    /// optional columns of the config.
+    pub fn build_transaction_info_upsert_statement(
         client: &mut Client,
         config: &AccountsDbPluginPostgresConfig,
     ) -> Result<Statement, GeyserPluginError> {
-        let stmt = "INSERT INTO transaction AS txn (index, failed, signer, signature, is_vote, slot, message_type, legacy_message, \
-        v0_loaded_message, signatures, message_hash, meta, updated_on) \
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) \
-        ON CONFLICT (slot, signature) DO UPDATE SET is_vote=excluded.is_vote, \
-        message_type=excluded.message_type, \
-        legacy_message=excluded.legacy_message, \
-        v0_loaded_message=excluded.v0_loaded_message, \
-        signatures=excluded.signatures, \
-        message_hash=excluded.message_hash, \
-        meta=excluded.meta, \
-        updated_on=excluded.updated_on";
-
-        let stmt = client.prepare(stmt);
+        let stmt = client.prepare(&Self::transaction_upsert_sql(config));
 
         match stmt {
             Err(err) => {
-                return Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
                     msg: format!(
                         "Error in preparing for the transaction update PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
                         err, config.host, config.user, config
                     ),
-                })));
+                })))
             }
             Ok(stmt) => Ok(stmt),
         }
@@ -570,55 +962,35 @@ impl SimplePostgresClient {
 
     pub(crate) fn log_transaction_impl(
         &mut self,
-        transaction_log_info: LogTransactionRequest,
+        transaction_log_info: &LogTransactionRequest,
     ) -> Result<(), GeyserPluginError> {
+        let signature_storage = self.signature_storage;
+        let store_transaction_json = self.store_transaction_json;
         let client = self.client.get_mut().unwrap();
         let statement = &client.update_transaction_log_stmt;
+        let insert_transaction_address_stmt = &client.insert_transaction_address_stmt;
+        let insert_sol_transfer_stmt = &client.insert_sol_transfer_stmt;
+        let insert_transaction_memo_stmt = &client.insert_transaction_memo_stmt;
+        let insert_transaction_instruction_stmt = &client.insert_transaction_instruction_stmt;
+        let insert_transaction_meta_stmts = &client.insert_transaction_meta_stmts;
+        let post_commit_hooks = &client.post_commit_hooks;
+        let notifier = &client.notifier;
+        let write_stats = &client.write_stats;
         let client = &mut client.client;
-        let updated_on = Utc::now().naive_utc();
 
-        let transaction_info = transaction_log_info.transaction_info;
-        let signer = if transaction_info.legacy_message.is_some() {
-            let legacy_message = transaction_info.legacy_message.as_ref().unwrap();
-            if !legacy_message.account_keys.is_empty() {
-                Some(legacy_message.account_keys[0].clone())
-            } else {
-                None
-            }
-        } else if transaction_info.v0_loaded_message.is_some() {
-            let v0_loaded_message = transaction_info.v0_loaded_message.as_ref().unwrap();
-            if !v0_loaded_message.message.account_keys.is_empty() {
-                Some(v0_loaded_message.message.account_keys[0].clone())
-            } else {
-                None
-            }
-        } else {
-            None
-        };
-        let signer: String = match signer {
-            // base58 encoded pubkey of the signer
-            Some(signer_bytes) => bs58::encode(signer_bytes).into_string(),
-            None => String::new(),
-        };
-        let failed = transaction_info.meta.error.is_some();
-        let result = client.query(
-            statement,
-            &[
-                &transaction_info.index,
-                &failed,
-                &signer,
-                &transaction_info.signature,
-                &transaction_info.is_vote,
-                &transaction_info.slot,
-                &transaction_info.message_type,
-                &transaction_info.legacy_message,
-                &transaction_info.v0_loaded_message,
-                &transaction_info.signatures,
-                &transaction_info.message_hash,
-                &transaction_info.meta,
-                &updated_on,
-            ],
-        );
+        let LogTransactionRequest {
+            transaction_info,
+            sol_transfers,
+            memos,
+            instructions,
+            token_balance_changes,
+            account_balances,
+            ..
+        } = transaction_log_info;
+        let values = TransactionRowValues::new(transaction_log_info, signature_storage);
+        let signature = values.signature();
+        let params = values.params(store_transaction_json);
+        let result = client.query(statement, &params);
 
         if let Err(err) = result {
             let msg = format!(
@@ -628,36 +1000,130 @@ impl SimplePostgresClient {
             error!("{}", msg);
             return Err(GeyserPluginError::AccountsUpdateError { msg });
         }
+        write_stats.record("transaction", 1, statement, &params);
+
+        if let Some(insert_transaction_address_stmt) = insert_transaction_address_stmt {
+            Self::insert_transaction_addresses(
+                client,
+                insert_transaction_address_stmt,
+                transaction_info,
+                signature,
+                write_stats,
+            )?;
+        }
 
-        Ok(())
+        if let Some(insert_sol_transfer_stmt) = insert_sol_transfer_stmt {
+            Self::insert_sol_transfers(
+                client,
+                insert_sol_transfer_stmt,
+                transaction_info.slot,
+                signature,
+                sol_transfers,
+                write_stats,
+            )?;
+        }
+
+        if let Some(insert_transaction_memo_stmt) = insert_transaction_memo_stmt {
+            Self::insert_transaction_memos(
+                client,
+                insert_transaction_memo_stmt,
+                transaction_info.slot,
+                signature,
+                memos,
+                write_stats,
+            )?;
+        }
+
+        if let Some(insert_transaction_instruction_stmt) = insert_transaction_instruction_stmt {
+            Self::insert_transaction_instructions(
+                client,
+                insert_transaction_instruction_stmt,
+                transaction_info.slot,
+                signature,
+                instructions,
+                write_stats,
+            )?;
+        }
+
+        if let Some(insert_transaction_meta_stmts) = insert_transaction_meta_stmts {
+            let meta = &transaction_info.meta;
+            Self::insert_transaction_meta(
+                client,
+                insert_transaction_meta_stmts,
+                transaction_info.slot,
+                signature,
+                account_balances,
+                token_balance_changes,
+                meta.rewards.as_deref().unwrap_or_default(),
+                meta.log_messages.as_deref().unwrap_or_default(),
+                write_stats,
+            )?;
+        }
+
+        notifier.notify(
+            client,
+            "transaction",
+            [json!({"signature": values.signature_text, "slot": transaction_info.slot})],
+        )?;
+        post_commit_hooks.execute(
+            client,
+            "transaction",
+            1,
+            transaction_info.slot,
+            transaction_info.slot,
+        )
     }
 }
 
 impl ParallelPostgresClient {
     fn build_transaction_request(
+        &self,
         slot: u64,
-        transaction_info: &ReplicaTransactionInfoV2,
-    ) -> LogTransactionRequest {
-        LogTransactionRequest {
-            transaction_info: build_db_transaction(slot, transaction_info),
-        }
+        transaction_info: &ReplicaTransactionInfo,
+    ) -> Result<LogTransactionRequest, GeyserPluginError> {
+        LogTransactionRequest::build(
+            slot,
+            transaction_info,
+            self.store_transaction_json,
+            self.store_sol_transfers,
+            self.store_transaction_memos,
+            &self.parsed_instruction_programs,
+            self.store_normalized_transaction_meta,
+        )
     }
 
     pub fn log_transaction_info(
         &self,
-        transaction_info: &ReplicaTransactionInfoV2,
+        transaction_info: &ReplicaTransactionInfo,
         slot: u64,
     ) -> Result<(), GeyserPluginError> {
-        let wrk_item = DbWorkItem::LogTransaction(Box::new(Self::build_transaction_request(
-            slot,
-            transaction_info,
-        )));
-
-        if let Err(err) = self.sender.send(wrk_item) {
+        if self
+            .degradation
+            .as_ref()
+            .is_some_and(|degradation| degradation.skips_transactions())
+        {
+            return Ok(());
+        }
+        let request = self.build_transaction_request(slot, transaction_info)?;
+
+        self.record_ingest_stored_transaction(slot);
+        self.record_received_transaction(slot);
+        self.record_notification(MetricStream::Transaction);
+        // The transactions are committed along with their slot with the per-slot commit
+        if let Some(slot_commit_buffer) = &self.slot_commit_buffer {
+            slot_commit_buffer.add_transaction(slot, request);
+        } else if let Err(err) = self
+            .queue_overflow
+            .send(DbWorkItem::LogTransaction(Box::new(request)))
+        {
             return Err(GeyserPluginError::SlotStatusUpdateError {
                 msg: format!("Failed to update the transaction, error: {:?}", err),
             });
         }
+
+        if let Some(analyze_row_counts) = &self.analyze_row_counts {
+            analyze_row_counts.add(AnalyzeTable::Transaction, 1);
+        }
         Ok(())
     }
 }
@@ -707,7 +1173,7 @@ pub(crate) mod tests {
         for i in 0..compiled_instruction.data.len() {
             assert_eq!(
                 compiled_instruction.data[i],
-                db_compiled_instruction.data[i] as u8
+                db_compiled_instruction.data[i]
             )
         }
     }
@@ -951,15 +1417,10 @@ pub(crate) mod tests {
                 .map(|inner_instructions| inner_instructions.len()),
         );
 
-        if transaction_status_meta.inner_instructions.is_some() {
-            for i in 0..transaction_status_meta
-                .inner_instructions
-                .as_ref()
-                .unwrap()
-                .len()
-            {
+        if let Some(inner_instructions) = &transaction_status_meta.inner_instructions {
+            for (i, inner_instruction) in inner_instructions.iter().enumerate() {
                 check_inner_instructions_equality(
-                    &transaction_status_meta.inner_instructions.as_ref().unwrap()[i],
+                    inner_instruction,
                     &db_transaction_status_meta
                         .inner_instructions
                         .as_ref()
@@ -979,10 +1440,10 @@ pub(crate) mod tests {
                 .map(|log_messages| log_messages.len()),
         );
 
-        if transaction_status_meta.log_messages.is_some() {
-            for i in 0..transaction_status_meta.log_messages.as_ref().unwrap().len() {
+        if let Some(log_messages) = &transaction_status_meta.log_messages {
+            for (i, log_message) in log_messages.iter().enumerate() {
                 assert_eq!(
-                    &transaction_status_meta.log_messages.as_ref().unwrap()[i],
+                    log_message,
                     &db_transaction_status_meta.log_messages.as_ref().unwrap()[i]
                 );
             }
@@ -1009,10 +1470,10 @@ pub(crate) mod tests {
                 .map(|rewards| rewards.len()),
         );
 
-        if transaction_status_meta.rewards.is_some() {
-            for i in 0..transaction_status_meta.rewards.as_ref().unwrap().len() {
+        if let Some(rewards) = &transaction_status_meta.rewards {
+            for (i, reward) in rewards.iter().enumerate() {
                 check_reward_equality(
-                    &transaction_status_meta.rewards.as_ref().unwrap()[i],
+                    reward,
                     &db_transaction_status_meta.rewards.as_ref().unwrap()[i],
                 );
             }
@@ -1339,7 +1800,7 @@ pub(crate) mod tests {
 
     fn check_transaction(
         slot: u64,
-        transaction: &ReplicaTransactionInfoV2,
+        transaction: &ReplicaTransactionInfo,
         db_transaction: &DbTransaction,
     ) {
         assert_eq!(transaction.signature.as_ref(), db_transaction.signature);
@@ -1389,36 +1850,152 @@ pub(crate) mod tests {
         solana_system_transaction::transfer(&keypair1, &pubkey1, 42, zero)
     }
 
+    /// A legacy transaction sanitized along with the status meta it is notified with,
+    /// for the tests of the rows built from the transactions
+    pub(crate) struct TestTransaction {
+        pub signature: Signature,
+        pub transaction: SanitizedTransaction,
+        /// The meta of a successful transaction, by default
+        pub meta: TransactionStatusMeta,
+        pub index: usize,
+    }
+
+    impl TestTransaction {
+        pub(crate) fn new(transaction: Transaction) -> Self {
+            let transaction = SanitizedTransaction::try_create(
+                VersionedTransaction::from(transaction),
+                Hash::new_unique(),
+                None,
+                SimpleAddressLoader::Disabled,
+                &ReservedAccountKeys::empty_key_set(),
+            )
+            .unwrap();
+            Self {
+                signature: *transaction.signature(),
+                transaction,
+                meta: TransactionStatusMeta::default(),
+                index: 0,
+            }
+        }
+
+        pub(crate) fn transaction_info(&self) -> ReplicaTransactionInfo<'_> {
+            ReplicaTransactionInfo {
+                signature: &self.signature,
+                is_vote: self.transaction.is_simple_vote_transaction(),
+                transaction: &self.transaction,
+                transaction_status_meta: &self.meta,
+                index: self.index,
+            }
+        }
+    }
+
     #[test]
     fn test_build_db_transaction_legacy() {
-        let signature = Signature::new_unique();
+        let mut transaction = TestTransaction::new(build_test_transaction_legacy());
+        transaction.meta = build_transaction_status_meta();
+        let transaction_info = transaction.transaction_info();
 
-        let message_hash = Hash::new_unique();
-        let transaction = build_test_transaction_legacy();
+        let slot = 54;
+        let db_transaction = build_db_transaction(slot, &transaction_info);
+        check_transaction(slot, &transaction_info, &db_transaction);
+        assert_eq!(db_transaction.decoded_error, None);
+    }
 
-        let transaction = VersionedTransaction::from(transaction);
+    #[test]
+    fn test_decode_transaction_error() {
+        let transaction = TestTransaction::new(build_test_transaction_legacy()).transaction;
+        let message = transaction.message();
 
-        let transaction = SanitizedTransaction::try_create(
-            transaction,
-            message_hash,
-            Some(true),
-            SimpleAddressLoader::Disabled,
-            &ReservedAccountKeys::empty_key_set(),
-        )
-        .unwrap();
+        assert_eq!(
+            decode_transaction_error(
+                &TransactionError::InstructionError(0, InstructionError::Custom(6000)),
+                message
+            ),
+            DbDecodedTransactionError {
+                error_code: "InstructionError::Custom".to_string(),
+                instruction_index: Some(0),
+                custom_code: Some(6000),
+                program: Some(solana_sdk::system_program::id().to_string()),
+            }
+        );
+        assert_eq!(
+            decode_transaction_error(
+                &TransactionError::InstructionError(0, InstructionError::InsufficientFunds),
+                message
+            ),
+            DbDecodedTransactionError {
+                error_code: "InstructionError::InsufficientFunds".to_string(),
+                instruction_index: Some(0),
+                custom_code: None,
+                program: Some(solana_sdk::system_program::id().to_string()),
+            }
+        );
+        // The program of an instruction missing from the message is not known
+        assert_eq!(
+            decode_transaction_error(
+                &TransactionError::InstructionError(3, InstructionError::Custom(1)),
+                message
+            )
+            .program,
+            None
+        );
+        assert_eq!(
+            decode_transaction_error(
+                &TransactionError::InsufficientFundsForRent { account_index: 2 },
+                message
+            ),
+            DbDecodedTransactionError {
+                error_code: "InsufficientFundsForRent".to_string(),
+                ..DbDecodedTransactionError::default()
+            }
+        );
+        assert_eq!(
+            decode_transaction_error(&TransactionError::AccountInUse, message).error_code,
+            "AccountInUse"
+        );
+    }
 
-        let transaction_status_meta = build_transaction_status_meta();
-        let transaction_info = ReplicaTransactionInfoV2 {
-            signature: &signature,
-            is_vote: false,
-            transaction: &transaction,
-            transaction_status_meta: &transaction_status_meta,
-            index: 0,
+    #[test]
+    fn test_build_transaction_json() {
+        let mut transaction = TestTransaction::new(build_test_transaction_legacy());
+        // The test inner instructions do not refer to the accounts of the transaction
+        transaction.meta = TransactionStatusMeta {
+            inner_instructions: None,
+            ..build_transaction_status_meta()
         };
+        let transaction_info = transaction.transaction_info();
 
-        let slot = 54;
-        let db_transaction = build_db_transaction(slot, &transaction_info);
-        check_transaction(slot, &transaction_info, &db_transaction);
+        let transaction_json = build_transaction_json(54, &transaction_info).unwrap();
+        assert_eq!(transaction_json["slot"], 54);
+        assert_eq!(transaction_json["blockTime"], serde_json::Value::Null);
+        assert_eq!(transaction_json["version"], "legacy");
+        assert_eq!(
+            transaction_json["transaction"]["signatures"][0],
+            transaction.signature.to_string()
+        );
+        // The system transfer is parsed in the 'jsonParsed' encoding
+        assert_eq!(
+            transaction_json["transaction"]["message"]["instructions"][0]["parsed"]["type"],
+            "transfer"
+        );
+        assert_eq!(transaction_json["meta"]["fee"], transaction.meta.fee);
+
+        // The request built for the config stores the JSON the same way
+        let config: AccountsDbPluginPostgresConfig =
+            serde_json::from_str("{\"store_transaction_json\": true}").unwrap();
+        let request = LogTransactionRequest::new(54, &transaction_info, &config).unwrap();
+        assert_eq!(request.transaction_json, Some(transaction_json));
+        assert!(request.sol_transfers.is_empty());
+        assert!(request.memos.is_empty());
+        assert!(request.token_balance_changes.is_empty());
+        assert!(request.account_balances.is_empty());
+        // The request round trips the JSON of the dead letter
+        let payload = serde_json::to_value(&request).unwrap();
+        let request: LogTransactionRequest = serde_json::from_value(payload.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&request).unwrap(), payload);
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str("{}").unwrap();
+        let request = LogTransactionRequest::new(54, &transaction_info, &config).unwrap();
+        assert_eq!(request.transaction_json, None);
     }
 
     fn build_test_transaction_v0() -> VersionedTransaction {
@@ -1454,7 +2031,7 @@ pub(crate) mod tests {
         .unwrap();
 
         let transaction_status_meta = build_transaction_status_meta();
-        let transaction_info = ReplicaTransactionInfoV2 {
+        let transaction_info = ReplicaTransactionInfo {
             signature: &signature,
             is_vote: true,
             transaction: &transaction,
@@ -0,0 +1,146 @@
+/// Module responsible for indexing the transactions by the addresses they mention in
+/// the PostgreSQL database, to serve getSignaturesForAddress.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::{
+            postgres_client_transaction::DbTransaction, postgres_client_write_stats::WriteStats,
+            SimplePostgresClient,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    postgres::{Client, Statement},
+    postgres_types::ToSql,
+};
+
+/// Get the addresses mentioned by the transaction, including the addresses loaded
+/// from the address lookup tables.
+fn transaction_addresses(transaction: &DbTransaction) -> Vec<&Vec<u8>> {
+    if let Some(legacy_message) = &transaction.legacy_message {
+        legacy_message.account_keys.iter().collect()
+    } else if let Some(v0_loaded_message) = &transaction.v0_loaded_message {
+        v0_loaded_message
+            .message
+            .account_keys
+            .iter()
+            .chain(&v0_loaded_message.loaded_addresses.writable)
+            .chain(&v0_loaded_message.loaded_addresses.readonly)
+            .collect()
+    } else {
+        Vec::default()
+    }
+}
+
+impl SimplePostgresClient {
+    /// Build the statement inserting all the account keys of a transaction into the
+    /// transaction_address table at once, one row per element of the array of keys.
+    pub fn build_transaction_address_insert_statement(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Statement, GeyserPluginError> {
+        let stmt = "INSERT INTO transaction_address (address, slot, index, signature) \
+            SELECT address, $2, $3, $4 FROM UNNEST($1::BYTEA[]) AS a (address) \
+            ON CONFLICT DO NOTHING";
+
+        let stmt = client.prepare(stmt);
+
+        match stmt {
+            Err(err) => {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "Error in preparing for the transaction address insert PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                        err, config.host, config.user, config
+                    ),
+                })))
+            }
+            Ok(stmt) => Ok(stmt),
+        }
+    }
+
+    /// Index the transaction by the addresses it mentions, the signature is in the
+    /// same storage as in the transaction table.
+    pub(crate) fn insert_transaction_addresses(
+        client: &mut Client,
+        statement: &Statement,
+        transaction: &DbTransaction,
+        signature: &(dyn ToSql + Sync),
+        write_stats: &WriteStats,
+    ) -> Result<(), GeyserPluginError> {
+        let addresses = transaction_addresses(transaction);
+        let params: [&(dyn ToSql + Sync); 4] =
+            [&addresses, &transaction.slot, &transaction.index, signature];
+        let result = client.execute(statement, &params);
+
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to persist the transaction addresses to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::TransactionUpdateError { msg });
+        }
+        write_stats.record("transaction_address", addresses.len(), statement, &params);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::postgres_client_transaction::{
+            DbLoadedAddresses, DbLoadedMessageV0, DbTransactionMessageHeader,
+            DbTransactionMessageV0, DbTransactionStatusMeta,
+        },
+    };
+
+    #[test]
+    fn test_transaction_addresses() {
+        let transaction = DbTransaction {
+            signature: vec![1; 64],
+            is_vote: false,
+            slot: 7,
+            message_type: 1,
+            legacy_message: None,
+            v0_loaded_message: Some(DbLoadedMessageV0 {
+                message: DbTransactionMessageV0 {
+                    header: DbTransactionMessageHeader {
+                        num_required_signatures: 1,
+                        num_readonly_signed_accounts: 0,
+                        num_readonly_unsigned_accounts: 0,
+                    },
+                    account_keys: vec![vec![1; 32], vec![2; 32]],
+                    recent_blockhash: vec![0; 32],
+                    instructions: vec![],
+                    address_table_lookups: vec![],
+                },
+                loaded_addresses: DbLoadedAddresses {
+                    writable: vec![vec![3; 32]],
+                    readonly: vec![vec![4; 32]],
+                },
+            }),
+            message_hash: vec![0; 32],
+            meta: DbTransactionStatusMeta {
+                error: None,
+                fee: 5000,
+                pre_balances: vec![],
+                post_balances: vec![],
+                inner_instructions: None,
+                log_messages: None,
+                pre_token_balances: None,
+                post_token_balances: None,
+                rewards: None,
+            },
+            index: 0,
+            signatures: vec![vec![1; 64]],
+            decoded_error: None,
+        };
+        assert_eq!(
+            transaction_addresses(&transaction),
+            vec![&vec![1; 32], &vec![2; 32], &vec![3; 32], &vec![4; 32]]
+        );
+    }
+}
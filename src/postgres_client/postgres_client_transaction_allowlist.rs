@@ -0,0 +1,226 @@
+/// Module responsible for polling the transaction_allowlist table, maintained by an
+/// external service, into the live transaction selection, so that the transactions of
+/// the addresses and the signatures monitored can be added without a redeploy of the
+/// config.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        geyser_interface::ReplicaTransactionInfo,
+        postgres_client::{ParallelPostgresClient, SimplePostgresClient},
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    postgres::Client,
+    std::{
+        collections::HashSet,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, RwLock,
+        },
+        thread::{sleep, Builder, JoinHandle},
+        time::{Duration, Instant},
+    },
+};
+
+const ALLOWLIST_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The entries of the transaction_allowlist table, the 32-byte addresses mentioned by
+/// the transactions and the 64-byte signatures of the transactions.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct AllowlistEntries {
+    addresses: HashSet<Vec<u8>>,
+    signatures: HashSet<Vec<u8>>,
+}
+
+impl AllowlistEntries {
+    fn new(entries: Vec<Vec<u8>>) -> Self {
+        let mut allowlist = Self::default();
+        for entry in entries {
+            match entry.len() {
+                32 => {
+                    allowlist.addresses.insert(entry);
+                }
+                64 => {
+                    allowlist.signatures.insert(entry);
+                }
+                len => warn!(
+                    "Ignored the transaction allowlist entry of {} bytes, neither an address nor a signature",
+                    len
+                ),
+            }
+        }
+        allowlist
+    }
+}
+
+/// The allowlist shared by the poller and the client selecting the transactions
+#[derive(Default)]
+pub(crate) struct TransactionAllowlist {
+    entries: RwLock<AllowlistEntries>,
+}
+
+impl TransactionAllowlist {
+    /// Check if the transaction or any of the addresses it mentions is allowlisted.
+    fn is_transaction_allowlisted(&self, transaction_info: &ReplicaTransactionInfo) -> bool {
+        let entries = self.entries.read().unwrap();
+        entries
+            .signatures
+            .contains(transaction_info.signature.as_ref())
+            || (!entries.addresses.is_empty()
+                && transaction_info
+                    .transaction
+                    .message()
+                    .account_keys()
+                    .iter()
+                    .any(|address| entries.addresses.contains(address.as_ref())))
+    }
+}
+
+pub(crate) struct TransactionAllowlistPoller {
+    client: Client,
+    interval: Duration,
+    allowlist: Arc<TransactionAllowlist>,
+}
+
+impl TransactionAllowlistPoller {
+    /// Connect and load the allowlist when the poll interval is configured, a failure
+    /// of the first load fails the loading of the plugin.
+    pub(crate) fn new(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Option<Self>, GeyserPluginError> {
+        let Some(interval) = config.transaction_allowlist_poll_interval_secs else {
+            return Ok(None);
+        };
+        let client = SimplePostgresClient::connect_to_db(config)?;
+        let mut poller = Self {
+            client,
+            interval: Duration::from_secs(interval),
+            allowlist: Arc::new(TransactionAllowlist::default()),
+        };
+        poller.poll()?;
+        Ok(Some(poller))
+    }
+
+    pub(crate) fn allowlist(&self) -> Arc<TransactionAllowlist> {
+        self.allowlist.clone()
+    }
+
+    /// Replace the allowlist with the entries of the table, the allowlist is kept as is
+    /// when the query fails.
+    fn poll(&mut self) -> Result<(), GeyserPluginError> {
+        let rows = match self
+            .client
+            .query("SELECT entry FROM transaction_allowlist", &[])
+        {
+            Ok(rows) => rows,
+            Err(err) => {
+                let msg = format!(
+                    "Failed to query the transaction allowlist from the PostgreSQL database. Error: {:?}",
+                    err
+                );
+                error!("{}", msg);
+                return Err(GeyserPluginError::Custom(Box::new(
+                    AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+                )));
+            }
+        };
+        let entries = AllowlistEntries::new(rows.iter().map(|row| row.get(0)).collect());
+        let mut allowlist = self.allowlist.entries.write().unwrap();
+        if *allowlist != entries {
+            info!(
+                "Updated the transaction allowlist to {} addresses and {} signatures",
+                entries.addresses.len(),
+                entries.signatures.len()
+            );
+            *allowlist = entries;
+        }
+        Ok(())
+    }
+
+    /// Poll the allowlist at the interval until the exit.
+    pub(crate) fn spawn(
+        mut self,
+        exit_worker: Arc<AtomicBool>,
+    ) -> JoinHandle<Result<(), GeyserPluginError>> {
+        Builder::new()
+            .name("allowlist-poller".to_string())
+            .spawn(move || -> Result<(), GeyserPluginError> {
+                let mut last_polled = Instant::now();
+                while !exit_worker.load(Ordering::Relaxed) {
+                    sleep(ALLOWLIST_CHECK_INTERVAL);
+                    if last_polled.elapsed() >= self.interval {
+                        // The errors are logged, the allowlist polled last is kept.
+                        let _ = self.poll();
+                        last_polled = Instant::now();
+                    }
+                }
+                Ok(())
+            })
+            .unwrap()
+    }
+}
+
+impl ParallelPostgresClient {
+    /// Check if the transaction is allowlisted, to be stored regardless of the
+    /// transaction_selector.
+    pub fn is_transaction_allowlisted(&self, transaction_info: &ReplicaTransactionInfo) -> bool {
+        self.transaction_allowlist
+            .as_ref()
+            .is_some_and(|allowlist| allowlist.is_transaction_allowlisted(transaction_info))
+    }
+
+    /// Check if the transaction allowlist is polled
+    pub fn has_transaction_allowlist(&self) -> bool {
+        self.transaction_allowlist.is_some()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::postgres_client_transaction::tests::TestTransaction,
+        solana_sdk::{
+            hash::Hash,
+            instruction::{AccountMeta, Instruction},
+            pubkey::Pubkey,
+            signature::{Keypair, Signer},
+            transaction::Transaction,
+        },
+    };
+
+    #[test]
+    fn test_transaction_allowlist() {
+        let keypair = Keypair::new();
+        let destination = Pubkey::new_unique();
+        let transaction = Transaction::new_signed_with_payer(
+            &[Instruction::new_with_bytes(
+                Pubkey::new_unique(),
+                &[],
+                vec![AccountMeta::new(destination, false)],
+            )],
+            Some(&keypair.pubkey()),
+            &[&keypair],
+            Hash::default(),
+        );
+        let transaction = TestTransaction::new(transaction);
+        let transaction_info = transaction.transaction_info();
+
+        let allowlist = TransactionAllowlist::default();
+        assert!(!allowlist.is_transaction_allowlisted(&transaction_info));
+
+        *allowlist.entries.write().unwrap() =
+            AllowlistEntries::new(vec![Pubkey::new_unique().to_bytes().to_vec(), vec![1; 16]]);
+        assert!(!allowlist.is_transaction_allowlisted(&transaction_info));
+
+        *allowlist.entries.write().unwrap() =
+            AllowlistEntries::new(vec![destination.to_bytes().to_vec()]);
+        assert!(allowlist.is_transaction_allowlisted(&transaction_info));
+
+        *allowlist.entries.write().unwrap() =
+            AllowlistEntries::new(vec![transaction.signature.as_ref().to_vec()]);
+        assert!(allowlist.is_transaction_allowlisted(&transaction_info));
+    }
+}
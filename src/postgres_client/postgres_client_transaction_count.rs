@@ -0,0 +1,265 @@
+/// Module responsible for counting the transactions selected per slot, the ones received
+/// by the plugin versus the ones committed by the workers, and storing the pair on the
+/// slot row, so that the transactions dropped by the queue or lost with the failed
+/// batches are visible with a query comparing the two.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::{DbWorkItem, ParallelPostgresClient, SimplePostgresClient},
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    postgres::{Client, Statement},
+    std::{
+        collections::BTreeMap,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        thread::{sleep, Builder, JoinHandle},
+        time::Duration,
+    },
+};
+
+pub(crate) const DEFAULT_STORE_SLOT_TRANSACTION_COUNTS: bool = false;
+
+const TRANSACTION_COUNT_WRITE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The slots counted, the lowest ones are no longer counted past
+const MAX_COUNTED_SLOTS: usize = 1000;
+
+impl DbWorkItem {
+    /// Get the slot of the transaction logged by the work item
+    pub(crate) fn transaction_slot(&self) -> Option<u64> {
+        match self {
+            DbWorkItem::LogTransaction(request) => Some(request.transaction_info.slot as u64),
+            _ => None,
+        }
+    }
+}
+
+/// The transactions of a slot counted since its first transaction
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct SlotTransactionCounts {
+    received: u64,
+    committed: u64,
+    /// Indicates if the counts are written to the slot row
+    written: bool,
+}
+
+/// The slots counted, and the lowest slot still counted, the transactions of the slots
+/// before it being ignored so that their counts written are not overwritten
+#[derive(Default)]
+struct CountedSlots {
+    slots: BTreeMap<u64, SlotTransactionCounts>,
+    counted_from: u64,
+}
+
+/// The transactions counted per slot, shared by the client notified and the workers
+#[derive(Default)]
+pub(crate) struct TransactionCounts {
+    slots: Mutex<CountedSlots>,
+}
+
+impl TransactionCounts {
+    fn update(&self, slot: u64, update: impl FnOnce(&mut SlotTransactionCounts)) {
+        let mut slots = self.slots.lock().unwrap();
+        if slot < slots.counted_from {
+            return;
+        }
+        let counts = slots.slots.entry(slot).or_default();
+        update(counts);
+        counts.written = false;
+        while slots.slots.len() > MAX_COUNTED_SLOTS {
+            if let Some((slot, _)) = slots.slots.pop_first() {
+                slots.counted_from = slot + 1;
+            }
+        }
+    }
+
+    fn record_received(&self, slot: u64) {
+        self.update(slot, |counts| counts.received += 1);
+    }
+
+    /// Record the transaction committed by a worker.
+    pub(crate) fn record_committed(&self, slot: u64) {
+        self.update(slot, |counts| counts.committed += 1);
+    }
+
+    /// Take the counts of the slots changed since they were last written, which are
+    /// then marked as written.
+    fn take_changed(&self) -> Vec<(u64, SlotTransactionCounts)> {
+        let mut slots = self.slots.lock().unwrap();
+        slots
+            .slots
+            .iter_mut()
+            .filter(|(_, counts)| !counts.written)
+            .map(|(slot, counts)| {
+                counts.written = true;
+                (*slot, *counts)
+            })
+            .collect()
+    }
+
+    /// Mark the counts of the slot as not written, for them to be written again.
+    fn mark_unwritten(&self, slot: u64) {
+        if let Some(counts) = self.slots.lock().unwrap().slots.get_mut(&slot) {
+            counts.written = false;
+        }
+    }
+}
+
+pub(crate) struct TransactionCountWriter {
+    client: Client,
+    statement: Statement,
+    counts: Arc<TransactionCounts>,
+}
+
+impl TransactionCountWriter {
+    pub(crate) fn new(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Option<Self>, GeyserPluginError> {
+        if !config
+            .store_slot_transaction_counts
+            .unwrap_or(DEFAULT_STORE_SLOT_TRANSACTION_COUNTS)
+        {
+            return Ok(None);
+        }
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+        let stmt = "UPDATE slot SET transactions_received = $2, transactions_committed = $3 \
+            WHERE slot = $1";
+        let statement = match client.prepare(stmt) {
+            Ok(statement) => statement,
+            Err(err) => {
+                return Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "Error in preparing for the slot transaction count PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                        err, config.host, config.user, config
+                    ),
+                })));
+            }
+        };
+        Ok(Some(Self {
+            client,
+            statement,
+            counts: Arc::new(TransactionCounts::default()),
+        }))
+    }
+
+    pub(crate) fn counts(&self) -> Arc<TransactionCounts> {
+        self.counts.clone()
+    }
+
+    /// Write the counts changed to the slot rows, the slots not written yet by the
+    /// workers being written again at the next interval.
+    fn write(&mut self) {
+        for (slot, counts) in self.counts.take_changed() {
+            let result = self.client.execute(
+                &self.statement,
+                &[
+                    &(slot as i64),
+                    &(counts.received as i64),
+                    &(counts.committed as i64),
+                ],
+            );
+            match result {
+                Ok(0) => self.counts.mark_unwritten(slot),
+                Ok(_) => {}
+                Err(err) => {
+                    error!(
+                        "Failed to persist the transaction counts of the slot {} to the PostgreSQL database. Error: {:?}",
+                        slot, err
+                    );
+                    self.counts.mark_unwritten(slot);
+                }
+            }
+        }
+    }
+
+    /// Write the counts changed at the interval until the exit, and once more then.
+    pub(crate) fn spawn(
+        mut self,
+        exit_worker: Arc<AtomicBool>,
+    ) -> JoinHandle<Result<(), GeyserPluginError>> {
+        Builder::new()
+            .name("transaction-count-writer".to_string())
+            .spawn(move || -> Result<(), GeyserPluginError> {
+                while !exit_worker.load(Ordering::Relaxed) {
+                    sleep(TRANSACTION_COUNT_WRITE_INTERVAL);
+                    self.write();
+                }
+                self.write();
+                Ok(())
+            })
+            .unwrap()
+    }
+}
+
+impl ParallelPostgresClient {
+    pub(crate) fn record_received_transaction(&self, slot: u64) {
+        if let Some(transaction_counts) = &self.transaction_counts {
+            transaction_counts.record_received(slot);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transaction_counts() {
+        let counts = TransactionCounts::default();
+        counts.record_received(1);
+        counts.record_received(1);
+        counts.record_committed(1);
+        counts.record_received(2);
+        assert_eq!(
+            counts.take_changed(),
+            vec![
+                (
+                    1,
+                    SlotTransactionCounts {
+                        received: 2,
+                        committed: 1,
+                        written: true,
+                    }
+                ),
+                (
+                    2,
+                    SlotTransactionCounts {
+                        received: 1,
+                        committed: 0,
+                        written: true,
+                    }
+                ),
+            ]
+        );
+        assert!(counts.take_changed().is_empty());
+
+        // The counts changed, or not written, are taken again
+        counts.record_committed(1);
+        counts.mark_unwritten(2);
+        assert_eq!(
+            counts
+                .take_changed()
+                .into_iter()
+                .map(|(slot, counts)| (slot, counts.received, counts.committed))
+                .collect::<Vec<_>>(),
+            vec![(1, 2, 2), (2, 1, 0)]
+        );
+
+        // The lowest slots are no longer counted past the max slots counted
+        for slot in 3..MAX_COUNTED_SLOTS as u64 + 3 {
+            counts.record_received(slot);
+        }
+        let slots = counts.slots.lock().unwrap();
+        assert_eq!(slots.slots.len(), MAX_COUNTED_SLOTS);
+        assert_eq!(slots.counted_from, 3);
+        drop(slots);
+        counts.record_committed(1);
+        assert!(!counts.slots.lock().unwrap().slots.contains_key(&1));
+    }
+}
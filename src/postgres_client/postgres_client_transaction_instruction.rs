@@ -0,0 +1,224 @@
+/// Module responsible for parsing the top level instructions of the programs configured
+/// as in the 'jsonParsed' encoding into the PostgreSQL database, so that the analytics
+/// of the common instructions do not require decoding them.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        geyser_interface::ReplicaTransactionInfo,
+        postgres_client::{
+            postgres_client_transaction::iter_instructions,
+            postgres_client_write_stats::WriteStats, SimplePostgresClient,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    postgres::{Client, Statement},
+    postgres_types::ToSql,
+    serde_derive::{Deserialize, Serialize},
+    solana_transaction_status::parse_instruction::parse,
+};
+
+/// The programs whose instructions can be parsed, named as in the 'jsonParsed' encoding
+pub(crate) const PARSED_INSTRUCTION_PROGRAMS: [&str; 9] = [
+    "address-lookup-table",
+    "bpf-loader",
+    "bpf-upgradeable-loader",
+    "spl-associated-token-account",
+    "spl-memo",
+    "spl-token",
+    "stake",
+    "system",
+    "vote",
+];
+
+/// A top level instruction parsed as in the 'jsonParsed' encoding
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DbTransactionInstruction {
+    pub instruction_index: i16,
+    /// The name of the program, e.g. "spl-token" for both of the token programs
+    pub program: String,
+    pub program_id: Vec<u8>,
+    /// The parsed instruction, e.g. {"type": "transfer", "info": {...}}
+    pub parsed: serde_json::Value,
+}
+
+/// Parse the top level instructions of the programs of the transaction, none for the
+/// failed transactions as their instructions had no effect. The instructions the parser
+/// of their program does not recognize are skipped.
+pub fn build_transaction_instructions(
+    transaction_info: &ReplicaTransactionInfo,
+    programs: &[String],
+) -> Vec<DbTransactionInstruction> {
+    if programs.is_empty() || transaction_info.transaction_status_meta.status.is_err() {
+        return Vec::default();
+    }
+    let message = transaction_info.transaction.message();
+    let account_keys = message.account_keys();
+
+    let mut instructions = Vec::default();
+    for (instruction_index, _, instruction) in iter_instructions(transaction_info)
+        .filter(|(_, inner_instruction_index, _)| inner_instruction_index.is_none())
+    {
+        let Some(program_id) = account_keys.get(instruction.program_id_index as usize) else {
+            continue;
+        };
+        let Ok(parsed) = parse(program_id, instruction, &account_keys, None) else {
+            continue;
+        };
+        if !programs.contains(&parsed.program) {
+            continue;
+        }
+        instructions.push(DbTransactionInstruction {
+            instruction_index: instruction_index as i16,
+            program: parsed.program,
+            program_id: program_id.to_bytes().to_vec(),
+            parsed: parsed.parsed,
+        });
+    }
+    instructions
+}
+
+impl SimplePostgresClient {
+    /// Build the statement inserting a parsed instruction into the
+    /// transaction_instruction table.
+    pub fn build_transaction_instruction_insert_statement(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Statement, GeyserPluginError> {
+        let stmt = "INSERT INTO transaction_instruction (slot, signature, instruction_index, \
+            program, program_id, parsed, updated_on) \
+            SELECT $1, $2, t.instruction_index, t.program, t.program_id, t.parsed, $3 \
+            FROM UNNEST($4::SMALLINT[], $5::VARCHAR[], $6::BYTEA[], $7::JSONB[]) \
+            AS t (instruction_index, program, program_id, parsed) \
+            ON CONFLICT DO NOTHING";
+
+        let stmt = client.prepare(stmt);
+
+        match stmt {
+            Err(err) => {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "Error in preparing for the transaction instruction insert PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                        err, config.host, config.user, config
+                    ),
+                })))
+            }
+            Ok(stmt) => Ok(stmt),
+        }
+    }
+
+    /// Insert the parsed instructions of the transaction, the signature is in the same
+    /// storage as in the transaction table.
+    pub(crate) fn insert_transaction_instructions(
+        client: &mut Client,
+        statement: &Statement,
+        slot: i64,
+        signature: &(dyn ToSql + Sync),
+        instructions: &[DbTransactionInstruction],
+        write_stats: &WriteStats,
+    ) -> Result<(), GeyserPluginError> {
+        if instructions.is_empty() {
+            return Ok(());
+        }
+        let updated_on = Utc::now().naive_utc();
+        let instruction_indexes: Vec<i16> = instructions
+            .iter()
+            .map(|instruction| instruction.instruction_index)
+            .collect();
+        let programs: Vec<&str> = instructions
+            .iter()
+            .map(|instruction| instruction.program.as_str())
+            .collect();
+        let program_ids: Vec<&Vec<u8>> = instructions
+            .iter()
+            .map(|instruction| &instruction.program_id)
+            .collect();
+        let parsed: Vec<&serde_json::Value> = instructions
+            .iter()
+            .map(|instruction| &instruction.parsed)
+            .collect();
+
+        let params: [&(dyn ToSql + Sync); 7] = [
+            &slot,
+            signature,
+            &updated_on,
+            &instruction_indexes,
+            &programs,
+            &program_ids,
+            &parsed,
+        ];
+        let result = client.execute(statement, &params);
+
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to persist the transaction instructions to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::TransactionUpdateError { msg });
+        }
+        write_stats.record(
+            "transaction_instruction",
+            instructions.len(),
+            statement,
+            &params,
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::postgres_client_transaction::tests::TestTransaction,
+        serde_json::json,
+        solana_sdk::{
+            hash::Hash,
+            signature::{Keypair, Signer},
+            transaction::TransactionError,
+        },
+    };
+
+    #[test]
+    fn test_build_transaction_instructions() {
+        let keypair = Keypair::new();
+        let destination = Keypair::new().pubkey();
+        let mut transaction = TestTransaction::new(solana_system_transaction::transfer(
+            &keypair,
+            &destination,
+            42,
+            Hash::default(),
+        ));
+        let transaction_info = transaction.transaction_info();
+
+        let programs = ["system".to_string()];
+        assert_eq!(
+            build_transaction_instructions(&transaction_info, &programs),
+            vec![DbTransactionInstruction {
+                instruction_index: 0,
+                program: "system".to_string(),
+                program_id: vec![0; 32],
+                parsed: json!({
+                    "type": "transfer",
+                    "info": {
+                        "source": keypair.pubkey().to_string(),
+                        "destination": destination.to_string(),
+                        "lamports": 42,
+                    }
+                }),
+            }]
+        );
+        assert!(
+            build_transaction_instructions(&transaction_info, &["vote".to_string()]).is_empty()
+        );
+
+        transaction.meta.status = Err(TransactionError::AccountNotFound);
+        assert!(
+            build_transaction_instructions(&transaction.transaction_info(), &programs).is_empty()
+        );
+    }
+}
@@ -0,0 +1,211 @@
+/// Module responsible for extracting the memos of the SPL Memo instructions of the
+/// transactions into the PostgreSQL database.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        geyser_interface::ReplicaTransactionInfo,
+        postgres_client::{
+            postgres_client_transaction::iter_instructions,
+            postgres_client_write_stats::WriteStats, SimplePostgresClient,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    postgres::{Client, Statement},
+    postgres_types::ToSql,
+    serde_derive::{Deserialize, Serialize},
+    solana_sdk::{instruction::CompiledInstruction, message::AccountKeys},
+};
+
+/// The program ids of the SPL Memo programs inlined to avoid an external dependency on
+/// the spl-memo crate
+mod spl_memo {
+    solana_sdk::declare_id!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+    pub(crate) mod v1 {
+        solana_sdk::declare_id!("Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo");
+    }
+}
+
+/// A memo of an SPL Memo instruction, at the top level or invoked by another program
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DbTransactionMemo {
+    pub instruction_index: i16,
+    /// The index among the inner instructions, None for the top level instructions
+    pub inner_instruction_index: Option<i16>,
+    pub memo: String,
+}
+
+/// Parse the memo of the instruction, None if it is not an SPL Memo instruction.
+fn parse_memo(instruction: &CompiledInstruction, account_keys: &AccountKeys) -> Option<String> {
+    let program_id = account_keys.get(instruction.program_id_index as usize)?;
+    if *program_id != spl_memo::ID && *program_id != spl_memo::v1::ID {
+        return None;
+    }
+    // The memo programs fail the instructions of invalid UTF-8, the lossy conversion
+    // only guards against a program not enforcing it.
+    Some(String::from_utf8_lossy(&instruction.data).into_owned())
+}
+
+/// Get the memos of the transaction, none for the failed transactions as their memos
+/// are not to be attributed.
+pub fn build_transaction_memos(
+    transaction_info: &ReplicaTransactionInfo,
+) -> Vec<DbTransactionMemo> {
+    let meta = transaction_info.transaction_status_meta;
+    if meta.status.is_err() {
+        return Vec::default();
+    }
+    let message = transaction_info.transaction.message();
+    let account_keys = message.account_keys();
+
+    let mut memos = Vec::default();
+    for (instruction_index, inner_instruction_index, instruction) in
+        iter_instructions(transaction_info)
+    {
+        if let Some(memo) = parse_memo(instruction, &account_keys) {
+            memos.push(DbTransactionMemo {
+                instruction_index: instruction_index as i16,
+                inner_instruction_index,
+                memo,
+            });
+        }
+    }
+    memos
+}
+
+impl SimplePostgresClient {
+    /// Build the statement inserting a memo into the transaction_memo table.
+    pub fn build_transaction_memo_insert_statement(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Statement, GeyserPluginError> {
+        let stmt = "INSERT INTO transaction_memo (slot, signature, memo_index, instruction_index, \
+            inner_instruction_index, memo, updated_on) \
+            SELECT $1, $2, t.memo_index - 1, t.instruction_index, t.inner_instruction_index, t.memo, $3 \
+            FROM UNNEST($4::SMALLINT[], $5::SMALLINT[], $6::TEXT[]) \
+            WITH ORDINALITY AS t (instruction_index, inner_instruction_index, memo, memo_index) \
+            ON CONFLICT DO NOTHING";
+
+        let stmt = client.prepare(stmt);
+
+        match stmt {
+            Err(err) => {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "Error in preparing for the transaction memo insert PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                        err, config.host, config.user, config
+                    ),
+                })))
+            }
+            Ok(stmt) => Ok(stmt),
+        }
+    }
+
+    /// Insert the memos of the transaction, the signature is in the same storage as in
+    /// the transaction table.
+    pub(crate) fn insert_transaction_memos(
+        client: &mut Client,
+        statement: &Statement,
+        slot: i64,
+        signature: &(dyn ToSql + Sync),
+        memos: &[DbTransactionMemo],
+        write_stats: &WriteStats,
+    ) -> Result<(), GeyserPluginError> {
+        if memos.is_empty() {
+            return Ok(());
+        }
+        let updated_on = Utc::now().naive_utc();
+        let instruction_indexes: Vec<i16> =
+            memos.iter().map(|memo| memo.instruction_index).collect();
+        let inner_instruction_indexes: Vec<Option<i16>> = memos
+            .iter()
+            .map(|memo| memo.inner_instruction_index)
+            .collect();
+        let texts: Vec<&str> = memos.iter().map(|memo| memo.memo.as_str()).collect();
+
+        let params: [&(dyn ToSql + Sync); 6] = [
+            &slot,
+            signature,
+            &updated_on,
+            &instruction_indexes,
+            &inner_instruction_indexes,
+            &texts,
+        ];
+        let result = client.execute(statement, &params);
+
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to persist the transaction memos to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::TransactionUpdateError { msg });
+        }
+        write_stats.record("transaction_memo", memos.len(), statement, &params);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::postgres_client_transaction::tests::TestTransaction,
+        solana_sdk::{
+            hash::Hash,
+            instruction::Instruction,
+            signature::{Keypair, Signer},
+            transaction::{Transaction, TransactionError},
+        },
+        solana_transaction_status::{InnerInstruction, InnerInstructions},
+    };
+
+    #[test]
+    fn test_build_transaction_memos() {
+        let keypair = Keypair::new();
+        let instruction = Instruction::new_with_bytes(spl_memo::ID, b"deposit 1234", vec![]);
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&keypair.pubkey()),
+            &[&keypair],
+            Hash::default(),
+        );
+        let mut transaction = TestTransaction::new(transaction);
+        // An inner memo, as if invoked by another program
+        let inner_instruction = CompiledInstruction {
+            program_id_index: 1,
+            accounts: vec![],
+            data: b"invoked".to_vec(),
+        };
+        transaction.meta.inner_instructions = Some(vec![InnerInstructions {
+            index: 0,
+            instructions: vec![InnerInstruction {
+                instruction: inner_instruction,
+                stack_height: Some(2),
+            }],
+        }]);
+
+        assert_eq!(
+            build_transaction_memos(&transaction.transaction_info()),
+            vec![
+                DbTransactionMemo {
+                    instruction_index: 0,
+                    inner_instruction_index: None,
+                    memo: "deposit 1234".to_string(),
+                },
+                DbTransactionMemo {
+                    instruction_index: 0,
+                    inner_instruction_index: Some(0),
+                    memo: "invoked".to_string(),
+                },
+            ]
+        );
+
+        transaction.meta.status = Err(TransactionError::AccountNotFound);
+        assert!(build_transaction_memos(&transaction.transaction_info()).is_empty());
+    }
+}
@@ -0,0 +1,471 @@
+/// Module responsible for writing the balances, the token balances, the rewards and the
+/// log messages of the transaction metadata into relational tables keyed by the slot and
+/// the signature, along with the composite meta of the transaction table, for the
+/// analytics queries.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        geyser_interface::ReplicaTransactionInfo,
+        postgres_client::{
+            postgres_client_write_stats::WriteStats, DbReward, DbRewardType, SimplePostgresClient,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    postgres::{Client, Statement},
+    postgres_types::ToSql,
+    serde_derive::{Deserialize, Serialize},
+    solana_sdk::message::AccountKeys,
+    solana_transaction_status::TransactionTokenBalance,
+    std::collections::BTreeMap,
+};
+
+/// The token balance of an account of a transaction before and after it, None before
+/// for the token accounts it creates and after for the ones it closes
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DbTokenBalanceChange {
+    pub account_index: i16,
+    /// The base58 encoded token account, empty when not in the account keys
+    pub account: String,
+    pub mint: String,
+    pub owner: String,
+    pub program_id: String,
+    pub decimals: i16,
+    /// The raw amounts, as the u64 amounts do not fit in a BIGINT
+    pub pre_amount: Option<String>,
+    pub post_amount: Option<String>,
+}
+
+/// The lamports of an account of a transaction before and after it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DbAccountBalance {
+    pub account_index: i16,
+    /// The base58 encoded account, empty when not in the account keys
+    pub account: String,
+    pub pre_balance: i64,
+    pub post_balance: i64,
+}
+
+fn account_balances(
+    pre_balances: &[u64],
+    post_balances: &[u64],
+    account_keys: &AccountKeys,
+) -> Vec<DbAccountBalance> {
+    pre_balances
+        .iter()
+        .zip(post_balances)
+        .enumerate()
+        .map(
+            |(account_index, (pre_balance, post_balance))| DbAccountBalance {
+                account_index: account_index as i16,
+                account: account_keys
+                    .get(account_index)
+                    .map(|account| account.to_string())
+                    .unwrap_or_default(),
+                pre_balance: *pre_balance as i64,
+                post_balance: *post_balance as i64,
+            },
+        )
+        .collect()
+}
+
+/// Get the balances of the accounts of the transaction, aligned with its account keys,
+/// including the addresses loaded from the address lookup tables.
+pub fn build_account_balances(transaction_info: &ReplicaTransactionInfo) -> Vec<DbAccountBalance> {
+    let meta = transaction_info.transaction_status_meta;
+    account_balances(
+        &meta.pre_balances,
+        &meta.post_balances,
+        &transaction_info.transaction.message().account_keys(),
+    )
+}
+
+fn token_balance_changes(
+    pre_token_balances: &[TransactionTokenBalance],
+    post_token_balances: &[TransactionTokenBalance],
+    account_keys: &AccountKeys,
+) -> Vec<DbTokenBalanceChange> {
+    let mut changes = BTreeMap::<u8, DbTokenBalanceChange>::default();
+    for (is_post, token_balance) in pre_token_balances
+        .iter()
+        .map(|token_balance| (false, token_balance))
+        .chain(
+            post_token_balances
+                .iter()
+                .map(|token_balance| (true, token_balance)),
+        )
+    {
+        let change = changes
+            .entry(token_balance.account_index)
+            .or_insert_with(|| DbTokenBalanceChange {
+                account_index: token_balance.account_index as i16,
+                account: account_keys
+                    .get(token_balance.account_index as usize)
+                    .map(|account| account.to_string())
+                    .unwrap_or_default(),
+                mint: token_balance.mint.clone(),
+                owner: token_balance.owner.clone(),
+                program_id: token_balance.program_id.clone(),
+                decimals: token_balance.ui_token_amount.decimals as i16,
+                pre_amount: None,
+                post_amount: None,
+            });
+        let amount = Some(token_balance.ui_token_amount.amount.clone());
+        if is_post {
+            // The owner of the token account may change within the transaction
+            change.owner = token_balance.owner.clone();
+            change.post_amount = amount;
+        } else {
+            change.pre_amount = amount;
+        }
+    }
+    changes.into_values().collect()
+}
+
+/// Get the token balance changes of the transaction, by the account index.
+pub fn build_token_balance_changes(
+    transaction_info: &ReplicaTransactionInfo,
+) -> Vec<DbTokenBalanceChange> {
+    let meta = transaction_info.transaction_status_meta;
+    token_balance_changes(
+        meta.pre_token_balances.as_deref().unwrap_or_default(),
+        meta.post_token_balances.as_deref().unwrap_or_default(),
+        &transaction_info.transaction.message().account_keys(),
+    )
+}
+
+fn prepare_statement(
+    client: &mut Client,
+    config: &AccountsDbPluginPostgresConfig,
+    stmt: &str,
+    description: &str,
+) -> Result<Statement, GeyserPluginError> {
+    client.prepare(stmt).map_err(|err| {
+        GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+            msg: format!(
+                "Error in preparing for the {} PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                description, err, config.host, config.user, config
+            ),
+        }))
+    })
+}
+
+fn execute_insert(
+    client: &mut Client,
+    statement: &Statement,
+    params: &[&(dyn ToSql + Sync)],
+    table: &'static str,
+    row_count: usize,
+    write_stats: &WriteStats,
+) -> Result<(), GeyserPluginError> {
+    if let Err(err) = client.execute(statement, params) {
+        let msg = format!(
+            "Failed to persist the {} rows to the PostgreSQL database. Error: {:?}",
+            table, err
+        );
+        error!("{}", msg);
+        return Err(GeyserPluginError::TransactionUpdateError { msg });
+    }
+    write_stats.record(table, row_count, statement, params);
+    Ok(())
+}
+
+/// The statements inserting the normalized transaction metadata
+pub(crate) struct TransactionMetaStatements {
+    insert_balance_stmt: Statement,
+    insert_token_balance_stmt: Statement,
+    insert_reward_stmt: Statement,
+    insert_log_stmt: Statement,
+}
+
+impl SimplePostgresClient {
+    /// Build the statements inserting the balances, the token balances, the rewards and
+    /// the log messages of a transaction.
+    pub(crate) fn build_transaction_meta_statements(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<TransactionMetaStatements, GeyserPluginError> {
+        let insert_balance_stmt = prepare_statement(
+            client,
+            config,
+            "INSERT INTO transaction_balance (slot, signature, account_index, account, pre_balance, \
+            post_balance, updated_on) \
+            SELECT $1, $2, t.account_index, t.account, t.pre_balance, t.post_balance, $3 \
+            FROM UNNEST($4::SMALLINT[], $5::VARCHAR[], $6::BIGINT[], $7::BIGINT[]) \
+            AS t (account_index, account, pre_balance, post_balance) \
+            ON CONFLICT DO NOTHING",
+            "transaction balance insert",
+        )?;
+        let insert_token_balance_stmt = prepare_statement(
+            client,
+            config,
+            "INSERT INTO transaction_token_balance (slot, signature, account_index, account, mint, owner, \
+            program_id, decimals, pre_amount, post_amount, updated_on) \
+            SELECT $1, $2, t.account_index, t.account, t.mint, t.owner, t.program_id, t.decimals, \
+            t.pre_amount::NUMERIC(20), t.post_amount::NUMERIC(20), $3 \
+            FROM UNNEST($4::SMALLINT[], $5::VARCHAR[], $6::VARCHAR[], $7::VARCHAR[], $8::VARCHAR[], \
+            $9::SMALLINT[], $10::TEXT[], $11::TEXT[]) \
+            AS t (account_index, account, mint, owner, program_id, decimals, pre_amount, post_amount) \
+            ON CONFLICT DO NOTHING",
+            "transaction token balance insert",
+        )?;
+        let insert_reward_stmt = prepare_statement(
+            client,
+            config,
+            "INSERT INTO transaction_reward (slot, signature, reward_index, pubkey, lamports, post_balance, \
+            reward_type, commission, updated_on) \
+            SELECT $1, $2, t.reward_index - 1, t.pubkey, t.lamports, t.post_balance, t.reward_type, \
+            t.commission, $3 \
+            FROM UNNEST($4::VARCHAR[], $5::BIGINT[], $6::BIGINT[], $7::\"RewardType\"[], $8::SMALLINT[]) \
+            WITH ORDINALITY AS t (pubkey, lamports, post_balance, reward_type, commission, reward_index) \
+            ON CONFLICT DO NOTHING",
+            "transaction reward insert",
+        )?;
+        let insert_log_stmt = prepare_statement(
+            client,
+            config,
+            "INSERT INTO transaction_log (slot, signature, log_index, message, updated_on) \
+            SELECT $1, $2, t.log_index - 1, t.message, $3 \
+            FROM UNNEST($4::TEXT[]) WITH ORDINALITY AS t (message, log_index) \
+            ON CONFLICT DO NOTHING",
+            "transaction log insert",
+        )?;
+        Ok(TransactionMetaStatements {
+            insert_balance_stmt,
+            insert_token_balance_stmt,
+            insert_reward_stmt,
+            insert_log_stmt,
+        })
+    }
+
+    /// Insert the balances, the token balance changes, the rewards and the log messages
+    /// of the transaction, the signature is in the same storage as in the transaction
+    /// table.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn insert_transaction_meta(
+        client: &mut Client,
+        statements: &TransactionMetaStatements,
+        slot: i64,
+        signature: &(dyn ToSql + Sync),
+        account_balances: &[DbAccountBalance],
+        token_balance_changes: &[DbTokenBalanceChange],
+        rewards: &[DbReward],
+        log_messages: &[String],
+        write_stats: &WriteStats,
+    ) -> Result<(), GeyserPluginError> {
+        let updated_on = Utc::now().naive_utc();
+
+        if !account_balances.is_empty() {
+            let account_indexes: Vec<i16> = account_balances
+                .iter()
+                .map(|balance| balance.account_index)
+                .collect();
+            let accounts: Vec<&str> = account_balances
+                .iter()
+                .map(|balance| balance.account.as_str())
+                .collect();
+            let pre_balances: Vec<i64> = account_balances
+                .iter()
+                .map(|balance| balance.pre_balance)
+                .collect();
+            let post_balances: Vec<i64> = account_balances
+                .iter()
+                .map(|balance| balance.post_balance)
+                .collect();
+            let params: [&(dyn ToSql + Sync); 7] = [
+                &slot,
+                signature,
+                &updated_on,
+                &account_indexes,
+                &accounts,
+                &pre_balances,
+                &post_balances,
+            ];
+            execute_insert(
+                client,
+                &statements.insert_balance_stmt,
+                &params,
+                "transaction_balance",
+                account_balances.len(),
+                write_stats,
+            )?;
+        }
+
+        if !token_balance_changes.is_empty() {
+            let changes = token_balance_changes;
+            let account_indexes: Vec<i16> =
+                changes.iter().map(|change| change.account_index).collect();
+            let accounts: Vec<&str> = changes
+                .iter()
+                .map(|change| change.account.as_str())
+                .collect();
+            let mints: Vec<&str> = changes.iter().map(|change| change.mint.as_str()).collect();
+            let owners: Vec<&str> = changes.iter().map(|change| change.owner.as_str()).collect();
+            let program_ids: Vec<&str> = changes
+                .iter()
+                .map(|change| change.program_id.as_str())
+                .collect();
+            let decimals: Vec<i16> = changes.iter().map(|change| change.decimals).collect();
+            let pre_amounts: Vec<Option<&str>> = changes
+                .iter()
+                .map(|change| change.pre_amount.as_deref())
+                .collect();
+            let post_amounts: Vec<Option<&str>> = changes
+                .iter()
+                .map(|change| change.post_amount.as_deref())
+                .collect();
+            let params: [&(dyn ToSql + Sync); 11] = [
+                &slot,
+                signature,
+                &updated_on,
+                &account_indexes,
+                &accounts,
+                &mints,
+                &owners,
+                &program_ids,
+                &decimals,
+                &pre_amounts,
+                &post_amounts,
+            ];
+            execute_insert(
+                client,
+                &statements.insert_token_balance_stmt,
+                &params,
+                "transaction_token_balance",
+                changes.len(),
+                write_stats,
+            )?;
+        }
+
+        if !rewards.is_empty() {
+            let pubkeys: Vec<&str> = rewards
+                .iter()
+                .map(|reward| reward.pubkey.as_str())
+                .collect();
+            let lamports: Vec<i64> = rewards.iter().map(|reward| reward.lamports).collect();
+            let post_balances: Vec<i64> =
+                rewards.iter().map(|reward| reward.post_balance).collect();
+            let reward_types: Vec<Option<DbRewardType>> = rewards
+                .iter()
+                .map(|reward| reward.reward_type.clone())
+                .collect();
+            let commissions: Vec<Option<i16>> =
+                rewards.iter().map(|reward| reward.commission).collect();
+            let params: [&(dyn ToSql + Sync); 8] = [
+                &slot,
+                signature,
+                &updated_on,
+                &pubkeys,
+                &lamports,
+                &post_balances,
+                &reward_types,
+                &commissions,
+            ];
+            execute_insert(
+                client,
+                &statements.insert_reward_stmt,
+                &params,
+                "transaction_reward",
+                rewards.len(),
+                write_stats,
+            )?;
+        }
+
+        if !log_messages.is_empty() {
+            let params: [&(dyn ToSql + Sync); 4] = [&slot, signature, &updated_on, &log_messages];
+            execute_insert(
+                client,
+                &statements.insert_log_stmt,
+                &params,
+                "transaction_log",
+                log_messages.len(),
+                write_stats,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*, solana_account_decoder::parse_token::UiTokenAmount, solana_sdk::pubkey::Pubkey,
+    };
+
+    fn token_balance(account_index: u8, owner: &str, amount: &str) -> TransactionTokenBalance {
+        TransactionTokenBalance {
+            account_index,
+            mint: "mint".to_string(),
+            ui_token_amount: UiTokenAmount {
+                ui_amount: None,
+                decimals: 6,
+                amount: amount.to_string(),
+                ui_amount_string: String::default(),
+            },
+            owner: owner.to_string(),
+            program_id: "program".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_account_balances() {
+        let keys = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let account_keys = AccountKeys::new(&keys, None);
+        let balances = account_balances(&[100, 0, 7], &[58, 42, 7], &account_keys);
+        assert_eq!(
+            balances,
+            vec![
+                DbAccountBalance {
+                    account_index: 0,
+                    account: keys[0].to_string(),
+                    pre_balance: 100,
+                    post_balance: 58,
+                },
+                DbAccountBalance {
+                    account_index: 1,
+                    account: keys[1].to_string(),
+                    pre_balance: 0,
+                    post_balance: 42,
+                },
+                DbAccountBalance {
+                    account_index: 2,
+                    account: String::default(),
+                    pre_balance: 7,
+                    post_balance: 7,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_token_balance_changes() {
+        let keys = [
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ];
+        let account_keys = AccountKeys::new(&keys, None);
+        // The account 1 is transferred to another owner, the account 2 is created
+        let changes = token_balance_changes(
+            &[token_balance(1, "alice", "18446744073709551615")],
+            &[token_balance(2, "bob", "5"), token_balance(1, "carol", "0")],
+            &account_keys,
+        );
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].account_index, 1);
+        assert_eq!(changes[0].account, keys[1].to_string());
+        assert_eq!(changes[0].owner, "carol");
+        assert_eq!(changes[0].decimals, 6);
+        assert_eq!(
+            changes[0].pre_amount.as_deref(),
+            Some("18446744073709551615")
+        );
+        assert_eq!(changes[0].post_amount.as_deref(), Some("0"));
+        assert_eq!(changes[1].account, keys[2].to_string());
+        assert_eq!(changes[1].pre_amount, None);
+        assert_eq!(changes[1].post_amount.as_deref(), Some("5"));
+    }
+}
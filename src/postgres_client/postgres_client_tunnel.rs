@@ -0,0 +1,532 @@
+/// Module responsible for the tunnel to the database in a private network, not directly
+/// reachable from the validator host. The tunnel listens on a local port, forwarding
+/// the connections to the database either through a SOCKS5 proxy or through an SSH jump
+/// host with the ssh command, and the connections of the plugin are made to the local
+/// port, the host name of the database being kept for the verification of its
+/// certificate.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError, TunnelConfig,
+        },
+        postgres_client::DEFAULT_POSTGRES_PORT,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    log::*,
+    postgres::config::Host,
+    std::{
+        io::{self, Read, Write},
+        net::{IpAddr, Ipv4Addr, Shutdown, TcpListener, TcpStream},
+        process::{Child, Command, Stdio},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread::{sleep, Builder, JoinHandle},
+        time::{Duration, Instant},
+    },
+};
+
+const TUNNEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long the ssh command is given to authenticate and listen on the local port
+const SSH_READY_TIMEOUT: Duration = Duration::from_secs(30);
+const SSH_RESTART_DELAY: Duration = Duration::from_secs(1);
+
+const SOCKS5_VERSION: u8 = 5;
+const SOCKS5_NO_AUTH: u8 = 0;
+const SOCKS5_USER_PASSWORD: u8 = 2;
+const SOCKS5_USER_PASSWORD_VERSION: u8 = 1;
+const SOCKS5_CONNECT: u8 = 1;
+const SOCKS5_IPV4: u8 = 1;
+const SOCKS5_DOMAIN: u8 = 3;
+const SOCKS5_IPV6: u8 = 4;
+
+fn to_tunnel_error(msg: String) -> GeyserPluginError {
+    error!("{}", msg);
+    GeyserPluginError::Custom(Box::new(
+        AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+    ))
+}
+
+/// The host and the port of the database the tunnel forwards the connections to, from
+/// the connection_str or the host and the port of the config.
+fn tunnel_target(config: &AccountsDbPluginPostgresConfig) -> Result<(String, u16), String> {
+    let Some(connection_str) = &config.connection_str else {
+        let host = config
+            .host
+            .clone()
+            .ok_or("The \"tunnel\" requires the \"connection_str\" or the \"host\" to be set")?;
+        return Ok((host, config.port.unwrap_or(DEFAULT_POSTGRES_PORT)));
+    };
+    let pg_config: postgres::Config = connection_str
+        .parse()
+        .map_err(|err| format!("The \"connection_str\" cannot be parsed: {}", err))?;
+    let port = match pg_config.get_ports() {
+        [] => DEFAULT_POSTGRES_PORT,
+        [port] => *port,
+        _ => return Err("The \"tunnel\" requires a single port in the \"connection_str\"".into()),
+    };
+    match (pg_config.get_hosts(), pg_config.get_hostaddrs()) {
+        (_, [hostaddr]) => Ok((hostaddr.to_string(), port)),
+        ([Host::Tcp(host)], []) => Ok((host.clone(), port)),
+        _ => Err("The \"tunnel\" requires a single TCP host in the \"connection_str\"".to_string()),
+    }
+}
+
+/// The config of the connections made through the tunnel listening on the local port.
+/// The host is kept for the verification of the certificate of the server, the
+/// connections being made to the hostaddr of the local end of the tunnel.
+pub(crate) fn tunneled_config(
+    connection_str: &str,
+    local_port: u16,
+) -> Result<postgres::Config, postgres::Error> {
+    let original: postgres::Config = connection_str.parse()?;
+    let mut config = postgres::Config::new();
+    if let Some(user) = original.get_user() {
+        config.user(user);
+    }
+    if let Some(password) = original.get_password() {
+        config.password(password);
+    }
+    if let Some(dbname) = original.get_dbname() {
+        config.dbname(dbname);
+    }
+    if let Some(options) = original.get_options() {
+        config.options(options);
+    }
+    if let Some(application_name) = original.get_application_name() {
+        config.application_name(application_name);
+    }
+    if let Some(connect_timeout) = original.get_connect_timeout() {
+        config.connect_timeout(*connect_timeout);
+    }
+    if let Some(tcp_user_timeout) = original.get_tcp_user_timeout() {
+        config.tcp_user_timeout(*tcp_user_timeout);
+    }
+    if let Some(keepalives_interval) = original.get_keepalives_interval() {
+        config.keepalives_interval(keepalives_interval);
+    }
+    if let Some(keepalives_retries) = original.get_keepalives_retries() {
+        config.keepalives_retries(keepalives_retries);
+    }
+    config
+        .ssl_mode(original.get_ssl_mode())
+        .keepalives(original.get_keepalives())
+        .keepalives_idle(original.get_keepalives_idle())
+        .target_session_attrs(original.get_target_session_attrs())
+        .channel_binding(original.get_channel_binding());
+    if let Some(Host::Tcp(host)) = original.get_hosts().first() {
+        config.host(host);
+    }
+    config
+        .hostaddr(IpAddr::V4(Ipv4Addr::LOCALHOST))
+        .port(local_port);
+    Ok(config)
+}
+
+/// Connect to the target through the SOCKS5 proxy, authenticating with the username
+/// and the password when they are configured.
+fn socks5_connect(
+    proxy: &str,
+    credentials: Option<(&str, &str)>,
+    host: &str,
+    port: u16,
+) -> io::Result<TcpStream> {
+    let mut stream = TcpStream::connect(proxy)?;
+    stream.set_nodelay(true)?;
+    let method = if credentials.is_some() {
+        SOCKS5_USER_PASSWORD
+    } else {
+        SOCKS5_NO_AUTH
+    };
+    stream.write_all(&[SOCKS5_VERSION, 1, method])?;
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply)?;
+    if reply != [SOCKS5_VERSION, method] {
+        return Err(io::Error::other(
+            "the SOCKS5 proxy rejected the authentication method",
+        ));
+    }
+    let length = |value: &str| {
+        u8::try_from(value.len())
+            .map_err(|_| io::Error::other(format!("{:?} is longer than 255 bytes", value)))
+    };
+    if let Some((user, password)) = credentials {
+        let mut request = vec![SOCKS5_USER_PASSWORD_VERSION, length(user)?];
+        request.extend_from_slice(user.as_bytes());
+        request.push(length(password)?);
+        request.extend_from_slice(password.as_bytes());
+        stream.write_all(&request)?;
+        stream.read_exact(&mut reply)?;
+        if reply[1] != 0 {
+            return Err(io::Error::other(
+                "the SOCKS5 proxy rejected the username and password",
+            ));
+        }
+    }
+
+    let mut request = vec![SOCKS5_VERSION, SOCKS5_CONNECT, 0];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(SOCKS5_IPV4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(SOCKS5_IPV6);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            request.extend_from_slice(&[SOCKS5_DOMAIN, length(host)?]);
+            request.extend_from_slice(host.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request)?;
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header)?;
+    if header[1] != 0 {
+        return Err(io::Error::other(format!(
+            "the SOCKS5 proxy failed to connect to {}:{} with the reply {}",
+            host, port, header[1]
+        )));
+    }
+    // The address the proxy bound, followed by its port, is skipped
+    let address_length = match header[3] {
+        SOCKS5_IPV4 => 4,
+        SOCKS5_IPV6 => 16,
+        _ => {
+            let mut length = [0u8; 1];
+            stream.read_exact(&mut length)?;
+            length[0] as usize
+        }
+    };
+    stream.read_exact(&mut vec![0u8; address_length + 2])?;
+    Ok(stream)
+}
+
+/// Copy the bytes both ways between the connection accepted and the one to the
+/// database, until both are closed.
+fn forward(local: TcpStream, remote: TcpStream) -> io::Result<()> {
+    let (mut local_reader, mut remote_writer) = (local.try_clone()?, remote.try_clone()?);
+    let upstream = Builder::new()
+        .name("pg-tunnel-upstream".to_string())
+        .spawn(move || {
+            let _ = io::copy(&mut local_reader, &mut remote_writer);
+            let _ = remote_writer.shutdown(Shutdown::Write);
+        })?;
+    let (mut remote_reader, mut local_writer) = (remote, local);
+    let _ = io::copy(&mut remote_reader, &mut local_writer);
+    let _ = local_writer.shutdown(Shutdown::Write);
+    let _ = upstream.join();
+    Ok(())
+}
+
+fn local_listener() -> io::Result<(TcpListener, u16)> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let port = listener.local_addr()?.port();
+    Ok((listener, port))
+}
+
+fn start_socks5(
+    tunnel: &TunnelConfig,
+    proxy: String,
+    target: (String, u16),
+    exit: Arc<AtomicBool>,
+) -> io::Result<(u16, JoinHandle<()>)> {
+    let (listener, local_port) = local_listener()?;
+    listener.set_nonblocking(true)?;
+    let credentials = tunnel
+        .socks5_user
+        .clone()
+        .zip(tunnel.socks5_password.clone());
+    let acceptor = Builder::new()
+        .name("pg-tunnel-socks5".to_string())
+        .spawn(move || {
+            while !exit.load(Ordering::Relaxed) {
+                let local = match listener.accept() {
+                    Ok((local, _)) => local,
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        sleep(TUNNEL_POLL_INTERVAL);
+                        continue;
+                    }
+                    Err(err) => {
+                        error!("Failed to accept a connection of the tunnel: {}", err);
+                        sleep(TUNNEL_POLL_INTERVAL);
+                        continue;
+                    }
+                };
+                let proxy = proxy.clone();
+                let credentials = credentials.clone();
+                let (host, port) = target.clone();
+                let _ = Builder::new()
+                    .name("pg-tunnel-conn".to_string())
+                    .spawn(move || {
+                        let credentials = credentials
+                            .as_ref()
+                            .map(|(user, password)| (user.as_str(), password.as_str()));
+                        let result = local.set_nonblocking(false).and_then(|()| {
+                            let remote = socks5_connect(&proxy, credentials, &host, port)?;
+                            forward(local, remote)
+                        });
+                        if let Err(err) = result {
+                            error!(
+                                "Failed to connect to {}:{} through the SOCKS5 proxy {}: {}",
+                                host, port, proxy, err
+                            );
+                        }
+                    });
+            }
+        })?;
+    Ok((local_port, acceptor))
+}
+
+/// The ssh command forwarding the local port to the target through the jump host.
+fn ssh_command(
+    tunnel: &TunnelConfig,
+    jump_host: &str,
+    local_port: u16,
+    target: &(String, u16),
+) -> Command {
+    let (host, port) = target;
+    let host = if host.contains(':') {
+        format!("[{}]", host)
+    } else {
+        host.clone()
+    };
+    let mut command = Command::new("ssh");
+    command
+        .args([
+            "-N",
+            "-o",
+            "ExitOnForwardFailure=yes",
+            "-o",
+            "BatchMode=yes",
+        ])
+        .args(["-o", "ServerAliveInterval=15", "-L"])
+        .arg(format!("127.0.0.1:{}:{}:{}", local_port, host, port));
+    if let Some(ssh_port) = tunnel.ssh_port {
+        command.arg("-p").arg(ssh_port.to_string());
+    }
+    if let Some(identity_file) = &tunnel.ssh_identity_file {
+        command.arg("-i").arg(identity_file);
+    }
+    command
+        .arg(jump_host)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null());
+    command
+}
+
+/// Spawn the ssh command and wait for it to listen on the local port.
+fn spawn_ssh(mut command: Command, local_port: u16) -> io::Result<Child> {
+    let mut child = command.spawn()?;
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Err(io::Error::other(format!(
+                "the ssh command exited with {}",
+                status
+            )));
+        }
+        if TcpStream::connect((Ipv4Addr::LOCALHOST, local_port)).is_ok() {
+            return Ok(child);
+        }
+        if started.elapsed() >= SSH_READY_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::other(format!(
+                "the ssh command is not listening on the port {} after {:?}",
+                local_port, SSH_READY_TIMEOUT
+            )));
+        }
+        sleep(TUNNEL_POLL_INTERVAL);
+    }
+}
+
+/// Start the ssh command, restarting it when it exits, such as on the drop of the
+/// connection to the jump host, until the tunnel is stopped.
+fn start_ssh(
+    tunnel: &TunnelConfig,
+    jump_host: String,
+    target: (String, u16),
+    exit: Arc<AtomicBool>,
+) -> io::Result<(u16, JoinHandle<()>)> {
+    // The port is released for the ssh command to listen on
+    let local_port = local_listener()?.1;
+    let tunnel = tunnel.clone();
+    let mut child = spawn_ssh(
+        ssh_command(&tunnel, &jump_host, local_port, &target),
+        local_port,
+    )?;
+    let supervisor = Builder::new()
+        .name("pg-tunnel-ssh".to_string())
+        .spawn(move || {
+            while !exit.load(Ordering::Relaxed) {
+                sleep(TUNNEL_POLL_INTERVAL);
+                let status = match child.try_wait() {
+                    Ok(Some(status)) => status,
+                    Ok(None) => continue,
+                    Err(err) => {
+                        error!("Failed to check the ssh command of the tunnel: {}", err);
+                        continue;
+                    }
+                };
+                warn!(
+                    "The ssh command of the tunnel through {} exited with {}, restarting it",
+                    jump_host, status
+                );
+                sleep(SSH_RESTART_DELAY);
+                match spawn_ssh(
+                    ssh_command(&tunnel, &jump_host, local_port, &target),
+                    local_port,
+                ) {
+                    Ok(restarted) => child = restarted,
+                    Err(err) => error!("Failed to restart the ssh command of the tunnel: {}", err),
+                }
+            }
+            let _ = child.kill();
+            let _ = child.wait();
+        })?;
+    Ok((local_port, supervisor))
+}
+
+/// The tunnel the connections to the database are made through, stopped when dropped
+pub struct Tunnel {
+    exit: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Tunnel {
+    /// Start the tunnel when it is configured, pointing the connections of the config
+    /// at its local port.
+    pub fn start(
+        config: &mut AccountsDbPluginPostgresConfig,
+    ) -> Result<Option<Self>, GeyserPluginError> {
+        let Some(tunnel) = config.tunnel.clone() else {
+            return Ok(None);
+        };
+        let target =
+            tunnel_target(config).map_err(|msg| GeyserPluginError::ConfigFileReadError { msg })?;
+        let exit = Arc::new(AtomicBool::new(false));
+        let (description, result) = match (&tunnel.socks5_proxy, &tunnel.ssh_jump_host) {
+            (Some(proxy), _) => (
+                format!("the SOCKS5 proxy {}", proxy),
+                start_socks5(&tunnel, proxy.clone(), target.clone(), exit.clone()),
+            ),
+            (None, Some(jump_host)) => (
+                format!("the SSH jump host {}", jump_host),
+                start_ssh(&tunnel, jump_host.clone(), target.clone(), exit.clone()),
+            ),
+            (None, None) => return Ok(None),
+        };
+        let (local_port, thread) = result.map_err(|err| {
+            to_tunnel_error(format!(
+                "Failed to start the tunnel to the PostgreSQL database through {}: {}",
+                description, err
+            ))
+        })?;
+        info!(
+            "Connecting to the PostgreSQL database {}:{} through {}, tunneled from the port {}",
+            target.0, target.1, description, local_port
+        );
+        config.tunnel_local_port = Some(local_port);
+        Ok(Some(Self {
+            exit,
+            thread: Some(thread),
+        }))
+    }
+}
+
+impl Drop for Tunnel {
+    fn drop(&mut self) {
+        self.exit.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tunnel_target() {
+        let mut config: AccountsDbPluginPostgresConfig =
+            serde_json::from_str("{\"host\": \"db.internal\", \"user\": \"solana\"}").unwrap();
+        assert_eq!(
+            tunnel_target(&config),
+            Ok(("db.internal".to_string(), DEFAULT_POSTGRES_PORT))
+        );
+        config.connection_str = Some("host=db.internal port=6432 user=solana".to_string());
+        assert_eq!(
+            tunnel_target(&config),
+            Ok(("db.internal".to_string(), 6432))
+        );
+        config.connection_str = Some("host=db1,db2 user=solana".to_string());
+        assert!(tunnel_target(&config).is_err());
+
+        let tunneled = tunneled_config(
+            "host=db.internal port=6432 user=solana dbname=ledger sslmode=require",
+            15432,
+        )
+        .unwrap();
+        assert_eq!(
+            tunneled.get_hosts(),
+            &[Host::Tcp("db.internal".to_string())]
+        );
+        assert_eq!(tunneled.get_hostaddrs(), &[IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+        assert_eq!(tunneled.get_ports(), &[15432]);
+        assert_eq!(tunneled.get_user(), Some("solana"));
+        assert_eq!(tunneled.get_dbname(), Some("ledger"));
+        assert_eq!(tunneled.get_ssl_mode(), postgres::config::SslMode::Require);
+    }
+
+    #[test]
+    fn test_socks5_connect() {
+        let (proxy, proxy_port) = local_listener().unwrap();
+        let proxy_thread = std::thread::spawn(move || {
+            let (mut stream, _) = proxy.accept().unwrap();
+            let mut greeting = [0u8; 3];
+            stream.read_exact(&mut greeting).unwrap();
+            assert_eq!(greeting, [SOCKS5_VERSION, 1, SOCKS5_USER_PASSWORD]);
+            stream
+                .write_all(&[SOCKS5_VERSION, SOCKS5_USER_PASSWORD])
+                .unwrap();
+            let mut credentials = [0u8; 12];
+            stream.read_exact(&mut credentials).unwrap();
+            assert_eq!(&credentials, b"\x01\x06solana\x03pwd");
+            stream
+                .write_all(&[SOCKS5_USER_PASSWORD_VERSION, 0])
+                .unwrap();
+            let mut request = [0u8; 18];
+            stream.read_exact(&mut request).unwrap();
+            assert_eq!(
+                &request[..5],
+                &[SOCKS5_VERSION, SOCKS5_CONNECT, 0, SOCKS5_DOMAIN, 11]
+            );
+            assert_eq!(&request[5..16], b"db.internal");
+            assert_eq!(&request[16..], &5432u16.to_be_bytes());
+            stream
+                .write_all(&[SOCKS5_VERSION, 0, 0, SOCKS5_IPV4, 10, 0, 0, 1, 0x15, 0x38])
+                .unwrap();
+            // Echo the bytes forwarded to the target
+            let mut message = [0u8; 4];
+            stream.read_exact(&mut message).unwrap();
+            stream.write_all(&message).unwrap();
+        });
+
+        let mut stream = socks5_connect(
+            &format!("127.0.0.1:{}", proxy_port),
+            Some(("solana", "pwd")),
+            "db.internal",
+            5432,
+        )
+        .unwrap();
+        stream.write_all(b"ping").unwrap();
+        let mut message = [0u8; 4];
+        stream.read_exact(&mut message).unwrap();
+        assert_eq!(&message, b"ping");
+        proxy_thread.join().unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+/// Module responsible for storing the rewards of the validators, the fee, rent and
+/// voting rewards of the blocks along with their commissions, so that the delegators
+/// can audit the validators per epoch from the PostgreSQL database.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::{
+            postgres_client_transaction::{DbReward, DbRewardType},
+            postgres_client_write_stats::WriteStats,
+            SimplePostgresClient,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    postgres::{Client, Statement},
+    postgres_types::ToSql,
+    solana_sdk::pubkey::Pubkey,
+    std::str::FromStr,
+};
+
+/// A reward of a validator in a block, of its identity or of its vote account
+#[derive(Clone, Debug, PartialEq)]
+pub struct DbValidatorReward {
+    pub pubkey: Vec<u8>,
+    pub reward_type: DbRewardType,
+    pub lamports: i64,
+    pub post_balance: i64,
+    /// The commission of the vote account, set for the voting rewards
+    pub commission: Option<i16>,
+}
+
+/// Get the rewards of the validators among the rewards of the block, the staking
+/// rewards of the stake accounts are left out.
+pub fn build_validator_rewards(rewards: &[DbReward]) -> Vec<DbValidatorReward> {
+    rewards
+        .iter()
+        .filter_map(|reward| {
+            let reward_type = match &reward.reward_type {
+                Some(DbRewardType::Staking) | None => return None,
+                Some(reward_type) => reward_type.clone(),
+            };
+            let pubkey = match Pubkey::from_str(&reward.pubkey) {
+                Ok(pubkey) => pubkey,
+                Err(err) => {
+                    warn!(
+                        "Ignored the validator reward of the invalid pubkey {:?}: ({})",
+                        reward.pubkey, err
+                    );
+                    return None;
+                }
+            };
+            Some(DbValidatorReward {
+                pubkey: pubkey.to_bytes().to_vec(),
+                reward_type,
+                lamports: reward.lamports,
+                post_balance: reward.post_balance,
+                commission: reward.commission,
+            })
+        })
+        .collect()
+}
+
+impl SimplePostgresClient {
+    /// Build the statement inserting the rewards of a block into the validator_reward
+    /// table.
+    pub fn build_validator_reward_insert_statement(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Statement, GeyserPluginError> {
+        let stmt = "INSERT INTO validator_reward (slot, epoch, pubkey, reward_type, lamports, \
+            post_balance, commission, updated_on) \
+            SELECT $1, $2, t.pubkey, t.reward_type, t.lamports, t.post_balance, t.commission, $3 \
+            FROM UNNEST($4::BYTEA[], $5::\"RewardType\"[], $6::BIGINT[], $7::BIGINT[], $8::SMALLINT[]) \
+            AS t (pubkey, reward_type, lamports, post_balance, commission) \
+            ON CONFLICT DO NOTHING";
+
+        let stmt = client.prepare(stmt);
+
+        match stmt {
+            Err(err) => {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "Error in preparing for the validator reward insert PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                        err, config.host, config.user, config
+                    ),
+                })))
+            }
+            Ok(stmt) => Ok(stmt),
+        }
+    }
+
+    pub(crate) fn insert_validator_rewards(
+        client: &mut Client,
+        statement: &Statement,
+        slot: i64,
+        epoch: i64,
+        rewards: &[DbValidatorReward],
+        write_stats: &WriteStats,
+    ) -> Result<(), GeyserPluginError> {
+        if rewards.is_empty() {
+            return Ok(());
+        }
+        let updated_on = Utc::now().naive_utc();
+        let pubkeys: Vec<&Vec<u8>> = rewards.iter().map(|reward| &reward.pubkey).collect();
+        let reward_types: Vec<&DbRewardType> =
+            rewards.iter().map(|reward| &reward.reward_type).collect();
+        let lamports: Vec<i64> = rewards.iter().map(|reward| reward.lamports).collect();
+        let post_balances: Vec<i64> = rewards.iter().map(|reward| reward.post_balance).collect();
+        let commissions: Vec<Option<i16>> =
+            rewards.iter().map(|reward| reward.commission).collect();
+
+        let params: [&(dyn ToSql + Sync); 8] = [
+            &slot,
+            &epoch,
+            &updated_on,
+            &pubkeys,
+            &reward_types,
+            &lamports,
+            &post_balances,
+            &commissions,
+        ];
+        let result = client.execute(statement, &params);
+
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to persist the validator rewards to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::SlotStatusUpdateError { msg });
+        }
+        write_stats.record("validator_reward", rewards.len(), statement, &params);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn reward(
+        pubkey: &str,
+        reward_type: Option<DbRewardType>,
+        commission: Option<i16>,
+    ) -> DbReward {
+        DbReward {
+            pubkey: pubkey.to_string(),
+            lamports: 1000,
+            post_balance: 5000,
+            reward_type,
+            commission,
+        }
+    }
+
+    #[test]
+    fn test_build_validator_rewards() {
+        let identity = Pubkey::new_unique();
+        let vote_account = Pubkey::new_unique();
+        let rewards = vec![
+            reward(&identity.to_string(), Some(DbRewardType::Fee), None),
+            reward(
+                &vote_account.to_string(),
+                Some(DbRewardType::Voting),
+                Some(5),
+            ),
+            reward(
+                &Pubkey::new_unique().to_string(),
+                Some(DbRewardType::Staking),
+                None,
+            ),
+            reward(&identity.to_string(), None, None),
+            reward("not-a-pubkey", Some(DbRewardType::Rent), None),
+        ];
+        assert_eq!(
+            build_validator_rewards(&rewards),
+            vec![
+                DbValidatorReward {
+                    pubkey: identity.to_bytes().to_vec(),
+                    reward_type: DbRewardType::Fee,
+                    lamports: 1000,
+                    post_balance: 5000,
+                    commission: None,
+                },
+                DbValidatorReward {
+                    pubkey: vote_account.to_bytes().to_vec(),
+                    reward_type: DbRewardType::Voting,
+                    lamports: 1000,
+                    post_balance: 5000,
+                    commission: Some(5),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,315 @@
+/// Module responsible for the verifications run against a read replica instead of the
+/// primary, so that reading back the rows does not compete with the writes. As a hot
+/// standby lags behind the primary, the rows written are read back once the standby
+/// has replayed the WAL past them: the write verifications are deferred until then,
+/// and the block chain verification waits for the standby to catch up with the
+/// primary, falling back to the primary when it lags behind for too long.
+use {
+    crate::{
+        accountsdb_plugin_postgres::AccountsDbPluginPostgresConfig,
+        postgres_client::{DbAccountInfo, DbTransaction, SimplePostgresClient, WriteVerifier},
+    },
+    log::*,
+    postgres::{types::ToSql, Client, Row},
+    solana_metrics::*,
+    std::{
+        collections::VecDeque,
+        thread::sleep,
+        time::{Duration, Instant},
+    },
+};
+
+pub(crate) const DEFAULT_VERIFICATION_MAX_LAG_SECS: u64 = 60;
+
+/// The interval at which the replay position of the replica is checked
+const REPLAY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The writes pending their verification per worker, the oldest ones are dropped past
+const MAX_PENDING_VERIFICATIONS: usize = 10000;
+
+/// A sampled write to verify once the replica has replayed it
+pub(crate) enum PendingWrite {
+    Account(DbAccountInfo),
+    Transaction(Box<DbTransaction>),
+}
+
+struct PendingVerification {
+    write: PendingWrite,
+    deferred_at: Instant,
+    /// The WAL position of the primary once the write is committed, assigned at the
+    /// first poll after the write
+    lsn: Option<u64>,
+}
+
+/// Parse a WAL position in the text format of pg_lsn, e.g. "16/B374D848".
+fn parse_lsn(lsn: &str) -> Option<u64> {
+    let (high, low) = lsn.split_once('/')?;
+    let high = u32::from_str_radix(high, 16).ok()?;
+    let low = u32::from_str_radix(low, 16).ok()?;
+    Some((u64::from(high) << 32) | u64::from(low))
+}
+
+/// The current WAL position of the primary.
+fn current_lsn(primary: &mut Client) -> Result<u64, String> {
+    let row = primary
+        .query_one("SELECT pg_current_wal_lsn()::text", &[])
+        .map_err(|err| err.to_string())?;
+    let lsn: String = row.get(0);
+    parse_lsn(&lsn).ok_or_else(|| format!("Invalid WAL position {:?}", lsn))
+}
+
+/// The connection of a worker to the verification replica, along with the writes
+/// pending their verification
+pub(crate) struct VerificationReplica {
+    config: AccountsDbPluginPostgresConfig,
+    /// Connected on its first use, and again after it is dropped
+    client: Option<Client>,
+    /// The time a write is waiting for the replica before its verification is skipped
+    max_lag: Duration,
+    pending: VecDeque<PendingVerification>,
+    last_polled: Instant,
+}
+
+impl VerificationReplica {
+    /// Build the replica when the verification connection is configured.
+    pub(crate) fn new(config: &AccountsDbPluginPostgresConfig) -> Option<Self> {
+        let mut replica = config.clone();
+        replica.connection_str = Some(config.verification_connection_str.clone()?);
+        Some(Self {
+            config: replica,
+            client: None,
+            max_lag: Duration::from_secs(
+                config
+                    .verification_max_lag_secs
+                    .unwrap_or(DEFAULT_VERIFICATION_MAX_LAG_SECS),
+            ),
+            pending: VecDeque::new(),
+            last_polled: Instant::now(),
+        })
+    }
+
+    fn client(&mut self) -> Option<&mut Client> {
+        if self.client.as_ref().is_some_and(Client::is_closed) {
+            self.client = None;
+        }
+        if self.client.is_none() {
+            match SimplePostgresClient::connect_to_db(&self.config) {
+                Ok(client) => self.client = Some(client),
+                Err(err) => {
+                    warn!("Failed to connect to the verification replica: ({})", err);
+                    return None;
+                }
+            }
+        }
+        self.client.as_mut()
+    }
+
+    /// The WAL position replayed by the replica, none when it is not a standby, such as
+    /// a logical replica, whose rows are then read back right away.
+    fn replay_lsn(&mut self) -> Result<Option<u64>, String> {
+        let client = self
+            .client()
+            .ok_or("The verification replica is not connected")?;
+        let row = client
+            .query_one("SELECT pg_last_wal_replay_lsn()::text", &[])
+            .map_err(|err| err.to_string())?;
+        let lsn: Option<String> = row.get(0);
+        lsn.map(|lsn| parse_lsn(&lsn).ok_or_else(|| format!("Invalid WAL position {:?}", lsn)))
+            .transpose()
+    }
+
+    /// Defer the verification of the write until the replica has replayed it.
+    pub(crate) fn defer(&mut self, write: PendingWrite) {
+        if self.pending.len() >= MAX_PENDING_VERIFICATIONS {
+            self.pending.pop_front();
+            inc_new_counter_info!("accountsdb-plugin-postgres-write-verification-skipped", 1);
+        }
+        self.pending.push_back(PendingVerification {
+            write,
+            deferred_at: Instant::now(),
+            lsn: None,
+        });
+    }
+
+    /// Skip the verifications waiting for longer than the max lag.
+    fn expire(&mut self, now: Instant) {
+        let max_lag = self.max_lag;
+        let count = self.pending.len();
+        self.pending
+            .retain(|pending| now.duration_since(pending.deferred_at) < max_lag);
+        let expired = count - self.pending.len();
+        if expired > 0 {
+            warn!(
+                "Skipped the verification of {} writes not replayed by the verification replica within {:?}",
+                expired, max_lag
+            );
+            inc_new_counter_info!(
+                "accountsdb-plugin-postgres-write-verification-skipped",
+                expired
+            );
+        }
+    }
+
+    /// Verify the writes replayed by the replica, at the poll interval. The writes are
+    /// committed by the time of the poll, the WAL position of the primary then is the
+    /// one the replica has to replay for them to be read back.
+    fn verify_replayed(&mut self, primary: &mut Client, verifier: &WriteVerifier) {
+        let now = Instant::now();
+        if self.pending.is_empty() || now.duration_since(self.last_polled) < REPLAY_POLL_INTERVAL {
+            return;
+        }
+        self.last_polled = now;
+        if self.pending.iter().any(|pending| pending.lsn.is_none()) {
+            match current_lsn(primary) {
+                Ok(lsn) => self
+                    .pending
+                    .iter_mut()
+                    .filter(|pending| pending.lsn.is_none())
+                    .for_each(|pending| pending.lsn = Some(lsn)),
+                Err(err) => {
+                    warn!("Failed to read the WAL position of the primary: ({})", err);
+                    self.expire(now);
+                    return;
+                }
+            }
+        }
+        let replay_lsn = match self.replay_lsn() {
+            Ok(replay_lsn) => replay_lsn,
+            Err(err) => {
+                warn!(
+                    "Failed to read the replay position of the verification replica: ({})",
+                    err
+                );
+                self.expire(now);
+                return;
+            }
+        };
+        let Some(client) = self.client.as_mut() else {
+            return;
+        };
+        while let Some(pending) = self.pending.front() {
+            if let (Some(lsn), Some(replay_lsn)) = (pending.lsn, replay_lsn) {
+                if lsn > replay_lsn {
+                    break;
+                }
+            }
+            match self.pending.pop_front().unwrap().write {
+                PendingWrite::Account(account) => verifier.verify_account(client, &account),
+                PendingWrite::Transaction(transaction) => {
+                    verifier.verify_transaction(client, &transaction)
+                }
+            }
+        }
+        self.expire(now);
+    }
+
+    /// Run the query on the replica once it has replayed the current WAL position of
+    /// the primary. Returns none when the replica is unreachable, fails the query, or
+    /// has not caught up within the max lag, for the query to be run on the primary.
+    pub(crate) fn query_replayed(
+        &mut self,
+        primary: &mut Client,
+        query: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Option<Vec<Row>> {
+        let lsn = match current_lsn(primary) {
+            Ok(lsn) => lsn,
+            Err(err) => {
+                warn!("Failed to read the WAL position of the primary: ({})", err);
+                return None;
+            }
+        };
+        let started = Instant::now();
+        loop {
+            match self.replay_lsn() {
+                Ok(replay_lsn) if replay_lsn.is_none_or(|replay_lsn| replay_lsn >= lsn) => break,
+                Ok(_) if started.elapsed() < self.max_lag => sleep(REPLAY_POLL_INTERVAL),
+                Ok(_) => {
+                    warn!(
+                        "The verification replica has not caught up with the primary within {:?}, querying the primary",
+                        self.max_lag
+                    );
+                    return None;
+                }
+                Err(err) => {
+                    warn!(
+                        "Failed to read the replay position of the verification replica, querying the primary: ({})",
+                        err
+                    );
+                    return None;
+                }
+            }
+        }
+        match self.client()?.query(query, params) {
+            Ok(rows) => Some(rows),
+            Err(err) => {
+                warn!(
+                    "Failed to query the verification replica, querying the primary: ({})",
+                    err
+                );
+                None
+            }
+        }
+    }
+}
+
+impl SimplePostgresClient {
+    /// Verify the writes deferred until the verification replica has replayed them,
+    /// when the replica is configured.
+    pub(crate) fn verify_replayed_writes(&mut self) {
+        if let (Some(replica), Some(verifier)) =
+            (&mut self.verification_replica, &self.write_verifier)
+        {
+            replica.verify_replayed(&mut self.client.get_mut().unwrap().client, verifier);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lsn() {
+        assert_eq!(parse_lsn("0/0"), Some(0));
+        assert_eq!(parse_lsn("16/B374D848"), Some(0x16_B374_D848));
+        assert!(parse_lsn("16/B374D848").unwrap() > parse_lsn("15/FFFFFFFF").unwrap());
+        assert_eq!(parse_lsn("16B374D848"), None);
+        assert_eq!(parse_lsn("16/G"), None);
+    }
+
+    #[test]
+    fn test_pending_verifications() {
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_str("{}").unwrap();
+        assert!(VerificationReplica::new(&config).is_none());
+
+        let config: AccountsDbPluginPostgresConfig = serde_json::from_value(serde_json::json!({
+            "connection_str": "host=primary",
+            "verification_connection_str": "host=replica",
+            "verification_max_lag_secs": 10,
+        }))
+        .unwrap();
+        let mut replica = VerificationReplica::new(&config).unwrap();
+        assert_eq!(
+            replica.config.connection_str.as_deref(),
+            Some("host=replica")
+        );
+        let account = DbAccountInfo {
+            pubkey: vec![1; 32],
+            lamports: 42,
+            owner: vec![2; 32],
+            executable: false,
+            rent_epoch: 7,
+            data: vec![],
+            slot: 10,
+            write_version: 1,
+            txn_signature: None,
+        };
+        replica.defer(PendingWrite::Account(account.clone()));
+        replica.defer(PendingWrite::Account(account));
+        let now = Instant::now();
+        replica.pending[0].deferred_at = now - Duration::from_secs(11);
+        replica.expire(now);
+        assert_eq!(replica.pending.len(), 1);
+    }
+}
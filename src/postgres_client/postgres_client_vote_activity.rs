@@ -0,0 +1,183 @@
+/// Module responsible for summarizing vote transactions into per-slot, per-voter
+/// activity records in the PostgreSQL database.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        geyser_interface::ReplicaTransactionInfo,
+        postgres_client::{
+            postgres_client_dedup_window::DedupWindow, postgres_client_metrics::MetricStream,
+            DbWorkItem, ParallelPostgresClient, SimplePostgresClient, StaticColumns,
+        },
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::Utc,
+    log::*,
+    postgres::{Client, Statement},
+    postgres_types::ToSql,
+    serde_derive::{Deserialize, Serialize},
+    solana_vote::vote_parser::parse_sanitized_vote_transaction,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DbVoteActivity {
+    pub slot: i64,
+    /// The vote account the vote is cast for
+    pub voter: Vec<u8>,
+    /// The latest slot voted on by the vote transaction
+    pub latest_vote_slot: Option<i64>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LogVoteActivityRequest {
+    pub vote_activity: DbVoteActivity,
+}
+
+/// Build the vote activity of the vote transaction notified at the slot, none for the
+/// transactions which are not votes.
+pub fn build_db_vote_activity(
+    slot: u64,
+    transaction_info: &ReplicaTransactionInfo,
+) -> Option<DbVoteActivity> {
+    let (vote_pubkey, vote, _switch_proof_hash, _signature) =
+        parse_sanitized_vote_transaction(transaction_info.transaction)?;
+    Some(DbVoteActivity {
+        slot: slot as i64,
+        voter: vote_pubkey.as_ref().to_vec(),
+        latest_vote_slot: vote.last_voted_slot().map(|vote_slot| vote_slot as i64),
+    })
+}
+
+impl SimplePostgresClient {
+    /// Build the statement counting a vote of a voter into the vote_activity table.
+    pub fn build_vote_activity_upsert_statement(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Statement, GeyserPluginError> {
+        let static_columns = StaticColumns::new(config);
+        let stmt = format!("INSERT INTO vote_activity AS va (slot, voter, vote_count, latest_vote_slot, updated_on{}) \
+        VALUES ($1, $2, 1, $3, $4{}) \
+        ON CONFLICT (slot, voter) DO UPDATE SET vote_count=va.vote_count + 1, \
+        latest_vote_slot=GREATEST(va.latest_vote_slot, excluded.latest_vote_slot), \
+        updated_on=excluded.updated_on{}", static_columns.names, static_columns.values,
+        DedupWindow::outside_condition(config, "va.slot")
+            .map(|condition| format!(" WHERE {}", condition))
+            .unwrap_or_default());
+
+        let stmt = client.prepare(&stmt);
+
+        match stmt {
+            Err(err) => {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "Error in preparing for the vote activity update PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                        err, config.host, config.user, config
+                    ),
+                })))
+            }
+            Ok(stmt) => Ok(stmt),
+        }
+    }
+
+    pub(crate) fn log_vote_activity_impl(
+        &mut self,
+        vote_activity_request: &LogVoteActivityRequest,
+    ) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        let statement = match &client.update_vote_activity_stmt {
+            Some(statement) => statement,
+            None => return Ok(()),
+        };
+        let write_stats = &client.write_stats;
+        let client = &mut client.client;
+        let updated_on = Utc::now().naive_utc();
+
+        let vote_activity = &vote_activity_request.vote_activity;
+        let params: [&(dyn ToSql + Sync); 4] = [
+            &vote_activity.slot,
+            &vote_activity.voter,
+            &vote_activity.latest_vote_slot,
+            &updated_on,
+        ];
+        let result = client.execute(statement, &params);
+
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to persist the vote activity to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::TransactionUpdateError { msg });
+        }
+        write_stats.record("vote_activity", 1, statement, &params);
+
+        Ok(())
+    }
+}
+
+impl ParallelPostgresClient {
+    pub fn log_vote_activity(
+        &self,
+        transaction_info: &ReplicaTransactionInfo,
+        slot: u64,
+    ) -> Result<(), GeyserPluginError> {
+        let vote_activity = match build_db_vote_activity(slot, transaction_info) {
+            Some(vote_activity) => vote_activity,
+            None => {
+                debug!(
+                    "Skipping vote activity of unrecognized vote transaction {} at slot {}",
+                    transaction_info.signature, slot
+                );
+                return Ok(());
+            }
+        };
+
+        let wrk_item =
+            DbWorkItem::LogVoteActivity(Box::new(LogVoteActivityRequest { vote_activity }));
+
+        self.record_notification(MetricStream::VoteActivity);
+        if let Err(err) = self.queue_overflow.send(wrk_item) {
+            return Err(GeyserPluginError::TransactionUpdateError {
+                msg: format!("Failed to update the vote activity, error: {:?}", err),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::postgres_client_transaction::tests::TestTransaction,
+        solana_sdk::{
+            hash::Hash,
+            signature::{Keypair, Signer},
+        },
+        solana_vote::vote_transaction::new_vote_transaction,
+    };
+
+    #[test]
+    fn test_build_db_vote_activity() {
+        let node_keypair = Keypair::new();
+        let vote_keypair = Keypair::new();
+        let transaction = new_vote_transaction(
+            vec![41, 42],
+            Hash::new_unique(),
+            Hash::new_unique(),
+            &node_keypair,
+            &vote_keypair,
+            &node_keypair,
+            None,
+        );
+        let transaction = TestTransaction::new(transaction);
+        let transaction_info = transaction.transaction_info();
+        assert!(transaction_info.is_vote);
+
+        let vote_activity = build_db_vote_activity(54, &transaction_info).unwrap();
+        assert_eq!(vote_activity.slot, 54);
+        assert_eq!(vote_activity.voter, vote_keypair.pubkey().as_ref().to_vec());
+        assert_eq!(vote_activity.latest_vote_slot, Some(42));
+    }
+}
@@ -0,0 +1,395 @@
+/// Module responsible for accounting the rows and the bytes written per table per
+/// interval, so that the growth of the database can be attributed to the streams.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::SimplePostgresClient,
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    bytes::BytesMut,
+    chrono::{NaiveDateTime, Utc},
+    log::*,
+    postgres::{Client, Row, Statement},
+    postgres_types::{IsNull, ToSql},
+    solana_metrics::datapoint_info,
+    std::{
+        collections::BTreeMap,
+        sync::Mutex,
+        time::{Duration, Instant},
+    },
+};
+
+/// The estimated overhead of a row on the disk: the tuple header padded to 24 bytes
+/// along with the 4-byte line pointer of the page.
+const ROW_OVERHEAD_BYTES: u64 = 28;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct TableWrites {
+    pub rows: u64,
+    /// The bytes of the encoded parameters along with the estimated row overhead
+    pub bytes: u64,
+}
+
+/// The writes of the statement batch open, accounted once it is committed
+#[derive(Default)]
+struct BatchWrites {
+    tables: BTreeMap<&'static str, TableWrites>,
+    account_upserts: AccountUpserts,
+}
+
+/// The outcomes of the rows upserted into the account table
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct AccountUpserts {
+    pub inserted: u64,
+    pub updated: u64,
+    /// The rows older than the stored versions or within the dedup window
+    pub skipped: u64,
+}
+
+impl AccountUpserts {
+    /// Count the outcomes of the upsert of the accounts from whether each row it
+    /// returned was inserted, the accounts without a row being skipped.
+    fn new(accounts: usize, inserted: impl IntoIterator<Item = bool>) -> Self {
+        let mut upserts = AccountUpserts::default();
+        for inserted in inserted {
+            if inserted {
+                upserts.inserted += 1;
+            } else {
+                upserts.updated += 1;
+            }
+        }
+        upserts.skipped = (accounts as u64).saturating_sub(upserts.inserted + upserts.updated);
+        upserts
+    }
+
+    fn add(&mut self, other: AccountUpserts) {
+        self.inserted += other.inserted;
+        self.updated += other.updated;
+        self.skipped += other.skipped;
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == AccountUpserts::default()
+    }
+}
+
+/// The writes per table accumulated over the current interval, a no-op unless the
+/// write stats are enabled. The writes within a statement batch are accounted only once
+/// the batch is committed.
+#[derive(Default)]
+pub(crate) struct WriteStats {
+    /// The statement inserting into the table_write_stats table when enabled
+    statement: Option<Statement>,
+    /// The statement inserting into the account_upsert_stats table when enabled
+    account_upserts_statement: Option<Statement>,
+    interval: Duration,
+    interval_started: Option<(Instant, NaiveDateTime)>,
+    tables: Mutex<BTreeMap<&'static str, TableWrites>>,
+    account_upserts: Mutex<AccountUpserts>,
+    /// The writes of the statement batch open, if any
+    batch: Mutex<Option<BatchWrites>>,
+}
+
+impl WriteStats {
+    /// Record the rows written to the table by the statement with the parameters, once
+    /// the statement has succeeded.
+    pub(crate) fn record(
+        &self,
+        table: &'static str,
+        rows: usize,
+        statement: &Statement,
+        params: &[&(dyn ToSql + Sync)],
+    ) {
+        if self.statement.is_none() {
+            return;
+        }
+        let mut bytes = ROW_OVERHEAD_BYTES * rows as u64;
+        let mut buf = BytesMut::new();
+        for (param, param_type) in params.iter().zip(statement.params()) {
+            buf.clear();
+            if let Ok(IsNull::No) = param.to_sql_checked(param_type, &mut buf) {
+                bytes += buf.len() as u64;
+            }
+        }
+
+        self.add_table_writes(
+            table,
+            TableWrites {
+                rows: rows as u64,
+                bytes,
+            },
+        );
+    }
+
+    fn add_table_writes(&self, table: &'static str, writes: TableWrites) {
+        let mut batch = self.batch.lock().unwrap();
+        let mut tables = self.tables.lock().unwrap();
+        let tables = match batch.as_mut() {
+            Some(batch) => &mut batch.tables,
+            None => &mut *tables,
+        };
+        let table_writes = tables.entry(table).or_default();
+        table_writes.rows += writes.rows;
+        table_writes.bytes += writes.bytes;
+    }
+
+    /// Hold the writes recorded from now on until the statement batch begun is
+    /// committed or rolled back.
+    pub(crate) fn begin_batch(&self) {
+        *self.batch.lock().unwrap() = Some(BatchWrites::default());
+    }
+
+    /// Account the writes of the statement batch committed.
+    pub(crate) fn commit_batch(&self) {
+        let Some(batch) = self.batch.lock().unwrap().take() else {
+            return;
+        };
+        for (table, writes) in batch.tables {
+            self.add_table_writes(table, writes);
+        }
+        self.add_account_upserts(batch.account_upserts);
+    }
+
+    /// Drop the writes of the statement batch rolled back, its items being written
+    /// again one by one.
+    pub(crate) fn discard_batch(&self) {
+        *self.batch.lock().unwrap() = None;
+    }
+
+    /// Record the outcomes of the upsert of the accounts into the account table from
+    /// the rows it returned, whose first column tells whether the row was inserted.
+    pub(crate) fn record_account_upserts(&self, accounts: usize, rows: &[Row]) {
+        if self.statement.is_none() {
+            return;
+        }
+        let upserts = AccountUpserts::new(accounts, rows.iter().map(|row| row.get(0)));
+        self.add_account_upserts(upserts);
+    }
+
+    fn add_account_upserts(&self, upserts: AccountUpserts) {
+        match self.batch.lock().unwrap().as_mut() {
+            Some(batch) => batch.account_upserts.add(upserts),
+            None => self.account_upserts.lock().unwrap().add(upserts),
+        }
+    }
+
+    /// Carry over the writes of the current interval from the stats of the connection
+    /// rebuilt.
+    pub(crate) fn carry_over(&mut self, previous: WriteStats) {
+        self.interval_started = previous.interval_started;
+        self.tables = previous.tables;
+        self.account_upserts = previous.account_upserts;
+    }
+
+    /// Report the writes of the interval to the metrics and the table_write_stats and
+    /// account_upsert_stats tables once the interval has elapsed, starting the next
+    /// interval.
+    fn report_if_due(&mut self, client: &mut Client) -> Result<(), GeyserPluginError> {
+        let Some(statement) = &self.statement else {
+            return Ok(());
+        };
+        let now = Utc::now().naive_utc();
+        let (started, interval_start) = *self
+            .interval_started
+            .get_or_insert_with(|| (Instant::now(), now));
+        if started.elapsed() < self.interval {
+            return Ok(());
+        }
+        self.interval_started = Some((Instant::now(), now));
+
+        let tables = std::mem::take(&mut *self.tables.lock().unwrap());
+        for (table, table_writes) in tables {
+            datapoint_info!(
+                "accountsdb-plugin-postgres-table-writes",
+                ("table", table, String),
+                ("rows", table_writes.rows as i64, i64),
+                ("bytes", table_writes.bytes as i64, i64),
+            );
+            let result = client.execute(
+                statement,
+                &[
+                    &table,
+                    &interval_start,
+                    &now,
+                    &(table_writes.rows as i64),
+                    &(table_writes.bytes as i64),
+                    &now,
+                ],
+            );
+
+            if let Err(err) = result {
+                let msg = format!(
+                    "Failed to persist the write stats to the PostgreSQL database. Error: {:?}",
+                    err
+                );
+                error!("{}", msg);
+                return Err(GeyserPluginError::SlotStatusUpdateError { msg });
+            }
+        }
+
+        let account_upserts = std::mem::take(&mut *self.account_upserts.lock().unwrap());
+        let Some(statement) = &self.account_upserts_statement else {
+            return Ok(());
+        };
+        if account_upserts.is_empty() {
+            return Ok(());
+        }
+        datapoint_info!(
+            "accountsdb-plugin-postgres-account-upserts",
+            ("inserted", account_upserts.inserted as i64, i64),
+            ("updated", account_upserts.updated as i64, i64),
+            ("skipped", account_upserts.skipped as i64, i64),
+        );
+        let result = client.execute(
+            statement,
+            &[
+                &interval_start,
+                &now,
+                &(account_upserts.inserted as i64),
+                &(account_upserts.updated as i64),
+                &(account_upserts.skipped as i64),
+                &now,
+            ],
+        );
+
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to persist the account upsert stats to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::SlotStatusUpdateError { msg });
+        }
+        Ok(())
+    }
+}
+
+impl SimplePostgresClient {
+    pub(crate) fn build_write_stats(
+        client: &mut Client,
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<WriteStats, GeyserPluginError> {
+        let Some(interval_secs) = config.write_stats_interval_secs else {
+            return Ok(WriteStats::default());
+        };
+        let stmt = "INSERT INTO table_write_stats (table_name, interval_start, interval_end, row_count, byte_count, updated_on) \
+            VALUES ($1, $2, $3, $4, $5, $6)";
+
+        let account_upserts_stmt = "INSERT INTO account_upsert_stats (interval_start, interval_end, inserted_count, updated_count, skipped_count, updated_on) \
+            VALUES ($1, $2, $3, $4, $5, $6)";
+
+        let stmts = client
+            .prepare(stmt)
+            .and_then(|stmt| Ok((stmt, client.prepare(account_upserts_stmt)?)));
+
+        match stmts {
+            Err(err) => {
+                Err(GeyserPluginError::Custom(Box::new(AccountsDbPluginPostgresError::DataSchemaError {
+                    msg: format!(
+                        "Error in preparing for the write stats insert PostgreSQL database: ({}) host: {:?} user: {:?} config: {:?}",
+                        err, config.host, config.user, config
+                    ),
+                })))
+            }
+            Ok((stmt, account_upserts_stmt)) => Ok(WriteStats {
+                statement: Some(stmt),
+                account_upserts_statement: Some(account_upserts_stmt),
+                interval: Duration::from_secs(interval_secs),
+                ..WriteStats::default()
+            }),
+        }
+    }
+
+    /// Report the writes of the interval once it has elapsed, when the write stats are
+    /// enabled.
+    pub(crate) fn report_write_stats_if_due(&mut self) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        client.write_stats.report_if_due(&mut client.client)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_writes() {
+        let write_stats = WriteStats::default();
+        let writes = TableWrites {
+            rows: 2,
+            bytes: 100,
+        };
+        write_stats.add_table_writes("account", writes);
+        write_stats.add_account_upserts(AccountUpserts::new(2, [true, false]));
+
+        // The writes of the batch rolled back are not accounted
+        write_stats.begin_batch();
+        write_stats.add_table_writes("account", writes);
+        write_stats.add_table_writes("slot", writes);
+        write_stats.add_account_upserts(AccountUpserts::new(2, [true, true]));
+        write_stats.discard_batch();
+        assert_eq!(
+            *write_stats.tables.lock().unwrap(),
+            BTreeMap::from([("account", writes)])
+        );
+        assert_eq!(
+            *write_stats.account_upserts.lock().unwrap(),
+            AccountUpserts {
+                inserted: 1,
+                updated: 1,
+                skipped: 0,
+            }
+        );
+
+        // The writes of the batch are accounted once committed
+        write_stats.begin_batch();
+        write_stats.add_table_writes("account", writes);
+        write_stats.add_table_writes("slot", writes);
+        write_stats.add_account_upserts(AccountUpserts::new(2, [true]));
+        assert_eq!(write_stats.tables.lock().unwrap()["account"].rows, 2);
+        write_stats.commit_batch();
+        assert_eq!(
+            *write_stats.tables.lock().unwrap(),
+            BTreeMap::from([
+                (
+                    "account",
+                    TableWrites {
+                        rows: 4,
+                        bytes: 200
+                    }
+                ),
+                ("slot", writes)
+            ])
+        );
+        assert_eq!(
+            *write_stats.account_upserts.lock().unwrap(),
+            AccountUpserts {
+                inserted: 2,
+                updated: 1,
+                skipped: 1,
+            }
+        );
+        // The writes recorded after the batch are accounted right away
+        write_stats.add_table_writes("slot", writes);
+        assert_eq!(write_stats.tables.lock().unwrap()["slot"].rows, 4);
+    }
+
+    #[test]
+    fn test_account_upserts() {
+        let mut upserts = AccountUpserts::new(4, [true, false, false]);
+        assert_eq!(
+            upserts,
+            AccountUpserts {
+                inserted: 1,
+                updated: 2,
+                skipped: 1,
+            }
+        );
+        upserts.add(AccountUpserts::new(1, []));
+        assert_eq!(upserts.skipped, 2);
+        assert!(!upserts.is_empty());
+        assert!(AccountUpserts::new(0, []).is_empty());
+    }
+}
@@ -0,0 +1,321 @@
+/// Module responsible for the verification of the writes, reading back a sample of the
+/// account and transaction rows just written and comparing them byte for byte with the
+/// updates they were written from, so that the bugs of the encoding of the rows and the
+/// silent truncations of the values are detected early. The mismatches are logged, and
+/// counted along with the rows verified. The rows are read back from the verification
+/// replica instead of the primary when one is configured.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{
+            AccountLayout, AccountsDbPluginPostgresConfig, SignatureStorage,
+        },
+        postgres_client::{
+            decompress_account_data, DbAccountInfo, DbLoadedMessageV0, DbTransaction,
+            DbTransactionMessage, DbTransactionStatusMeta, PendingWrite, SimplePostgresClient,
+        },
+    },
+    log::*,
+    postgres::Client,
+    postgres_types::ToSql,
+    serde::Serialize,
+    solana_metrics::*,
+    solana_sdk::hash::hashv,
+};
+
+/// The sampling of the rows read back after they are written
+#[derive(Debug)]
+pub(crate) struct WriteVerifier {
+    /// The rows whose key hash is below the threshold are read back
+    threshold: u64,
+    account_layout: AccountLayout,
+    signature_storage: SignatureStorage,
+}
+
+/// An account row read back, with its data decompressed
+#[derive(Debug, PartialEq, Eq)]
+struct AccountRow {
+    slot: i64,
+    write_version: i64,
+    owner: Vec<u8>,
+    lamports: i64,
+    executable: bool,
+    rent_epoch: i64,
+    data: Vec<u8>,
+}
+
+/// Check if the value written and the one read back differ, compared by their
+/// serialization.
+fn differs<T: Serialize>(written: &T, read_back: &T) -> bool {
+    bincode::serialize(written).ok() != bincode::serialize(read_back).ok()
+}
+
+/// The columns of the account row read back differing from the account written.
+fn account_mismatches(account: &DbAccountInfo, row: &AccountRow) -> Vec<&'static str> {
+    [
+        ("owner", account.owner != row.owner),
+        ("lamports", account.lamports != row.lamports),
+        ("executable", account.executable != row.executable),
+        ("rent_epoch", account.rent_epoch != row.rent_epoch),
+        ("data", account.data != row.data),
+    ]
+    .into_iter()
+    .filter_map(|(column, is_different)| is_different.then_some(column))
+    .collect()
+}
+
+impl WriteVerifier {
+    /// Build the verifier when the percentage of the rows to verify is configured.
+    pub(crate) fn new(config: &AccountsDbPluginPostgresConfig) -> Option<Self> {
+        let percentage = config
+            .write_verification_percentage
+            .filter(|percentage| *percentage > 0.0)?;
+        let threshold = if percentage >= 100.0 {
+            u64::MAX
+        } else {
+            (percentage / 100.0 * u64::MAX as f64) as u64
+        };
+        Some(Self {
+            threshold,
+            account_layout: config.account_layout.unwrap_or_default(),
+            signature_storage: config.signature_storage.unwrap_or_default(),
+        })
+    }
+
+    /// Check if the row of the key written at the slot is read back, sampled by the hash
+    /// of the key and the slot so that the updates of every key are sampled over time.
+    pub(crate) fn is_sampled(&self, key: &[u8], slot: i64) -> bool {
+        let hash = hashv(&[key, &slot.to_le_bytes()]);
+        let value = u64::from_le_bytes(hash.as_ref()[..8].try_into().unwrap());
+        self.threshold == u64::MAX || value < self.threshold
+    }
+
+    fn account_select_sql(&self) -> &'static str {
+        match self.account_layout {
+            AccountLayout::Wide => {
+                "SELECT slot, write_version, owner, lamports, executable, rent_epoch, data, data_codec \
+                FROM account WHERE pubkey = $1"
+            }
+            // The data of the split layout is in the account_data table
+            AccountLayout::Split => {
+                "SELECT a.slot, a.write_version, a.owner, a.lamports, a.executable, a.rent_epoch, \
+                d.data, d.data_codec FROM account a JOIN account_data d USING (pubkey) WHERE a.pubkey = $1"
+            }
+        }
+    }
+}
+
+fn record_verification(table: &str, key: &[u8], slot: i64, mismatches: &[&str]) {
+    inc_new_counter_info!("accountsdb-plugin-postgres-write-verification-verified", 1);
+    if mismatches.is_empty() {
+        return;
+    }
+    error!(
+        "The {} row of {} at the slot {} read back differs from the one written in the columns {:?}",
+        table,
+        bs58::encode(key).into_string(),
+        slot,
+        mismatches
+    );
+    inc_new_counter_error!("accountsdb-plugin-postgres-write-verification-mismatch", 1);
+}
+
+impl WriteVerifier {
+    /// Read back the account row with the client and compare it with the account. The
+    /// row replaced by a newer version of the account in the meantime is left out. The
+    /// verification does not fail the write, the failures to read back are logged.
+    pub(crate) fn verify_account(&self, client: &mut Client, account: &DbAccountInfo) {
+        let row = match client.query_opt(self.account_select_sql(), &[&account.pubkey]) {
+            Ok(row) => row,
+            Err(err) => {
+                warn!(
+                    "Failed to read back the account row to verify it: {:?}",
+                    err
+                );
+                return;
+            }
+        };
+        let Some(row) = row else {
+            record_verification("account", &account.pubkey, account.slot, &["pubkey"]);
+            return;
+        };
+        let data = match decompress_account_data(row.get(6), row.get(7)) {
+            Ok(data) => data,
+            Err(err) => {
+                error!(
+                    "Failed to decompress the data of the account row read back: {}",
+                    err
+                );
+                record_verification("account", &account.pubkey, account.slot, &["data"]);
+                return;
+            }
+        };
+        let row = AccountRow {
+            slot: row.get(0),
+            write_version: row.get(1),
+            owner: row.get(2),
+            lamports: row.get(3),
+            executable: row.get(4),
+            rent_epoch: row.get(5),
+            data,
+        };
+        if (row.slot, row.write_version) != (account.slot, account.write_version) {
+            return;
+        }
+        let mismatches = account_mismatches(account, &row);
+        record_verification("account", &account.pubkey, account.slot, &mismatches);
+    }
+
+    /// Read back the transaction row with the client and compare it with the
+    /// transaction.
+    pub(crate) fn verify_transaction(&self, client: &mut Client, transaction: &DbTransaction) {
+        let signature_text = bs58::encode(&transaction.signature).into_string();
+        let signature: &(dyn ToSql + Sync) = match self.signature_storage {
+            SignatureStorage::Base58 => &signature_text,
+            SignatureStorage::Bytea | SignatureStorage::Both => &transaction.signature,
+        };
+        let row = match client.query_opt(
+            "SELECT index, is_vote, message_type, legacy_message, v0_loaded_message, signatures, \
+            message_hash, meta FROM transaction WHERE slot = $1 AND signature = $2",
+            &[&transaction.slot, signature],
+        ) {
+            Ok(row) => row,
+            Err(err) => {
+                warn!(
+                    "Failed to read back the transaction row to verify it: {:?}",
+                    err
+                );
+                return;
+            }
+        };
+        let Some(row) = row else {
+            record_verification(
+                "transaction",
+                &transaction.signature,
+                transaction.slot,
+                &["signature"],
+            );
+            return;
+        };
+        let legacy_message: Option<DbTransactionMessage> = row.get(3);
+        let v0_loaded_message: Option<DbLoadedMessageV0> = row.get(4);
+        let signatures: Vec<Vec<u8>> = row.get(5);
+        let message_hash: Vec<u8> = row.get(6);
+        let meta: DbTransactionStatusMeta = row.get(7);
+        let mismatches: Vec<&str> = [
+            ("index", transaction.index != row.get::<_, i64>(0)),
+            ("is_vote", transaction.is_vote != row.get::<_, bool>(1)),
+            (
+                "message_type",
+                transaction.message_type != row.get::<_, i16>(2),
+            ),
+            (
+                "legacy_message",
+                differs(&transaction.legacy_message, &legacy_message),
+            ),
+            (
+                "v0_loaded_message",
+                differs(&transaction.v0_loaded_message, &v0_loaded_message),
+            ),
+            ("signatures", transaction.signatures != signatures),
+            ("message_hash", transaction.message_hash != message_hash),
+            ("meta", differs(&transaction.meta, &meta)),
+        ]
+        .into_iter()
+        .filter_map(|(column, is_different)| is_different.then_some(column))
+        .collect();
+        record_verification(
+            "transaction",
+            &transaction.signature,
+            transaction.slot,
+            &mismatches,
+        );
+    }
+}
+
+impl SimplePostgresClient {
+    /// Verify the account row just written when it is sampled, deferred until the
+    /// verification replica has replayed it when one is configured.
+    pub(crate) fn verify_account_write(&mut self, account: &DbAccountInfo) {
+        let Some(verifier) = &self.write_verifier else {
+            return;
+        };
+        if !verifier.is_sampled(&account.pubkey, account.slot) {
+            return;
+        }
+        if let Some(replica) = &mut self.verification_replica {
+            replica.defer(PendingWrite::Account(account.clone()));
+            return;
+        }
+        verifier.verify_account(&mut self.client.get_mut().unwrap().client, account);
+    }
+
+    /// Verify the transaction row just written when it is sampled, deferred until the
+    /// verification replica has replayed it when one is configured.
+    pub(crate) fn verify_transaction_write(&mut self, transaction: &DbTransaction) {
+        let Some(verifier) = &self.write_verifier else {
+            return;
+        };
+        if !verifier.is_sampled(&transaction.signature, transaction.slot) {
+            return;
+        }
+        if let Some(replica) = &mut self.verification_replica {
+            replica.defer(PendingWrite::Transaction(Box::new(transaction.clone())));
+            return;
+        }
+        verifier.verify_transaction(&mut self.client.get_mut().unwrap().client, transaction);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_verifier() {
+        let mut config: AccountsDbPluginPostgresConfig = serde_json::from_str("{}").unwrap();
+        assert!(WriteVerifier::new(&config).is_none());
+        config.write_verification_percentage = Some(0.0);
+        assert!(WriteVerifier::new(&config).is_none());
+
+        config.write_verification_percentage = Some(100.0);
+        let verifier = WriteVerifier::new(&config).unwrap();
+        assert!((0..1000).all(|slot| verifier.is_sampled(b"key", slot)));
+
+        config.write_verification_percentage = Some(10.0);
+        let verifier = WriteVerifier::new(&config).unwrap();
+        let sampled = (0..10_000)
+            .filter(|slot| verifier.is_sampled(b"key", *slot))
+            .count();
+        assert!((800..1200).contains(&sampled), "sampled: {}", sampled);
+    }
+
+    #[test]
+    fn test_account_mismatches() {
+        let account = DbAccountInfo {
+            pubkey: vec![1; 32],
+            lamports: 42,
+            owner: vec![2; 32],
+            executable: false,
+            rent_epoch: 7,
+            data: vec![3; 200],
+            slot: 10,
+            write_version: 1,
+            txn_signature: None,
+        };
+        let mut row = AccountRow {
+            slot: 10,
+            write_version: 1,
+            owner: vec![2; 32],
+            lamports: 42,
+            executable: false,
+            rent_epoch: 7,
+            data: vec![3; 200],
+        };
+        assert!(account_mismatches(&account, &row).is_empty());
+        row.data.truncate(128);
+        row.lamports = 41;
+        assert_eq!(account_mismatches(&account, &row), ["lamports", "data"]);
+        assert!(differs(&Some(vec![1u8, 2]), &Some(vec![1u8])));
+        assert!(!differs(&None::<Vec<u8>>, &None));
+    }
+}
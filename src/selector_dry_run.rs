@@ -0,0 +1,228 @@
+/// The selector dry run is responsible for running the accounts selectors against the
+/// account updates for a period without writing them, counting the updates and the
+/// estimated bytes of the rows each condition of the selectors selects. The counts are
+/// written into the selector_dry_run_stats table at the end of the period, for the
+/// database to be sized before the ingestion is enabled.
+use {
+    crate::{
+        accounts_selector::AccountsSelector,
+        accountsdb_plugin_postgres::{
+            AccountsDbPluginPostgresConfig, AccountsDbPluginPostgresError,
+        },
+        postgres_client::{shard_configs, SimplePostgresClient},
+    },
+    agave_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    chrono::{NaiveDateTime, Utc},
+    log::*,
+    postgres::Client,
+    std::{
+        collections::BTreeMap,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        thread::{sleep, Builder, JoinHandle},
+        time::{Duration, Instant},
+    },
+};
+
+const SELECTOR_DRY_RUN_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+/// The approximate bytes of an account row besides its data, the fixed columns along
+/// with the tuple header and the entry of the primary key index
+const ACCOUNT_ROW_OVERHEAD_BYTES: u64 = 176;
+
+const CREATE_STATS_TABLE_SQL: &str = "CREATE TABLE IF NOT EXISTS selector_dry_run_stats (\
+    started_on TIMESTAMP NOT NULL, \
+    ended_on TIMESTAMP NOT NULL, \
+    rule VARCHAR(64) NOT NULL, \
+    is_startup BOOL NOT NULL, \
+    updates BIGINT NOT NULL, \
+    estimated_bytes BIGINT NOT NULL, \
+    CONSTRAINT selector_dry_run_stats_pk PRIMARY KEY (started_on, rule, is_startup))";
+
+/// The counts of the updates selected by a condition of the selectors
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct RuleStats {
+    updates: u64,
+    estimated_bytes: u64,
+}
+
+/// The counts by the condition of the selectors and whether the updates are of the
+/// startup
+type DryRunStats = BTreeMap<(String, bool), RuleStats>;
+
+fn to_dry_run_error(action: &str, err: postgres::Error) -> GeyserPluginError {
+    let msg = format!(
+        "Failed to {} of the selector dry run in the PostgreSQL database. Error: {:?}",
+        action, err
+    );
+    error!("{}", msg);
+    GeyserPluginError::Custom(Box::new(
+        AccountsDbPluginPostgresError::DataStoreConnectionError { msg },
+    ))
+}
+
+pub(crate) struct SelectorDryRun {
+    stats: Arc<Mutex<DryRunStats>>,
+    /// Set once the period is over, the updates are no longer counted
+    is_done: Arc<AtomicBool>,
+    /// Set to end the period early when the plugin is unloaded
+    exit: Arc<AtomicBool>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl SelectorDryRun {
+    /// Connect, create the selector_dry_run_stats table and start the period when the
+    /// dry run is configured. The stats are written into the first database of the
+    /// sharding.
+    pub(crate) fn new(
+        config: &AccountsDbPluginPostgresConfig,
+    ) -> Result<Option<Self>, GeyserPluginError> {
+        let Some(dry_run_secs) = config.selector_dry_run_secs else {
+            return Ok(None);
+        };
+        let mut client = SimplePostgresClient::connect_to_db(&shard_configs(config)[0])?;
+        client
+            .batch_execute(CREATE_STATS_TABLE_SQL)
+            .map_err(|err| to_dry_run_error("create the stats table", err))?;
+        info!(
+            "Running the selectors for {} seconds without writing the updates",
+            dry_run_secs
+        );
+
+        let stats = Arc::new(Mutex::new(DryRunStats::default()));
+        let is_done = Arc::new(AtomicBool::new(false));
+        let exit = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let stats = stats.clone();
+            let is_done = is_done.clone();
+            let exit = exit.clone();
+            let started_on = Utc::now().naive_utc();
+            let period = Duration::from_secs(dry_run_secs);
+            Builder::new()
+                .name("selector-dry-run".to_string())
+                .spawn(move || {
+                    let started = Instant::now();
+                    while started.elapsed() < period && !exit.load(Ordering::Relaxed) {
+                        sleep(SELECTOR_DRY_RUN_CHECK_INTERVAL);
+                    }
+                    is_done.store(true, Ordering::Relaxed);
+                    let stats = std::mem::take(&mut *stats.lock().unwrap());
+                    // The error is logged, the stats are lost along with the dry run
+                    let _ = Self::write_stats(&mut client, started_on, &stats);
+                })
+                .unwrap()
+        };
+        Ok(Some(Self {
+            stats,
+            is_done,
+            exit,
+            writer: Some(writer),
+        }))
+    }
+
+    /// Count the account update against the conditions of the selector selecting it.
+    pub(crate) fn record(
+        &self,
+        selector: &AccountsSelector,
+        pubkey: &[u8],
+        owner: &[u8],
+        lamports: u64,
+        data: &[u8],
+        is_startup: bool,
+    ) {
+        if self.is_done.load(Ordering::Relaxed) {
+            return;
+        }
+        if !selector.is_lamports_selected(lamports) {
+            return;
+        }
+        let matched_rules = selector.matched_rules(pubkey, owner, data);
+        if matched_rules.is_empty() {
+            return;
+        }
+        let mut stats = self.stats.lock().unwrap();
+        for rule in matched_rules {
+            add_update(&mut stats, rule, is_startup, data.len());
+        }
+    }
+
+    fn write_stats(
+        client: &mut Client,
+        started_on: NaiveDateTime,
+        stats: &DryRunStats,
+    ) -> Result<(), GeyserPluginError> {
+        let ended_on = Utc::now().naive_utc();
+        let mut transaction = client
+            .transaction()
+            .map_err(|err| to_dry_run_error("start writing the stats", err))?;
+        for ((rule, is_startup), rule_stats) in stats {
+            transaction
+                .execute(
+                    "INSERT INTO selector_dry_run_stats (started_on, ended_on, rule, is_startup, updates, estimated_bytes) \
+                    VALUES ($1, $2, $3, $4, $5, $6)",
+                    &[
+                        &started_on,
+                        &ended_on,
+                        rule,
+                        is_startup,
+                        &(rule_stats.updates as i64),
+                        &(rule_stats.estimated_bytes as i64),
+                    ],
+                )
+                .map_err(|err| to_dry_run_error("write the stats", err))?;
+        }
+        transaction
+            .commit()
+            .map_err(|err| to_dry_run_error("commit the stats", err))?;
+        info!(
+            "The selector dry run is over, wrote the stats of {} selector conditions into the \
+            selector_dry_run_stats table, reload the plugin without the \"selector_dry_run_secs\" \
+            to write the updates",
+            stats.len()
+        );
+        Ok(())
+    }
+
+    /// End the period and write the stats counted so far, when the plugin is unloaded
+    /// before the end of the period.
+    pub(crate) fn join(&mut self) {
+        self.exit.store(true, Ordering::Relaxed);
+        if let Some(writer) = self.writer.take() {
+            writer.join().unwrap();
+        }
+    }
+}
+
+fn add_update(stats: &mut DryRunStats, rule: String, is_startup: bool, data_len: usize) {
+    let rule_stats = stats.entry((rule, is_startup)).or_default();
+    rule_stats.updates += 1;
+    rule_stats.estimated_bytes += ACCOUNT_ROW_OVERHEAD_BYTES + data_len as u64;
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_update() {
+        let mut stats = DryRunStats::default();
+        add_update(&mut stats, "*".to_string(), true, 165);
+        add_update(&mut stats, "*".to_string(), true, 0);
+        add_update(&mut stats, "*".to_string(), false, 82);
+        assert_eq!(
+            stats[&("*".to_string(), true)],
+            RuleStats {
+                updates: 2,
+                estimated_bytes: 2 * ACCOUNT_ROW_OVERHEAD_BYTES + 165,
+            }
+        );
+        assert_eq!(
+            stats[&("*".to_string(), false)],
+            RuleStats {
+                updates: 1,
+                estimated_bytes: ACCOUNT_ROW_OVERHEAD_BYTES + 82,
+            }
+        );
+    }
+}
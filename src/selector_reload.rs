@@ -0,0 +1,201 @@
+/// The selector reload is responsible for watching the config file of the plugin and
+/// swapping in the accounts and transaction selectors changed in it at runtime, without
+/// reloading the plugin. The other settings, such as the connection, stay as loaded.
+/// The selectors swapped in are recorded into the config_history table when enabled.
+use {
+    crate::{
+        accountsdb_plugin_postgres::{AccountsDbPluginPostgresConfig, Selectors},
+        config_include::load_config_file,
+        config_validation::parse_config,
+        postgres_client::{shard_configs, ConfigChange, SimplePostgresClient},
+    },
+    log::*,
+    std::{
+        fs,
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, RwLock,
+        },
+        thread::{sleep, Builder, JoinHandle},
+        time::{Duration, Instant, SystemTime},
+    },
+};
+
+/// The interval the exit of the plugin is checked at between the checks of the file
+const SELECTOR_RELOAD_EXIT_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The config with the selectors cleared, to compare the settings which are fixed
+/// once loaded.
+fn without_selectors(config: &AccountsDbPluginPostgresConfig) -> AccountsDbPluginPostgresConfig {
+    AccountsDbPluginPostgresConfig {
+        accounts_selector: None,
+        startup_accounts_selector: None,
+        transaction_selector: None,
+        ..config.clone()
+    }
+}
+
+pub(crate) struct SelectorReloader {
+    config_file: PathBuf,
+    /// The config loaded, whose settings besides the selectors are kept, along with the
+    /// selectors last swapped in
+    config: AccountsDbPluginPostgresConfig,
+    selectors: Arc<RwLock<Arc<Selectors>>>,
+    check_interval: Duration,
+    /// The modification time of the config file when last loaded
+    modified: Option<SystemTime>,
+}
+
+impl SelectorReloader {
+    pub(crate) fn new(
+        config_file: PathBuf,
+        config: &AccountsDbPluginPostgresConfig,
+        selectors: Arc<RwLock<Arc<Selectors>>>,
+    ) -> Option<Self> {
+        let check_interval = Duration::from_secs(config.selector_reload_interval_secs?);
+        let modified = Self::modified(&config_file);
+        Some(Self {
+            config_file,
+            config: config.clone(),
+            selectors,
+            check_interval,
+            modified,
+        })
+    }
+
+    fn modified(config_file: &Path) -> Option<SystemTime> {
+        fs::metadata(config_file)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    /// Load the selectors of the config file when it has been modified since last
+    /// loaded, keeping the current selectors when the config is not valid. Returns
+    /// whether the selectors were swapped.
+    fn reload_if_modified(&mut self) -> bool {
+        let modified = Self::modified(&self.config_file);
+        if modified.is_none() || modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+
+        let loaded = load_config_file(&self.config_file)
+            .and_then(|value| Ok((parse_config(value.clone())?, value)));
+        let (config, value) = match loaded {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                warn!(
+                    "Failed to reload the selectors from the config file {:?}, keeping the current selectors: ({})",
+                    self.config_file, err
+                );
+                return false;
+            }
+        };
+        if without_selectors(&config) != without_selectors(&self.config) {
+            warn!(
+                "Only the selectors of the config file {:?} are reloaded, the changes to the other settings require reloading the plugin",
+                self.config_file
+            );
+        }
+
+        *self.selectors.write().unwrap() = Arc::new(Selectors::from_config(&value));
+        info!(
+            "Reloaded the selectors from the config file {:?}",
+            self.config_file
+        );
+        self.config = AccountsDbPluginPostgresConfig {
+            accounts_selector: config.accounts_selector,
+            startup_accounts_selector: config.startup_accounts_selector,
+            transaction_selector: config.transaction_selector,
+            ..self.config.clone()
+        };
+        // Nothing is written to the database during the selector dry run
+        let shard_configs = if self.config.selector_dry_run_secs.is_none() {
+            shard_configs(&self.config)
+        } else {
+            vec![]
+        };
+        for config in shard_configs {
+            if SimplePostgresClient::record_config_history(&config, ConfigChange::SelectorReload)
+                .is_err()
+            {
+                warn!("The selectors reloaded are not recorded into the config_history table");
+            }
+        }
+        true
+    }
+
+    pub(crate) fn spawn(mut self, exit: Arc<AtomicBool>) -> JoinHandle<()> {
+        Builder::new()
+            .name("selector-reloader".to_string())
+            .spawn(move || {
+                let mut last_check = Instant::now();
+                while !exit.load(Ordering::Relaxed) {
+                    if last_check.elapsed() >= self.check_interval {
+                        last_check = Instant::now();
+                        self.reload_if_modified();
+                    }
+                    sleep(SELECTOR_RELOAD_EXIT_CHECK_INTERVAL);
+                }
+            })
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, std::env};
+
+    #[test]
+    fn test_reload_selectors() {
+        let config_file =
+            env::temp_dir().join(format!("test_reload_selectors_{}.json", std::process::id()));
+        let write_config = |owner: &str, threads: usize| {
+            let config = format!(
+                "{{\"threads\": {}, \"accounts_selector\": {{\"owners\": [\"{}\"]}}}}",
+                threads, owner
+            );
+            fs::write(&config_file, config).unwrap();
+        };
+        let owner = "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin";
+        let other_owner = "9oT9R5ZyRovSVnt37QvVoBttGpNqR3J7unkb567NP8k3";
+        let is_selected = |selectors: &RwLock<Arc<Selectors>>, owner: &str| {
+            let owner = bs58::decode(owner).into_vec().unwrap();
+            selectors
+                .read()
+                .unwrap()
+                .accounts_selector(false)
+                .is_some_and(|selector| selector.is_account_selected(&[], &owner, &[]))
+        };
+
+        write_config(owner, 1);
+        let value = load_config_file(&config_file).unwrap();
+        let config = parse_config(value.clone()).unwrap();
+        let selectors = Arc::new(RwLock::new(Arc::new(Selectors::from_config(&value))));
+        let mut reloader = SelectorReloader {
+            config_file: config_file.clone(),
+            config,
+            selectors: selectors.clone(),
+            check_interval: Duration::default(),
+            modified: SelectorReloader::modified(&config_file),
+        };
+        assert!(!reloader.reload_if_modified());
+        assert!(is_selected(&selectors, owner));
+
+        // Rewound, as the rewrite may fall within the granularity of the modification time
+        reloader.modified = Some(SystemTime::UNIX_EPOCH);
+        write_config(other_owner, 2);
+        assert!(reloader.reload_if_modified());
+        assert!(!is_selected(&selectors, owner));
+        assert!(is_selected(&selectors, other_owner));
+
+        // The selectors are kept when the config is not valid
+        reloader.modified = Some(SystemTime::UNIX_EPOCH);
+        fs::write(&config_file, "{\"accounts_selector\": {\"owner\": []}}").unwrap();
+        assert!(!reloader.reload_if_modified());
+        assert!(is_selected(&selectors, other_owner));
+
+        fs::remove_file(&config_file).unwrap();
+    }
+}
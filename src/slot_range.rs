@@ -0,0 +1,57 @@
+/// The slot range is responsible for filtering the notifications by their slots
+/// in the plugin framework.
+use log::*;
+
+#[derive(Debug, Default)]
+pub(crate) struct SlotRange {
+    /// The first slot of interest, inclusive
+    pub start_slot: Option<u64>,
+    /// The last slot of interest, inclusive
+    pub stop_slot: Option<u64>,
+}
+
+impl SlotRange {
+    pub fn new(start_slot: Option<u64>, stop_slot: Option<u64>) -> Self {
+        info!(
+            "Creating SlotRange from start_slot: {:?}, stop_slot: {:?}",
+            start_slot, stop_slot
+        );
+        Self {
+            start_slot,
+            stop_slot,
+        }
+    }
+
+    pub fn is_slot_selected(&self, slot: u64) -> bool {
+        self.start_slot.is_none_or(|start_slot| slot >= start_slot)
+            && self.stop_slot.is_none_or(|stop_slot| slot <= stop_slot)
+    }
+
+    /// Check if the slot reaches the stop slot
+    pub fn is_stop_slot_reached(&self, slot: u64) -> bool {
+        self.stop_slot.is_some_and(|stop_slot| slot >= stop_slot)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slot_range() {
+        let slot_range = SlotRange::default();
+        assert!(slot_range.is_slot_selected(0));
+        assert!(!slot_range.is_stop_slot_reached(u64::MAX));
+
+        let slot_range = SlotRange::new(Some(10), Some(20));
+        assert!(!slot_range.is_slot_selected(9));
+        assert!(slot_range.is_slot_selected(10));
+        assert!(slot_range.is_slot_selected(20));
+        assert!(!slot_range.is_slot_selected(21));
+        assert!(!slot_range.is_stop_slot_reached(19));
+        assert!(slot_range.is_stop_slot_reached(20));
+
+        let slot_range = SlotRange::new(Some(10), None);
+        assert!(slot_range.is_slot_selected(u64::MAX));
+    }
+}
@@ -0,0 +1,65 @@
+/// The sysvar sampler is responsible for sampling the updates of the sysvar accounts
+/// written every slot, which are mostly noise for the consumers of the account updates.
+use {
+    log::*,
+    solana_sdk::{
+        pubkey::Pubkey,
+        sysvar::{clock, slot_hashes, slot_history},
+    },
+};
+
+/// The sysvar accounts updated every slot
+const SAMPLED_SYSVARS: [Pubkey; 3] = [clock::ID, slot_hashes::ID, slot_history::ID];
+
+#[derive(Debug, Default)]
+pub(crate) struct SysvarSampler {
+    /// Store one in this many slots of the updates of the sampled sysvars, all the
+    /// updates are stored when not set
+    pub sampling_rate: Option<u64>,
+}
+
+impl SysvarSampler {
+    pub fn new(sampling_rate: Option<u64>) -> Self {
+        info!(
+            "Creating SysvarSampler from sysvar_sampling_rate: {:?}",
+            sampling_rate
+        );
+        Self { sampling_rate }
+    }
+
+    /// Check if the account update is to be stored. The sysvars are updated once per
+    /// slot, sampling by the slot keeps the samples aligned across the sysvars. The
+    /// updates during the startup are always stored for the initial state.
+    pub fn is_update_sampled(&self, pubkey: &[u8], slot: u64, is_startup: bool) -> bool {
+        match self.sampling_rate {
+            Some(sampling_rate) if !is_startup => {
+                slot.is_multiple_of(sampling_rate)
+                    || !SAMPLED_SYSVARS
+                        .iter()
+                        .any(|sysvar| sysvar.as_ref() == pubkey)
+            }
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sysvar_sampler() {
+        let clock = clock::ID.to_bytes();
+        let other = Pubkey::new_unique().to_bytes();
+
+        let sampler = SysvarSampler::default();
+        assert!(sampler.is_update_sampled(&clock, 1, false));
+
+        let sampler = SysvarSampler::new(Some(10));
+        assert!(sampler.is_update_sampled(&clock, 20, false));
+        assert!(!sampler.is_update_sampled(&clock, 21, false));
+        assert!(!sampler.is_update_sampled(&slot_hashes::ID.to_bytes(), 21, false));
+        assert!(sampler.is_update_sampled(&clock, 21, true));
+        assert!(sampler.is_update_sampled(&other, 21, false));
+    }
+}
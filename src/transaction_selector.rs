@@ -4,8 +4,16 @@ use {log::*, solana_sdk::pubkey::Pubkey, std::collections::HashSet};
 
 pub(crate) struct TransactionSelector {
     pub mentioned_addresses: HashSet<Vec<u8>>,
+    /// The addresses the transactions must all mention to be selected
+    pub all_mentioned_addresses: HashSet<Vec<u8>>,
     pub select_all_transactions: bool,
     pub select_all_vote_transactions: bool,
+    /// The vote transactions are never selected
+    pub exclude_votes: bool,
+    /// Only the vote transactions are selected
+    pub include_only_votes: bool,
+    /// The failed transactions are never selected
+    pub exclude_failed: bool,
 }
 
 #[allow(dead_code)]
@@ -13,8 +21,12 @@ impl TransactionSelector {
     pub fn default() -> Self {
         Self {
             mentioned_addresses: HashSet::default(),
+            all_mentioned_addresses: HashSet::default(),
             select_all_transactions: false,
             select_all_vote_transactions: false,
+            exclude_votes: false,
+            include_only_votes: false,
+            exclude_failed: false,
         }
     }
 
@@ -22,10 +34,12 @@ impl TransactionSelector {
     /// To select all transactions use ["*"] or ["all"]
     /// To select all vote transactions, use ["all_votes"]
     /// To select transactions mentioning specific addresses use ["<pubkey1>", "<pubkey2>", ...]
-    pub fn new(mentioned_addresses: &[String]) -> Self {
+    /// The transactions mentioning every one of the `all_mentioned_addresses` are also
+    /// selected.
+    pub fn new(mentioned_addresses: &[String], all_mentioned_addresses: &[String]) -> Self {
         info!(
-            "Creating TransactionSelector from addresses: {:?}",
-            mentioned_addresses
+            "Creating TransactionSelector from addresses: {:?}, all of addresses: {:?}",
+            mentioned_addresses, all_mentioned_addresses
         );
 
         let select_all_transactions = mentioned_addresses
@@ -34,16 +48,20 @@ impl TransactionSelector {
         if select_all_transactions {
             return Self {
                 mentioned_addresses: HashSet::default(),
+                all_mentioned_addresses: HashSet::default(),
                 select_all_transactions,
                 select_all_vote_transactions: true,
+                ..Self::default()
             };
         }
         let select_all_vote_transactions = mentioned_addresses.iter().any(|key| key == "all_votes");
         if select_all_vote_transactions {
             return Self {
                 mentioned_addresses: HashSet::default(),
+                all_mentioned_addresses: HashSet::default(),
                 select_all_transactions,
                 select_all_vote_transactions: true,
+                ..Self::default()
             };
         }
 
@@ -51,33 +69,71 @@ impl TransactionSelector {
             .iter()
             .map(|key| bs58::decode(key).into_vec().unwrap())
             .collect();
+        let all_mentioned_addresses = all_mentioned_addresses
+            .iter()
+            .map(|key| bs58::decode(key).into_vec().unwrap())
+            .collect();
 
         Self {
             mentioned_addresses,
+            all_mentioned_addresses,
             select_all_transactions: false,
             select_all_vote_transactions: false,
+            ..Self::default()
+        }
+    }
+
+    /// Filter the transactions selected by whether they are votes and whether they
+    /// failed, the filters taking precedence over the mentions.
+    pub fn with_filters(
+        mut self,
+        exclude_votes: bool,
+        include_only_votes: bool,
+        exclude_failed: bool,
+    ) -> Self {
+        if exclude_votes || include_only_votes || exclude_failed {
+            info!(
+                "Filtering TransactionSelector with exclude_votes: {}, include_only_votes: {}, exclude_failed: {}",
+                exclude_votes, include_only_votes, exclude_failed
+            );
         }
+        self.exclude_votes = exclude_votes;
+        self.include_only_votes = include_only_votes;
+        self.exclude_failed = exclude_failed;
+        self
     }
 
     /// Check if a transaction is of interest.
     pub fn is_transaction_selected(
         &self,
         is_vote: bool,
+        is_failed: bool,
         mentioned_addresses: Box<dyn Iterator<Item = &Pubkey> + '_>,
     ) -> bool {
         if !self.is_enabled() {
             return false;
         }
+        if (self.exclude_votes && is_vote)
+            || (self.include_only_votes && !is_vote)
+            || (self.exclude_failed && is_failed)
+        {
+            return false;
+        }
 
         if self.select_all_transactions || (self.select_all_vote_transactions && is_vote) {
             return true;
         }
+        let mut all_mentioned = HashSet::new();
         for address in mentioned_addresses {
             if self.mentioned_addresses.contains(address.as_ref()) {
                 return true;
             }
+            if self.all_mentioned_addresses.contains(address.as_ref()) {
+                all_mentioned.insert(address);
+            }
         }
-        false
+        !self.all_mentioned_addresses.is_empty()
+            && all_mentioned.len() == self.all_mentioned_addresses.len()
     }
 
     /// Check if any transaction is of interest at all
@@ -85,6 +141,7 @@ impl TransactionSelector {
         self.select_all_transactions
             || self.select_all_vote_transactions
             || !self.mentioned_addresses.is_empty()
+            || !self.all_mentioned_addresses.is_empty()
     }
 }
 
@@ -97,19 +154,19 @@ pub(crate) mod tests {
         let pubkey1 = Pubkey::new_unique();
         let pubkey2 = Pubkey::new_unique();
 
-        let selector = TransactionSelector::new(&[pubkey1.to_string()]);
+        let selector = TransactionSelector::new(&[pubkey1.to_string()], &[]);
 
         assert!(selector.is_enabled());
 
         let addresses = [pubkey1];
 
-        assert!(selector.is_transaction_selected(false, Box::new(addresses.iter())));
+        assert!(selector.is_transaction_selected(false, false, Box::new(addresses.iter())));
 
         let addresses = [pubkey2];
-        assert!(!selector.is_transaction_selected(false, Box::new(addresses.iter())));
+        assert!(!selector.is_transaction_selected(false, false, Box::new(addresses.iter())));
 
         let addresses = [pubkey1, pubkey2];
-        assert!(selector.is_transaction_selected(false, Box::new(addresses.iter())));
+        assert!(selector.is_transaction_selected(false, false, Box::new(addresses.iter())));
     }
 
     #[test]
@@ -117,19 +174,19 @@ pub(crate) mod tests {
         let pubkey1 = Pubkey::new_unique();
         let pubkey2 = Pubkey::new_unique();
 
-        let selector = TransactionSelector::new(&["*".to_string()]);
+        let selector = TransactionSelector::new(&["*".to_string()], &[]);
 
         assert!(selector.is_enabled());
 
         let addresses = [pubkey1];
 
-        assert!(selector.is_transaction_selected(false, Box::new(addresses.iter())));
+        assert!(selector.is_transaction_selected(false, false, Box::new(addresses.iter())));
 
         let addresses = [pubkey2];
-        assert!(selector.is_transaction_selected(false, Box::new(addresses.iter())));
+        assert!(selector.is_transaction_selected(false, false, Box::new(addresses.iter())));
 
         let addresses = [pubkey1, pubkey2];
-        assert!(selector.is_transaction_selected(false, Box::new(addresses.iter())));
+        assert!(selector.is_transaction_selected(false, false, Box::new(addresses.iter())));
     }
 
     #[test]
@@ -137,19 +194,19 @@ pub(crate) mod tests {
         let pubkey1 = Pubkey::new_unique();
         let pubkey2 = Pubkey::new_unique();
 
-        let selector = TransactionSelector::new(&["all".to_string()]);
+        let selector = TransactionSelector::new(&["all".to_string()], &[]);
 
         assert!(selector.is_enabled());
 
         let addresses = [pubkey1];
 
-        assert!(selector.is_transaction_selected(false, Box::new(addresses.iter())));
+        assert!(selector.is_transaction_selected(false, false, Box::new(addresses.iter())));
 
         let addresses = [pubkey2];
-        assert!(selector.is_transaction_selected(false, Box::new(addresses.iter())));
+        assert!(selector.is_transaction_selected(false, false, Box::new(addresses.iter())));
 
         let addresses = [pubkey1, pubkey2];
-        assert!(selector.is_transaction_selected(false, Box::new(addresses.iter())));
+        assert!(selector.is_transaction_selected(false, false, Box::new(addresses.iter())));
     }
 
     #[test]
@@ -157,19 +214,19 @@ pub(crate) mod tests {
         let pubkey1 = Pubkey::new_unique();
         let pubkey2 = Pubkey::new_unique();
 
-        let selector = TransactionSelector::new(&["all_votes".to_string()]);
+        let selector = TransactionSelector::new(&["all_votes".to_string()], &[]);
 
         assert!(selector.is_enabled());
 
         let addresses = [pubkey1];
 
-        assert!(!selector.is_transaction_selected(false, Box::new(addresses.iter())));
+        assert!(!selector.is_transaction_selected(false, false, Box::new(addresses.iter())));
 
         let addresses = [pubkey2];
-        assert!(selector.is_transaction_selected(true, Box::new(addresses.iter())));
+        assert!(selector.is_transaction_selected(true, false, Box::new(addresses.iter())));
 
         let addresses = [pubkey1, pubkey2];
-        assert!(selector.is_transaction_selected(true, Box::new(addresses.iter())));
+        assert!(selector.is_transaction_selected(true, false, Box::new(addresses.iter())));
     }
 
     #[test]
@@ -177,18 +234,88 @@ pub(crate) mod tests {
         let pubkey1 = Pubkey::new_unique();
         let pubkey2 = Pubkey::new_unique();
 
-        let selector = TransactionSelector::new(&[]);
+        let selector = TransactionSelector::new(&[], &[]);
 
         assert!(!selector.is_enabled());
 
         let addresses = [pubkey1];
 
-        assert!(!selector.is_transaction_selected(false, Box::new(addresses.iter())));
+        assert!(!selector.is_transaction_selected(false, false, Box::new(addresses.iter())));
 
         let addresses = [pubkey2];
-        assert!(!selector.is_transaction_selected(true, Box::new(addresses.iter())));
+        assert!(!selector.is_transaction_selected(true, false, Box::new(addresses.iter())));
 
         let addresses = [pubkey1, pubkey2];
-        assert!(!selector.is_transaction_selected(true, Box::new(addresses.iter())));
+        assert!(!selector.is_transaction_selected(true, false, Box::new(addresses.iter())));
+    }
+
+    #[test]
+    fn test_select_filtered_transaction() {
+        let pubkey = Pubkey::new_unique();
+        let addresses = [pubkey];
+        let is_selected = |selector: &TransactionSelector, is_vote, is_failed| {
+            selector.is_transaction_selected(is_vote, is_failed, Box::new(addresses.iter()))
+        };
+
+        let selector =
+            TransactionSelector::new(&["*".to_string()], &[]).with_filters(true, false, false);
+        assert!(is_selected(&selector, false, false));
+        assert!(is_selected(&selector, false, true));
+        assert!(!is_selected(&selector, true, false));
+
+        let selector =
+            TransactionSelector::new(&["*".to_string()], &[]).with_filters(true, false, true);
+        assert!(is_selected(&selector, false, false));
+        assert!(!is_selected(&selector, false, true));
+        assert!(!is_selected(&selector, true, false));
+
+        let selector =
+            TransactionSelector::new(&["*".to_string()], &[]).with_filters(false, true, true);
+        assert!(is_selected(&selector, true, false));
+        assert!(!is_selected(&selector, true, true));
+        assert!(!is_selected(&selector, false, false));
+
+        // The filters apply to the transactions selected by the mentions
+        let selector =
+            TransactionSelector::new(&[pubkey.to_string()], &[]).with_filters(true, false, true);
+        assert!(is_selected(&selector, false, false));
+        assert!(!is_selected(&selector, true, false));
+        let other = [Pubkey::new_unique()];
+        assert!(!selector.is_transaction_selected(false, false, Box::new(other.iter())));
+        assert!(!TransactionSelector::new(&[], &[])
+            .with_filters(false, true, false)
+            .is_enabled());
+    }
+
+    #[test]
+    fn test_select_all_mentioned_transaction() {
+        let market = Pubkey::new_unique();
+        let wallet = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+
+        let selector = TransactionSelector::new(
+            &[other.to_string()],
+            &[market.to_string(), wallet.to_string()],
+        );
+
+        assert!(selector.is_enabled());
+
+        let addresses = [market];
+        assert!(!selector.is_transaction_selected(false, false, Box::new(addresses.iter())));
+
+        let addresses = [market, market];
+        assert!(!selector.is_transaction_selected(false, false, Box::new(addresses.iter())));
+
+        let addresses = [wallet, Pubkey::new_unique(), market];
+        assert!(selector.is_transaction_selected(false, false, Box::new(addresses.iter())));
+
+        // The any-of mentions still select on their own
+        let addresses = [other];
+        assert!(selector.is_transaction_selected(false, false, Box::new(addresses.iter())));
+
+        let selector = TransactionSelector::new(&[], &[market.to_string()]);
+        assert!(selector.is_enabled());
+        let addresses = [market];
+        assert!(selector.is_transaction_selected(false, false, Box::new(addresses.iter())));
     }
 }
@@ -1,4 +1,4 @@
-#![allow(clippy::integer_arithmetic)]
+#![allow(clippy::arithmetic_side_effects)]
 
 /// Integration testing for the PostgreSQL plugin
 /// This requires a PostgreSQL database named 'solana' be setup at localhost at port 5432
@@ -12,7 +12,8 @@
 /// sudo -u postgres psql --command "CREATE USER solana WITH SUPERUSER PASSWORD 'solana';"
 /// sudo -u postgres createdb -O solana solana
 /// PGPASSWORD=solana psql -U solana -p 5432 -h localhost -w -d solana -f scripts/create_schema.sql
-/// Before run "cargo test", do a build by "cargo build" otherwise it may use stale build of the dynamic library.
+/// Run it with "cargo test --features local-cluster-tests", after a build by "cargo build" otherwise it may use
+/// stale build of the dynamic library.
 /// The test will cover transmitting accounts, transaction and slot and
 /// block metadata.
 use {